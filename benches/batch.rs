@@ -0,0 +1,92 @@
+use bitcoin_fee_model::{BatchInput, FeeModel, ModelData, PredictScratch};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rayon::prelude::*;
+
+const BUCKETS: [u64; 16] = [
+    13u64, 1, 32, 24, 14, 62, 1174, 453, 197, 291, 333, 3304, 307, 229, 36, 58,
+];
+const ROWS: usize = 10_000;
+
+fn bench_estimate_batch(c: &mut Criterion) {
+    let model = FeeModel::new().expect("embedded models");
+    let ts = 1613708045i64;
+    let inputs: Vec<BatchInput> = (0..ROWS)
+        .map(|i| BatchInput {
+            block_target: [1u16, 2, 6, 144][i % 4],
+            timestamp: ts + i as i64 * 600,
+            fee_buckets: &BUCKETS,
+            last_block_ts: ts + i as i64 * 600 - 300,
+        })
+        .collect();
+
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut group = c.benchmark_group("estimate_batch");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            inputs
+                .iter()
+                .map(|i| {
+                    model.estimate_with_buckets(
+                        i.block_target,
+                        Some(i.timestamp),
+                        i.fee_buckets,
+                        i.last_block_ts,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+    let mut threads = 1;
+    while threads <= cores {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .expect("thread pool");
+        group.bench_with_input(BenchmarkId::new("threads", threads), &inputs, |b, i| {
+            b.iter(|| pool.install(|| model.estimate_batch(black_box(i))))
+        });
+        threads *= 2;
+    }
+    group.finish();
+}
+
+/// Predictions with the scratch of every thread of rayon against the default of
+/// `norm_predict_slice`
+fn bench_predict_scratch(c: &mut Criterion) {
+    let path = "models/20211027-180925/model.cbor";
+    let model = ModelData::from_reader(std::fs::File::open(path).expect("model")).expect("model");
+    let inputs: Vec<Vec<f32>> = (0..ROWS)
+        .map(|i| {
+            (0..model.fields.len())
+                .map(|j| ((i + j) % 10) as f32)
+                .collect()
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("predict_scratch");
+    group.throughput(Throughput::Elements(ROWS as u64));
+    group.bench_function("default", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .par_iter()
+                .map(|i| model.norm_predict_slice(i))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("scratch", |b| {
+        b.iter(|| {
+            black_box(&inputs)
+                .par_iter()
+                .map_init(
+                    || PredictScratch::for_model(&model),
+                    |scratch, i| model.norm_predict_with(i, scratch),
+                )
+                .collect::<Vec<_>>()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_estimate_batch, bench_predict_scratch);
+criterion_main!(benches);
@@ -0,0 +1,79 @@
+use bitcoin_fee_model::{FeeModel, ModelData, ModelInput, MAX_BUCKETS};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const BUCKETS: [u64; 16] = [
+    13u64, 1, 32, 24, 14, 62, 1174, 453, 197, 291, 333, 3304, 307, 229, 36, 58,
+];
+
+fn model_input() -> ModelInput {
+    let mut buckets = [0.0f32; MAX_BUCKETS];
+    for (bucket, value) in buckets.iter_mut().zip(BUCKETS.iter()) {
+        *bucket = *value as f32;
+    }
+    ModelInput {
+        confirms_in: 6.0,
+        day_of_week: 4.0,
+        hour: 4.0,
+        delta_last: 300.0,
+        buckets,
+    }
+}
+
+fn bench_norm_predict(c: &mut Criterion) {
+    let model = ModelData::from_slice(include_bytes!("../models/20211027-180925/model.cbor"))
+        .expect("valid model");
+    let input = model_input();
+    let map = input.to_map();
+
+    c.bench_function("norm_predict map", |b| {
+        b.iter(|| model.norm_predict(black_box(&map)))
+    });
+    c.bench_function("norm_predict_input", |b| {
+        b.iter(|| model.norm_predict_input(black_box(&input)))
+    });
+    let quantized = model.quantize_int8();
+    c.bench_function("norm_predict_input int8", |b| {
+        b.iter(|| quantized.norm_predict_input(black_box(&input)))
+    });
+}
+
+fn bench_estimate(c: &mut Criterion) {
+    let model = FeeModel::new().expect("embedded models");
+    let ts = 1613708045i64;
+    let rates: Vec<f64> = (0..5000).map(|i| 1.0 + (i % 300) as f64).collect();
+
+    c.bench_function("estimate_with_buckets", |b| {
+        b.iter(|| model.estimate_with_buckets(6, black_box(Some(ts)), &BUCKETS, ts - 300))
+    });
+    c.bench_function("estimate", |b| {
+        b.iter(|| model.estimate(6, black_box(Some(ts)), &rates, ts - 300))
+    });
+}
+
+/// A cold start creating the model from the embedded models for every estimate, against the
+/// estimates with the model shared by the process
+fn bench_static_estimate(c: &mut Criterion) {
+    let ts = 1613708045i64;
+    let rates: Vec<f64> = (0..5000).map(|i| 1.0 + (i % 300) as f64).collect();
+
+    let mut group = c.benchmark_group("static_estimate");
+    group.bench_function("cold", |b| {
+        b.iter(|| {
+            let model = FeeModel::new().expect("embedded models");
+            model.estimate(6, black_box(Some(ts)), &rates, ts - 300)
+        })
+    });
+    bitcoin_fee_model::init();
+    group.bench_function("warm", |b| {
+        b.iter(|| bitcoin_fee_model::static_estimate(6, black_box(Some(ts)), &rates, ts - 300))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_norm_predict,
+    bench_estimate,
+    bench_static_estimate
+);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+use bitcoin_fee_model::ModelData;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const CBOR: &[u8] = include_bytes!("../models/20211027-180925/model.cbor");
+
+fn bench_load(c: &mut Criterion) {
+    let postcard = ModelData::from_slice(CBOR)
+        .and_then(|m| m.to_postcard())
+        .expect("valid model");
+
+    let mut group = c.benchmark_group("load");
+    group.bench_function("cbor", |b| {
+        b.iter(|| ModelData::from_slice(black_box(CBOR)))
+    });
+    group.bench_function("postcard", |b| {
+        b.iter(|| ModelData::from_postcard(black_box(&postcard)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_load);
+criterion_main!(benches);
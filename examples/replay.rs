@@ -0,0 +1,68 @@
+//! Replay the windows bundled in `models/replay.csv.zz` with the embedded models and print the
+//! errors of the estimates of every target, optionally writing every estimate to a CSV.
+//!
+//! The windows are synthetic, not historical data: `models/make_replay.py` generates them from a
+//! simulated congestion, so the errors only show how the estimates are evaluated, not how
+//! accurate the models are. Needs the `backtest` feature and the default `embedded-models` and
+//! `std` features:
+//!
+//! cargo run --example replay --features backtest -- predictions.csv
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use bitcoin_fee_model::backtest::run_backtest_with;
+use bitcoin_fee_model::dataset::read_replay_csv;
+use bitcoin_fee_model::FeeModel;
+
+const TARGETS: [u16; 4] = [1, 3, 6, 24];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let compressed = include_bytes!("../models/replay.csv.zz");
+    let csv = miniz_oxide::inflate::decompress_to_vec_zlib(compressed)
+        .map_err(|e| format!("cannot decompress the replay sample: {:?}", e))?;
+    let windows = read_replay_csv(&csv[..], &TARGETS)?;
+    let rows = windows.iter().flat_map(|window| window.backtest_rows());
+    let model = FeeModel::new()?;
+
+    let mut out = match std::env::args().nth(1) {
+        Some(path) => {
+            let mut out = BufWriter::new(File::create(path)?);
+            writeln!(out, "timestamp,target,predicted,actual")?;
+            Some(out)
+        }
+        None => None,
+    };
+    let mut written = Ok(());
+    let report = run_backtest_with(&model, rows, |row, predicted| {
+        if let (Some(out), Ok(())) = (out.as_mut(), &written) {
+            let predicted = predicted.map_or(String::new(), |p| p.to_string());
+            written = writeln!(
+                out,
+                "{},{},{},{}",
+                row.timestamp, row.target, predicted, row.required_fee_rate
+            );
+        }
+    });
+    written?;
+    if let Some(mut out) = out {
+        out.flush()?;
+    }
+
+    println!("target  count  failed     mae  median  overpay  under");
+    let overall = ("all".to_string(), &report.overall);
+    let per_target = report.per_target.iter().map(|(t, m)| (t.to_string(), m));
+    for (target, m) in per_target.chain(std::iter::once(overall)) {
+        println!(
+            "{:>6} {:>6} {:>7} {:>7.2} {:>7.2} {:>8.3} {:>6.3}",
+            target,
+            m.count,
+            m.failed,
+            m.mae,
+            m.median_absolute_error,
+            m.overpay_ratio,
+            m.underestimate_fraction
+        );
+    }
+    Ok(())
+}
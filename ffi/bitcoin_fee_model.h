@@ -0,0 +1,117 @@
+#ifndef BITCOIN_FEE_MODEL_H
+#define BITCOIN_FEE_MODEL_H
+
+/* Generated with `cbindgen --config cbindgen.toml --output ffi/bitcoin_fee_model.h`, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define FEE_MODEL_OK 0
+
+/**
+ * A required pointer argument is null
+ */
+#define FEE_MODEL_ERR_NULL_POINTER -1
+
+/**
+ * The model data is invalid
+ */
+#define FEE_MODEL_ERR_INVALID_MODEL -2
+
+/**
+ * The fee buckets don't match the ones expected by the models
+ */
+#define FEE_MODEL_ERR_WRONG_BUCKET_COUNT -3
+
+/**
+ * There are no fee rates to estimate from
+ */
+#define FEE_MODEL_ERR_INSUFFICIENT_DATA -4
+
+/**
+ * A timestamp is out of the supported range
+ */
+#define FEE_MODEL_ERR_INVALID_TIMESTAMP -5
+
+/**
+ * Any other error
+ */
+#define FEE_MODEL_ERR_OTHER -6
+
+/**
+ * A fee rate is NaN or infinite
+ */
+#define FEE_MODEL_ERR_INVALID_INPUT -7
+
+/**
+ * The models of the low and the high targets, or of the tiers of [`FeeModel::from_tiers`], with
+ * the configuration of the estimates. It's `Send` and `Sync` and estimates take `&self`, so a
+ * single instance can estimate from many threads.
+ */
+typedef struct FeeModel FeeModel;
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Create the fee model using the models embedded in the library, to be released with
+ * [`fee_model_free`]. Returns null on error.
+ */
+struct FeeModel *fee_model_new(void);
+
+/**
+ * Create the fee model from the CBOR encoded `low` and `high` models, to be released with
+ * [`fee_model_free`]. Returns null on error.
+ *
+ * # Safety
+ *
+ * `low` and `high` must point to `low_len` and `high_len` readable bytes
+ */
+struct FeeModel *fee_model_from_bytes(const uint8_t *low,
+                                      uintptr_t low_len,
+                                      const uint8_t *high,
+                                      uintptr_t high_len);
+
+/**
+ * Estimate the fee rate in sat/vB for `block_target` like `FeeModel::estimate`, writing it in
+ * `out_rate`. `timestamp` and `last_block_ts` are unix times in seconds.
+ *
+ * # Safety
+ *
+ * `model` must be a pointer returned by this library and not yet freed, `fee_rates` must point
+ * to `fee_rates_len` values, or may be null if `fee_rates_len` is zero, `out_rate` must be
+ * writable
+ */
+int fee_model_estimate(const struct FeeModel *model,
+                       uint16_t block_target,
+                       int64_t timestamp,
+                       const double *fee_rates,
+                       uintptr_t fee_rates_len,
+                       int64_t last_block_ts,
+                       float *out_rate);
+
+/**
+ * Release a model created by this library, null is ignored
+ *
+ * # Safety
+ *
+ * `model` must be a pointer returned by this library and not yet freed
+ */
+void fee_model_free(struct FeeModel *model);
+
+/**
+ * The description of the last error on the calling thread, null if no error happened. The
+ * string is valid until the next call failing on the same thread and must not be freed.
+ */
+const char *fee_model_last_error_message(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BITCOIN_FEE_MODEL_H */
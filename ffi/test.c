@@ -0,0 +1,39 @@
+/*
+ * Exercise the C bindings, build the library with `cargo build --features ffi` then:
+ * cc ffi/test.c -Iffi -Ltarget/debug -lbitcoin_fee_model -o target/ffi_test
+ * LD_LIBRARY_PATH=target/debug target/ffi_test
+ */
+#include <assert.h>
+#include <stdio.h>
+
+#include "bitcoin_fee_model.h"
+
+int main(void) {
+    FeeModel *model = fee_model_new();
+    assert(model != NULL);
+    assert(fee_model_last_error_message() == NULL);
+
+    double fee_rates[1000];
+    for (int i = 0; i < 1000; i++) {
+        fee_rates[i] = 1.0 + (i % 300);
+    }
+    int64_t ts = 1613708045;
+    float rate = 0.0f;
+    int code = fee_model_estimate(model, 6, ts, fee_rates, 1000, ts - 300, &rate);
+    assert(code == FEE_MODEL_OK);
+    assert(rate >= 1.0f);
+    printf("6 blocks: %f sat/vB\n", rate);
+
+    code = fee_model_estimate(model, 6, ts, NULL, 0, ts - 300, &rate);
+    assert(code == FEE_MODEL_ERR_INSUFFICIENT_DATA);
+    printf("expected error: %s\n", fee_model_last_error_message());
+
+    code = fee_model_estimate(NULL, 6, ts, fee_rates, 1000, ts - 300, &rate);
+    assert(code == FEE_MODEL_ERR_NULL_POINTER);
+
+    uint8_t garbage[4] = {0xff, 0xff, 0xff, 0xff};
+    assert(fee_model_from_bytes(garbage, 4, garbage, 4) == NULL);
+
+    fee_model_free(model);
+    return 0;
+}
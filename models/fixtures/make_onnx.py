@@ -0,0 +1,179 @@
+"""Write the ONNX fixture `onnx.onnx` loaded by `ModelData::from_onnx` and print its expected
+predictions, with the protobuf messages of ONNX encoded by hand so that it needs only Python 3.
+
+The graph standardizes the 4 columns of its input with Sub and Div, then computes three dense
+layers: a Gemm with transB=1 followed by Relu, a MatMul and an Add followed by Sigmoid and a Gemm
+without activation. The columns are named in the `feature_names` metadata.
+
+    python3 models/fixtures/make_onnx.py
+"""
+
+import math
+import os
+import struct
+
+FEATURE_NAMES = ["target", "since_last", "hour", "dow"]
+MEAN = [6.0, 600.0, 12.0, 3.0]
+STD = [4.0, 300.0, 7.0, 2.0]
+# units x inputs, transposed by transB
+W0 = [
+    [0.5, -0.25, 0.125, 0.75],
+    [-0.5, 0.375, 0.25, -0.125],
+    [0.25, 0.5, -0.75, 0.5],
+]
+B0 = [0.1, -0.2, 0.3]
+# inputs x units
+W1 = [
+    [0.6, -0.4],
+    [-0.3, 0.8],
+    [0.2, 0.5],
+]
+B1 = [0.05, -0.1]
+W2 = [[12.0], [7.5]]
+B2 = [4.0]
+
+INPUTS = [
+    [1.0, 60.0, 4.0, 0.0],
+    [6.0, 600.0, 12.0, 3.0],
+    [144.0, 1800.0, 23.0, 6.0],
+]
+
+FLOAT = 1
+ATTRIBUTE_FLOAT = 1
+ATTRIBUTE_INT = 2
+
+
+def varint(value):
+    out = b""
+    while True:
+        byte = value & 0x7F
+        value >>= 7
+        if value:
+            out += bytes([byte | 0x80])
+        else:
+            return out + bytes([byte])
+
+
+def key(field, wire):
+    return varint(field << 3 | wire)
+
+
+def uint(field, value):
+    return key(field, 0) + varint(value)
+
+
+def length_delimited(field, payload):
+    if isinstance(payload, str):
+        payload = payload.encode()
+    return key(field, 2) + varint(len(payload)) + payload
+
+
+def tensor(name, dims, values):
+    packed_dims = b"".join(varint(d) for d in dims)
+    raw = b"".join(struct.pack("<f", v) for v in values)
+    return (
+        length_delimited(1, packed_dims)
+        + uint(2, FLOAT)
+        + length_delimited(8, name)
+        + length_delimited(9, raw)
+    )
+
+
+def attribute_int(name, value):
+    return length_delimited(1, name) + uint(3, value) + uint(20, ATTRIBUTE_INT)
+
+
+def attribute_float(name, value):
+    return (
+        length_delimited(1, name)
+        + key(2, 5)
+        + struct.pack("<f", value)
+        + uint(20, ATTRIBUTE_FLOAT)
+    )
+
+
+def node(op_type, inputs, output, attributes=()):
+    message = b"".join(length_delimited(1, i) for i in inputs)
+    message += length_delimited(2, output) + length_delimited(3, output)
+    message += length_delimited(4, op_type)
+    message += b"".join(length_delimited(5, a) for a in attributes)
+    return message
+
+
+def value_info(name, width):
+    dims = length_delimited(1, length_delimited(2, "batch"))
+    dims += length_delimited(1, uint(1, width))
+    tensor_type = uint(1, FLOAT) + length_delimited(2, dims)
+    return length_delimited(1, name) + length_delimited(2, length_delimited(1, tensor_type))
+
+
+def flatten(rows):
+    return [v for row in rows for v in row]
+
+
+def graph():
+    nodes = [
+        node("Sub", ["input", "mean"], "centered"),
+        node("Div", ["centered", "std"], "normalized"),
+        node(
+            "Gemm",
+            ["normalized", "w0", "b0"],
+            "dense",
+            [attribute_int("transB", 1), attribute_float("alpha", 1.0)],
+        ),
+        node("Relu", ["dense"], "relu"),
+        node("MatMul", ["relu", "w1"], "dense_1"),
+        node("Add", ["dense_1", "b1"], "biased_1"),
+        node("Sigmoid", ["biased_1"], "sigmoid"),
+        node("Gemm", ["sigmoid", "w2", "b2"], "output"),
+    ]
+    initializers = [
+        tensor("mean", [4], MEAN),
+        tensor("std", [4], STD),
+        tensor("w0", [3, 4], flatten(W0)),
+        tensor("b0", [3], B0),
+        tensor("w1", [3, 2], flatten(W1)),
+        tensor("b1", [2], B1),
+        tensor("w2", [2, 1], flatten(W2)),
+        tensor("b2", [1], B2),
+    ]
+    message = b"".join(length_delimited(1, n) for n in nodes)
+    message += length_delimited(2, "onnx-example")
+    message += b"".join(length_delimited(5, t) for t in initializers)
+    message += length_delimited(11, value_info("input", 4))
+    message += length_delimited(12, value_info("output", 1))
+    return message
+
+
+def model():
+    opset = length_delimited(1, "") + uint(2, 13)
+    props = length_delimited(1, "feature_names") + length_delimited(2, ",".join(FEATURE_NAMES))
+    return (
+        uint(1, 8)
+        + length_delimited(2, "make_onnx.py")
+        + length_delimited(7, graph())
+        + length_delimited(8, opset)
+        + length_delimited(14, props)
+    )
+
+
+def f32(value):
+    return struct.unpack("<f", struct.pack("<f", value))[0]
+
+
+def predict(values):
+    x = [(v - m) / s for v, m, s in zip(values, MEAN, STD)]
+    x = [max(0.0, sum(xi * f32(w) for xi, w in zip(x, row)) + f32(b)) for row, b in zip(W0, B0)]
+    x = [
+        1.0 / (1.0 + math.exp(-(sum(xi * f32(W1[i][j]) for i, xi in enumerate(x)) + f32(b))))
+        for j, b in enumerate(B1)
+    ]
+    return sum(xi * f32(W2[i][0]) for i, xi in enumerate(x)) + f32(B2[0])
+
+
+if __name__ == "__main__":
+    path = os.path.join(os.path.dirname(os.path.abspath(__file__)), "onnx.onnx")
+    with open(path, "wb") as f:
+        f.write(model())
+    for values in INPUTS:
+        print(values, predict(values))
@@ -0,0 +1,49 @@
+"""Generate `replay.csv.zz`, the sample of the `replay` example.
+
+The windows are synthetic: the fee rates of the last blocks follow a congestion level drifting
+over a couple of days, and the required fee rate of every target is a quantile of the window
+perturbed by noise. The file has the format read by `dataset::read_replay_csv`, an export of
+historical windows in the same format replaces it.
+
+    python3 models/make_replay.py
+"""
+
+import math
+import random
+import zlib
+
+TARGETS = [1, 3, 6, 24]
+WINDOWS = 300
+RATES = 200
+START = 1613708045
+
+
+def quantile(values, q):
+    values = sorted(values)
+    return values[min(len(values) - 1, int(q * len(values)))]
+
+
+def main():
+    rng = random.Random(21)
+    congestion = 0.3
+    lines = ["timestamp,last_block_ts,fee_rates," + ",".join(f"required_{t}" for t in TARGETS)]
+    for i in range(WINDOWS):
+        timestamp = START + i * 600 + rng.randrange(600)
+        last_block_ts = timestamp - min(int(rng.expovariate(1 / 600)), 7200)
+        congestion = min(1.0, max(0.0, congestion + rng.gauss(0, 0.05)))
+        median = math.exp(math.log(2) + congestion * math.log(40))
+        rates = [max(1.0, round(median * math.exp(rng.gauss(0, 0.9)), 1)) for _ in range(RATES)]
+        required = []
+        for target in TARGETS:
+            q = 0.5 + 0.45 / math.sqrt(target)
+            noise = math.exp(rng.gauss(0, 0.25))
+            required.append(round(max(1.0, quantile(rates, q) * noise), 2))
+        fee_rates = " ".join(f"{r:g}" for r in rates)
+        lines.append(f"{timestamp},{last_block_ts},{fee_rates}," + ",".join(map(str, required)))
+    data = ("\n".join(lines) + "\n").encode()
+    with open("models/replay.csv.zz", "wb") as f:
+        f.write(zlib.compress(data, 9))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,39 @@
+"""Tests of the python bindings, run with `maturin develop && pytest python`"""
+
+import time
+
+import pytest
+
+from bitcoin_fee_model import FeeModel
+
+TS = 1613708045
+RATES = [1.0 + (i % 300) for i in range(1000)]
+
+
+def test_estimate():
+    model = FeeModel()
+    estimate = model.estimate(6, TS, fee_rates=RATES, last_block_ts=TS - 300)
+    assert estimate >= 1.0
+    now = int(time.time())
+    assert model.estimate(6, fee_rates=RATES, last_block_ts=now - 300) >= 1.0
+    # any sequence of floats
+    assert model.estimate(6, TS, fee_rates=tuple(RATES), last_block_ts=TS - 300) == estimate
+
+
+def test_from_files():
+    low = "models/20211027-180849/model.cbor"
+    high = "models/20211027-180925/model.cbor"
+    model = FeeModel.from_files(low, high)
+    expected = FeeModel().estimate(6, TS, fee_rates=RATES, last_block_ts=TS - 300)
+    assert model.estimate(6, TS, fee_rates=RATES, last_block_ts=TS - 300) == expected
+
+    with pytest.raises(ValueError, match="missing.cbor"):
+        FeeModel.from_files("models/fixtures/missing.cbor", high)
+
+
+def test_errors():
+    model = FeeModel()
+    with pytest.raises(ValueError, match="No fee rates"):
+        model.estimate(6, TS, fee_rates=[], last_block_ts=TS - 300)
+    with pytest.raises(TypeError):
+        model.estimate(6, TS, fee_rates="1.0", last_block_ts=TS - 300)
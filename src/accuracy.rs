@@ -0,0 +1,164 @@
+//! Running accuracy of the estimates against the fee rates that were required to confirm, see
+//! [`AccuracyTracker`]
+
+use alloc::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Accuracy of the estimates of a target, see [`AccuracyTracker::metrics`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct AccuracyMetrics {
+    /// estimates recorded, for the recent metrics their decayed weight
+    pub count: f64,
+    /// mean of `|prediction - required|`
+    pub mae: f64,
+    /// mean of `prediction - required`, negative if the estimates are mostly too low
+    pub bias: f64,
+    /// fraction of the estimates lower than the required fee rate, which would have not confirmed
+    /// within the target
+    pub underestimate_fraction: f64,
+}
+
+/// Weighted sums of the errors of a target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct Sums {
+    weight: f64,
+    absolute_error: f64,
+    error: f64,
+    underestimates: f64,
+}
+
+impl Sums {
+    fn add(&mut self, error: f64) {
+        self.weight += 1.0;
+        self.absolute_error += error.abs();
+        self.error += error;
+        if error < 0.0 {
+            self.underestimates += 1.0;
+        }
+    }
+
+    fn decay(&mut self, factor: f64) {
+        self.weight *= factor;
+        self.absolute_error *= factor;
+        self.error *= factor;
+        self.underestimates *= factor;
+    }
+
+    fn metrics(&self) -> AccuracyMetrics {
+        AccuracyMetrics {
+            count: self.weight,
+            mae: self.absolute_error / self.weight,
+            bias: self.error / self.weight,
+            underestimate_fraction: self.underestimates / self.weight,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+struct TargetSums {
+    all: Sums,
+    recent: Sums,
+}
+
+/// The accuracy of the estimates of every target, updated with the fee rate that would have
+/// sufficed to confirm within the target once known, for example by a daemon comparing its
+/// estimates with the following blocks, serializable to persist it across restarts.
+///
+/// Besides the metrics of every estimate recorded, the recent ones weight the estimates
+/// exponentially less with every following estimate of the same target, halving the weight
+/// after `half_life` of them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccuracyTracker {
+    half_life: u32,
+    targets: BTreeMap<u16, TargetSums>,
+}
+
+impl AccuracyTracker {
+    /// A tracker without estimates whose recent metrics halve the weight of an estimate after
+    /// `half_life` estimates of its target, at least one
+    pub fn new(half_life: u32) -> AccuracyTracker {
+        AccuracyTracker {
+            half_life: half_life.max(1),
+            targets: BTreeMap::new(),
+        }
+    }
+
+    /// Record the estimate `prediction` for `target` and the fee rate `required` to confirm
+    /// within it, both in sat/vB
+    pub fn record(&mut self, prediction: f32, required: f32, target: u16) {
+        let factor = libm::pow(0.5, 1.0 / self.half_life as f64);
+        let sums = self.targets.entry(target).or_default();
+        let error = prediction as f64 - required as f64;
+        sums.all.add(error);
+        sums.recent.decay(factor);
+        sums.recent.add(error);
+    }
+
+    /// The metrics of every estimate recorded for `target`, `None` without estimates
+    pub fn metrics(&self, target: u16) -> Option<AccuracyMetrics> {
+        self.targets.get(&target).map(|sums| sums.all.metrics())
+    }
+
+    /// Like [`AccuracyTracker::metrics`] with the recent estimates weighting more, see
+    /// [`AccuracyTracker::new`]
+    pub fn recent_metrics(&self, target: u16) -> Option<AccuracyMetrics> {
+        self.targets.get(&target).map(|sums| sums.recent.metrics())
+    }
+
+    /// The targets with estimates recorded, increasing
+    pub fn targets(&self) -> impl Iterator<Item = u16> + '_ {
+        self.targets.keys().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::AccuracyTracker;
+
+    #[test]
+    fn test_accuracy_tracker() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let mut tracker = AccuracyTracker::new(1);
+        tracker.record(10.0, 8.0, 1);
+        tracker.record(5.0, 7.0, 1);
+        tracker.record(20.0, 30.0, 6);
+        tracker.record(9.0, 9.0, 1);
+        assert_eq!(tracker.targets().collect::<Vec<_>>(), vec![1, 6]);
+        assert!(tracker.metrics(2).is_none());
+
+        // errors 2, -2 and 0
+        let all = tracker.metrics(1).unwrap();
+        assert!(close(all.count, 3.0));
+        assert!(close(all.mae, 4.0 / 3.0));
+        assert!(close(all.bias, 0.0));
+        assert!(close(all.underestimate_fraction, 1.0 / 3.0));
+        // weighted 0.25, 0.5 and 1
+        let recent = tracker.recent_metrics(1).unwrap();
+        assert!(close(recent.count, 1.75));
+        assert!(close(recent.mae, 1.5 / 1.75));
+        assert!(close(recent.bias, -0.5 / 1.75));
+        assert!(close(recent.underestimate_fraction, 0.5 / 1.75));
+
+        let six = tracker.metrics(6).unwrap();
+        assert_eq!(six, tracker.recent_metrics(6).unwrap());
+        assert!(close(six.bias, -10.0));
+        assert!(close(six.underestimate_fraction, 1.0));
+
+        let mut slow = AccuracyTracker::new(2);
+        for _ in 0..2 {
+            slow.record(10.0, 8.0, 1);
+        }
+        assert!(close(
+            slow.recent_metrics(1).unwrap().count,
+            1.0 + 0.5f64.sqrt()
+        ));
+        assert!(close(slow.recent_metrics(1).unwrap().mae, 2.0));
+
+        let json = serde_json::to_string(&tracker).unwrap();
+        let restored: AccuracyTracker = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, tracker);
+        tracker.record(9.0, 9.0, 1);
+        assert!(close(tracker.metrics(1).unwrap().mae, 1.0));
+    }
+}
@@ -0,0 +1,206 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+
+use crate::Error;
+
+/// Activation function applied to the outputs of a dense layer, serialized by name in the
+/// `activations` of the model
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Activation {
+    /// `max(0, x)`, named `relu`
+    Relu,
+    /// `x` if positive, `x * alpha` otherwise, named like `leaky_relu(0.01)`
+    LeakyRelu(f32),
+    /// named `tanh`
+    Tanh,
+    /// `1 / (1 + e^-x)`, named `sigmoid`
+    Sigmoid,
+    /// named `identity`
+    Identity,
+    /// `x * Φ(x)` with the exact normal distribution `Φ(x) = erfc(-x / √2) / 2`, not the tanh
+    /// approximation, named `gelu`
+    Gelu,
+}
+
+impl Activation {
+    /// Parse the name of an activation, errors with [`Error::UnsupportedActivation`] if unknown
+    /// or if the slope of the leaky relu is not finite
+    pub fn from_name(name: &str) -> Result<Activation, Error> {
+        let activation = match name {
+            "relu" => Activation::Relu,
+            "tanh" => Activation::Tanh,
+            "sigmoid" => Activation::Sigmoid,
+            "identity" => Activation::Identity,
+            "gelu" => Activation::Gelu,
+            _ => name
+                .strip_prefix("leaky_relu(")
+                .and_then(|s| s.strip_suffix(')'))
+                .and_then(|alpha| alpha.trim().parse::<f32>().ok())
+                .filter(|alpha| alpha.is_finite())
+                .map(Activation::LeakyRelu)
+                .ok_or_else(|| Error::UnsupportedActivation(name.to_string()))?,
+        };
+        Ok(activation)
+    }
+
+    /// The name parsed by [`Activation::from_name`]
+    pub fn name(&self) -> String {
+        match self {
+            Activation::Relu => "relu".to_string(),
+            Activation::LeakyRelu(alpha) => format!("leaky_relu({})", alpha),
+            Activation::Tanh => "tanh".to_string(),
+            Activation::Sigmoid => "sigmoid".to_string(),
+            Activation::Identity => "identity".to_string(),
+            Activation::Gelu => "gelu".to_string(),
+        }
+    }
+
+    /// The transcendental functions are computed by the `libm` crate in Rust, not by the libm of
+    /// the platform, so that results are the same on every platform
+    pub fn apply(&self, x: f32) -> f32 {
+        match self {
+            Activation::Relu if x < 0.0 => 0.0,
+            Activation::LeakyRelu(alpha) if x < 0.0 => x * alpha,
+            Activation::Tanh => libm::tanhf(x),
+            Activation::Sigmoid => 1.0 / (1.0 + libm::expf(-x)),
+            Activation::Gelu => {
+                let cdf = 0.5 * libm::erfcf(-x * core::f32::consts::FRAC_1_SQRT_2);
+                x * cdf
+            }
+            _ => x,
+        }
+    }
+
+    /// Like [`Activation::apply`] in double precision
+    pub fn apply_f64(&self, x: f64) -> f64 {
+        match self {
+            Activation::Relu if x < 0.0 => 0.0,
+            Activation::LeakyRelu(alpha) if x < 0.0 => x * *alpha as f64,
+            Activation::Tanh => libm::tanh(x),
+            Activation::Sigmoid => 1.0 / (1.0 + libm::exp(-x)),
+            Activation::Gelu => {
+                let cdf = 0.5 * libm::erfc(-x * core::f64::consts::FRAC_1_SQRT_2);
+                x * cdf
+            }
+            _ => x,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Activation, Error};
+
+    const INPUTS: [f64; 6] = [-3.0, -1.0, -0.25, 0.0, 0.5, 2.0];
+
+    /// Computed with python `math` in double precision
+    const GOLDEN: [(&str, [f64; 6]); 6] = [
+        ("relu", [0.0, 0.0, 0.0, 0.0, 0.5, 2.0]),
+        ("leaky_relu(0.25)", [-0.75, -0.25, -0.0625, 0.0, 0.5, 2.0]),
+        (
+            "tanh",
+            [
+                -0.9950547536867305,
+                -0.7615941559557649,
+                -0.24491866240370913,
+                0.0,
+                0.46211715726000974,
+                0.9640275800758169,
+            ],
+        ),
+        (
+            "sigmoid",
+            [
+                0.04742587317756678,
+                0.2689414213699951,
+                0.43782349911420193,
+                0.5,
+                0.6224593312018546,
+                0.8807970779778823,
+            ],
+        ),
+        ("identity", INPUTS),
+        (
+            "gelu",
+            [
+                -0.00404969409489031,
+                -0.15865525393145707,
+                -0.10032341857926907,
+                0.0,
+                0.34573123063700656,
+                1.9544997361036416,
+            ],
+        ),
+    ];
+
+    /// Bits of [`Activation::apply`] of `INPUTS`, computed by the `libm` crate, a port of musl in
+    /// Rust which doesn't depend on the libm of the platform
+    const GOLDEN_BITS: [(&str, [u32; 6]); 3] = [
+        (
+            "tanh",
+            [
+                0xbf7ebbe9, 0xbf42f7d6, 0xbe7acbf5, 0x00000000, 0x3eec9a9e, 0x3f76ca83,
+            ],
+        ),
+        (
+            "sigmoid",
+            [
+                0x3d4241a2, 0x3e89b2b1, 0x3ee02a67, 0x3f000000, 0x3f1f597f, 0x3f617bea,
+            ],
+        ),
+        (
+            "gelu",
+            [
+                0xbb84b350, 0xbe227686, 0xbdcd765e, 0x00000000, 0x3eb103af, 0x3ffa2d0c,
+            ],
+        ),
+    ];
+
+    #[test]
+    fn test_activation_bits() {
+        for (name, expected) in GOLDEN_BITS.iter() {
+            let activation = Activation::from_name(name).unwrap();
+            for (x, expected) in INPUTS.iter().zip(expected.iter()) {
+                let got = activation.apply(*x as f32).to_bits();
+                assert_eq!(got, *expected, "{} {}", name, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_activations() {
+        for (name, expected) in GOLDEN.iter() {
+            let activation = Activation::from_name(name).unwrap();
+            assert_eq!(activation.name(), *name);
+            for (x, expected) in INPUTS.iter().zip(expected.iter()) {
+                let got = activation.apply_f64(*x);
+                assert!((got - expected).abs() <= 1e-15, "{} {} {}", name, x, got);
+                let got = activation.apply(*x as f32);
+                // a few ulps, the rounding of the argument of erfc is amplified in the tail
+                let tolerance = 8.0 * f32::EPSILON * (expected.abs() as f32).max(f32::MIN_POSITIVE);
+                assert!(
+                    (got - *expected as f32).abs() <= tolerance,
+                    "{} {}",
+                    name,
+                    got
+                );
+            }
+        }
+        assert_eq!(
+            Activation::from_name("leaky_relu( 0.01 )").unwrap(),
+            Activation::LeakyRelu(0.01)
+        );
+        for name in [
+            "swish",
+            "leaky_relu",
+            "leaky_relu(x)",
+            "leaky_relu(inf)",
+            "Relu",
+        ]
+        .iter()
+        {
+            let err = Activation::from_name(name).unwrap_err();
+            assert!(matches!(err, Error::UnsupportedActivation(n) if n == *name));
+        }
+    }
+}
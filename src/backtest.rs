@@ -0,0 +1,278 @@
+//! Evaluate the estimates of a [`FeeModel`] against the fee rates that were actually required to
+//! confirm, see [`run_backtest`]
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use crate::csv::CsvReader;
+use crate::{Combine, Error, FeeModel};
+
+/// A past estimate request with its outcome
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestRow {
+    /// unix time in seconds of the estimate
+    pub timestamp: i64,
+    /// unix time in seconds of the last block before `timestamp`
+    pub last_block_ts: i64,
+    /// fee rates in sat/vB of the transactions of the last blocks, as for [`FeeModel::estimate`]
+    pub fee_rates: Vec<f64>,
+    /// confirmation target in blocks
+    pub target: u16,
+    /// the lowest fee rate in sat/vB of the transactions confirmed within `target` blocks after
+    /// `timestamp`
+    pub required_fee_rate: f64,
+}
+
+/// Accuracy of the estimates of a set of [`BacktestRow`]
+///
+/// The values are NaN if no estimate succeeded
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetMetrics {
+    /// rows estimated
+    pub count: usize,
+    /// rows for which [`FeeModel::estimate`] returned an error, not included in the metrics
+    pub failed: usize,
+    /// mean of `|estimate - required_fee_rate|`
+    pub mae: f64,
+    /// median of `|estimate - required_fee_rate|`
+    pub median_absolute_error: f64,
+    /// fee rate paid above the required one, divided by the sum of the required fee rates
+    pub overpay_ratio: f64,
+    /// fraction of the estimates lower than the required fee rate, which would have not confirmed
+    /// within the target
+    pub underestimate_fraction: f64,
+}
+
+/// The result of [`run_backtest`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BacktestReport {
+    /// metrics of the rows of each target
+    pub per_target: BTreeMap<u16, TargetMetrics>,
+    /// metrics of all the rows
+    pub overall: TargetMetrics,
+}
+
+#[derive(Default)]
+struct Outcomes {
+    /// pairs of estimate and required fee rate
+    estimates: Vec<(f64, f64)>,
+    failed: usize,
+}
+
+impl Outcomes {
+    fn metrics(&self) -> TargetMetrics {
+        let errors: Vec<f64> = self
+            .estimates
+            .iter()
+            .map(|(estimate, required)| (estimate - required).abs())
+            .collect();
+        let count = self.estimates.len() as f64;
+        let overpay: f64 = self
+            .estimates
+            .iter()
+            .map(|(estimate, required)| (estimate - required).max(0.0))
+            .sum();
+        let required: f64 = self.estimates.iter().map(|(_, required)| required).sum();
+        let under = self.estimates.iter().filter(|(e, r)| e < r).count();
+        TargetMetrics {
+            count: self.estimates.len(),
+            failed: self.failed,
+            mae: errors.iter().sum::<f64>() / count,
+            median_absolute_error: if errors.is_empty() {
+                f64::NAN
+            } else {
+                Combine::Median.apply_f64(&errors)
+            },
+            overpay_ratio: overpay / required,
+            underestimate_fraction: under as f64 / count,
+        }
+    }
+}
+
+/// Estimate every row with `model` and compare the estimates with the required fee rates
+pub fn run_backtest(model: &FeeModel, rows: impl Iterator<Item = BacktestRow>) -> BacktestReport {
+    run_backtest_with(model, rows, |_, _| ())
+}
+
+/// Like [`run_backtest`], calling `on_estimate` with every row and its estimate, `None` if it
+/// failed, for example to write the estimates next to the required fee rates
+pub fn run_backtest_with<F: FnMut(&BacktestRow, Option<f32>)>(
+    model: &FeeModel,
+    rows: impl Iterator<Item = BacktestRow>,
+    mut on_estimate: F,
+) -> BacktestReport {
+    let mut per_target: BTreeMap<u16, Outcomes> = BTreeMap::new();
+    let mut overall = Outcomes::default();
+    for row in rows {
+        let outcomes = per_target.entry(row.target).or_default();
+        let estimate = model.estimate(
+            row.target,
+            Some(row.timestamp),
+            &row.fee_rates,
+            row.last_block_ts,
+        );
+        on_estimate(&row, estimate.as_ref().ok().copied());
+        match estimate {
+            Ok(estimate) => {
+                let pair = (estimate as f64, row.required_fee_rate);
+                outcomes.estimates.push(pair);
+                overall.estimates.push(pair);
+            }
+            Err(_) => {
+                outcomes.failed += 1;
+                overall.failed += 1;
+            }
+        }
+    }
+    BacktestReport {
+        per_target: per_target
+            .iter()
+            .map(|(target, outcomes)| (*target, outcomes.metrics()))
+            .collect(),
+        overall: overall.metrics(),
+    }
+}
+
+/// Read the rows of a CSV with header
+/// `timestamp,last_block_ts,target,required_fee_rate,fee_rates`, where `fee_rates` are separated
+/// by spaces and may be empty. The columns may be in any order and unknown columns are ignored.
+pub fn read_csv<R: Read>(reader: R) -> Result<Vec<BacktestRow>, Error> {
+    let mut csv = CsvReader::new(reader)?;
+    let timestamp = csv.column("timestamp")?;
+    let last_block_ts = csv.column("last_block_ts")?;
+    let target = csv.column("target")?;
+    let required_fee_rate = csv.column("required_fee_rate")?;
+    let fee_rates = csv.column("fee_rates")?;
+    let mut rows = vec![];
+    while let Some(record) = csv.next_record() {
+        let record = record?;
+        let rates = record
+            .get(fee_rates)
+            .split_whitespace()
+            .map(|rate| rate.parse().map_err(|_| record.invalid("fee_rates", rate)))
+            .collect::<Result<_, _>>()?;
+        rows.push(BacktestRow {
+            timestamp: record.parse(timestamp, "timestamp")?,
+            last_block_ts: record.parse(last_block_ts, "last_block_ts")?,
+            fee_rates: rates,
+            target: record.parse(target, "target")?,
+            required_fee_rate: record.parse(required_fee_rate, "required_fee_rate")?,
+        });
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::backtest::{read_csv, run_backtest, run_backtest_with};
+    #[cfg(feature = "embedded-models")]
+    use crate::dataset::read_replay_csv;
+    #[cfg(feature = "embedded-models")]
+    use crate::tests::get_fee_model;
+    use crate::{Error, FeeModel, ModelData};
+
+    #[test]
+    fn test_backtest() {
+        let quantiles = || {
+            let bytes = include_bytes!("../models/fixtures/quantiles.cbor");
+            ModelData::from_slice(bytes).unwrap()
+        };
+        // estimates `2 * target + delta_last / 100 - 0.5`
+        let model = FeeModel::from_models(quantiles(), quantiles());
+        let rows = read_csv(File::open("models/fixtures/backtest.csv").unwrap()).unwrap();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0].fee_rates, vec![1.0, 2.5, 10.0, 40.0]);
+        let report = run_backtest(&model, rows.into_iter());
+
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let low = &report.per_target[&1];
+        assert_eq!((low.count, low.failed), (2, 0));
+        assert!(close(low.mae, 1.0));
+        assert!(close(low.median_absolute_error, 1.0));
+        assert!(close(low.overpay_ratio, 1.5 / 11.0));
+        assert!(close(low.underestimate_fraction, 0.5));
+        let high = &report.per_target[&6];
+        assert_eq!((high.count, high.failed), (3, 1));
+        assert!(close(high.mae, 1.0));
+        assert!(close(high.median_absolute_error, 0.5));
+        assert!(close(high.overpay_ratio, 2.5 / 35.5));
+        assert!(close(high.underestimate_fraction, 1.0 / 3.0));
+        let overall = &report.overall;
+        assert_eq!((overall.count, overall.failed), (5, 1));
+        assert!(close(overall.mae, 1.0));
+        assert!(close(overall.median_absolute_error, 0.5));
+        assert!(close(overall.overpay_ratio, 4.0 / 46.5));
+        assert!(close(overall.underestimate_fraction, 0.4));
+
+        let empty = run_backtest(&model, vec![].into_iter());
+        assert!(empty.per_target.is_empty());
+        assert_eq!(empty.overall.count, 0);
+        assert!(empty.overall.mae.is_nan());
+    }
+
+    /// The first rows of the sample of the `replay` example, see `models/make_replay.py`
+    #[cfg(feature = "embedded-models")]
+    #[test]
+    fn test_replay() {
+        let bytes = include_bytes!("../models/replay.csv.zz");
+        let csv = miniz_oxide::inflate::decompress_to_vec_zlib(bytes).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let slice: Vec<&str> = csv.lines().take(11).collect();
+        let windows = read_replay_csv(slice.join("\n").as_bytes(), &[1, 3, 6, 24]).unwrap();
+        let rows: Vec<_> = windows.iter().flat_map(|w| w.backtest_rows()).collect();
+        assert_eq!(rows.len(), 40);
+        assert_eq!((rows[0].target, rows[3].target), (1, 24));
+        assert_eq!(rows[0].timestamp, rows[3].timestamp);
+        assert_eq!(rows[0].fee_rates.len(), 200);
+
+        let mut estimates = vec![];
+        let report = run_backtest_with(&get_fee_model(), rows.iter().cloned(), |row, estimate| {
+            estimates.push((row.target, estimate.unwrap()))
+        });
+        assert_eq!(estimates.len(), 40);
+        assert_eq!(report, run_backtest(&get_fee_model(), rows.into_iter()));
+        // the products of the `ndarray` feature are summed in another order
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-3;
+        let overall = &report.overall;
+        assert_eq!((overall.count, overall.failed), (40, 0));
+        assert!(close(overall.mae, 6.11836));
+        assert!(close(overall.median_absolute_error, 5.54288));
+        assert!(close(overall.overpay_ratio, 0.18938));
+        assert!(close(overall.underestimate_fraction, 0.675));
+        let next = &report.per_target[&1];
+        assert_eq!((next.count, next.failed), (10, 0));
+        assert!(close(next.mae, 8.91991));
+        assert!(close(next.underestimate_fraction, 0.4));
+        let day = &report.per_target[&24];
+        assert!(close(day.mae, 4.87177));
+        assert!(close(day.underestimate_fraction, 1.0));
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let csv = "fee_rates,target,timestamp,last_block_ts\n1 2,1,100,50\n";
+        let err = read_csv(csv.as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::MissingColumn(c) if c == "required_fee_rate"));
+
+        let header = "timestamp,last_block_ts,target,required_fee_rate,fee_rates\n";
+        let err = read_csv(format!("{}100,50,1,2.0,1 x\n", header).as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid csv at line 2: invalid fee_rates \"x\""
+        );
+        let err = read_csv(format!("{}\n100,50,-1,2.0,1\n", header).as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid csv at line 3: invalid target \"-1\""
+        );
+        let err = read_csv(format!("{}100,50,1\n", header).as_bytes()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid csv at line 2: expected 5 values, found 3"
+        );
+        let err = read_csv(&b""[..]).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid csv at line 1: missing header");
+    }
+}
@@ -0,0 +1,208 @@
+//! Command line estimates, run `bitcoin-fee-model --help` for the usage
+
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use bitcoin_fee_model::{cbor_to_postcard, FeeEstimate, FeeModel, ModelKind};
+use serde_json::json;
+
+const USAGE: &str = "Usage: bitcoin-fee-model estimate --target <BLOCKS> --fee-rates-file <FILE> \
+--last-block-ts <UNIX_SECONDS> [--timestamp <UNIX_SECONDS>] [--low <MODEL> --high <MODEL>] [--json]
+       bitcoin-fee-model convert <CBOR_MODEL> <OUTPUT>
+
+estimate prints the fee rate estimate in sat/vB, convert writes a CBOR model in the faster
+loading postcard layout, accepted like the CBOR models by --low and --high.
+
+  --target          confirmation target in blocks
+  --fee-rates-file  fee rates of the transactions of the last blocks in sat/vB, one per line, `-`
+                    reads them from stdin
+  --last-block-ts   unix time in seconds of the last block
+  --timestamp       unix time in seconds of the estimate, the current time if missing
+  --low, --high     models for the 1 and 2 blocks targets and for the longer ones, CBOR or
+                    converted, the embedded models if missing
+  --json            print the estimate with the model inputs and outputs as JSON";
+
+enum Command {
+    Estimate(Args),
+    Convert { input: PathBuf, output: PathBuf },
+}
+
+struct Args {
+    target: u16,
+    fee_rates_file: String,
+    last_block_ts: i64,
+    timestamp: Option<i64>,
+    models: Option<(PathBuf, PathBuf)>,
+    json: bool,
+}
+
+fn parse_command(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+    match args.next().as_deref() {
+        Some("estimate") => parse_args(args).map(Command::Estimate),
+        Some("convert") => {
+            let input = args.next().ok_or("missing model to convert")?;
+            let output = args.next().ok_or("missing output file")?;
+            if let Some(arg) = args.next() {
+                return Err(format!("unknown argument {}", arg));
+            }
+            Ok(Command::Convert {
+                input: input.into(),
+                output: output.into(),
+            })
+        }
+        Some(command) => Err(format!("unknown command {}", command)),
+        None => Err("missing command".into()),
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let (mut target, mut fee_rates_file, mut last_block_ts) = (None, None, None);
+    let (mut timestamp, mut low, mut high, mut json) = (None, None, None, false);
+    while let Some(arg) = args.next() {
+        if arg == "--json" {
+            json = true;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value of {}", arg))?;
+        let number = |value: &str| {
+            value
+                .parse::<i64>()
+                .map_err(|_| format!("invalid value of {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "--target" => {
+                let value = number(&value)?;
+                let value =
+                    u16::try_from(value).map_err(|_| format!("invalid target {}", value))?;
+                target = Some(value);
+            }
+            "--fee-rates-file" => fee_rates_file = Some(value),
+            "--last-block-ts" => last_block_ts = Some(number(&value)?),
+            "--timestamp" => timestamp = Some(number(&value)?),
+            "--low" => low = Some(PathBuf::from(value)),
+            "--high" => high = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    let models = match (low, high) {
+        (Some(low), Some(high)) => Some((low, high)),
+        (None, None) => None,
+        _ => return Err("--low and --high must be given together".into()),
+    };
+    Ok(Args {
+        target: target.ok_or("missing --target")?,
+        fee_rates_file: fee_rates_file.ok_or("missing --fee-rates-file")?,
+        last_block_ts: last_block_ts.ok_or("missing --last-block-ts")?,
+        timestamp,
+        models,
+        json,
+    })
+}
+
+/// Parse one fee rate per line, skipping empty lines
+fn read_fee_rates<R: Read>(reader: R) -> Result<Vec<f64>, String> {
+    let mut fee_rates = vec![];
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line.map_err(|e| format!("cannot read fee rates: {}", e))?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let rate = line
+            .parse()
+            .map_err(|_| format!("invalid fee rate at line {}: {}", i + 1, line))?;
+        fee_rates.push(rate);
+    }
+    Ok(fee_rates)
+}
+
+fn to_json(estimate: &FeeEstimate) -> serde_json::Value {
+    let model = match estimate.model {
+        ModelKind::Low => "low",
+        ModelKind::High => "high",
+        ModelKind::Long => "long",
+        ModelKind::Blended => "blended",
+    };
+    let inputs = &estimate.inputs;
+    json!({
+        "rate": estimate.rate,
+        "block_target": estimate.block_target,
+        "model": model,
+        "raw_output": estimate.raw_output,
+        "member_outputs": estimate.member_outputs,
+        "clamped": estimate.clamped,
+        "delta_last_clamped": estimate.delta_last_clamped,
+        "buckets_capped": estimate.buckets_capped,
+        "filtered_fee_rates": estimate.filtered_fee_rates,
+        "inputs": {
+            "confirms_in": inputs.confirms_in,
+            "day_of_week": inputs.day_of_week,
+            "hour": inputs.hour,
+            "delta_last": inputs.delta_last,
+            "buckets": inputs.buckets,
+        },
+    })
+}
+
+fn run(args: Args) -> Result<String, String> {
+    let fee_rates = match args.fee_rates_file.as_str() {
+        "-" => read_fee_rates(io::stdin().lock())?,
+        path => {
+            let file = File::open(path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+            read_fee_rates(file)?
+        }
+    };
+    let model = match &args.models {
+        Some((low, high)) => FeeModel::from_paths(low, high),
+        None => FeeModel::new(),
+    };
+    let model = model.map_err(|e| e.to_string())?;
+    let estimate = model
+        .estimate_detailed(args.target, args.timestamp, &fee_rates, args.last_block_ts)
+        .map_err(|e| e.to_string())?;
+    if args.json {
+        Ok(to_json(&estimate).to_string())
+    } else {
+        Ok(estimate.rate.to_string())
+    }
+}
+
+fn convert(input: &Path, output: &Path) -> Result<String, String> {
+    let open = |e: io::Error| format!("cannot open {}: {}", input.display(), e);
+    let cbor = BufReader::new(File::open(input).map_err(open)?);
+    let create = |e: io::Error| format!("cannot create {}: {}", output.display(), e);
+    let postcard = File::create(output).map_err(create)?;
+    cbor_to_postcard(cbor, postcard).map_err(|e| e.to_string())?;
+    Ok(format!("{} written", output.display()))
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let command = match parse_command(args.into_iter()) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            exit(2);
+        }
+    };
+    let result = match command {
+        Command::Estimate(args) => run(args),
+        Command::Convert { input, output } => convert(&input, &output),
+    };
+    match result {
+        Ok(output) => println!("{}", output),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
+        }
+    }
+}
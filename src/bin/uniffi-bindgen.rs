@@ -0,0 +1,5 @@
+//! Generates the Swift and Kotlin bindings of the `uniffi` feature, see `src/mobile.rs`
+
+fn main() {
+    uniffi::uniffi_bindgen_main()
+}
@@ -0,0 +1,194 @@
+//! Estimates memoized until their inputs change, see [`CachedFeeModel`]
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use crate::{Error, FeeModel};
+
+/// Default of [`CachedFeeModel::with_ttl`]
+const DEFAULT_TTL: Duration = Duration::from_secs(60);
+
+const MILLIS_PER_HOUR: i64 = 60 * 60 * 1000;
+
+/// A [`FeeModel`] with the fee rates of the last blocks, memoizing the estimates of every target.
+///
+/// The inputs of the models change only with a new block and when the hour changes, apart from the
+/// seconds since the last block, so estimates are reused until [`CachedFeeModel::update`] is
+/// called, the hour (UTC) of the clock of the model changes or the TTL expires. Can be shared
+/// between threads, estimates take a read lock unless they must be computed.
+#[derive(Debug)]
+pub struct CachedFeeModel {
+    model: FeeModel,
+    ttl: Duration,
+    state: RwLock<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// the sanitized fee rates and the timestamp of the last block
+    window: Option<(Vec<f64>, i64)>,
+    /// incremented at every update, so that estimates computed with a previous window are not
+    /// cached
+    generation: u64,
+    /// the estimate of every target, with the time of the clock in milliseconds it was computed at
+    estimates: HashMap<u16, (f32, i64)>,
+}
+
+impl CachedFeeModel {
+    /// Estimates error with [`Error::InsufficientData`] until the first
+    /// [`CachedFeeModel::update`]
+    pub fn new(model: FeeModel) -> Self {
+        CachedFeeModel {
+            model,
+            ttl: DEFAULT_TTL,
+            state: RwLock::new(State::default()),
+        }
+    }
+
+    /// Recompute estimates older than `ttl`, 60 seconds by default
+    pub fn with_ttl(self, ttl: Duration) -> Self {
+        CachedFeeModel { ttl, ..self }
+    }
+
+    pub fn model(&self) -> &FeeModel {
+        &self.model
+    }
+
+    /// Replace the fee rates of the last blocks and the timestamp of the last block, like at every
+    /// new block, and drop the cached estimates
+    pub fn update(&self, fee_rates: &[f64], last_block_ts: i64) -> Result<(), Error> {
+        let fee_rates = self.model.sanitize(fee_rates)?.into_owned();
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.window = Some((fee_rates, last_block_ts));
+        state.generation += 1;
+        state.estimates.clear();
+        Ok(())
+    }
+
+    /// Like [`FeeModel::estimate`] at the time of the clock of the model with the last
+    /// [`CachedFeeModel::update`], see [`FeeModel::with_clock`]
+    pub fn estimate(&self, block_target: u16) -> Result<f32, Error> {
+        let now = self.model.now()?;
+        let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+        if let Some((rate, computed_at)) = state.estimates.get(&block_target) {
+            if self.is_fresh(*computed_at, now) {
+                return Ok(*rate);
+            }
+        }
+        let (fee_rates, last_block_ts) = state.window.as_ref().ok_or(Error::InsufficientData)?;
+        let generation = state.generation;
+        let rate = self.model.estimate(
+            block_target,
+            Some(now.div_euclid(1000)),
+            fee_rates,
+            *last_block_ts,
+        )?;
+        drop(state);
+
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        if state.generation == generation {
+            state.estimates.insert(block_target, (rate, now));
+        }
+        Ok(rate)
+    }
+
+    fn is_fresh(&self, computed_at: i64, now: i64) -> bool {
+        let ttl = self.ttl.as_millis() as i64;
+        let same_hour = computed_at.div_euclid(MILLIS_PER_HOUR) == now.div_euclid(MILLIS_PER_HOUR);
+        same_hour && (0..ttl).contains(&(now - computed_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::model_data::tests::get_test_model_with_hour_renamed;
+    use crate::tests::{get_fee_model, get_low_model, sample_rates, SAMPLE_TS};
+    use crate::{CachedFeeModel, Error, FeeModel};
+
+    #[test]
+    fn test_cached_fee_model() {
+        // 2021-02-19 04:14:05 UTC
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let now = Arc::new(AtomicI64::new(ts));
+        let clock = now.clone();
+        let model = get_fee_model()
+            .with_clock(move || Utc.timestamp_opt(clock.load(Ordering::SeqCst), 0).unwrap());
+        let cached = CachedFeeModel::new(model).with_ttl(Duration::from_secs(120));
+        assert!(matches!(cached.estimate(6), Err(Error::InsufficientData)));
+
+        cached.update(&rates, ts - 300).unwrap();
+        let estimate = |ts: i64| cached.model().estimate(6, Some(ts), &rates, ts - 300);
+        let first = cached.estimate(6).unwrap();
+        assert_eq!(first, estimate(ts).unwrap());
+        // cache hits ignore the seconds elapsed since the last block
+        now.store(ts + 60, Ordering::SeqCst);
+        assert_ne!(
+            cached.model().estimate(6, None, &rates, ts - 300).unwrap(),
+            first
+        );
+        assert_eq!(cached.estimate(6).unwrap().to_bits(), first.to_bits());
+
+        // the TTL expired
+        now.store(ts + 120, Ordering::SeqCst);
+        let expired = cached.estimate(6).unwrap();
+        assert_eq!(
+            expired,
+            cached.model().estimate(6, None, &rates, ts - 300).unwrap()
+        );
+        assert_ne!(expired, first);
+
+        // the hour changes at 05:00:00
+        cached.update(&rates, ts - 300).unwrap();
+        now.store(ts + 2754, Ordering::SeqCst);
+        let before = cached.estimate(6).unwrap();
+        now.store(ts + 2755, Ordering::SeqCst);
+        let after = cached.estimate(6).unwrap();
+        assert_eq!(
+            after,
+            cached.model().estimate(6, None, &rates, ts - 300).unwrap()
+        );
+        assert_ne!(before, after);
+
+        // a new block
+        now.store(ts, Ordering::SeqCst);
+        cached.update(&rates, ts - 300).unwrap();
+        let low = cached.estimate(1).unwrap();
+        cached.update(&rates[..500], ts - 30).unwrap();
+        let new_block = cached.estimate(1).unwrap();
+        let expected = cached.model().estimate(1, Some(ts), &rates[..500], ts - 30);
+        assert_eq!(new_block, expected.unwrap());
+        assert_ne!(new_block, low);
+        assert!(matches!(
+            cached.update(&[f64::NAN], ts),
+            Err(Error::InvalidInput("fee_rates"))
+        ));
+    }
+
+    #[test]
+    fn test_cached_summary_features() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        // the high model reads the median of the fee rates, not only their buckets
+        let high = get_test_model_with_hour_renamed("p50");
+        let model = FeeModel::from_models(get_low_model(), high)
+            .with_clock(move || Utc.timestamp_opt(ts, 0).unwrap());
+        let cached = CachedFeeModel::new(model);
+        for fee_rates in [&rates[..], &rates[..200], &rates[700..]].iter() {
+            cached.update(fee_rates, ts - 300).unwrap();
+            for target in [1u16, 2, 6, 144].iter() {
+                let expected = cached
+                    .model()
+                    .estimate(*target, Some(ts), fee_rates, ts - 300);
+                assert_eq!(cached.estimate(*target).unwrap(), expected.unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! SHA-256 digests of the serialized models, to verify the integrity of model files
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use sha2::{Digest, Sha256};
+
+use crate::Error;
+
+/// SHA-256 digest of `bytes`
+pub(crate) fn sha256(bytes: &[u8]) -> [u8; 32] {
+    Sha256::digest(bytes).into()
+}
+
+/// SHA-256 digest of bytes given in chunks
+#[cfg(feature = "std")]
+#[derive(Default)]
+pub(crate) struct Sha256Stream(Sha256);
+
+#[cfg(feature = "std")]
+impl Sha256Stream {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.0.update(bytes);
+    }
+
+    pub(crate) fn finish(self) -> [u8; 32] {
+        self.0.finalize().into()
+    }
+}
+
+/// Lowercase hex encoding of `bytes`
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+/// Parse a SHA-256 digest from its 64 hex characters
+fn digest_from_hex(hex: &str) -> Result<[u8; 32], Error> {
+    let invalid = || Error::InvalidChecksum(hex.into());
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let mut digest = [0u8; 32];
+    for (byte, chunk) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        let chunk = core::str::from_utf8(chunk).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(chunk, 16).map_err(|_| invalid())?;
+    }
+    Ok(digest)
+}
+
+/// Expected SHA-256 digests of the serialized low and high models, see
+/// [`crate::FeeModel::from_paths_verified`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelChecksums {
+    pub low: [u8; 32],
+    pub high: [u8; 32],
+    /// When present the digests must be signed
+    #[cfg(feature = "signature")]
+    pub signature: Option<ModelSignature>,
+}
+
+impl ModelChecksums {
+    pub fn new(low: [u8; 32], high: [u8; 32]) -> Self {
+        ModelChecksums {
+            low,
+            high,
+            #[cfg(feature = "signature")]
+            signature: None,
+        }
+    }
+
+    /// Digests in hex, like the output of `sha256sum`
+    pub fn from_hex(low: &str, high: &str) -> Result<Self, Error> {
+        Ok(ModelChecksums::new(
+            digest_from_hex(low)?,
+            digest_from_hex(high)?,
+        ))
+    }
+
+    /// Require the digests to be signed by `signature`
+    #[cfg(feature = "signature")]
+    pub fn with_signature(self, signature: ModelSignature) -> Self {
+        ModelChecksums {
+            signature: Some(signature),
+            ..self
+        }
+    }
+
+    /// Check the signature, if any, of the expected digests
+    pub(crate) fn verify_signature(&self) -> Result<(), Error> {
+        #[cfg(feature = "signature")]
+        if let Some(signature) = &self.signature {
+            signature.verify(&self.low, &signature.low)?;
+            signature.verify(&self.high, &signature.high)?;
+        }
+        Ok(())
+    }
+}
+
+/// ECDSA signatures with `pubkey` of the digests of the low and high models
+#[cfg(feature = "signature")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSignature {
+    pub pubkey: secp256k1::PublicKey,
+    pub low: secp256k1::ecdsa::Signature,
+    pub high: secp256k1::ecdsa::Signature,
+}
+
+#[cfg(feature = "signature")]
+impl ModelSignature {
+    fn verify(
+        &self,
+        digest: &[u8; 32],
+        signature: &secp256k1::ecdsa::Signature,
+    ) -> Result<(), Error> {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let message = secp256k1::Message::from_digest(*digest);
+        secp.verify_ecdsa(&message, signature, &self.pubkey)
+            .map_err(|_| Error::InvalidSignature(to_hex(digest)))
+    }
+}
+
+/// Errors with [`Error::ChecksumMismatch`] if the digest of `bytes` is not `expected`
+pub(crate) fn verify(expected: &[u8; 32], bytes: &[u8]) -> Result<(), Error> {
+    let actual = sha256(bytes);
+    if &actual != expected {
+        return Err(Error::ChecksumMismatch {
+            expected: to_hex(expected),
+            actual: to_hex(&actual),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::checksum::{digest_from_hex, sha256, to_hex};
+    use crate::Error;
+
+    #[test]
+    fn test_hex() {
+        let digest = sha256(b"");
+        let hex = to_hex(&digest);
+        assert_eq!(
+            hex,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(digest_from_hex(&hex).unwrap(), digest);
+        assert!(matches!(
+            digest_from_hex(&hex[1..]),
+            Err(Error::InvalidChecksum(_))
+        ));
+        let non_hex = hex.replace('e', "g");
+        assert!(digest_from_hex(&non_hex).is_err());
+        let non_ascii = format!("é{}", &hex[2..]);
+        assert!(digest_from_hex(&non_ascii).is_err());
+    }
+}
@@ -0,0 +1,269 @@
+//! Compact model layout that loads faster than CBOR, see [`ModelData::from_postcard`].
+//!
+//! The bytes start with the magic `BFPC` followed by the postcard encoding of the version of the
+//! layout, of a CBOR header with the same content of the header of the flat layout, and of the
+//! kernels and the biases of the three layers as sequences of f32, kernels in row-major order.
+//! The header stays CBOR since the metadata skips its missing fields, which postcard can't read
+//! back; the weights, most of the bytes, are read without parsing every value. CBOR remains the
+//! canonical interchange format, see [`cbor_to_postcard`].
+
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::matrix::Matrix;
+use crate::model_data::{check_finite, check_len, FieldsDescribe, Kernel, Weights};
+use crate::{Activation, Error, ModelData, ModelInfo, ModelLimits, OutputTransform};
+
+const MAGIC: &[u8; 4] = b"BFPC";
+const POSTCARD_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct HeaderRef<'a> {
+    norm: &'a FieldsDescribe,
+    fields: &'a [String],
+    alpha: f32,
+    activations: Vec<String>,
+    output_transform: OutputTransform,
+    #[serde(skip_serializing_if = "<[f32]>::is_empty")]
+    quantiles: &'a [f32],
+    metadata: &'a ModelInfo,
+    /// inputs, then the outputs of every layer
+    widths: [usize; 4],
+}
+
+#[derive(Deserialize)]
+struct Header {
+    norm: FieldsDescribe,
+    fields: Vec<String>,
+    alpha: f32,
+    #[serde(default)]
+    activations: Option<Vec<String>>,
+    #[serde(default)]
+    output_transform: OutputTransform,
+    #[serde(default)]
+    quantiles: Vec<f32>,
+    #[serde(default)]
+    metadata: ModelInfo,
+    widths: [usize; 4],
+}
+
+/// Everything after the version
+#[derive(Serialize, Deserialize)]
+struct Body {
+    header: Vec<u8>,
+    /// kernel and bias of every layer, from the input to the output
+    weights: [Vec<f32>; 6],
+}
+
+fn postcard_error(e: postcard::Error) -> Error {
+    Error::Postcard(e)
+}
+
+impl ModelData {
+    /// Load a model in the postcard layout written by [`ModelData::to_postcard`], checked like
+    /// the CBOR models of [`ModelData::from_slice`] and giving the same predictions. Errors with
+    /// [`Error::InvalidPostcardModel`] if the magic or the version don't match.
+    pub fn from_postcard(bytes: &[u8]) -> Result<ModelData, Error> {
+        ModelData::from_postcard_with_limits(bytes, &ModelLimits::default())
+    }
+
+    /// Like [`ModelData::from_postcard`] with custom `limits`
+    pub fn from_postcard_with_limits(
+        bytes: &[u8],
+        limits: &ModelLimits,
+    ) -> Result<ModelData, Error> {
+        let invalid = Error::InvalidPostcardModel;
+        limits.check("max_bytes", limits.max_bytes, bytes.len())?;
+        if !is_postcard(bytes) {
+            return Err(invalid("bad magic"));
+        }
+        let (version, rest): (u32, _) =
+            postcard::take_from_bytes(&bytes[MAGIC.len()..]).map_err(postcard_error)?;
+        if version != POSTCARD_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        let body: Body = postcard::from_bytes(rest).map_err(postcard_error)?;
+        let header: Header = serde_cbor::from_slice(&body.header).map_err(Error::Deserialize)?;
+        limits.check_widths(&header.widths)?;
+        let [inputs, n0, n1, o] = header.widths;
+        check_len("dense/kernel:0", header.fields.len(), inputs)?;
+
+        let [k0, b0, k1, b1, k2, b2] = body.weights;
+        let matrix = |layer: &'static str, values: Vec<f32>, width: usize, height: usize| {
+            check_len(layer, width * height, values.len())?;
+            check_finite(layer, &values)?;
+            Ok::<_, Error>(Matrix::from_buffer(
+                values.into_boxed_slice(),
+                width,
+                height,
+            ))
+        };
+        let weights = Weights {
+            l0_kernel: Kernel::F32(matrix("dense/kernel:0", k0, n0, inputs)?),
+            l0_bias: matrix("dense/bias:0", b0, n0, 1)?,
+            l1_kernel: Kernel::F32(matrix("dense_1/kernel:0", k1, n1, n0)?),
+            l1_bias: matrix("dense_1/bias:0", b1, n1, 1)?,
+            l2_kernel: Kernel::F32(matrix("dense_2/kernel:0", k2, o, n1)?),
+            l2_bias: matrix("dense_2/bias:0", b2, o, 1)?,
+        };
+        ModelData::from_parts(
+            header.norm,
+            header.fields,
+            header.alpha,
+            header.activations,
+            header.output_transform,
+            header.quantiles,
+            header.metadata,
+            weights,
+        )
+    }
+
+    /// Write the model in the postcard layout loadable with [`ModelData::from_postcard`], int8
+    /// and sparse kernels are written dequantized, giving the same predictions
+    pub fn to_postcard(&self) -> Result<Vec<u8>, Error> {
+        let w = &self.weights;
+        let header = HeaderRef {
+            norm: &self.norm,
+            fields: &self.fields,
+            alpha: self.alpha,
+            activations: self.activations.iter().map(Activation::name).collect(),
+            output_transform: self.output_transform,
+            quantiles: self.quantiles(),
+            metadata: &self.info,
+            widths: [
+                self.fields.len(),
+                w.l0_bias.width(),
+                w.l1_bias.width(),
+                w.l2_bias.width(),
+            ],
+        };
+        let kernel = |kernel: &Kernel| -> Vec<f32> {
+            (0..kernel.height())
+                .flat_map(|i| (0..kernel.width()).map(move |j| kernel.get(i, j)))
+                .collect()
+        };
+        let body = Body {
+            header: serde_cbor::to_vec(&header).map_err(Error::Serialize)?,
+            weights: [
+                kernel(&w.l0_kernel),
+                w.l0_bias.as_slice().to_vec(),
+                kernel(&w.l1_kernel),
+                w.l1_bias.as_slice().to_vec(),
+                kernel(&w.l2_kernel),
+                w.l2_bias.as_slice().to_vec(),
+            ],
+        };
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(postcard::to_allocvec(&POSTCARD_VERSION).map_err(postcard_error)?);
+        bytes.extend(postcard::to_allocvec(&body).map_err(postcard_error)?);
+        Ok(bytes)
+    }
+}
+
+/// `bytes` start with the magic of the postcard layout, CBOR models never do
+pub(crate) fn is_postcard(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Convert a CBOR model, like the ones in the `models` dir, to the postcard layout loadable with
+/// [`ModelData::from_postcard`]
+#[cfg(feature = "std")]
+pub fn cbor_to_postcard<R: Read, W: Write>(cbor: R, mut postcard: W) -> Result<(), Error> {
+    let bytes = ModelData::from_reader(cbor)?.to_postcard()?;
+    postcard.write_all(&bytes).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::model_data::tests::{assert_same_predictions, MODELS};
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::{cbor_to_postcard, Activation, Error, FeeModel, ModelData, ModelLimits};
+
+    #[test]
+    fn test_postcard() {
+        for bytes in MODELS.iter() {
+            let cbor = ModelData::from_slice(bytes).unwrap();
+            let mut postcard = vec![];
+            cbor_to_postcard(*bytes, &mut postcard).unwrap();
+            let loaded = ModelData::from_postcard(&postcard).unwrap();
+            assert_eq!(loaded.fields, cbor.fields);
+            assert_eq!(loaded.info, cbor.info);
+            assert_eq!(loaded.quantiles(), cbor.quantiles());
+            assert_same_predictions(&cbor, &loaded);
+            assert_eq!(loaded.to_postcard().unwrap(), postcard);
+            assert!(postcard.len() < bytes.len());
+
+            let quantized = cbor.quantize_int8();
+            let loaded = ModelData::from_postcard(&quantized.to_postcard().unwrap()).unwrap();
+            assert_same_predictions(&quantized, &loaded);
+
+            let mut gelu = cbor;
+            gelu.activations[1] = Activation::Gelu;
+            let loaded = ModelData::from_postcard(&gelu.to_postcard().unwrap()).unwrap();
+            assert_eq!(loaded.activations, gelu.activations);
+            assert_same_predictions(&gelu, &loaded);
+        }
+
+        let postcard = ModelData::from_slice(MODELS[0])
+            .unwrap()
+            .to_postcard()
+            .unwrap();
+        let mut bad = postcard.clone();
+        bad[0] = b'X';
+        let err = ModelData::from_postcard(&bad).unwrap_err();
+        assert!(matches!(err, Error::InvalidPostcardModel("bad magic")));
+        let mut bad = postcard.clone();
+        bad[4] = 2;
+        let err = ModelData::from_postcard(&bad).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidPostcardModel("unsupported version")
+        ));
+        let err = ModelData::from_postcard(&postcard[..postcard.len() - 4]).unwrap_err();
+        assert!(matches!(err, Error::Postcard(_)));
+        let mut bad = postcard.clone();
+        let last = bad.len() - 4;
+        bad[last..].copy_from_slice(&f32::NAN.to_le_bytes());
+        let err = ModelData::from_postcard(&bad).unwrap_err();
+        assert!(matches!(err, Error::NonFiniteWeight("dense_2/bias:0")));
+        let limits = ModelLimits {
+            max_parameters: 10,
+            ..Default::default()
+        };
+        let err = ModelData::from_postcard_with_limits(&postcard, &limits).unwrap_err();
+        assert!(matches!(err, Error::LimitExceeded { .. }));
+    }
+
+    #[test]
+    fn test_from_paths_postcard() {
+        let dir = std::env::temp_dir().join(format!("bfee-postcard-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let convert = |name: &str| {
+            let cbor = format!("models/{}/model.cbor", name);
+            let path = dir.join(format!("{}.bfpc", name));
+            let file = std::fs::File::create(&path).unwrap();
+            cbor_to_postcard(std::fs::File::open(cbor).unwrap(), file).unwrap();
+            path
+        };
+        let low = convert("20211027-180849");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        // the formats can be mixed
+        let model = FeeModel::from_paths(&low, high).unwrap();
+        let expected = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        for target in [1u16, 2, 6, 144] {
+            let estimate = model.estimate(target, Some(ts), &rates, ts - 300).unwrap();
+            let golden = expected
+                .estimate(target, Some(ts), &rates, ts - 300)
+                .unwrap();
+            assert_eq!(estimate.to_bits(), golden.to_bits());
+        }
+    }
+}
@@ -0,0 +1,447 @@
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::fee_bucket::{BucketMode, FeeBuckets};
+use crate::{Combine, Error, ModelInput, Staleness};
+
+/// Parameters used to build the inputs of the models, must match the ones used at training time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeeModelConfig {
+    /// Every bucket upper limit is this percent higher than the previous one, starting from 1 sat/vB
+    pub bucket_increment_percent: u32,
+    /// Buckets are created until this fee rate (sat/vB) is reached, higher rates go in the last one
+    pub bucket_limit: f64,
+    /// What the buckets count, the histogram if missing
+    #[serde(default)]
+    pub bucket_mode: BucketMode,
+    /// Number of buckets, `None` for the ones of the increment up to the limit. Another number
+    /// gives [`FeeBuckets::with_count`] buckets up to the limit instead, like the models with
+    /// another number of bucket features.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_count: Option<usize>,
+}
+
+impl Default for FeeModelConfig {
+    /// The configuration used to train the embedded models
+    fn default() -> Self {
+        FeeModelConfig {
+            bucket_increment_percent: 50,
+            bucket_limit: 500.0,
+            bucket_mode: BucketMode::Histogram,
+            bucket_count: None,
+        }
+    }
+}
+
+impl FeeModelConfig {
+    pub(crate) fn fee_buckets(&self) -> FeeBuckets {
+        FeeBuckets::new(self.bucket_increment_percent, self.bucket_limit)
+            .with_mode(self.bucket_mode)
+    }
+
+    /// The buckets of this configuration, with [`FeeModelConfig::bucket_count`] if any
+    pub(crate) fn counted_fee_buckets(&self) -> Result<FeeBuckets, Error> {
+        match self.bucket_count {
+            Some(count) => self.fee_buckets_with_count(count),
+            None => Ok(self.fee_buckets()),
+        }
+    }
+
+    /// Whether the buckets of `self` and `other` have the same limits, whatever their count
+    pub(crate) fn same_limits(&self, other: &FeeModelConfig) -> bool {
+        self.bucket_increment_percent == other.bucket_increment_percent
+            && self.bucket_limit == other.bucket_limit
+    }
+
+    /// The buckets of this configuration if there are `n_buckets` of them, otherwise
+    /// [`FeeBuckets::with_count`] up to the same limit
+    pub(crate) fn fee_buckets_with_count(&self, n_buckets: usize) -> Result<FeeBuckets, Error> {
+        let fee_buckets = self.fee_buckets();
+        if fee_buckets.len() == n_buckets {
+            return Ok(fee_buckets);
+        }
+        let fee_buckets = FeeBuckets::with_count(n_buckets, self.bucket_limit)?;
+        Ok(fee_buckets.with_mode(self.bucket_mode))
+    }
+}
+
+/// Processing applied to the inputs and to the predictions of the models
+#[derive(Debug, Clone, PartialEq)]
+pub struct EstimateOptions {
+    /// In batch estimations like [`crate::FeeModel::estimate_targets`], ensure a target never estimates
+    /// less than a greater target, by lowering every estimate to the minimum of the estimates of
+    /// the lower or equal targets. Single target estimations are not affected.
+    pub enforce_monotonic: bool,
+    /// To avoid the jump at the switch between the low and the high model, targets strictly
+    /// between `from` and `to` are linearly interpolated between the estimate at `from`, done with
+    /// the low model when `from` is at most [`crate::FeeModel::dispatch_threshold`], and the
+    /// estimate at `to`, done with the high model.
+    /// `None`, the default, or a range with `from >= to` keeps the hard switch.
+    pub blend: Option<BlendRange>,
+    /// Estimates lower than this fee rate (sat/vB) are raised to it, defaults to 1.0 sat/vB, the
+    /// minimum relay fee of Bitcoin Core. Nodes relaying lower fee rates can lower it down to
+    /// 0.0, the fractional estimates are returned in single precision without rounding.
+    pub min_fee_rate: f32,
+    /// Estimates higher than this fee rate (sat/vB) are lowered to it, `None` by default
+    pub max_fee_rate: Option<f32>,
+    /// Seconds since the last block lower than this are raised to it before being fed to the
+    /// models, like negative values caused by a local clock lagging the block timestamp.
+    /// Defaults to 0.
+    pub min_delta_last: i64,
+    /// Seconds since the last block higher than this are lowered to it before being fed to the
+    /// models, like after the node has been offline. Defaults to 2 hours. The models declaring
+    /// the greatest `delta_last` they have been trained with in [`crate::ModelInfo::max_delta_last`]
+    /// bound it to that too, see [`crate::FeeModel::max_delta_last`].
+    pub max_delta_last: Option<i64>,
+    /// Bucket counts higher than this are lowered to it before being fed to the models, as the
+    /// last blocks can't have more transactions and the counts are exact in single precision
+    /// only up to 2^24, see [`crate::FeeEstimate::buckets_capped`]. Defaults to 1,000,000, `None`
+    /// feeds any count, the greatest ones rounded.
+    pub max_bucket_count: Option<u64>,
+    /// A last block more in the future than this errors with [`crate::Error::InvalidInput`],
+    /// like a `last_block_ts` in milliseconds instead of seconds. Defaults to 2 hours, how much a
+    /// block timestamp can be ahead of the network time; closer ones are bounded by
+    /// [`EstimateOptions::min_delta_last`].
+    pub max_last_block_future: Duration,
+    /// A last block older than this errors with [`crate::Error::InvalidInput`], as it's likely
+    /// not a unix time in seconds or a wrong block. Defaults to 30 days, `None` accepts any age.
+    pub max_last_block_age: Option<Duration>,
+    /// How the predictions of the members of an ensemble are combined, the mean by default
+    pub combine: Combine,
+    /// In [`crate::FeeModel::estimate_from_blocks`], the fee rates of a block count as
+    /// `0.5^(height_offset / half_life)` transactions instead of one, so that older blocks weigh
+    /// less. The half-life is in blocks and must be positive, `None`, the default, weighs every
+    /// block 1.0 like the other estimates.
+    pub recency_half_life: Option<f64>,
+    /// [`crate::FeeModel::estimate_partial`] errors with [`crate::Error::InsufficientData`] for
+    /// the fee rates of fewer blocks than this, defaults to 3
+    pub min_blocks_covered: u8,
+    /// Fees computed by [`crate::FeeModel::estimate_fee`] lower than this (sat) are raised to
+    /// it, defaults to 0
+    pub min_fee: u64,
+    /// How targets greater than [`crate::FeeModel::max_target`] are estimated, clamped to it by
+    /// default
+    pub target_policy: TargetPolicy,
+    /// `Some(max)` drops the zero fee rates and the ones higher than `max` (sat/vB), like
+    /// 10,000 sat/vB, before bucketing them, as they are likely computed from wrong data. `None`,
+    /// the default, buckets every fee rate. Negative fee rates always error with
+    /// [`crate::Error::InvalidInput`].
+    pub filter_fee_rates: Option<f64>,
+    /// A last block older than this makes the estimates stale, likely because the node is behind
+    /// the chain tip, see [`crate::FeeEstimate::staleness`]. Defaults to 2 hours.
+    pub stale_after: Duration,
+    /// Error with [`crate::Error::StaleChainTip`] instead of estimating when the last block is
+    /// older than [`EstimateOptions::stale_after`], `false` by default
+    pub reject_stale: bool,
+    /// Compute [`crate::FeeEstimate::uncertainty_hint`] in
+    /// [`crate::FeeModel::estimate_detailed`], at the cost of up to four more predictions.
+    /// `false` by default.
+    pub uncertainty_hint: bool,
+    /// What to do with the inputs out of the [`crate::ModelInfo::feature_ranges`] of the models,
+    /// [`Strictness::Permissive`] by default
+    pub strictness: Strictness,
+    /// How the estimates are rounded after the other bounds, [`Rounding::None`] by default. The
+    /// rounded estimates stay between [`EstimateOptions::min_fee_rate`] and
+    /// [`EstimateOptions::max_fee_rate`], the estimate before rounding is in
+    /// [`crate::FeeEstimate::unrounded_rate`].
+    pub rounding: Rounding,
+    /// How the `day_of_week` and `hour` features are chosen, from the time of the estimate by
+    /// default, see [`TimeFeatures`]
+    pub time_features: TimeFeatures,
+}
+
+/// The `day_of_week` and `hour` features of the estimates, see
+/// [`EstimateOptions::time_features`]. The estimates of the time of the day correlate with the
+/// timezone of the caller, which callers like a coinjoin coordinator prefer not to reveal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeFeatures {
+    /// the day and the hour (UTC) of the timestamp of the estimate or of the current time
+    #[default]
+    FromTimestamp,
+    /// the same `hour`, from 0 to 23, and `day`, from 0, monday, to 6, for every estimate, like
+    /// the [`crate::FeatureOverrides`] of every estimate. Errors with
+    /// [`crate::Error::InvalidInput`] if out of range.
+    Fixed { hour: u8, day: u8 },
+    /// the mean of the predictions at `samples` evenly spaced hours, the day of the week
+    /// rotating at every sample, so that 168 samples cover every hour of the week once. Every
+    /// estimate costs `samples` forward passes of every model instead of one, the overridden
+    /// hour and day of [`crate::FeatureOverrides`] are averaged over too. The inputs of
+    /// [`crate::FeeEstimate::inputs`] report the mean hour and day of the samples. Errors with
+    /// [`crate::Error::InvalidInput`] for zero samples.
+    Marginalize { samples: u8 },
+}
+
+impl TimeFeatures {
+    /// Set the time features of `input`, built with the ones of the timestamp, as configured
+    pub(crate) fn apply(&self, input: &mut ModelInput) -> Result<(), Error> {
+        match *self {
+            TimeFeatures::FromTimestamp => Ok(()),
+            TimeFeatures::Fixed { hour, day } if hour <= 23 && day <= 6 => {
+                input.hour = hour as f32;
+                input.day_of_week = day as f32;
+                Ok(())
+            }
+            TimeFeatures::Marginalize { samples } if samples > 0 => {
+                let (mut day, mut hour) = (0.0, 0.0);
+                for sample in TimeFeatures::sample_inputs(input, samples) {
+                    day += sample.day_of_week;
+                    hour += sample.hour;
+                }
+                input.day_of_week = day / samples as f32;
+                input.hour = hour / samples as f32;
+                Ok(())
+            }
+            _ => Err(Error::InvalidInput("time_features")),
+        }
+    }
+
+    /// The `(day, hour)` of the sample `i` of [`TimeFeatures::Marginalize`] with `samples`
+    pub(crate) fn sample(i: u8, samples: u8) -> (u8, u8) {
+        let (i, samples) = (i as u32, samples.max(1) as u32);
+        let hour = (2 * i + 1) * 24 / (2 * samples);
+        ((i % 7) as u8, hour as u8)
+    }
+
+    /// `predict` of `input`, or the mean of `predict` of `input` at the time of every sample
+    /// of [`TimeFeatures::Marginalize`]
+    pub(crate) fn mean<F>(&self, input: &ModelInput, mut predict: F) -> Result<f64, Error>
+    where
+        F: FnMut(&ModelInput) -> Result<f64, Error>,
+    {
+        let samples = match *self {
+            TimeFeatures::Marginalize { samples } if samples > 0 => samples,
+            _ => return predict(input),
+        };
+        let mut sum = 0.0;
+        for input in TimeFeatures::sample_inputs(input, samples) {
+            sum += predict(&input)?;
+        }
+        Ok(sum / samples as f64)
+    }
+
+    /// Like [`TimeFeatures::mean`] for `predict` returning several values, like the quantiles
+    /// of a model, averaged one by one
+    pub(crate) fn mean_each<F>(&self, input: &ModelInput, mut predict: F) -> Result<Vec<f32>, Error>
+    where
+        F: FnMut(&ModelInput) -> Result<Vec<f32>, Error>,
+    {
+        let samples = match *self {
+            TimeFeatures::Marginalize { samples } if samples > 0 => samples,
+            _ => return predict(input),
+        };
+        let mut sums = Vec::new();
+        for input in TimeFeatures::sample_inputs(input, samples) {
+            let values = predict(&input)?;
+            sums.resize(values.len(), 0.0f64);
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum += value as f64;
+            }
+        }
+        Ok(sums
+            .iter()
+            .map(|sum| (sum / samples as f64) as f32)
+            .collect())
+    }
+
+    /// `input` at the time of every sample of [`TimeFeatures::Marginalize`] with `samples`
+    fn sample_inputs(input: &ModelInput, samples: u8) -> impl Iterator<Item = ModelInput> + '_ {
+        (0..samples).map(move |i| {
+            let (day, hour) = TimeFeatures::sample(i, samples);
+            let mut input = input.clone();
+            input.day_of_week = day as f32;
+            input.hour = hour as f32;
+            input
+        })
+    }
+
+    pub(crate) fn is_marginalized(&self) -> bool {
+        matches!(self, TimeFeatures::Marginalize { .. })
+    }
+}
+
+/// The rounding of the estimates, to display them or to avoid broadcasting the exact output of
+/// the models, see [`EstimateOptions::rounding`]. The estimates are always rounded up.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Rounding {
+    /// the estimates are the outputs of the models within the bounds of the options
+    #[default]
+    None,
+    /// up to `decimals` decimal digits, like 13.8 for 13.734621 with one
+    Up { decimals: u8 },
+    /// up to a multiple of `sat_per_vb`, like 14.0 for 13.734621 with 0.5, errors with
+    /// [`crate::Error::InvalidInput`] unless positive
+    ToIncrement { sat_per_vb: f32 },
+    /// add a uniform jitter between 0 and `spread` sat/vB and round up to `decimals`, so equal
+    /// estimates differ, drawn from the generator of [`crate::FeeModel::with_rng`]. Errors with
+    /// [`crate::Error::MissingRng`] without one and [`crate::Error::InvalidInput`] for a negative
+    /// spread.
+    Randomized { spread: f32, decimals: u8 },
+}
+
+impl Rounding {
+    /// Round `rate` up, with `uniform` between 0 and 1 for [`Rounding::Randomized`]
+    pub(crate) fn apply<F>(&self, rate: f32, uniform: F) -> Result<f32, Error>
+    where
+        F: FnOnce() -> Result<f32, Error>,
+    {
+        match *self {
+            Rounding::None => Ok(rate),
+            Rounding::Up { decimals } => Ok(round_up(rate, libm::pow(10.0, -(decimals as f64)))),
+            Rounding::ToIncrement { sat_per_vb } if sat_per_vb > 0.0 && sat_per_vb.is_finite() => {
+                Ok(round_up(rate, sat_per_vb as f64))
+            }
+            Rounding::ToIncrement { .. } => Err(Error::InvalidInput("rounding")),
+            Rounding::Randomized { spread, decimals } if spread >= 0.0 && spread.is_finite() => {
+                let jittered = rate + uniform()? * spread;
+                Ok(round_up(jittered, libm::pow(10.0, -(decimals as f64))))
+            }
+            Rounding::Randomized { .. } => Err(Error::InvalidInput("rounding")),
+        }
+    }
+}
+
+/// `rate` rounded up to a multiple of `increment`, the multiple whose single precision value is
+/// `rate` if any, like 13.7 for the closest `f32` to 13.7 which is lower than it
+fn round_up(rate: f32, increment: f64) -> f32 {
+    let steps = libm::ceil(rate as f64 / increment);
+    let below = ((steps - 1.0) * increment) as f32;
+    if below >= rate {
+        below
+    } else {
+        (steps * increment) as f32
+    }
+}
+
+/// What to do with the targets greater than the ones the models have been trained for, see
+/// [`EstimateOptions::target_policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TargetPolicy {
+    /// estimate them like [`crate::FeeModel::max_target`], instead of extrapolating
+    #[default]
+    Clamp,
+    /// error with [`crate::Error::InvalidTarget`]
+    Reject,
+}
+
+/// How to estimate from inputs out of the ranges seen in training, where the predictions
+/// extrapolate, see [`EstimateOptions::strictness`]. The fields without a range declared by the
+/// models are not checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strictness {
+    /// estimate without checking the ranges
+    #[default]
+    Permissive,
+    /// estimate and list the fields out of range in
+    /// [`crate::FeeEstimate::out_of_range_features`]
+    Warn,
+    /// error with [`crate::Error::OutOfDistribution`]
+    Strict,
+}
+
+impl Default for EstimateOptions {
+    fn default() -> Self {
+        EstimateOptions {
+            enforce_monotonic: false,
+            blend: None,
+            min_fee_rate: 1.0,
+            max_fee_rate: None,
+            min_delta_last: 0,
+            max_delta_last: Some(2 * 60 * 60),
+            max_bucket_count: Some(1_000_000),
+            max_last_block_future: Duration::from_secs(2 * 60 * 60),
+            max_last_block_age: Some(Duration::from_secs(30 * 24 * 60 * 60)),
+            combine: Combine::Mean,
+            recency_half_life: None,
+            min_blocks_covered: 3,
+            min_fee: 0,
+            target_policy: TargetPolicy::Clamp,
+            filter_fee_rates: None,
+            stale_after: Duration::from_secs(2 * 60 * 60),
+            reject_stale: false,
+            uncertainty_hint: false,
+            strictness: Strictness::Permissive,
+            rounding: Rounding::None,
+            time_features: TimeFeatures::FromTimestamp,
+        }
+    }
+}
+
+impl EstimateOptions {
+    /// Bound `rate` between [`EstimateOptions::min_fee_rate`] and [`EstimateOptions::max_fee_rate`],
+    /// returns the bounded rate and whether it changed. A NaN `rate` is raised to the minimum.
+    pub(crate) fn clamp(&self, rate: f32) -> (f32, bool) {
+        self.clamp_with_min(rate, self.min_fee_rate)
+    }
+
+    /// Like [`EstimateOptions::clamp`] with `min_fee_rate` instead of the one of the options
+    pub(crate) fn clamp_with_min(&self, rate: f32, min_fee_rate: f32) -> (f32, bool) {
+        let mut clamped = rate.max(min_fee_rate);
+        if let Some(max) = self.max_fee_rate {
+            clamped = clamped.min(max);
+        }
+        let changed = clamped != rate;
+        (clamped, changed)
+    }
+
+    /// Like [`EstimateOptions::clamp`] in double precision, without reporting the change
+    pub(crate) fn clamp_f64(&self, rate: f64) -> f64 {
+        let mut clamped = rate.max(self.min_fee_rate as f64);
+        if let Some(max) = self.max_fee_rate {
+            clamped = clamped.min(max as f64);
+        }
+        clamped
+    }
+
+    /// Bound `delta` between [`EstimateOptions::min_delta_last`] and `max`, returns the bounded
+    /// delta and whether it changed
+    pub(crate) fn clamp_delta_last(&self, delta: f64, max: Option<i64>) -> (f64, bool) {
+        let mut clamped = delta.max(self.min_delta_last as f64);
+        if let Some(max) = max {
+            clamped = clamped.min(max as f64);
+        }
+        (clamped, clamped != delta)
+    }
+
+    /// Check the last block at the unix time in milliseconds `last_block` is neither more in the
+    /// future nor older at `now` than allowed
+    pub(crate) fn check_last_block(&self, now: i64, last_block: i64) -> Result<(), Error> {
+        let millis = |d: Duration| i64::try_from(d.as_millis()).unwrap_or(i64::MAX);
+        let age = now.saturating_sub(last_block);
+        let too_old = self.max_last_block_age.is_some_and(|max| age > millis(max));
+        if age < -millis(self.max_last_block_future) || too_old {
+            return Err(Error::InvalidInput("last_block_ts"));
+        }
+        Ok(())
+    }
+
+    /// The staleness of a last block at the unix time in milliseconds `last_block` at `now`
+    pub(crate) fn staleness(&self, now: i64, last_block: i64) -> Staleness {
+        let age = Duration::from_millis(now.saturating_sub(last_block).max(0) as u64);
+        if age > self.stale_after {
+            Staleness::Stale { age }
+        } else {
+            Staleness::Fresh
+        }
+    }
+}
+
+/// Targets range where the low and the high model estimates are blended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlendRange {
+    pub from: u16,
+    pub to: u16,
+}
+
+impl BlendRange {
+    /// Weight of the high model for `block_target`, `None` if the target is not blended
+    pub(crate) fn high_weight(&self, block_target: u16) -> Option<f32> {
+        if self.from < block_target && block_target < self.to {
+            Some((block_target - self.from) as f32 / (self.to - self.from) as f32)
+        } else {
+            None
+        }
+    }
+}
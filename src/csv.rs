@@ -0,0 +1,95 @@
+//! Minimal reader of comma separated values with a header line, without quoting
+
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::str::FromStr;
+
+use crate::Error;
+
+pub(crate) struct CsvReader<R> {
+    lines: Lines<BufReader<R>>,
+    header: Vec<String>,
+    /// number of the last line read, starting from 1
+    line: usize,
+}
+
+/// A line of values, in the order of the columns of the header
+pub(crate) struct Record {
+    line: usize,
+    values: Vec<String>,
+}
+
+impl<R: Read> CsvReader<R> {
+    /// Read the header, errors with [`Error::InvalidCsv`] if there is none
+    pub(crate) fn new(reader: R) -> Result<Self, Error> {
+        let mut lines = BufReader::new(reader).lines();
+        let header = lines.next().ok_or(Error::InvalidCsv {
+            line: 1,
+            message: "missing header".into(),
+        })?;
+        let header = header?.split(',').map(|c| c.trim().to_string()).collect();
+        Ok(CsvReader {
+            lines,
+            header,
+            line: 1,
+        })
+    }
+
+    /// Index of the column `name`, errors with [`Error::MissingColumn`] if not in the header
+    pub(crate) fn column(&self, name: &str) -> Result<usize, Error> {
+        self.header
+            .iter()
+            .position(|c| c == name)
+            .ok_or_else(|| Error::MissingColumn(name.to_string()))
+    }
+
+    /// The next non empty line, with as many values as the columns
+    pub(crate) fn next_record(&mut self) -> Option<Result<Record, Error>> {
+        for line in &mut self.lines {
+            self.line += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(Error::Io(e))),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let values: Vec<String> = line.split(',').map(|v| v.trim().to_string()).collect();
+            if values.len() != self.header.len() {
+                return Some(Err(Error::InvalidCsv {
+                    line: self.line,
+                    message: format!(
+                        "expected {} values, found {}",
+                        self.header.len(),
+                        values.len()
+                    ),
+                }));
+            }
+            return Some(Ok(Record {
+                line: self.line,
+                values,
+            }));
+        }
+        None
+    }
+}
+
+impl Record {
+    /// The value of the column at `index` named `name`, errors with [`Error::InvalidCsv`] if it
+    /// can't be parsed
+    pub(crate) fn parse<T: FromStr>(&self, index: usize, name: &str) -> Result<T, Error> {
+        let value = self.get(index);
+        value.parse().map_err(|_| self.invalid(name, value))
+    }
+
+    pub(crate) fn get(&self, index: usize) -> &str {
+        &self.values[index]
+    }
+
+    /// The error of the invalid `value` of the column `name`
+    pub(crate) fn invalid(&self, name: &str, value: &str) -> Error {
+        Error::InvalidCsv {
+            line: self.line,
+            message: format!("invalid {} {:?}", name, value),
+        }
+    }
+}
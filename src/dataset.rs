@@ -0,0 +1,227 @@
+//! Read the per-block features the models are trained on, to compare them with the
+//! [`ModelInput`] computed by the crate, see [`read_csv`], and the windows of fee rates replayed
+//! to evaluate the estimates, see [`read_replay_csv`]
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+#[cfg(feature = "backtest")]
+use crate::backtest::BacktestRow;
+
+use crate::csv::CsvReader;
+use crate::model_input::MAX_BUCKETS;
+use crate::{Error, ModelInput};
+
+/// A row of the training data
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetRow {
+    /// unix time in seconds of the transaction seen in the mempool
+    pub timestamp: i64,
+    pub confirms_in: f32,
+    /// 0 is monday
+    pub day_of_week: f32,
+    pub hour: f32,
+    /// seconds elapsed since the last block
+    pub delta_last: f32,
+    /// number of transactions in every fee bucket, the columns `b0..bN`, unused buckets are zero
+    pub buckets: [f32; MAX_BUCKETS],
+    /// fee rate in sat/vB the transaction paid to confirm in `confirms_in` blocks
+    pub observed_fee_rate: f32,
+}
+
+impl DatasetRow {
+    /// The input of [`crate::ModelData::norm_predict_input`], use [`ModelInput::to_map`] for
+    /// [`crate::ModelData::norm_predict`]
+    pub fn to_input(&self) -> ModelInput {
+        ModelInput {
+            confirms_in: self.confirms_in,
+            day_of_week: self.day_of_week,
+            hour: self.hour,
+            delta_last: self.delta_last,
+            buckets: self.buckets,
+        }
+    }
+}
+
+/// The fee rates of the transactions of the last blocks at a time, with the fee rate that was
+/// required to confirm within every target
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayWindow {
+    /// unix time in seconds of the estimate
+    pub timestamp: i64,
+    /// unix time in seconds of the last block before `timestamp`
+    pub last_block_ts: i64,
+    /// fee rates in sat/vB of the transactions of the last blocks
+    pub fee_rates: Vec<f64>,
+    /// the target and the lowest fee rate in sat/vB of the transactions confirmed within it
+    pub required_fee_rates: Vec<(u16, f64)>,
+}
+
+impl ReplayWindow {
+    /// A row of [`crate::backtest::run_backtest`] for every target of the window, in order
+    #[cfg(feature = "backtest")]
+    pub fn backtest_rows(&self) -> impl Iterator<Item = BacktestRow> + '_ {
+        self.required_fee_rates
+            .iter()
+            .map(move |(target, required_fee_rate)| BacktestRow {
+                timestamp: self.timestamp,
+                last_block_ts: self.last_block_ts,
+                fee_rates: self.fee_rates.clone(),
+                target: *target,
+                required_fee_rate: *required_fee_rate,
+            })
+    }
+}
+
+/// Read the CSV at `path` with header `timestamp,confirms_in,day_of_week,hour,delta_last,b0,..,
+/// b15,observed_fee_rate`, the columns may be in any order and unknown columns are ignored.
+/// The number of buckets is the one of the greatest bucket column, up to [`MAX_BUCKETS`].
+/// Errors with [`Error::MissingColumn`] if a column is missing and with [`Error::InvalidCsv`] if a
+/// value is not a number.
+pub fn read_csv<P: AsRef<Path>>(path: P) -> Result<Vec<DatasetRow>, Error> {
+    let mut csv = CsvReader::new(File::open(path)?)?;
+    let timestamp = csv.column("timestamp")?;
+    let confirms_in = csv.column("confirms_in")?;
+    let day_of_week = csv.column("day_of_week")?;
+    let hour = csv.column("hour")?;
+    let delta_last = csv.column("delta_last")?;
+    let names = ModelInput::feature_names(MAX_BUCKETS);
+    let bucket_count = names[4..]
+        .iter()
+        .rposition(|name| csv.column(name).is_ok())
+        .map_or(0, |last| last + 1);
+    let buckets = names[4..4 + bucket_count]
+        .iter()
+        .map(|name| Ok((csv.column(name)?, name.as_str())))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let observed_fee_rate = csv.column("observed_fee_rate")?;
+    let mut rows = vec![];
+    while let Some(record) = csv.next_record() {
+        let record = record?;
+        let mut row = DatasetRow {
+            timestamp: record.parse(timestamp, "timestamp")?,
+            confirms_in: record.parse(confirms_in, "confirms_in")?,
+            day_of_week: record.parse(day_of_week, "day_of_week")?,
+            hour: record.parse(hour, "hour")?,
+            delta_last: record.parse(delta_last, "delta_last")?,
+            buckets: [0.0; MAX_BUCKETS],
+            observed_fee_rate: record.parse(observed_fee_rate, "observed_fee_rate")?,
+        };
+        for (bucket, (index, name)) in row.buckets.iter_mut().zip(buckets.iter()) {
+            *bucket = record.parse(*index, name)?;
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Read the windows of a CSV with the required fee rate of every target of `targets`, with
+/// header `timestamp,last_block_ts,fee_rates,required_1,required_3` and so on, where `fee_rates`
+/// are separated by spaces. The columns may be in any order and unknown columns are ignored.
+/// Errors with [`Error::MissingColumn`] if a column is missing and with [`Error::InvalidCsv`] if a
+/// value is not a number.
+pub fn read_replay_csv<R: Read>(reader: R, targets: &[u16]) -> Result<Vec<ReplayWindow>, Error> {
+    let mut csv = CsvReader::new(reader)?;
+    let timestamp = csv.column("timestamp")?;
+    let last_block_ts = csv.column("last_block_ts")?;
+    let fee_rates = csv.column("fee_rates")?;
+    let required = targets
+        .iter()
+        .map(|target| {
+            let name = format!("required_{}", target);
+            Ok((*target, csv.column(&name)?, name))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut windows = vec![];
+    while let Some(record) = csv.next_record() {
+        let record = record?;
+        let rates = record
+            .get(fee_rates)
+            .split_whitespace()
+            .map(|rate| rate.parse().map_err(|_| record.invalid("fee_rates", rate)))
+            .collect::<Result<_, _>>()?;
+        let required_fee_rates = required
+            .iter()
+            .map(|(target, index, name)| Ok((*target, record.parse(*index, name)?)))
+            .collect::<Result<_, Error>>()?;
+        windows.push(ReplayWindow {
+            timestamp: record.parse(timestamp, "timestamp")?,
+            last_block_ts: record.parse(last_block_ts, "last_block_ts")?,
+            fee_rates: rates,
+            required_fee_rates,
+        });
+    }
+    Ok(windows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::dataset::{read_csv, read_replay_csv};
+    use crate::tests::{assert_approx_eq, get_fee_model};
+    use crate::Error;
+
+    #[test]
+    fn test_read_csv() {
+        // computed by `FeeModel::estimate_detailed`, the targets 1 and 2 with the low model
+        let golden = [41.93682, 20.38756, -1.9973259, 11.526333];
+        let rows = read_csv("models/fixtures/dataset.csv").unwrap();
+        assert_eq!(rows.len(), golden.len());
+        assert_eq!(rows[0].timestamp, 1613708045);
+        assert_eq!(rows[0].delta_last, 300.0);
+        assert_eq!(rows[0].buckets[13], 291.0);
+        assert_eq!(rows[1].observed_fee_rate, 5.5);
+        let fee_model = get_fee_model();
+        for (row, expected) in rows.iter().zip(golden.iter()) {
+            let model = &fee_model.model_for(row.confirms_in as u16).1[0];
+            let input = row.to_input();
+            assert_approx_eq(model.norm_predict_input(&input).unwrap(), *expected);
+            assert_approx_eq(model.norm_predict(&input.to_map()).unwrap(), *expected);
+        }
+
+        let path = std::env::temp_dir().join("bitcoin-fee-model-dataset.csv");
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(file, "timestamp,confirms_in,day_of_week,hour,b0").unwrap();
+        let err = read_csv(&path).unwrap_err();
+        assert_eq!(err.to_string(), "Missing csv column delta_last");
+        let header = "timestamp,confirms_in,day_of_week,hour,delta_last,b0,b1,b2,b3,b4,b5,b6,b7,\
+b8,b9,b10,b11,b12,b13,b14,b15,observed_fee_rate";
+        let header_no_b7 = header.replace("b7,", "");
+        std::fs::write(&path, header_no_b7).unwrap();
+        let err = read_csv(&path).unwrap_err();
+        assert!(matches!(err, Error::MissingColumn(c) if c == "b7"));
+        let row = "1,1,0,0,60,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,1.5x,1";
+        std::fs::write(&path, format!("{}\n{}\n", header, row)).unwrap();
+        let err = read_csv(&path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid csv at line 2: invalid b15 \"1.5x\""
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(read_csv(&path).unwrap_err(), Error::Io(_)));
+    }
+
+    #[test]
+    fn test_read_replay_csv() {
+        let csv = "timestamp,fee_rates,last_block_ts,required_6,required_1\n\
+                   100,1 2.5 3,50,2.0,4.5\n\
+                   700,,650,1.0,1.5\n";
+        let windows = read_replay_csv(csv.as_bytes(), &[1, 6]).unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!((windows[0].timestamp, windows[0].last_block_ts), (100, 50));
+        assert_eq!(windows[0].fee_rates, vec![1.0, 2.5, 3.0]);
+        assert_eq!(windows[0].required_fee_rates, vec![(1, 4.5), (6, 2.0)]);
+        assert!(windows[1].fee_rates.is_empty());
+
+        let missing = read_replay_csv(csv.as_bytes(), &[2]).unwrap_err();
+        assert!(matches!(missing, Error::MissingColumn(c) if c == "required_2"));
+        let invalid = csv.replace("2.5", "2.5x");
+        let err = read_replay_csv(invalid.as_bytes(), &[1]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid csv at line 2: invalid fee_rates \"2.5x\""
+        );
+    }
+}
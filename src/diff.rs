@@ -0,0 +1,323 @@
+//! Comparison of two models before replacing one with the other, see [`ModelData::diff`] and
+//! [`FeeModel::prediction_diff`]
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model_data::Kernel;
+use crate::{Error, FeatureOverrides, FeeModel, InputBuckets, ModelData};
+
+/// The differences between two models, see [`ModelData::diff`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelDiff {
+    /// the fields of the other model missing in this one
+    pub added_fields: Vec<String>,
+    /// the fields of this model missing in the other one
+    pub removed_fields: Vec<String>,
+    /// the models have the same fields in another order
+    pub reordered_fields: bool,
+    /// the dense layers from the input to the output
+    pub layers: Vec<LayerDiff>,
+}
+
+impl ModelDiff {
+    /// The models have the same fields in the same order and layers of the same shapes with the
+    /// same activations, only their weights may differ
+    pub fn is_structurally_identical(&self) -> bool {
+        self.added_fields.is_empty()
+            && self.removed_fields.is_empty()
+            && !self.reordered_fields
+            && self
+                .layers
+                .iter()
+                .all(|l| l.shape == l.other_shape && l.activation == l.other_activation)
+    }
+}
+
+/// The differences between the layers at the same depth of two models
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LayerDiff {
+    /// `(input_dim, output_dim)` of the layer of this model, see [`crate::LayerInfo`]
+    pub shape: (usize, usize),
+    pub other_shape: (usize, usize),
+    /// named like in the `activations` of the serialized models
+    pub activation: String,
+    pub other_activation: String,
+    /// L2 norm of the difference of the kernels, dequantized, `None` unless the models are
+    /// structurally identical, see [`ModelDiff::is_structurally_identical`]
+    pub kernel_delta: Option<f32>,
+    /// L2 norm of the difference of the biases, `None` like `kernel_delta`
+    pub bias_delta: Option<f32>,
+}
+
+/// The inputs [`FeeModel::prediction_diff`] and [`crate::snapshot::snapshot`] evaluate the
+/// models on: every target with every buckets at every day, hour and seconds since the last
+/// block, see [`PredictionGrid::points`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PredictionGrid {
+    pub targets: Vec<u16>,
+    /// counts like the ones of [`crate::FeeBuckets::get`], with the number of buckets of the
+    /// models
+    pub buckets: Vec<Vec<u64>>,
+    /// from 0, monday, to 6
+    pub days_of_week: Vec<u8>,
+    /// hours of the day (UTC) from 0 to 23
+    pub hours: Vec<u8>,
+    /// seconds elapsed since the last block
+    pub delta_lasts: Vec<f32>,
+}
+
+/// A point of a [`PredictionGrid`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GridPoint {
+    pub block_target: u16,
+    /// the index of the counts in [`PredictionGrid::buckets`]
+    pub buckets: usize,
+    pub day_of_week: u8,
+    pub hour: u8,
+    pub delta_last: f32,
+}
+
+impl GridPoint {
+    /// The time features of the point
+    pub(crate) fn overrides(&self) -> FeatureOverrides {
+        FeatureOverrides {
+            day_of_week: Some(self.day_of_week),
+            hour: Some(self.hour),
+            delta_last: Some(self.delta_last),
+        }
+    }
+}
+
+impl PredictionGrid {
+    /// Every point of the grid, the buckets varying the slowest, then the days, the hours, the
+    /// seconds since the last block and the targets
+    pub fn points(&self) -> impl Iterator<Item = GridPoint> + '_ {
+        (0..self.buckets.len()).flat_map(move |buckets| {
+            self.days_of_week.iter().flat_map(move |day_of_week| {
+                self.hours.iter().flat_map(move |hour| {
+                    self.delta_lasts.iter().flat_map(move |delta_last| {
+                        self.targets.iter().map(move |block_target| GridPoint {
+                            block_target: *block_target,
+                            buckets,
+                            day_of_week: *day_of_week,
+                            hour: *hour,
+                            delta_last: *delta_last,
+                        })
+                    })
+                })
+            })
+        })
+    }
+}
+
+/// How much the estimates of another model differ for a target, see
+/// [`FeeModel::prediction_diff`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PredictionDelta {
+    pub block_target: u16,
+    /// the greatest `|other - estimate| / estimate` over the grid
+    pub max_relative: f32,
+    /// the mean of the relative differences over the grid
+    pub mean_relative: f32,
+}
+
+impl ModelData {
+    /// The differences between this model and `other`, like a newer version of it: the fields,
+    /// the shapes and activations of the layers and, for structurally identical models, the
+    /// norms of the differences of the weights of every layer, which are all zero for the same
+    /// model. The normalization of the fields is not compared.
+    pub fn diff(&self, other: &ModelData) -> ModelDiff {
+        let missing_in = |fields: &[String], of: &[String]| -> Vec<String> {
+            of.iter().filter(|f| !fields.contains(f)).cloned().collect()
+        };
+        let added_fields = missing_in(&self.fields, &other.fields);
+        let removed_fields = missing_in(&other.fields, &self.fields);
+        let reordered_fields =
+            added_fields.is_empty() && removed_fields.is_empty() && self.fields != other.fields;
+        let layers = self
+            .layers()
+            .zip(other.layers())
+            .map(|(this, that)| LayerDiff {
+                shape: (this.input_dim, this.output_dim),
+                other_shape: (that.input_dim, that.output_dim),
+                activation: this.activation.name(),
+                other_activation: that.activation.name(),
+                kernel_delta: None,
+                bias_delta: None,
+            })
+            .collect();
+        let mut diff = ModelDiff {
+            added_fields,
+            removed_fields,
+            reordered_fields,
+            layers,
+        };
+        if diff.is_structurally_identical() {
+            let pairs = [
+                (&self.weights.l0_kernel, &other.weights.l0_kernel),
+                (&self.weights.l1_kernel, &other.weights.l1_kernel),
+                (&self.weights.l2_kernel, &other.weights.l2_kernel),
+            ];
+            let biases = [
+                (&self.weights.l0_bias, &other.weights.l0_bias),
+                (&self.weights.l1_bias, &other.weights.l1_bias),
+                (&self.weights.l2_bias, &other.weights.l2_bias),
+            ];
+            for (layer, ((this, that), (bias, other_bias))) in
+                diff.layers.iter_mut().zip(pairs.iter().zip(biases.iter()))
+            {
+                layer.kernel_delta = Some(kernel_delta(this, that));
+                let deltas = bias.as_slice().iter().zip(other_bias.as_slice());
+                layer.bias_delta = Some(l2_norm(deltas.map(|(a, b)| a - b)));
+            }
+        }
+        diff
+    }
+}
+
+impl FeeModel {
+    /// Estimate every point of `grid` with this model and `other`, like a candidate replacing
+    /// it, and summarize for every target the differences of the estimates of `other`, relative
+    /// to the ones of this model. The estimates are bounded as configured in the options of
+    /// every model.
+    ///
+    /// The buckets are used like in [`FeeModel::estimate_with_buckets`] and must be the ones of
+    /// both models, the extras of the models using them are zero, errors like the estimates.
+    pub fn prediction_diff(
+        &self,
+        other: &FeeModel,
+        grid: &PredictionGrid,
+    ) -> Result<Vec<PredictionDelta>, Error> {
+        let mut sums = alloc::vec![(0.0f32, 0.0f32); grid.targets.len()];
+        // the targets vary the fastest
+        for (i, point) in grid.points().enumerate() {
+            let (max, sum) = &mut sums[i % grid.targets.len()];
+            let estimate = self.estimate_grid_point(grid, point)?;
+            let other = other.estimate_grid_point(grid, point)?;
+            let relative = (other - estimate).abs() / estimate.abs().max(f32::MIN_POSITIVE);
+            *max = max.max(relative);
+            *sum += relative;
+        }
+        let points = grid.buckets.len() * grid.days_of_week.len() * grid.hours.len();
+        let points = (points * grid.delta_lasts.len()).max(1) as f32;
+        let deltas = grid.targets.iter().zip(sums);
+        Ok(deltas
+            .map(|(block_target, (max, sum))| PredictionDelta {
+                block_target: *block_target,
+                max_relative: max,
+                mean_relative: sum / points,
+            })
+            .collect())
+    }
+
+    /// Like [`FeeModel::estimate_with_buckets`] with the features of `point` of `grid`
+    pub(crate) fn estimate_grid_point(
+        &self,
+        grid: &PredictionGrid,
+        point: GridPoint,
+    ) -> Result<f32, Error> {
+        let fee_buckets = &grid.buckets[point.buckets];
+        let (mut input, _) = self.build_input(0, fee_buckets, 0)?;
+        point.overrides().apply(&mut input)?;
+        let buckets = InputBuckets::new(fee_buckets, None);
+        let estimate = self.predict_target(point.block_target, buckets, &mut input, None)?;
+        Ok(estimate.rate)
+    }
+}
+
+fn kernel_delta(this: &Kernel, other: &Kernel) -> f32 {
+    let deltas = (0..this.height())
+        .flat_map(|i| (0..this.width()).map(move |j| this.get(i, j) - other.get(i, j)));
+    l2_norm(deltas)
+}
+
+fn l2_norm<I: Iterator<Item = f32>>(values: I) -> f32 {
+    let sum_squares: f64 = values.map(|v| v as f64 * v as f64).sum();
+    libm::sqrt(sum_squares) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::diff::PredictionGrid;
+    use crate::model_data::tests::{get_test_model, BUCKETS};
+    use crate::tests::{get_fee_model, get_high_model, get_low_model};
+    use crate::{EstimateOptions, FeeModel, ModelData};
+
+    #[test]
+    fn test_model_diff() {
+        for model in [get_test_model(), get_low_model()].iter() {
+            let diff = model.diff(model);
+            assert!(diff.is_structurally_identical());
+            assert_eq!(diff.layers.len(), 3);
+            for layer in diff.layers.iter() {
+                assert_eq!(layer.kernel_delta, Some(0.0));
+                assert_eq!(layer.bias_delta, Some(0.0));
+            }
+        }
+        let diff = get_low_model().diff(&get_high_model());
+        assert!(diff.is_structurally_identical());
+        assert!(diff.layers.iter().all(|l| l.kernel_delta.unwrap() > 0.0));
+        let quantized = get_low_model().quantize_int8();
+        let diff = get_low_model().diff(&quantized);
+        assert!(diff.layers[0].kernel_delta.unwrap() > 0.0);
+        assert_eq!(diff.layers[0].bias_delta, Some(0.0));
+
+        let load = |name| ModelData::from_reader(std::fs::File::open(name).unwrap()).unwrap();
+        let eight = load("models/fixtures/buckets8.cbor");
+        let thirty_two = load("models/fixtures/buckets32.cbor");
+        let diff = eight.diff(&thirty_two);
+        assert!(!diff.is_structurally_identical());
+        assert_eq!(diff.added_fields.len(), 24);
+        assert_eq!(diff.added_fields[0], "b8");
+        assert!(diff.removed_fields.is_empty());
+        assert_eq!(diff.layers[0].shape, (12, 4));
+        assert_eq!(diff.layers[0].other_shape, (36, 4));
+        assert_eq!(diff.layers[1].shape, diff.layers[1].other_shape);
+        assert!(diff.layers.iter().all(|l| l.kernel_delta.is_none()));
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(json["layers"][0]["other_shape"], serde_json::json!([36, 4]));
+    }
+
+    #[test]
+    fn test_prediction_diff() {
+        let model = get_fee_model();
+        let grid = PredictionGrid {
+            targets: vec![1, 2, 6, 144],
+            buckets: vec![BUCKETS.to_vec(), vec![5; 16]],
+            days_of_week: vec![2],
+            hours: vec![0, 6, 12, 18],
+            delta_lasts: vec![300.0, 1200.0],
+        };
+        let points: Vec<_> = grid.points().collect();
+        assert_eq!(points.len(), 4 * 2 * 4 * 2);
+        assert_eq!(points[1].block_target, 2);
+        assert_eq!(points[4].delta_last, 1200.0);
+        assert_eq!(points[8].hour, 6);
+        assert_eq!(points[32].buckets, 1);
+        let deltas = model.prediction_diff(&model, &grid).unwrap();
+        assert_eq!(deltas.len(), 4);
+        for (delta, target) in deltas.iter().zip(grid.targets.iter()) {
+            assert_eq!(delta.block_target, *target);
+            assert_eq!(delta.max_relative, 0.0);
+            assert_eq!(delta.mean_relative, 0.0);
+        }
+
+        let options = EstimateOptions {
+            min_fee_rate: 30.0,
+            ..Default::default()
+        };
+        let raised = get_fee_model().with_options(options);
+        let deltas = model.prediction_diff(&raised, &grid).unwrap();
+        assert!(deltas.iter().any(|d| d.max_relative > 0.0));
+        assert!(deltas.iter().all(|d| d.mean_relative <= d.max_relative));
+
+        let path = Path::new("models/fixtures/buckets8.cbor");
+        let eight = FeeModel::from_paths(path, path).unwrap();
+        assert!(model.prediction_diff(&eight, &grid).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+use alloc::vec::Vec;
+
+use crate::{Error, ModelData};
+
+/// How the predictions of the members of an ensemble are combined, see
+/// [`crate::FeeModel::ensemble`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Combine {
+    /// the arithmetic mean of the predictions
+    #[default]
+    Mean,
+    /// the middle prediction, or the mean of the two middle ones for an even number of members
+    Median,
+}
+
+impl Combine {
+    /// Combine in double precision, allocating only for the median of more than
+    /// [`STACK_MEMBERS`] outputs
+    pub(crate) fn apply(&self, outputs: &[f32]) -> f32 {
+        let mut stack = [0.0f64; STACK_MEMBERS];
+        if outputs.len() <= STACK_MEMBERS {
+            let stack = &mut stack[..outputs.len()];
+            for (s, o) in stack.iter_mut().zip(outputs) {
+                *s = *o as f64;
+            }
+            return self.apply_mut(stack) as f32;
+        }
+        let mut outputs: Vec<f64> = outputs.iter().map(|o| *o as f64).collect();
+        self.apply_mut(&mut outputs) as f32
+    }
+
+    pub(crate) fn apply_f64(&self, outputs: &[f64]) -> f64 {
+        match self {
+            Combine::Mean => mean(outputs),
+            Combine::Median => self.apply_mut(&mut outputs.to_vec()),
+        }
+    }
+
+    /// Like [`Combine::apply_f64`], sorting `outputs` in place for the median
+    fn apply_mut(&self, outputs: &mut [f64]) -> f64 {
+        match self {
+            Combine::Mean => mean(outputs),
+            Combine::Median => {
+                outputs.sort_unstable_by(|a, b| {
+                    a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal)
+                });
+                let middle = outputs.len() / 2;
+                if outputs.len() % 2 == 0 {
+                    (outputs[middle - 1] + outputs[middle]) / 2.0
+                } else {
+                    outputs[middle]
+                }
+            }
+        }
+    }
+}
+
+fn mean(outputs: &[f64]) -> f64 {
+    outputs.iter().sum::<f64>() / outputs.len() as f64
+}
+
+/// The members of the ensembles combined without allocating, see [`MemberOutputs`]
+pub(crate) const STACK_MEMBERS: usize = 16;
+
+/// The outputs of the members of the ensembles of a prediction, on the stack up to
+/// [`STACK_MEMBERS`] of them, like the two single models of a blended prediction
+#[derive(Debug, Clone)]
+pub(crate) struct MemberOutputs {
+    stack: [f32; STACK_MEMBERS],
+    len: usize,
+    /// every output once there are more than [`STACK_MEMBERS`]
+    heap: Vec<f32>,
+}
+
+impl MemberOutputs {
+    pub(crate) fn new() -> Self {
+        MemberOutputs {
+            stack: [0.0; STACK_MEMBERS],
+            len: 0,
+            heap: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, output: f32) {
+        if self.heap.is_empty() && self.len < STACK_MEMBERS {
+            self.stack[self.len] = output;
+            self.len += 1;
+            return;
+        }
+        if self.heap.is_empty() {
+            self.heap.extend_from_slice(&self.stack[..self.len]);
+        }
+        self.heap.push(output);
+    }
+
+    pub(crate) fn as_slice(&self) -> &[f32] {
+        if self.heap.is_empty() {
+            &self.stack[..self.len]
+        } else {
+            &self.heap
+        }
+    }
+}
+
+/// Check `members` is not empty and every member has the features and the quantiles of the first
+/// one
+pub(crate) fn check_members(members: &[ModelData]) -> Result<(), Error> {
+    let first = members.first().ok_or(Error::EmptyEnsemble)?;
+    for (member, model) in members.iter().enumerate().skip(1) {
+        if model.feature_names() != first.feature_names() || model.quantiles() != first.quantiles()
+        {
+            return Err(Error::EnsembleMismatch(member));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Combine;
+
+    #[test]
+    fn test_combine() {
+        assert_eq!(Combine::Mean.apply(&[1.5]), 1.5);
+        assert_eq!(Combine::Mean.apply(&[1.0, 2.0, 6.0]), 3.0);
+        assert_eq!(Combine::Median.apply(&[6.0, 1.0, 2.0]), 2.0);
+        assert_eq!(Combine::Median.apply(&[6.0, 1.0, 2.0, 3.0]), 2.5);
+        assert_eq!(Combine::Median.apply_f64(&[0.25]), 0.25);
+    }
+}
@@ -1,4 +1,10 @@
-use std::fmt;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
 
 #[derive(Debug)]
 pub enum Error {
@@ -6,6 +12,322 @@ pub enum Error {
     MissingStdData(String),
     UnconnectedBlocks,
     LastTsMissing,
+    Deserialize(serde_cbor::Error),
+    /// Writing the model failed
+    Serialize(serde_cbor::Error),
+    /// The model is bigger than the given [`crate::ModelLimits`]
+    LimitExceeded {
+        limit: &'static str,
+        max: usize,
+        got: usize,
+    },
+    /// A weight of the given layer is NaN or infinite
+    NonFiniteWeight(&'static str),
+    /// A scale of the given int8 quantized layer is not finite and positive
+    InvalidScale(&'static str),
+    /// The row pointers or the column indices of the given sparse layer are not valid
+    InvalidSparseIndices(&'static str),
+    /// The offset or the scale normalizing the field are not finite or the scale is zero, or the
+    /// field has a minimum without maximum
+    InvalidNormalization(String),
+    /// The activation of a layer is unknown, see [`crate::Activation::from_name`]
+    UnsupportedActivation(String),
+    /// The quantiles of the outputs are not distinct values between 0 and 1 excluded, or their
+    /// number doesn't match the outputs
+    InvalidQuantiles,
+    /// The targets of the outputs are not increasing from 1, or the model has also quantiles, see
+    /// [`crate::ModelInfo::output_targets`]
+    InvalidOutputTargets(Vec<u16>),
+    /// The model has no output for the requested quantile
+    MissingQuantile(f32),
+    /// The embedded models can't be decompressed
+    #[cfg(feature = "embedded-models")]
+    Decompress,
+    /// The model has been written with a format newer than [`crate::MODEL_FORMAT_VERSION`]
+    UnsupportedModelVersion(u32),
+    /// The state of a [`crate::BlockTracker`] has been saved with a format newer than
+    /// [`crate::TRACKER_FORMAT_VERSION`]
+    UnsupportedTrackerVersion(u32),
+    DimensionMismatch {
+        layer: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+    /// Loading the model file at the given path failed
+    #[cfg(feature = "std")]
+    ModelFile(PathBuf, Box<Error>),
+    /// The model failed [`crate::ModelData::validate`] with the given issues
+    InvalidModel(Vec<crate::ValidationIssue>),
+    /// The given input of the estimate is NaN or infinite, it's a negative fee rate or a last
+    /// block too far from the time of the estimate, see [`crate::EstimateOptions::max_last_block_age`]
+    InvalidInput(&'static str),
+    /// The prediction of the model is NaN or infinite
+    NonFiniteOutput,
+    /// The member at the given index of an ensemble has different features or quantiles than the
+    /// first
+    EnsembleMismatch(usize),
+    /// An ensemble has no members
+    EmptyEnsemble,
+    /// The number of fee buckets doesn't match the `b0..bN` features of the model
+    WrongBucketCount {
+        expected: usize,
+        got: usize,
+    },
+    /// The features of the `model`, `low`, `high` or `long`, don't match the inputs computed by
+    /// [`crate::FeeModel`]: `missing` are not expected by the model and `extra` are expected but
+    /// not computed, see [`crate::FeeModel::validate_features`]
+    FeatureMismatch {
+        model: &'static str,
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+    /// The `low`, `high` or `long` model declares in its metadata the `expected` [`crate::BucketMode`],
+    /// the fee buckets use `got`
+    BucketModeMismatch {
+        model: &'static str,
+        expected: crate::BucketMode,
+        got: crate::BucketMode,
+    },
+    /// The given model declares in its metadata buckets with other limits than the previous
+    /// models, see [`crate::ModelInfo::bucket_config`]
+    BucketConfigMismatch {
+        model: &'static str,
+        expected: crate::FeeModelConfig,
+        got: crate::FeeModelConfig,
+    },
+    /// The models of a fee model disagree on the given features, like `delta_last` in seconds
+    /// in a model and in minutes in the other, see [`crate::FeeModel::validate_consistency`]
+    ModelMismatch(Vec<crate::ModelConflict>),
+    /// Bucket limits must be finite and strictly increasing
+    InvalidBucketLimits(Vec<f64>),
+    /// The maximum targets of the tiers of [`crate::FeeModel::from_tiers`] must be positive and
+    /// strictly increasing, with at least one tier
+    InvalidTiers(Vec<u16>),
+    /// The block target is 0, or greater than [`crate::FeeModel::max_target`] with
+    /// [`crate::TargetPolicy::Reject`]
+    InvalidTarget(u16),
+    /// The fee rate is lower than the estimate of [`crate::FeeModel::max_target`], see
+    /// [`crate::FeeModel::targets_for_rate`]
+    FeeRateTooLow(f32),
+    /// A required field of an [`crate::EstimateRequest`] has not been set, see
+    /// [`crate::EstimateRequestBuilder::build`]
+    MissingField(&'static str),
+    /// The last block, as old as given, is older than [`crate::EstimateOptions::stale_after`]
+    /// and [`crate::EstimateOptions::reject_stale`] is set
+    StaleChainTip(core::time::Duration),
+    /// The given features of the input are out of the ranges seen in training, see
+    /// [`crate::Strictness::Strict`]
+    OutOfDistribution(Vec<String>),
+    /// [`crate::Rounding::Randomized`] without a generator, see `FeeModel::with_rng` with the
+    /// `rand` feature
+    MissingRng,
+    /// Every fee bucket is empty, a case the models have not been trained on, for example when
+    /// no transaction with a known fee rate is in the last blocks
+    InsufficientData,
+    /// The unix timestamp is out of the supported range
+    InvalidTimestamp(i64),
+    /// Without the `chrono` feature there is no current time and a timestamp is required
+    MissingTimestamp,
+    /// The model uses a feature not available in [`crate::ModelInput`] and not supplied in the
+    /// extras, see [`crate::FeeModel::estimate_with_extras`]
+    MissingExtra(String),
+    /// The `low`, `high` or `long` model is trained on the number of transactions in every bucket and
+    /// can't estimate from buckets weighted by vsize, see [`crate::ModelInfo::weighted_buckets`]
+    UnweightedModel(&'static str),
+    /// The `low`, `high` or `long` model is trained with a fixed bucket limit and has no
+    /// `bucket_limit` feature, see [`crate::BucketScaling::Adaptive`]
+    FixedBucketLimit(&'static str),
+    /// The input of [`crate::ModelData::norm`] has no value for the given field of the model
+    MissingFeature(String),
+    /// The override of the given feature is out of range, see [`crate::FeatureOverrides`]
+    InvalidOverride(&'static str),
+    /// The header of the CSV has no column with the given name
+    MissingColumn(String),
+    /// The CSV is not valid at the given line, starting from 1
+    InvalidCsv {
+        line: usize,
+        message: String,
+    },
+    /// Invalid JSON model
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+    /// Loading the manifest of [`crate::FeeModel::from_directory`] at the given path failed
+    #[cfg(feature = "json")]
+    ManifestFile(PathBuf, Box<Error>),
+    /// The targets of the `model` entry of the manifest start from `min_target`, overlapping the
+    /// ones of the previous entry up to `previous_max`
+    #[cfg(feature = "json")]
+    TierOverlap {
+        model: String,
+        min_target: u16,
+        previous_max: u16,
+    },
+    /// The targets of the `model` entry of the manifest start from `min_target` instead of
+    /// `expected`, leaving the targets in between without a model
+    #[cfg(feature = "json")]
+    TierGap {
+        model: String,
+        min_target: u16,
+        expected: u16,
+    },
+    /// The `min_target` of the `model` entry of the manifest is greater than its `max_target`
+    #[cfg(feature = "json")]
+    EmptyTier {
+        model: String,
+        min_target: u16,
+        max_target: u16,
+    },
+    /// The flat model file is not valid, see [`crate::ModelData::from_mmap`]
+    #[cfg(feature = "mmap")]
+    InvalidFlatModel(&'static str),
+    /// Reading or writing a model in the postcard layout failed
+    #[cfg(feature = "postcard")]
+    Postcard(postcard::Error),
+    /// The model in the postcard layout is not valid, see [`crate::ModelData::from_postcard`]
+    #[cfg(feature = "postcard")]
+    InvalidPostcardModel(&'static str),
+    /// The ONNX model uses an operator not supported by [`crate::ModelData::from_onnx`]
+    #[cfg(feature = "onnx")]
+    UnsupportedOnnxOp(String),
+    /// The ONNX model is not valid or not in the subset supported by
+    /// [`crate::ModelData::from_onnx`]
+    #[cfg(feature = "onnx")]
+    InvalidOnnxModel(&'static str),
+    /// The digest of the model is not the expected one, both are hex encoded
+    #[cfg(feature = "checksum")]
+    ChecksumMismatch {
+        expected: String,
+        actual: String,
+    },
+    /// The string is not a hex encoded SHA-256 digest
+    #[cfg(feature = "checksum")]
+    InvalidChecksum(String),
+    /// The signature of the hex encoded digest is not valid
+    #[cfg(feature = "signature")]
+    InvalidSignature(String),
+    #[cfg(feature = "rpc")]
+    Rpc(bitcoincore_rpc::Error),
+    /// The HTTP request failed or its response is not valid
+    #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+    Http(reqwest::Error),
+    /// The HTTP request returned an error status
+    #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+    HttpStatus {
+        url: String,
+        status: u16,
+    },
+    /// The JSON-RPC call returned an error or an invalid result
+    #[cfg(feature = "rpc-async")]
+    JsonRpc {
+        method: &'static str,
+        message: String,
+    },
+    #[cfg(feature = "zmq")]
+    Zmq(zmq::Error),
+    /// Registering the metrics failed, see `metrics::PrometheusMetrics`
+    #[cfg(feature = "prometheus")]
+    Prometheus(prometheus::Error),
+}
+
+impl Error {
+    /// The name of the variant, like `"InsufficientData"`, to count the errors by kind, see
+    /// [`crate::MetricsSink`]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::MissingMeanData(..) => "MissingMeanData",
+            Error::MissingStdData(..) => "MissingStdData",
+            Error::UnconnectedBlocks => "UnconnectedBlocks",
+            Error::LastTsMissing => "LastTsMissing",
+            Error::Deserialize(..) => "Deserialize",
+            Error::Serialize(..) => "Serialize",
+            Error::LimitExceeded { .. } => "LimitExceeded",
+            Error::NonFiniteWeight(..) => "NonFiniteWeight",
+            Error::InvalidScale(..) => "InvalidScale",
+            Error::InvalidSparseIndices(..) => "InvalidSparseIndices",
+            Error::InvalidNormalization(..) => "InvalidNormalization",
+            Error::UnsupportedActivation(..) => "UnsupportedActivation",
+            Error::InvalidQuantiles => "InvalidQuantiles",
+            Error::InvalidOutputTargets(..) => "InvalidOutputTargets",
+            Error::MissingQuantile(..) => "MissingQuantile",
+            #[cfg(feature = "embedded-models")]
+            Error::Decompress => "Decompress",
+            Error::UnsupportedModelVersion(..) => "UnsupportedModelVersion",
+            Error::UnsupportedTrackerVersion(..) => "UnsupportedTrackerVersion",
+            Error::DimensionMismatch { .. } => "DimensionMismatch",
+            #[cfg(feature = "std")]
+            Error::Io(..) => "Io",
+            #[cfg(feature = "std")]
+            Error::ModelFile(..) => "ModelFile",
+            Error::InvalidModel(..) => "InvalidModel",
+            Error::InvalidInput(..) => "InvalidInput",
+            Error::NonFiniteOutput => "NonFiniteOutput",
+            Error::EnsembleMismatch(..) => "EnsembleMismatch",
+            Error::EmptyEnsemble => "EmptyEnsemble",
+            Error::WrongBucketCount { .. } => "WrongBucketCount",
+            Error::FeatureMismatch { .. } => "FeatureMismatch",
+            Error::BucketModeMismatch { .. } => "BucketModeMismatch",
+            Error::BucketConfigMismatch { .. } => "BucketConfigMismatch",
+            Error::ModelMismatch(..) => "ModelMismatch",
+            Error::InvalidBucketLimits(..) => "InvalidBucketLimits",
+            Error::InvalidTiers(..) => "InvalidTiers",
+            Error::InvalidTarget(..) => "InvalidTarget",
+            Error::FeeRateTooLow(..) => "FeeRateTooLow",
+            Error::MissingField(..) => "MissingField",
+            Error::StaleChainTip(..) => "StaleChainTip",
+            Error::OutOfDistribution(..) => "OutOfDistribution",
+            Error::MissingRng => "MissingRng",
+            Error::InsufficientData => "InsufficientData",
+            Error::InvalidTimestamp(..) => "InvalidTimestamp",
+            Error::MissingTimestamp => "MissingTimestamp",
+            Error::MissingExtra(..) => "MissingExtra",
+            Error::UnweightedModel(..) => "UnweightedModel",
+            Error::FixedBucketLimit(..) => "FixedBucketLimit",
+            Error::MissingFeature(..) => "MissingFeature",
+            Error::InvalidOverride(..) => "InvalidOverride",
+            Error::MissingColumn(..) => "MissingColumn",
+            Error::InvalidCsv { .. } => "InvalidCsv",
+            #[cfg(feature = "json")]
+            Error::Json(..) => "Json",
+            #[cfg(feature = "json")]
+            Error::ManifestFile(..) => "ManifestFile",
+            #[cfg(feature = "json")]
+            Error::TierOverlap { .. } => "TierOverlap",
+            #[cfg(feature = "json")]
+            Error::TierGap { .. } => "TierGap",
+            #[cfg(feature = "json")]
+            Error::EmptyTier { .. } => "EmptyTier",
+            #[cfg(feature = "mmap")]
+            Error::InvalidFlatModel(..) => "InvalidFlatModel",
+            #[cfg(feature = "postcard")]
+            Error::Postcard(..) => "Postcard",
+            #[cfg(feature = "postcard")]
+            Error::InvalidPostcardModel(..) => "InvalidPostcardModel",
+            #[cfg(feature = "onnx")]
+            Error::UnsupportedOnnxOp(..) => "UnsupportedOnnxOp",
+            #[cfg(feature = "onnx")]
+            Error::InvalidOnnxModel(..) => "InvalidOnnxModel",
+            #[cfg(feature = "checksum")]
+            Error::ChecksumMismatch { .. } => "ChecksumMismatch",
+            #[cfg(feature = "checksum")]
+            Error::InvalidChecksum(..) => "InvalidChecksum",
+            #[cfg(feature = "signature")]
+            Error::InvalidSignature(..) => "InvalidSignature",
+            #[cfg(feature = "rpc")]
+            Error::Rpc(..) => "Rpc",
+            #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+            Error::Http(..) => "Http",
+            #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+            Error::HttpStatus { .. } => "HttpStatus",
+            #[cfg(feature = "rpc-async")]
+            Error::JsonRpc { .. } => "JsonRpc",
+            #[cfg(feature = "zmq")]
+            Error::Zmq(..) => "Zmq",
+            #[cfg(feature = "prometheus")]
+            Error::Prometheus(..) => "Prometheus",
+        }
+    }
 }
 
 impl fmt::Display for Error {
@@ -15,8 +337,363 @@ impl fmt::Display for Error {
             Error::MissingStdData(s) => write!(f, "Missing std field {} ", s),
             Error::UnconnectedBlocks => write!(f, "Supplied blocks must be ordered and connected "),
             Error::LastTsMissing => write!(f, "None of the 10 blocks is"),
+            Error::Deserialize(e) => write!(f, "Invalid model data: {}", e),
+            Error::Serialize(e) => write!(f, "Cannot write model data: {}", e),
+            Error::LimitExceeded { limit, max, got } => {
+                write!(f, "Model exceeds {}: maximum {}, found {}", limit, max, got)
+            }
+            Error::NonFiniteWeight(layer) => write!(f, "Layer {} has non finite weights", layer),
+            Error::InvalidScale(layer) => write!(f, "Layer {} has invalid scales", layer),
+            Error::InvalidSparseIndices(layer) => {
+                write!(f, "Layer {} has invalid sparse indices", layer)
+            }
+            Error::InvalidNormalization(s) => write!(f, "Invalid normalization of field {}", s),
+            Error::UnsupportedActivation(s) => write!(f, "Unsupported activation {}", s),
+            Error::InvalidQuantiles => write!(f, "Invalid quantiles of the model outputs"),
+            Error::InvalidOutputTargets(t) => write!(f, "Invalid targets of the outputs {:?}", t),
+            Error::MissingQuantile(q) => write!(f, "The model doesn't predict the quantile {}", q),
+            #[cfg(feature = "embedded-models")]
+            Error::Decompress => write!(f, "Cannot decompress the embedded models"),
+            Error::UnsupportedModelVersion(v) => {
+                write!(f, "Unsupported model format version {}", v)
+            }
+            Error::UnsupportedTrackerVersion(v) => {
+                write!(f, "Unsupported block tracker format version {}", v)
+            }
+            Error::DimensionMismatch {
+                layer,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Layer {} has wrong dimension: expected {}, found {}",
+                layer, expected, got
+            ),
+            #[cfg(feature = "std")]
+            Error::Io(e) => write!(f, "Io error: {}", e),
+            #[cfg(feature = "std")]
+            Error::ModelFile(path, e) => {
+                write!(f, "Cannot load model {}: {}", path.display(), e)
+            }
+            Error::InvalidModel(issues) => {
+                write!(f, "Invalid model: ")?;
+                for (i, issue) in issues.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", issue)?;
+                }
+                Ok(())
+            }
+            Error::InvalidInput("negative fee rate") => {
+                write!(f, "Invalid input fee_rates, they must not be negative")
+            }
+            Error::InvalidInput("last_block_ts") => write!(
+                f,
+                "Invalid input last_block_ts, it must be the unix time in seconds of a recent block"
+            ),
+            Error::InvalidInput(s) => write!(f, "Invalid input {}, it must be finite", s),
+            Error::NonFiniteOutput => write!(f, "The model prediction is not finite"),
+            Error::EnsembleMismatch(i) => {
+                write!(
+                    f,
+                    "Member {} of the ensemble has different features or quantiles",
+                    i
+                )
+            }
+            Error::EmptyEnsemble => write!(f, "The ensemble has no members"),
+            Error::WrongBucketCount { expected, got } => write!(
+                f,
+                "Wrong number of fee buckets: expected {}, found {}",
+                expected, got
+            ),
+            Error::FeatureMismatch {
+                model,
+                missing,
+                extra,
+            } => write!(
+                f,
+                "The features of the {} model don't match the inputs, missing: [{}], extra: [{}]",
+                model,
+                missing.join(", "),
+                extra.join(", ")
+            ),
+            Error::BucketModeMismatch {
+                model,
+                expected,
+                got,
+            } => write!(
+                f,
+                "The {} model expects {:?} buckets, found {:?}",
+                model, expected, got
+            ),
+            Error::BucketConfigMismatch {
+                model,
+                expected,
+                got,
+            } => write!(
+                f,
+                "The {} model declares buckets {}% wide up to {} sat/vB, the previous models {}% \
+                 wide up to {} sat/vB",
+                model,
+                got.bucket_increment_percent,
+                got.bucket_limit,
+                expected.bucket_increment_percent,
+                expected.bucket_limit
+            ),
+            Error::ModelMismatch(conflicts) => {
+                write!(f, "Inconsistent models: ")?;
+                for (i, conflict) in conflicts.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", conflict)?;
+                }
+                Ok(())
+            }
+            Error::InvalidBucketLimits(l) => write!(f, "Invalid bucket limits {:?}", l),
+            Error::InvalidTiers(t) => write!(f, "Invalid tier maximum targets {:?}", t),
+            Error::InvalidTarget(t) => write!(f, "Invalid block target {}", t),
+            Error::FeeRateTooLow(r) => {
+                write!(
+                    f,
+                    "The fee rate {} is lower than the estimate of every target",
+                    r
+                )
+            }
+            Error::MissingField(field) => write!(f, "Missing field {} of the request", field),
+            Error::StaleChainTip(age) => {
+                write!(f, "The last block is stale, {} seconds old", age.as_secs())
+            }
+            Error::OutOfDistribution(fields) => write!(
+                f,
+                "The features {} are out of the ranges seen in training",
+                fields.join(", ")
+            ),
+            Error::MissingRng => write!(f, "Randomized rounding without a random generator"),
+            Error::InsufficientData => write!(f, "No fee rates to estimate from"),
+            Error::InvalidTimestamp(t) => write!(f, "Invalid timestamp {}", t),
+            Error::MissingTimestamp => write!(f, "A timestamp is required without chrono"),
+            Error::MissingExtra(s) => write!(f, "Missing extra feature {}", s),
+            Error::MissingFeature(s) => write!(f, "The input has no value for the feature {}", s),
+            Error::InvalidOverride(s) => write!(f, "The override of {} is out of range", s),
+            Error::UnweightedModel(s) => {
+                write!(
+                    f,
+                    "The {} model is not trained on buckets weighted by vsize",
+                    s
+                )
+            }
+            Error::FixedBucketLimit(s) => {
+                write!(
+                    f,
+                    "The {} model is trained with a fixed bucket limit, it has no bucket_limit feature",
+                    s
+                )
+            }
+            Error::MissingColumn(s) => write!(f, "Missing csv column {}", s),
+            Error::InvalidCsv { line, message } => {
+                write!(f, "Invalid csv at line {}: {}", line, message)
+            }
+            #[cfg(feature = "json")]
+            Error::Json(e) => write!(f, "Invalid json model: {}", e),
+            #[cfg(feature = "json")]
+            Error::ManifestFile(path, e) => {
+                write!(f, "Cannot load manifest {}: {}", path.display(), e)
+            }
+            #[cfg(feature = "json")]
+            Error::TierOverlap {
+                model,
+                min_target,
+                previous_max,
+            } => write!(
+                f,
+                "The targets of model {} from {} overlap the previous ones up to {}",
+                model, min_target, previous_max
+            ),
+            #[cfg(feature = "json")]
+            Error::TierGap {
+                model,
+                min_target,
+                expected,
+            } => write!(
+                f,
+                "The targets of model {} start from {}, expected {}",
+                model, min_target, expected
+            ),
+            #[cfg(feature = "json")]
+            Error::EmptyTier {
+                model,
+                min_target,
+                max_target,
+            } => write!(
+                f,
+                "The targets of model {} from {} to {} are empty",
+                model, min_target, max_target
+            ),
+            #[cfg(feature = "mmap")]
+            Error::InvalidFlatModel(s) => write!(f, "Invalid flat model: {}", s),
+            #[cfg(feature = "postcard")]
+            Error::Postcard(e) => write!(f, "Invalid postcard model: {}", e),
+            #[cfg(feature = "postcard")]
+            Error::InvalidPostcardModel(s) => write!(f, "Invalid postcard model: {}", s),
+            #[cfg(feature = "onnx")]
+            Error::UnsupportedOnnxOp(op) => write!(f, "Unsupported ONNX op {}", op),
+            #[cfg(feature = "onnx")]
+            Error::InvalidOnnxModel(s) => write!(f, "Invalid ONNX model: {}", s),
+            #[cfg(feature = "checksum")]
+            Error::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Checksum mismatch: expected {}, found {}",
+                expected, actual
+            ),
+            #[cfg(feature = "checksum")]
+            Error::InvalidChecksum(s) => write!(f, "Invalid checksum {}", s),
+            #[cfg(feature = "signature")]
+            Error::InvalidSignature(s) => write!(f, "Invalid signature of checksum {}", s),
+            #[cfg(feature = "rpc")]
+            Error::Rpc(e) => write!(f, "Rpc error: {}", e),
+            #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+            Error::Http(e) => write!(f, "Http error: {}", e),
+            #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+            Error::HttpStatus { url, status } => write!(f, "Http status {} from {}", status, url),
+            #[cfg(feature = "rpc-async")]
+            Error::JsonRpc { method, message } => {
+                write!(f, "Json-rpc {} failed: {}", method, message)
+            }
+            #[cfg(feature = "zmq")]
+            Error::Zmq(e) => write!(f, "Zmq error: {}", e),
+            #[cfg(feature = "prometheus")]
+            Error::Prometheus(e) => write!(f, "Prometheus error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Deserialize(e) | Error::Serialize(e) => Some(e),
+            Error::Io(e) => Some(e),
+            Error::ModelFile(_, e) => Some(e.as_ref()),
+            #[cfg(feature = "json")]
+            Error::Json(e) => Some(e),
+            #[cfg(feature = "json")]
+            Error::ManifestFile(_, e) => Some(e.as_ref()),
+            #[cfg(feature = "postcard")]
+            Error::Postcard(e) => Some(e),
+            #[cfg(feature = "rpc")]
+            Error::Rpc(e) => Some(e),
+            #[cfg(any(feature = "esplora", feature = "rpc-async"))]
+            Error::Http(e) => Some(e),
+            #[cfg(feature = "zmq")]
+            Error::Zmq(e) => Some(e),
+            #[cfg(feature = "prometheus")]
+            Error::Prometheus(e) => Some(e),
+            _ => None,
         }
     }
 }
 
-impl std::error::Error for Error {}
+/// Reading a model failed, writing it errors with [`Error::Serialize`]
+impl From<serde_cbor::Error> for Error {
+    fn from(e: serde_cbor::Error) -> Self {
+        Error::Deserialize(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+#[cfg(feature = "rpc")]
+impl From<bitcoincore_rpc::Error> for Error {
+    fn from(e: bitcoincore_rpc::Error) -> Self {
+        Error::Rpc(e)
+    }
+}
+
+#[cfg(any(feature = "esplora", feature = "rpc-async"))]
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+#[cfg(feature = "zmq")]
+impl From<zmq::Error> for Error {
+    fn from(e: zmq::Error) -> Self {
+        Error::Zmq(e)
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl From<prometheus::Error> for Error {
+    fn from(e: prometheus::Error) -> Self {
+        Error::Prometheus(e)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::error::Error as _;
+    use std::path::PathBuf;
+
+    use crate::{Error, ModelData};
+
+    #[test]
+    fn test_error_context() {
+        let err = ModelData::from_slice(&[0xff]).unwrap_err();
+        assert!(matches!(err, Error::Deserialize(_)));
+        assert!(err.to_string().starts_with("Invalid model data: "));
+        assert!(err.source().is_some());
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let err = Error::ModelFile(PathBuf::from("model.cbor"), Box::new(io.into()));
+        assert_eq!(
+            err.to_string(),
+            "Cannot load model model.cbor: Io error: no such file"
+        );
+        let source = err.source().unwrap();
+        assert_eq!(source.to_string(), "Io error: no such file");
+        assert_eq!(source.source().unwrap().to_string(), "no such file");
+
+        let err = Error::DimensionMismatch {
+            layer: "dense/kernel:0",
+            expected: 20,
+            got: 19,
+        };
+        let expected = "Layer dense/kernel:0 has wrong dimension: expected 20, found 19";
+        assert_eq!(err.to_string(), expected);
+        assert!(err.source().is_none());
+        let err = Error::InvalidInput("fee_rates");
+        assert_eq!(
+            err.to_string(),
+            "Invalid input fee_rates, it must be finite"
+        );
+        let err = Error::InvalidInput("negative fee rate");
+        assert_eq!(
+            err.to_string(),
+            "Invalid input fee_rates, they must not be negative"
+        );
+        let err = Error::MissingFeature("hour".into());
+        assert_eq!(
+            err.to_string(),
+            "The input has no value for the feature hour"
+        );
+
+        let cbor: Result<u32, _> = serde_cbor::from_slice(&[]);
+        let err: Error = cbor.unwrap_err().into();
+        assert!(matches!(err, Error::Deserialize(_)));
+    }
+}
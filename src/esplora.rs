@@ -0,0 +1,277 @@
+//! Fee rates of the last blocks from an Esplora compatible HTTP API, like blockstream.info or
+//! mempool.space, for clients without a full node
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::{AsyncFeeSource, Error, FeeWindow, WINDOW};
+
+/// Transactions returned by every page of `GET /block/:hash/txs/:start_index`
+const PAGE_SIZE: usize = 25;
+
+#[derive(Deserialize)]
+struct EsploraBlock {
+    id: String,
+    timestamp: i64,
+    tx_count: usize,
+    previousblockhash: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct EsploraTx {
+    txid: String,
+    vin: Vec<EsploraVin>,
+    weight: u64,
+    fee: u64,
+}
+
+#[derive(Deserialize)]
+struct EsploraVin {
+    txid: String,
+    is_coinbase: bool,
+}
+
+/// Async client of an Esplora API
+#[derive(Debug, Clone)]
+pub struct EsploraClient {
+    base_url: String,
+    client: reqwest::Client,
+    max_retries: u32,
+    backoff: Duration,
+    timeout: Option<Duration>,
+}
+
+impl EsploraClient {
+    /// `base_url` like `https://blockstream.info/api`, without the trailing slash
+    pub fn new(base_url: &str) -> Self {
+        EsploraClient {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            max_retries: 5,
+            backoff: Duration::from_millis(500),
+            timeout: None,
+        }
+    }
+
+    /// Retry requests rate limited (429) or failed with a server error up to `max_retries`
+    /// times, waiting `backoff` the first time and doubling it at every retry, unless the server
+    /// asks for a different wait with `Retry-After`
+    pub fn with_retries(self, max_retries: u32, backoff: Duration) -> Self {
+        EsploraClient {
+            max_retries,
+            backoff,
+            ..self
+        }
+    }
+
+    /// Fail every request taking longer than `timeout`, from its start to the end of the body,
+    /// with [`Error::Http`]. Requests have no timeout by default.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        EsploraClient {
+            timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Fetch the [`WINDOW`] blocks ending at the current tip and return the fee rates of their
+    /// transactions with every input in the window, ready for [`crate::FeeModel::estimate`], with
+    /// the timestamp of the tip. Coinbases and transactions spending outputs created before the
+    /// window are skipped.
+    pub async fn fetch_fee_rates(&self) -> Result<(Vec<f64>, i64), Error> {
+        let tip = self.get("/blocks/tip/hash").await?.text().await;
+        let tip = tip.map_err(Error::Http)?;
+        self.fetch_fee_rates_at(tip.trim()).await
+    }
+
+    /// Like [`EsploraClient::fetch_fee_rates`] for the window ending at block `tip`
+    pub async fn fetch_fee_rates_at(&self, tip: &str) -> Result<(Vec<f64>, i64), Error> {
+        let mut txs = vec![];
+        let mut hash = Some(tip.to_string());
+        let mut tip_time = None;
+        for _ in 0..WINDOW {
+            let block: EsploraBlock = match &hash {
+                Some(hash) => self.get_json(&format!("/block/{}", hash)).await?,
+                None => break,
+            };
+            tip_time.get_or_insert(block.timestamp);
+            for start in (0..block.tx_count).step_by(PAGE_SIZE) {
+                let path = format!("/block/{}/txs/{}", block.id, start);
+                let page: Vec<EsploraTx> = self.get_json(&path).await?;
+                txs.extend(page);
+            }
+            hash = block.previousblockhash;
+        }
+
+        let window: HashSet<&str> = txs.iter().map(|tx| tx.txid.as_str()).collect();
+        let fee_rates = txs
+            .iter()
+            .filter(|tx| {
+                tx.vin
+                    .iter()
+                    .all(|vin| !vin.is_coinbase && window.contains(vin.txid.as_str()))
+            })
+            .map(|tx| tx.fee as f64 / (tx.weight as f64 / 4.0))
+            .collect();
+        Ok((fee_rates, tip_time.unwrap_or_default()))
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        self.get(path).await?.json().await.map_err(Error::Http)
+    }
+
+    async fn get(&self, path: &str) -> Result<Response, Error> {
+        let url = format!("{}{}", self.base_url, path);
+        let mut backoff = self.backoff;
+        let mut retries = 0;
+        loop {
+            let mut request = self.client.get(&url);
+            if let Some(timeout) = self.timeout {
+                request = request.timeout(timeout);
+            }
+            let response = request.send().await.map_err(Error::Http)?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            let retry = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+            if !retry || retries >= self.max_retries {
+                return Err(Error::HttpStatus {
+                    url,
+                    status: status.as_u16(),
+                });
+            }
+            let wait = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(backoff);
+            tokio::time::sleep(wait).await;
+            retries += 1;
+            backoff *= 2;
+        }
+    }
+}
+
+/// The window ending at the current tip, see [`EsploraClient::fetch_fee_rates`]
+impl AsyncFeeSource for EsploraClient {
+    async fn fee_window(&self) -> Result<FeeWindow, Error> {
+        let (fee_rates, last_block_ts) = self.fetch_fee_rates().await?;
+        Ok(FeeWindow {
+            fee_rates,
+            last_block_ts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EsploraClient;
+    use crate::{AsyncFeeSource, Error};
+    use serde_json::json;
+    use std::time::Duration;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn tx(txid: &str, inputs: &[&str], fee: u64) -> serde_json::Value {
+        let vin: Vec<_> = inputs
+            .iter()
+            .map(|i| json!({"txid": i, "vout": 0, "is_coinbase": *i == "coinbase"}))
+            .collect();
+        json!({"txid": txid, "vin": vin, "weight": 800, "fee": fee})
+    }
+
+    async fn mount(server: &MockServer, p: &str, body: serde_json::Value) {
+        Mock::given(method("GET"))
+            .and(path(p))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_fetch_fee_rates() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/blocks/tip/hash"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("b1\n"))
+            .mount(&server)
+            .await;
+        let block = |id: &str, prev: Option<&str>, tx_count: usize| json!({"id": id, "timestamp": 1613708045, "tx_count": tx_count, "previousblockhash": prev});
+        mount(&server, "/block/b1", block("b1", Some("b0"), 27)).await;
+        mount(&server, "/block/b0", block("b0", None, 2)).await;
+
+        // the second page of the tip block spends outputs of both blocks
+        let mut first_page = vec![tx("c1", &["coinbase"], 0)];
+        first_page.extend((1..25).map(|i| tx(&format!("t{}", i), &["c0"], 200)));
+        mount(&server, "/block/b1/txs/0", json!(first_page)).await;
+        let second_page = json!([tx("s1", &["t1", "a0"], 400), tx("s2", &["t1", "out"], 400)]);
+        mount(&server, "/block/b1/txs/25", second_page).await;
+        let oldest = json!([tx("c0", &["coinbase"], 0), tx("a0", &["out"], 100)]);
+        mount(&server, "/block/b0/txs/0", oldest).await;
+
+        let client = EsploraClient::new(&server.uri());
+        let (rates, ts) = client.fetch_fee_rates().await.unwrap();
+        assert_eq!(ts, 1613708045);
+        // coinbases and transactions with a prevout outside the window are skipped
+        assert_eq!(rates.len(), 25);
+        assert_eq!(rates.iter().filter(|r| **r == 1.0).count(), 24);
+        assert_eq!(rates.iter().filter(|r| **r == 2.0).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/block/b0"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(2)
+            .mount(&server)
+            .await;
+        let block = json!({"id": "b0", "timestamp": 1, "tx_count": 0, "previousblockhash": null});
+        mount(&server, "/block/b0", block).await;
+
+        let client = EsploraClient::new(&server.uri()).with_retries(2, Duration::from_millis(1));
+        let (rates, ts) = client.fetch_fee_rates_at("b0").await.unwrap();
+        assert!(rates.is_empty());
+        assert_eq!(ts, 1);
+
+        let client = EsploraClient::new(&server.uri()).with_retries(0, Duration::from_millis(1));
+        let err = client.fetch_fee_rates_at("missing").await.unwrap_err();
+        assert!(matches!(err, Error::HttpStatus { status: 404, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_malformed() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/blocks/tip/hash"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("slow"))
+            .mount(&server)
+            .await;
+        let slow = ResponseTemplate::new(200)
+            .set_body_json(json!({}))
+            .set_delay(Duration::from_secs(5));
+        Mock::given(method("GET"))
+            .and(path("/block/slow"))
+            .respond_with(slow)
+            .mount(&server)
+            .await;
+        mount(&server, "/block/b0", json!({"id": "b0", "timestamp": "1"})).await;
+
+        let client = EsploraClient::new(&server.uri()).with_timeout(Duration::from_millis(50));
+        let err = client.fee_window().await.unwrap_err();
+        assert!(
+            matches!(&err, Error::Http(e) if e.is_timeout()),
+            "{:?}",
+            err
+        );
+        let err = client.fetch_fee_rates_at("b0").await.unwrap_err();
+        assert!(matches!(&err, Error::Http(e) if e.is_decode()), "{:?}", err);
+    }
+}
@@ -0,0 +1,269 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use serde::Serialize;
+
+use crate::{Error, FeeRate, ModelInput, Network};
+
+/// Which of the models produced an estimate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelKind {
+    /// the model trained for the targets up to [`crate::FeeModel::dispatch_threshold`], 1 and 2
+    /// blocks for the embedded models
+    Low,
+    /// the model trained for the greater targets, from 3 blocks for the embedded models
+    High,
+    /// a model trained for targets longer than the ones of the high model, see
+    /// [`crate::FeeModel::from_tiers`]
+    Long,
+    /// both models, interpolated as configured in [`crate::EstimateOptions::blend`]
+    Blended,
+}
+
+/// An estimate with the context used to compute it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeEstimate {
+    /// the estimated fee rate in sat/vB, bounded and rounded as configured in
+    /// [`crate::EstimateOptions`]
+    pub rate: f32,
+    /// `rate` before [`crate::EstimateOptions::rounding`], equal to it without rounding
+    pub unrounded_rate: f32,
+    pub model: ModelKind,
+    /// the greatest target of the low model, see [`crate::FeeModel::dispatch_threshold`]
+    pub dispatch_threshold: u16,
+    pub block_target: u16,
+    /// the target the models estimated, `block_target` bounded to
+    /// [`crate::FeeModel::max_target`] as configured in [`crate::EstimateOptions::target_policy`]
+    pub effective_target: u16,
+    /// the network of the models, see [`crate::FeeModel::for_network`]
+    pub network: Network,
+    /// the features fed to the model, before normalization
+    pub inputs: ModelInput,
+    /// the output of the network, combining the outputs of the members of an ensemble
+    pub raw_output: f32,
+    /// the outputs of every member of the ensemble, the ones at the `from` target first when
+    /// blended, see [`crate::FeeModel::ensemble`]
+    pub member_outputs: Vec<f32>,
+    /// whether `raw_output` was out of the configured bounds and `rate` differs from it
+    pub clamped: bool,
+    /// whether the seconds since the last block were out of the configured bounds and
+    /// `inputs.delta_last` differs from them
+    pub delta_last_clamped: bool,
+    /// whether some of the bucket counts were higher than
+    /// [`crate::EstimateOptions::max_bucket_count`] and have been lowered to it in
+    /// `inputs.buckets`
+    pub buckets_capped: bool,
+    /// the fee rates dropped by [`crate::EstimateOptions::filter_fee_rates`] before bucketing
+    pub filtered_fee_rates: usize,
+    /// the fee rates given to the estimate, the filtered ones too, the items consumed by
+    /// [`crate::FeeModel::estimate_iter_detailed`]
+    pub fee_rates_consumed: usize,
+    /// the transactions dropped as counted more than once, see
+    /// [`crate::FeeModel::estimate_detailed_from_txs`], zero for the other estimates
+    pub duplicate_txs: usize,
+    /// whether the last block is older than [`crate::EstimateOptions::stale_after`]
+    pub staleness: Staleness,
+    /// A cheap proxy of the uncertainty of `rate`, the disagreement of the predictions around
+    /// it relative to it, computed only with [`crate::EstimateOptions::uncertainty_hint`]: the
+    /// greatest of
+    ///
+    /// * the local slope `|estimate(N - 1) - estimate(N + 1)| / 2` at the target `N`, one sided
+    ///   at the first and at the last target
+    /// * for the targets at a boundary between two models, the last target of a model and the
+    ///   first of the next, like 2 and 3 for the low and the high model, the difference of the
+    ///   estimates of the two models for the target
+    ///
+    /// divided by `rate`, zero for equal predictions. The estimates are bounded like `rate`.
+    pub uncertainty_hint: Option<f32>,
+    /// the fields of the inputs out of the [`crate::ModelInfo::feature_ranges`] of the models
+    /// used, checked only with [`crate::Strictness::Warn`]
+    pub out_of_range_features: Vec<String>,
+    /// the blocks covered by the fee rates of a degraded estimate of
+    /// [`crate::FeeModel::estimate_partial_detailed`], fewer than [`crate::WINDOW`], `None`
+    /// for the estimates from a whole window
+    pub blocks_covered: Option<u8>,
+}
+
+/// How old the last block of an estimate is, see [`crate::EstimateOptions::stale_after`]. A
+/// stale estimate extrapolates from a long time without blocks, which more likely means the
+/// node is behind than an actual congestion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Staleness {
+    Fresh,
+    /// the time elapsed since the last block
+    Stale {
+        age: Duration,
+    },
+}
+
+/// The estimates of both the low and the high model for the same input, see
+/// [`crate::FeeModel::estimate_both`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BothEstimates {
+    /// the estimate of the low model in sat/vB, bounded like [`FeeEstimate::rate`]
+    pub low: f32,
+    /// the estimate of the high model in sat/vB, bounded like [`FeeEstimate::rate`]
+    pub high: f32,
+    /// the estimate of [`crate::FeeModel::estimate`], of the model chosen for the target
+    pub rate: f32,
+    /// the model chosen for the target
+    pub model: ModelKind,
+}
+
+/// The fee rates of the transactions of a block, see [`crate::FeeModel::estimate_from_blocks`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockFeeRates {
+    /// how many blocks this one is below the last block, 0 for the last block
+    pub height_offset: u8,
+    /// fee rates in sat/vB
+    pub rates: Vec<f64>,
+}
+
+/// Values replacing the time features derived from the timestamps, see
+/// [`crate::FeeModel::estimate_with_features`]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FeatureOverrides {
+    /// from 0, monday, to 6
+    pub day_of_week: Option<u8>,
+    /// hour of the day (UTC) from 0 to 23
+    pub hour: Option<u8>,
+    /// seconds elapsed since the last block, used as given without the bounds of
+    /// [`crate::EstimateOptions::max_delta_last`] and [`crate::EstimateOptions::min_delta_last`]
+    pub delta_last: Option<f32>,
+}
+
+impl FeatureOverrides {
+    /// Replace the features of `input` with the overrides, errors with
+    /// [`Error::InvalidOverride`] if one is out of range
+    pub(crate) fn apply(&self, input: &mut ModelInput) -> Result<(), Error> {
+        if let Some(day_of_week) = self.day_of_week {
+            if day_of_week > 6 {
+                return Err(Error::InvalidOverride("day_of_week"));
+            }
+            input.day_of_week = day_of_week as f32;
+        }
+        if let Some(hour) = self.hour {
+            if hour > 23 {
+                return Err(Error::InvalidOverride("hour"));
+            }
+            input.hour = hour as f32;
+        }
+        if let Some(delta_last) = self.delta_last {
+            if !delta_last.is_finite() || delta_last < 0.0 {
+                return Err(Error::InvalidOverride("delta_last"));
+            }
+            input.delta_last = delta_last;
+        }
+        Ok(())
+    }
+}
+
+/// An estimate shaped like the result of the `estimatesmartfee` RPC of Bitcoin Core, see
+/// [`crate::FeeModel::estimate_smart`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SmartFeeResult {
+    /// fee rate in BTC/kvB, rounded to 8 decimals and at least 0.00000001 for a positive rate
+    pub feerate: f64,
+    /// the target of the estimate
+    pub blocks: u16,
+}
+
+impl SmartFeeResult {
+    pub(crate) fn new(rate: FeeRate, blocks: u16) -> Self {
+        // 1 sat/vB is 1000 sat/kvB, rounded to the satoshi like the 8 decimals of a BTC amount
+        let sat_per_kvb = libm::round(rate.as_sat_per_vb() * 1000.0);
+        // rates below 0.0005 sat/vB keep the smallest amount instead of becoming free
+        let sat_per_kvb = if rate > FeeRate::ZERO {
+            sat_per_kvb.max(1.0)
+        } else {
+            sat_per_kvb
+        };
+        SmartFeeResult {
+            feerate: sat_per_kvb / 100_000_000.0,
+            blocks,
+        }
+    }
+}
+
+/// The values computed by a model for an input, see [`crate::ModelData::predict_trace`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredictTrace {
+    /// the input of the network, every field normalized in the order of the model fields
+    pub normalized: Vec<f32>,
+    /// for every layer, its outputs before the activation
+    pub pre_activations: Vec<Vec<f32>>,
+    /// for every layer, its outputs after the activation, the input of the next one
+    pub activations: Vec<Vec<f32>>,
+    /// the outputs of the network with the output transform inverted, rearranged like
+    /// [`crate::ModelData::predict_outputs`]
+    pub outputs: Vec<f32>,
+    /// the prediction returned by [`crate::ModelData::norm_predict_input`]
+    pub prediction: f32,
+}
+
+/// A row of [`crate::FeeModel::estimate_batch`], with the arguments of
+/// [`crate::FeeModel::estimate_with_buckets`]
+#[cfg(feature = "rayon")]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchInput<'a> {
+    pub block_target: u16,
+    /// unix time in seconds of the estimate
+    pub timestamp: i64,
+    /// fee buckets computed with [`crate::FeeBuckets::get`]
+    pub fee_buckets: &'a [u64],
+    /// unix time in seconds of the last block
+    pub last_block_ts: i64,
+}
+
+/// Lower `estimates` so that they are non increasing when ordered by the corresponding `targets`
+pub(crate) fn clamp_monotonic(targets: &[u16], estimates: &mut [f32]) {
+    let mut order: Vec<usize> = (0..targets.len()).collect();
+    order.sort_by_key(|i| targets[*i]);
+    let mut min = f32::INFINITY;
+    for i in order {
+        min = min.min(estimates[i]);
+        estimates[i] = min;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::estimate::{clamp_monotonic, SmartFeeResult};
+    use crate::FeeRate;
+
+    #[test]
+    fn test_clamp_monotonic() {
+        let targets = [1u16, 3, 2, 6, 12];
+        let mut estimates = [10.0f32, 9.0, 5.0, 7.0, 1.0];
+        clamp_monotonic(&targets, &mut estimates);
+        assert_eq!(estimates, [10.0, 5.0, 5.0, 5.0, 1.0]);
+
+        let mut empty: [f32; 0] = [];
+        clamp_monotonic(&[], &mut empty);
+    }
+
+    #[test]
+    fn test_smart_fee_result() {
+        let result = |sat_per_vb: f64| SmartFeeResult::new(FeeRate::from_sat_per_vb(sat_per_vb), 6);
+        assert_eq!(result(1.0).feerate, 0.00001);
+        assert_eq!(result(25.5).feerate, 0.000255);
+        assert_eq!(result(100_000.0).feerate, 1.0);
+        // rounded to the satoshi per kvB
+        assert_eq!(result(12.3454).feerate, 0.00012345);
+        assert_eq!(result(12.3456).feerate, 0.00012346);
+        // fractional rates of the sub 1 sat/vB relay fees
+        assert_eq!(result(0.25).feerate, 0.0000025);
+        assert_eq!(result(0.1).feerate, 0.000001);
+        assert_eq!(result(0.0004).feerate, 0.00000001);
+        assert_eq!(result(0.0).feerate, 0.0);
+        // estimates are f32
+        assert_eq!(result(14.3f32 as f64).feerate, 0.000143);
+
+        let json = serde_json::to_value(result(25.5)).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "feerate": 0.000255, "blocks": 6 })
+        );
+    }
+}
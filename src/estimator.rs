@@ -0,0 +1,151 @@
+//! A common interface of the fee estimators, to swap or chain them, see [`FeeEstimator`]
+
+use crate::{BlockTracker, Error, FeeModel, FeeRate};
+
+/// Estimates the fee rate to confirm within a target, keeping itself the data it estimates from,
+/// like the last blocks or a connection to a node
+pub trait FeeEstimator {
+    /// The fee rate to confirm within `block_target` blocks
+    fn estimate(&self, block_target: u16) -> Result<FeeRate, Error>;
+}
+
+/// A [`FeeModel`] estimating at the current time from the blocks of a [`BlockTracker`], without
+/// the `chrono` feature it errors with [`Error::MissingTimestamp`]
+#[derive(Debug, Clone)]
+pub struct TrackedFeeModel {
+    model: FeeModel,
+    tracker: BlockTracker,
+}
+
+impl TrackedFeeModel {
+    pub fn new(model: FeeModel, tracker: BlockTracker) -> Self {
+        TrackedFeeModel { model, tracker }
+    }
+
+    pub fn model(&self) -> &FeeModel {
+        &self.model
+    }
+
+    pub fn tracker(&self) -> &BlockTracker {
+        &self.tracker
+    }
+
+    /// The tracker, to push the new blocks
+    pub fn tracker_mut(&mut self) -> &mut BlockTracker {
+        &mut self.tracker
+    }
+}
+
+/// Errors with [`Error::InsufficientData`] while the tracker is empty
+impl FeeEstimator for TrackedFeeModel {
+    fn estimate(&self, block_target: u16) -> Result<FeeRate, Error> {
+        let rate = self.tracker.estimate(&self.model, block_target, None)?;
+        Ok(FeeRate::from(rate))
+    }
+}
+
+/// Estimates with `primary` and, if it errors for any reason, like [`Error::InsufficientData`]
+/// after startup, with `fallback`. Errors with the error of `fallback` if both fail.
+#[derive(Debug, Clone)]
+pub struct Fallback<A, B> {
+    pub primary: A,
+    pub fallback: B,
+}
+
+impl<A, B> Fallback<A, B> {
+    pub fn new(primary: A, fallback: B) -> Self {
+        Fallback { primary, fallback }
+    }
+}
+
+impl<A: FeeEstimator, B: FeeEstimator> FeeEstimator for Fallback<A, B> {
+    fn estimate(&self, block_target: u16) -> Result<FeeRate, Error> {
+        self.primary
+            .estimate(block_target)
+            .or_else(|_| self.fallback.estimate(block_target))
+    }
+}
+
+impl<T: FeeEstimator + ?Sized> FeeEstimator for &T {
+    fn estimate(&self, block_target: u16) -> Result<FeeRate, Error> {
+        (**self).estimate(block_target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::RefCell;
+
+    use alloc::vec::Vec;
+
+    use crate::estimator::{Fallback, FeeEstimator};
+    use crate::{Error, FeeRate};
+
+    /// Returns `result` and records the calls in `calls`
+    struct Fixed<'a> {
+        name: &'static str,
+        result: Result<f64, fn() -> Error>,
+        calls: &'a RefCell<Vec<&'static str>>,
+    }
+
+    impl FeeEstimator for Fixed<'_> {
+        fn estimate(&self, _block_target: u16) -> Result<FeeRate, Error> {
+            self.calls.borrow_mut().push(self.name);
+            self.result.map(FeeRate::from_sat_per_vb).map_err(|e| e())
+        }
+    }
+
+    #[test]
+    fn test_fallback() {
+        let calls = RefCell::new(Vec::new());
+        let fixed = |name, result: Result<f64, fn() -> Error>| Fixed {
+            name,
+            result,
+            calls: &calls,
+        };
+        let fallback = Fallback::new(fixed("a", Ok(5.0)), fixed("b", Ok(7.0)));
+        assert_eq!(fallback.estimate(6).unwrap(), FeeRate::from_sat_per_vb(5.0));
+        assert_eq!(calls.take(), ["a"]);
+
+        let fallback = Fallback::new(
+            fixed("a", Err(|| Error::InsufficientData)),
+            fixed("b", Ok(7.0)),
+        );
+        assert_eq!(fallback.estimate(6).unwrap(), FeeRate::from_sat_per_vb(7.0));
+        assert_eq!(calls.take(), ["a", "b"]);
+
+        // any error falls back, the error of the fallback is returned
+        let primary = fixed("a", Err(|| Error::MissingTimestamp));
+        let chained = Fallback::new(&primary, fixed("b", Err(|| Error::InsufficientData)));
+        let err = chained.estimate(6).unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+        assert_eq!(calls.take(), ["a", "b"]);
+        let chained = Fallback::new(chained, fixed("c", Ok(9.0)));
+        assert_eq!(chained.estimate(6).unwrap(), FeeRate::from_sat_per_vb(9.0));
+        assert_eq!(calls.take(), ["a", "b", "c"]);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_tracked_fee_model() {
+        use chrono::{TimeZone, Utc};
+
+        use crate::estimator::TrackedFeeModel;
+        use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+        use crate::BlockTracker;
+
+        let ts = SAMPLE_TS;
+        let model = get_fee_model().with_clock(move || Utc.timestamp_opt(ts, 0).unwrap());
+        let mut tracked = TrackedFeeModel::new(model, BlockTracker::new(0));
+        let err = tracked.estimate(6).unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+
+        let rates = sample_rates();
+        for block in rates.chunks(100) {
+            tracked.tracker_mut().push_block(block.to_vec(), ts - 300);
+        }
+        let expected = tracked.model().estimate(6, Some(ts), &rates, ts - 300);
+        let estimate = tracked.estimate(6).unwrap();
+        assert_eq!(estimate, FeeRate::from(expected.unwrap()));
+    }
+}
@@ -1,26 +1,337 @@
-#[derive(Debug)]
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// What the count of every bucket in [`FeeBuckets::get`] contains
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BucketMode {
+    /// the rates in the bucket, the representation of the embedded models
+    #[default]
+    Histogram,
+    /// the rates in the bucket and in the following ones, the transactions paying at least the
+    /// limit of the previous bucket
+    CumulativeFromTop,
+    /// the rates in the bucket and in the previous ones, the transactions paying less than the
+    /// limit of the bucket
+    CumulativeFromBottom,
+}
+
+impl BucketMode {
+    /// Transform the histogram `counts` to this mode
+    fn apply(&self, counts: &mut [u64]) {
+        match self {
+            BucketMode::Histogram => (),
+            BucketMode::CumulativeFromTop => {
+                for i in (1..counts.len()).rev() {
+                    counts[i - 1] = counts[i - 1].saturating_add(counts[i]);
+                }
+            }
+            BucketMode::CumulativeFromBottom => {
+                for i in 1..counts.len() {
+                    counts[i] = counts[i].saturating_add(counts[i - 1]);
+                }
+            }
+        }
+    }
+}
+
+/// How the upper limit of the buckets is chosen, see [`crate::FeeModel::with_bucket_scaling`]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BucketScaling {
+    /// the limit of the configured buckets, like the one the embedded models are trained with
+    #[default]
+    Fixed,
+    /// the `percentile` of the fee rates of the window, from 0 excluded to 100, rounded up to the
+    /// next limit of the buckets of the model increment, so that a spike of high fee rates doesn't
+    /// pile up in the last bucket
+    Adaptive { percentile: f32 },
+}
+
+impl BucketScaling {
+    /// The limit of the buckets of `fee_rates` for buckets every `increment_percent` higher than
+    /// the previous one, `None` for [`BucketScaling::Fixed`]. The percentile is the nearest-rank
+    /// one, like in [`crate::summary_features`], and the limit the first one strictly greater.
+    pub(crate) fn limit(&self, fee_rates: &[f64], increment_percent: u32) -> Option<f64> {
+        let percentile = match self {
+            BucketScaling::Fixed => return None,
+            BucketScaling::Adaptive { percentile } => *percentile as f64,
+        };
+        let mut rates = fee_rates.to_vec();
+        let rate = if rates.is_empty() {
+            0.0
+        } else {
+            // ceil(p / 100 * n), at least the first rate
+            let rank = libm::ceil(percentile / 100.0 * rates.len() as f64) as usize;
+            let index = rank.clamp(1, rates.len()) - 1;
+            *rates.select_nth_unstable_by(index, |a, b| a.total_cmp(b)).1
+        };
+        // like `create_buckets_limits`, the rates of the window are under the limit
+        let ratio = 1.0 + increment_percent.max(1) as f64 / 100.0;
+        let mut limit = ratio;
+        while limit <= rate {
+            limit *= ratio;
+        }
+        Some(limit)
+    }
+}
+
+/// Groups fee rates (sat/vB) in buckets and count how many fall in each of them.
+///
+/// Every bucket is identified by its exclusive upper limit: a rate goes in the first bucket whose
+/// limit is strictly greater than the rate, so a rate exactly equal to a limit is counted in the
+/// following bucket. Rates greater or equal to the last limit, and NaN, are counted in the last
+/// bucket. The counts are then transformed according to the [`BucketMode`], the histogram by
+/// default.
+#[derive(Debug, Clone)]
 pub struct FeeBuckets {
     buckets_limits: Vec<f64>,
+    mode: BucketMode,
 }
 
 impl FeeBuckets {
     pub fn new(increment_percent: u32, upper_limit: f64) -> Self {
         let buckets_limits = create_buckets_limits(increment_percent, upper_limit);
-        FeeBuckets { buckets_limits }
+        FeeBuckets {
+            buckets_limits,
+            mode: BucketMode::Histogram,
+        }
+    }
+
+    /// Create buckets with the given upper limits, which must be finite and strictly increasing
+    pub fn from_limits(buckets_limits: Vec<f64>) -> Result<Self, Error> {
+        if buckets_limits.is_empty()
+            || buckets_limits.iter().any(|l| !l.is_finite())
+            || buckets_limits.windows(2).any(|w| w[0] >= w[1])
+        {
+            return Err(Error::InvalidBucketLimits(buckets_limits));
+        }
+        Ok(FeeBuckets {
+            buckets_limits,
+            mode: BucketMode::Histogram,
+        })
+    }
+
+    /// Use `mode` in [`FeeBuckets::get`] and [`FeeBuckets::get_weighted`]
+    pub fn with_mode(self, mode: BucketMode) -> Self {
+        FeeBuckets { mode, ..self }
+    }
+
+    pub fn mode(&self) -> BucketMode {
+        self.mode
+    }
+
+    /// Create `n_buckets` buckets with geometrically spaced limits, the first bucket contains rates
+    /// lower than `min`, the last one contains rates from the previous limit up to `max` and over
+    pub fn logarithmic(n_buckets: usize, min: f64, max: f64) -> Result<Self, Error> {
+        if n_buckets < 2 || !min.is_finite() || !max.is_finite() || min <= 0.0 || max <= min {
+            return Err(Error::InvalidBucketLimits(vec![min, max]));
+        }
+        let ratio = libm::pow(max / min, 1.0 / (n_buckets - 1) as f64);
+        let mut buckets_limits: Vec<f64> = (0..n_buckets - 1)
+            .map(|i| min * libm::pow(ratio, i as f64))
+            .collect();
+        buckets_limits.push(max);
+        FeeBuckets::from_limits(buckets_limits)
+    }
+
+    /// Create `n_buckets` buckets like [`FeeBuckets::new`], every limit the same percent higher
+    /// than the previous one starting from 1 sat/vB, with the percent chosen so that the last
+    /// limit is `upper_limit`, for models trained with a different number of buckets
+    pub fn with_count(n_buckets: usize, upper_limit: f64) -> Result<Self, Error> {
+        if n_buckets == 0 || !upper_limit.is_finite() || upper_limit <= 1.0 {
+            return Err(Error::InvalidBucketLimits(vec![upper_limit]));
+        }
+        let ratio = libm::pow(upper_limit, 1.0 / n_buckets as f64);
+        let mut buckets_limits: Vec<f64> =
+            (1..n_buckets).map(|i| libm::pow(ratio, i as f64)).collect();
+        buckets_limits.push(upper_limit);
+        FeeBuckets::from_limits(buckets_limits)
+    }
+
+    /// The upper limit of every bucket
+    pub fn limits(&self) -> &[f64] {
+        &self.buckets_limits
+    }
+
+    /// Number of buckets
+    pub fn len(&self) -> usize {
+        self.buckets_limits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets_limits.is_empty()
     }
 
     pub fn get(&self, rates: &[f64]) -> Vec<u64> {
+        self.get_iter(rates.iter().copied())
+    }
+
+    /// Like [`FeeBuckets::get`] consuming the rates of an iterator, like a stream of the
+    /// transactions of the blocks, without collecting them
+    pub fn get_iter<I: IntoIterator<Item = f64>>(&self, rates: I) -> Vec<u64> {
         let mut buckets = vec![0u64; self.buckets_limits.len()];
+        self.count(rates, &mut buckets);
+        buckets
+    }
+
+    /// Like [`FeeBuckets::get`] without allocating, writing the counts to `out` which must have
+    /// [`FeeBuckets::len`] elements, errors with [`Error::WrongBucketCount`] otherwise
+    pub fn get_into(&self, rates: &[f64], out: &mut [u64]) -> Result<(), Error> {
+        if out.len() != self.len() {
+            return Err(Error::WrongBucketCount {
+                expected: self.len(),
+                got: out.len(),
+            });
+        }
+        out.iter_mut().for_each(|count| *count = 0);
+        self.count(rates.iter().copied(), out);
+        Ok(())
+    }
+
+    /// Like [`FeeBuckets::get_into`] returning the counts in an array on the stack, `N` must be
+    /// [`FeeBuckets::len`]
+    pub fn get_array<const N: usize>(&self, rates: &[f64]) -> Result<[u64; N], Error> {
+        let mut counts = [0u64; N];
+        self.get_into(rates, &mut counts)?;
+        Ok(counts)
+    }
+
+    /// Add `rates` to the zeroed `counts`, which must have [`FeeBuckets::len`] elements, and
+    /// transform them to the mode
+    pub(crate) fn count<I: IntoIterator<Item = f64>>(&self, rates: I, counts: &mut [u64]) {
         for rate in rates {
-            let index = self
-                .buckets_limits
-                .iter()
-                .position(|e| e > rate)
-                .unwrap_or(self.buckets_limits.len() - 1);
-            buckets[index] += 1;
+            counts[self.index(rate)] += 1;
+        }
+        self.mode.apply(counts);
+    }
+
+    /// Like [`FeeBuckets::get`] with `(rate, weight)` pairs, every bucket contains the sum of the
+    /// weights of its rates divided by `unit`, rounded to the nearest integer
+    pub fn get_weighted(&self, weighted_rates: &[(f64, u64)], unit: u64) -> Vec<u64> {
+        let mut weights = vec![0u64; self.buckets_limits.len()];
+        for (rate, weight) in weighted_rates {
+            let index = self.index(*rate);
+            weights[index] = weights[index].saturating_add(*weight);
         }
+        let unit = unit.max(1);
+        let mut buckets: Vec<u64> = weights
+            .into_iter()
+            .map(|w| w / unit + u64::from(w % unit >= unit - unit / 2))
+            .collect();
+        self.mode.apply(&mut buckets);
         buckets
     }
+
+    fn index(&self, rate: f64) -> usize {
+        bucket_index(&self.buckets_limits, rate)
+    }
+
+    /// The limits and the edge rules of the buckets as data, see [`BucketSpec`]
+    pub fn spec(&self) -> BucketSpec {
+        let limits = &self.buckets_limits;
+        let last = limits.len() - 1;
+        let ranges = (0..limits.len())
+            .map(|i| BucketRange {
+                lower: i.checked_sub(1).map(|previous| limits[previous]),
+                upper: if i == last { None } else { Some(limits[i]) },
+            })
+            .collect();
+        BucketSpec {
+            limits: limits.clone(),
+            ranges,
+            nan_bucket: last,
+            mode: self.mode,
+        }
+    }
+}
+
+/// The bucket of `rate` among the buckets with the exclusive upper `limits`, the rule of
+/// [`FeeBuckets`] shared with [`BucketSpec::bucketize`]
+fn bucket_index(limits: &[f64], rate: f64) -> usize {
+    limits
+        .iter()
+        .position(|e| *e > rate)
+        .unwrap_or(limits.len() - 1)
+}
+
+/// The rates of a bucket of a [`BucketSpec`], from `lower` included to `upper` excluded
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BucketRange {
+    /// the limit of the previous bucket, `None` for the first bucket which contains every rate
+    /// under its upper limit, zero and negative ones too
+    pub lower: Option<f64>,
+    /// the limit of the bucket, `None` for the last bucket which contains every rate from the
+    /// previous limit, its own limit and the greater rates too
+    pub upper: Option<f64>,
+}
+
+/// The boundaries of [`FeeBuckets`] and their edge rules as data, for example to group the fee
+/// rates in another language or in a database exactly like the estimates do, see
+/// [`crate::FeeModel::bucket_spec`].
+///
+/// A rate equal to a limit is in the following bucket; the rates lower than the first limit are
+/// in the first bucket, the ones greater or equal to the next to last limit and NaN in the last
+/// one. [`BucketSpec::bucketize`] is the function the buckets themselves use.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BucketSpec {
+    /// the exclusive upper limit of every bucket, strictly increasing, like [`FeeBuckets::limits`]
+    pub limits: Vec<f64>,
+    /// the rates of every bucket
+    pub ranges: Vec<BucketRange>,
+    /// the bucket of NaN, the last one
+    pub nan_bucket: usize,
+    /// how the counts of the buckets accumulate, see [`FeeBuckets::get`]
+    pub mode: BucketMode,
+}
+
+impl BucketSpec {
+    /// The bucket of `rate`, the index of its count in [`FeeBuckets::get`] before the counts are
+    /// accumulated according to the mode
+    pub fn bucketize(&self, rate: f64) -> usize {
+        bucket_index(&self.limits, rate)
+    }
+}
+
+/// The counts of [`FeeBuckets::get`] updated one rate at a time, to keep the histogram of a
+/// moving window of blocks without recomputing it at every block
+#[derive(Debug, Clone)]
+pub struct BucketAccumulator {
+    buckets: FeeBuckets,
+    counts: Vec<u64>,
+}
+
+impl BucketAccumulator {
+    /// An empty histogram of `buckets`
+    pub fn new(buckets: FeeBuckets) -> Self {
+        let counts = vec![0; buckets.len()];
+        BucketAccumulator { buckets, counts }
+    }
+
+    pub fn add(&mut self, rate: f64) {
+        self.counts[self.buckets.index(rate)] += 1;
+    }
+
+    /// Remove a rate previously added. The count saturates at zero if the bucket of `rate` is
+    /// empty, a rate never added but in the bucket of other rates can't be detected and removes
+    /// one of them.
+    pub fn remove(&mut self, rate: f64) {
+        let count = &mut self.counts[self.buckets.index(rate)];
+        *count = count.saturating_sub(1);
+    }
+
+    /// The number of rates in every bucket, like [`FeeBuckets::get`] of the rates added and not
+    /// removed with [`BucketMode::Histogram`], whatever the mode of the buckets
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    pub fn buckets(&self) -> &FeeBuckets {
+        &self.buckets
+    }
 }
 
 pub fn create_buckets_limits(increment_percent: u32, upper_limit: f64) -> Vec<f64> {
@@ -36,3 +347,295 @@ pub fn create_buckets_limits(increment_percent: u32, upper_limit: f64) -> Vec<f6
     }
     buckets_limits
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::fee_bucket::create_buckets_limits;
+    use crate::{BucketAccumulator, BucketMode, BucketRange, BucketScaling, Error, FeeBuckets};
+
+    #[test]
+    fn test_default_limits() {
+        let buckets = FeeBuckets::new(50, 500.0);
+        assert_eq!(buckets.len(), 16);
+        assert_eq!(buckets.limits()[0], 1.5);
+        assert!(buckets.limits()[15] > 500.0);
+    }
+
+    #[test]
+    fn test_get() {
+        let buckets = FeeBuckets::from_limits(vec![1.0, 2.0, 4.0]).unwrap();
+        assert_eq!(buckets.get(&[]), vec![0, 0, 0]);
+        assert_eq!(buckets.get(&[0.0, 0.5]), vec![2, 0, 0]);
+        // a rate equal to a limit goes in the following bucket
+        assert_eq!(buckets.get(&[1.0, 2.0]), vec![0, 1, 1]);
+        // above the last limit and NaN go in the last bucket
+        assert_eq!(buckets.get(&[4.0, 1000.0, f64::NAN]), vec![0, 0, 3]);
+    }
+
+    #[test]
+    fn test_bucket_spec() {
+        let buckets = FeeBuckets::from_limits(vec![1.0, 2.0, 4.0]).unwrap();
+        let spec = buckets.spec();
+        assert_eq!(spec.limits, vec![1.0, 2.0, 4.0]);
+        assert_eq!(
+            spec.ranges,
+            vec![
+                BucketRange {
+                    lower: None,
+                    upper: Some(1.0)
+                },
+                BucketRange {
+                    lower: Some(1.0),
+                    upper: Some(2.0)
+                },
+                BucketRange {
+                    lower: Some(2.0),
+                    upper: None
+                },
+            ]
+        );
+        assert_eq!(spec.nan_bucket, 2);
+        assert_eq!(spec.mode, BucketMode::Histogram);
+        let json = serde_json::to_string(&spec).unwrap();
+        assert_eq!(
+            serde_json::from_str::<crate::BucketSpec>(&json).unwrap(),
+            spec
+        );
+
+        // pseudo random rates, the limits, the rates around them and the special values
+        let mut state = 11u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            state >> 33
+        };
+        let all = [
+            buckets,
+            FeeBuckets::new(50, 500.0),
+            FeeBuckets::with_count(32, 500.0).unwrap(),
+            FeeBuckets::from_limits(vec![50.0, 500.0]).unwrap(),
+        ];
+        for buckets in all.iter() {
+            let spec = buckets.spec();
+            let mut rates: Vec<f64> = (0..5000)
+                .map(|_| (next() % 100_000) as f64 / 97.0)
+                .collect();
+            for limit in spec.limits.iter() {
+                rates.extend([*limit, limit - 1e-9, limit + 1e-9].iter());
+            }
+            rates.extend([0.0, -1.0, 50.0, 500.0, f64::NAN, f64::INFINITY, f64::MAX].iter());
+            for rate in rates {
+                let index = spec.bucketize(rate);
+                let mut expected = vec![0; buckets.len()];
+                expected[index] = 1;
+                assert_eq!(buckets.get(&[rate]), expected, "{}", rate);
+
+                // the ranges describe the same buckets
+                let range = spec.ranges[index];
+                if rate.is_nan() {
+                    assert_eq!(index, spec.nan_bucket);
+                } else {
+                    assert!(range.lower.map_or(true, |lower| rate >= lower));
+                    assert!(range.upper.map_or(true, |upper| rate < upper));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bucket_mode() {
+        let limits = vec![1.0, 2.0, 4.0, 8.0];
+        let buckets = FeeBuckets::from_limits(limits).unwrap();
+        assert_eq!(buckets.mode(), BucketMode::Histogram);
+        let rates = [0.5, 1.0, 1.5, 1.5, 3.0, 9.0, 100.0, 2.0];
+        let histogram = buckets.get(&rates);
+        assert_eq!(histogram, vec![1, 3, 2, 2]);
+        let top = buckets.clone().with_mode(BucketMode::CumulativeFromTop);
+        assert_eq!(top.mode(), BucketMode::CumulativeFromTop);
+        let top = top.get(&rates);
+        assert_eq!(top, vec![8, 7, 4, 2]);
+        let bottom = buckets.clone().with_mode(BucketMode::CumulativeFromBottom);
+        let bottom = bottom.get(&rates);
+        assert_eq!(bottom, vec![1, 4, 6, 8]);
+        // the differences of consecutive cumulative counts are the histogram
+        let total = rates.len() as u64;
+        assert_eq!(top[0], total);
+        assert_eq!(bottom[3], total);
+        for i in 0..3 {
+            assert_eq!(top[i] - top[i + 1], histogram[i]);
+            assert_eq!(bottom[i + 1] - bottom[i], histogram[i + 1]);
+        }
+
+        let weighted = [(0.5, 500), (1.5, 250), (5.0, 250)];
+        let top = buckets.with_mode(BucketMode::CumulativeFromTop);
+        assert_eq!(top.get_weighted(&weighted, 250), vec![4, 2, 1, 1]);
+        assert_eq!(top.get(&[]), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_get_into() {
+        let mut state = 7u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            state >> 33
+        };
+        let modes = [
+            BucketMode::Histogram,
+            BucketMode::CumulativeFromTop,
+            BucketMode::CumulativeFromBottom,
+        ];
+        for i in 0..300 {
+            let buckets = FeeBuckets::new(50, 500.0).with_mode(modes[i % modes.len()]);
+            let len = next() as usize % 2000;
+            let mut rates: Vec<f64> = (0..len).map(|_| (next() % 100_000) as f64 / 97.0).collect();
+            // the limits themselves and the special values
+            rates.extend(buckets.limits().iter().take(next() as usize % 17));
+            if i % 10 == 0 {
+                rates.extend([0.0, -1.0, f64::NAN, f64::INFINITY].iter());
+            }
+            let expected = buckets.get(&rates);
+            let mut out = [u64::MAX; 16];
+            buckets.get_into(&rates, &mut out).unwrap();
+            assert_eq!(&out[..], &expected[..]);
+            let array: [u64; 16] = buckets.get_array(&rates).unwrap();
+            assert_eq!(&array[..], &expected[..]);
+            let (first, second) = rates.split_at(len / 2);
+            let chained = first.iter().chain(second.iter()).copied();
+            assert_eq!(buckets.get_iter(chained), expected);
+        }
+
+        let buckets = FeeBuckets::new(50, 500.0);
+        let err = buckets.get_into(&[1.0], &mut [0; 15]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::WrongBucketCount {
+                expected: 16,
+                got: 15
+            }
+        ));
+        assert!(buckets.get_array::<17>(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn test_get_weighted() {
+        let buckets = FeeBuckets::from_limits(vec![1.0, 2.0, 4.0]).unwrap();
+        assert_eq!(buckets.get_weighted(&[], 250), vec![0, 0, 0]);
+        let weighted = [(0.5, 500), (1.5, 124), (1.8, 1), (3.0, 125), (10.0, 1000)];
+        assert_eq!(buckets.get_weighted(&weighted, 250), vec![2, 1, 5]);
+        assert_eq!(buckets.get_weighted(&weighted, 1), vec![500, 125, 1125]);
+        assert_eq!(buckets.get_weighted(&[(1.0, 374)], 250), vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn test_bucket_accumulator() {
+        let buckets = FeeBuckets::new(50, 500.0);
+        let mut accumulator = BucketAccumulator::new(buckets.clone());
+        assert_eq!(accumulator.counts(), buckets.get(&[]).as_slice());
+        // pseudo random adds and removes of the added rates, checked against the surviving ones
+        let mut state = 42u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            state >> 33
+        };
+        let mut added = vec![];
+        for _ in 0..5000 {
+            if added.is_empty() || next() % 3 != 0 {
+                let rate = (next() % 100_000) as f64 / 100.0;
+                accumulator.add(rate);
+                added.push(rate);
+            } else {
+                let rate = added.swap_remove(next() as usize % added.len());
+                accumulator.remove(rate);
+            }
+            assert_eq!(accumulator.counts(), buckets.get(&added).as_slice());
+        }
+
+        // removing from an empty bucket saturates at zero
+        let mut accumulator = BucketAccumulator::new(buckets.clone());
+        accumulator.add(100.0);
+        accumulator.remove(1.0);
+        accumulator.remove(100.0);
+        accumulator.remove(100.0);
+        assert_eq!(accumulator.counts(), buckets.get(&[]).as_slice());
+    }
+
+    #[test]
+    fn test_logarithmic() {
+        let buckets = FeeBuckets::logarithmic(4, 1.0, 1000.0).unwrap();
+        let expected = [1.0, 10.0, 100.0, 1000.0];
+        for (limit, expected) in buckets.limits().iter().zip(expected.iter()) {
+            assert!((limit - expected).abs() < 1e-9, "{} != {}", limit, expected);
+        }
+
+        let buckets = FeeBuckets::logarithmic(16, 1.0, 500.0).unwrap();
+        assert_eq!(buckets.len(), 16);
+        let rates: Vec<f64> = (0..5000).map(|i| i as f64 * 0.13).collect();
+        for rate in rates.iter() {
+            let counts = buckets.get(&[*rate]);
+            assert_eq!(counts.iter().filter(|c| **c == 1).count(), 1);
+            assert_eq!(counts.iter().sum::<u64>(), 1);
+        }
+        let counts = buckets.get(&rates);
+        assert_eq!(counts.iter().sum::<u64>(), rates.len() as u64);
+
+        assert!(FeeBuckets::logarithmic(1, 1.0, 10.0).is_err());
+        assert!(FeeBuckets::logarithmic(4, 0.0, 10.0).is_err());
+        assert!(FeeBuckets::logarithmic(4, 10.0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_with_count() {
+        for n in [1usize, 8, 16, 32].iter() {
+            let buckets = FeeBuckets::with_count(*n, 500.0).unwrap();
+            assert_eq!(buckets.len(), *n);
+            assert_eq!(buckets.limits()[n - 1], 500.0);
+            let ratio = buckets.limits()[0];
+            for w in buckets.limits().windows(2) {
+                assert!((w[1] / w[0] - ratio).abs() < 1e-9);
+            }
+        }
+        assert!(FeeBuckets::with_count(0, 500.0).is_err());
+        assert!(FeeBuckets::with_count(8, 1.0).is_err());
+        assert!(FeeBuckets::with_count(8, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_from_limits() {
+        assert!(FeeBuckets::from_limits(vec![1.0, 2.0]).is_ok());
+        for limits in [
+            vec![],
+            vec![2.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, f64::NAN],
+            vec![1.0, f64::INFINITY],
+        ]
+        .iter()
+        {
+            let err = FeeBuckets::from_limits(limits.clone()).unwrap_err();
+            assert!(matches!(err, Error::InvalidBucketLimits(_)));
+        }
+    }
+
+    #[test]
+    fn test_adaptive_limit() {
+        let limits = create_buckets_limits(50, 500.0);
+        let adaptive = BucketScaling::Adaptive { percentile: 99.0 };
+        assert_eq!(BucketScaling::Fixed.limit(&[1.0, 2.0], 50), None);
+        assert_eq!(adaptive.limit(&[], 50), Some(limits[0]));
+        assert_eq!(adaptive.limit(&[0.0, 0.5], 50), Some(limits[0]));
+        // a rate equal to a limit is under the following one
+        assert_eq!(adaptive.limit(&[limits[3]], 50), Some(limits[4]));
+        assert_eq!(adaptive.limit(&[limits[3] - 0.01], 50), Some(limits[3]));
+
+        let mut rates: Vec<f64> = (1..=99).map(|i| i as f64).collect();
+        rates.push(10_000.0);
+        assert_eq!(adaptive.limit(&rates, 50), Some(limits[11]));
+        let all = BucketScaling::Adaptive { percentile: 100.0 };
+        assert!(all.limit(&rates, 50).unwrap() > 10_000.0);
+    }
+}
@@ -0,0 +1,141 @@
+use core::cmp::Ordering;
+use core::fmt;
+
+/// A fee rate, stored in sat/vB.
+///
+/// A `FeeRate` is never negative nor NaN: constructors clamp those values to zero, and arithmetic
+/// saturates at zero and infinity.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FeeRate(f64);
+
+impl FeeRate {
+    pub const ZERO: FeeRate = FeeRate(0.0);
+
+    pub fn from_sat_per_vb(sat_per_vb: f64) -> FeeRate {
+        if sat_per_vb > 0.0 {
+            FeeRate(sat_per_vb)
+        } else {
+            FeeRate::ZERO
+        }
+    }
+
+    /// 1 vbyte is 4 weight units, so 1 sat/vB is 250 sat/kWU
+    pub fn from_sat_per_kwu(sat_per_kwu: f64) -> FeeRate {
+        FeeRate::from_sat_per_vb(sat_per_kwu / 250.0)
+    }
+
+    /// 1 BTC/kvB is 100_000 sat/vB
+    pub fn from_btc_per_kvb(btc_per_kvb: f64) -> FeeRate {
+        FeeRate::from_sat_per_vb(btc_per_kvb * 100_000.0)
+    }
+
+    pub fn as_sat_per_vb(&self) -> f64 {
+        self.0
+    }
+
+    pub fn as_sat_per_kwu(&self) -> f64 {
+        self.0 * 250.0
+    }
+
+    pub fn as_btc_per_kvb(&self) -> f64 {
+        self.0 / 100_000.0
+    }
+
+    pub fn saturating_add(self, other: FeeRate) -> FeeRate {
+        FeeRate::from_sat_per_vb(self.0 + other.0)
+    }
+
+    /// Subtract `other`, returning zero if it's greater than `self`
+    pub fn saturating_sub(self, other: FeeRate) -> FeeRate {
+        FeeRate::from_sat_per_vb(self.0 - other.0)
+    }
+
+    pub fn saturating_mul(self, factor: f64) -> FeeRate {
+        FeeRate::from_sat_per_vb(self.0 * factor)
+    }
+}
+
+impl From<f32> for FeeRate {
+    /// Convert a rate in sat/vB, like the ones returned by [`crate::FeeModel::estimate`]
+    fn from(sat_per_vb: f32) -> Self {
+        FeeRate::from_sat_per_vb(sat_per_vb as f64)
+    }
+}
+
+impl Eq for FeeRate {}
+
+impl PartialOrd for FeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).expect("FeeRate is never NaN")
+    }
+}
+
+impl fmt::Display for FeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*} sat/vB", precision, self.0),
+            None => write!(f, "{} sat/vB", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FeeRate;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-12, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let rate = FeeRate::from_sat_per_vb(1.0);
+        assert_close(rate.as_sat_per_kwu(), 250.0);
+        assert_close(rate.as_btc_per_kvb(), 0.00001);
+
+        let rate = FeeRate::from_sat_per_vb(25.5);
+        assert_close(rate.as_sat_per_kwu(), 6375.0);
+        assert_close(rate.as_btc_per_kvb(), 0.000255);
+
+        let rate = FeeRate::from_sat_per_vb(0.25);
+        assert_close(rate.as_sat_per_kwu(), 62.5);
+        assert_close(rate.as_btc_per_kvb(), 0.0000025);
+
+        assert_close(FeeRate::from_sat_per_kwu(62.5).as_sat_per_vb(), 0.25);
+        assert_close(FeeRate::from_btc_per_kvb(0.000255).as_sat_per_vb(), 25.5);
+        assert_close(FeeRate::from(0.5f32).as_sat_per_vb(), 0.5);
+    }
+
+    #[test]
+    fn test_clamping_and_arithmetic() {
+        assert_eq!(FeeRate::from_sat_per_vb(-1.0), FeeRate::ZERO);
+        assert_eq!(FeeRate::from_sat_per_vb(f64::NAN), FeeRate::ZERO);
+
+        let one = FeeRate::from_sat_per_vb(1.0);
+        let two = FeeRate::from_sat_per_vb(2.0);
+        assert_eq!(one.saturating_add(one), two);
+        assert_eq!(two.saturating_sub(one), one);
+        assert_eq!(one.saturating_sub(two), FeeRate::ZERO);
+        assert_eq!(one.saturating_mul(2.0), two);
+        assert_eq!(one.saturating_mul(-2.0), FeeRate::ZERO);
+
+        assert!(one < two);
+        assert_eq!(one.max(two), two);
+        let mut rates = vec![two, FeeRate::ZERO, one];
+        rates.sort();
+        assert_eq!(rates, vec![FeeRate::ZERO, one, two]);
+    }
+
+    #[test]
+    fn test_display() {
+        let rate = FeeRate::from_sat_per_vb(12.3456);
+        assert_eq!(rate.to_string(), "12.3456 sat/vB");
+        assert_eq!(format!("{:.2}", rate), "12.35 sat/vB");
+    }
+}
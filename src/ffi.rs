@@ -0,0 +1,218 @@
+//! C bindings, see `ffi/bitcoin_fee_model.h` generated with `cbindgen`.
+//!
+//! Functions returning `int` return [`FEE_MODEL_OK`] or one of the negative error codes, the
+//! description of the last error on the calling thread is returned by
+//! [`fee_model_last_error_message`].
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use std::slice;
+
+use crate::{Error, FeeModel};
+
+pub const FEE_MODEL_OK: c_int = 0;
+/// A required pointer argument is null
+pub const FEE_MODEL_ERR_NULL_POINTER: c_int = -1;
+/// The model data is invalid
+pub const FEE_MODEL_ERR_INVALID_MODEL: c_int = -2;
+/// The fee buckets don't match the ones expected by the models
+pub const FEE_MODEL_ERR_WRONG_BUCKET_COUNT: c_int = -3;
+/// There are no fee rates to estimate from
+pub const FEE_MODEL_ERR_INSUFFICIENT_DATA: c_int = -4;
+/// A timestamp is out of the supported range
+pub const FEE_MODEL_ERR_INVALID_TIMESTAMP: c_int = -5;
+/// Any other error
+pub const FEE_MODEL_ERR_OTHER: c_int = -6;
+/// A fee rate is NaN or infinite
+pub const FEE_MODEL_ERR_INVALID_INPUT: c_int = -7;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+fn error_code(error: Error) -> c_int {
+    let code = match &error {
+        Error::MissingMeanData(_)
+        | Error::MissingStdData(_)
+        | Error::Deserialize(_)
+        | Error::Decompress
+        | Error::UnsupportedModelVersion(_)
+        | Error::LimitExceeded { .. }
+        | Error::NonFiniteWeight(_)
+        | Error::InvalidScale(_)
+        | Error::InvalidSparseIndices(_)
+        | Error::InvalidNormalization(_)
+        | Error::UnsupportedActivation(_)
+        | Error::InvalidQuantiles
+        | Error::FeatureMismatch { .. }
+        | Error::BucketModeMismatch { .. }
+        | Error::BucketConfigMismatch { .. }
+        | Error::ModelMismatch(_)
+        | Error::InvalidTiers(_)
+        | Error::DimensionMismatch { .. } => FEE_MODEL_ERR_INVALID_MODEL,
+        #[cfg(feature = "mmap")]
+        Error::InvalidFlatModel(_) => FEE_MODEL_ERR_INVALID_MODEL,
+        Error::WrongBucketCount { .. } => FEE_MODEL_ERR_WRONG_BUCKET_COUNT,
+        Error::InsufficientData => FEE_MODEL_ERR_INSUFFICIENT_DATA,
+        Error::InvalidTimestamp(_) => FEE_MODEL_ERR_INVALID_TIMESTAMP,
+        Error::InvalidInput(_) | Error::InvalidOverride(_) | Error::InvalidTarget(_) => {
+            FEE_MODEL_ERR_INVALID_INPUT
+        }
+        _ => FEE_MODEL_ERR_OTHER,
+    };
+    set_last_error(error.to_string());
+    code
+}
+
+fn into_raw(model: Result<FeeModel, Error>) -> *mut FeeModel {
+    match model {
+        Ok(model) => Box::into_raw(Box::new(model)),
+        Err(e) => {
+            error_code(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Create the fee model using the models embedded in the library, to be released with
+/// [`fee_model_free`]. Returns null on error.
+#[no_mangle]
+pub extern "C" fn fee_model_new() -> *mut FeeModel {
+    into_raw(FeeModel::new())
+}
+
+/// Create the fee model from the CBOR encoded `low` and `high` models, to be released with
+/// [`fee_model_free`]. Returns null on error.
+///
+/// # Safety
+///
+/// `low` and `high` must point to `low_len` and `high_len` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn fee_model_from_bytes(
+    low: *const u8,
+    low_len: usize,
+    high: *const u8,
+    high_len: usize,
+) -> *mut FeeModel {
+    if low.is_null() || high.is_null() {
+        set_last_error("null model bytes".to_string());
+        return ptr::null_mut();
+    }
+    let low = slice::from_raw_parts(low, low_len);
+    let high = slice::from_raw_parts(high, high_len);
+    into_raw(FeeModel::from_bytes(low, high))
+}
+
+/// Estimate the fee rate in sat/vB for `block_target` like `FeeModel::estimate`, writing it in
+/// `out_rate`. `timestamp` and `last_block_ts` are unix times in seconds.
+///
+/// # Safety
+///
+/// `model` must be a pointer returned by this library and not yet freed, `fee_rates` must point
+/// to `fee_rates_len` values, or may be null if `fee_rates_len` is zero, `out_rate` must be
+/// writable
+#[no_mangle]
+pub unsafe extern "C" fn fee_model_estimate(
+    model: *const FeeModel,
+    block_target: u16,
+    timestamp: i64,
+    fee_rates: *const f64,
+    fee_rates_len: usize,
+    last_block_ts: i64,
+    out_rate: *mut f32,
+) -> c_int {
+    if model.is_null() || out_rate.is_null() || (fee_rates.is_null() && fee_rates_len > 0) {
+        set_last_error("null pointer argument".to_string());
+        return FEE_MODEL_ERR_NULL_POINTER;
+    }
+    let fee_rates = if fee_rates_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(fee_rates, fee_rates_len)
+    };
+    match (*model).estimate(block_target, Some(timestamp), fee_rates, last_block_ts) {
+        Ok(rate) => {
+            *out_rate = rate;
+            FEE_MODEL_OK
+        }
+        Err(e) => error_code(e),
+    }
+}
+
+/// Release a model created by this library, null is ignored
+///
+/// # Safety
+///
+/// `model` must be a pointer returned by this library and not yet freed
+#[no_mangle]
+pub unsafe extern "C" fn fee_model_free(model: *mut FeeModel) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+/// The description of the last error on the calling thread, null if no error happened. The
+/// string is valid until the next call failing on the same thread and must not be freed.
+#[no_mangle]
+pub extern "C" fn fee_model_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |m| m.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::*;
+    use crate::tests::{sample_rates, SAMPLE_TS};
+    use std::ffi::CStr;
+
+    #[test]
+    fn test_ffi() {
+        let low = include_bytes!("../models/20211027-180849/model.cbor");
+        let high = include_bytes!("../models/20211027-180925/model.cbor");
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        unsafe {
+            let model = fee_model_from_bytes(low.as_ptr(), low.len(), high.as_ptr(), high.len());
+            assert!(!model.is_null());
+            let mut rate = 0.0f32;
+            let code = fee_model_estimate(
+                model,
+                6,
+                ts,
+                rates.as_ptr(),
+                rates.len(),
+                ts - 300,
+                &mut rate,
+            );
+            assert_eq!(code, FEE_MODEL_OK);
+            let expected = (*model).estimate(6, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(rate, expected);
+
+            let code = fee_model_estimate(model, 6, ts, ptr::null(), 0, ts - 300, &mut rate);
+            assert_eq!(code, FEE_MODEL_ERR_INSUFFICIENT_DATA);
+            let message = CStr::from_ptr(fee_model_last_error_message());
+            assert_eq!(message.to_str().unwrap(), "No fee rates to estimate from");
+
+            let nan = [f64::NAN];
+            let code = fee_model_estimate(model, 6, ts, nan.as_ptr(), 1, ts - 300, &mut rate);
+            assert_eq!(code, FEE_MODEL_ERR_INVALID_INPUT);
+
+            let code = fee_model_estimate(model, 6, ts, rates.as_ptr(), 1, ts, ptr::null_mut());
+            assert_eq!(code, FEE_MODEL_ERR_NULL_POINTER);
+            fee_model_free(model);
+            fee_model_free(ptr::null_mut());
+
+            let model = fee_model_from_bytes(low.as_ptr(), 100, high.as_ptr(), high.len());
+            assert!(model.is_null());
+            assert!(!fee_model_last_error_message().is_null());
+        }
+        let mismatch = Error::ModelMismatch(Vec::new());
+        assert_eq!(error_code(mismatch), FEE_MODEL_ERR_INVALID_MODEL);
+    }
+}
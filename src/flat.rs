@@ -0,0 +1,269 @@
+//! Flat model layout that can be memory mapped and used without copying the weights.
+//!
+//! The file starts with the magic `BFEE`, the little endian u32 `0x01020304` used to detect the
+//! endianness, the u32 version of the layout and the u32 length of a CBOR header with the
+//! normalization data, the fields, alpha, the activations, the output transform, the quantiles,
+//! the metadata and the widths of the layers. Version 1 has no activations and version 2 no
+//! output transform, they default like in the CBOR models without them. After zero padding to a
+//! multiple of 4 bytes follow the little endian f32 of the kernels and the biases of the three
+//! layers, kernels in row-major order.
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+
+use crate::matrix::Matrix;
+use crate::model_data::{check_finite, check_len, FieldsDescribe, Kernel, Weights};
+use crate::{Activation, OutputTransform};
+use crate::{Error, ModelData, ModelInfo, ModelLimits};
+
+const MAGIC: &[u8; 4] = b"BFEE";
+const ENDIANNESS: u32 = 0x0102_0304;
+const FLAT_VERSION: u32 = 3;
+/// magic, endianness, version and header length
+const PREAMBLE_LEN: usize = 16;
+
+#[derive(Serialize)]
+struct HeaderRef<'a> {
+    norm: &'a FieldsDescribe,
+    fields: &'a [String],
+    alpha: f32,
+    activations: Vec<String>,
+    output_transform: OutputTransform,
+    #[serde(skip_serializing_if = "<[f32]>::is_empty")]
+    quantiles: &'a [f32],
+    metadata: &'a ModelInfo,
+    /// inputs, then the outputs of every layer
+    widths: [usize; 4],
+}
+
+#[derive(Deserialize)]
+struct Header {
+    norm: FieldsDescribe,
+    fields: Vec<String>,
+    alpha: f32,
+    #[serde(default)]
+    activations: Option<Vec<String>>,
+    #[serde(default)]
+    output_transform: OutputTransform,
+    #[serde(default)]
+    quantiles: Vec<f32>,
+    #[serde(default)]
+    metadata: ModelInfo,
+    widths: [usize; 4],
+}
+
+fn padding(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    let mut buf = [0u8; 4];
+    buf.copy_from_slice(&bytes[offset..offset + 4]);
+    u32::from_ne_bytes(buf)
+}
+
+fn io(e: std::io::Error) -> Error {
+    Error::Io(e)
+}
+
+impl ModelData {
+    /// Load a model in the flat layout written by [`ModelData::to_flat_writer`], mapping the
+    /// file in memory and using the weights without copying them. Predictions are the same of
+    /// the model loaded from CBOR. Errors with [`Error::InvalidFlatModel`] if the magic or the
+    /// endianness don't match, big endian targets are not supported.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the model is alive
+    pub unsafe fn from_mmap<P: AsRef<Path>>(path: P) -> Result<ModelData, Error> {
+        let file = File::open(path).map_err(io)?;
+        let map = Arc::new(Mmap::map(&file).map_err(io)?);
+        ModelData::from_flat(map, &ModelLimits::default())
+    }
+
+    fn from_flat(map: Arc<Mmap>, limits: &ModelLimits) -> Result<ModelData, Error> {
+        let invalid = Error::InvalidFlatModel;
+        if map.len() < PREAMBLE_LEN || &map[..4] != MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        if read_u32(&map, 4) != ENDIANNESS {
+            return Err(invalid("bad endianness"));
+        }
+        if !(1..=FLAT_VERSION).contains(&read_u32(&map, 8)) {
+            return Err(invalid("unsupported version"));
+        }
+        let header_len = read_u32(&map, 12) as usize;
+        limits.check("max_bytes", limits.max_bytes, header_len)?;
+        let header_end = PREAMBLE_LEN + header_len;
+        let header = map.get(PREAMBLE_LEN..header_end);
+        let header = header.ok_or(invalid("truncated header"))?;
+        let header: Header = serde_cbor::from_slice(header).map_err(Error::Deserialize)?;
+        limits.check_widths(&header.widths)?;
+        let [inputs, n0, n1, o] = header.widths;
+        check_len("dense/kernel:0", header.fields.len(), inputs)?;
+
+        let mut offset = header_end + padding(header_end);
+        let mut next = |layer: &'static str, width: usize, height: usize| {
+            let matrix = Matrix::from_mapped(map.clone(), offset, width, height);
+            let matrix = matrix.ok_or(invalid("truncated weights"))?;
+            check_finite(layer, matrix.as_slice())?;
+            offset += width * height * 4;
+            Ok::<_, Error>(matrix)
+        };
+        let weights = Weights {
+            l0_kernel: Kernel::F32(next("dense/kernel:0", n0, inputs)?),
+            l0_bias: next("dense/bias:0", n0, 1)?,
+            l1_kernel: Kernel::F32(next("dense_1/kernel:0", n1, n0)?),
+            l1_bias: next("dense_1/bias:0", n1, 1)?,
+            l2_kernel: Kernel::F32(next("dense_2/kernel:0", o, n1)?),
+            l2_bias: next("dense_2/bias:0", o, 1)?,
+        };
+        if offset != map.len() {
+            return Err(invalid("trailing bytes"));
+        }
+        ModelData::from_parts(
+            header.norm,
+            header.fields,
+            header.alpha,
+            header.activations,
+            header.output_transform,
+            header.quantiles,
+            header.metadata,
+            weights,
+        )
+    }
+
+    /// Write the model in the flat layout loadable with [`ModelData::from_mmap`], int8 kernels are
+    /// written dequantized, giving the same predictions
+    pub fn to_flat_writer<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let w = &self.weights;
+        let header = HeaderRef {
+            norm: &self.norm,
+            fields: &self.fields,
+            alpha: self.alpha,
+            activations: self.activations.iter().map(Activation::name).collect(),
+            output_transform: self.output_transform,
+            quantiles: self.quantiles(),
+            metadata: &self.info,
+            widths: [
+                self.fields.len(),
+                w.l0_bias.width(),
+                w.l1_bias.width(),
+                w.l2_bias.width(),
+            ],
+        };
+        let header = serde_cbor::to_vec(&header).map_err(Error::Serialize)?;
+        let mut bytes = Vec::with_capacity(PREAMBLE_LEN + header.len() + 3);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&ENDIANNESS.to_le_bytes());
+        bytes.extend_from_slice(&FLAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&header);
+        bytes.resize(bytes.len() + padding(bytes.len()), 0);
+        let layers = [
+            (&w.l0_kernel, &w.l0_bias),
+            (&w.l1_kernel, &w.l1_bias),
+            (&w.l2_kernel, &w.l2_bias),
+        ];
+        for (kernel, bias) in layers.iter() {
+            for i in 0..kernel.height() {
+                for j in 0..kernel.width() {
+                    bytes.extend_from_slice(&kernel.get(i, j).to_le_bytes());
+                }
+            }
+            for value in bias.as_slice() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        writer.write_all(&bytes).map_err(io)
+    }
+}
+
+/// Convert a CBOR model, like the ones in the `models` dir, to the flat layout loadable with
+/// [`ModelData::from_mmap`]
+pub fn cbor_to_flat<R: Read, W: Write>(cbor: R, flat: W) -> Result<(), Error> {
+    ModelData::from_reader(cbor)?.to_flat_writer(flat)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model_data::tests::{assert_same_predictions, MODELS};
+    use crate::{cbor_to_flat, Activation, Error, ModelData};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn flat_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bfee-flat-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_from_mmap() {
+        for (i, bytes) in MODELS.iter().enumerate() {
+            let cbor = ModelData::from_slice(bytes).unwrap();
+            let mut flat = vec![];
+            cbor_to_flat(*bytes, &mut flat).unwrap();
+            let path = flat_file(&format!("model{}.bfee", i), &flat);
+            let mapped = unsafe { ModelData::from_mmap(&path) }.unwrap();
+            assert_eq!(mapped.fields, cbor.fields);
+            assert_eq!(mapped.info, cbor.info);
+            assert_same_predictions(&cbor, &mapped);
+
+            let mut again = vec![];
+            mapped.to_flat_writer(&mut again).unwrap();
+            assert_eq!(again, flat);
+
+            let quantized = cbor.quantize_int8();
+            let mut flat = vec![];
+            quantized.to_flat_writer(&mut flat).unwrap();
+            let path = flat_file(&format!("quantized{}.bfee", i), &flat);
+            let mapped = unsafe { ModelData::from_mmap(&path) }.unwrap();
+            assert_same_predictions(&quantized, &mapped);
+
+            let mut gelu = cbor;
+            gelu.activations[1] = Activation::Gelu;
+            let mut flat = vec![];
+            gelu.to_flat_writer(&mut flat).unwrap();
+            let path = flat_file(&format!("gelu{}.bfee", i), &flat);
+            let mapped = unsafe { ModelData::from_mmap(&path) }.unwrap();
+            assert_eq!(mapped.activations, gelu.activations);
+            assert_same_predictions(&gelu, &mapped);
+        }
+
+        let mut flat = vec![];
+        cbor_to_flat(MODELS[0], &mut flat).unwrap();
+        let load = |name: &str, bytes: &[u8]| {
+            let path = flat_file(name, bytes);
+            unsafe { ModelData::from_mmap(path) }.unwrap_err()
+        };
+        let mut bad = flat.clone();
+        bad[0] = b'X';
+        let err = load("magic.bfee", &bad);
+        assert!(matches!(err, Error::InvalidFlatModel("bad magic")));
+        let mut bad = flat.clone();
+        bad[4..8].copy_from_slice(&0x0102_0304u32.to_be_bytes());
+        let err = load("endianness.bfee", &bad);
+        assert!(matches!(err, Error::InvalidFlatModel("bad endianness")));
+        let err = load("truncated.bfee", &flat[..flat.len() - 4]);
+        assert!(matches!(err, Error::InvalidFlatModel("truncated weights")));
+        let mut bad = flat.clone();
+        bad.push(0);
+        let err = load("trailing.bfee", &bad);
+        assert!(matches!(err, Error::InvalidFlatModel("trailing bytes")));
+        let mut bad = flat.clone();
+        let last = bad.len() - 4;
+        bad[last..].copy_from_slice(&f32::NAN.to_le_bytes());
+        let err = load("nan.bfee", &bad);
+        assert!(matches!(err, Error::NonFiniteWeight("dense_2/bias:0")));
+    }
+}
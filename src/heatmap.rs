@@ -0,0 +1,132 @@
+//! The estimates of a window of fee rates over a grid of targets, hours and days of the week, to
+//! plot them offline, see [`FeeModel::grid`]
+
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{rebucket_rates, Error, FeatureOverrides, FeeModel, InputBuckets};
+
+/// The estimates of [`FeeModel::grid`] with the labels of the axes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridResult {
+    pub targets: Vec<u16>,
+    /// hours of the day (UTC) from 0 to 23
+    pub hours: Vec<u8>,
+    /// from 0, monday, to 6
+    pub weekdays: Vec<u8>,
+    /// the estimates in sat/vB, row-major: the weekdays varying the slowest, then the targets
+    /// and the hours, a row per weekday and target, see [`GridResult::rate`]
+    pub rates: Vec<f32>,
+}
+
+impl GridResult {
+    /// The estimate of `target` at `hour` of `weekday`, `None` if they are not in the axes
+    pub fn rate(&self, target: u16, hour: u8, weekday: u8) -> Option<f32> {
+        let t = self.targets.iter().position(|v| *v == target)?;
+        let h = self.hours.iter().position(|v| *v == hour)?;
+        let w = self.weekdays.iter().position(|v| *v == weekday)?;
+        let row = w * self.targets.len() + t;
+        self.rates.get(row * self.hours.len() + h).copied()
+    }
+}
+
+impl FeeModel {
+    /// Estimate every target of `targets` at every hour of `hours` and day of `weekdays`, like
+    /// [`FeeModel::estimate_with_features`] at the current time, see [`FeeModel::with_clock`],
+    /// with the hour and the day overridden. The fee rates are bucketed once and the other
+    /// features are the same for every estimate, bounded as configured in the options.
+    ///
+    /// Errors like the estimates, with [`Error::InvalidOverride`] for an hour greater than 23 or a
+    /// day greater than 6, and with [`Error::MissingTimestamp`] without the `chrono` feature.
+    pub fn grid(
+        &self,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+        targets: &[u16],
+        hours: &[u8],
+        weekdays: &[u8],
+    ) -> Result<GridResult, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(None, last_block_ts)?;
+        let (input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let mut rates = Vec::with_capacity(weekdays.len() * targets.len() * hours.len());
+        for weekday in weekdays {
+            for target in targets {
+                for hour in hours {
+                    let overrides = FeatureOverrides {
+                        day_of_week: Some(*weekday),
+                        hour: Some(*hour),
+                        delta_last: None,
+                    };
+                    let mut input = input.clone();
+                    overrides.apply(&mut input)?;
+                    let estimate =
+                        self.predict_target(*target, buckets, &mut input, extras.as_ref())?;
+                    rates.push(estimate.rate);
+                }
+            }
+        }
+        Ok(GridResult {
+            targets: targets.to_vec(),
+            hours: hours.to_vec(),
+            weekdays: weekdays.to_vec(),
+            rates,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "chrono"))]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::heatmap::GridResult;
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::{Error, FeatureOverrides};
+
+    #[test]
+    fn test_grid() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model().with_clock(move || Utc.timestamp_opt(ts, 0).unwrap());
+        let (targets, hours, weekdays) = ([1u16, 2, 6, 144], [0u8, 8, 16, 23], [1u8, 5]);
+        let grid = model
+            .grid(&rates, ts - 300, &targets, &hours, &weekdays)
+            .unwrap();
+        assert_eq!(grid.rates.len(), 4 * 4 * 2);
+        assert_eq!(grid.targets, targets);
+
+        for (target, hour, weekday) in [(6, 16, 5), (1, 0, 1), (144, 23, 1)].iter() {
+            let overrides = FeatureOverrides {
+                day_of_week: Some(*weekday),
+                hour: Some(*hour),
+                delta_last: None,
+            };
+            let expected = model
+                .estimate_with_features(*target, Some(ts), &rates, ts - 300, overrides)
+                .unwrap();
+            assert_eq!(grid.rate(*target, *hour, *weekday), Some(expected));
+        }
+        // the second weekday, the third target and the first hour
+        let row = 4 + 2;
+        assert_eq!(grid.rates[row * 4], grid.rate(6, 0, 5).unwrap());
+        assert_eq!(grid.rate(3, 0, 1), None);
+
+        let json = serde_json::to_string(&grid).unwrap();
+        let restored: GridResult = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, grid);
+
+        let err = model.grid(&rates, ts - 300, &targets, &[24], &weekdays);
+        assert!(matches!(err, Err(Error::InvalidOverride("hour"))));
+        let err = model.grid(&rates, ts - 300, &[0], &hours, &weekdays);
+        assert!(matches!(err, Err(Error::InvalidTarget(0))));
+        let empty = model
+            .grid(&rates, ts - 300, &[], &hours, &weekdays)
+            .unwrap();
+        assert!(empty.rates.is_empty());
+    }
+}
@@ -0,0 +1,387 @@
+//! Randomized checks of the properties expected of the estimates of any model, to validate custom
+//! models before deploying them, see [`check_model`]
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{Error, FeatureOverrides, FeeModel};
+
+/// Unix time in seconds of the first window generated, 2021-02-19
+const START: i64 = 1613708045;
+
+/// What [`check_model`] checks and how many windows it generates
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantConfig {
+    /// the random windows of fee rates estimated
+    pub windows: usize,
+    /// the targets estimated for every window, the estimates of the first one must be on
+    /// average at least the ones of the last one
+    pub targets: Vec<u16>,
+    /// the greatest ratio between the estimates of a window and of the same window with 1% more
+    /// transactions, copies of random ones of the window
+    pub max_jump: f32,
+    /// the greatest ratio between the estimates of a window at different hours and days of the
+    /// week
+    pub max_time_factor: f32,
+}
+
+impl Default for InvariantConfig {
+    fn default() -> Self {
+        InvariantConfig {
+            windows: 32,
+            targets: vec![1, 2, 6, 24, 144],
+            max_jump: 10.0,
+            max_time_factor: 4.0,
+        }
+    }
+}
+
+/// A property not satisfied by the estimates of a model, the windows are numbered from 0 in the
+/// order they are generated from the seed of [`check_model`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// the estimate errored, with the [`Error::kind`] and the message of the error
+    Failed {
+        window: usize,
+        target: u16,
+        kind: &'static str,
+        message: String,
+    },
+    /// the estimate is not finite or not positive
+    NotPositive {
+        window: usize,
+        target: u16,
+        rate: f32,
+    },
+    /// the mean estimate of the first target is lower than the one of the last target
+    TargetOrder {
+        short: u16,
+        long: u16,
+        short_mean: f32,
+        long_mean: f32,
+    },
+    /// adding 1% of transactions to the window changed the estimate more than
+    /// [`InvariantConfig::max_jump`]
+    Discontinuous {
+        window: usize,
+        target: u16,
+        rate: f32,
+        perturbed: f32,
+    },
+    /// the estimates at different hours and days of the week differ more than
+    /// [`InvariantConfig::max_time_factor`]
+    TimeSensitive {
+        window: usize,
+        target: u16,
+        min: f32,
+        max: f32,
+    },
+}
+
+/// The outcome of [`check_model`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvariantReport {
+    pub windows: usize,
+    /// the estimates computed, including the perturbed ones
+    pub estimates: usize,
+    pub violations: Vec<Violation>,
+}
+
+impl InvariantReport {
+    /// Whether every property is satisfied
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// Check the estimates of `model` over [`InvariantConfig::windows`] random windows generated from
+/// `rng_seed`, the same seed generating the same windows:
+///
+/// * every estimate is finite and positive
+/// * the estimates of the first of [`InvariantConfig::targets`] are on average at least the ones
+///   of the last
+/// * adding 1% of transactions to a window doesn't change the estimates more than
+///   [`InvariantConfig::max_jump`] times
+/// * estimating a window at every day of the week, at 0, 6, 12 and 18 UTC, doesn't change the
+///   estimates more than [`InvariantConfig::max_time_factor`] times
+///
+/// The windows have from 1,000 to 10,000 transactions with log-normal fee rates, the median
+/// between 2 and 80 sat/vB, estimated up to an hour after the last block. The properties not
+/// satisfied are reported in the result, the checks don't panic.
+pub fn check_model(model: &FeeModel, config: InvariantConfig, rng_seed: u64) -> InvariantReport {
+    let mut rng = SplitMix64(rng_seed);
+    let mut report = InvariantReport {
+        windows: config.windows,
+        estimates: 0,
+        violations: vec![],
+    };
+    let mut sums = vec![0.0f64; config.targets.len()];
+    let mut counts = vec![0usize; config.targets.len()];
+    for window in 0..config.windows {
+        let timestamp = START + (rng.below(4 * 365 * 24) * 3600) as i64;
+        let last_block_ts = timestamp - rng.below(3600) as i64;
+        let fee_rates = random_window(&mut rng);
+        let mut perturbed = fee_rates.clone();
+        for _ in 0..fee_rates.len().div_ceil(100) {
+            perturbed.push(fee_rates[rng.below(fee_rates.len() as u64) as usize]);
+        }
+        for (i, target) in config.targets.iter().copied().enumerate() {
+            let estimate = |report: &mut InvariantReport, fee_rates: &[f64], overrides| {
+                report.estimates += 1;
+                let result = model.estimate_with_features(
+                    target,
+                    Some(timestamp),
+                    fee_rates,
+                    last_block_ts,
+                    overrides,
+                );
+                check_rate(window, target, result, &mut report.violations)
+            };
+            let rate = match estimate(&mut report, &fee_rates, FeatureOverrides::default()) {
+                Some(rate) => rate,
+                None => continue,
+            };
+            sums[i] += rate as f64;
+            counts[i] += 1;
+            if let Some(perturbed) = estimate(&mut report, &perturbed, FeatureOverrides::default())
+            {
+                if ratio(rate, perturbed) > config.max_jump {
+                    let violation = Violation::Discontinuous {
+                        window,
+                        target,
+                        rate,
+                        perturbed,
+                    };
+                    report.violations.push(violation);
+                }
+            }
+            let (mut min, mut max) = (rate, rate);
+            for day_of_week in 0..7 {
+                for hour in [0, 6, 12, 18].iter() {
+                    let overrides = FeatureOverrides {
+                        day_of_week: Some(day_of_week),
+                        hour: Some(*hour),
+                        delta_last: None,
+                    };
+                    if let Some(rate) = estimate(&mut report, &fee_rates, overrides) {
+                        min = min.min(rate);
+                        max = max.max(rate);
+                    }
+                }
+            }
+            if ratio(min, max) > config.max_time_factor {
+                let violation = Violation::TimeSensitive {
+                    window,
+                    target,
+                    min,
+                    max,
+                };
+                report.violations.push(violation);
+            }
+        }
+    }
+    let mean = |i: usize| (sums[i] / counts[i].max(1) as f64) as f32;
+    if let (Some(short), Some(long)) = (config.targets.first(), config.targets.last()) {
+        let (short_mean, long_mean) = (mean(0), mean(config.targets.len() - 1));
+        if short_mean < long_mean {
+            report.violations.push(Violation::TargetOrder {
+                short: *short,
+                long: *long,
+                short_mean,
+                long_mean,
+            });
+        }
+    }
+    report
+}
+
+/// The estimate of `result` if finite and positive, otherwise adds the violation
+fn check_rate(
+    window: usize,
+    target: u16,
+    result: Result<f32, Error>,
+    violations: &mut Vec<Violation>,
+) -> Option<f32> {
+    match result {
+        Ok(rate) if rate.is_finite() && rate > 0.0 => Some(rate),
+        Ok(rate) => {
+            violations.push(Violation::NotPositive {
+                window,
+                target,
+                rate,
+            });
+            None
+        }
+        Err(e) => {
+            violations.push(Violation::Failed {
+                window,
+                target,
+                kind: e.kind(),
+                message: e.to_string(),
+            });
+            None
+        }
+    }
+}
+
+/// The ratio of the greatest of two positive rates to the other one
+fn ratio(a: f32, b: f32) -> f32 {
+    a.max(b) / a.min(b)
+}
+
+/// The fee rates of a random window, see [`check_model`]
+fn random_window(rng: &mut SplitMix64) -> Vec<f64> {
+    let median = libm::exp(libm::log(2.0) + rng.unit() * libm::log(40.0));
+    let len = 1000 + rng.below(9000) as usize;
+    (0..len)
+        .map(|_| {
+            let rate = median * libm::exp(0.9 * rng.normal());
+            libm::round(rate.max(1.0) * 10.0) / 10.0
+        })
+        .collect()
+}
+
+/// The SplitMix64 generator, to generate the same windows from a seed on every platform
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `0..n`, `n` must be positive
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    /// Standard normal with the Box-Muller transform
+    fn normal(&mut self) -> f64 {
+        let u = 1.0 - self.unit();
+        let v = self.unit();
+        libm::sqrt(-2.0 * libm::log(u)) * libm::cos(2.0 * core::f64::consts::PI * v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::invariants::{check_model, InvariantConfig, Violation};
+    use crate::matrix::Matrix;
+    use crate::model_data::Kernel;
+    use crate::{EstimateOptions, FeeModel, ModelData};
+
+    #[cfg(feature = "embedded-models")]
+    #[test]
+    fn test_embedded_models_invariants() {
+        let model = FeeModel::new().unwrap();
+        // the embedded models depend strongly on the time: the estimates of a window for 6
+        // blocks range from the minimum fee rate to tens of sat/vB over the week
+        let config = InvariantConfig {
+            max_time_factor: 500.0,
+            ..Default::default()
+        };
+        let report = check_model(&model, config, 42);
+        assert!(report.is_ok(), "{:?}", report.violations);
+        assert_eq!(report.windows, 32);
+        // 1 + 1 + 28 estimates per window and target
+        assert_eq!(report.estimates, 32 * 5 * 30);
+
+        // with the default time factor
+        let config = InvariantConfig {
+            windows: 4,
+            ..Default::default()
+        };
+        let report = check_model(&model, config.clone(), 0);
+        assert!(!report.is_ok());
+        assert!(report
+            .violations
+            .iter()
+            .all(|v| matches!(v, Violation::TimeSensitive { .. })));
+        assert_eq!(report, check_model(&model, config, 0));
+    }
+
+    #[test]
+    fn test_violations() {
+        let constant = |rate| ModelData::constant(rate, 16).unwrap();
+        let config = InvariantConfig {
+            windows: 3,
+            ..Default::default()
+        };
+
+        // a constant model satisfies every property
+        let model = FeeModel::from_models(constant(5.0), constant(5.0));
+        assert!(check_model(&model, config.clone(), 1).is_ok());
+
+        // cheaper for the next block than for a day
+        let model = FeeModel::from_models(constant(1.0), constant(50.0));
+        let report = check_model(&model, config.clone(), 1);
+        let expected = Violation::TargetOrder {
+            short: 1,
+            long: 144,
+            short_mean: 1.0,
+            long_mean: 50.0,
+        };
+        assert_eq!(report.violations, vec![expected]);
+
+        let zero =
+            FeeModel::from_models(constant(0.0), constant(0.0)).with_options(EstimateOptions {
+                min_fee_rate: 0.0,
+                ..Default::default()
+            });
+        let report = check_model(&zero, config.clone(), 1);
+        assert!(!report.is_ok());
+        assert!(report
+            .violations
+            .iter()
+            .all(|v| matches!(v, Violation::NotPositive { rate, .. } if *rate == 0.0)));
+        assert_eq!(report.violations.len(), report.estimates);
+
+        // 2 + hour sat/vB, from 2 at midnight to 20 at 18
+        let mut hourly = constant(2.0);
+        let mut kernel = Matrix::zeros(1, hourly.feature_names().len());
+        kernel[2][0] = 1.0;
+        hourly.weights.l0_kernel = Kernel::F32(kernel);
+        hourly.weights.l1_kernel = Kernel::F32(Matrix::from_array(vec![1.0].into_boxed_slice()));
+        hourly.weights.l2_kernel = Kernel::F32(Matrix::from_array(vec![1.0].into_boxed_slice()));
+        let model = FeeModel::from_models(hourly.clone(), hourly);
+        let report = check_model(&model, config, 1);
+        assert_eq!(report.violations.len(), 3 * 5);
+        for violation in report.violations {
+            match violation {
+                Violation::TimeSensitive { min, max, .. } => {
+                    assert_eq!(min, 2.0);
+                    assert!(max >= 20.0);
+                }
+                v => panic!("unexpected {:?}", v),
+            }
+        }
+
+        let failing = check_model(
+            &model,
+            InvariantConfig {
+                windows: 1,
+                targets: vec![0],
+                ..Default::default()
+            },
+            1,
+        );
+        assert!(matches!(
+            &failing.violations[0],
+            Violation::Failed {
+                window: 0,
+                target: 0,
+                kind: "InvalidTarget",
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+//! Import of the models exported from Keras as JSON, see [`ModelData::from_keras_json`]
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::io::Read;
+
+use serde::Deserialize;
+
+use crate::matrix::Matrix;
+use crate::model_data::{check_finite, check_len, read_limited, FieldsDescribe, Kernel, Weights};
+use crate::{Activation, Error, ModelData, ModelInfo, ModelLimits, OutputTransform};
+
+/// Names of the kernel and the bias of every layer, like in the CBOR models
+const LAYERS: [(&str, &str); 3] = [
+    ("dense/kernel:0", "dense/bias:0"),
+    ("dense_1/kernel:0", "dense_1/bias:0"),
+    ("dense_2/kernel:0", "dense_2/bias:0"),
+];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KerasModel {
+    features: Vec<String>,
+    input_norm: InputNorm,
+    layers: Vec<DenseLayer>,
+    #[serde(default)]
+    output_transform: OutputTransform,
+    #[serde(default)]
+    quantiles: Vec<f32>,
+    #[serde(default)]
+    metadata: ModelInfo,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InputNorm {
+    mean: Vec<f32>,
+    std: Vec<f32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DenseLayer {
+    /// `input_dim` x `units` in row-major order, like `kernel.numpy().flatten()`
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+    activation: String,
+}
+
+impl ModelData {
+    /// Load a model exported from Keras as JSON, checked like the CBOR models of
+    /// [`ModelData::from_reader`] and bounded by the default [`ModelLimits`]:
+    ///
+    /// ```json
+    /// {
+    ///   "features": ["confirms_in", "day_of_week", "hour", "delta_last", "b0", ...],
+    ///   "input_norm": { "mean": [6.0, 3.0, ...], "std": [4.0, 2.0, ...] },
+    ///   "layers": [
+    ///     { "weights": [0.5, -0.25, ...], "bias": [0.1, ...], "activation": "relu" },
+    ///     ...
+    ///   ],
+    ///   "output_transform": "log1p",
+    ///   "metadata": { "name": "20240101-120000" }
+    /// }
+    /// ```
+    ///
+    /// The features are standardized with the `mean` and `std` at the same position. The three
+    /// dense layers, from the input to the output, have the kernel of Keras in row-major order in
+    /// `weights`, `input_dim` rows of as many values as the `bias`, and one of the activations
+    /// of [`Activation::from_name`] or `linear`. `output_transform`, `quantiles` and `metadata`
+    /// are optional, like in the CBOR models.
+    ///
+    /// Errors with [`Error::Json`] if the JSON doesn't follow the schema and with
+    /// [`Error::DimensionMismatch`] naming the layer like in the CBOR models, `dense_1/bias:0`
+    /// for the bias of the second layer, if the shapes are not consistent.
+    pub fn from_keras_json<R: Read>(reader: R) -> Result<ModelData, Error> {
+        let limits = ModelLimits::default();
+        let bytes = read_limited(reader, &limits)?;
+        let keras: KerasModel = serde_json::from_slice(&bytes).map_err(Error::Json)?;
+        let fields = keras.features;
+        check_len("input_norm.mean", fields.len(), keras.input_norm.mean.len())?;
+        check_len("input_norm.std", fields.len(), keras.input_norm.std.len())?;
+        check_len("layers", LAYERS.len(), keras.layers.len())?;
+        let widths = [
+            fields.len(),
+            keras.layers[0].bias.len(),
+            keras.layers[1].bias.len(),
+            keras.layers[2].bias.len(),
+        ];
+        limits.check_widths(&widths)?;
+
+        let mut matrices = Vec::with_capacity(LAYERS.len());
+        let mut activations = Vec::with_capacity(LAYERS.len());
+        for (i, (layer, (kernel_name, bias_name))) in
+            keras.layers.into_iter().zip(LAYERS).enumerate()
+        {
+            let (inputs, units) = (widths[i], widths[i + 1]);
+            check_len(kernel_name, inputs * units, layer.weights.len())?;
+            check_finite(kernel_name, &layer.weights)?;
+            check_finite(bias_name, &layer.bias)?;
+            let kernel = Matrix::from_buffer(layer.weights.into_boxed_slice(), units, inputs);
+            let bias = Matrix::from_buffer(layer.bias.into_boxed_slice(), units, 1);
+            matrices.push((Kernel::F32(kernel), bias));
+            activations.push(match layer.activation.as_str() {
+                "linear" => "identity".into(),
+                _ => layer.activation,
+            });
+        }
+        let mut matrices = matrices.into_iter();
+        let mut next = || matrices.next().expect("three layers");
+        let ((l0_kernel, l0_bias), (l1_kernel, l1_bias), (l2_kernel, l2_bias)) =
+            (next(), next(), next());
+        let weights = Weights {
+            l0_bias,
+            l0_kernel,
+            l1_bias,
+            l1_kernel,
+            l2_bias,
+            l2_kernel,
+        };
+
+        let norm = |values: Vec<f32>| fields.iter().cloned().zip(values).collect();
+        let norm =
+            FieldsDescribe::standardized(norm(keras.input_norm.mean), norm(keras.input_norm.std));
+        // only used by the models without activations, keep the slope of a leaky layer if any
+        let alpha = activations
+            .iter()
+            .find_map(|name| match Activation::from_name(name) {
+                Ok(Activation::LeakyRelu(alpha)) => Some(alpha),
+                _ => None,
+            })
+            .unwrap_or(0.0);
+        ModelData::from_parts(
+            norm,
+            fields,
+            alpha,
+            Some(activations),
+            keras.output_transform,
+            keras.quantiles,
+            keras.metadata,
+            weights,
+        )
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::excessive_precision)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+
+    use crate::tests::assert_approx_eq;
+    use crate::{Activation, Error, ModelData};
+
+    const KERAS: &[u8] = include_bytes!("../models/fixtures/keras.json");
+
+    /// Predictions of `keras.json` computed in Python, in double precision, by the forward pass
+    /// `x = act(x @ kernel.reshape(len(x), units) + bias)` of the standardized inputs
+    const EXPECTED: [([f32; 4], f32); 3] = [
+        ([1.0, 60.0, 300.0, 10.0], 25.83354890763038),
+        ([6.0, 600.0, 100.0, 50.0], 22.27001298248672),
+        ([24.0, 1800.0, 20.0, 200.0], 37.82931321501367),
+    ];
+
+    fn load(value: &Value) -> Result<ModelData, Error> {
+        ModelData::from_keras_json(value.to_string().as_bytes())
+    }
+
+    #[test]
+    fn test_from_keras_json() {
+        let model = ModelData::from_keras_json(KERAS).unwrap();
+        assert_eq!(model.feature_names()[1], "delta_last");
+        assert_eq!(model.info.name.as_deref(), Some("keras-example"));
+        assert_eq!(model.activations[1], Activation::Tanh);
+        assert_eq!(model.activations[2], Activation::Identity);
+        let layers: Vec<_> = model
+            .layers()
+            .map(|l| (l.input_dim, l.output_dim))
+            .collect();
+        assert_eq!(layers, vec![(4, 3), (3, 2), (2, 1)]);
+        for (values, expected) in EXPECTED.iter() {
+            let input: HashMap<String, f32> = model
+                .feature_names()
+                .iter()
+                .cloned()
+                .zip(values.iter().copied())
+                .collect();
+            assert_approx_eq(model.norm_predict(&input).unwrap(), *expected);
+        }
+        // the same model once converted to CBOR
+        let cbor = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+        crate::model_data::tests::assert_same_predictions(&model, &cbor);
+    }
+
+    #[test]
+    fn test_keras_json_shapes() {
+        let valid: Value = serde_json::from_slice(KERAS).unwrap();
+        let mut bad = valid.clone();
+        bad["layers"][1]["weights"] = json!([0.1, 0.2, 0.3, 0.4, 0.5]);
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "dense_1/kernel:0",
+                expected: 6,
+                got: 5
+            }
+        ));
+        let mut bad = valid.clone();
+        bad["layers"][2]["bias"] = json!([1.0, 2.0]);
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "dense_2/kernel:0",
+                ..
+            }
+        ));
+        let mut bad = valid.clone();
+        bad["input_norm"]["std"] = json!([1.0]);
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "input_norm.std",
+                ..
+            }
+        ));
+        let mut bad = valid.clone();
+        bad["layers"].as_array_mut().unwrap().pop();
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "layers",
+                ..
+            }
+        ));
+        let mut bad = valid.clone();
+        bad["layers"][0]["activation"] = json!("softmax");
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedActivation(_)));
+        let mut bad = valid.clone();
+        bad["input_norm"]["std"][0] = json!(0.0);
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(err, Error::InvalidNormalization(_)));
+        let mut bad = valid;
+        bad["layers"][0]["kernel"] = json!([]);
+        let err = load(&bad).unwrap_err();
+        assert!(matches!(err, Error::Json(_)));
+    }
+}
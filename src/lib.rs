@@ -1,154 +1,5887 @@
-use std::collections::HashMap;
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use chrono::{DateTime, Datelike, NaiveDateTime, Timelike, Utc};
+#[cfg(not(any(feature = "std", feature = "alloc")))]
+compile_error!("either the `std` or the `alloc` feature is required");
 
-use crate::fee_bucket::FeeBuckets;
-use crate::matrix::{size::*, SizeMarker};
-use crate::model_data::ModelData;
+extern crate alloc;
 
+use alloc::borrow::Cow;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+#[cfg(any(feature = "chrono", feature = "std"))]
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::{BufReader, Read};
+#[cfg(feature = "std")]
+use std::path::Path;
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+use crate::ensemble::MemberOutputs;
+
+mod accuracy;
+mod activation;
+#[cfg(feature = "chrono")]
+mod cache;
+mod config;
+mod diff;
+mod ensemble;
 mod error;
+mod estimate;
+mod estimator;
 mod fee_bucket;
+mod fee_rate;
+mod heatmap;
+mod loader;
 mod matrix;
 mod model_data;
+mod model_input;
+mod network;
+mod provider;
+mod request;
+mod scratch;
+mod smoother;
+mod source;
+mod summary;
+mod time;
+mod tracker;
+mod validate;
+
+#[cfg(feature = "checksum")]
+mod checksum;
+
+#[cfg(feature = "postcard")]
+mod compact;
+
+#[cfg(feature = "std")]
+mod csv;
+
+#[cfg(feature = "mmap")]
+mod flat;
+
+#[cfg(feature = "json-import")]
+mod keras;
+
+#[cfg(feature = "onnx")]
+mod onnx;
+
+#[cfg(feature = "json")]
+mod record;
+
+#[cfg(feature = "json")]
+mod registry;
+
+#[cfg(feature = "std")]
+mod metrics;
+
+#[cfg(feature = "std")]
+mod reload;
+
+#[cfg(feature = "backtest")]
+pub mod backtest;
+
+#[cfg(feature = "test-utils")]
+pub mod invariants;
+
+#[cfg(feature = "std")]
+pub mod dataset;
+
+pub mod snapshot;
 
 #[cfg(feature = "use-bitcoin")]
 pub mod process_blocks;
 
+#[cfg(feature = "use-bitcoin")]
+mod psbt;
+
+#[cfg(feature = "rpc")]
+pub mod rpc;
+
+#[cfg(feature = "esplora")]
+pub mod esplora;
+
+#[cfg(feature = "rpc-async")]
+pub mod rpc_async;
+
+#[cfg(feature = "zmq")]
+pub mod service;
+
+#[cfg(feature = "server")]
+pub mod server;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "python")]
+mod python;
+
+#[cfg(feature = "uniffi")]
+mod mobile;
+
+#[cfg(feature = "uniffi")]
+uniffi::setup_scaffolding!("bitcoin_fee_model");
+
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
 #[cfg(feature = "use-bitcoin")]
 pub extern crate bitcoin;
 
 #[cfg(feature = "use-bitcoin")]
 pub use process_blocks::process_blocks;
+#[cfg(feature = "use-bitcoin")]
+pub use psbt::PsbtFeeAdvice;
 
+pub use accuracy::{AccuracyMetrics, AccuracyTracker};
+pub use activation::Activation;
+#[cfg(feature = "chrono")]
+pub use cache::CachedFeeModel;
+#[cfg(feature = "checksum")]
+pub use checksum::ModelChecksums;
+#[cfg(feature = "signature")]
+pub use checksum::ModelSignature;
+#[cfg(all(feature = "postcard", feature = "std"))]
+pub use compact::cbor_to_postcard;
+pub use config::{
+    BlendRange, EstimateOptions, FeeModelConfig, Rounding, Strictness, TargetPolicy, TimeFeatures,
+};
+pub use diff::{GridPoint, LayerDiff, ModelDiff, PredictionDelta, PredictionGrid};
+pub use ensemble::Combine;
 pub use error::Error;
-pub use model_data::models::*;
+#[cfg(feature = "rayon")]
+pub use estimate::BatchInput;
+pub use estimate::{
+    BlockFeeRates, BothEstimates, FeatureOverrides, FeeEstimate, ModelKind, PredictTrace,
+    SmartFeeResult, Staleness,
+};
+pub use estimator::{Fallback, FeeEstimator, TrackedFeeModel};
+pub use fee_bucket::{
+    BucketAccumulator, BucketMode, BucketRange, BucketScaling, BucketSpec, FeeBuckets,
+};
+pub use fee_rate::FeeRate;
+#[cfg(feature = "mmap")]
+pub use flat::cbor_to_flat;
+pub use heatmap::GridResult;
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusMetrics;
+#[cfg(feature = "std")]
+pub use metrics::{AtomicMetrics, EstimateEvent, EstimateOutcome, MetricsSink};
+pub use model_data::{
+    FeatureRange, FieldsDescribe, LayerInfo, ModelData, ModelInfo, ModelLimits, OutputTransform,
+    SizeReport, MODEL_FORMAT_VERSION,
+};
+pub use model_input::{ModelInput, MAX_BUCKETS};
+pub use network::Network;
+pub use provider::{FeatureProvider, FeeHistogram};
+#[cfg(feature = "json")]
+pub use record::{replay, InputRecorder, RecordedCall, ReplayDiff};
+#[cfg(feature = "std")]
+pub use reload::ReloadableFeeModel;
+pub use request::{EstimateRequest, EstimateRequestBuilder};
+pub use scratch::PredictScratch;
+pub use smoother::EstimateSmoother;
+pub use source::{AsyncFeeSource, FeeWindow};
+pub use summary::{summary_features, SummaryFeatures, SUMMARY_FEATURES};
+pub use time::Timestamp;
+pub use tracker::{BlockTracker, TRACKER_FORMAT_VERSION, WINDOW};
+pub use validate::{ConflictReason, ModelConflict, ValidationIssue, MAX_SCALE_RATIO};
+
+/// The model of [`FeeModel::toy`]
+#[cfg(feature = "test-utils")]
+const TOY_MODEL: &[u8] = include_bytes!("../models/toy.cbor");
+
+/// Highest block target the embedded models have been trained for
+pub const MAX_TARGET: u16 = 1008;
 
-pub struct FeeModel<N> {
-    /// for 1,2 blocks
-    low: ModelData<Size20, N, Size1>,
-    /// for 3-1008 blocks
-    high: ModelData<Size20, N, Size1>,
+/// Highest block target of the low models not declaring their own, see
+/// [`FeeModel::dispatch_threshold`]
+const LOW_MAX_TARGET: u16 = 2;
+
+/// Targets evaluated by [`FeeModel::estimate_all`]: every block up to 25, then 144, 504 and 1008,
+/// without the ones greater than [`FeeModel::max_target`]
+pub const DEFAULT_TARGETS: [u16; 28] = [
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 144,
+    504, 1008,
+];
+
+/// Expected time between blocks, converting durations to targets in [`FeeModel::estimate_within`]
+pub const BLOCK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Virtual size (vB) counted as one transaction in [`FeeModel::estimate_from_histogram`]
+pub const HISTOGRAM_TX_VSIZE: u64 = 250;
+
+/// The extra feature of the models trained with adaptive bucket limits, see
+/// [`FeeModel::with_bucket_scaling`]
+const BUCKET_LIMIT: &str = "bucket_limit";
+
+/// The models of the low and the high targets, or of the tiers of [`FeeModel::from_tiers`], with
+/// the configuration of the estimates. It's `Send` and `Sync` and estimates take `&self`, so a
+/// single instance can estimate from many threads.
+#[derive(Clone)]
+pub struct FeeModel {
+    /// by increasing `max_target`, at least one: the low model up to
+    /// [`FeeModel::dispatch_threshold`], 2 blocks by default, and the high one up to 1008 blocks
+    /// unless created with [`FeeModel::from_tiers`]
+    tiers: Vec<Tier>,
+    fee_buckets: FeeBuckets,
+    /// the configuration of `fee_buckets`, `None` if given with [`FeeModel::with_fee_buckets`]
+    bucket_config: Option<FeeModelConfig>,
+    /// how the limit of `fee_buckets` is chosen when estimating from fee rates
+    bucket_scaling: BucketScaling,
+    options: EstimateOptions,
+    /// the network the models have been trained for
+    network: Network,
+    /// source of the current time when no timestamp is given
+    #[cfg(feature = "chrono")]
+    clock: Arc<dyn Fn() -> DateTime<Utc> + Send + Sync>,
+    /// source of the jitter of [`Rounding::Randomized`], see [`FeeModel::with_rng`]
+    #[cfg(feature = "rand")]
+    rng: Option<Arc<std::sync::Mutex<dyn rand_core::RngCore + Send>>>,
+    /// receives the estimates, see [`FeeModel::with_metrics`]
+    #[cfg(feature = "std")]
+    metrics: Option<Arc<dyn MetricsSink>>,
 }
 
-impl<N: SizeMarker> FeeModel<N> {
-    pub fn new(low: ModelData<Size20, N, Size1>, high: ModelData<Size20, N, Size1>) -> FeeModel<N> {
-        FeeModel { low, high }
+// `FeeModel` must stay shareable between threads, see its documentation
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FeeModel>();
+    assert_send_sync::<ModelData>();
+    #[cfg(feature = "chrono")]
+    assert_send_sync::<CachedFeeModel>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<ReloadableFeeModel>();
+};
+
+impl fmt::Debug for FeeModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FeeModel")
+            .field("tiers", &self.tiers)
+            .field("fee_buckets", &self.fee_buckets)
+            .field("bucket_config", &self.bucket_config)
+            .field("bucket_scaling", &self.bucket_scaling)
+            .field("options", &self.options)
+            .field("network", &self.network)
+            .finish_non_exhaustive()
     }
+}
 
-    pub fn estimate_with_buckets(
-        &self,
-        block_target: u16,
-        timestamp: Option<u32>,
-        fee_buckets: &[u64],
-        last_block_ts: u32,
-    ) -> Result<f32, Error> {
-        let mut input = HashMap::new();
-        input.insert("confirms_in".to_string(), block_target as f32);
+/// The models trained for the targets up to `max_target` and greater than the ones of the previous
+/// tier
+#[derive(Debug, Clone)]
+struct Tier {
+    max_target: u16,
+    /// the members of the ensemble, at least one, have the same features
+    models: Vec<ModelData>,
+    /// the buckets of the models when their number differs from the one of
+    /// [`FeeModel::fee_buckets`], see [`FeeModel::fee_buckets_for`]
+    fee_buckets: Option<FeeBuckets>,
+}
+
+impl Tier {
+    fn kind(index: usize) -> (ModelKind, &'static str) {
+        match index {
+            0 => (ModelKind::Low, "low"),
+            1 => (ModelKind::High, "high"),
+            _ => (ModelKind::Long, "long"),
+        }
+    }
+}
+
+/// The models embedded in the library, see [`FeeModel::new`]
+#[cfg(feature = "embedded-models")]
+impl Default for FeeModel {
+    fn default() -> Self {
+        FeeModel::new().expect("embedded models are valid")
+    }
+}
+
+/// Create the model of [`FeeModel::shared`] now instead of at the first estimate, for example
+/// at the start of a serverless function before awaiting its first request. The embedded models
+/// are decompressed and parsed once per process, later calls only return the shared model.
+#[cfg(all(feature = "std", feature = "embedded-models"))]
+pub fn init() -> &'static FeeModel {
+    FeeModel::shared()
+}
+
+/// Like [`FeeModel::estimate`] with the embedded models of [`FeeModel::shared`], created at the
+/// first call unless [`init`] has been called, for deployments that can't keep a model between
+/// invocations but keep the process, like serverless functions
+#[cfg(all(feature = "std", feature = "embedded-models"))]
+pub fn static_estimate(
+    block_target: u16,
+    timestamp: Option<i64>,
+    fee_rates: &[f64],
+    last_block_ts: i64,
+) -> Result<f32, Error> {
+    FeeModel::shared().estimate(block_target, timestamp, fee_rates, last_block_ts)
+}
+
+impl FeeModel {
+    /// Create the fee model using the models embedded in the library, or the CBOR files named by
+    /// the `BITCOIN_FEE_MODEL_LOW` and `BITCOIN_FEE_MODEL_HIGH` env vars at build time
+    #[cfg(feature = "embedded-models")]
+    pub fn new() -> Result<FeeModel, Error> {
+        let low = decompress(include_bytes!(concat!(env!("OUT_DIR"), "/low.cbor.zz")))?;
+        let high = decompress(include_bytes!(concat!(env!("OUT_DIR"), "/high.cbor.zz")))?;
+        let mut low = ModelData::from_slice(&low)?;
+        let mut high = ModelData::from_slice(&high)?;
+        // the models of the repo predate metadata
+        let info = |name: &str, max_target| ModelInfo {
+            name: Some(name.into()),
+            bucket_config: Some(FeeModelConfig::default()),
+            max_target: Some(max_target),
+            units: [("delta_last".to_string(), "seconds".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            ..ModelInfo::default()
+        };
+        if low.info == ModelInfo::default() {
+            low.info = info(env!("BITCOIN_FEE_MODEL_LOW_NAME"), LOW_MAX_TARGET);
+        }
+        if high.info == ModelInfo::default() {
+            high.info = info(env!("BITCOIN_FEE_MODEL_HIGH_NAME"), MAX_TARGET);
+        }
+        FeeModel::try_from_models(low, high)
+    }
+
+    /// The fee model of `network`: the embedded models for [`Network::Bitcoin`] and a model
+    /// estimating 1 sat/vB for the other networks, which have no trained models
+    #[cfg(feature = "embedded-models")]
+    pub fn for_network(network: Network) -> Result<FeeModel, Error> {
+        if network.has_embedded_model() {
+            return FeeModel::new();
+        }
+        let bucket_count = FeeModelConfig::default().fee_buckets().len();
+        let constant = ModelData::constant(1.0, bucket_count)?;
+        Ok(FeeModel::from_models(constant.clone(), constant).with_network(network))
+    }
 
-        let utc: DateTime<Utc> = match timestamp {
-            Some(timestamp) => {
-                let naive = NaiveDateTime::from_timestamp(timestamp as i64, 0);
-                DateTime::from_utc(naive, Utc)
+    /// A handcrafted fee model for tests, estimating `1 + (1008 - target) / 100 + b15 / 1000`
+    /// sat/vB with `b15` the count of the last bucket, see `models/toy.cbor`
+    #[cfg(feature = "test-utils")]
+    pub fn toy() -> FeeModel {
+        let toy = ModelData::from_slice(TOY_MODEL).expect("the toy model is valid");
+        FeeModel::from_models(toy.clone(), toy)
+    }
+
+    /// The fee model using the models embedded in the library, created once per process at the
+    /// first call. Use [`FeeModel::new`] for an instance that can be configured.
+    #[cfg(all(feature = "std", feature = "embedded-models"))]
+    pub fn shared() -> &'static FeeModel {
+        static SHARED: std::sync::OnceLock<FeeModel> = std::sync::OnceLock::new();
+        SHARED.get_or_init(|| FeeModel::new().expect("embedded models are valid"))
+    }
+
+    /// Create the fee model estimating the targets up to the [`ModelInfo::max_target`] declared
+    /// by `low` with it, 2 blocks if it declares none or one not lower than [`MAX_TARGET`], and
+    /// the greater ones with `high`, see [`FeeModel::dispatch_threshold`]
+    pub fn from_models(low: ModelData, high: ModelData) -> FeeModel {
+        FeeModel::from_members(vec![low], vec![high])
+    }
+
+    /// Like [`FeeModel::from_models`], erroring with [`Error::ModelMismatch`] if the models
+    /// don't agree on their features, see [`FeeModel::validate_consistency`]
+    pub fn try_from_models(low: ModelData, high: ModelData) -> Result<FeeModel, Error> {
+        let model = FeeModel::from_models(low, high);
+        model.validate_consistency()?;
+        Ok(model)
+    }
+
+    /// Like [`FeeModel::from_models`] for the models loaded by the constructors, checking their
+    /// features with their own number of buckets, see [`FeeModel::validate_features`]
+    fn from_loaded(low: ModelData, high: ModelData) -> Result<FeeModel, Error> {
+        FeeModel::from_models(low, high).check_loaded()
+    }
+
+    fn check_loaded(self) -> Result<FeeModel, Error> {
+        for (kind, model) in self.members() {
+            check_features(model, kind, model.bucket_count())?;
+        }
+        self.check_bucket_mode(self.fee_buckets.mode())?;
+        self.check_bucket_configs()?;
+        self.validate_consistency()?;
+        Ok(self)
+    }
+
+    /// Create the fee model from `(max_target, model)` tiers by increasing `max_target`, a target
+    /// estimated by the first tier reaching it, like `vec![(2, low), (47, high), (1008, long)]`.
+    /// Errors with [`Error::InvalidTiers`] if the maximum targets are empty, zero or not increasing
+    /// and with [`Error::ModelMismatch`] if the models disagree, see
+    /// [`FeeModel::validate_consistency`].
+    pub fn from_tiers(tiers: Vec<(u16, ModelData)>) -> Result<FeeModel, Error> {
+        let max_targets: Vec<u16> = tiers.iter().map(|(max, _)| *max).collect();
+        let increasing = max_targets.windows(2).all(|w| w[0] < w[1]);
+        if max_targets.first().map_or(true, |first| *first == 0) || !increasing {
+            return Err(Error::InvalidTiers(max_targets));
+        }
+        let tiers = tiers
+            .into_iter()
+            .map(|(max_target, model)| Tier {
+                max_target,
+                models: vec![model],
+                fee_buckets: None,
+            })
+            .collect();
+        let model = FeeModel::from_tier_list(tiers);
+        model.validate_consistency()?;
+        Ok(model)
+    }
+
+    /// Create the fee model of a single model predicting every target in one pass, see
+    /// [`ModelInfo::output_targets`]. Errors with [`Error::InvalidOutputTargets`] for a model
+    /// without them and like [`FeeModel::from_paths`] for mismatched features.
+    pub fn from_multi_output(model: ModelData) -> Result<FeeModel, Error> {
+        let last = match model.output_targets().last() {
+            Some(last) => *last,
+            None => return Err(Error::InvalidOutputTargets(vec![])),
+        };
+        FeeModel::from_tiers(vec![(last, model)])?.check_loaded()
+    }
+
+    /// The model of a fee model of only a model predicting every target, see
+    /// [`FeeModel::from_multi_output`], `None` with [`EstimateOptions::blend`]
+    fn multi_output(&self) -> Option<&ModelData> {
+        match self.tiers.as_slice() {
+            [tier] if tier.models.len() == 1 && self.options.blend.is_none() => {
+                let model = &tier.models[0];
+                Some(model).filter(|m| !m.output_targets().is_empty())
+            }
+            _ => None,
+        }
+    }
+
+    fn from_members(low: Vec<ModelData>, high: Vec<ModelData>) -> FeeModel {
+        let threshold = low
+            .first()
+            .and_then(|m| m.info.max_target)
+            .filter(|t| (1..MAX_TARGET).contains(t))
+            .unwrap_or(LOW_MAX_TARGET);
+        FeeModel::from_tier_list(vec![
+            Tier {
+                max_target: threshold,
+                models: low,
+                fee_buckets: None,
+            },
+            Tier {
+                max_target: MAX_TARGET,
+                models: high,
+                fee_buckets: None,
+            },
+        ])
+    }
+
+    /// The buckets are the ones of the first model declaring a [`ModelInfo::bucket_config`], or the
+    /// default ones, and the tiers whose models have another number of buckets get their own
+    fn from_tier_list(mut tiers: Vec<Tier>) -> FeeModel {
+        let config = tiers
+            .iter()
+            .flat_map(|t| t.models.iter())
+            .find_map(|m| m.info.bucket_config.clone())
+            .unwrap_or_default();
+        // models without buckets keep the default ones and fail at estimate
+        let buckets_of = |tier: &Tier| {
+            let count = tier.models.first().map_or(0, ModelData::bucket_count);
+            config
+                .fee_buckets_with_count(count)
+                .unwrap_or_else(|_| config.fee_buckets())
+        };
+        let fee_buckets = buckets_of(&tiers[0]);
+        for tier in tiers.iter_mut().skip(1) {
+            let own = buckets_of(tier);
+            if own.len() != fee_buckets.len() {
+                tier.fee_buckets = Some(own);
             }
-            None => Utc::now(),
+        }
+        let bucket_config = FeeModelConfig {
+            bucket_count: Some(fee_buckets.len()),
+            ..config
         };
-        let day_of_week = utc.weekday().num_days_from_monday() as f32;
-        input.insert("day_of_week".to_string(), day_of_week);
-        input.insert("hour".to_string(), utc.hour() as f32);
+        FeeModel {
+            tiers,
+            fee_buckets,
+            bucket_config: Some(bucket_config),
+            bucket_scaling: BucketScaling::Fixed,
+            options: EstimateOptions::default(),
+            network: Network::Bitcoin,
+            #[cfg(feature = "chrono")]
+            clock: Arc::new(Utc::now),
+            #[cfg(feature = "rand")]
+            rng: None,
+            #[cfg(feature = "std")]
+            metrics: None,
+        }
+    }
 
-        let delta = utc.timestamp() - last_block_ts as i64;
-        input.insert("delta_last".to_string(), delta as f32);
+    /// Create the fee model from ensembles of models, their predictions combined as configured in
+    /// [`EstimateOptions::combine`]. Errors with [`Error::EnsembleMismatch`] if the members of an
+    /// ensemble have different features and with [`Error::EmptyEnsemble`] if it has none.
+    pub fn ensemble(
+        low_models: Vec<ModelData>,
+        high_models: Vec<ModelData>,
+    ) -> Result<FeeModel, Error> {
+        ensemble::check_members(&low_models)?;
+        ensemble::check_members(&high_models)?;
+        Ok(FeeModel::from_members(low_models, high_models))
+    }
 
-        for (i, fee_bucket) in fee_buckets.iter().take(16).enumerate() {
-            input.insert(format!("b{}", i), *fee_bucket as f32);
+    /// Check the models expect exactly the features computed by the estimate methods. Errors with
+    /// [`Error::FeatureMismatch`] listing the features missing and the ones in addition.
+    pub fn validate_features(&self) -> Result<(), Error> {
+        for (i, tier) in self.tiers.iter().enumerate() {
+            let bucket_count = self.tier_buckets(tier).len();
+            for model in tier.models.iter() {
+                check_features(model, Tier::kind(i).1, bucket_count)?;
+            }
         }
+        Ok(())
+    }
 
-        if block_target <= 2 {
-            self.low.norm_predict(&input)
+    /// Check the models agree on their features, units, scales and buckets. Errors with
+    /// [`Error::ModelMismatch`] listing every conflict.
+    pub fn validate_consistency(&self) -> Result<(), Error> {
+        let mut members = self.members();
+        let first = match members.next() {
+            Some(first) => first,
+            None => return Ok(()),
+        };
+        let conflicts: Vec<ModelConflict> = members
+            .flat_map(|model| validate::conflicts(first, model))
+            .collect();
+        if conflicts.is_empty() {
+            Ok(())
         } else {
-            self.high.norm_predict(&input)
+            Err(Error::ModelMismatch(conflicts))
+        }
+    }
+
+    /// Use the given `config` to compute model inputs in [`FeeModel::estimate`],
+    /// errors if the resulting buckets don't match the ones expected by the models
+    pub fn with_config(self, config: FeeModelConfig) -> Result<FeeModel, Error> {
+        let model = self.with_fee_buckets(config.counted_fee_buckets()?)?;
+        Ok(FeeModel {
+            bucket_config: Some(config),
+            ..model
+        })
+    }
+
+    /// Use the given `fee_buckets` to compute model inputs, for models trained with other buckets.
+    /// Errors with [`Error::WrongBucketCount`] or [`Error::BucketModeMismatch`] if the models
+    /// expect another number of buckets or another mode.
+    pub fn with_fee_buckets(self, fee_buckets: FeeBuckets) -> Result<FeeModel, Error> {
+        for (_, model) in self.members() {
+            if model.bucket_count() != fee_buckets.len() {
+                return Err(Error::WrongBucketCount {
+                    expected: model.bucket_count(),
+                    got: fee_buckets.len(),
+                });
+            }
+        }
+        self.check_bucket_mode(fee_buckets.mode())?;
+        let tiers = self
+            .tiers
+            .into_iter()
+            .map(|tier| Tier {
+                fee_buckets: None,
+                ..tier
+            })
+            .collect();
+        Ok(FeeModel {
+            tiers,
+            fee_buckets,
+            bucket_config: None,
+            ..self
+        })
+    }
+
+    /// Choose the limit of the buckets computed from fee rates as configured in `scaling`. Errors
+    /// with [`Error::FixedBucketLimit`] if a model lacks the `bucket_limit` feature of
+    /// [`BucketScaling::Adaptive`], with [`Error::WrongBucketCount`] if the tiers have different
+    /// numbers of buckets and with [`Error::InvalidInput`] if the percentile is not in `(0, 100]`.
+    pub fn with_bucket_scaling(self, scaling: BucketScaling) -> Result<FeeModel, Error> {
+        if let BucketScaling::Adaptive { percentile } = scaling {
+            if !(percentile > 0.0 && percentile <= 100.0) {
+                return Err(Error::InvalidInput("percentile"));
+            }
+            for (kind, model) in self.members() {
+                if !model.extra_fields().any(|f| f == BUCKET_LIMIT) {
+                    return Err(Error::FixedBucketLimit(kind));
+                }
+            }
+            if let Some(own) = self.tiers.iter().find_map(|t| t.fee_buckets.as_ref()) {
+                return Err(Error::WrongBucketCount {
+                    expected: self.fee_buckets.len(),
+                    got: own.len(),
+                });
+            }
+        }
+        Ok(FeeModel {
+            bucket_scaling: scaling,
+            ..self
+        })
+    }
+
+    pub fn bucket_scaling(&self) -> BucketScaling {
+        self.bucket_scaling
+    }
+
+    /// Check every model declaring a [`BucketMode`] in its metadata declares `mode`
+    fn check_bucket_mode(&self, mode: BucketMode) -> Result<(), Error> {
+        for (kind, model) in self.members() {
+            match &model.info.bucket_config {
+                Some(config) if config.bucket_mode != mode => {
+                    return Err(Error::BucketModeMismatch {
+                        model: kind,
+                        expected: config.bucket_mode,
+                        got: mode,
+                    })
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the models declaring a configuration in their metadata declare the same bucket
+    /// limits, see [`Error::BucketConfigMismatch`]
+    fn check_bucket_configs(&self) -> Result<(), Error> {
+        let mut expected: Option<&FeeModelConfig> = None;
+        for (kind, model) in self.members() {
+            let config = match &model.info.bucket_config {
+                Some(config) => config,
+                None => continue,
+            };
+            match expected {
+                Some(expected) if !expected.same_limits(config) => {
+                    return Err(Error::BucketConfigMismatch {
+                        model: kind,
+                        expected: expected.clone(),
+                        got: config.clone(),
+                    })
+                }
+                Some(_) => (),
+                None => expected = Some(config),
+            }
+        }
+        Ok(())
+    }
+
+    /// Use the given `options` to post-process the predictions
+    pub fn with_options(self, options: EstimateOptions) -> FeeModel {
+        FeeModel { options, ..self }
+    }
+
+    pub fn options(&self) -> &EstimateOptions {
+        &self.options
+    }
+
+    /// Use `clock` instead of [`Utc::now`] as the current time when estimating without a timestamp
+    #[cfg(feature = "chrono")]
+    pub fn with_clock<F>(self, clock: F) -> FeeModel
+    where
+        F: Fn() -> DateTime<Utc> + Send + Sync + 'static,
+    {
+        FeeModel {
+            clock: Arc::new(clock),
+            ..self
+        }
+    }
+
+    /// Draw the jitter of [`Rounding::Randomized`] from `rng`, shared by the clones of the model
+    /// and locked for every estimate rounded with it
+    #[cfg(feature = "rand")]
+    pub fn with_rng<R: rand_core::RngCore + Send + 'static>(self, rng: R) -> FeeModel {
+        FeeModel {
+            rng: Some(Arc::new(std::sync::Mutex::new(rng))),
+            ..self
+        }
+    }
+
+    /// Report the duration and the outcome of the estimates to `metrics`, like [`AtomicMetrics`],
+    /// shared by the clones of the model, see [`MetricsSink`] for the estimates reported. Without
+    /// a sink the estimates aren't timed.
+    #[cfg(feature = "std")]
+    pub fn with_metrics(self, metrics: Arc<dyn MetricsSink>) -> FeeModel {
+        FeeModel {
+            metrics: Some(metrics),
+            ..self
         }
     }
 
-    /// compute the fee estimation given the desired `block_target`
-    /// `timestamp` if None it's initialized to current time.
+    /// Declare the network the models have been trained for, [`Network::Bitcoin`] by default,
+    /// reported in [`FeeEstimate::network`]
+    pub fn with_network(self, network: Network) -> FeeModel {
+        FeeModel { network, ..self }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Description of the model used for the targets up to [`FeeModel::dispatch_threshold`], the
+    /// first member of an ensemble
+    pub fn low_model_info(&self) -> &ModelInfo {
+        &self.model_for(1).1[0].info
+    }
+
+    /// Description of the model used for the targets greater than
+    /// [`FeeModel::dispatch_threshold`], the first member of an ensemble
+    pub fn high_model_info(&self) -> &ModelInfo {
+        &self.model_for(self.high_target()).1[0].info
+    }
+
+    /// The greatest target estimated by the low model, the [`ModelInfo::max_target`] it declares or
+    /// 2 blocks, or the `max_target` of the first tier
+    pub fn dispatch_threshold(&self) -> u16 {
+        self.tiers[0].max_target
+    }
+
+    /// The first target of the high model, see [`FeeModel::dispatch_threshold`]
+    fn high_target(&self) -> u16 {
+        self.dispatch_threshold().saturating_add(1)
+    }
+
+    /// The greatest target the models have been trained for, the greater ones are handled as
+    /// configured in [`EstimateOptions::target_policy`]
+    pub fn max_target(&self) -> u16 {
+        let last = &self.tiers[self.tiers.len() - 1];
+        let max_targets = last.models.iter().filter_map(|m| m.info.max_target);
+        max_targets.min().unwrap_or(last.max_target)
+    }
+
+    /// The bound in seconds of the `delta_last` fed to the models, the smallest of
+    /// [`EstimateOptions::max_delta_last`] and of the [`ModelInfo::max_delta_last`] of the models.
+    /// `None` doesn't bound it.
+    pub fn max_delta_last(&self) -> Option<i64> {
+        let models = self.tiers.iter().flat_map(|t| t.models.iter());
+        let caps = models.filter_map(|m| m.info.max_delta_last);
+        caps.chain(self.options.max_delta_last).min()
+    }
+
+    /// SHA-256 digests of the models described by [`FeeModel::low_model_info`] and
+    /// [`FeeModel::high_model_info`], see [`ModelData::sha256`]
+    #[cfg(feature = "checksum")]
+    pub fn checksums(&self) -> ModelChecksums {
+        let low = self.model_for(1).1[0].sha256();
+        let high = self.model_for(self.high_target()).1[0].sha256();
+        ModelChecksums::new(low, high)
+    }
+
+    /// The buckets used in [`FeeModel::estimate`] to group fee rates, the ones of the models of
+    /// the first tier, see [`FeeModel::fee_buckets_for`]
+    pub fn fee_buckets(&self) -> &FeeBuckets {
+        &self.fee_buckets
+    }
+
+    /// The configuration of [`FeeModel::fee_buckets`], `None` after [`FeeModel::with_fee_buckets`]
+    pub fn bucket_config(&self) -> Option<&FeeModelConfig> {
+        self.bucket_config.as_ref()
+    }
+
+    /// The limits and the edge rules of [`FeeModel::fee_buckets`] as data, to group fee rates
+    /// outside of the crate exactly like [`FeeModel::estimate`] does, see [`BucketSpec`]. With
+    /// [`BucketScaling::Adaptive`] the estimates use other limits, depending on the fee rates.
+    pub fn bucket_spec(&self) -> BucketSpec {
+        self.fee_buckets.spec()
+    }
+
+    /// The buckets of the models estimating `block_target`, the ones
+    /// [`FeeModel::estimate_with_buckets`] expects for it
+    pub fn fee_buckets_for(&self, block_target: u16) -> &FeeBuckets {
+        self.tier_buckets(self.tier_for(block_target).1)
+    }
+
+    /// Create the fee model from CBOR encoded `low` (1,2 blocks) and `high` (3-1008 blocks) models,
+    /// or split at the target declared by `low`, see [`FeeModel::dispatch_threshold`]
+    #[cfg(feature = "std")]
+    pub fn from_readers<R1: Read, R2: Read>(low: R1, high: R2) -> Result<FeeModel, Error> {
+        let low = ModelData::from_reader(low)?;
+        let high = ModelData::from_reader(high)?;
+        FeeModel::from_loaded(low, high)
+    }
+
+    /// Like [`FeeModel::from_readers`], available also without the `std` feature
+    pub fn from_bytes(low: &[u8], high: &[u8]) -> Result<FeeModel, Error> {
+        let low = ModelData::from_slice(low)?;
+        let high = ModelData::from_slice(high)?;
+        FeeModel::from_loaded(low, high)
+    }
+
+    /// Create the fee model from CBOR files on the filesystem, errors report which file failed.
+    /// With the `postcard` feature the files may also be in the faster loading postcard layout of
+    /// [`ModelData::to_postcard`], detected by their first bytes.
+    #[cfg(feature = "std")]
+    pub fn from_paths(low: &Path, high: &Path) -> Result<FeeModel, Error> {
+        let low = load_model_file(low)?;
+        let high = load_model_file(high)?;
+        FeeModel::from_loaded(low, high)
+    }
+
+    /// Create the fee model from the tiers listed in the `manifest.json` of `path`, every model in
+    /// `<name>/model.cbor` with its `min_target` and `max_target`. Errors with
+    /// [`Error::ManifestFile`] if the manifest can't be read and with [`Error::TierGap`],
+    /// [`Error::TierOverlap`] or [`Error::EmptyTier`] if the ranges don't cover every target from 1
+    /// once.
+    #[cfg(feature = "json")]
+    pub fn from_directory(path: &Path) -> Result<FeeModel, Error> {
+        let tiers = registry::load_tiers(path)?;
+        FeeModel::from_tiers(tiers)?.check_loaded()
+    }
+
+    /// Like [`FeeModel::from_paths`], verifying the SHA-256 digests of the files, and their
+    /// signature if any, before loading them. Errors with [`Error::ChecksumMismatch`] if a file
+    /// has not the `expected` digest.
+    #[cfg(all(feature = "std", feature = "checksum"))]
+    pub fn from_paths_verified(
+        low: &Path,
+        high: &Path,
+        expected: &ModelChecksums,
+    ) -> Result<FeeModel, Error> {
+        expected.verify_signature()?;
+        let low = load_verified_model_file(low, &expected.low)?;
+        let high = load_verified_model_file(high, &expected.high)?;
+        FeeModel::from_loaded(low, high)
+    }
+
+    /// Like [`FeeModel::estimate`], but with `fee_buckets` already computed with [`FeeBuckets::get`],
+    /// so that the same buckets can be reused for many `block_target`, with the buckets of
+    /// [`FeeModel::fee_buckets_for`] the target
+    pub fn estimate_with_buckets(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_buckets: &[u64],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let prediction = self.measured(block_target, Prediction::outcome, || {
+            let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+            let (mut input, _) = self.build_input(now, fee_buckets, last_block)?;
+            let buckets = InputBuckets::new(fee_buckets, None);
+            self.predict_target(block_target, buckets, &mut input, None)
+        })?;
+        Ok(prediction.rate)
+    }
+
+    /// Like [`FeeModel::estimate`] with the buckets and the timestamp of the last block of
+    /// `provider`, for any source of fee rates, like a [`FeeWindow`] or a [`FeeHistogram`]
+    pub fn estimate_from<P: FeatureProvider + ?Sized>(
+        &self,
+        provider: &P,
+        block_target: u16,
+        timestamp: Option<i64>,
+    ) -> Result<f32, Error> {
+        let fee_buckets = provider.buckets(&self.fee_buckets)?;
+        let (now, last_block) = self.unix_millis(timestamp, provider.last_block_ts())?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = provider.fee_rates().and_then(|r| self.summary_extras(r));
+        let rebucket = |fee_buckets: &FeeBuckets, input: &mut ModelInput| {
+            let max_count = self.options.max_bucket_count;
+            fill_buckets(
+                &mut input.buckets,
+                &provider.buckets(fee_buckets)?,
+                max_count,
+            )
+        };
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        self.predict_target(block_target, buckets, &mut input, extras.as_ref())
+            .map(|e| e.rate)
+    }
+
+    /// Like [`FeeModel::estimate_with_buckets`] for every row of `inputs`, evaluated in parallel
+    /// on the rayon thread pool, results are in the order of `inputs`
+    #[cfg(feature = "rayon")]
+    pub fn estimate_batch(&self, inputs: &[BatchInput]) -> Vec<Result<f32, Error>> {
+        use rayon::prelude::*;
+
+        inputs
+            .par_iter()
+            .map(|i| {
+                self.estimate_with_buckets(
+                    i.block_target,
+                    Some(i.timestamp),
+                    i.fee_buckets,
+                    i.last_block_ts,
+                )
+            })
+            .collect()
+    }
+
+    /// compute the fee estimation in sat/vB given the desired `block_target`
+    /// `timestamp` unix time in seconds, if None it's initialized to current time, required without
+    /// the `chrono` feature.
     /// `fee_rates` contains the fee rates of transactions in the last 10 blocks, only for transactions
     /// having inputs in this last 10 blocks (so the fee rate is known)
-    /// `last_block_ts` last
+    /// `last_block_ts` unix time in seconds of the last block
+    /// Errors with [`Error::InsufficientData`] if `fee_rates` is empty and with
+    /// [`Error::InvalidInput`] or [`Error::InvalidTimestamp`] if an input is invalid.
     pub fn estimate(
         &self,
         block_target: u16,
-        timestamp: Option<u32>,
+        timestamp: Option<i64>,
         fee_rates: &[f64],
-        last_block_ts: u32,
+        last_block_ts: i64,
     ) -> Result<f32, Error> {
-        let fee_buckets = FeeBuckets::new(50, 500.0).get(fee_rates);
-        self.estimate_with_buckets(block_target, timestamp, &fee_buckets, last_block_ts)
+        self.estimate_request(&EstimateRequest {
+            block_target,
+            timestamp,
+            fee_rates,
+            last_block_ts,
+            min_fee: None,
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::model_data::tests::BUCKETS;
-    use crate::model_data::ModelData;
-    use crate::*;
-    use crate::{get_model_high, get_model_low};
-    use serde::Deserialize;
-    use std::collections::HashMap;
+    /// Like [`FeeModel::estimate`] with the prediction computed in double precision, to validate
+    /// the models against a reference implementation
+    pub fn estimate_f64(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<f64, Error> {
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let block_target = self.effective_target(block_target)?;
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let mut at = |target: u16| {
+            input.confirms_in = target as f32;
+            let tier = self.tier_for(target).1;
+            let (input, bucket_count) = tier_input(tier, &input, buckets)?;
+            let mut outputs = vec![];
+            for model in tier.models.iter() {
+                check_bucket_count(model, bucket_count)?;
+                let prediction = self.options.time_features.mean(&input, |input| {
+                    let prediction = model.norm_predict_input_f64(input)?;
+                    if !prediction.is_finite() {
+                        return Err(Error::NonFiniteOutput);
+                    }
+                    Ok(prediction)
+                })?;
+                outputs.push(prediction);
+            }
+            Ok::<_, Error>(self.options.combine.apply_f64(&outputs))
+        };
+        let blend = self
+            .options
+            .blend
+            .and_then(|range| range.high_weight(block_target).map(|w| (range, w as f64)));
+        let raw_output = match blend {
+            Some((range, weight)) => at(range.from)? * (1.0 - weight) + at(range.to)? * weight,
+            None => at(block_target)?,
+        };
+        let rate = self.options.clamp_f64(raw_output);
+        match self.options.rounding {
+            Rounding::None => Ok(rate),
+            _ => Ok(self.round(rate as f32, self.options.min_fee_rate)? as f64),
+        }
+    }
 
-    const EPS_1000: f32 = f32::EPSILON * 1000.0;
+    /// Explain the estimate of [`FeeModel::estimate`] with the attribution of every feature of the
+    /// model of `block_target`, sorted by decreasing absolute value
+    pub fn explain(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<Vec<(String, f32)>, Error> {
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let block_target = self.effective_target(block_target)?;
+        input.confirms_in = block_target as f32;
+        let tier = self.tier_for(block_target).1;
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let (input, bucket_count) = tier_input(tier, &input, buckets)?;
+        let models = &tier.models;
+        let mut members = vec![];
+        for model in models {
+            check_bucket_count(model, bucket_count)?;
+            let time_features = self.options.time_features;
+            members.push(time_features.mean_each(&input, |input| model.attributions(input))?);
+        }
+        // members have the same features, see `FeeModel::ensemble`
+        let attributions = models[0]
+            .feature_names()
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                let outputs: Vec<f32> = members.iter().map(|m| m[i]).collect();
+                (name.clone(), self.options.combine.apply(&outputs))
+            })
+            .collect();
+        Ok(model_data::sort_attributions(attributions))
+    }
 
-    pub fn assert_approx_eq(a: f32, b: f32) {
-        let delta = (a - b).abs();
-        assert!(
-            delta < EPS_1000,
-            "diff is {} eps_1000 is {}",
-            delta,
-            EPS_1000
-        );
+    /// Like [`FeeModel::estimate`] for models predicting quantiles, the estimates of `quantiles` in
+    /// the same order. Errors with [`Error::MissingQuantile`] if the models don't predict one of
+    /// them.
+    pub fn estimate_quantiles(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+        quantiles: &[f32],
+    ) -> Result<Vec<f32>, Error> {
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let block_target = self.effective_target(block_target)?;
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let mut at = |target: u16| {
+            input.confirms_in = target as f32;
+            let tier = self.tier_for(target).1;
+            let (input, bucket_count) = tier_input(tier, &input, buckets)?;
+            // for every quantile, the outputs of the members
+            let mut outputs = vec![Vec::new(); quantiles.len()];
+            for model in tier.models.iter() {
+                check_bucket_count(model, bucket_count)?;
+                let indexes = quantiles
+                    .iter()
+                    .map(|q| model.quantile_index(*q))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let time_features = self.options.time_features;
+                let predictions =
+                    time_features.mean_each(&input, |input| model.norm_predict_quantiles(input))?;
+                for (output, index) in outputs.iter_mut().zip(indexes) {
+                    let prediction = predictions[index];
+                    if !prediction.is_finite() {
+                        return Err(Error::NonFiniteOutput);
+                    }
+                    output.push(prediction);
+                }
+            }
+            let combine = |o: &Vec<f32>| self.options.combine.apply(o);
+            Ok(outputs.iter().map(combine).collect::<Vec<f32>>())
+        };
+        let blend = self
+            .options
+            .blend
+            .and_then(|range| range.high_weight(block_target).map(|w| (range, w)));
+        let raw_outputs = match blend {
+            Some((range, weight)) => {
+                let from = at(range.from)?;
+                let to = at(range.to)?;
+                from.iter()
+                    .zip(to)
+                    .map(|(from, to)| from * (1.0 - weight) + to * weight)
+                    .collect()
+            }
+            None => at(block_target)?,
+        };
+        raw_outputs
+            .into_iter()
+            .map(|o| self.round(self.options.clamp(o).0, self.options.min_fee_rate))
+            .collect()
     }
 
-    #[test]
-    pub fn test_estimate() {
-        let model = FeeModel::new(get_model_low(), get_model_high());
-        let ts = 1613708045u32;
-        let one = model
-            .estimate_with_buckets(1, Some(ts), &BUCKETS, ts - 300)
-            .unwrap();
-        let two = model
-            .estimate_with_buckets(2, Some(ts), &BUCKETS, ts - 300)
-            .unwrap();
-        assert!(one > two, "1 block ({}) > 2 ({})", one, two);
+    /// Like [`FeeModel::estimate`], with the current time `now` and the time of the `last_block`
+    /// as datetimes, the seconds since the last block keep the sub-second precision
+    #[cfg(feature = "chrono")]
+    pub fn estimate_at(
+        &self,
+        block_target: u16,
+        now: DateTime<Utc>,
+        fee_rates: &[f64],
+        last_block: DateTime<Utc>,
+    ) -> Result<f32, Error> {
+        self.estimate_millis(
+            block_target,
+            now.timestamp_millis(),
+            fee_rates,
+            last_block.timestamp_millis(),
+        )
     }
 
-    #[derive(Deserialize)]
-    struct TestVector {
-        test_vector: Vec<f32>,
-        result: f32,
+    /// Like [`FeeModel::estimate`] with the times as any type convertible to a [`Timestamp`].
+    /// Errors with [`Error::InvalidTimestamp`] for the times before 1970 or too far in the future.
+    pub fn estimate_at_time<T: Into<Timestamp>>(
+        &self,
+        block_target: u16,
+        timestamp: Option<T>,
+        fee_rates: &[f64],
+        last_block: T,
+    ) -> Result<f32, Error> {
+        let now = match timestamp {
+            Some(timestamp) => timestamp.into().unix_millis()?,
+            None => self.now()?,
+        };
+        let last_block = last_block.into().unix_millis()?;
+        self.estimate_millis(block_target, now, fee_rates, last_block)
     }
 
-    /// ensure the models are loaded correctly by using a test_vector created at the end of the training
-    /// for example ensure models are not swapped between high and low or parameters are swapped
-    #[test]
-    pub fn test_vector() {
-        // ensure the model is loaded correct
-        let model = FeeModel::new(get_model_low(), get_model_high());
+    fn estimate_millis(
+        &self,
+        block_target: u16,
+        now: i64,
+        fee_rates: &[f64],
+        last_block: i64,
+    ) -> Result<f32, Error> {
+        self.predict_millis(block_target, now, fee_rates, last_block)
+            .map(|p| p.rate)
+    }
 
-        let bytes_low = include_bytes!("../models/20211027-180849/test_vector.cbor");
-        let bytes_high = include_bytes!("../models/20211027-180925/test_vector.cbor");
+    /// The prediction of [`FeeModel::estimate_millis`]
+    fn predict_millis(
+        &self,
+        block_target: u16,
+        now: i64,
+        fee_rates: &[f64],
+        last_block: i64,
+    ) -> Result<Prediction, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        self.predict_target(block_target, buckets, &mut input, extras.as_ref())
+    }
 
-        test_single_vector(&model.low, bytes_low);
-        test_single_vector(&model.high, bytes_high);
+    /// Like [`FeeModel::estimate`] for models using features not in [`ModelInput`], taken from
+    /// `extras` by name. Errors with [`Error::MissingExtra`] if an extra used by a model is
+    /// missing.
+    pub fn estimate_with_extras(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+        extras: &BTreeMap<String, f32>,
+    ) -> Result<f32, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let with_computed;
+        let extras = if self.uses_summary() || self.uses_bucket_limit() {
+            let mut computed = self.computed_extras(fee_rates);
+            computed.extend(extras.iter().map(|(k, v)| (k.clone(), *v)));
+            with_computed = computed;
+            &with_computed
+        } else {
+            extras
+        };
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        self.predict_target(block_target, buckets, &mut input, Some(extras))
+            .map(|e| e.rate)
     }
 
-    fn test_single_vector(model: &ModelData<Size20, Size64, Size1>, bytes: &[u8]) {
-        let test: TestVector = serde_cbor::from_slice(&bytes[..]).unwrap();
+    /// Like [`FeeModel::estimate`] with the time features set in `overrides` instead of derived
+    /// from the timestamps. Errors with [`Error::InvalidOverride`] for an override out of its
+    /// range.
+    pub fn estimate_with_features(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+        overrides: FeatureOverrides,
+    ) -> Result<f32, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        overrides.apply(&mut input)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        self.predict_target(block_target, buckets, &mut input, extras.as_ref())
+            .map(|e| e.rate)
+    }
 
-        let mut input = HashMap::new();
-        for (i, field) in model.fields.iter().enumerate() {
-            input.insert(field.to_owned(), test.test_vector[i]);
-        }
-        let result = model.norm_predict(&input).unwrap();
-        assert_approx_eq(result, test.result);
+    /// Like [`FeeModel::estimate`] with an Electrum style fee `histogram` of `(fee_rate, vsize)`
+    /// pairs, every [`HISTOGRAM_TX_VSIZE`] vB counted as a transaction
+    pub fn estimate_from_histogram(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        histogram: &[(f64, u64)],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let histogram = self.sanitize_weighted(histogram, "histogram")?;
+        let fee_buckets = self
+            .fee_buckets
+            .get_weighted(&histogram, HISTOGRAM_TX_VSIZE);
+        self.estimate_with_buckets(block_target, timestamp, &fee_buckets, last_block_ts)
+    }
+
+    /// Like [`FeeModel::estimate`] with the `(fee_rate, vsize)` of every transaction, adding its
+    /// vsize to its bucket instead of 1. Errors with [`Error::UnweightedModel`] if a model is not
+    /// trained on weighted buckets, see [`ModelInfo::weighted_buckets`].
+    pub fn estimate_weighted(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        rates_and_vsizes: &[(f64, u64)],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        for (kind, model) in self.members() {
+            if !model.info.weighted_buckets {
+                return Err(Error::UnweightedModel(kind));
+            }
+        }
+        let rates_and_vsizes = self.sanitize_weighted(rates_and_vsizes, "rates_and_vsizes")?;
+        let fee_buckets = self.fee_buckets.get_weighted(&rates_and_vsizes, 1);
+        self.estimate_with_buckets(block_target, timestamp, &fee_buckets, last_block_ts)
+    }
+
+    /// Like [`FeeModel::estimate`] with the fee rates grouped by block, so that with
+    /// [`EstimateOptions::recency_half_life`] the transactions of older blocks count less in the
+    /// buckets. Without it the estimate is the one of the fee rates of every block.
+    pub fn estimate_from_blocks(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        blocks: &[BlockFeeRates],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let block_rates = blocks
+            .iter()
+            .map(|b| self.sanitize(&b.rates))
+            .collect::<Result<Vec<_>, _>>()?;
+        let fee_rates: Vec<f64> = block_rates.iter().flat_map(|r| r.iter().copied()).collect();
+        let fee_buckets = self.buckets(&fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let half_life = self.options.recency_half_life;
+        let max_count = self.options.max_bucket_count;
+        if half_life.is_some_and(|h| h.is_nan() || h <= 0.0) {
+            return Err(Error::InvalidInput("recency_half_life"));
+        }
+        let rebucket = |fee_buckets: &FeeBuckets, input: &mut ModelInput| {
+            fill_buckets(
+                &mut input.buckets,
+                &BucketCounts::new(fee_buckets, &fee_rates)?,
+                max_count,
+            )?;
+            if let Some(half_life) = half_life {
+                let mut weighted = [0.0f64; MAX_BUCKETS];
+                for (block, rates) in blocks.iter().zip(block_rates.iter()) {
+                    let weight = libm::exp2(-(block.height_offset as f64) / half_life);
+                    let counts = fee_buckets.get(rates);
+                    for (bucket, count) in weighted.iter_mut().zip(counts) {
+                        *bucket += count as f64 * weight;
+                    }
+                }
+                for (bucket, weighted) in input.buckets.iter_mut().zip(weighted.iter()) {
+                    *bucket = count_feature(*weighted, max_count);
+                }
+            }
+            Ok(())
+        };
+        if half_life.is_some() {
+            rebucket(&*self.scaled_buckets(&fee_rates)?, &mut input)?;
+        }
+        let extras = self.summary_extras(&fee_rates);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        self.predict_target(block_target, buckets, &mut input, extras.as_ref())
+            .map(|e| e.rate)
+    }
+
+    /// Like [`FeeModel::estimate`], but returns a [`FeeRate`] instead of a plain sat/vB value
+    pub fn estimate_rate(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<FeeRate, Error> {
+        self.estimate(block_target, timestamp, fee_rates, last_block_ts)
+            .map(FeeRate::from)
+    }
+
+    /// Like [`FeeModel::estimate`], returns the fee in satoshi, rounded up, of a transaction of
+    /// `tx_weight_wu` weight units, never lower than [`EstimateOptions::min_fee`]
+    pub fn estimate_fee(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+        tx_weight_wu: u64,
+    ) -> Result<u64, Error> {
+        let rate = self.estimate(block_target, timestamp, fee_rates, last_block_ts)?;
+        Ok(self.fee_for_weight(rate, tx_weight_wu))
+    }
+
+    fn fee_for_weight(&self, rate: f32, weight_wu: u64) -> u64 {
+        let vsize = weight_wu.div_ceil(4);
+        // a `rate` like 1.1 is slightly greater than 1.1 in `f32`, without the tolerance 10 vB
+        // would pay 12 sat
+        let fee = rate as f64 * vsize as f64 * (1.0 - f32::EPSILON as f64);
+        (libm::ceil(fee) as u64).max(self.options.min_fee)
+    }
+
+    /// Like [`FeeModel::estimate_fee`] with the weight of `tx`
+    #[cfg(feature = "use-bitcoin")]
+    pub fn estimate_tx_fee(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+        tx: &bitcoin::Transaction,
+    ) -> Result<u64, Error> {
+        let weight = tx.get_weight() as u64;
+        self.estimate_fee(block_target, timestamp, fee_rates, last_block_ts, weight)
+    }
+
+    /// Like [`FeeModel::estimate_fee`] for the transaction of `psbt` once signed, its weight
+    /// estimated from the kind of its inputs. The inputs of unknown kind, counted in
+    /// [`PsbtFeeAdvice::unknown_inputs`], are sized like p2pkh ones.
+    #[cfg(feature = "use-bitcoin")]
+    pub fn fee_for_psbt(
+        &self,
+        psbt: &bitcoin::util::psbt::PartiallySignedTransaction,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<PsbtFeeAdvice, Error> {
+        let rate = self.estimate(block_target, timestamp, fee_rates, last_block_ts)?;
+        let (weight, unknown_inputs) = psbt::estimate_signed_weight(psbt);
+        Ok(PsbtFeeAdvice {
+            fee: self.fee_for_weight(rate, weight),
+            vsize: weight.div_ceil(4),
+            rate,
+            unknown_inputs,
+        })
+    }
+
+    /// Like [`FeeModel::estimate`] at the current time with the fee rates of the window fetched
+    /// from `source`, the prediction runs once the window is available. Without the `chrono`
+    /// feature it errors with [`Error::MissingTimestamp`].
+    pub async fn estimate_from_source<S: AsyncFeeSource>(
+        &self,
+        source: &S,
+        block_target: u16,
+    ) -> Result<f32, Error> {
+        let window = source.fee_window().await?;
+        self.estimate(block_target, None, &window.fee_rates, window.last_block_ts)
+    }
+
+    /// Like [`FeeModel::estimate`], shaped like the result of the `estimatesmartfee` RPC of Bitcoin
+    /// Core, with the fee rate in BTC/kvB and the target used in `blocks`
+    pub fn estimate_smart(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<SmartFeeResult, Error> {
+        let blocks = block_target.clamp(1, self.max_target());
+        let rate = self.estimate_rate(blocks, timestamp, fee_rates, last_block_ts)?;
+        Ok(SmartFeeResult::new(rate, blocks))
+    }
+
+    /// Like [`FeeModel::estimate`], but returns the model used and its inputs with the estimate
+    pub fn estimate_detailed(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, Error> {
+        let outcome = |e: &FeeEstimate| (e.model, e.clamped);
+        self.measured(block_target, outcome, || {
+            self.detailed(block_target, timestamp, fee_rates, None, last_block_ts)
+        })
+    }
+
+    /// Like [`FeeModel::estimate`] with the fee rates of only the last `blocks_covered` blocks, the
+    /// bucket counts scaled to a whole [`WINDOW`]. Errors with [`Error::InsufficientData`] if
+    /// `blocks_covered` is lower than [`EstimateOptions::min_blocks_covered`] and with
+    /// [`Error::InvalidInput`] if it's zero or greater than `WINDOW`.
+    pub fn estimate_partial(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        blocks_covered: u8,
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let estimate = self.estimate_partial_detailed(
+            block_target,
+            timestamp,
+            fee_rates,
+            blocks_covered,
+            last_block_ts,
+        )?;
+        Ok(estimate.rate)
+    }
+
+    /// Like [`FeeModel::estimate_partial`] with the context of [`FeeModel::estimate_detailed`],
+    /// the estimates from fewer than [`WINDOW`] blocks are flagged as degraded in
+    /// [`FeeEstimate::blocks_covered`]
+    pub fn estimate_partial_detailed(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        blocks_covered: u8,
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, Error> {
+        let outcome = |e: &FeeEstimate| (e.model, e.clamped);
+        self.measured(block_target, outcome, || {
+            if blocks_covered == 0 || blocks_covered as usize > WINDOW {
+                return Err(Error::InvalidInput("blocks_covered"));
+            }
+            if blocks_covered < self.options.min_blocks_covered {
+                return Err(Error::InsufficientData);
+            }
+            let partial = Some(blocks_covered).filter(|b| (*b as usize) < WINDOW);
+            self.detailed(block_target, timestamp, fee_rates, partial, last_block_ts)
+        })
+    }
+
+    /// Like [`FeeModel::estimate`] consuming the fee rates of an iterator without collecting them,
+    /// unless the models need the fee rates themselves, like the ones using [`SUMMARY_FEATURES`]
+    pub fn estimate_iter<I: IntoIterator<Item = f64>>(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: I,
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("estimate", block_target).entered();
+        let prediction = self.measured(block_target, Prediction::outcome, || {
+            let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+            match self.stream(fee_rates)? {
+                StreamedRates::Rates(fee_rates) => {
+                    self.predict_millis(block_target, now, &fee_rates, last_block)
+                }
+                StreamedRates::Counts { counts, .. } => {
+                    let (mut input, _) = self.build_input(now, &counts, last_block)?;
+                    let buckets = InputBuckets::new(&counts, None);
+                    self.predict_target(block_target, buckets, &mut input, None)
+                }
+            }
+        })?;
+        Ok(prediction.rate)
+    }
+
+    /// Like [`FeeModel::estimate_iter`] with the context of [`FeeModel::estimate_detailed`], the
+    /// number of fee rates consumed in [`FeeEstimate::fee_rates_consumed`]
+    pub fn estimate_iter_detailed<I: IntoIterator<Item = f64>>(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: I,
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, Error> {
+        let outcome = |e: &FeeEstimate| (e.model, e.clamped);
+        self.measured(block_target, outcome, || match self.stream(fee_rates)? {
+            StreamedRates::Rates(fee_rates) => {
+                self.detailed(block_target, timestamp, &fee_rates, None, last_block_ts)
+            }
+            StreamedRates::Counts {
+                counts,
+                consumed,
+                filtered,
+            } => {
+                let buckets = InputBuckets::new(&counts, None);
+                let estimate = self.detailed_buckets(
+                    block_target,
+                    timestamp,
+                    &counts,
+                    buckets,
+                    None,
+                    last_block_ts,
+                )?;
+                Ok(FeeEstimate {
+                    filtered_fee_rates: filtered,
+                    fee_rates_consumed: consumed,
+                    ..estimate
+                })
+            }
+        })
+    }
+
+    /// The estimate of [`FeeModel::estimate_detailed`], with the bucket counts scaled to a whole
+    /// window if the fee rates cover only `partial` blocks, see [`FeeModel::estimate_partial`]
+    fn detailed(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        partial: Option<u8>,
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, Error> {
+        let fee_rates_consumed = fee_rates.len();
+        let sanitized = &self.sanitize(fee_rates)?;
+        let filtered_fee_rates = fee_rates.len() - sanitized.len();
+        let fee_rates = sanitized;
+        let scale = partial.map_or(1.0, |blocks| WINDOW as f64 / blocks as f64);
+        let fee_buckets = self.buckets(fee_rates)?.scaled(scale);
+        let extras = self.summary_extras(fee_rates);
+        let max_count = self.options.max_bucket_count;
+        let rebucket = |fee_buckets: &FeeBuckets, input: &mut ModelInput| {
+            let counts = BucketCounts::new(fee_buckets, fee_rates)?.scaled(scale);
+            fill_buckets(&mut input.buckets, &counts, max_count)
+        };
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let estimate = self.detailed_buckets(
+            block_target,
+            timestamp,
+            &fee_buckets,
+            buckets,
+            extras.as_ref(),
+            last_block_ts,
+        )?;
+        Ok(FeeEstimate {
+            filtered_fee_rates,
+            fee_rates_consumed,
+            blocks_covered: partial,
+            ..estimate
+        })
+    }
+
+    /// The estimate of [`FeeModel::detailed`] from the `fee_buckets` of the fee rates, with
+    /// no fee rates filtered or consumed
+    fn detailed_buckets(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_buckets: &[u64],
+        buckets: InputBuckets,
+        extras: Option<&BTreeMap<String, f32>>,
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, Error> {
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, bounded) = self.build_input(now, fee_buckets, last_block)?;
+        let prediction = self.predict_target(block_target, buckets, &mut input, extras)?;
+        let uncertainty_hint = if self.options.uncertainty_hint {
+            let target = prediction.effective_target;
+            Some(self.uncertainty_hint(target, prediction.rate, buckets, &input, extras)?)
+        } else {
+            None
+        };
+        Ok(FeeEstimate {
+            rate: prediction.rate,
+            unrounded_rate: prediction.unrounded_rate,
+            model: prediction.model,
+            block_target,
+            effective_target: prediction.effective_target,
+            network: self.network,
+            inputs: input,
+            raw_output: prediction.raw_output,
+            member_outputs: prediction.member_outputs.as_slice().to_vec(),
+            clamped: prediction.clamped,
+            delta_last_clamped: bounded.delta_last,
+            buckets_capped: bounded.buckets,
+            filtered_fee_rates: 0,
+            fee_rates_consumed: 0,
+            duplicate_txs: 0,
+            dispatch_threshold: self.dispatch_threshold(),
+            staleness: self.options.staleness(now, last_block),
+            uncertainty_hint,
+            out_of_range_features: prediction.out_of_range_features,
+            blocks_covered: None,
+        })
+    }
+
+    /// Like [`FeeModel::estimate`] from the fee rates of transactions identified by their id,
+    /// counting a transaction once, with the fee rate of its first occurrence
+    pub fn estimate_from_txs<I: Ord>(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        txs: &[(I, f64)],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let (fee_rates, _) = dedup_txs(txs);
+        self.estimate(block_target, timestamp, &fee_rates, last_block_ts)
+    }
+
+    /// Like [`FeeModel::estimate_from_txs`], with the context of
+    /// [`FeeModel::estimate_detailed`] and the number of transactions counted more than once in
+    /// [`FeeEstimate::duplicate_txs`]
+    pub fn estimate_detailed_from_txs<I: Ord>(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        txs: &[(I, f64)],
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, Error> {
+        let (fee_rates, duplicate_txs) = dedup_txs(txs);
+        let estimate =
+            self.estimate_detailed(block_target, timestamp, &fee_rates, last_block_ts)?;
+        Ok(FeeEstimate {
+            duplicate_txs,
+            ..estimate
+        })
+    }
+
+    /// The disagreement of the predictions around the estimate `rate` of `target`, see
+    /// [`FeeEstimate::uncertainty_hint`]
+    fn uncertainty_hint(
+        &self,
+        target: u16,
+        rate: f32,
+        buckets: InputBuckets,
+        input: &ModelInput,
+        extras: Option<&BTreeMap<String, f32>>,
+    ) -> Result<f32, Error> {
+        let mut input = input.clone();
+        let below = target.saturating_sub(1).max(1);
+        let above = target.saturating_add(1).min(self.max_target()).max(target);
+        let mut rate_at = |t: u16| {
+            let prediction = self.predict_target(t, buckets, &mut input, extras)?;
+            Ok::<_, Error>(prediction.rate)
+        };
+        let mut spread = match above - below {
+            0 => 0.0,
+            span => (rate_at(below)? - rate_at(above)?).abs() / span as f32,
+        };
+        let boundary = self.tiers.windows(2).find(|pair| {
+            let max_target = pair[0].max_target;
+            target == max_target || target == max_target.saturating_add(1)
+        });
+        if let Some(pair) = boundary {
+            input.confirms_in = target as f32;
+            let predict = |tier: &Tier| {
+                let (input, bucket_count) = tier_input(tier, &input, buckets)?;
+                let outputs = tier
+                    .models
+                    .iter()
+                    .map(|model| {
+                        let time_features = self.options.time_features;
+                        predict_model(model, bucket_count, &input, extras, time_features)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok::<_, Error>(self.options.clamp(self.options.combine.apply(&outputs)).0)
+            };
+            spread = spread.max((predict(&pair[0])? - predict(&pair[1])?).abs());
+        }
+        Ok(if spread == 0.0 { 0.0 } else { spread / rate })
+    }
+
+    /// Like [`FeeModel::estimate`], with the estimates of both the low and the high model for
+    /// `block_target` regardless of the model chosen for it
+    pub fn estimate_both(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<BothEstimates, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let estimate = self.predict_target(block_target, buckets, &mut input, extras.as_ref())?;
+        let predict = |tier: &Tier| {
+            let (input, bucket_count) = tier_input(tier, &input, buckets)?;
+            let outputs = tier
+                .models
+                .iter()
+                .map(|model| {
+                    let time_features = self.options.time_features;
+                    predict_model(model, bucket_count, &input, extras.as_ref(), time_features)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let rate = self.options.clamp(self.options.combine.apply(&outputs)).0;
+            self.round(rate, self.options.min_fee_rate)
+        };
+        Ok(BothEstimates {
+            low: predict(self.tier_for(1).1)?,
+            high: predict(self.tier_for(self.high_target()).1)?,
+            rate: estimate.rate,
+            model: estimate.model,
+        })
+    }
+
+    /// Like [`FeeModel::estimate`] to confirm within `duration`, interpolated between the estimates
+    /// of the whole numbers of blocks around it, one every [`BLOCK_INTERVAL`]
+    pub fn estimate_within(
+        &self,
+        duration: Duration,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let blocks = duration.as_secs_f64() / BLOCK_INTERVAL.as_secs_f64();
+        let blocks = blocks.clamp(1.0, u16::MAX as f64);
+        let below = libm::floor(blocks);
+        let weight = (blocks - below) as f32;
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let mut at = |target: u16| {
+            self.predict_target(target, buckets, &mut input, extras.as_ref())
+                .map(|e| e.unrounded_rate)
+        };
+        let mut rate = at(below as u16)?;
+        if weight != 0.0 {
+            rate = rate * (1.0 - weight) + at(below as u16 + 1)? * weight;
+        }
+        self.round(rate, self.options.min_fee_rate)
+    }
+
+    /// Like [`FeeModel::estimate`] for every target in `targets`, results are in the same order.
+    /// With [`EstimateOptions::enforce_monotonic`] results never increase with the target.
+    pub fn estimate_targets(
+        &self,
+        targets: &[u16],
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<Vec<f32>, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        // a marginalized estimate predicts at every time, target by target
+        let multi_output = self
+            .multi_output()
+            .filter(|_| !self.options.time_features.is_marginalized());
+        let mut estimates = match multi_output {
+            Some(model) => {
+                check_bucket_count(model, buckets.count)?;
+                let outputs = model_outputs(model, &input, extras.as_ref())?;
+                targets
+                    .iter()
+                    .map(|target| {
+                        let output = model.target_output(&outputs, self.effective_target(*target)?);
+                        if !output.is_finite() {
+                            return Err(Error::NonFiniteOutput);
+                        }
+                        let rate = self.options.clamp(self.options.combine.apply(&[output])).0;
+                        self.round(rate, self.options.min_fee_rate)
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            None => targets
+                .iter()
+                .map(|target| {
+                    self.predict_target(*target, buckets, &mut input, extras.as_ref())
+                        .map(|e| e.rate)
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        if self.options.enforce_monotonic {
+            estimate::clamp_monotonic(targets, &mut estimates);
+        }
+        Ok(estimates)
+    }
+
+    /// Estimate every target in [`DEFAULT_TARGETS`] in a single pass.
+    /// Targets greater than [`FeeModel::max_target`] are omitted from the result instead of
+    /// clamped or rejected.
+    pub fn estimate_all(
+        &self,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<BTreeMap<u16, f32>, Error> {
+        let targets: Vec<u16> = DEFAULT_TARGETS
+            .iter()
+            .copied()
+            .filter(|t| *t <= self.max_target())
+            .collect();
+        let estimates = self.estimate_targets(&targets, timestamp, fee_rates, last_block_ts)?;
+        Ok(targets.into_iter().zip(estimates).collect())
+    }
+
+    /// The smallest target estimated at most `fee_rate` (sat/vB), between 1 and
+    /// [`FeeModel::max_target`]. Errors with [`Error::FeeRateTooLow`] if even the estimate of the
+    /// max target is greater.
+    pub fn targets_for_rate(
+        &self,
+        fee_rate: f32,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<u16, Error> {
+        let fee_rates = &self.sanitize(fee_rates)?;
+        let fee_buckets = self.buckets(fee_rates)?;
+        let (now, last_block) = self.unix_millis(timestamp, last_block_ts)?;
+        let (mut input, _) = self.build_input(now, &fee_buckets, last_block)?;
+        let extras = self.summary_extras(fee_rates);
+        let rebucket = rebucket_rates(fee_rates, self.options.max_bucket_count);
+        let buckets = InputBuckets::new(&fee_buckets, Some(&rebucket));
+        let mut within = |target: u16| {
+            self.predict_target(target, buckets, &mut input, extras.as_ref())
+                .map(|e| e.rate <= fee_rate)
+        };
+        let max_target = self.max_target();
+        if !self.options.enforce_monotonic {
+            for target in 1..=max_target {
+                if within(target)? {
+                    return Ok(target);
+                }
+            }
+            return Err(Error::FeeRateTooLow(fee_rate));
+        }
+        if !within(max_target)? {
+            return Err(Error::FeeRateTooLow(fee_rate));
+        }
+        if within(1)? {
+            return Ok(1);
+        }
+        // the estimate of `above` is greater than `fee_rate`, the one of `below` is not
+        let (mut above, mut below) = (1, max_target);
+        while below - above > 1 {
+            let middle = above + (below - above) / 2;
+            if within(middle)? {
+                below = middle;
+            } else {
+                above = middle;
+            }
+        }
+        Ok(below)
+    }
+
+    /// Whether a model uses the [`SUMMARY_FEATURES`]
+    fn uses_summary(&self) -> bool {
+        let mut models = self.members();
+        models.any(|(_, m)| summary::uses_summary(m.extra_fields()))
+    }
+
+    /// Whether a model uses the limit of the buckets, see [`FeeModel::with_bucket_scaling`]
+    fn uses_bucket_limit(&self) -> bool {
+        let mut models = self.members();
+        models.any(|(_, m)| m.extra_fields().any(|f| f == BUCKET_LIMIT))
+    }
+
+    /// The extras computed from `fee_rates` used by the models: the [`SUMMARY_FEATURES`] and the
+    /// bucket limit
+    fn computed_extras(&self, fee_rates: &[f64]) -> BTreeMap<String, f32> {
+        let mut extras = if self.uses_summary() {
+            summary_features(fee_rates).to_extras()
+        } else {
+            BTreeMap::new()
+        };
+        if self.uses_bucket_limit() {
+            let limit = self.bucket_limit(fee_rates);
+            extras.insert(BUCKET_LIMIT.to_string(), limit as f32);
+        }
+        extras
+    }
+
+    /// The [`FeeModel::computed_extras`] of `fee_rates` and zero for the other extras of the
+    /// models, like in the estimates without extras, `None` if no model uses computed extras
+    fn summary_extras(&self, fee_rates: &[f64]) -> Option<BTreeMap<String, f32>> {
+        if !self.uses_summary() && !self.uses_bucket_limit() {
+            return None;
+        }
+        let mut extras = self.computed_extras(fee_rates);
+        for (_, model) in self.members() {
+            for field in model.extra_fields() {
+                extras.entry(field.to_string()).or_insert(0.0);
+            }
+        }
+        Some(extras)
+    }
+
+    /// Check `fee_rates` are finite and not negative, dropping the ones filtered by
+    /// [`EstimateOptions::filter_fee_rates`]
+    fn sanitize<'a>(&self, fee_rates: &'a [f64]) -> Result<Cow<'a, [f64]>, Error> {
+        for rate in fee_rates {
+            check_fee_rate(*rate, "fee_rates")?;
+        }
+        match self.options.filter_fee_rates {
+            Some(max) => {
+                let plausible = |rate: &f64| *rate != 0.0 && *rate <= max;
+                Ok(Cow::Owned(
+                    fee_rates.iter().copied().filter(plausible).collect(),
+                ))
+            }
+            None => Ok(Cow::Borrowed(fee_rates)),
+        }
+    }
+
+    /// Like [`FeeModel::sanitize`] with `(fee_rate, vsize)` pairs, erroring with
+    /// [`Error::InvalidInput`] of `input` for a fee rate not finite
+    fn sanitize_weighted<'a>(
+        &self,
+        pairs: &'a [(f64, u64)],
+        input: &'static str,
+    ) -> Result<Cow<'a, [(f64, u64)]>, Error> {
+        for (rate, _) in pairs {
+            check_fee_rate(*rate, input)?;
+        }
+        match self.options.filter_fee_rates {
+            Some(max) => {
+                let plausible = |(rate, _): &(f64, u64)| *rate != 0.0 && *rate <= max;
+                Ok(Cow::Owned(
+                    pairs.iter().copied().filter(plausible).collect(),
+                ))
+            }
+            None => Ok(Cow::Borrowed(pairs)),
+        }
+    }
+
+    /// Whether the estimates need the fee rates and not only their buckets, see
+    /// [`FeeModel::estimate_iter`]
+    fn needs_fee_rates(&self) -> bool {
+        self.uses_summary()
+            || self.uses_bucket_limit()
+            || self.bucket_scaling != BucketScaling::Fixed
+            || self.tiers.iter().any(|tier| tier.fee_buckets.is_some())
+    }
+
+    /// The fee rates of `fee_rates` checked and filtered like [`FeeModel::sanitize`] and counted
+    /// in the buckets, or collected if [`FeeModel::needs_fee_rates`]
+    fn stream<I: IntoIterator<Item = f64>>(&self, fee_rates: I) -> Result<StreamedRates, Error> {
+        if self.needs_fee_rates() {
+            return Ok(StreamedRates::Rates(fee_rates.into_iter().collect()));
+        }
+        let (mut consumed, mut filtered, mut invalid) = (0, 0, None);
+        let max = self.options.filter_fee_rates;
+        let checked = fee_rates.into_iter().map_while(|rate| {
+            consumed += 1;
+            if !rate.is_finite() {
+                invalid = Some(Error::InvalidInput("fee_rates"));
+            } else if rate < 0.0 {
+                invalid = Some(Error::InvalidInput("negative fee rate"));
+            }
+            Some(rate).filter(|_| invalid.is_none())
+        });
+        let plausible = checked.filter(|rate| match max {
+            Some(max) if *rate == 0.0 || *rate > max => {
+                filtered += 1;
+                false
+            }
+            _ => true,
+        });
+        let counts = BucketCounts::from_rates(&self.fee_buckets, plausible)?;
+        match invalid {
+            Some(error) => Err(error),
+            None => Ok(StreamedRates::Counts {
+                counts,
+                consumed,
+                filtered,
+            }),
+        }
+    }
+
+    /// The fee buckets of `fee_rates`, checked by [`FeeModel::sanitize`]
+    fn buckets(&self, fee_rates: &[f64]) -> Result<BucketCounts, Error> {
+        BucketCounts::new(&*self.scaled_buckets(fee_rates)?, fee_rates)
+    }
+
+    /// The buckets of `fee_rates` as configured in [`FeeModel::with_bucket_scaling`]
+    fn scaled_buckets(&self, fee_rates: &[f64]) -> Result<Cow<'_, FeeBuckets>, Error> {
+        match self
+            .bucket_scaling
+            .limit(fee_rates, self.bucket_increment())
+        {
+            Some(limit) => {
+                let fee_buckets = FeeBuckets::with_count(self.fee_buckets.len(), limit)?;
+                Ok(Cow::Owned(fee_buckets.with_mode(self.fee_buckets.mode())))
+            }
+            None => Ok(Cow::Borrowed(&self.fee_buckets)),
+        }
+    }
+
+    /// The upper limit of the buckets of `fee_rates`, see [`FeeModel::with_bucket_scaling`]
+    fn bucket_limit(&self, fee_rates: &[f64]) -> f64 {
+        let scaled = self
+            .bucket_scaling
+            .limit(fee_rates, self.bucket_increment());
+        let fixed = || {
+            self.fee_buckets
+                .limits()
+                .last()
+                .copied()
+                .unwrap_or_default()
+        };
+        scaled.unwrap_or_else(fixed)
+    }
+
+    /// The bucket increment declared in the metadata of the first model declaring one, the one of
+    /// [`FeeModelConfig::default`] otherwise
+    fn bucket_increment(&self) -> u32 {
+        let mut configs = self
+            .members()
+            .filter_map(|(_, m)| m.info.bucket_config.as_ref());
+        let config = configs.next().cloned().unwrap_or_default();
+        config.bucket_increment_percent
+    }
+
+    /// The target the models estimate for `block_target`, bounded to [`FeeModel::max_target`] as
+    /// configured in [`EstimateOptions::target_policy`]
+    fn effective_target(&self, block_target: u16) -> Result<u16, Error> {
+        let max_target = self.max_target();
+        match (block_target, self.options.target_policy) {
+            (0, _) => Err(Error::InvalidTarget(0)),
+            (target, _) if target <= max_target => Ok(target),
+            (_, TargetPolicy::Clamp) => Ok(max_target),
+            (target, TargetPolicy::Reject) => Err(Error::InvalidTarget(target)),
+        }
+    }
+
+    /// The first tier with `max_target` greater or equal to `block_target`, the last one for
+    /// greater targets, see [`FeeModel::from_tiers`]
+    fn tier_for(&self, block_target: u16) -> (ModelKind, &Tier) {
+        let index = self
+            .tiers
+            .iter()
+            .position(|t| t.max_target >= block_target)
+            .unwrap_or(self.tiers.len() - 1);
+        (Tier::kind(index).0, &self.tiers[index])
+    }
+
+    /// The models of the tier of `block_target`, see [`FeeModel::tier_for`]
+    fn model_for(&self, block_target: u16) -> (ModelKind, &[ModelData]) {
+        let (kind, tier) = self.tier_for(block_target);
+        (kind, &tier.models)
+    }
+
+    fn tier_buckets<'a>(&'a self, tier: &'a Tier) -> &'a FeeBuckets {
+        tier.fee_buckets.as_ref().unwrap_or(&self.fee_buckets)
+    }
+
+    /// Every model of every tier, named like in the errors
+    fn members(&self) -> impl Iterator<Item = (&'static str, &ModelData)> {
+        let tiers = self.tiers.iter().enumerate();
+        tiers.flat_map(|(i, t)| t.models.iter().map(move |m| (Tier::kind(i).1, m)))
+    }
+
+    /// Set `confirms_in` in `input`, predict with the model trained for `block_target` and bound
+    /// the result as configured in the options, without allocating for the models predicting
+    /// without allocations, see [`ModelData::norm_predict_slice`]
+    fn predict_target(
+        &self,
+        block_target: u16,
+        buckets: InputBuckets,
+        input: &mut ModelInput,
+        extras: Option<&BTreeMap<String, f32>>,
+    ) -> Result<Prediction, Error> {
+        let effective_target = self.effective_target(block_target)?;
+        input.confirms_in = effective_target as f32;
+        let blend = self
+            .options
+            .blend
+            .and_then(|range| range.high_weight(effective_target).map(|w| (range, w)));
+        let mut member_outputs = MemberOutputs::new();
+        let mut out_of_range_features = Vec::new();
+        let mut predict = |tier: &Tier, input: &ModelInput| {
+            let (input, bucket_count) = tier_input(tier, input, buckets)?;
+            let start = member_outputs.as_slice().len();
+            for model in tier.models.iter() {
+                self.check_ranges(model, &input, extras, &mut out_of_range_features)?;
+                let time_features = self.options.time_features;
+                let output = predict_model(model, bucket_count, &input, extras, time_features)?;
+                member_outputs.push(output);
+            }
+            let outputs = &member_outputs.as_slice()[start..];
+            Ok::<_, Error>(self.options.combine.apply(outputs))
+        };
+        let (kind, raw_output) = match blend {
+            Some((range, weight)) => {
+                input.confirms_in = range.from as f32;
+                let from = predict(self.tier_for(range.from).1, input)?;
+                input.confirms_in = range.to as f32;
+                let to = predict(self.tier_for(range.to).1, input)?;
+                input.confirms_in = effective_target as f32;
+                (ModelKind::Blended, from * (1.0 - weight) + to * weight)
+            }
+            None => {
+                let (kind, tier) = self.tier_for(effective_target);
+                (kind, predict(tier, input)?)
+            }
+        };
+        let (unrounded_rate, clamped) = self.options.clamp(raw_output);
+        let rate = self.round(unrounded_rate, self.options.min_fee_rate)?;
+        #[cfg(feature = "tracing")]
+        {
+            tracing::debug!(
+                block_target,
+                model = ?kind,
+                delta_last = input.delta_last,
+                buckets = ?&input.buckets[..buckets.count],
+                "predicted"
+            );
+            tracing::trace!(raw_output, rate, clamped, "bounded");
+        }
+        Ok(Prediction {
+            rate,
+            unrounded_rate,
+            model: kind,
+            effective_target,
+            raw_output,
+            member_outputs,
+            clamped,
+            out_of_range_features,
+        })
+    }
+
+    /// Run `estimate`, reporting its duration and its outcome, the model used and whether the
+    /// prediction has been clamped, to the sink of [`FeeModel::with_metrics`] if any
+    #[cfg(feature = "std")]
+    fn measured<T>(
+        &self,
+        block_target: u16,
+        outcome: impl FnOnce(&T) -> (ModelKind, bool),
+        estimate: impl FnOnce() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let metrics = match &self.metrics {
+            Some(metrics) => metrics,
+            None => return estimate(),
+        };
+        let start = std::time::Instant::now();
+        let result = estimate();
+        metrics.record(&EstimateEvent {
+            block_target,
+            duration: start.elapsed(),
+            outcome: result.as_ref().map(|r| {
+                let (model, clamped) = outcome(r);
+                EstimateOutcome { model, clamped }
+            }),
+        });
+        result
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn measured<T>(
+        &self,
+        _block_target: u16,
+        _outcome: impl FnOnce(&T) -> (ModelKind, bool),
+        estimate: impl FnOnce() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        estimate()
+    }
+
+    /// Round `rate` as configured in [`EstimateOptions::rounding`], within `min_fee_rate` and
+    /// [`EstimateOptions::max_fee_rate`]
+    pub(crate) fn round(&self, rate: f32, min_fee_rate: f32) -> Result<f32, Error> {
+        let rounded = self.options.rounding.apply(rate, || self.uniform())?;
+        Ok(self.options.clamp_with_min(rounded, min_fee_rate).0)
+    }
+
+    /// A number between 0 and 1 drawn from the generator of [`FeeModel::with_rng`]
+    #[cfg(feature = "rand")]
+    fn uniform(&self) -> Result<f32, Error> {
+        let rng = self.rng.as_ref().ok_or(Error::MissingRng)?;
+        let mut rng = rng
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        Ok((rng.next_u32() >> 8) as f32 / (1u32 << 24) as f32)
+    }
+
+    #[cfg(not(feature = "rand"))]
+    fn uniform(&self) -> Result<f32, Error> {
+        Err(Error::MissingRng)
+    }
+
+    /// Add the fields of `input` out of the ranges of `model` to `out_of_range` with
+    /// [`Strictness::Warn`], error with them with [`Strictness::Strict`], see
+    /// [`ModelData::out_of_range_features`]
+    fn check_ranges(
+        &self,
+        model: &ModelData,
+        input: &ModelInput,
+        extras: Option<&BTreeMap<String, f32>>,
+        out_of_range: &mut Vec<String>,
+    ) -> Result<(), Error> {
+        if self.options.strictness == Strictness::Permissive {
+            return Ok(());
+        }
+        let fields = model.out_of_range_features(input, extras);
+        if self.options.strictness == Strictness::Strict && !fields.is_empty() {
+            return Err(Error::OutOfDistribution(fields));
+        }
+        for field in fields {
+            if !out_of_range.contains(&field) {
+                out_of_range.push(field);
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert the unix timestamps in seconds of the public API to milliseconds, `None`
+    /// `timestamp` is the time of the clock
+    fn unix_millis(&self, timestamp: Option<i64>, last_block_ts: i64) -> Result<(i64, i64), Error> {
+        let now = match timestamp {
+            Some(timestamp) => time::millis(timestamp)?,
+            None => self.now()?,
+        };
+        Ok((now, time::millis(last_block_ts)?))
+    }
+
+    #[cfg(feature = "chrono")]
+    fn now(&self) -> Result<i64, Error> {
+        Ok((self.clock)().timestamp_millis())
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    fn now(&self) -> Result<i64, Error> {
+        Err(Error::MissingTimestamp)
+    }
+
+    /// Build the model input with every feature except `confirms_in` from the unix times in
+    /// milliseconds `now` and `last_block`, with the features bounded. Errors with
+    /// [`Error::InsufficientData`] if the buckets are all empty and with [`Error::InvalidInput`] or
+    /// [`Error::StaleChainTip`] for a last block too far from `now`.
+    fn build_input(
+        &self,
+        now: i64,
+        fee_buckets: &[u64],
+        last_block: i64,
+    ) -> Result<(ModelInput, Bounded), Error> {
+        let mut buckets = [0.0f32; MAX_BUCKETS];
+        let max_count = self.options.max_bucket_count;
+        fill_buckets(&mut buckets, fee_buckets, max_count)?;
+        // no buckets at all is reported as a wrong bucket count by the model
+        if !fee_buckets.is_empty() && fee_buckets.iter().all(|b| *b == 0) {
+            return Err(Error::InsufficientData);
+        }
+        self.options.check_last_block(now, last_block)?;
+        if let Staleness::Stale { age } = self.options.staleness(now, last_block) {
+            if self.options.reject_stale {
+                return Err(Error::StaleChainTip(age));
+            }
+        }
+        let delta = now.saturating_sub(last_block) as f64 / 1000.0;
+        let max_delta_last = self.max_delta_last();
+        let (delta, delta_clamped) = self.options.clamp_delta_last(delta, max_delta_last);
+        let delta_last = saturating_f32(delta);
+        if !delta_last.is_finite() {
+            return Err(Error::InvalidInput("delta_last"));
+        }
+
+        let mut input = ModelInput {
+            confirms_in: 0.0,
+            day_of_week: time::day_of_week(now) as f32,
+            hour: time::hour(now) as f32,
+            delta_last,
+            buckets,
+        };
+        self.options.time_features.apply(&mut input)?;
+        let bounded = Bounded {
+            delta_last: delta_clamped,
+            buckets: max_count.is_some_and(|max| fee_buckets.iter().any(|b| *b > max)),
+        };
+        Ok((input, bounded))
+    }
+}
+
+/// The features of [`FeeModel::build_input`] changed to stay within the bounds of the options
+#[derive(Debug, Clone, Copy)]
+struct Bounded {
+    delta_last: bool,
+    buckets: bool,
+}
+
+/// Set the buckets of a [`ModelInput`] to `fee_buckets`, lowered to `max_count` if given, and
+/// the unused ones to zero, errors if there are more than [`MAX_BUCKETS`]
+fn fill_buckets(
+    buckets: &mut [f32; MAX_BUCKETS],
+    fee_buckets: &[u64],
+    max_count: Option<u64>,
+) -> Result<(), Error> {
+    if fee_buckets.len() > MAX_BUCKETS {
+        return Err(Error::WrongBucketCount {
+            expected: MAX_BUCKETS,
+            got: fee_buckets.len(),
+        });
+    }
+    *buckets = [0.0; MAX_BUCKETS];
+    for (bucket, fee_bucket) in buckets.iter_mut().zip(fee_buckets.iter()) {
+        *bucket = count_feature(*fee_bucket as f64, max_count);
+    }
+    Ok(())
+}
+
+/// The counts of [`FeeBuckets::get`] on the stack, see [`FeeBuckets::get_into`]
+#[derive(Debug, Clone, Copy)]
+struct BucketCounts {
+    counts: [u64; MAX_BUCKETS],
+    len: usize,
+}
+
+impl BucketCounts {
+    /// Errors if there are more than [`MAX_BUCKETS`] buckets, like the inputs of the models
+    fn new(fee_buckets: &FeeBuckets, fee_rates: &[f64]) -> Result<Self, Error> {
+        BucketCounts::from_rates(fee_buckets, fee_rates.iter().copied())
+    }
+
+    /// Like [`BucketCounts::new`] consuming the rates of an iterator
+    fn from_rates<I: IntoIterator<Item = f64>>(
+        fee_buckets: &FeeBuckets,
+        fee_rates: I,
+    ) -> Result<Self, Error> {
+        let len = fee_buckets.len();
+        if len > MAX_BUCKETS {
+            return Err(Error::WrongBucketCount {
+                expected: MAX_BUCKETS,
+                got: len,
+            });
+        }
+        let mut counts = [0; MAX_BUCKETS];
+        fee_buckets.count(fee_rates, &mut counts[..len]);
+        Ok(BucketCounts { counts, len })
+    }
+
+    /// The counts multiplied by `factor` and rounded to the nearest integer
+    fn scaled(mut self, factor: f64) -> Self {
+        if factor != 1.0 {
+            for count in self.counts[..self.len].iter_mut() {
+                *count = libm::round(*count as f64 * factor) as u64;
+            }
+        }
+        self
+    }
+}
+
+impl core::ops::Deref for BucketCounts {
+    type Target = [u64];
+
+    fn deref(&self) -> &[u64] {
+        &self.counts[..self.len]
+    }
+}
+
+/// The estimate of [`FeeModel::predict_target`], the parts of a [`FeeEstimate`] not allocating
+struct Prediction {
+    rate: f32,
+    /// see [`FeeEstimate::unrounded_rate`]
+    unrounded_rate: f32,
+    model: ModelKind,
+    effective_target: u16,
+    raw_output: f32,
+    member_outputs: MemberOutputs,
+    clamped: bool,
+    /// see [`FeeEstimate::out_of_range_features`]
+    out_of_range_features: Vec<String>,
+}
+
+impl Prediction {
+    /// The outcome reported to the sink of [`FeeModel::with_metrics`]
+    fn outcome(&self) -> (ModelKind, bool) {
+        (self.model, self.clamped)
+    }
+}
+
+/// The fee rates of an iterator, see [`FeeModel::stream`], the counts not boxed as they don't
+/// outlive the estimate
+#[allow(clippy::large_enum_variant)]
+enum StreamedRates {
+    /// collected, for the models needing them
+    Rates(Vec<f64>),
+    /// counted in [`FeeModel::fee_buckets`], with the number of rates consumed and filtered
+    Counts {
+        counts: BucketCounts,
+        consumed: usize,
+        filtered: usize,
+    },
+}
+
+/// Computes the buckets of a [`ModelInput`] again with the given [`FeeBuckets`]
+type Rebucket<'a> = &'a dyn Fn(&FeeBuckets, &mut ModelInput) -> Result<(), Error>;
+
+/// The buckets of a [`ModelInput`] built with [`FeeModel::fee_buckets`]: their number and how
+/// to compute them for the tiers with their own buckets, `None` if they are given already
+/// computed
+#[derive(Clone, Copy)]
+struct InputBuckets<'a> {
+    count: usize,
+    rebucket: Option<Rebucket<'a>>,
+}
+
+impl<'a> InputBuckets<'a> {
+    fn new(fee_buckets: &[u64], rebucket: Option<Rebucket<'a>>) -> Self {
+        InputBuckets {
+            count: fee_buckets.len(),
+            rebucket,
+        }
+    }
+}
+
+/// The fee rates of `txs` counting every id once, with the first fee rate of the id, and the
+/// number of transactions dropped
+fn dedup_txs<I: Ord>(txs: &[(I, f64)]) -> (Vec<f64>, usize) {
+    let mut seen = BTreeSet::new();
+    let fee_rates: Vec<f64> = txs
+        .iter()
+        .filter(|(id, _)| seen.insert(id))
+        .map(|(_, rate)| *rate)
+        .collect();
+    let duplicates = txs.len() - fee_rates.len();
+    (fee_rates, duplicates)
+}
+
+/// Check the fee rate `rate` is finite, else [`Error::InvalidInput`] of `input`, and not negative
+fn check_fee_rate(rate: f64, input: &'static str) -> Result<(), Error> {
+    if !rate.is_finite() {
+        return Err(Error::InvalidInput(input));
+    }
+    if rate < 0.0 {
+        return Err(Error::InvalidInput("negative fee rate"));
+    }
+    Ok(())
+}
+
+/// The bucket count `count` as a feature, lowered to `max_count` if given: counts and weighted
+/// counts are finite and exact in single precision up to 2^24
+fn count_feature(count: f64, max_count: Option<u64>) -> f32 {
+    let count = match max_count {
+        Some(max) => count.min(max as f64),
+        None => count,
+    };
+    saturating_f32(count)
+}
+
+/// `value` in single precision, the greatest finite `f32` of its sign if out of their range
+/// instead of infinite, NaN stays NaN
+fn saturating_f32(value: f64) -> f32 {
+    value.clamp(f32::MIN as f64, f32::MAX as f64) as f32
+}
+
+/// Group `fee_rates` again, see [`InputBuckets`]
+fn rebucket_rates(
+    fee_rates: &[f64],
+    max_count: Option<u64>,
+) -> impl Fn(&FeeBuckets, &mut ModelInput) -> Result<(), Error> + '_ {
+    move |fee_buckets, input| {
+        let counts = BucketCounts::new(fee_buckets, fee_rates)?;
+        fill_buckets(&mut input.buckets, &counts, max_count)
+    }
+}
+
+/// The input of the models of `tier` with its number of buckets: `input` with the buckets of the
+/// tier if it has its own and they can be computed
+fn tier_input<'a>(
+    tier: &Tier,
+    input: &'a ModelInput,
+    buckets: InputBuckets,
+) -> Result<(Cow<'a, ModelInput>, usize), Error> {
+    match (&tier.fee_buckets, buckets.rebucket) {
+        (Some(fee_buckets), Some(rebucket)) => {
+            let mut input = input.clone();
+            rebucket(fee_buckets, &mut input)?;
+            Ok((Cow::Owned(input), fee_buckets.len()))
+        }
+        _ => Ok((Cow::Borrowed(input), buckets.count)),
+    }
+}
+
+/// The prediction of `model`, averaged over the time features of `time_features` if
+/// [`TimeFeatures::Marginalize`]
+fn predict_model(
+    model: &ModelData,
+    bucket_count: usize,
+    input: &ModelInput,
+    extras: Option<&BTreeMap<String, f32>>,
+    time_features: TimeFeatures,
+) -> Result<f32, Error> {
+    check_bucket_count(model, bucket_count)?;
+    if time_features.is_marginalized() {
+        let mean = time_features.mean(input, |input| {
+            predict_once(model, input, extras).map(f64::from)
+        })?;
+        return Ok(mean as f32);
+    }
+    predict_once(model, input, extras)
+}
+
+/// The prediction of `model` for `input`, checked to be finite
+fn predict_once(
+    model: &ModelData,
+    input: &ModelInput,
+    extras: Option<&BTreeMap<String, f32>>,
+) -> Result<f32, Error> {
+    let prediction = match extras {
+        _ if !model.output_targets().is_empty() => {
+            let outputs = model_outputs(model, input, extras)?;
+            model.target_output(&outputs, input.confirms_in as u16)
+        }
+        Some(extras) => model.norm_predict_with_extras(input, extras)?,
+        None => model.norm_predict_input(input)?,
+    };
+    if !prediction.is_finite() {
+        return Err(Error::NonFiniteOutput);
+    }
+    Ok(prediction)
+}
+
+/// Every output of a model with [`ModelData::output_targets`]
+fn model_outputs(
+    model: &ModelData,
+    input: &ModelInput,
+    extras: Option<&BTreeMap<String, f32>>,
+) -> Result<Vec<f32>, Error> {
+    match extras {
+        Some(extras) => model.norm_predict_outputs_with_extras(input, extras),
+        None => model.norm_predict_quantiles(input),
+    }
+}
+
+/// Check `model` expects the features of [`ModelInput`] with `bucket_count` buckets, but
+/// `confirms_in` for the models with [`ModelData::output_targets`]
+fn check_features(model: &ModelData, kind: &'static str, bucket_count: usize) -> Result<(), Error> {
+    let mut expected = ModelInput::feature_names(bucket_count);
+    if !model.output_targets().is_empty() {
+        expected.retain(|f| f != "confirms_in");
+    }
+    let fields = model.feature_names();
+    let missing: Vec<String> = expected
+        .iter()
+        .filter(|f| !fields.contains(f))
+        .cloned()
+        .collect();
+    let extra: Vec<String> = fields
+        .iter()
+        .filter(|f| !expected.contains(f))
+        .cloned()
+        .collect();
+    if !missing.is_empty() || !extra.is_empty() {
+        return Err(Error::FeatureMismatch {
+            model: kind,
+            missing,
+            extra,
+        });
+    }
+    Ok(())
+}
+
+fn check_bucket_count(model: &ModelData, bucket_count: usize) -> Result<(), Error> {
+    let expected = model.bucket_count();
+    if bucket_count != expected {
+        return Err(Error::WrongBucketCount {
+            expected,
+            got: bucket_count,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+fn load_model_file(path: &Path) -> Result<ModelData, Error> {
+    File::open(path)
+        .map_err(Error::Io)
+        .and_then(|f| load_model_reader(BufReader::new(f)))
+        .map_err(|e| Error::ModelFile(path.to_path_buf(), Box::new(e)))
+}
+
+/// Load a CBOR model or, with the `postcard` feature, a model in the postcard layout detected
+/// by its magic
+#[cfg(feature = "std")]
+fn load_model_reader<R: Read>(reader: R) -> Result<ModelData, Error> {
+    let bytes = model_data::read_limited(reader, &ModelLimits::default())?;
+    load_model_slice(&bytes)
+}
+
+/// Like [`load_model_reader`] from `bytes`
+#[cfg(feature = "std")]
+fn load_model_slice(bytes: &[u8]) -> Result<ModelData, Error> {
+    #[cfg(feature = "postcard")]
+    if compact::is_postcard(bytes) {
+        return ModelData::from_postcard(bytes);
+    }
+    ModelData::from_slice(bytes)
+}
+
+/// Inflate a zlib compressed embedded model, bounded by the default [`ModelLimits::max_bytes`]
+#[cfg(feature = "embedded-models")]
+fn decompress(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let max_bytes = ModelLimits::default().max_bytes;
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(bytes, max_bytes)
+        .map_err(|_| Error::Decompress)
+}
+
+#[cfg(all(feature = "std", feature = "checksum"))]
+fn load_verified_model_file(path: &Path, expected: &[u8; 32]) -> Result<ModelData, Error> {
+    std::fs::read(path)
+        .map_err(Error::Io)
+        .and_then(|bytes| {
+            checksum::verify(expected, &bytes)?;
+            load_model_slice(&bytes)
+        })
+        .map_err(|e| Error::ModelFile(path.to_path_buf(), Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model_data::tests::{
+        get_test_model_with_extra, get_test_model_with_hour_renamed, with_field_renamed, BUCKETS,
+    };
+    use crate::*;
+
+    const EPS_1000: f32 = f32::EPSILON * 1000.0;
+
+    /// Unix time in seconds of the estimates of the tests, 2021-02-19 04:14:05 UTC
+    pub const SAMPLE_TS: i64 = 1613708045;
+
+    /// The fee rates of the last blocks of the estimates of the tests, 1000 transactions from 1 to
+    /// 300 sat/vB
+    pub fn sample_rates() -> Vec<f64> {
+        (0..1000).map(|i| 1.0 + (i % 300) as f64).collect()
+    }
+
+    /// The models of the repo, loaded from the filesystem so that they are available without the
+    /// `embedded-models` feature
+    pub fn get_fee_model() -> FeeModel {
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        FeeModel::from_paths(low, high).unwrap()
+    }
+
+    pub fn get_low_model() -> ModelData {
+        get_fee_model().tiers.remove(0).models.remove(0)
+    }
+
+    pub fn get_high_model() -> ModelData {
+        get_fee_model().tiers.remove(1).models.remove(0)
+    }
+
+    pub fn assert_approx_eq(a: f32, b: f32) {
+        let delta = (a - b).abs();
+        assert!(
+            delta < EPS_1000,
+            "diff is {} eps_1000 is {}",
+            delta,
+            EPS_1000
+        );
+    }
+
+    #[cfg(feature = "embedded-models")]
+    mod embedded {
+        use super::{assert_approx_eq, sample_rates, SAMPLE_TS};
+        use crate::model_data::tests::BUCKETS;
+        use crate::model_data::ModelData;
+        use crate::{Error, FeeModel};
+        use serde::Deserialize;
+        use std::collections::HashMap;
+
+        /// Equality of predictions with golden values computed by the default matrix product,
+        /// which `ndarray` computes adding the products in another order
+        fn assert_backend_eq(a: f32, b: f32) {
+            #[cfg(not(feature = "ndarray"))]
+            assert_eq!(a, b);
+            #[cfg(feature = "ndarray")]
+            assert_approx_eq(a, b);
+        }
+
+        /// The raw outputs of the embedded models must be the same bit for bit on every platform,
+        /// see [`crate::matrix::Matrix::dot_axpy`]
+        #[cfg(not(feature = "ndarray"))]
+        #[test]
+        fn test_golden_outputs() {
+            // target and bits of the raw output of the two inputs
+            let golden: [(u16, u32, u32); 28] = [
+                (1, 0x4227bf4e, 0x420d47b8),
+                (2, 0x4203c2d2, 0x418771b7),
+                (3, 0x41fd945e, 0xc1aec382),
+                (4, 0x41fb1f3e, 0xc1afd60a),
+                (5, 0x4204086e, 0xc1b0e87c),
+                (6, 0x42049be6, 0xc1b1fb04),
+                (7, 0x4204bee4, 0xc1ae3a8a),
+                (8, 0x42027b35, 0xc19b3939),
+                (9, 0x41dc38ba, 0xc18cca10),
+                (10, 0x41b6cbc4, 0xc17d50b4),
+                (11, 0x41a4b24c, 0xc16e8764),
+                (12, 0x4194070d, 0xc15fbe0c),
+                (13, 0x4183f992, 0xc150f4ac),
+                (14, 0x41806318, 0xc1422ad8),
+                (15, 0x417a6662, 0xc13361c0),
+                (16, 0x41740648, 0xc1249842),
+                (17, 0x417380a7, 0xc115cee0),
+                (18, 0x4178dc49, 0xc105a95e),
+                (19, 0x417e3806, 0xc0ccea10),
+                (20, 0x4181c9be, 0xc08ea89e),
+                (21, 0x418477a2, 0xc020cef4),
+                (22, 0x4187256b, 0xbf210d60),
+                (23, 0x4189d33e, 0x3f965cb8),
+                (24, 0x418c8104, 0x403e9fe0),
+                (25, 0x418f2ee6, 0x4099093e),
+                (144, 0x41accee0, 0x419e8df4),
+                (504, 0x40eb52d8, 0x40fc95be),
+                (1008, 0x40f78ebc, 0x41035aba),
+            ];
+            let model = FeeModel::new().unwrap();
+            let ts = SAMPLE_TS;
+            let rates = sample_rates();
+            let later = ts + 200_000;
+            let other: Vec<f64> = (0..300)
+                .map(|i| 1.0 + (i * 7 % 200) as f64 * 0.37)
+                .collect();
+            for (target, first, second) in golden.iter() {
+                let raw = |ts: i64, rates: &[f64], last: i64| {
+                    let detailed = model.estimate_detailed(*target, Some(ts), rates, last);
+                    detailed.unwrap().raw_output.to_bits()
+                };
+                assert_eq!(raw(ts, &rates, ts - 300), *first, "{}", target);
+                assert_eq!(raw(later, &other, later - 1200), *second, "{}", target);
+            }
+            let targets: Vec<u16> = golden.iter().map(|g| g.0).collect();
+            assert_eq!(targets, crate::DEFAULT_TARGETS);
+        }
+
+        #[test]
+        fn test_estimate_f64() {
+            // evaluated in float64 by a python implementation of the keras dense layers, from
+            // the weights of the embedded models and the inputs of `estimate`
+            let golden = [
+                (1, 41.9367711278776),
+                (2, 32.940286285273174),
+                (3, 31.697431885951225),
+                (6, 33.15220592546021),
+                (12, 18.50345093878108),
+                (144, 21.600989169114563),
+                (1008, 7.736140559475945),
+            ];
+            let model = FeeModel::new().unwrap();
+            let ts = SAMPLE_TS;
+            let rates = sample_rates();
+            for (target, expected) in golden.iter() {
+                let estimate = model.estimate_f64(*target, Some(ts), &rates, ts - 300);
+                let estimate = estimate.unwrap();
+                assert!(
+                    (estimate - expected).abs() < 1e-9,
+                    "{} {}",
+                    target,
+                    estimate
+                );
+                let single = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+                assert!((estimate - single as f64).abs() < 1e-4 * estimate);
+            }
+            assert!(matches!(
+                model.estimate_f64(6, Some(ts), &[], ts - 300),
+                Err(Error::InsufficientData)
+            ));
+            assert!(matches!(
+                model.tiers[0].models[0].norm_predict_f64(&[1.0]),
+                Err(Error::DimensionMismatch { layer: "input", .. })
+            ));
+        }
+
+        #[test]
+        pub fn test_decompress() {
+            let golden = [
+                (1, 41.93682),
+                (2, 32.940254),
+                (3, 31.697445),
+                (6, 33.152245),
+                (12, 18.503443),
+                (144, 21.601013),
+                (1008, 7.7361736),
+            ];
+            let model = FeeModel::new().unwrap();
+            let raw = crate::tests::get_fee_model();
+            let ts = SAMPLE_TS;
+            let rates = sample_rates();
+            for (target, expected) in golden.iter() {
+                let estimate = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+                assert_backend_eq(estimate, *expected);
+                let raw_estimate = raw.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+                assert_eq!(estimate, raw_estimate);
+            }
+
+            let compressed = include_bytes!("../models/20211027-180849/model.cbor.zz");
+            let err = super::super::decompress(&compressed[..100]).unwrap_err();
+            assert!(matches!(err, Error::Decompress));
+            assert!(super::super::decompress(b"not zlib").is_err());
+        }
+
+        #[cfg(feature = "rayon")]
+        #[test]
+        fn test_estimate_batch() {
+            use crate::BatchInput;
+
+            let model = FeeModel::new().unwrap();
+            let ts = SAMPLE_TS;
+            let short = [1u64; 3];
+            let inputs: Vec<BatchInput> = (0..2000)
+                .map(|i| BatchInput {
+                    block_target: [1, 2, 3, 6, 144, 1008][i % 6],
+                    timestamp: ts + i as i64 * 97,
+                    fee_buckets: if i % 100 == 7 { &short } else { &BUCKETS },
+                    last_block_ts: ts - (i as i64 % 50) * 60,
+                })
+                .collect();
+            let batch = model.estimate_batch(&inputs);
+            assert_eq!(batch.len(), inputs.len());
+            for (input, result) in inputs.iter().zip(batch) {
+                let expected = model.estimate_with_buckets(
+                    input.block_target,
+                    Some(input.timestamp),
+                    input.fee_buckets,
+                    input.last_block_ts,
+                );
+                match (result, expected) {
+                    (Ok(a), Ok(b)) => assert_eq!(a.to_bits(), b.to_bits()),
+                    (Err(a), Err(b)) => assert_eq!(a.to_string(), b.to_string()),
+                    (a, b) => panic!("{:?} {:?}", a, b),
+                }
+            }
+            assert!(model.estimate_batch(&[]).is_empty());
+        }
+
+        #[test]
+        pub fn test_shared() {
+            fn assert_send_sync<T: Send + Sync>() {}
+            assert_send_sync::<FeeModel>();
+
+            let barrier = std::sync::Arc::new(std::sync::Barrier::new(8));
+            let threads: Vec<_> = (0..8)
+                .map(|_| {
+                    let barrier = barrier.clone();
+                    std::thread::spawn(move || {
+                        barrier.wait();
+                        FeeModel::shared() as *const FeeModel as usize
+                    })
+                })
+                .collect();
+            let shared = FeeModel::shared() as *const FeeModel as usize;
+            for thread in threads {
+                assert_eq!(thread.join().unwrap(), shared);
+            }
+            assert!(std::ptr::eq(FeeModel::shared(), FeeModel::shared()));
+        }
+
+        #[test]
+        pub fn test_static_estimate() {
+            let ts = SAMPLE_TS;
+            let rates = sample_rates();
+            assert!(std::ptr::eq(crate::init(), FeeModel::shared()));
+            for target in [1u16, 6, 144].iter() {
+                let expected = FeeModel::shared().estimate(*target, Some(ts), &rates, ts - 300);
+                let rate = crate::static_estimate(*target, Some(ts), &rates, ts - 300);
+                assert_eq!(rate.unwrap(), expected.unwrap());
+            }
+            let err = crate::static_estimate(0, Some(ts), &rates, ts - 300);
+            assert!(matches!(err, Err(Error::InvalidTarget(0))));
+        }
+
+        #[test]
+        pub fn test_new() {
+            let model = FeeModel::new().unwrap();
+            assert_eq!(model.tiers[0].models[0].fields.len(), 20);
+            assert_eq!(model.tiers[1].models[0].fields.len(), 20);
+            let info = model.high_model_info();
+            assert_eq!(info.name.as_deref(), Some("20211027-180925"));
+            assert_eq!(info.bucket_config, Some(crate::FeeModelConfig::default()));
+            assert_eq!(
+                model.low_model_info().name.as_deref(),
+                Some("20211027-180849")
+            );
+            let default = FeeModel::default();
+            assert_eq!(format!("{:?}", default), format!("{:?}", model));
+        }
+
+        #[test]
+        pub fn test_estimate() {
+            let model = FeeModel::new().unwrap();
+            let ts = SAMPLE_TS;
+            let one = model
+                .estimate_with_buckets(1, Some(ts), &BUCKETS, ts - 300)
+                .unwrap();
+            let two = model
+                .estimate_with_buckets(2, Some(ts), &BUCKETS, ts - 300)
+                .unwrap();
+            assert!(one > two, "1 block ({}) > 2 ({})", one, two);
+        }
+
+        #[derive(Deserialize)]
+        struct TestVector {
+            test_vector: Vec<f32>,
+            result: f32,
+        }
+
+        #[test]
+        pub fn test_wrong_bucket_count() {
+            let model = FeeModel::new().unwrap();
+            let ts = SAMPLE_TS;
+            for len in [0usize, 15, 17].iter() {
+                let buckets = vec![1u64; *len];
+                for target in [1u16, 6].iter() {
+                    let err = model
+                        .estimate_with_buckets(*target, Some(ts), &buckets, ts - 300)
+                        .unwrap_err();
+                    assert!(matches!(
+                        err,
+                        Error::WrongBucketCount { expected: 16, got } if got == *len
+                    ));
+                }
+            }
+        }
+
+        /// ensure the models are loaded correctly by using a test_vector created at the end of the training
+        /// for example ensure models are not swapped between high and low or parameters are swapped
+        #[test]
+        pub fn test_vector() {
+            // ensure the model is loaded correct
+            let model = FeeModel::new().unwrap();
+
+            let bytes_low = include_bytes!("../models/20211027-180849/test_vector.cbor");
+            let bytes_high = include_bytes!("../models/20211027-180925/test_vector.cbor");
+
+            test_single_vector(&model.tiers[0].models[0], bytes_low);
+            test_single_vector(&model.tiers[1].models[0], bytes_high);
+        }
+
+        fn test_single_vector(model: &ModelData, bytes: &[u8]) {
+            let test: TestVector = serde_cbor::from_slice(bytes).unwrap();
+
+            let mut input = HashMap::new();
+            for (i, field) in model.fields.iter().enumerate() {
+                input.insert(field.to_owned(), test.test_vector[i]);
+            }
+            let result = model.norm_predict(&input).unwrap();
+            assert_approx_eq(result, test.result);
+        }
+    }
+
+    #[test]
+    pub fn test_from_readers() {
+        let low = include_bytes!("../models/20211027-180849/model.cbor");
+        let high = include_bytes!("../models/20211027-180925/model.cbor");
+        let model = FeeModel::from_readers(&low[..], &high[..]).unwrap();
+        let ts = SAMPLE_TS;
+        let low_path = Path::new("models/20211027-180849/model.cbor");
+        let high_path = Path::new("models/20211027-180925/model.cbor");
+        let expected = FeeModel::from_paths(low_path, high_path)
+            .unwrap()
+            .estimate_with_buckets(6, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        let result = model
+            .estimate_with_buckets(6, Some(ts), &BUCKETS, ts - 300)
+            .unwrap();
+        assert_approx_eq(result, expected);
+
+        let err = FeeModel::from_bytes(&low[..100], &high[..]);
+        assert!(matches!(err, Err(Error::Deserialize(_))));
+    }
+
+    #[test]
+    pub fn test_with_config() {
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        let model = FeeModel::from_paths(low, high).unwrap();
+        assert_eq!(
+            model.fee_buckets().limits(),
+            FeeBuckets::new(50, 500.0).limits()
+        );
+        let ts = SAMPLE_TS;
+        let rates = [1.0, 2.0, 5.0, 10.0, 30.0, 100.0, 1000.0];
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+
+        let model = model.with_config(FeeModelConfig::default()).unwrap();
+        let result = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_approx_eq(result, expected);
+
+        let config = FeeModelConfig {
+            bucket_increment_percent: 100,
+            bucket_limit: 2000.0,
+            ..Default::default()
+        };
+        let err = model.with_config(config).err().unwrap();
+        assert!(matches!(
+            err,
+            Error::WrongBucketCount {
+                expected: 16,
+                got: 11
+            }
+        ));
+
+        let model = FeeModel::from_paths(low, high).unwrap();
+        let fee_buckets = FeeBuckets::logarithmic(16, 1.0, 1000.0).unwrap();
+        let model = model.with_fee_buckets(fee_buckets.clone()).unwrap();
+        assert_eq!(model.fee_buckets().limits(), fee_buckets.limits());
+        let log_estimate = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert!(log_estimate.is_finite());
+    }
+
+    #[test]
+    pub fn test_bucket_count_from_model() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let eight = Path::new("models/fixtures/buckets8.cbor");
+        let thirty_two = Path::new("models/fixtures/buckets32.cbor");
+        for (path, count) in [(eight, 8usize), (thirty_two, 32)].iter() {
+            let model = FeeModel::from_paths(path, path).unwrap();
+            assert_eq!(model.fee_buckets().len(), *count);
+            assert_eq!(model.fee_buckets().limits()[count - 1], 500.0);
+            model.validate_features().unwrap();
+            for target in [1u16, 2, 6, 144].iter() {
+                let estimate = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+                assert!(estimate.is_finite());
+                let buckets = model.fee_buckets().get(&rates);
+                let with_buckets =
+                    model.estimate_with_buckets(*target, Some(ts), &buckets, ts - 300);
+                assert_eq!(with_buckets.unwrap(), estimate);
+            }
+        }
+
+        // every tier gets the buckets of its models
+        let model = FeeModel::from_paths(eight, thirty_two).unwrap();
+        assert_eq!(model.fee_buckets_for(1).len(), 8);
+        assert_eq!(model.fee_buckets_for(6).len(), 32);
+        model.validate_features().unwrap();
+        let low = FeeModel::from_paths(eight, eight).unwrap();
+        let high = FeeModel::from_paths(thirty_two, thirty_two).unwrap();
+        for (target, alone) in [(1u16, &low), (6, &high)].iter() {
+            let estimate = model.estimate(*target, Some(ts), &rates, ts - 300);
+            let expected = alone.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(estimate.unwrap(), expected);
+            let detailed = model.estimate_detailed(*target, Some(ts), &rates, ts - 300);
+            assert_eq!(detailed.unwrap().rate, expected);
+        }
+        let both = model.estimate_both(6, Some(ts), &rates, ts - 300).unwrap();
+        assert!(both.low.is_finite() && both.high.is_finite());
+        // given buckets must be the ones of the target
+        let buckets = model.fee_buckets_for(6).get(&rates);
+        let estimate = model.estimate_with_buckets(6, Some(ts), &buckets, ts - 300);
+        assert_eq!(
+            estimate.unwrap(),
+            high.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        );
+        let err = model.estimate_with_buckets(1, Some(ts), &buckets, ts - 300);
+        assert!(matches!(
+            err,
+            Err(Error::WrongBucketCount {
+                expected: 8,
+                got: 32
+            })
+        ));
+        let err = model.with_fee_buckets(FeeBuckets::with_count(8, 500.0).unwrap());
+        assert!(matches!(err, Err(Error::WrongBucketCount { .. })));
+
+        // the 16 buckets models keep their buckets
+        let embedded = get_fee_model();
+        let expected = FeeBuckets::new(50, 500.0);
+        assert_eq!(embedded.fee_buckets().limits(), expected.limits());
+        assert_eq!(embedded.fee_buckets_for(6).limits(), expected.limits());
+    }
+
+    #[test]
+    pub fn test_bucket_config_metadata() {
+        let low = Path::new("models/fixtures/buckets25_low.cbor");
+        let high = Path::new("models/fixtures/buckets25_high.cbor");
+        let model = FeeModel::from_paths(low, high).unwrap();
+        let expected = FeeBuckets::new(25, 1000.0);
+        assert_eq!(expected.len(), 31);
+        assert_eq!(model.fee_buckets().limits(), expected.limits());
+        assert_eq!(model.fee_buckets_for(6).limits(), expected.limits());
+        let config = model.bucket_config().unwrap();
+        assert_eq!(config.bucket_increment_percent, 25);
+        assert_eq!(config.bucket_limit, 1000.0);
+        assert_eq!(config.bucket_count, Some(31));
+        assert_eq!(
+            config,
+            model.low_model_info().bucket_config.as_ref().unwrap()
+        );
+
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let estimate = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        let buckets = expected.get(&rates);
+        let with_buckets = model.estimate_with_buckets(6, Some(ts), &buckets, ts - 300);
+        assert_eq!(with_buckets.unwrap(), estimate);
+        let reconfigured = model.clone().with_config(config.clone()).unwrap();
+        assert_eq!(reconfigured.fee_buckets().limits(), expected.limits());
+        let custom = model.clone().with_fee_buckets(expected.clone()).unwrap();
+        assert!(custom.bucket_config().is_none());
+
+        // the embedded models get the default configuration
+        let embedded = get_fee_model();
+        let config = embedded.bucket_config().unwrap();
+        assert_eq!(config.bucket_count, Some(16));
+        assert_eq!(
+            embedded.fee_buckets().limits(),
+            config.fee_buckets().limits()
+        );
+
+        let mut other = ModelData::from_slice(&std::fs::read(high).unwrap()).unwrap();
+        other.info.bucket_config.as_mut().unwrap().bucket_limit = 500.0;
+        let other = other.to_vec().unwrap();
+        let err = FeeModel::from_bytes(&std::fs::read(low).unwrap(), &other).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BucketConfigMismatch { model: "high", .. }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The high model declares buckets 25% wide up to 500 sat/vB, the previous models 25% \
+             wide up to 1000 sat/vB"
+        );
+    }
+
+    #[test]
+    pub fn test_bucket_mode_metadata() {
+        let low = || get_low_model();
+        let with_mode = |mode| {
+            let mut model = low();
+            model.info.bucket_config = Some(FeeModelConfig {
+                bucket_mode: mode,
+                ..Default::default()
+            });
+            model.to_vec().unwrap()
+        };
+        let top = with_mode(BucketMode::CumulativeFromTop);
+        let model = FeeModel::from_bytes(&top, &top).unwrap();
+        assert_eq!(model.fee_buckets().mode(), BucketMode::CumulativeFromTop);
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let cumulative = FeeBuckets::new(50, 500.0)
+            .with_mode(BucketMode::CumulativeFromTop)
+            .get(&rates);
+        let expected = model.estimate_with_buckets(1, Some(ts), &cumulative, ts - 300);
+        let got = model.estimate(1, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(got, expected.unwrap());
+        // without metadata any mode is accepted
+        let untagged = low().to_vec().unwrap();
+        let model = FeeModel::from_bytes(&top, &untagged).unwrap();
+        assert_eq!(model.fee_buckets().mode(), BucketMode::CumulativeFromTop);
+
+        let err = model
+            .clone()
+            .with_config(FeeModelConfig::default())
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BucketModeMismatch {
+                model: "low",
+                expected: BucketMode::CumulativeFromTop,
+                got: BucketMode::Histogram,
+            }
+        ));
+        let bottom = FeeBuckets::new(50, 500.0).with_mode(BucketMode::CumulativeFromBottom);
+        assert!(model.clone().with_fee_buckets(bottom).is_err());
+        let config = FeeModelConfig {
+            bucket_mode: BucketMode::CumulativeFromTop,
+            ..Default::default()
+        };
+        assert!(model.with_config(config).is_ok());
+
+        let histogram = with_mode(BucketMode::Histogram);
+        let err = FeeModel::from_bytes(&top, &histogram).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "The high model expects Histogram buckets, found CumulativeFromTop"
+        );
+        assert_eq!(get_fee_model().fee_buckets().mode(), BucketMode::Histogram);
+    }
+
+    #[test]
+    pub fn test_estimate_targets() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let targets = [1u16, 2, 3, 6, 12, 24, 144];
+        let results = model
+            .estimate_targets(&targets, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(results.len(), targets.len());
+        for (target, result) in targets.iter().zip(results.iter()) {
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_approx_eq(*result, expected);
+        }
+        assert!(model
+            .estimate_targets(&[], Some(ts), &rates, ts - 300)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    pub fn test_targets_for_rate() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let monotonic = EstimateOptions {
+            enforce_monotonic: true,
+            ..Default::default()
+        };
+        for model in [get_fee_model(), get_fee_model().with_options(monotonic)].iter() {
+            let estimate = |target| model.estimate(target, Some(ts), &rates, ts - 300).unwrap();
+            let lowest = estimate(model.max_target());
+            for rate in [lowest, 8.0, 10.0, 20.0, 32.0, 33.0, estimate(1), 1000.0] {
+                let target = model
+                    .targets_for_rate(rate, Some(ts), &rates, ts - 300)
+                    .unwrap();
+                assert!(estimate(target) <= rate, "{} {}", rate, target);
+                if target > 1 {
+                    assert!(rate < estimate(target - 1), "{} {}", rate, target);
+                }
+            }
+            assert_eq!(
+                model
+                    .targets_for_rate(estimate(1), Some(ts), &rates, ts - 300)
+                    .unwrap(),
+                1
+            );
+            let err = model
+                .targets_for_rate(0.5, Some(ts), &rates, ts - 300)
+                .unwrap_err();
+            assert!(matches!(err, Error::FeeRateTooLow(_)));
+        }
+    }
+
+    #[test]
+    pub fn test_estimate_all() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let all = model.estimate_all(Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(all.len(), DEFAULT_TARGETS.len());
+        assert_eq!(all.keys().next(), Some(&1));
+        assert_eq!(all.keys().last(), Some(&MAX_TARGET));
+        for (target, value) in all.iter() {
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_approx_eq(*value, expected);
+        }
+
+        let tiers = vec![(2, get_low_model()), (144, get_high_model())];
+        let options = EstimateOptions {
+            target_policy: TargetPolicy::Reject,
+            ..Default::default()
+        };
+        let model = FeeModel::from_tiers(tiers).unwrap().with_options(options);
+        let all = model.estimate_all(Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(all.len(), DEFAULT_TARGETS.len() - 2);
+        assert_eq!(all.keys().last(), Some(&144));
+    }
+
+    #[test]
+    pub fn test_multi_output() {
+        // one output a target, estimating `2 * target + delta_last / 100`
+        let path = Path::new("models/fixtures/multi_output.cbor");
+        let multi = ModelData::from_reader(File::open(path).unwrap()).unwrap();
+        assert_eq!(multi.output_targets(), &[1, 2, 3, 6, 12, 24, 72, 144]);
+        let model = FeeModel::from_multi_output(multi.clone()).unwrap();
+        model.validate_features().unwrap();
+        assert_eq!(model.max_target(), 144);
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let estimate = |target| model.estimate(target, Some(ts), &rates, ts - 300).unwrap();
+        assert_approx_eq(estimate(1), 5.0);
+        assert_approx_eq(estimate(6), 15.0);
+        assert_approx_eq(estimate(12), 27.0);
+        assert_approx_eq(estimate(144), 291.0);
+        // interpolated between 6 and 12
+        assert_approx_eq(estimate(9), 21.0);
+        assert_approx_eq(estimate(7), 17.0);
+        // out of the targets of the outputs
+        assert_approx_eq(estimate(1008), 291.0);
+
+        let all = model.estimate_all(Some(ts), &rates, ts - 300).unwrap();
+        for (target, value) in all.iter() {
+            assert_eq!(value.to_bits(), estimate(*target).to_bits(), "{}", target);
+        }
+        let targets = [9u16, 1, 100];
+        let estimates = model
+            .estimate_targets(&targets, Some(ts), &rates, ts - 300)
+            .unwrap();
+        for (target, value) in targets.iter().zip(estimates) {
+            assert_eq!(value.to_bits(), estimate(*target).to_bits(), "{}", target);
+        }
+
+        // in a tier, with another model for the first targets
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let tiers = FeeModel::from_paths(low, path).unwrap();
+        let rate = tiers.estimate(9, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(rate.to_bits(), estimate(9).to_bits());
+        let rate = tiers.estimate(1, Some(ts), &rates, ts - 300).unwrap();
+        let embedded = get_fee_model().estimate(1, Some(ts), &rates, ts - 300);
+        assert_eq!(rate, embedded.unwrap());
+
+        let (input, _) = model
+            .build_input(ts * 1000, &[5; 16], (ts - 300) * 1000)
+            .unwrap();
+        assert_approx_eq(multi.norm_predict_target(&input, 9).unwrap(), 21.0);
+        let low = get_low_model();
+        let mut expected = input.clone();
+        expected.confirms_in = 2.0;
+        assert_eq!(
+            low.norm_predict_target(&input, 2).unwrap(),
+            low.norm_predict_input(&expected).unwrap()
+        );
+
+        let err = FeeModel::from_multi_output(get_low_model()).unwrap_err();
+        assert!(matches!(err, Error::InvalidOutputTargets(_)));
+        let mut invalid = multi;
+        invalid.info.output_targets.swap(0, 1);
+        let err = ModelData::from_slice(&invalid.to_vec().unwrap()).unwrap_err();
+        assert!(matches!(err, Error::InvalidOutputTargets(_)));
+        invalid.info.output_targets.pop();
+        invalid.info.output_targets.sort();
+        let err = ModelData::from_slice(&invalid.to_vec().unwrap()).unwrap_err();
+        assert!(matches!(err, Error::DimensionMismatch { .. }));
+    }
+
+    #[test]
+    pub fn test_estimate_detailed() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        for (target, kind) in [
+            (1u16, ModelKind::Low),
+            (2, ModelKind::Low),
+            (3, ModelKind::High),
+        ]
+        .iter()
+        {
+            let detailed = model
+                .estimate_detailed(*target, Some(ts), &rates, ts - 300)
+                .unwrap();
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_approx_eq(detailed.rate, expected);
+            assert_approx_eq(detailed.raw_output, expected);
+            assert_eq!(detailed.model, *kind);
+            assert_eq!(detailed.block_target, *target);
+            assert_eq!(detailed.inputs.confirms_in, *target as f32);
+            assert_eq!(detailed.inputs.delta_last, 300.0);
+            let buckets = model.fee_buckets().get(&rates);
+            assert_eq!(detailed.inputs.buckets[0], buckets[0] as f32);
+        }
+    }
+
+    #[test]
+    fn test_staleness() {
+        use core::time::Duration;
+
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let (ten_minutes, three_hours, two_days) = (600, 3 * 60 * 60, 2 * 24 * 60 * 60);
+        let staleness = |model: &FeeModel, age| {
+            let detailed = model.estimate_detailed(6, Some(ts), &rates, ts - age);
+            detailed.unwrap().staleness
+        };
+        assert_eq!(staleness(&model, ten_minutes), Staleness::Fresh);
+        // a last block after the timestamp is fresh
+        assert_eq!(staleness(&model, -60), Staleness::Fresh);
+        for age in [three_hours, two_days].iter() {
+            assert_eq!(
+                staleness(&model, *age),
+                Staleness::Stale {
+                    age: Duration::from_secs(*age as u64)
+                }
+            );
+            // estimated like before by default
+            assert!(model.estimate(6, Some(ts), &rates, ts - age).is_ok());
+        }
+
+        let strict = model.clone().with_options(EstimateOptions {
+            stale_after: Duration::from_secs(24 * 60 * 60),
+            reject_stale: true,
+            ..Default::default()
+        });
+        assert_eq!(staleness(&strict, three_hours), Staleness::Fresh);
+        for age in [ten_minutes, three_hours].iter() {
+            let expected = model.estimate(6, Some(ts), &rates, ts - age).unwrap();
+            let estimate = strict.estimate(6, Some(ts), &rates, ts - age).unwrap();
+            assert_eq!(estimate, expected);
+        }
+        let err = strict
+            .estimate(6, Some(ts), &rates, ts - two_days)
+            .unwrap_err();
+        assert!(matches!(err, Error::StaleChainTip(age) if age.as_secs() == 2 * 24 * 60 * 60));
+        assert_eq!(
+            err.to_string(),
+            "The last block is stale, 172800 seconds old"
+        );
+        let buckets = model.fee_buckets().get(&rates);
+        let err = strict.estimate_with_buckets(6, Some(ts), &buckets, ts - two_days);
+        assert!(matches!(err, Err(Error::StaleChainTip(_))));
+    }
+
+    #[test]
+    fn test_target_bounds() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let detailed =
+            |model: &FeeModel, target| model.estimate_detailed(target, Some(ts), &rates, ts - 300);
+        assert_eq!(model.max_target(), MAX_TARGET);
+        let err = model.estimate(0, Some(ts), &rates, ts - 300).unwrap_err();
+        assert!(matches!(err, Error::InvalidTarget(0)));
+        assert_eq!(err.to_string(), "Invalid block target 0");
+        assert!(matches!(detailed(&model, 0), Err(Error::InvalidTarget(0))));
+        let err = model.estimate_f64(0, Some(ts), &rates, ts - 300);
+        assert!(matches!(err, Err(Error::InvalidTarget(0))));
+
+        let first = detailed(&model, 1).unwrap();
+        assert_eq!((first.block_target, first.effective_target), (1, 1));
+        let max = detailed(&model, MAX_TARGET).unwrap();
+        assert_eq!(max.effective_target, MAX_TARGET);
+        // greater targets are clamped instead of extrapolated
+        for target in [MAX_TARGET + 1, 5000, u16::MAX] {
+            let clamped = detailed(&model, target).unwrap();
+            assert_eq!(clamped.block_target, target);
+            assert_eq!(clamped.effective_target, MAX_TARGET);
+            assert_eq!(clamped.rate, max.rate);
+            assert_eq!(clamped.inputs.confirms_in, MAX_TARGET as f32);
+        }
+
+        let options = EstimateOptions {
+            target_policy: TargetPolicy::Reject,
+            ..Default::default()
+        };
+        let rejecting = model.with_options(options);
+        let max_rate = rejecting.estimate(MAX_TARGET, Some(ts), &rates, ts - 300);
+        assert_eq!(max_rate.unwrap(), max.rate);
+        let err = rejecting.estimate(MAX_TARGET + 1, Some(ts), &rates, ts - 300);
+        assert!(matches!(err, Err(Error::InvalidTarget(1009))));
+        let err = rejecting.estimate_targets(&[6, 2000], Some(ts), &rates, ts - 300);
+        assert!(matches!(err, Err(Error::InvalidTarget(2000))));
+        // the smart estimate bounds the target itself, like Core
+        let smart = rejecting.estimate_smart(2000, Some(ts), &rates, ts - 300);
+        assert_eq!(smart.unwrap().blocks, MAX_TARGET);
+
+        // the maximum of the metadata of the high model
+        let mut high = get_high_model();
+        high.info.max_target = Some(144);
+        let model = FeeModel::from_models(get_low_model(), high);
+        assert_eq!(model.max_target(), 144);
+        let clamped = detailed(&model, 145).unwrap();
+        assert_eq!(clamped.effective_target, 144);
+        assert_eq!(clamped.rate, detailed(&model, 144).unwrap().rate);
+        #[cfg(feature = "embedded-models")]
+        assert_eq!(FeeModel::new().unwrap().max_target(), MAX_TARGET);
+    }
+
+    #[test]
+    fn test_network() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        assert_eq!(model.network(), Network::Bitcoin);
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.network, Network::Bitcoin);
+
+        // a model trained for a network is tagged with it, and estimates the same
+        let tagged = get_fee_model().with_network(Network::Signet);
+        let signet = tagged
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(signet.network, Network::Signet);
+        assert_eq!(signet.rate, detailed.rate);
+
+        let constant = ModelData::constant(3.5, 16).unwrap();
+        assert_eq!(constant.feature_names().len(), 20);
+        let model = FeeModel::from_models(constant.clone(), constant);
+        let estimates = model.estimate_targets(&[1, 6, 144], Some(ts), &rates, ts - 300);
+        assert_eq!(estimates.unwrap(), vec![3.5; 3]);
+        let err = ModelData::constant(f32::NAN, 16).unwrap_err();
+        assert!(matches!(err, Error::NonFiniteWeight(_)));
+    }
+
+    #[cfg(feature = "embedded-models")]
+    #[test]
+    fn test_for_network() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let mainnet = FeeModel::for_network(Network::Bitcoin).unwrap();
+        let embedded = FeeModel::new().unwrap();
+        for target in [1u16, 2, 6, 144] {
+            let estimate = mainnet.estimate(target, Some(ts), &rates, ts - 300);
+            let expected = embedded.estimate(target, Some(ts), &rates, ts - 300);
+            assert_eq!(estimate.unwrap(), expected.unwrap());
+        }
+        assert_eq!(mainnet.high_model_info(), embedded.high_model_info());
+
+        for network in [Network::Testnet, Network::Signet, Network::Regtest] {
+            assert!(!network.has_embedded_model());
+            let model = FeeModel::for_network(network).unwrap();
+            assert_eq!(model.network(), network);
+            for target in [1u16, 2, 6, 144] {
+                let detailed = model.estimate_detailed(target, Some(ts), &rates, ts - 300);
+                let detailed = detailed.unwrap();
+                assert_eq!(detailed.rate, 1.0);
+                assert_eq!(detailed.network, network);
+            }
+            let options = EstimateOptions {
+                min_fee_rate: 2.0,
+                ..Default::default()
+            };
+            let raised = model.with_options(options);
+            assert_eq!(raised.estimate(6, Some(ts), &rates, ts - 300).unwrap(), 2.0);
+        }
+    }
+
+    #[test]
+    fn test_estimate_within() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let within = |minutes: u64| {
+            let duration = Duration::from_secs(minutes * 60);
+            model
+                .estimate_within(duration, Some(ts), &rates, ts - 300)
+                .unwrap()
+        };
+        let estimate = |target| model.estimate(target, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(within(10), estimate(1));
+        // shorter than a block
+        assert_eq!(within(0), estimate(1));
+        assert_eq!(within(5), estimate(1));
+        assert_eq!(within(45), (estimate(4) + estimate(5)) / 2.0);
+        // between the low and the high model
+        assert_eq!(within(25), estimate(2) * 0.5 + estimate(3) * 0.5);
+        assert_eq!(within(24 * 60), estimate(144));
+        let duration = Duration::from_secs((1442 * 60) + 30);
+        let quarter = model.estimate_within(duration, Some(ts), &rates, ts - 300);
+        assert_approx_eq(
+            quarter.unwrap(),
+            estimate(144) * 0.75 + estimate(145) * 0.25,
+        );
+        // past the greatest target like it
+        assert_eq!(within(7 * 24 * 60 + 5), estimate(MAX_TARGET));
+        let years = Duration::from_secs(u64::MAX);
+        let err = model.estimate_within(years, Some(ts), &[f64::NAN], ts - 300);
+        assert!(matches!(err, Err(Error::InvalidInput("fee_rates"))));
+    }
+
+    #[test]
+    fn test_estimate_both() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        for target in [1u16, 2, 3, 4] {
+            let both = model
+                .estimate_both(target, Some(ts), &rates, ts - 300)
+                .unwrap();
+            let expected = model.estimate(target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(both.rate, expected);
+            let low = FeeModel::from_models(get_low_model(), get_low_model());
+            let high = FeeModel::from_models(get_high_model(), get_high_model());
+            assert_eq!(
+                both.low,
+                low.estimate(target, Some(ts), &rates, ts - 300).unwrap()
+            );
+            assert_eq!(
+                both.high,
+                high.estimate(target, Some(ts), &rates, ts - 300).unwrap()
+            );
+            if target <= 2 {
+                assert_eq!(both.model, ModelKind::Low);
+                assert_eq!(both.rate, both.low);
+            } else {
+                assert_eq!(both.model, ModelKind::High);
+                assert_eq!(both.rate, both.high);
+            }
+            assert_ne!(both.low, both.high);
+        }
+
+        // the blended estimate is between the two
+        let options = EstimateOptions {
+            blend: Some(BlendRange { from: 2, to: 4 }),
+            ..Default::default()
+        };
+        let blended = model.with_options(options);
+        let both = blended
+            .estimate_both(3, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(both.model, ModelKind::Blended);
+        assert_eq!(
+            both.rate,
+            blended.estimate(3, Some(ts), &rates, ts - 300).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_estimate_rate() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let rate = model.estimate_rate(6, Some(ts), &rates, ts - 300).unwrap();
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_approx_eq(rate.as_sat_per_vb() as f32, expected);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    pub fn test_tracing() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Level, Metadata};
+
+        /// The level and the formatted fields of every span and event
+        type Captured = Arc<Mutex<Vec<(Level, Vec<(String, String)>)>>>;
+
+        struct Fields(Vec<(String, String)>);
+
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+                self.0
+                    .push((field.name().to_string(), format!("{:?}", value)));
+            }
+            // the fields are `f32`, recorded as `f64` or formatted depending on the features
+            fn record_f64(&mut self, field: &Field, value: f64) {
+                self.record_debug(field, &(value as f32));
+            }
+        }
+
+        struct Capture(Captured);
+
+        impl tracing::Subscriber for Capture {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes) -> Id {
+                let mut fields = Fields(vec![("span".into(), span.metadata().name().into())]);
+                span.record(&mut fields);
+                let mut captured = self.0.lock().unwrap();
+                captured.push((*span.metadata().level(), fields.0));
+                Id::from_u64(captured.len() as u64)
+            }
+            fn record(&self, _: &Id, _: &Record) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event) {
+                let mut fields = Fields(vec![]);
+                event.record(&mut fields);
+                let level = *event.metadata().level();
+                self.0.lock().unwrap().push((level, fields.0));
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        let captured = Captured::default();
+        let rate = tracing::subscriber::with_default(Capture(captured.clone()), || {
+            model.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        });
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        let field = |value: &str| value.to_string();
+        let captured = captured.lock().unwrap();
+        assert_eq!(captured.len(), 3);
+        assert_eq!(
+            captured[0],
+            (
+                Level::DEBUG,
+                vec![
+                    ("span".into(), field("estimate")),
+                    ("block_target".into(), field("6")),
+                ]
+            )
+        );
+        let buckets = &detailed.inputs.buckets[..model.fee_buckets().len()];
+        let buckets = format!("{:?}", buckets);
+        assert_eq!(
+            captured[1],
+            (
+                Level::DEBUG,
+                vec![
+                    ("message".into(), field("predicted")),
+                    ("block_target".into(), field("6")),
+                    ("model".into(), field("High")),
+                    ("delta_last".into(), field("300.0")),
+                    ("buckets".into(), buckets),
+                ]
+            )
+        );
+        assert_eq!(
+            captured[2],
+            (
+                Level::TRACE,
+                vec![
+                    ("message".into(), field("bounded")),
+                    ("raw_output".into(), format!("{:?}", detailed.raw_output)),
+                    ("rate".into(), format!("{:?}", rate)),
+                    ("clamped".into(), field("false")),
+                ]
+            )
+        );
+    }
+
+    #[test]
+    pub fn test_estimate_with_features() {
+        // 2021-02-19 04:14:05 UTC, a friday
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        let with = |ts: i64, overrides: FeatureOverrides| {
+            model.estimate_with_features(6, Some(ts), &rates, ts - 300, overrides)
+        };
+        let estimate = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(with(ts, FeatureOverrides::default()).unwrap(), estimate);
+
+        // a sunday with the buckets of friday
+        let sunday = FeatureOverrides {
+            day_of_week: Some(6),
+            ..Default::default()
+        };
+        let two_days = 2 * 24 * 60 * 60;
+        let expected = model.estimate(6, Some(ts + two_days), &rates, ts + two_days - 300);
+        let overridden = with(ts, sunday).unwrap();
+        assert_eq!(overridden, expected.unwrap());
+        assert_ne!(overridden, estimate);
+
+        // reproduce an estimate from the logged features at another time
+        let logged = model
+            .estimate_detailed(1, Some(ts), &rates, ts - 300)
+            .unwrap();
+        let overrides = FeatureOverrides {
+            day_of_week: Some(logged.inputs.day_of_week as u8),
+            hour: Some(logged.inputs.hour as u8),
+            delta_last: Some(logged.inputs.delta_last),
+        };
+        let later = ts + 12345;
+        let reproduced = model.estimate_with_features(1, Some(later), &rates, ts, overrides);
+        assert_eq!(reproduced.unwrap(), logged.rate);
+
+        // the overridden delta is not bounded
+        let three_hours = FeatureOverrides {
+            delta_last: Some(3.0 * 60.0 * 60.0),
+            ..Default::default()
+        };
+        let options = EstimateOptions {
+            max_delta_last: None,
+            ..Default::default()
+        };
+        let unbounded = get_fee_model().with_options(options);
+        let expected = unbounded.estimate(6, Some(ts), &rates, ts - 3 * 60 * 60);
+        assert_eq!(with(ts, three_hours).unwrap(), expected.unwrap());
+
+        let invalid = [
+            (Some(7), None, None, "day_of_week"),
+            (Some(9), None, None, "day_of_week"),
+            (None, Some(24), None, "hour"),
+            (None, Some(25), None, "hour"),
+            (None, None, Some(f32::NAN), "delta_last"),
+            (None, None, Some(-1.0), "delta_last"),
+        ];
+        for (day_of_week, hour, delta_last, name) in invalid.iter() {
+            let overrides = FeatureOverrides {
+                day_of_week: *day_of_week,
+                hour: *hour,
+                delta_last: *delta_last,
+            };
+            let err = with(ts, overrides).unwrap_err();
+            assert!(matches!(err, Error::InvalidOverride(n) if n == *name));
+        }
+        assert_eq!(
+            Error::InvalidOverride("hour").to_string(),
+            "The override of hour is out of range"
+        );
+    }
+
+    #[test]
+    pub fn test_estimate_fee() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let pinned = |rate: f32, min_fee: u64| {
+            let options = EstimateOptions {
+                min_fee_rate: rate,
+                max_fee_rate: Some(rate),
+                min_fee,
+                ..Default::default()
+            };
+            get_fee_model().with_options(options)
+        };
+        let fee = |model: &FeeModel, weight: u64| {
+            model
+                .estimate_fee(6, Some(ts), &rates, ts - 300, weight)
+                .unwrap()
+        };
+        let model = pinned(1.1, 0);
+        // (weight, fee): vsize is the weight divided by 4 rounded up
+        let cases = [
+            (0u64, 0u64),
+            (1, 2),
+            (4, 2),
+            (400, 110),
+            (401, 112),
+            (403, 112),
+        ];
+        for (weight, expected) in cases.iter() {
+            assert_eq!(fee(&model, *weight), *expected, "weight {}", weight);
+        }
+        let model = pinned(2.5, 0);
+        assert_eq!(fee(&model, 561), 353);
+        assert_eq!(fee(&model, 564), 353);
+        assert_eq!(fee(&model, 565), 355);
+        let model = pinned(1.1, 200);
+        assert_eq!(fee(&model, 400), 200);
+        assert_eq!(fee(&model, 800), 220);
+
+        let model = get_fee_model();
+        let rate = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        let fee = model
+            .estimate_fee(6, Some(ts), &rates, ts - 300, 561)
+            .unwrap();
+        assert_eq!(fee, libm::ceil(rate as f64 * 141.0) as u64);
+
+        #[cfg(feature = "use-bitcoin")]
+        {
+            let tx = process_blocks::tests::make_tx(&[(Default::default(), 0)], &[1000]);
+            let weight = tx.get_weight() as u64;
+            assert_eq!(
+                model
+                    .estimate_tx_fee(6, Some(ts), &rates, ts - 300, &tx)
+                    .unwrap(),
+                model
+                    .estimate_fee(6, Some(ts), &rates, ts - 300, weight)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_estimate_smart() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let quantiles = include_bytes!("../models/fixtures/quantiles.cbor");
+        let quantiles = || ModelData::from_slice(quantiles).unwrap();
+        // estimates `2 * target + delta_last / 100 - 0.5` sat/vB
+        let model = FeeModel::from_models(quantiles(), quantiles());
+        let smart = |model: &FeeModel, target| {
+            model
+                .estimate_smart(target, Some(ts), &rates, ts - 300)
+                .unwrap()
+        };
+        let expected = SmartFeeResult {
+            feerate: 0.000145,
+            blocks: 6,
+        };
+        assert_eq!(smart(&model, 6), expected);
+        assert_eq!(smart(&model, 0).blocks, 1);
+        assert_eq!(smart(&model, 0).feerate, 0.000045);
+        assert_eq!(smart(&model, u16::MAX).blocks, MAX_TARGET);
+        assert_eq!(smart(&model, u16::MAX).feerate, 0.020185);
+        let options = EstimateOptions {
+            min_fee_rate: 50.0,
+            ..Default::default()
+        };
+        assert_eq!(smart(&model.with_options(options), 6).feerate, 0.0005);
+    }
+
+    #[test]
+    pub fn test_estimate_with_extras() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let mut extras = BTreeMap::new();
+        extras.insert("mempool_vsize".to_string(), 1_000_000.0);
+        for target in [1, 6].iter() {
+            let with_extras =
+                model.estimate_with_extras(*target, Some(ts), &rates, ts - 300, &extras);
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(with_extras.unwrap(), expected);
+        }
+
+        let low = model_data::tests::get_test_model();
+        let high = model_data::tests::get_test_model_with_extra();
+        let model = FeeModel::from_models(low, high);
+        let estimate = model.estimate_with_extras(6, Some(ts), &rates, ts - 300, &extras);
+        assert!(estimate.is_ok());
+        let estimate = model.estimate_with_extras(6, Some(ts), &rates, ts - 300, &BTreeMap::new());
+        assert!(matches!(estimate, Err(Error::MissingExtra(_))));
+        // the model without extras is not affected
+        let estimate = model.estimate_with_extras(1, Some(ts), &rates, ts - 300, &BTreeMap::new());
+        assert!(estimate.is_ok());
+    }
+
+    #[test]
+    pub fn test_enforce_monotonic() {
+        let ts = SAMPLE_TS;
+        // with few transactions the raw models estimate more for 3 blocks than for 2
+        let rates: Vec<f64> = get_fee_model()
+            .fee_buckets()
+            .limits()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, limit)| vec![limit * 0.99; i % 5 + 1])
+            .collect();
+        let targets: Vec<u16> = (1..=30).chain([144, 504, 1008].iter().copied()).collect();
+        let raw = get_fee_model()
+            .estimate_targets(&targets, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(raw.windows(2).any(|w| w[0] < w[1]));
+
+        let options = EstimateOptions {
+            enforce_monotonic: true,
+            ..Default::default()
+        };
+        let model = get_fee_model().with_options(options);
+        let clamped = model
+            .estimate_targets(&targets, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(clamped.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(clamped[0], raw[0]);
+        for (c, r) in clamped.iter().zip(raw.iter()) {
+            assert!(c <= r);
+        }
+
+        // unordered targets are clamped according to the target order
+        let unordered = model
+            .estimate_targets(&[3, 1, 2], Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(unordered, vec![clamped[2], clamped[0], clamped[1]]);
+
+        // single target estimations are not affected
+        let single = model.estimate(3, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(single, raw[2]);
+    }
+
+    #[test]
+    pub fn test_tiers() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let quantiles = || {
+            let bytes = include_bytes!("../models/fixtures/quantiles.cbor");
+            ModelData::from_slice(bytes).unwrap()
+        };
+        let tiers = vec![
+            (2, get_low_model()),
+            (47, get_high_model()),
+            (1008, quantiles()),
+        ];
+        // the long model has no time features
+        let err = FeeModel::from_tiers(tiers.clone()).unwrap_err();
+        assert!(matches!(&err, Error::ModelMismatch(c) if c.len() == 2));
+        let tiers = tiers.into_iter().map(|(max_target, model)| Tier {
+            max_target,
+            models: vec![model],
+            fee_buckets: None,
+        });
+        let model = FeeModel::from_tier_list(tiers.collect());
+        let two_tiers = get_fee_model();
+        let long = FeeModel::from_models(quantiles(), quantiles());
+        let cases = [
+            (2u16, ModelKind::Low, &two_tiers),
+            (3, ModelKind::High, &two_tiers),
+            (47, ModelKind::High, &two_tiers),
+            (48, ModelKind::Long, &long),
+            (1008, ModelKind::Long, &long),
+            // the last tier estimates the greater targets
+            (2000, ModelKind::Long, &long),
+        ];
+        for (target, kind, expected) in cases.iter() {
+            let detailed = model.estimate_detailed(*target, Some(ts), &rates, ts - 300);
+            let detailed = detailed.unwrap();
+            assert_eq!(detailed.model, *kind, "target {}", target);
+            let expected = expected.estimate(*target, Some(ts), &rates, ts - 300);
+            assert_eq!(detailed.rate, expected.unwrap());
+        }
+        // `2 * 48 + 300 / 100 - 0.5`
+        assert_approx_eq(
+            model.estimate(48, Some(ts), &rates, ts - 300).unwrap(),
+            98.5,
+        );
+        assert_eq!(model.high_model_info(), two_tiers.high_model_info());
+
+        let single = FeeModel::from_tiers(vec![(6, get_low_model())]).unwrap();
+        let low = FeeModel::from_models(get_low_model(), get_low_model());
+        assert_eq!(
+            single.estimate(144, Some(ts), &rates, ts - 300).unwrap(),
+            low.estimate(144, Some(ts), &rates, ts - 300).unwrap()
+        );
+        for max_targets in [vec![], vec![0, 6], vec![2, 2], vec![6, 2]].iter() {
+            let tiers = max_targets.iter().map(|m| (*m, get_low_model())).collect();
+            let err = FeeModel::from_tiers(tiers).err().unwrap();
+            assert!(matches!(err, Error::InvalidTiers(t) if &t == max_targets));
+        }
+    }
+
+    #[test]
+    pub fn test_blend() {
+        let ts = SAMPLE_TS;
+        let targets: Vec<u16> = (1..=6).collect();
+        // compare the outputs of the networks, without the minimum fee rate
+        let unbounded = EstimateOptions {
+            min_fee_rate: f32::NEG_INFINITY,
+            ..Default::default()
+        };
+        let hard_model = get_fee_model().with_options(unbounded.clone());
+        let options = EstimateOptions {
+            blend: Some(BlendRange { from: 2, to: 4 }),
+            ..unbounded
+        };
+        let blend_model = get_fee_model().with_options(options);
+        let max_jump = |v: &[f32]| {
+            v.windows(2)
+                .map(|w| (w[0] - w[1]).abs())
+                .fold(0.0f32, f32::max)
+        };
+
+        for scale in [1u64, 2, 10].iter() {
+            let buckets: Vec<u64> = (0..16).map(|i| scale * (i % 5 + 1)).collect();
+            let estimate = |model: &FeeModel, target: u16| {
+                model
+                    .estimate_with_buckets(target, Some(ts), &buckets, ts - 300)
+                    .unwrap()
+            };
+            let hard: Vec<f32> = targets.iter().map(|t| estimate(&hard_model, *t)).collect();
+            let blend: Vec<f32> = targets.iter().map(|t| estimate(&blend_model, *t)).collect();
+
+            // outside the blended range nothing changes
+            for i in [0usize, 1, 3, 4, 5].iter() {
+                assert_eq!(hard[*i], blend[*i]);
+            }
+            assert!(max_jump(&blend[1..4]) <= max_jump(&hard[1..4]) + EPS_1000);
+            assert!(max_jump(&blend) <= max_jump(&hard) + EPS_1000);
+            let middle = (hard[1] + hard[3]) / 2.0;
+            assert_approx_eq(blend[2], middle);
+        }
+
+        let rates = [1.0, 5.0, 20.0];
+        let detailed = blend_model
+            .estimate_detailed(3, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.model, ModelKind::Blended);
+    }
+
+    #[test]
+    pub fn test_uncertainty_hint() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let options = EstimateOptions {
+            uncertainty_hint: true,
+            ..Default::default()
+        };
+        let hint = |model: &FeeModel, target: u16| {
+            let detailed = model.estimate_detailed(target, Some(ts), &rates, ts - 300);
+            detailed.unwrap().uncertainty_hint
+        };
+        assert_eq!(hint(&get_fee_model(), 6), None);
+        let model = get_fee_model().with_options(options.clone());
+        for target in [1u16, 2, 3, 6, 144, 1008].iter() {
+            let hint = hint(&model, *target).unwrap();
+            assert!(hint.is_finite() && hint >= 0.0, "{} {}", target, hint);
+        }
+
+        // models predicting the same rate for every target
+        let flat = |rate: f32| {
+            let mut model = get_low_model();
+            let kernel = model.weights.l2_kernel.as_f32();
+            let zeros = matrix::Matrix::zeros(kernel.width(), kernel.height());
+            model.weights.l2_kernel = model_data::Kernel::F32(zeros);
+            model.weights.l2_bias = matrix::Matrix::from_array(vec![rate].into_boxed_slice());
+            model
+        };
+        let model = FeeModel::from_models(flat(20.0), flat(20.0)).with_options(options.clone());
+        for target in [1u16, 2, 3, 6, 1008].iter() {
+            assert_eq!(hint(&model, *target), Some(0.0), "{}", target);
+        }
+        // a jump between the low and the high model
+        let model = FeeModel::from_models(flat(20.0), flat(5.0)).with_options(options);
+        assert_eq!(hint(&model, 1), Some(0.0));
+        assert_eq!(hint(&model, 2), Some(15.0 / 20.0));
+        assert_eq!(hint(&model, 3), Some(15.0 / 5.0));
+        assert_eq!(hint(&model, 4), Some(0.0));
+        assert_eq!(hint(&model, 1008), Some(0.0));
+    }
+
+    #[test]
+    pub fn test_estimate_from_txs() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let txs: Vec<([u8; 32], f64)> = rates
+            .iter()
+            .enumerate()
+            .map(|(i, rate)| {
+                let mut txid = [0u8; 32];
+                txid[..8].copy_from_slice(&(i as u64).to_le_bytes());
+                (txid, *rate)
+            })
+            .collect();
+        let model = get_fee_model();
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        let estimate = model.estimate_from_txs(6, Some(ts), &txs, ts - 300);
+        assert_eq!(estimate.unwrap(), expected);
+
+        // the same transactions seen by two sources
+        let mut merged = txs.clone();
+        merged.extend_from_slice(&txs[..400]);
+        let doubled = [&rates[..], &rates[..400]].concat();
+        let doubled = model.estimate(6, Some(ts), &doubled, ts - 300);
+        assert_ne!(doubled.unwrap(), expected);
+        let detailed = model
+            .estimate_detailed_from_txs(6, Some(ts), &merged, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.rate, expected);
+        assert_eq!(detailed.duplicate_txs, 400);
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.duplicate_txs, 0);
+
+        // the estimate doesn't depend on the order of the transactions
+        for seed in 1..6u64 {
+            let mut shuffled = merged.clone();
+            let mut state = seed;
+            for i in (1..shuffled.len()).rev() {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                shuffled.swap(i, (state >> 33) as usize % (i + 1));
+            }
+            let estimate = model.estimate_from_txs(6, Some(ts), &shuffled, ts - 300);
+            assert_eq!(estimate.unwrap(), expected, "{}", seed);
+        }
+    }
+
+    #[test]
+    pub fn test_dispatch_threshold() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        assert_eq!(model.dispatch_threshold(), 2);
+        let detailed = model.estimate_detailed(3, Some(ts), &rates, ts - 300);
+        assert_eq!(detailed.unwrap().dispatch_threshold, 2);
+
+        // a pair split at 4 blocks, declared by the low model
+        let model = FeeModel::from_paths(
+            Path::new("models/fixtures/threshold4_low.cbor"),
+            Path::new("models/fixtures/threshold4_high.cbor"),
+        )
+        .unwrap();
+        assert_eq!(model.dispatch_threshold(), 4);
+        assert_eq!(model.max_target(), MAX_TARGET);
+        assert_eq!(
+            model.high_model_info().name.as_deref(),
+            Some("threshold4_high")
+        );
+        for (target, kind, rate) in [
+            (1, ModelKind::Low, 20.0),
+            (3, ModelKind::Low, 20.0),
+            (4, ModelKind::Low, 20.0),
+            (5, ModelKind::High, 5.0),
+            (1008, ModelKind::High, 5.0),
+        ]
+        .iter()
+        {
+            let detailed = model
+                .estimate_detailed(*target, Some(ts), &rates, ts - 300)
+                .unwrap();
+            assert_eq!(detailed.model, *kind, "{}", target);
+            assert_eq!(detailed.rate, *rate, "{}", target);
+            assert_eq!(detailed.dispatch_threshold, 4);
+        }
+        let both = model.estimate_both(4, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!((both.low, both.high), (20.0, 5.0));
+
+        // a threshold the high model can't follow falls back to 2 blocks
+        let mut low = get_low_model();
+        low.info.max_target = Some(MAX_TARGET);
+        let model = FeeModel::from_models(low, get_high_model());
+        assert_eq!(model.dispatch_threshold(), 2);
+    }
+
+    #[test]
+    pub fn test_strictness() {
+        // a model trained with up to 10000 transactions in the last bucket and up to an hour
+        // since the last block
+        let path = Path::new("models/fixtures/feature_ranges.cbor");
+        let model = FeeModel::from_paths(path, path).unwrap();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let congested: Vec<f64> = rates.iter().cloned().chain(vec![2000.0; 30_000]).collect();
+        let with = |strictness| {
+            model.clone().with_options(EstimateOptions {
+                strictness,
+                ..Default::default()
+            })
+        };
+
+        let permissive = model.estimate_detailed(6, Some(ts), &congested, ts - 5400);
+        let permissive = permissive.unwrap();
+        assert!(permissive.inputs.buckets[15] > 10_000.0);
+        assert_eq!(permissive.rate, 10.0);
+        assert!(permissive.out_of_range_features.is_empty());
+
+        let warn = with(Strictness::Warn);
+        let detailed = warn.estimate_detailed(6, Some(ts), &congested, ts - 5400);
+        let detailed = detailed.unwrap();
+        assert_eq!(detailed.rate, 10.0);
+        assert_eq!(detailed.out_of_range_features, vec!["b15", "delta_last"]);
+        let detailed = warn
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(detailed.out_of_range_features.is_empty());
+
+        let strict = with(Strictness::Strict);
+        let err = strict.estimate(6, Some(ts), &rates, ts - 5400).unwrap_err();
+        assert!(matches!(&err, Error::OutOfDistribution(f) if f == &["delta_last"]));
+        assert_eq!(
+            err.to_string(),
+            "The features delta_last are out of the ranges seen in training"
+        );
+        assert_eq!(
+            strict.estimate(6, Some(ts), &rates, ts - 300).unwrap(),
+            10.0
+        );
+        // the models without ranges are not checked
+        let unchecked = get_fee_model().with_options(strict.options().clone());
+        assert!(unchecked
+            .estimate(6, Some(ts), &congested, ts - 5400)
+            .is_ok());
+    }
+
+    #[test]
+    pub fn test_rounding() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let constant = |rate: f32, options: EstimateOptions| {
+            let model = ModelData::constant(rate, 16).unwrap();
+            FeeModel::from_models(model.clone(), model).with_options(options)
+        };
+        let rounded = |rate: f32, rounding: Rounding| {
+            let options = EstimateOptions {
+                rounding,
+                ..Default::default()
+            };
+            let detailed = constant(rate, options).estimate_detailed(6, Some(ts), &rates, ts - 300);
+            detailed.map(|d| (d.rate, d.unrounded_rate))
+        };
+        assert_eq!(
+            rounded(13.734621, Rounding::None).unwrap(),
+            (13.734621, 13.734621)
+        );
+        for (decimals, expected) in [(0, 14.0), (1, 13.8), (2, 13.74), (6, 13.734621)].iter() {
+            let up = Rounding::Up {
+                decimals: *decimals,
+            };
+            assert_eq!(rounded(13.734621, up).unwrap(), (*expected, 13.734621));
+        }
+        assert_eq!(rounded(13.7, Rounding::Up { decimals: 1 }).unwrap().0, 13.7);
+        for (increment, expected) in [(0.5, 14.0), (0.25, 13.75), (5.0, 15.0)].iter() {
+            let to = Rounding::ToIncrement {
+                sat_per_vb: *increment,
+            };
+            assert_eq!(rounded(13.734621, to).unwrap(), (*expected, 13.734621));
+        }
+        let half = Rounding::ToIncrement { sat_per_vb: 0.5 };
+        assert_eq!(rounded(13.5, half).unwrap().0, 13.5);
+
+        // the estimates stay within the bounds of the options
+        let third = Rounding::ToIncrement { sat_per_vb: 0.3 };
+        assert_eq!(rounded(0.2, third).unwrap(), (1.2, 1.0));
+        let capped = constant(
+            13.734621,
+            EstimateOptions {
+                max_fee_rate: Some(13.75),
+                rounding: Rounding::Up { decimals: 0 },
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            capped.estimate(6, Some(ts), &rates, ts - 300).unwrap(),
+            13.75
+        );
+
+        let zero = Rounding::ToIncrement { sat_per_vb: 0.0 };
+        assert!(matches!(
+            rounded(13.7, zero),
+            Err(Error::InvalidInput("rounding"))
+        ));
+        let randomized = Rounding::Randomized {
+            spread: 1.0,
+            decimals: 2,
+        };
+        assert!(matches!(rounded(13.7, randomized), Err(Error::MissingRng)));
+    }
+
+    /// The options rounding the estimates to multiples of 0.4 sat/vB
+    fn rounded_options() -> EstimateOptions {
+        EstimateOptions {
+            rounding: Rounding::ToIncrement { sat_per_vb: 0.4 },
+            ..Default::default()
+        }
+    }
+
+    fn assert_rounded(rate: f64) {
+        let steps = rate / 0.4;
+        assert!((steps - libm::round(steps)).abs() < 1e-4, "{}", rate);
+    }
+
+    #[test]
+    pub fn test_rounding_within() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model().with_options(rounded_options());
+        // interpolated between the estimates of 2 and 3 blocks
+        let duration = Duration::from_secs(25 * 60);
+        let rate = model.estimate_within(duration, Some(ts), &rates, ts - 300);
+        assert_rounded(rate.unwrap() as f64);
+    }
+
+    #[test]
+    pub fn test_rounding_targets() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let targets = [1u16, 2, 5, 6, 144];
+        let model = get_fee_model().with_options(rounded_options());
+        let path = Path::new("models/fixtures/multi_output.cbor");
+        let multi = ModelData::from_reader(File::open(path).unwrap()).unwrap();
+        let multi = FeeModel::from_multi_output(multi).unwrap();
+        for model in [model, multi.with_options(rounded_options())].iter() {
+            let estimates = model.estimate_targets(&targets, Some(ts), &rates, ts - 300);
+            for rate in estimates.unwrap() {
+                assert_rounded(rate as f64);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_rounding_f64() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model().with_options(rounded_options());
+        let rate = model.estimate_f64(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_rounded(rate);
+        assert_eq!(
+            rate as f32,
+            model.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_rounding_quantiles() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let bytes = include_bytes!("../models/fixtures/quantiles.cbor");
+        let quantiles = ModelData::from_slice(bytes).unwrap();
+        let model = FeeModel::from_models(quantiles.clone(), quantiles);
+        let model = model.with_options(rounded_options());
+        // 7.25, 14.5 and 21.75 unrounded
+        let estimates = model.estimate_quantiles(6, Some(ts), &rates, ts - 300, &[0.1, 0.5, 0.9]);
+        let estimates = estimates.unwrap();
+        for rate in estimates.iter() {
+            assert_rounded(*rate as f64);
+        }
+        assert_eq!(estimates, vec![7.6, 14.8, 22.0]);
+    }
+
+    #[test]
+    pub fn test_rounding_both() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model().with_options(rounded_options());
+        for target in [1u16, 3, 144].iter() {
+            let both = model.estimate_both(*target, Some(ts), &rates, ts - 300);
+            let both = both.unwrap();
+            for rate in [both.rate, both.low, both.high].iter() {
+                assert_rounded(*rate as f64);
+            }
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    pub fn test_randomized_rounding() {
+        struct Lcg(u64);
+        impl rand_core::RngCore for Lcg {
+            fn next_u32(&mut self) -> u32 {
+                self.0 = self
+                    .0
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                (self.0 >> 32) as u32
+            }
+            fn next_u64(&mut self) -> u64 {
+                rand_core::impls::next_u64_via_u32(self)
+            }
+            fn fill_bytes(&mut self, dest: &mut [u8]) {
+                rand_core::impls::fill_bytes_via_next(self, dest)
+            }
+            fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+                self.fill_bytes(dest);
+                Ok(())
+            }
+        }
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let options = EstimateOptions {
+            rounding: Rounding::Randomized {
+                spread: 1.0,
+                decimals: 1,
+            },
+            ..Default::default()
+        };
+        let model = |seed| {
+            let constant = ModelData::constant(13.734621, 16).unwrap();
+            let model = FeeModel::from_models(constant.clone(), constant);
+            model.with_options(options.clone()).with_rng(Lcg(seed))
+        };
+        let estimates = |model: &FeeModel| -> Vec<f32> {
+            (0..20)
+                .map(|_| model.estimate(6, Some(ts), &rates, ts - 300).unwrap())
+                .collect()
+        };
+        let first = estimates(&model(7));
+        assert_eq!(first, estimates(&model(7)));
+        assert_ne!(first, estimates(&model(8)));
+        assert!(first.windows(2).any(|w| w[0] != w[1]));
+        for rate in first.iter() {
+            assert!(*rate >= 13.734621 && *rate <= 14.8, "{}", rate);
+            assert_eq!(*rate, (rate * 10.0).round() / 10.0);
+        }
+        let detailed = model(7).estimate_detailed(6, Some(ts), &rates, ts - 300);
+        assert_eq!(detailed.unwrap().unrounded_rate, 13.734621);
+    }
+
+    #[test]
+    pub fn test_sub_sat_rates() {
+        // a model whose outputs are between 0.15 and 0.85 sat/vB
+        let path = Path::new("models/fixtures/sub_sat.cbor");
+        let model = FeeModel::from_paths(path, path).unwrap();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let targets = [1u16, 2, 6, 144];
+        for target in targets.iter() {
+            let rate = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(rate, 1.0);
+        }
+
+        let options = EstimateOptions {
+            min_fee_rate: 0.0,
+            ..Default::default()
+        };
+        let model = model.with_options(options);
+        let estimates = model
+            .estimate_targets(&targets, Some(ts), &rates, ts - 300)
+            .unwrap();
+        for (target, expected) in targets.iter().zip(estimates.iter()) {
+            let estimate = |t| model.estimate_detailed(t, Some(ts), &rates, ts - 300);
+            let detailed = estimate(*target).unwrap();
+            assert!(
+                detailed.rate > 0.1 && detailed.rate < 0.9,
+                "{}",
+                detailed.rate
+            );
+            assert_eq!(detailed.rate, detailed.raw_output);
+            assert!(!detailed.clamped);
+            assert_eq!(detailed.rate, *expected);
+            let rate = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(rate, *expected);
+
+            let double = model.estimate_f64(*target, Some(ts), &rates, ts - 300);
+            assert!((double.unwrap() / rate as f64 - 1.0).abs() < 1e-5);
+            let fee_rate = model.estimate_rate(*target, Some(ts), &rates, ts - 300);
+            let fee_rate = fee_rate.unwrap();
+            assert_eq!(fee_rate.as_sat_per_vb(), rate as f64);
+            assert_eq!(fee_rate.as_btc_per_kvb(), rate as f64 / 100_000.0);
+            let smart = model.estimate_smart(*target, Some(ts), &rates, ts - 300);
+            let sat_per_kvb = libm::round(rate as f64 * 1000.0);
+            assert!(sat_per_kvb >= 100.0);
+            assert_eq!(smart.unwrap().feerate, sat_per_kvb / 100_000_000.0);
+            let fee = model.estimate_fee(*target, Some(ts), &rates, ts - 300, 564);
+            assert_eq!(fee.unwrap(), libm::ceil(rate as f64 * 141.0) as u64);
+        }
+
+        // the floor can sit below 1 sat/vB
+        let lowest = estimates.iter().cloned().fold(f32::INFINITY, f32::min);
+        let highest = estimates.iter().cloned().fold(0.0, f32::max);
+        let floor = (lowest + highest) / 2.0;
+        let options = EstimateOptions {
+            min_fee_rate: floor,
+            ..Default::default()
+        };
+        let model = model.with_options(options);
+        let rates: Vec<f64> = targets
+            .iter()
+            .map(|t| model.estimate(*t, Some(ts), &rates, ts - 300).unwrap() as f64)
+            .collect();
+        for (estimate, rate) in estimates.iter().zip(rates.iter()) {
+            assert_eq!(*rate as f32, estimate.max(floor));
+        }
+    }
+
+    #[test]
+    pub fn test_clamp() {
+        let ts = SAMPLE_TS;
+        let model = get_fee_model();
+        // many transactions in every bucket make the network estimate less than 1 sat/vB
+        let rates: Vec<f64> = model
+            .fee_buckets()
+            .limits()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, limit)| vec![limit * 0.99; 10 * (i % 5 + 1)])
+            .collect();
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(detailed.raw_output < 1.0);
+        assert_eq!(detailed.rate, 1.0);
+        assert!(detailed.clamped);
+        let rate = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(rate, 1.0);
+
+        let options = EstimateOptions {
+            min_fee_rate: 0.5,
+            max_fee_rate: Some(10.0),
+            ..Default::default()
+        };
+        let model = model.with_options(options);
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.rate, detailed.raw_output.max(0.5));
+
+        let rates = sample_rates();
+        let detailed = model
+            .estimate_detailed(1, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(detailed.raw_output > 10.0);
+        assert_eq!(detailed.rate, 10.0);
+        assert!(detailed.clamped);
+        let detailed = get_fee_model()
+            .estimate_detailed(1, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.rate, detailed.raw_output);
+        assert!(!detailed.clamped);
+    }
+
+    #[test]
+    pub fn test_insufficient_data() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        #[cfg(feature = "chrono")]
+        {
+            let err = model.estimate(6, None, &[], ts).unwrap_err();
+            assert!(matches!(err, Error::InsufficientData));
+        }
+        let err = model.estimate(6, Some(ts), &[], ts).unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+        let err = model.estimate_all(Some(ts), &[], ts - 300).unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+        let err = model
+            .estimate_with_buckets(6, Some(ts), &[0; 16], ts - 300)
+            .unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+        assert!(model.estimate(6, Some(ts), &[1.0], ts - 300).is_ok());
+    }
+
+    #[test]
+    pub fn test_delta_last_bounds() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let estimate = |model: &FeeModel, last_block_ts: i64| {
+            model
+                .estimate_detailed(6, Some(ts), &rates, last_block_ts)
+                .unwrap()
+        };
+
+        let in_range = estimate(&model, ts - 300);
+        assert_eq!(in_range.inputs.delta_last, 300.0);
+        assert!(!in_range.delta_last_clamped);
+
+        // last block in the future because of a lagging local clock
+        let future = estimate(&model, ts + 60);
+        assert_eq!(future.inputs.delta_last, 0.0);
+        assert!(future.delta_last_clamped);
+        assert_eq!(future.rate, estimate(&model, ts).rate);
+
+        // node offline for a day
+        let past = estimate(&model, ts - 24 * 60 * 60);
+        assert_eq!(past.inputs.delta_last, 7200.0);
+        assert!(past.delta_last_clamped);
+        assert_eq!(past.rate, estimate(&model, ts - 7200).rate);
+
+        let options = EstimateOptions {
+            min_delta_last: 60,
+            max_delta_last: None,
+            ..Default::default()
+        };
+        let model = model.with_options(options);
+        assert_eq!(estimate(&model, ts + 60).inputs.delta_last, 60.0);
+        let past = estimate(&model, ts - 24 * 60 * 60);
+        assert_eq!(past.inputs.delta_last, 86400.0);
+        assert!(!past.delta_last_clamped);
+        assert_eq!(model.max_delta_last(), None);
+
+        // the bound declared by a model applies with the one of the options
+        let mut high = get_high_model();
+        high.info.max_delta_last = Some(1800);
+        let declared = FeeModel::from_models(get_low_model(), high);
+        assert_eq!(declared.max_delta_last(), Some(1800));
+        let past = estimate(&declared, ts - 3600);
+        assert_eq!(past.inputs.delta_last, 1800.0);
+        assert!(past.delta_last_clamped);
+        let options = EstimateOptions {
+            max_delta_last: Some(600),
+            ..Default::default()
+        };
+        assert_eq!(declared.with_options(options).max_delta_last(), Some(600));
+    }
+
+    #[test]
+    pub fn test_last_block_plausible() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let estimate =
+            |model: &FeeModel, last_block_ts| model.estimate(6, Some(ts), &rates, last_block_ts);
+
+        // a last block in milliseconds, delta_last about -1.6e12 seconds
+        let err = estimate(&model, ts * 1000).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput("last_block_ts")));
+        assert_eq!(
+            err.to_string(),
+            "Invalid input last_block_ts, it must be the unix time in seconds of a recent block"
+        );
+        assert!(estimate(&model, ts + 2 * 60 * 60).is_ok());
+        assert!(estimate(&model, ts + 2 * 60 * 60 + 1).is_err());
+
+        // a last block a month old, or the timestamp in milliseconds
+        assert!(estimate(&model, ts - 30 * 24 * 60 * 60).is_ok());
+        let err = estimate(&model, ts - 30 * 24 * 60 * 60 - 1).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput("last_block_ts")));
+        let err = model.estimate(6, Some(ts * 1000), &rates, ts).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput("last_block_ts")));
+
+        let options = EstimateOptions {
+            max_last_block_future: Duration::from_secs(0),
+            max_last_block_age: None,
+            ..Default::default()
+        };
+        let lenient = model.with_options(options);
+        assert!(estimate(&lenient, ts + 1).is_err());
+        assert!(estimate(&lenient, ts - 365 * 24 * 60 * 60).is_ok());
+    }
+
+    #[test]
+    pub fn test_filter_fee_rates() {
+        let strict = get_fee_model();
+        let options = EstimateOptions {
+            filter_fee_rates: Some(10_000.0),
+            ..Default::default()
+        };
+        let lenient = get_fee_model().with_options(options);
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let mut noisy = rates.clone();
+        noisy.extend([-1.0, 0.0, 50_000.0].iter());
+        for model in [&strict, &lenient].iter() {
+            let err = model.estimate(6, Some(ts), &noisy, ts - 300).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("negative fee rate")));
+            let blocks = [BlockFeeRates {
+                height_offset: 0,
+                rates: noisy.clone(),
+            }];
+            let err = model.estimate_from_blocks(6, Some(ts), &blocks, ts - 300);
+            assert!(matches!(err, Err(Error::InvalidInput("negative fee rate"))));
+        }
+
+        noisy.retain(|rate| *rate >= 0.0);
+        let detailed = strict
+            .estimate_detailed(6, Some(ts), &noisy, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.filtered_fee_rates, 0);
+        let mut expected = strict.buckets(&rates).unwrap().to_vec();
+        expected[0] += 1;
+        *expected.last_mut().unwrap() += 1;
+        assert_eq!(strict.buckets(&noisy).unwrap().to_vec(), expected);
+
+        let detailed = lenient
+            .estimate_detailed(6, Some(ts), &noisy, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.filtered_fee_rates, 2);
+        let expected = strict.estimate_detailed(6, Some(ts), &rates, ts - 300);
+        assert_eq!(detailed.rate, expected.unwrap().rate);
+        for target in [1u16, 2, 6, 144].iter() {
+            let estimate = lenient.estimate(*target, Some(ts), &noisy, ts - 300);
+            let expected = strict.estimate(*target, Some(ts), &rates, ts - 300);
+            assert_eq!(estimate.unwrap(), expected.unwrap());
+        }
+        let only_zeros = lenient.estimate(6, Some(ts), &[0.0; 10], ts - 300);
+        assert!(matches!(only_zeros, Err(Error::InsufficientData)));
+    }
+
+    #[test]
+    pub fn test_non_finite() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let mut rates = sample_rates();
+        for rate in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY].iter() {
+            rates[10] = *rate;
+            let err = model.estimate(6, Some(ts), &rates, ts - 300).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("fee_rates")));
+            let err = model
+                .estimate_f64(6, Some(ts), &rates, ts - 300)
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("fee_rates")));
+            let err = model.estimate_all(Some(ts), &rates, ts - 300).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("fee_rates")));
+            let histogram = [(1.0, 1000), (*rate, 1000)];
+            let err = model
+                .estimate_from_histogram(6, Some(ts), &histogram, ts - 300)
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("histogram")));
+        }
+        rates[10] = 1.0;
+
+        // the largest delta between supported timestamps is still finite in f32
+        let options = EstimateOptions {
+            max_delta_last: None,
+            max_last_block_age: None,
+            ..Default::default()
+        };
+        let unbounded = get_fee_model().with_options(options);
+        let detailed = unbounded
+            .estimate_detailed(6, Some(i64::MAX / 1000), &rates, i64::MIN / 1000)
+            .unwrap();
+        assert_eq!(detailed.inputs.delta_last, i64::MAX as f32 / 1000.0);
+        assert!(detailed.raw_output.is_finite());
+
+        let mut high = get_high_model();
+        high.output_transform = OutputTransform::Log1p;
+        high.weights.l2_bias[0][0] += 1000.0;
+        let overflowing = FeeModel::from_models(get_low_model(), high);
+        let err = overflowing
+            .estimate(6, Some(ts), &rates, ts - 300)
+            .unwrap_err();
+        assert!(matches!(err, Error::NonFiniteOutput));
+        let err = overflowing
+            .estimate_f64(6, Some(ts), &rates, ts - 300)
+            .unwrap_err();
+        assert!(matches!(err, Error::NonFiniteOutput));
+        assert!(overflowing.estimate(1, Some(ts), &rates, ts - 300).is_ok());
+    }
+
+    #[test]
+    pub fn test_capped_features() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let count = model.fee_buckets().get(&rates).len();
+
+        // the greatest counts reach the models lowered to the default bound
+        let huge = vec![u64::MAX; count];
+        let (input, bounded) = model
+            .build_input(ts * 1000, &huge, (ts - 300) * 1000)
+            .unwrap();
+        assert!(input.buckets[..count].iter().all(|b| *b == 1_000_000.0));
+        assert!(bounded.buckets && !bounded.delta_last);
+        let capped = vec![1_000_000; count];
+        for target in [1u16, 6, 1008].iter() {
+            let estimate = model.estimate_with_buckets(*target, Some(ts), &huge, ts - 300);
+            let expected = model.estimate_with_buckets(*target, Some(ts), &capped, ts - 300);
+            assert_eq!(estimate.unwrap(), expected.unwrap());
+        }
+        let (_, bounded) = model
+            .build_input(ts * 1000, &capped, (ts - 300) * 1000)
+            .unwrap();
+        assert!(!bounded.buckets);
+
+        // without the bound they are still finite, rounded to single precision
+        let unbounded = model.clone().with_options(EstimateOptions {
+            max_bucket_count: None,
+            ..Default::default()
+        });
+        let (input, bounded) = unbounded
+            .build_input(ts * 1000, &huge, (ts - 300) * 1000)
+            .unwrap();
+        assert!(input.buckets[..count].iter().all(|b| *b == u64::MAX as f32));
+        assert!(!bounded.buckets);
+
+        // the capping is reported with the estimates from the fee rates
+        let low_bound = model.clone().with_options(EstimateOptions {
+            max_bucket_count: Some(50),
+            ..Default::default()
+        });
+        let detailed = low_bound
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(detailed.buckets_capped);
+        assert!(detailed.inputs.buckets.iter().all(|b| *b <= 50.0));
+        assert!(detailed.inputs.buckets.contains(&50.0));
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(!detailed.buckets_capped);
+
+        // a last block years ago is bounded like any stale one
+        let years = 5 * 365 * 24 * 60 * 60;
+        let any_age = model.with_options(EstimateOptions {
+            max_last_block_age: None,
+            ..Default::default()
+        });
+        let detailed = any_age
+            .estimate_detailed(6, Some(ts), &rates, ts - years)
+            .unwrap();
+        assert_eq!(detailed.inputs.delta_last, 7200.0);
+        assert!(detailed.delta_last_clamped);
+        assert!(detailed.rate.is_finite());
+        let (input, bounded) = any_age
+            .build_input(ts * 1000, &huge, (ts - years) * 1000)
+            .unwrap();
+        assert!(bounded.buckets && bounded.delta_last);
+        assert!(input
+            .buckets
+            .iter()
+            .chain(&[input.delta_last])
+            .all(|f| f.is_finite()));
+
+        assert_eq!(saturating_f32(f64::MAX), f32::MAX);
+        assert_eq!(saturating_f32(f64::MIN), f32::MIN);
+        assert!(saturating_f32(f64::NAN).is_nan());
+        assert_eq!(count_feature(f64::INFINITY, None), f32::MAX);
+        assert_eq!(count_feature(123.0, Some(100)), 100.0);
+    }
+
+    #[test]
+    pub fn test_estimate_partial() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        // every block with the same transactions
+        let block: Vec<f64> = (0..100).map(|i| 1.0 + (i % 60) as f64 * 2.5).collect();
+        let blocks =
+            |n: usize| -> Vec<f64> { block.iter().copied().cycle().take(n * 100).collect() };
+        let full = blocks(10);
+        for target in [1u16, 2, 6, 144].iter() {
+            let expected = model
+                .estimate_detailed(*target, Some(ts), &full, ts - 300)
+                .unwrap();
+            assert_eq!(expected.blocks_covered, None);
+            let half = model
+                .estimate_partial_detailed(*target, Some(ts), &blocks(5), 5, ts - 300)
+                .unwrap();
+            assert_eq!(half.inputs.buckets, expected.inputs.buckets);
+            assert_eq!(half.rate, expected.rate);
+            assert_eq!(half.blocks_covered, Some(5));
+            // the scaled counts are rounded
+            let three = model
+                .estimate_partial(*target, Some(ts), &blocks(3), 3, ts - 300)
+                .unwrap();
+            assert!((three - expected.rate).abs() / expected.rate < 0.01);
+            let whole = model
+                .estimate_partial_detailed(*target, Some(ts), &full, 10, ts - 300)
+                .unwrap();
+            assert_eq!(whole, expected);
+        }
+        // without scaling a partial window looks like a quiet mempool
+        let unscaled = model.estimate(6, Some(ts), &blocks(3), ts - 300).unwrap();
+        let scaled = model
+            .estimate_partial(6, Some(ts), &blocks(3), 3, ts - 300)
+            .unwrap();
+        assert_ne!(unscaled, scaled);
+
+        let partial = |model: &FeeModel, blocks_covered| {
+            model.estimate_partial(6, Some(ts), &blocks(2), blocks_covered, ts - 300)
+        };
+        assert!(matches!(partial(&model, 2), Err(Error::InsufficientData)));
+        assert!(matches!(
+            partial(&model, 0),
+            Err(Error::InvalidInput("blocks_covered"))
+        ));
+        assert!(matches!(
+            partial(&model, 11),
+            Err(Error::InvalidInput("blocks_covered"))
+        ));
+        let lenient = model.with_options(EstimateOptions {
+            min_blocks_covered: 1,
+            ..Default::default()
+        });
+        assert!(partial(&lenient, 2).is_ok());
+        assert!(matches!(partial(&lenient, 0), Err(Error::InvalidInput(_))));
+    }
+
+    #[test]
+    pub fn test_estimate_iter() {
+        let ts = SAMPLE_TS;
+        let mut state = 3u64;
+        let mut next = || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            state >> 33
+        };
+        let filtering = get_fee_model().with_options(EstimateOptions {
+            filter_fee_rates: Some(150.0),
+            ..Default::default()
+        });
+        let models = [get_fee_model(), filtering];
+        for i in 0..40 {
+            let model = &models[i % 2];
+            let len = 1 + next() as usize % 3000;
+            let mut rates: Vec<f64> = (0..len).map(|_| (next() % 50_000) as f64 / 97.0).collect();
+            rates.extend([0.0, 1.5, 500.0].iter().take(i % 4));
+            let stream = || rates.iter().copied();
+            for target in [1u16, 2, 6, 144].iter() {
+                let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+                let streamed = model
+                    .estimate_iter(*target, Some(ts), stream(), ts - 300)
+                    .unwrap();
+                assert_eq!(streamed, expected);
+                let expected = model
+                    .estimate_detailed(*target, Some(ts), &rates, ts - 300)
+                    .unwrap();
+                let streamed = model
+                    .estimate_iter_detailed(*target, Some(ts), stream(), ts - 300)
+                    .unwrap();
+                assert_eq!(streamed, expected);
+                assert_eq!(streamed.fee_rates_consumed, rates.len());
+            }
+        }
+        let filtered = models[1]
+            .estimate_iter_detailed(6, Some(ts), vec![0.0, 10.0, 200.0, 20.0], ts - 300)
+            .unwrap();
+        assert_eq!(filtered.fee_rates_consumed, 4);
+        assert_eq!(filtered.filtered_fee_rates, 2);
+
+        let model = get_fee_model();
+        let estimate_iter = |rates: Vec<f64>| model.estimate_iter(6, Some(ts), rates, ts - 300);
+        let err = estimate_iter(vec![1.0, f64::NAN, -1.0]).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput("fee_rates")));
+        let err = estimate_iter(vec![1.0, -1.0, f64::NAN]).unwrap_err();
+        assert!(matches!(err, Error::InvalidInput("negative fee rate")));
+        let err = estimate_iter(vec![]).unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+
+        // the models using the limit of the buckets get the collected rates
+        let model = FeeModel::from_models(
+            get_test_model_with_hour_renamed("bucket_limit"),
+            get_test_model_with_hour_renamed("bucket_limit"),
+        );
+        let rates = sample_rates();
+        let expected = model.estimate_detailed(6, Some(ts), &rates, ts - 300);
+        let streamed = model.estimate_iter_detailed(6, Some(ts), rates.clone(), ts - 300);
+        assert_eq!(streamed.unwrap(), expected.unwrap());
+    }
+
+    #[test]
+    pub fn test_time_features() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        let with_time = |time_features| {
+            get_fee_model().with_options(EstimateOptions {
+                time_features,
+                ..Default::default()
+            })
+        };
+        let fixed = with_time(TimeFeatures::Fixed { hour: 5, day: 2 });
+        for target in [1u16, 2, 6, 144].iter() {
+            let overrides = FeatureOverrides {
+                day_of_week: Some(2),
+                hour: Some(5),
+                delta_last: None,
+            };
+            let expected = model
+                .estimate_with_features(*target, Some(ts), &rates, ts - 300, overrides)
+                .unwrap();
+            let estimate = fixed.estimate(*target, Some(ts), &rates, ts - 300);
+            assert_eq!(estimate.unwrap(), expected);
+        }
+        let detailed = fixed
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(
+            (detailed.inputs.day_of_week, detailed.inputs.hour),
+            (2.0, 5.0)
+        );
+
+        let samples = 24;
+        let marginalized = with_time(TimeFeatures::Marginalize { samples });
+        for target in [1u16, 2, 6, 144].iter() {
+            let outputs: Vec<FeeEstimate> = (0..samples)
+                .map(|i| {
+                    let (day, hour) = TimeFeatures::sample(i, samples);
+                    let fixed = with_time(TimeFeatures::Fixed { hour, day });
+                    fixed
+                        .estimate_detailed(*target, Some(ts), &rates, ts - 300)
+                        .unwrap()
+                })
+                .collect();
+            let estimate = marginalized
+                .estimate_detailed(*target, Some(ts), &rates, ts - 300)
+                .unwrap();
+            let rates_at = outputs.iter().map(|e| e.rate);
+            let min = rates_at.clone().fold(f32::INFINITY, f32::min);
+            let max = rates_at.fold(f32::NEG_INFINITY, f32::max);
+            assert!(min < max);
+            assert!(min <= estimate.rate && estimate.rate <= max);
+            let mean = outputs.iter().map(|e| e.raw_output).sum::<f32>() / samples as f32;
+            assert_approx_eq(estimate.raw_output, mean);
+            // the same estimate at any time of the day
+            let later = marginalized
+                .estimate(*target, Some(ts + 5 * 3600), &rates, ts + 5 * 3600 - 300)
+                .unwrap();
+            assert_eq!(later, estimate.rate);
+            let f64_estimate = marginalized
+                .estimate_f64(*target, Some(ts), &rates, ts - 300)
+                .unwrap();
+            assert_approx_eq(f64_estimate as f32, estimate.rate);
+        }
+        // the inputs report the mean time of the samples
+        let detailed = marginalized
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        let times = (0..samples).map(|i| TimeFeatures::sample(i, samples));
+        let mean_day = times.clone().map(|(day, _)| day as f32).sum::<f32>() / samples as f32;
+        let mean_hour = times.map(|(_, hour)| hour as f32).sum::<f32>() / samples as f32;
+        assert_eq!(
+            (detailed.inputs.day_of_week, detailed.inputs.hour),
+            (mean_day, mean_hour)
+        );
+
+        // the quantiles and the attributions are averaged over the samples too
+        let bytes = include_bytes!("../models/fixtures/quantiles.cbor");
+        let mut hourly = with_field_renamed(bytes, "delta_last", "hour");
+        // not linear in the hour
+        hourly.output_transform = OutputTransform::Sqrt;
+        let hourly = |time_features| {
+            FeeModel::from_models(hourly.clone(), hourly.clone()).with_options(EstimateOptions {
+                time_features,
+                ..Default::default()
+            })
+        };
+        let quantiles = [0.1, 0.5, 0.9];
+        let mut quantiles_at = vec![0.0f32; quantiles.len()];
+        let mut attributions_at = BTreeMap::new();
+        for i in 0..samples {
+            let (day, hour) = TimeFeatures::sample(i, samples);
+            let model = hourly(TimeFeatures::Fixed { hour, day });
+            let estimates = model.estimate_quantiles(6, Some(ts), &rates, ts - 300, &quantiles);
+            for (sum, estimate) in quantiles_at.iter_mut().zip(estimates.unwrap()) {
+                *sum += estimate / samples as f32;
+            }
+            let model = with_time(TimeFeatures::Fixed { hour, day });
+            for (name, value) in model.explain(6, Some(ts), &rates, ts - 300).unwrap() {
+                *attributions_at.entry(name).or_insert(0.0) += value / samples as f32;
+            }
+        }
+        let model = hourly(TimeFeatures::Marginalize { samples });
+        let estimates = model.estimate_quantiles(6, Some(ts), &rates, ts - 300, &quantiles);
+        let estimates = estimates.unwrap();
+        for (estimate, expected) in estimates.iter().zip(quantiles_at) {
+            assert_approx_eq(*estimate, expected);
+        }
+        let now = hourly(TimeFeatures::FromTimestamp);
+        let estimates_now = now.estimate_quantiles(6, Some(ts), &rates, ts - 300, &quantiles);
+        assert_ne!(estimates, estimates_now.unwrap());
+        let attributions = marginalized.explain(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(attributions.len(), attributions_at.len());
+        for (name, value) in attributions {
+            assert_approx_eq(value, attributions_at[&name]);
+        }
+
+        // 168 samples are every hour of the week
+        let week: BTreeSet<(u8, u8)> = (0..168).map(|i| TimeFeatures::sample(i, 168)).collect();
+        assert_eq!(week.len(), 168);
+        assert!(week.iter().all(|(day, hour)| *day <= 6 && *hour <= 23));
+
+        for time_features in [
+            TimeFeatures::Fixed { hour: 24, day: 0 },
+            TimeFeatures::Fixed { hour: 0, day: 7 },
+            TimeFeatures::Marginalize { samples: 0 },
+        ] {
+            let err = with_time(time_features)
+                .estimate(6, Some(ts), &rates, ts - 300)
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("time_features")));
+        }
+    }
+
+    #[test]
+    pub fn test_ensemble() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let shifted = |shift: f32| {
+            let mut high = get_high_model();
+            high.weights.l2_bias[0][0] += shift;
+            high
+        };
+        let low = || vec![get_low_model()];
+        let output = |model: &FeeModel, target: u16| {
+            let detailed = model.estimate_detailed(target, Some(ts), &rates, ts - 300);
+            detailed.unwrap()
+        };
+        let single = |shift: f32| {
+            let model = FeeModel::ensemble(low(), vec![shifted(shift)]).unwrap();
+            output(&model, 6).raw_output
+        };
+        let (a, b, c) = (single(0.0), single(1.0), single(5.0));
+        assert_eq!(a, output(&get_fee_model(), 6).raw_output);
+
+        let ensemble = FeeModel::ensemble(low(), vec![shifted(0.0), shifted(1.0)]).unwrap();
+        let detailed = output(&ensemble, 6);
+        assert_eq!(detailed.member_outputs, vec![a, b]);
+        assert_approx_eq(detailed.raw_output, (a + b) / 2.0);
+        let rate = ensemble.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(rate, detailed.rate);
+        let detailed = output(&ensemble, 1);
+        assert_eq!(detailed.member_outputs.len(), 1);
+        assert_eq!(detailed.raw_output, output(&get_fee_model(), 1).raw_output);
+        let f64_output = ensemble
+            .estimate_f64(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!((f64_output - (a as f64 + b as f64) / 2.0).abs() < 1e-4);
+
+        let members = vec![shifted(5.0), shifted(0.0), shifted(1.0)];
+        let options = EstimateOptions {
+            combine: Combine::Median,
+            ..Default::default()
+        };
+        let median = FeeModel::ensemble(low(), members)
+            .unwrap()
+            .with_options(options);
+        let detailed = output(&median, 6);
+        assert_eq!(detailed.member_outputs, vec![c, a, b]);
+        assert_eq!(detailed.raw_output, b);
+
+        let options = EstimateOptions {
+            blend: Some(BlendRange { from: 2, to: 6 }),
+            ..Default::default()
+        };
+        let blended = ensemble.with_options(options);
+        assert_eq!(output(&blended, 4).member_outputs.len(), 3);
+
+        let mismatch = vec![shifted(0.0), get_test_model_with_extra()];
+        let err = FeeModel::ensemble(low(), mismatch).err().unwrap();
+        assert!(matches!(err, Error::EnsembleMismatch(1)));
+        let err = FeeModel::ensemble(vec![], vec![shifted(0.0)])
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::EmptyEnsemble));
+    }
+
+    #[test]
+    pub fn test_concurrent_estimates() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = std::sync::Arc::new(get_fee_model());
+        let targets = [1u16, 2, 3, 6, 12, 144, 1008];
+        let expected: Vec<f32> = targets
+            .iter()
+            .map(|t| model.estimate(*t, Some(ts), &rates, ts - 300).unwrap())
+            .collect();
+        let barrier = std::sync::Arc::new(std::sync::Barrier::new(8));
+        let threads: Vec<_> = (0..8)
+            .map(|thread| {
+                let (model, barrier) = (model.clone(), barrier.clone());
+                let rates = rates.clone();
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    (0..200)
+                        .map(|i| {
+                            let target = targets[(thread + i) % targets.len()];
+                            let estimate = model.estimate(target, Some(ts), &rates, ts - 300);
+                            (target, estimate.unwrap())
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for thread in threads {
+            for (target, estimate) in thread.join().unwrap() {
+                let index = targets.iter().position(|t| *t == target).unwrap();
+                assert_eq!(estimate.to_bits(), expected[index].to_bits());
+            }
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    pub fn test_toy() {
+        let model = FeeModel::toy();
+        let ts = SAMPLE_TS;
+        let formula = |target: u16, b15: u64| {
+            let target = target.min(MAX_TARGET) as f32;
+            1.0f32 + ((1008.0 - target) / 100.0 + b15 as f32 / 1000.0)
+        };
+        for high_fee_txs in [0u64, 1, 250, 1000, 123_456].iter() {
+            let mut rates = sample_rates();
+            rates.extend((0..*high_fee_txs).map(|i| 437.9 + i as f64));
+            for target in [1u16, 2, 3, 6, 25, 144, 1007, 1008, 2000].iter() {
+                let expected = formula(*target, *high_fee_txs);
+                let estimate = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+                assert_eq!(estimate, expected, "{} {}", target, high_fee_txs);
+                // the other features are ignored
+                let later = model.estimate(*target, Some(ts + 12345), &rates, ts - 60);
+                assert_eq!(later.unwrap(), expected);
+            }
+        }
+        assert_eq!(
+            model.estimate(6, Some(ts), &[1.0], ts - 300).unwrap(),
+            11.02
+        );
+        let rates = vec![500.0; 1000];
+        assert_eq!(
+            model.estimate(6, Some(ts), &rates, ts - 300).unwrap(),
+            12.02
+        );
+
+        let mut buckets = vec![7u64; model.fee_buckets().len()];
+        buckets[15] = 4321;
+        let estimate = model.estimate_with_buckets(3, Some(ts), &buckets, ts - 300);
+        assert_eq!(estimate.unwrap(), formula(3, 4321));
+        let detailed = model
+            .estimate_detailed(1, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.model, ModelKind::Low);
+        assert_eq!(detailed.raw_output, formula(1, 1000));
+        assert!(!detailed.clamped);
+
+        // bounded like the other models
+        let bounded = model.with_options(EstimateOptions {
+            max_fee_rate: Some(5.0),
+            ..Default::default()
+        });
+        assert_eq!(
+            bounded.estimate(6, Some(ts), &rates, ts - 300).unwrap(),
+            5.0
+        );
+    }
+
+    #[test]
+    pub fn test_debug_clone() {
+        #[derive(Debug, Clone)]
+        struct AppState {
+            model: FeeModel,
+        }
+
+        let state = AppState {
+            model: get_fee_model(),
+        };
+        let formatted = format!("{:?}", state);
+        let start =
+            "AppState { model: FeeModel { tiers: [Tier { max_target: 2, models: [ModelData {";
+        assert!(formatted.starts_with(start));
+        assert!(formatted.contains("checksum"));
+        assert!(formatted.len() < 10_000, "{}", formatted.len());
+        let cloned = state.clone();
+        assert_eq!(format!("{:?}", cloned), formatted);
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        assert_eq!(
+            cloned
+                .model
+                .estimate(6, Some(ts), &rates, ts - 300)
+                .unwrap(),
+            get_fee_model()
+                .estimate(6, Some(ts), &rates, ts - 300)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bucket_scaling() {
+        let ts = SAMPLE_TS;
+        let adaptive = BucketScaling::Adaptive { percentile: 99.0 };
+        // the embedded models are trained with a fixed limit
+        let err = get_fee_model().with_bucket_scaling(adaptive).unwrap_err();
+        assert!(matches!(err, Error::FixedBucketLimit("low")));
+        assert!(get_fee_model()
+            .with_bucket_scaling(BucketScaling::Fixed)
+            .is_ok());
+
+        let model = || {
+            FeeModel::from_models(
+                get_test_model_with_hour_renamed("bucket_limit"),
+                get_test_model_with_hour_renamed("bucket_limit"),
+            )
+        };
+        for percentile in [0.0, 101.0, f32::NAN] {
+            let scaling = BucketScaling::Adaptive { percentile };
+            let err = model().with_bucket_scaling(scaling).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("percentile")));
+        }
+
+        // a spike over the fixed limit piles up in the last bucket
+        let mut rates = sample_rates();
+        rates.extend((0..1000).map(|i| 600.0 + i as f64));
+        let fixed = model();
+        let estimate = fixed.estimate_detailed(6, Some(ts), &rates, ts - 300);
+        let buckets = estimate.unwrap().inputs.buckets;
+        assert_eq!(buckets[15], 1000.0);
+        let extras = fixed.summary_extras(&rates).unwrap();
+        assert_eq!(
+            extras["bucket_limit"],
+            *fixed.fee_buckets().limits().last().unwrap() as f32
+        );
+
+        let scaled = model().with_bucket_scaling(adaptive).unwrap();
+        assert_eq!(scaled.bucket_scaling(), adaptive);
+        let estimate = scaled.estimate_detailed(6, Some(ts), &rates, ts - 300);
+        let buckets = estimate.unwrap().inputs.buckets;
+        assert!(buckets[15] < 300.0);
+        assert!(buckets[13..15].iter().all(|b| *b > 200.0));
+        assert_eq!(buckets.iter().sum::<f32>(), 2000.0);
+        // the 99th percentile, 1579 sat/vB, rounded up to the increment of 50%
+        let limit = 1.5f64.powi(19);
+        let extras = scaled.summary_extras(&rates).unwrap();
+        assert_approx_eq(extras["bucket_limit"], limit as f32);
+        let mut given = BTreeMap::new();
+        given.insert("bucket_limit".to_string(), limit as f32);
+        let with_extras = scaled.estimate_with_extras(6, Some(ts), &rates, ts - 300, &given);
+        let estimate = scaled.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_approx_eq(with_extras.unwrap(), estimate);
+    }
+
+    #[test]
+    pub fn test_summary_extras() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let p50 = summary_features(&rates).p50;
+        let model = FeeModel::from_models(
+            get_test_model_with_hour_renamed("p50"),
+            get_test_model_with_hour_renamed("p50"),
+        );
+        let (mut input, _) = model
+            .build_input(
+                ts * 1000,
+                &model.fee_buckets().get(&rates),
+                (ts - 300) * 1000,
+            )
+            .unwrap();
+        input.confirms_in = 6.0;
+        let mut extras = BTreeMap::new();
+        extras.insert("p50".to_string(), p50 as f32);
+        let expected = model.tiers[1].models[0].norm_predict_with_extras(&input, &extras);
+        let expected = expected.unwrap();
+        let got = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_approx_eq(got, expected.max(1.0));
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.raw_output, expected);
+        let targets = model.estimate_targets(&[6], Some(ts), &rates, ts - 300);
+        assert_eq!(targets.unwrap(), vec![got]);
+
+        // given extras take precedence
+        extras.insert("p50".to_string(), 3.0);
+        let with_extras = model.estimate_with_extras(6, Some(ts), &rates, ts - 300, &extras);
+        let given = model.tiers[1].models[0]
+            .norm_predict_with_extras(&input, &extras)
+            .unwrap();
+        assert_approx_eq(with_extras.unwrap(), given.max(1.0));
+        assert_ne!(given, expected);
+
+        // other extras are zero, unless given in estimate_with_extras
+        let model = FeeModel::from_models(
+            get_test_model_with_hour_renamed("p50"),
+            get_test_model_with_extra(),
+        );
+        let expected = model.tiers[1].models[0].norm_predict_input(&input).unwrap();
+        let got = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_approx_eq(got, expected.max(1.0));
+        let err = model
+            .estimate_with_extras(6, Some(ts), &rates, ts - 300, &BTreeMap::new())
+            .unwrap_err();
+        assert!(matches!(err, Error::MissingExtra(f) if f == "mempool_vsize"));
+    }
+
+    #[test]
+    pub fn test_validate_features() {
+        get_fee_model().validate_features().unwrap();
+        let model = FeeModel::from_models(get_low_model(), get_test_model_with_extra());
+        let err = model.validate_features().unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::FeatureMismatch { model: "high", missing, extra }
+                if missing == &["hour"] && extra == &["mempool_vsize"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The features of the high model don't match the inputs, missing: [hour], extra: \
+             [mempool_vsize]"
+        );
+
+        // checked at load
+        let low = get_low_model().to_vec().unwrap();
+        let extra = get_test_model_with_extra().to_vec().unwrap();
+        let err = FeeModel::from_bytes(&extra, &low).err().unwrap();
+        assert!(matches!(err, Error::FeatureMismatch { model: "low", .. }));
+        let quantiles = include_bytes!("../models/fixtures/quantiles.cbor");
+        let err = FeeModel::from_bytes(&low, quantiles).err().unwrap();
+        assert!(matches!(
+            err,
+            Error::FeatureMismatch { model: "high", missing, extra }
+                if missing == ["day_of_week", "hour"] && extra.is_empty()
+        ));
+        FeeModel::from_bytes(&low, &low).unwrap();
+    }
+
+    #[test]
+    pub fn test_explain() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        for target in [1u16, 6].iter() {
+            let attributions = model.explain(*target, Some(ts), &rates, ts - 300).unwrap();
+            let detailed = model.estimate_detailed(*target, Some(ts), &rates, ts - 300);
+            let models = model.model_for(*target).1;
+            let expected = models[0].explain_input(&detailed.unwrap().inputs).unwrap();
+            assert_eq!(attributions, expected);
+            assert_eq!(attributions.len(), models[0].feature_names().len());
+        }
+        let members = vec![get_high_model(), get_high_model()];
+        let ensemble = FeeModel::ensemble(vec![get_low_model()], members).unwrap();
+        assert_eq!(
+            ensemble.explain(6, Some(ts), &rates, ts - 300).unwrap(),
+            get_fee_model()
+                .explain(6, Some(ts), &rates, ts - 300)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_estimate_quantiles() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let quantiles = || {
+            let bytes = include_bytes!("../models/fixtures/quantiles.cbor");
+            ModelData::from_slice(bytes).unwrap()
+        };
+        let model = FeeModel::from_models(quantiles(), quantiles());
+        let estimate = |model: &FeeModel, target: u16, quantiles: &[f32]| {
+            model.estimate_quantiles(target, Some(ts), &rates, ts - 300, quantiles)
+        };
+        // the outputs are 1.5, 0.5 and 1 times `2 * confirms_in + delta_last / 100 - 0.5`
+        let got = estimate(&model, 6, &[0.5, 0.1, 0.9]).unwrap();
+        assert_eq!(got, vec![14.5, 7.25, 21.75]);
+        assert_eq!(estimate(&model, 1, &[0.1]).unwrap(), vec![2.25]);
+        assert_eq!(model.estimate(6, Some(ts), &rates, ts - 300).unwrap(), 14.5);
+        let err = estimate(&model, 6, &[0.1, 0.25]).unwrap_err();
+        assert!(matches!(err, Error::MissingQuantile(q) if q == 0.25));
+        let err = estimate(&get_fee_model(), 6, &[0.5]).unwrap_err();
+        assert!(matches!(err, Error::MissingQuantile(q) if q == 0.5));
+
+        let ensemble = FeeModel::ensemble(vec![quantiles()], vec![quantiles(), quantiles()]);
+        let got = estimate(&ensemble.unwrap(), 6, &[0.1, 0.5, 0.9]).unwrap();
+        assert_eq!(got, vec![7.25, 14.5, 21.75]);
+        let options = EstimateOptions {
+            blend: Some(BlendRange { from: 2, to: 6 }),
+            ..Default::default()
+        };
+        let blended = model.with_options(options);
+        let got = estimate(&blended, 4, &[0.1, 0.5, 0.9]).unwrap();
+        assert_eq!(got, vec![5.25, 10.5, 15.75]);
+    }
+
+    #[test]
+    pub fn test_timestamps() {
+        let model = get_fee_model();
+        let rates = sample_rates();
+        // after 2038, the u32 overflow
+        let ts = i64::from(u32::MAX) + 1_000_000;
+        let detailed = model
+            .estimate_detailed(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(detailed.inputs.delta_last, 300.0);
+
+        let u32_ts = 1613708045u32;
+        let from_u32 = model
+            .estimate(6, Some(u32_ts.into()), &rates, i64::from(u32_ts - 300))
+            .unwrap();
+        let expected = model
+            .estimate(6, Some(SAMPLE_TS), &rates, SAMPLE_TS - 300)
+            .unwrap();
+        assert_eq!(from_u32, expected);
+
+        for invalid in [i64::MAX, i64::MIN].iter() {
+            let err = model.estimate(6, Some(*invalid), &rates, ts).unwrap_err();
+            assert!(matches!(err, Error::InvalidTimestamp(t) if t == *invalid));
+            let err = model.estimate(6, Some(ts), &rates, *invalid).unwrap_err();
+            assert!(matches!(err, Error::InvalidTimestamp(t) if t == *invalid));
+        }
+    }
+
+    #[test]
+    fn test_bucket_spec() {
+        let model = get_fee_model();
+        let spec = model.bucket_spec();
+        assert_eq!(spec, model.fee_buckets().spec());
+        assert_eq!(spec.limits.len(), 16);
+        // 50 and 500 sat/vB are not limits, 500 is in the last bucket with the greater rates
+        assert_eq!(spec.bucketize(50.0), 9);
+        assert_eq!(spec.bucketize(500.0), 15);
+        assert_eq!(spec.bucketize(spec.limits[3]), 4);
+
+        // the counts of the spec estimate like the fee rates
+        let ts = SAMPLE_TS;
+        let mut rates = sample_rates();
+        rates.extend(spec.limits.iter());
+        let mut counts = vec![0u64; spec.limits.len()];
+        for rate in rates.iter() {
+            counts[spec.bucketize(*rate)] += 1;
+        }
+        for target in [1u16, 6, 144].iter() {
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            let from_counts = model
+                .estimate_with_buckets(*target, Some(ts), &counts, ts - 300)
+                .unwrap();
+            assert_eq!(from_counts, expected);
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    pub fn test_estimate_at() {
+        use chrono::{TimeZone, Utc};
+
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let now = Utc.timestamp_opt(ts, 0).unwrap();
+        let last_block = Utc.timestamp_opt(ts - 300, 0).unwrap();
+        for target in [1u16, 6, 144].iter() {
+            let at = model.estimate_at(*target, now, &rates, last_block).unwrap();
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(at, expected);
+        }
+
+        // 2021-02-19 04:14:05 UTC is a Friday
+        let buckets = model.fee_buckets().get(&rates);
+        let (input, _) = model
+            .build_input(
+                now.timestamp_millis(),
+                &buckets,
+                last_block.timestamp_millis(),
+            )
+            .unwrap();
+        assert_eq!(input.day_of_week, 4.0);
+        assert_eq!(input.hour, 4.0);
+        assert_eq!(input.delta_last, 300.0);
+
+        let last_block = Utc.timestamp_millis_opt((ts - 300) * 1000 + 500).unwrap();
+        let at = model.estimate_at(6, now, &rates, last_block).unwrap();
+        let (input, _) = model
+            .build_input(
+                now.timestamp_millis(),
+                &buckets,
+                last_block.timestamp_millis(),
+            )
+            .unwrap();
+        assert_eq!(input.delta_last, 299.5);
+        assert_ne!(at, model.estimate(6, Some(ts), &rates, ts - 300).unwrap());
+    }
+
+    #[test]
+    fn test_estimate_at_time() {
+        use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        let secs = model.estimate_at_time(6, Some(ts as u64), &rates, ts as u64 - 300);
+        assert_eq!(secs.unwrap(), expected);
+        let time = |millis: i64| UNIX_EPOCH + Duration::from_millis(millis as u64);
+        let now = time(ts * 1000);
+        let at = model.estimate_at_time(6, Some(now), &rates, time((ts - 300) * 1000));
+        assert_eq!(at.unwrap(), expected);
+        #[cfg(feature = "chrono")]
+        {
+            let clocked = get_fee_model().with_clock(move || now.into());
+            let at = clocked.estimate_at_time(6, None, &rates, time((ts - 300) * 1000));
+            assert_eq!(at.unwrap(), expected);
+        }
+
+        // the milliseconds are kept, like in estimate_at
+        let at = model.estimate_at_time(6, Some(now), &rates, time((ts - 300) * 1000 + 500));
+        assert_ne!(at.unwrap(), expected);
+
+        let before_epoch: SystemTime = UNIX_EPOCH - Duration::from_secs(1);
+        let err = model.estimate_at_time(6, Some(now), &rates, before_epoch);
+        assert!(matches!(err, Err(Error::InvalidTimestamp(-1))));
+        let err = model.estimate_at_time(6, Some(u64::MAX), &rates, ts as u64);
+        assert!(matches!(err, Err(Error::InvalidTimestamp(_))));
+    }
+
+    #[cfg(not(feature = "chrono"))]
+    #[test]
+    pub fn test_missing_timestamp() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let err = model.estimate(6, None, &rates, ts - 300).unwrap_err();
+        assert!(matches!(err, Error::MissingTimestamp));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    pub fn test_with_clock() {
+        use chrono::{TimeZone, Utc};
+
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model().with_clock(move || Utc.timestamp_opt(ts, 0).unwrap());
+        let detailed = model.estimate_detailed(6, None, &rates, ts - 300).unwrap();
+        assert_eq!(detailed.inputs.delta_last, 300.0);
+        assert_eq!(detailed.inputs.hour, 4.0);
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(model.estimate(6, None, &rates, ts - 300).unwrap(), expected);
+
+        // the timestamp given has precedence over the clock
+        let later = model
+            .estimate_detailed(6, Some(ts + 3600), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(later.inputs.hour, 5.0);
+    }
+
+    #[test]
+    pub fn test_estimate_from_histogram() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let histogram: Vec<(f64, u64)> = (1..300).map(|i| (i as f64, 250 * (i % 7))).collect();
+        let rates: Vec<f64> = histogram
+            .iter()
+            .flat_map(|(rate, vsize)| vec![*rate; (*vsize / 250) as usize])
+            .collect();
+        for target in [1u16, 6, 144].iter() {
+            let from_histogram = model
+                .estimate_from_histogram(*target, Some(ts), &histogram, ts - 300)
+                .unwrap();
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(from_histogram, expected);
+        }
+
+        // the vsize of a bucket counts as many transactions
+        let big = [(10.0, 2500)];
+        let small = [10.0; 10];
+        assert_eq!(
+            model
+                .estimate_from_histogram(6, Some(ts), &big, ts - 300)
+                .unwrap(),
+            model.estimate(6, Some(ts), &small, ts - 300).unwrap()
+        );
+        let err = model
+            .estimate_from_histogram(6, Some(ts), &[], ts - 300)
+            .unwrap_err();
+        assert!(matches!(err, Error::InsufficientData));
+        let err = model.estimate_from_histogram(6, Some(ts), &[(-5.0, 1000)], ts - 300);
+        assert!(matches!(err, Err(Error::InvalidInput("negative fee rate"))));
+        let err = model.estimate(6, Some(ts), &[-5.0], ts - 300);
+        assert!(matches!(err, Err(Error::InvalidInput("negative fee rate"))));
+
+        // the implausible fee rates are filtered like the ones of single transactions
+        let filtering = get_fee_model().with_options(EstimateOptions {
+            filter_fee_rates: Some(100.0),
+            ..Default::default()
+        });
+        assert_eq!(
+            filtering
+                .estimate_from_histogram(6, Some(ts), &histogram, ts - 300)
+                .unwrap(),
+            filtering.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn test_estimate_from_blocks() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let blocks: Vec<BlockFeeRates> = rates
+            .chunks(100)
+            .enumerate()
+            .map(|(i, rates)| BlockFeeRates {
+                height_offset: i as u8,
+                rates: rates.to_vec(),
+            })
+            .collect();
+        let model = get_fee_model();
+        for target in [1u16, 6].iter() {
+            let got = model.estimate_from_blocks(*target, Some(ts), &blocks, ts - 300);
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(got.unwrap().to_bits(), expected.to_bits());
+        }
+
+        let decayed = |half_life| {
+            let options = EstimateOptions {
+                recency_half_life: Some(half_life),
+                ..Default::default()
+            };
+            get_fee_model().with_options(options).estimate_from_blocks(
+                6,
+                Some(ts),
+                &blocks,
+                ts - 300,
+            )
+        };
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(decayed(f64::INFINITY).unwrap(), expected);
+        // with a half-life of 1 block the counts of the i-th block are halved i times
+        let mut fee_buckets = vec![0.0f64; model.fee_buckets().len()];
+        for block in blocks.iter() {
+            let counts = model.fee_buckets().get(&block.rates);
+            for (bucket, count) in fee_buckets.iter_mut().zip(counts) {
+                *bucket += count as f64 / 2f64.powi(block.height_offset as i32);
+            }
+        }
+        let (mut input, _) = model
+            .build_input(
+                ts * 1000,
+                &model.fee_buckets().get(&rates),
+                (ts - 300) * 1000,
+            )
+            .unwrap();
+        for (bucket, weighted) in input.buckets.iter_mut().zip(fee_buckets.iter()) {
+            *bucket = *weighted as f32;
+        }
+        input.confirms_in = 6.0;
+        let expected = model.tiers[1].models[0].norm_predict_input(&input).unwrap();
+        assert_approx_eq(decayed(1.0).unwrap(), expected);
+        assert_ne!(decayed(1.0).unwrap(), decayed(5.0).unwrap());
+        for half_life in [0.0, -1.0, f64::NAN].iter() {
+            let err = decayed(*half_life).unwrap_err();
+            assert!(matches!(err, Error::InvalidInput("recency_half_life")));
+        }
+    }
+
+    #[test]
+    pub fn test_estimate_weighted() {
+        let ts = SAMPLE_TS;
+        let weighted: Vec<(f64, u64)> = (1..300).map(|i| (i as f64, 110 + 50 * (i % 7))).collect();
+        let err = get_fee_model()
+            .estimate_weighted(6, Some(ts), &weighted, ts - 300)
+            .unwrap_err();
+        assert!(matches!(err, Error::UnweightedModel("low")));
+        let mut model = get_fee_model();
+        model.tiers[0].models[0].info.weighted_buckets = true;
+        let err = model.estimate_weighted(6, Some(ts), &weighted, ts - 300);
+        assert!(matches!(err.unwrap_err(), Error::UnweightedModel("high")));
+
+        model.tiers[1].models[0].info.weighted_buckets = true;
+        // every transaction adds its vsize to its bucket
+        let mut fee_buckets = vec![0u64; model.fee_buckets().len()];
+        for (rate, vsize) in weighted.iter() {
+            let counts = model.fee_buckets().get(&[*rate]);
+            for (bucket, count) in fee_buckets.iter_mut().zip(counts) {
+                *bucket += count * vsize;
+            }
+        }
+        assert_eq!(model.fee_buckets().get_weighted(&weighted, 1), fee_buckets);
+        for target in [1u16, 6].iter() {
+            let got = model.estimate_weighted(*target, Some(ts), &weighted, ts - 300);
+            let expected = model.estimate_with_buckets(*target, Some(ts), &fee_buckets, ts - 300);
+            assert_eq!(got.unwrap(), expected.unwrap());
+        }
+        // with a vsize of 1 every transaction counts as 1
+        let rates: Vec<f64> = weighted.iter().map(|(rate, _)| *rate).collect();
+        let ones: Vec<(f64, u64)> = rates.iter().map(|rate| (*rate, 1)).collect();
+        assert_eq!(
+            model
+                .estimate_weighted(6, Some(ts), &ones, ts - 300)
+                .unwrap(),
+            model.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        );
+        let err = model.estimate_weighted(6, Some(ts), &[(f64::NAN, 100)], ts - 300);
+        assert!(matches!(err, Err(Error::InvalidInput("rates_and_vsizes"))));
+        let err = model.estimate_weighted(6, Some(ts), &[(-5.0, 1000)], ts - 300);
+        assert!(matches!(err, Err(Error::InvalidInput("negative fee rate"))));
+    }
+
+    #[test]
+    pub fn test_from_paths() {
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        FeeModel::from_paths(low, high).unwrap();
+
+        let truncated = Path::new("models/fixtures/truncated.cbor");
+        let err = FeeModel::from_paths(low, truncated).err().unwrap();
+        match &err {
+            Error::ModelFile(path, e) => {
+                assert_eq!(path, truncated);
+                assert!(matches!(**e, Error::Deserialize(_)));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        assert!(err.to_string().contains("truncated.cbor"));
+
+        let missing = Path::new("models/fixtures/missing.cbor");
+        let err = FeeModel::from_paths(missing, high).err().unwrap();
+        match &err {
+            Error::ModelFile(path, e) => {
+                assert_eq!(path, missing);
+                assert!(matches!(**e, Error::Io(_)));
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[cfg(feature = "checksum")]
+    #[test]
+    pub fn test_from_paths_verified() {
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        let checksums = ModelChecksums::from_hex(
+            "cadf8eea120b1327c0a5f37b7acbf0b46f52c95f22ea79d8e9b166430850adef",
+            "cd5092642a3ae2d2d1be8d3d0c1b4862bcc7bea478cfa744ef30375166b4a0b5",
+        )
+        .unwrap();
+        let model = FeeModel::from_paths_verified(low, high, &checksums).unwrap();
+        assert_eq!(model.tiers[0].models[0].sha256(), checksums.low);
+        assert_eq!(model.tiers[1].models[0].sha256(), checksums.high);
+
+        let mut bytes = std::fs::read(high).unwrap();
+        bytes[100] ^= 1;
+        let flipped = std::env::temp_dir().join("bitcoin-fee-model-flipped.cbor");
+        std::fs::write(&flipped, &bytes).unwrap();
+        let err = FeeModel::from_paths_verified(low, &flipped, &checksums)
+            .err()
+            .unwrap();
+        match err {
+            Error::ModelFile(path, e) => {
+                assert_eq!(path, flipped);
+                assert!(
+                    matches!(*e, Error::ChecksumMismatch { expected, .. } if expected == "cd5092642a3ae2d2d1be8d3d0c1b4862bcc7bea478cfa744ef30375166b4a0b5")
+                );
+            }
+            e => panic!("unexpected error {:?}", e),
+        }
+        std::fs::remove_file(flipped).unwrap();
+    }
+
+    #[cfg(feature = "signature")]
+    #[test]
+    pub fn test_from_paths_signed() {
+        use secp256k1::{Message, Secp256k1, SecretKey};
+
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        let model = get_fee_model();
+        let checksums = ModelChecksums::new(
+            model.tiers[0].models[0].sha256(),
+            model.tiers[1].models[0].sha256(),
+        );
+
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let sign = |digest: [u8; 32]| secp.sign_ecdsa(&Message::from_digest(digest), &key);
+        let signature = ModelSignature {
+            pubkey: key.public_key(&secp),
+            low: sign(checksums.low),
+            high: sign(checksums.high),
+        };
+        let signed = checksums.clone().with_signature(signature.clone());
+        FeeModel::from_paths_verified(low, high, &signed).unwrap();
+
+        let swapped = ModelSignature {
+            low: signature.high,
+            high: signature.low,
+            ..signature
+        };
+        let signed = checksums.with_signature(swapped);
+        let err = FeeModel::from_paths_verified(low, high, &signed)
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::InvalidSignature(_)));
     }
 }
@@ -0,0 +1,742 @@
+//! Streaming deserialization of the serialized models, see [`Loader`]. The kernels are visited
+//! row by row and written to a single buffer, without the nested vectors of every row, and the
+//! limits are checked while reading, so that a model exceeding them errors without being read to
+//! the end. The dimensions are checked once read, see [`RawRows`].
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::model_data::{
+    check_version, FieldsDescribe, ModelInfo, OutputTransform, RawKernel, RawModelData, RawRows,
+    RawWeights,
+};
+use crate::{Error, ModelLimits};
+
+/// The state of the deserialization of a model: the limits, the parameters read so far and the
+/// error of a failed check, which the deserializer only sees as a message
+pub(crate) struct Loader<'a> {
+    limits: &'a ModelLimits,
+    parameters: Cell<usize>,
+    failure: RefCell<Option<Error>>,
+}
+
+impl<'a> Loader<'a> {
+    pub(crate) fn new(limits: &'a ModelLimits) -> Self {
+        Loader {
+            limits,
+            parameters: Cell::new(0),
+            failure: RefCell::new(None),
+        }
+    }
+
+    /// Deserialize the layout of a model from `deserializer`
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<RawModelData, D::Error> {
+        deserializer.deserialize_map(ModelVisitor(self))
+    }
+
+    /// The error of a failed deserialization: the check that failed, if any, or `error`
+    pub(crate) fn error(&self, error: Error) -> Error {
+        self.failure.borrow_mut().take().unwrap_or(error)
+    }
+
+    /// Record `error` to be returned by [`Loader::error`], aborting the deserialization
+    fn fail<E: de::Error>(&self, error: Error) -> E {
+        let message = error.to_string();
+        let mut failure = self.failure.borrow_mut();
+        if failure.is_none() {
+            *failure = Some(error);
+        }
+        E::custom(message)
+    }
+
+    fn check<E: de::Error>(&self, result: Result<(), Error>) -> Result<(), E> {
+        result.map_err(|e| self.fail(e))
+    }
+
+    /// Check the number of inputs or of neurons of a layer
+    fn check_width<E: de::Error>(&self, width: usize) -> Result<(), E> {
+        let limits = self.limits;
+        self.check(limits.check("max_layer_width", limits.max_layer_width, width))
+    }
+
+    /// Count a weight or a bias
+    fn add_parameter<E: de::Error>(&self) -> Result<(), E> {
+        let parameters = self.parameters.get() + 1;
+        self.parameters.set(parameters);
+        let limits = self.limits;
+        self.check(limits.check("max_parameters", limits.max_parameters, parameters))
+    }
+
+    /// The capacity to reserve for the elements announced by `hint`, at most a layer
+    fn capacity(&self, hint: Option<usize>) -> usize {
+        hint.unwrap_or(0).min(self.limits.max_layer_width)
+    }
+}
+
+/// Keys of the top level map of a model, see [`RawModelData`]
+enum ModelField {
+    FormatVersion,
+    Norm,
+    Weights,
+    Fields,
+    Alpha,
+    Activations,
+    OutputTransform,
+    Quantiles,
+    Metadata,
+    Other,
+}
+
+impl ModelField {
+    fn from_name(name: &str) -> ModelField {
+        match name {
+            "format_version" => ModelField::FormatVersion,
+            "norm" => ModelField::Norm,
+            "weights" => ModelField::Weights,
+            "fields" => ModelField::Fields,
+            "alpha" => ModelField::Alpha,
+            "activations" => ModelField::Activations,
+            "output_transform" => ModelField::OutputTransform,
+            "quantiles" => ModelField::Quantiles,
+            "metadata" => ModelField::Metadata,
+            _ => ModelField::Other,
+        }
+    }
+}
+
+/// Keys of the map of the weights, see [`RawWeights`]
+enum WeightsField {
+    Bias(usize),
+    Kernel(usize),
+    Other,
+}
+
+impl WeightsField {
+    fn from_name(name: &str) -> WeightsField {
+        match name {
+            "dense/bias:0" => WeightsField::Bias(0),
+            "dense/kernel:0" => WeightsField::Kernel(0),
+            "dense_1/bias:0" => WeightsField::Bias(1),
+            "dense_1/kernel:0" => WeightsField::Kernel(1),
+            "dense_2/bias:0" => WeightsField::Bias(2),
+            "dense_2/kernel:0" => WeightsField::Kernel(2),
+            _ => WeightsField::Other,
+        }
+    }
+}
+
+/// Keys of the map of a quantized or a sparse kernel
+enum KernelField {
+    Values,
+    Scales,
+    ZeroPoints,
+    ColumnIndices,
+    RowPointers,
+    Width,
+    F16,
+    Other,
+}
+
+impl KernelField {
+    fn from_name(name: &str) -> KernelField {
+        match name {
+            "values" => KernelField::Values,
+            "scales" => KernelField::Scales,
+            "zero_points" => KernelField::ZeroPoints,
+            "column_indices" => KernelField::ColumnIndices,
+            "row_pointers" => KernelField::RowPointers,
+            "width" => KernelField::Width,
+            "f16" => KernelField::F16,
+            _ => KernelField::Other,
+        }
+    }
+}
+
+/// A key of a map mapped to `F` by name, without allocating
+struct Key<F>(fn(&str) -> F);
+
+impl<'de, F> DeserializeSeed<'de> for Key<F> {
+    type Value = F;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<F, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de, F> Visitor<'de> for Key<F> {
+    type Value = F;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a field name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<F, E> {
+        Ok((self.0)(name))
+    }
+
+    fn visit_bytes<E: de::Error>(self, name: &[u8]) -> Result<F, E> {
+        Ok((self.0)(core::str::from_utf8(name).unwrap_or("")))
+    }
+}
+
+/// Set `slot` to `value`, erroring if the key `name` is repeated
+fn set<T, E: de::Error>(slot: &mut Option<T>, value: T, name: &'static str) -> Result<(), E> {
+    if slot.is_some() {
+        return Err(E::duplicate_field(name));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+struct ModelVisitor<'l, 'a>(&'l Loader<'a>);
+
+impl<'de, 'l, 'a> Visitor<'de> for ModelVisitor<'l, 'a> {
+    type Value = RawModelData;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("struct RawModelData")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawModelData, A::Error> {
+        let loader = self.0;
+        let mut format_version = None;
+        let mut norm = None;
+        let mut weights = None;
+        let mut fields: Option<Vec<String>> = None;
+        let mut alpha = None;
+        let mut activations = None;
+        let mut output_transform = None;
+        let mut quantiles = None;
+        let mut metadata = None;
+        while let Some(key) = map.next_key_seed(Key(ModelField::from_name))? {
+            match key {
+                ModelField::FormatVersion => {
+                    let version = map.next_value()?;
+                    // the layout of the other fields depends on it
+                    loader.check(check_version(version))?;
+                    set(&mut format_version, version, "format_version")?;
+                }
+                ModelField::Norm => set(&mut norm, map.next_value::<FieldsDescribe>()?, "norm")?,
+                ModelField::Weights => {
+                    let value = map.next_value_seed(WeightsSeed(loader))?;
+                    set(&mut weights, value, "weights")?
+                }
+                ModelField::Fields => {
+                    let value: Vec<String> = map.next_value()?;
+                    loader.check_width(value.len())?;
+                    set(&mut fields, value, "fields")?
+                }
+                ModelField::Alpha => set(&mut alpha, map.next_value::<f32>()?, "alpha")?,
+                ModelField::Activations => {
+                    let value = map.next_value::<Option<Vec<String>>>()?;
+                    set(&mut activations, value, "activations")?
+                }
+                ModelField::OutputTransform => {
+                    let value = map.next_value::<OutputTransform>()?;
+                    set(&mut output_transform, value, "output_transform")?
+                }
+                ModelField::Quantiles => {
+                    let value = map.next_value::<Vec<f32>>()?;
+                    set(&mut quantiles, value, "quantiles")?
+                }
+                ModelField::Metadata => {
+                    let value = map.next_value::<ModelInfo>()?;
+                    set(&mut metadata, value, "metadata")?
+                }
+                ModelField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(RawModelData {
+            format_version: format_version.unwrap_or_default(),
+            norm: norm.ok_or_else(|| de::Error::missing_field("norm"))?,
+            weights: weights.ok_or_else(|| de::Error::missing_field("weights"))?,
+            fields: fields.ok_or_else(|| de::Error::missing_field("fields"))?,
+            alpha: alpha.ok_or_else(|| de::Error::missing_field("alpha"))?,
+            activations: activations.unwrap_or_default(),
+            output_transform: output_transform.unwrap_or_default(),
+            quantiles: quantiles.unwrap_or_default(),
+            metadata: metadata.unwrap_or_default(),
+        })
+    }
+}
+
+/// Names of the kernel and the bias of every layer, see [`crate::validate`]
+const LAYERS: [(&str, &str); 3] = crate::validate::LAYERS;
+
+struct WeightsSeed<'l, 'a>(&'l Loader<'a>);
+
+impl<'de, 'l, 'a> DeserializeSeed<'de> for WeightsSeed<'l, 'a> {
+    type Value = RawWeights;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<RawWeights, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'l, 'a> Visitor<'de> for WeightsSeed<'l, 'a> {
+    type Value = RawWeights;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("struct RawWeights")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawWeights, A::Error> {
+        let loader = self.0;
+        let mut biases: [Option<Vec<f32>>; 3] = [None, None, None];
+        let mut kernels: [Option<RawKernel>; 3] = [None, None, None];
+        while let Some(key) = map.next_key_seed(Key(WeightsField::from_name))? {
+            match key {
+                WeightsField::Bias(i) => {
+                    let bias = map.next_value_seed(BiasSeed(loader))?;
+                    set(&mut biases[i], bias, LAYERS[i].1)?;
+                }
+                WeightsField::Kernel(i) => {
+                    // the dimensions are checked once read, after the limits
+                    let kernel = map.next_value_seed(KernelSeed(loader))?;
+                    set(&mut kernels[i], kernel, LAYERS[i].0)?;
+                }
+                WeightsField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let [b0, b1, b2] = biases;
+        let [k0, k1, k2] = kernels;
+        let missing = |name| move || de::Error::missing_field(name);
+        Ok(RawWeights {
+            l0_bias: b0.ok_or_else(missing(LAYERS[0].1))?,
+            l0_kernel: k0.ok_or_else(missing(LAYERS[0].0))?,
+            l1_bias: b1.ok_or_else(missing(LAYERS[1].1))?,
+            l1_kernel: k1.ok_or_else(missing(LAYERS[1].0))?,
+            l2_bias: b2.ok_or_else(missing(LAYERS[2].1))?,
+            l2_kernel: k2.ok_or_else(missing(LAYERS[2].0))?,
+        })
+    }
+}
+
+/// The biases of a layer, as many as its neurons
+struct BiasSeed<'l, 'a>(&'l Loader<'a>);
+
+impl<'de, 'l, 'a> DeserializeSeed<'de> for BiasSeed<'l, 'a> {
+    type Value = Vec<f32>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Vec<f32>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'l, 'a> Visitor<'de> for BiasSeed<'l, 'a> {
+    type Value = Vec<f32>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of biases")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<f32>, A::Error> {
+        let loader = self.0;
+        let mut bias = Vec::new();
+        RowSeed {
+            loader,
+            out: &mut bias,
+        }
+        .visit_seq(&mut seq)?;
+        Ok(bias)
+    }
+}
+
+/// A row of a kernel, or the biases, appended to `out`, returning its length
+struct RowSeed<'l, 'a, 'o, T> {
+    loader: &'l Loader<'a>,
+    out: &'o mut Vec<T>,
+}
+
+impl<'l, 'a, 'o, T> RowSeed<'l, 'a, 'o, T> {
+    fn visit_seq<'de, A: SeqAccess<'de>>(self, seq: &mut A) -> Result<usize, A::Error>
+    where
+        T: Deserialize<'de>,
+    {
+        let loader = self.loader;
+        if let Some(hint) = seq.size_hint() {
+            loader.check_width(hint)?;
+        }
+        let start = self.out.len();
+        self.out.reserve(loader.capacity(seq.size_hint()));
+        while let Some(value) = seq.next_element()? {
+            loader.check_width(self.out.len() - start + 1)?;
+            loader.add_parameter()?;
+            self.out.push(value);
+        }
+        Ok(self.out.len() - start)
+    }
+}
+
+impl<'de, 'l, 'a, 'o, T: Deserialize<'de>> DeserializeSeed<'de> for RowSeed<'l, 'a, 'o, T> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'l, 'a, 'o, T: Deserialize<'de>> Visitor<'de> for RowSeed<'l, 'a, 'o, T> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a row of weights")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<usize, A::Error> {
+        RowSeed::visit_seq(self, &mut seq)
+    }
+}
+
+/// The rows of a kernel, flattened while read
+struct RowsBuilder<'l, 'a, T> {
+    loader: &'l Loader<'a>,
+    /// the capacity to reserve for the rows once the first one is read
+    rows_hint: usize,
+    rows: RawRows<T>,
+}
+
+impl<'l, 'a, T> RowsBuilder<'l, 'a, T> {
+    fn new(loader: &'l Loader<'a>) -> Self {
+        RowsBuilder {
+            loader,
+            rows_hint: 0,
+            rows: RawRows::default(),
+        }
+    }
+
+    /// Append the row `row`
+    fn push_row<'de, A: SeqAccess<'de>>(&mut self, row: &mut A) -> Result<(), A::Error>
+    where
+        T: Deserialize<'de>,
+    {
+        let loader = self.loader;
+        let rows = &mut self.rows;
+        let seed = RowSeed {
+            loader,
+            out: &mut rows.values,
+        };
+        let len = seed.visit_seq(row)?;
+        if rows.rows == 0 {
+            // the other rows are as wide as the first one
+            rows.width = len;
+            rows.values
+                .reserve(self.rows_hint.saturating_sub(1).saturating_mul(len));
+        } else if len != rows.width && rows.ragged.is_none() {
+            rows.ragged = Some(len);
+        }
+        rows.rows += 1;
+        loader.check_width(rows.rows)
+    }
+}
+
+/// The next row of a kernel, appended to the builder
+struct RowOf<'b, 'l, 'a, T>(&'b mut RowsBuilder<'l, 'a, T>);
+
+impl<'de, 'b, 'l, 'a, T: Deserialize<'de>> DeserializeSeed<'de> for RowOf<'b, 'l, 'a, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'b, 'l, 'a, T: Deserialize<'de>> Visitor<'de> for RowOf<'b, 'l, 'a, T> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a row of weights")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        self.0.push_row(&mut seq)
+    }
+}
+
+/// A kernel of any [`RawKernel`] layout, chosen by the type of the value and the keys of the map
+struct KernelSeed<'l, 'a>(&'l Loader<'a>);
+
+impl<'de, 'l, 'a> DeserializeSeed<'de> for KernelSeed<'l, 'a> {
+    type Value = RawKernel;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<RawKernel, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+/// The error of a kernel of none of the layouts
+fn unknown_layout<E: de::Error>() -> E {
+    E::custom("data did not match any variant of untagged enum RawKernel")
+}
+
+impl<'de, 'l, 'a> Visitor<'de> for KernelSeed<'l, 'a> {
+    type Value = RawKernel;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the rows of a kernel or a quantized or sparse kernel")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<RawKernel, A::Error> {
+        let mut builder = RowsBuilder::new(self.0);
+        builder.rows_hint = self.0.capacity(seq.size_hint());
+        while seq.next_element_seed(RowOf(&mut builder))?.is_some() {}
+        Ok(RawKernel::F32(builder.rows))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<RawKernel, A::Error> {
+        let mut values = None;
+        let mut scales = None;
+        let mut zero_points = None;
+        let mut column_indices = None;
+        let mut row_pointers = None;
+        let mut width = None;
+        let mut half = None;
+        while let Some(key) = map.next_key_seed(Key(KernelField::from_name))? {
+            match key {
+                KernelField::Values => {
+                    let values_seed = ValuesSeed(self.0);
+                    set(&mut values, map.next_value_seed(values_seed)?, "values")?
+                }
+                KernelField::Scales => set(&mut scales, map.next_value::<Vec<f32>>()?, "scales")?,
+                KernelField::ZeroPoints => {
+                    let value = map.next_value::<Vec<i8>>()?;
+                    set(&mut zero_points, value, "zero_points")?
+                }
+                KernelField::ColumnIndices => {
+                    let value = map.next_value::<Vec<u32>>()?;
+                    set(&mut column_indices, value, "column_indices")?
+                }
+                KernelField::RowPointers => {
+                    let value = map.next_value::<Vec<u32>>()?;
+                    set(&mut row_pointers, value, "row_pointers")?
+                }
+                KernelField::Width => set(&mut width, map.next_value::<usize>()?, "width")?,
+                KernelField::F16 => match map.next_value_seed(KernelSeed(self.0))? {
+                    RawKernel::F32(rows) => set(&mut half, rows, "f16")?,
+                    _ => return Err(unknown_layout()),
+                },
+                KernelField::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if let Some(rows) = half {
+            return Ok(RawKernel::F16(rows));
+        }
+        let values = values.ok_or_else(unknown_layout)?;
+        // quantized first, like the untagged enum the layouts used to be
+        if let (Some(scales), Some(zero_points)) = (scales, zero_points) {
+            let values = match values {
+                Values::Rows(rows) => rows,
+                Values::Empty => RawRows::default(),
+                Values::Flat(_) => return Err(unknown_layout()),
+            };
+            return Ok(RawKernel::Int8 {
+                values,
+                scales,
+                zero_points,
+            });
+        }
+        match (values, column_indices, row_pointers, width) {
+            (Values::Flat(values), Some(column_indices), Some(row_pointers), Some(width)) => {
+                Ok(RawKernel::Sparse {
+                    values,
+                    column_indices,
+                    row_pointers,
+                    width,
+                })
+            }
+            (Values::Empty, Some(column_indices), Some(row_pointers), Some(width)) => {
+                Ok(RawKernel::Sparse {
+                    values: Vec::new(),
+                    column_indices,
+                    row_pointers,
+                    width,
+                })
+            }
+            _ => Err(unknown_layout()),
+        }
+    }
+}
+
+/// The `values` of a quantized kernel, rows of integers, or of a sparse one, the weights
+enum Values {
+    Rows(RawRows<i8>),
+    Flat(Vec<f32>),
+    Empty,
+}
+
+struct ValuesSeed<'l, 'a>(&'l Loader<'a>);
+
+impl<'de, 'l, 'a> DeserializeSeed<'de> for ValuesSeed<'l, 'a> {
+    type Value = Values;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Values, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'l, 'a> Visitor<'de> for ValuesSeed<'l, 'a> {
+    type Value = Values;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the values of a quantized or sparse kernel")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Values, A::Error> {
+        let loader = self.0;
+        let mut builder = RowsBuilder::new(loader);
+        builder.rows_hint = loader.capacity(seq.size_hint());
+        let mut flat = Vec::new();
+        while let Some(element) = seq.next_element_seed(ElementSeed(&mut builder))? {
+            match element {
+                Element::Row if flat.is_empty() => (),
+                Element::Number(value) if builder.rows.rows == 0 => {
+                    if flat.is_empty() {
+                        flat.reserve(loader.capacity(seq.size_hint()));
+                    }
+                    loader.add_parameter()?;
+                    flat.push(value);
+                }
+                _ => return Err(unknown_layout()),
+            }
+        }
+        Ok(if builder.rows.rows > 0 {
+            Values::Rows(builder.rows)
+        } else if !flat.is_empty() {
+            Values::Flat(flat)
+        } else {
+            Values::Empty
+        })
+    }
+}
+
+/// An element of [`Values`], the rows are appended to the builder of [`ElementSeed`]
+enum Element {
+    Row,
+    Number(f32),
+}
+
+struct ElementSeed<'b, 'l, 'a>(&'b mut RowsBuilder<'l, 'a, i8>);
+
+impl<'de, 'b, 'l, 'a> DeserializeSeed<'de> for ElementSeed<'b, 'l, 'a> {
+    type Value = Element;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Element, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de, 'b, 'l, 'a> Visitor<'de> for ElementSeed<'b, 'l, 'a> {
+    type Value = Element;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a row of integers or a weight")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Element, A::Error> {
+        self.0.push_row(&mut seq)?;
+        Ok(Element::Row)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<Element, E> {
+        Ok(Element::Number(value as f32))
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<Element, E> {
+        Ok(Element::Number(value as f32))
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Element, E> {
+        Ok(Element::Number(value as f32))
+    }
+}
+
+/// The reader of [`crate::ModelData::from_reader`]: reads at most one byte more than
+/// [`ModelLimits::max_bytes`] and keeps the errors of `inner`, which the deserializer only sees
+/// by their kind
+#[cfg(feature = "std")]
+pub(crate) struct LimitedReader<R> {
+    inner: R,
+    max_bytes: usize,
+    read: usize,
+    error: Option<io::Error>,
+    #[cfg(feature = "checksum")]
+    digest: crate::checksum::Sha256Stream,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> LimitedReader<R> {
+    pub(crate) fn new(inner: R, limits: &ModelLimits) -> Self {
+        LimitedReader {
+            inner,
+            max_bytes: limits.max_bytes,
+            read: 0,
+            error: None,
+            #[cfg(feature = "checksum")]
+            digest: Default::default(),
+        }
+    }
+
+    /// The error of a failed deserialization from this reader
+    pub(crate) fn error(&mut self, loader: &Loader, error: serde_cbor::Error) -> Error {
+        if self.read > self.max_bytes {
+            return Error::LimitExceeded {
+                limit: "max_bytes",
+                max: self.max_bytes,
+                got: self.read,
+            };
+        }
+        match self.error.take() {
+            Some(e) => Error::Io(e),
+            None => loader.error(Error::Deserialize(error)),
+        }
+    }
+
+    /// The digest of the bytes read
+    #[cfg(feature = "checksum")]
+    pub(crate) fn sha256(self) -> [u8; 32] {
+        self.digest.finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for LimitedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = (self.max_bytes + 1).saturating_sub(self.read);
+        if remaining == 0 {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        let len = buf.len().min(remaining);
+        let read = match self.inner.read(&mut buf[..len]) {
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => return Err(e),
+            Err(e) => {
+                let kind = e.kind();
+                self.error = Some(e);
+                return Err(kind.into());
+            }
+        };
+        self.read += read;
+        if self.read > self.max_bytes {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        #[cfg(feature = "checksum")]
+        self.digest.update(&buf[..read]);
+        Ok(read)
+    }
+}
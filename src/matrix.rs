@@ -1,44 +1,125 @@
-use std::marker::PhantomData;
-use std::ops::{Index, IndexMut};
+use alloc::boxed::Box;
+#[cfg(feature = "mmap")]
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Deref, Index, IndexMut};
 
-pub mod size {
-    include!(concat!(env!("OUT_DIR"), "/sizes.rs"));
-}
+use serde::de::Error as _;
+use serde::ser::SerializeSeq;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use size::*;
+use crate::activation::Activation;
 
-pub trait SizeMarker: std::fmt::Debug {
-    fn size() -> usize;
+/// Row-major matrix with `height` rows of `width` elements each
+#[derive(Debug)]
+pub struct Matrix {
+    data: Data,
+    width: usize,
+    height: usize,
 }
 
+/// Elements of a [`Matrix`], owned or borrowed from a memory mapped model file
 #[derive(Debug)]
-pub struct Matrix<W, H>(Box<[f32]>, PhantomData<W>, PhantomData<H>);
+enum Data {
+    Owned(Box<[f32]>),
+    /// `len` native endian f32 starting at `offset`, which is aligned
+    #[cfg(feature = "mmap")]
+    Mapped {
+        map: Arc<memmap2::Mmap>,
+        offset: usize,
+        len: usize,
+    },
+}
 
-impl<W: SizeMarker, H: SizeMarker> Default for Matrix<W, H> {
-    fn default() -> Self {
-        let slice = vec![0.0; W::size() * H::size()].into_boxed_slice();
+impl Deref for Data {
+    type Target = [f32];
 
-        Matrix(slice, PhantomData, PhantomData)
+    fn deref(&self) -> &[f32] {
+        match self {
+            Data::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            Data::Mapped { map, offset, len } => {
+                let bytes = &map[*offset..*offset + *len * 4];
+                // SAFETY: bounds and alignment are checked in `Matrix::from_mapped`, every bit
+                // pattern is a valid f32
+                unsafe { core::slice::from_raw_parts(bytes.as_ptr() as *const f32, *len) }
+            }
+        }
     }
 }
 
-impl<W: SizeMarker, H: SizeMarker> Matrix<W, H> {
-    pub(crate) fn from_buffer(buf: Box<[f32]>) -> Self {
-        if buf.len() != W::size() * H::size() {
+impl Matrix {
+    pub fn zeros(width: usize, height: usize) -> Self {
+        let data = vec![0.0; width * height].into_boxed_slice();
+
+        Matrix {
+            data: Data::Owned(data),
+            width,
+            height,
+        }
+    }
+
+    pub(crate) fn from_buffer(buf: Box<[f32]>, width: usize, height: usize) -> Self {
+        if buf.len() != width * height {
             panic!(
                 "Invalid buffer size: expected {}, found {}",
-                H::size() * W::size(),
+                width * height,
                 buf.len()
             );
         }
 
-        Matrix(buf, PhantomData, PhantomData)
+        Matrix {
+            data: Data::Owned(buf),
+            width,
+            height,
+        }
+    }
+
+    /// A matrix whose native endian elements are `map[offset..]`, without copying them
+    #[cfg(feature = "mmap")]
+    pub(crate) fn from_mapped(
+        map: Arc<memmap2::Mmap>,
+        offset: usize,
+        width: usize,
+        height: usize,
+    ) -> Option<Self> {
+        let len = width.checked_mul(height)?;
+        let end = len.checked_mul(4)?.checked_add(offset)?;
+        let aligned = (map.as_ptr() as usize + offset) % core::mem::align_of::<f32>() == 0;
+        if end > map.len() || !aligned {
+            return None;
+        }
+        Some(Matrix {
+            data: Data::Mapped { map, offset, len },
+            width,
+            height,
+        })
+    }
+
+    /// Elements in row-major order
+    pub fn as_slice(&self) -> &[f32] {
+        &self.data
     }
 
-    pub fn _transpose(&self) -> Matrix<H, W> {
-        let mut new = Matrix::<H, W>::default();
-        for i in 0..H::size() {
-            for k in 0..W::size() {
+    /// Create a matrix with a single row
+    pub fn from_array(arr: Box<[f32]>) -> Self {
+        let width = arr.len();
+        Matrix::from_buffer(arr, width, 1)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn _transpose(&self) -> Matrix {
+        let mut new = Matrix::zeros(self.height, self.width);
+        for i in 0..self.height {
+            for k in 0..self.width {
                 new[k][i] = self[i][k]
             }
         }
@@ -47,9 +128,11 @@ impl<W: SizeMarker, H: SizeMarker> Matrix<W, H> {
     }
 
     pub fn add(&self, other: &Self) -> Self {
-        let mut result = Self::default();
-        for i in 0..H::size() {
-            for k in 0..W::size() {
+        assert_eq!(self.width, other.width, "add: width mismatch");
+        assert_eq!(self.height, other.height, "add: height mismatch");
+        let mut result = Matrix::zeros(self.width, self.height);
+        for i in 0..self.height {
+            for k in 0..self.width {
                 result[i][k] = self[i][k] + other[i][k]
             }
         }
@@ -57,15 +140,28 @@ impl<W: SizeMarker, H: SizeMarker> Matrix<W, H> {
         result
     }
 
-    pub fn dot<W2: SizeMarker>(&self, other: &Matrix<W2, W>) -> Matrix<W2, H> {
-        let mut result = Matrix::<W2, H>::default();
-        for i in 0..H::size() {
-            for j in 0..W2::size() {
-                let mut acc = 0.0;
-                for k in 0..W::size() {
-                    acc += self[i][k] * other[k][j];
-                }
-                result[i][j] = acc;
+    /// Matrix product, computed by [`Matrix::dot_axpy`], or by `ndarray` with the `ndarray`
+    /// feature, which uses BLAS if `ndarray/blas` is enabled and whose results may depend on the
+    /// platform
+    pub fn dot(&self, other: &Matrix) -> Matrix {
+        #[cfg(feature = "ndarray")]
+        return self.dot_ndarray(other);
+        #[cfg(not(feature = "ndarray"))]
+        return self.dot_axpy(other);
+    }
+
+    /// Matrix product, every element of the result is accumulated from zero adding the products
+    /// in the order of `k`, so that results are the same with or without SIMD instructions, which
+    /// compute many elements of the same row at once. Multiplications and additions are never
+    /// fused, so results are also the same on every platform, like x86_64 and aarch64.
+    pub fn dot_axpy(&self, other: &Matrix) -> Matrix {
+        assert_eq!(self.width, other.height, "dot: dimension mismatch");
+        let axpy = axpy_impl();
+        let mut result = Matrix::zeros(other.width, self.height);
+        for i in 0..self.height {
+            let row = &mut result[i];
+            for k in 0..self.width {
+                axpy(row, self[i][k], &other[k]);
             }
         }
 
@@ -73,9 +169,9 @@ impl<W: SizeMarker, H: SizeMarker> Matrix<W, H> {
     }
 
     pub fn relu(&self, alpha: f32) -> Self {
-        let mut result = Self::default();
-        for i in 0..H::size() {
-            for j in 0..W::size() {
+        let mut result = Matrix::zeros(self.width, self.height);
+        for i in 0..self.height {
+            for j in 0..self.width {
                 if self[i][j] < 0.0 {
                     result[i][j] = self[i][j] * alpha;
                 } else {
@@ -88,48 +184,586 @@ impl<W: SizeMarker, H: SizeMarker> Matrix<W, H> {
     }
 }
 
-impl<W: SizeMarker> Matrix<W, Size1> {
-    pub fn from_array(arr: Box<[f32]>) -> Matrix<W, Size1> {
-        if arr.len() != W::size() {
-            panic!(
-                "Invalid array size: expected {}, found {}",
-                W::size(),
-                arr.len()
-            );
+/// Matrix quantized to int8 with a scale and a zero point for every row, the element `(i, j)` is
+/// `(values[i][j] - zero_points[i]) * scales[i]`
+#[derive(Debug, Clone)]
+pub struct QuantizedMatrix {
+    values: Box<[i8]>,
+    scales: Box<[f32]>,
+    zero_points: Box<[i8]>,
+    width: usize,
+    height: usize,
+}
+
+impl QuantizedMatrix {
+    /// Matrix of `values` in row-major order with one of `scales` and `zero_points` per row, `None`
+    /// if the lengths don't match or a scale is not finite and positive
+    pub(crate) fn new(
+        values: Box<[i8]>,
+        scales: Box<[f32]>,
+        zero_points: Box<[i8]>,
+        width: usize,
+    ) -> Option<Self> {
+        let height = scales.len();
+        let valid = scales.iter().all(|s| s.is_finite() && *s > 0.0);
+        if !valid || zero_points.len() != height || values.len() != width.checked_mul(height)? {
+            return None;
+        }
+        Some(QuantizedMatrix {
+            values,
+            scales,
+            zero_points,
+            width,
+            height,
+        })
+    }
+
+    /// Quantize every row of `matrix` in 256 levels between its minimum and its maximum, the
+    /// range always includes zero so that zero is exact
+    pub fn quantize(matrix: &Matrix) -> Self {
+        let mut values = Vec::with_capacity(matrix.width * matrix.height);
+        let mut scales = Vec::with_capacity(matrix.height);
+        let mut zero_points = Vec::with_capacity(matrix.height);
+        for i in 0..matrix.height {
+            let row = &matrix[i];
+            let min = row.iter().fold(0.0f32, |a, b| a.min(*b));
+            let max = row.iter().fold(0.0f32, |a, b| a.max(*b));
+            let scale = if max > min { (max - min) / 255.0 } else { 1.0 };
+            let zero_point = round_i8(-128.0 - min / scale);
+            for x in row {
+                values.push(round_i8(x / scale + zero_point as f32));
+            }
+            scales.push(scale);
+            zero_points.push(zero_point);
+        }
+        QuantizedMatrix {
+            values: values.into_boxed_slice(),
+            scales: scales.into_boxed_slice(),
+            zero_points: zero_points.into_boxed_slice(),
+            width: matrix.width,
+            height: matrix.height,
+        }
+    }
+
+    /// The dequantized element at row `i` and column `j`
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        let q = self.values[i * self.width + j];
+        (q as i32 - self.zero_points[i] as i32) as f32 * self.scales[i]
+    }
+
+    pub fn dequantize(&self) -> Matrix {
+        let mut result = Matrix::zeros(self.width, self.height);
+        for i in 0..self.height {
+            for j in 0..self.width {
+                result[i][j] = self.get(i, j);
+            }
+        }
+        result
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The quantized values of row `i`
+    pub fn row(&self, i: usize) -> &[i8] {
+        &self.values[i * self.width..(i + 1) * self.width]
+    }
+
+    pub fn scales(&self) -> &[f32] {
+        &self.scales
+    }
+
+    pub fn zero_points(&self) -> &[i8] {
+        &self.zero_points
+    }
+}
+
+/// Matrix of IEEE 754 half precision floats, half the size of a [`Matrix`], converted to f32 when
+/// read. Half precision has 11 significant bits, a relative precision of about 5e-4, and its
+/// greatest finite value is 65504.
+#[cfg(feature = "f16")]
+#[derive(Debug, Clone)]
+pub struct HalfMatrix {
+    values: Box<[half::f16]>,
+    width: usize,
+    height: usize,
+}
+
+#[cfg(feature = "f16")]
+impl HalfMatrix {
+    /// Round every element of `matrix` to the nearest half precision float, the ones greater
+    /// than 65504 in absolute value become infinite
+    pub fn from_matrix(matrix: &Matrix) -> Self {
+        let values = matrix.as_slice().iter().map(|x| half::f16::from_f32(*x));
+        HalfMatrix {
+            values: values.collect(),
+            width: matrix.width,
+            height: matrix.height,
+        }
+    }
+
+    /// The element at row `i` and column `j`
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        self.values[i * self.width + j].to_f32()
+    }
+
+    /// Write row `i` converted to f32 in `out`, of the width of the matrix
+    pub(crate) fn row_f32(&self, i: usize, out: &mut [f32]) {
+        let row = &self.values[i * self.width..(i + 1) * self.width];
+        for (value, half) in out.iter_mut().zip(row.iter()) {
+            *value = half.to_f32();
+        }
+    }
+
+    pub fn to_f32(&self) -> Matrix {
+        let values = self.values.iter().map(|x| x.to_f32());
+        Matrix::from_buffer(values.collect(), self.width, self.height)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+/// Round half away from zero and saturate to i8, `f32::round` is not available without `std`
+fn round_i8(x: f32) -> i8 {
+    let rounded = if x < 0.0 { x - 0.5 } else { x + 0.5 };
+    rounded.clamp(-128.0, 127.0) as i8
+}
+
+impl Matrix {
+    /// Like [`Matrix::dot_axpy`], dequantizing the rows of `other` on the fly, the result is the
+    /// same of the dot with [`QuantizedMatrix::dequantize`]. With the `ndarray` feature the whole
+    /// matrix is dequantized and multiplied with [`Matrix::dot`].
+    pub fn dot_int8(&self, other: &QuantizedMatrix) -> Matrix {
+        #[cfg(feature = "ndarray")]
+        return self.dot(&other.dequantize());
+        #[cfg(not(feature = "ndarray"))]
+        return self.dot_int8_axpy(other);
+    }
+
+    #[cfg_attr(feature = "ndarray", allow(dead_code))]
+    fn dot_int8_axpy(&self, other: &QuantizedMatrix) -> Matrix {
+        assert_eq!(self.width, other.height, "dot: dimension mismatch");
+        let axpy = axpy_impl();
+        let mut result = Matrix::zeros(other.width, self.height);
+        let mut dequantized = vec![0.0; other.width];
+        for k in 0..other.height {
+            for (j, value) in dequantized.iter_mut().enumerate() {
+                *value = other.get(k, j);
+            }
+            for i in 0..self.height {
+                axpy(&mut result[i], self[i][k], &dequantized);
+            }
+        }
+
+        result
+    }
+
+    /// Like [`Matrix::dot_axpy`], converting the rows of `other` to f32 on the fly, the result is
+    /// the same of the dot with [`HalfMatrix::to_f32`]. With the `ndarray` feature the whole
+    /// matrix is converted and multiplied with [`Matrix::dot`].
+    #[cfg(feature = "f16")]
+    pub fn dot_f16(&self, other: &HalfMatrix) -> Matrix {
+        #[cfg(feature = "ndarray")]
+        return self.dot(&other.to_f32());
+        #[cfg(not(feature = "ndarray"))]
+        return self.dot_f16_axpy(other);
+    }
+
+    #[cfg(feature = "f16")]
+    #[cfg_attr(feature = "ndarray", allow(dead_code))]
+    fn dot_f16_axpy(&self, other: &HalfMatrix) -> Matrix {
+        assert_eq!(self.width, other.height, "dot: dimension mismatch");
+        let axpy = axpy_impl();
+        let mut result = Matrix::zeros(other.width, self.height);
+        let mut converted = vec![0.0; other.width];
+        for k in 0..other.height {
+            other.row_f32(k, &mut converted);
+            for i in 0..self.height {
+                axpy(&mut result[i], self[i][k], &converted);
+            }
+        }
+
+        result
+    }
+
+    /// Apply `activation` to every element
+    pub fn activation(&self, activation: Activation) -> Self {
+        let mut result = Matrix::zeros(self.width, self.height);
+        for i in 0..self.height {
+            for j in 0..self.width {
+                result[i][j] = activation.apply(self[i][j]);
+            }
+        }
+        result
+    }
+}
+
+/// Matrix in compressed sparse row form, storing only the elements different from zero: the
+/// elements of row `i` are `values[row_pointers[i]..row_pointers[i + 1]]`, in the columns with
+/// the same indices in `column_indices`
+#[derive(Debug, Clone)]
+pub struct SparseMatrix {
+    values: Box<[f32]>,
+    column_indices: Box<[u32]>,
+    row_pointers: Box<[u32]>,
+    width: usize,
+}
+
+impl SparseMatrix {
+    /// `None` if the row pointers are not increasing from zero to the number of values, or the
+    /// column indices of a row are not increasing and lower than `width`
+    pub(crate) fn new(
+        values: Box<[f32]>,
+        column_indices: Box<[u32]>,
+        row_pointers: Box<[u32]>,
+        width: usize,
+    ) -> Option<Self> {
+        let last = *row_pointers.last()? as usize;
+        if row_pointers[0] != 0 || last != values.len() || column_indices.len() != values.len() {
+            return None;
+        }
+        for bounds in row_pointers.windows(2) {
+            let columns = column_indices.get(bounds[0] as usize..bounds[1] as usize)?;
+            let increasing = columns.windows(2).all(|c| c[0] < c[1]);
+            if !increasing || columns.last().is_some_and(|c| *c as usize >= width) {
+                return None;
+            }
+        }
+        Some(SparseMatrix {
+            values,
+            column_indices,
+            row_pointers,
+            width,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.row_pointers.len() - 1
+    }
+
+    /// The column indices and the values of the elements of row `i` different from zero
+    pub fn row(&self, i: usize) -> (&[u32], &[f32]) {
+        let range = self.row_pointers[i] as usize..self.row_pointers[i + 1] as usize;
+        (&self.column_indices[range.clone()], &self.values[range])
+    }
+
+    /// The element at row `i` and column `j`
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        let (columns, values) = self.row(i);
+        columns
+            .binary_search(&(j as u32))
+            .map_or(0.0, |index| values[index])
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    pub fn column_indices(&self) -> &[u32] {
+        &self.column_indices
+    }
+
+    pub fn row_pointers(&self) -> &[u32] {
+        &self.row_pointers
+    }
+
+    pub fn to_dense(&self) -> Matrix {
+        let mut result = Matrix::zeros(self.width, self.height());
+        for i in 0..self.height() {
+            let (columns, values) = self.row(i);
+            for (j, value) in columns.iter().zip(values.iter()) {
+                result[i][*j as usize] = *value;
+            }
         }
+        result
+    }
+}
 
-        Matrix(arr, PhantomData, PhantomData)
+impl Matrix {
+    /// Convert to compressed sparse row form, dropping the elements whose absolute value is not
+    /// greater than `threshold`. With a zero `threshold` only zeros are dropped and
+    /// [`Matrix::dot_sparse`] gives the same results of [`Matrix::dot_axpy`] with finite
+    /// elements.
+    pub fn to_sparse(&self, threshold: f32) -> SparseMatrix {
+        let mut values = vec![];
+        let mut column_indices = vec![];
+        let mut row_pointers = Vec::with_capacity(self.height + 1);
+        row_pointers.push(0);
+        for i in 0..self.height {
+            for (j, value) in self[i].iter().enumerate() {
+                if value.abs() > threshold {
+                    values.push(*value);
+                    column_indices.push(j as u32);
+                }
+            }
+            row_pointers.push(values.len() as u32);
+        }
+        SparseMatrix {
+            values: values.into_boxed_slice(),
+            column_indices: column_indices.into_boxed_slice(),
+            row_pointers: row_pointers.into_boxed_slice(),
+            width: self.width,
+        }
+    }
+
+    /// Product with a sparse matrix, skipping its zeros, the products are added in the same order
+    /// of [`Matrix::dot_axpy`]
+    pub fn dot_sparse(&self, other: &SparseMatrix) -> Matrix {
+        assert_eq!(self.width, other.height(), "dot: dimension mismatch");
+        let mut result = Matrix::zeros(other.width, self.height);
+        for i in 0..self.height {
+            let row = &mut result[i];
+            for k in 0..self.width {
+                let x = self[i][k];
+                let (columns, values) = other.row(k);
+                for (j, value) in columns.iter().zip(values.iter()) {
+                    row[*j as usize] += x * value;
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl Matrix {
+    /// The matrix as an `ndarray` view, without copying
+    pub fn view(&self) -> ndarray::ArrayView2<'_, f32> {
+        ndarray::ArrayView2::from_shape((self.height, self.width), &self.data)
+            .expect("data length is width times height")
+    }
+
+    fn dot_ndarray(&self, other: &Matrix) -> Matrix {
+        assert_eq!(self.width, other.height, "dot: dimension mismatch");
+        let product = self.view().dot(&other.view());
+        let buf: Box<[f32]> = product.iter().copied().collect();
+        Matrix::from_buffer(buf, other.width, self.height)
+    }
+}
+
+/// `out = input * kernel` for the row vector `input`, like [`Matrix::dot_axpy`] of a matrix of
+/// one row with the same results, without allocating
+pub(crate) fn dot_row(input: &[f32], kernel: &Matrix, out: &mut [f32]) {
+    assert_eq!(input.len(), kernel.height, "dot: dimension mismatch");
+    let axpy = axpy_impl();
+    out.iter_mut().for_each(|o| *o = 0.0);
+    for (k, x) in input.iter().enumerate() {
+        axpy(out, *x, &kernel[k]);
     }
 }
 
-impl<W: SizeMarker, H: SizeMarker> Index<usize> for Matrix<W, H> {
+/// Like [`dot_row`] with [`Matrix::dot_int8_axpy`], dequantizing every row of `kernel` in
+/// `dequantized`, of its width
+pub(crate) fn dot_row_int8(
+    input: &[f32],
+    kernel: &QuantizedMatrix,
+    out: &mut [f32],
+    dequantized: &mut [f32],
+) {
+    assert_eq!(input.len(), kernel.height(), "dot: dimension mismatch");
+    let axpy = axpy_impl();
+    out.iter_mut().for_each(|o| *o = 0.0);
+    for (k, x) in input.iter().enumerate() {
+        for (j, value) in dequantized.iter_mut().enumerate() {
+            *value = kernel.get(k, j);
+        }
+        axpy(out, *x, dequantized);
+    }
+}
+
+/// Like [`dot_row`] with [`Matrix::dot_f16_axpy`], converting every row of `kernel` in
+/// `converted`, of its width
+#[cfg(feature = "f16")]
+pub(crate) fn dot_row_f16(
+    input: &[f32],
+    kernel: &HalfMatrix,
+    out: &mut [f32],
+    converted: &mut [f32],
+) {
+    assert_eq!(input.len(), kernel.height(), "dot: dimension mismatch");
+    let axpy = axpy_impl();
+    out.iter_mut().for_each(|o| *o = 0.0);
+    for (k, x) in input.iter().enumerate() {
+        kernel.row_f32(k, converted);
+        axpy(out, *x, converted);
+    }
+}
+
+/// Like [`dot_row`] with [`Matrix::dot_sparse`]
+pub(crate) fn dot_row_sparse(input: &[f32], kernel: &SparseMatrix, out: &mut [f32]) {
+    assert_eq!(input.len(), kernel.height(), "dot: dimension mismatch");
+    out.iter_mut().for_each(|o| *o = 0.0);
+    for (k, x) in input.iter().enumerate() {
+        let (columns, values) = kernel.row(k);
+        for (j, value) in columns.iter().zip(values.iter()) {
+            out[*j as usize] += x * value;
+        }
+    }
+}
+
+/// Number of f32 processed at once by [`axpy_chunked`], the width of an AVX register
+const LANES: usize = 8;
+
+/// `out += x * row`, with the fastest implementation available on this cpu
+fn axpy_impl() -> fn(&mut [f32], f32, &[f32]) {
+    #[cfg(all(feature = "std", target_arch = "x86_64"))]
+    {
+        if std::is_x86_feature_detected!("avx") {
+            // SAFETY: the cpu supports avx
+            return |out, x, row| unsafe { axpy_avx(out, x, row) };
+        }
+    }
+    axpy_chunked
+}
+
+fn axpy_scalar(out: &mut [f32], x: f32, row: &[f32]) {
+    assert_eq!(out.len(), row.len(), "axpy: length mismatch");
+    for (o, w) in out.iter_mut().zip(row.iter()) {
+        *o += x * w;
+    }
+}
+
+/// Like [`axpy_scalar`] in chunks of [`LANES`] independent elements, which the compiler turns in
+/// SIMD instructions of the target, like SSE2 on x86_64 or NEON on aarch64
+fn axpy_chunked(out: &mut [f32], x: f32, row: &[f32]) {
+    assert_eq!(out.len(), row.len(), "axpy: length mismatch");
+    let mut out_chunks = out.chunks_exact_mut(LANES);
+    let mut row_chunks = row.chunks_exact(LANES);
+    for (o, w) in (&mut out_chunks).zip(&mut row_chunks) {
+        for l in 0..LANES {
+            o[l] += x * w[l];
+        }
+    }
+    axpy_scalar(out_chunks.into_remainder(), x, row_chunks.remainder());
+}
+
+/// Like [`axpy_scalar`] with AVX, multiplication and addition are not fused to keep the results
+/// of the other implementations
+///
+/// # Safety
+///
+/// The cpu must support AVX
+#[cfg(all(feature = "std", target_arch = "x86_64"))]
+#[target_feature(enable = "avx")]
+unsafe fn axpy_avx(out: &mut [f32], x: f32, row: &[f32]) {
+    use core::arch::x86_64::_mm256_storeu_ps;
+    use core::arch::x86_64::{_mm256_add_ps, _mm256_loadu_ps, _mm256_mul_ps, _mm256_set1_ps};
+
+    assert_eq!(out.len(), row.len(), "axpy: length mismatch");
+    let simd_len = out.len() / LANES * LANES;
+    let xs = _mm256_set1_ps(x);
+    for j in (0..simd_len).step_by(LANES) {
+        let o = _mm256_loadu_ps(out.as_ptr().add(j));
+        let w = _mm256_loadu_ps(row.as_ptr().add(j));
+        _mm256_storeu_ps(
+            out.as_mut_ptr().add(j),
+            _mm256_add_ps(o, _mm256_mul_ps(xs, w)),
+        );
+    }
+    axpy_scalar(&mut out[simd_len..], x, &row[simd_len..]);
+}
+
+impl Clone for Matrix {
+    fn clone(&self) -> Self {
+        Matrix::from_buffer(self.as_slice().into(), self.width, self.height)
+    }
+}
+
+impl Index<usize> for Matrix {
     type Output = [f32];
 
     fn index(&self, index: usize) -> &Self::Output {
-        let start = index * W::size();
-        let end = start + W::size();
-        &self.0[start..end]
+        let start = index * self.width;
+        let end = start + self.width;
+        &self.data[start..end]
     }
 }
 
-impl<W: SizeMarker, H: SizeMarker> IndexMut<usize> for Matrix<W, H> {
+impl IndexMut<usize> for Matrix {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        let start = index * W::size();
-        let end = start + W::size();
-        &mut self.0[start..end]
+        let start = index * self.width;
+        let end = start + self.width;
+        // mapped data is read only, copy it on first write
+        if let Some(copy) = self.owned_copy() {
+            self.data = Data::Owned(copy);
+        }
+        match &mut self.data {
+            Data::Owned(data) => &mut data[start..end],
+            #[cfg(feature = "mmap")]
+            Data::Mapped { .. } => unreachable!(),
+        }
+    }
+}
+
+impl Matrix {
+    #[cfg(feature = "mmap")]
+    fn owned_copy(&self) -> Option<Box<[f32]>> {
+        match &self.data {
+            Data::Mapped { .. } => Some(self.data.to_vec().into_boxed_slice()),
+            Data::Owned(_) => None,
+        }
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn owned_copy(&self) -> Option<Box<[f32]>> {
+        None
+    }
+}
+
+/// Serialized as the sequence of its rows
+impl Serialize for Matrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.height))?;
+        for i in 0..self.height {
+            seq.serialize_element(&self[i])?;
+        }
+        seq.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Matrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rows = Vec::<Vec<f32>>::deserialize(deserializer)?;
+        let width = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != width) {
+            return Err(D::Error::custom("matrix rows must have the same length"));
+        }
+        let height = rows.len();
+        let buf: Box<[f32]> = rows.into_iter().flatten().collect();
+        Ok(Matrix::from_buffer(buf, width, height))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::matrix::{size::*, Matrix, SizeMarker};
+    use crate::matrix::{
+        axpy_chunked, axpy_impl, axpy_scalar, Matrix, QuantizedMatrix, SparseMatrix,
+    };
     use crate::model_data::tests::get_test_model;
     use crate::tests::assert_approx_eq;
+    use crate::ModelData;
 
-    impl<W: SizeMarker, H: SizeMarker> Matrix<W, H> {
+    impl Matrix {
         pub fn assert_approx_eq(&self, other: &Self) {
-            for i in 0..H::size() {
-                for j in 0..W::size() {
+            assert_eq!(self.width, other.width);
+            assert_eq!(self.height, other.height);
+            for i in 0..self.height {
+                for j in 0..self.width {
                     assert_approx_eq(self[i][j], other[i][j]);
                 }
             }
@@ -139,8 +773,10 @@ mod tests {
     #[test]
     fn test_transpose() {
         let model = get_test_model();
-        let original = model.weights.l2_kernel;
+        let original = model.weights.l2_kernel.as_f32();
         let transposed = original._transpose();
+        assert_eq!(transposed.width(), original.height());
+        assert_eq!(transposed.height(), original.width());
         for i in 0..4 {
             assert_approx_eq(original[i][0], transposed[0][i])
         }
@@ -149,9 +785,9 @@ mod tests {
     #[test]
     fn test_dot() {
         let model = get_test_model();
-        let a = model.weights.l2_kernel;
+        let a = model.weights.l2_kernel.as_f32();
         let b = a._transpose();
-        let result = b.dot(&a);
+        let result = b.dot(a);
 
         let mut acc = 0.0;
         for i in 0..4 {
@@ -159,22 +795,204 @@ mod tests {
         }
         assert_approx_eq(result[0][0], acc);
 
-        let _test = model.weights.l0_kernel.dot(&model.weights.l1_kernel);
+        let _test = model
+            .weights
+            .l0_kernel
+            .as_f32()
+            .dot(model.weights.l1_kernel.as_f32());
     }
 
     #[test]
     fn test_add() {
-        let m1 = Matrix::<Size1, Size1>::from_array(vec![1.0f32].into_boxed_slice());
-        let m2 = Matrix::<Size1, Size1>::from_array(vec![1.0f32].into_boxed_slice());
+        let m1 = Matrix::from_array(vec![1.0f32].into_boxed_slice());
+        let m2 = Matrix::from_array(vec![1.0f32].into_boxed_slice());
         let result = m1.add(&m2);
         assert_approx_eq(2.0f32, result[0][0]);
     }
 
+    #[test]
+    fn test_serde() {
+        let model = get_test_model();
+        let kernel = model.weights.l0_kernel.as_f32();
+        let json = serde_json::to_string(kernel).unwrap();
+        let back: Matrix = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            (back.width(), back.height()),
+            (kernel.width(), kernel.height())
+        );
+        assert_eq!(back.as_slice(), kernel.as_slice());
+
+        assert!(serde_json::from_str::<Matrix>("[[1.0, 2.0], [3.0]]").is_err());
+    }
+
+    #[test]
+    fn test_simd() {
+        let values: Vec<f32> = (0..200u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) % 1000) as f32 / 37.0 - 13.0)
+            .collect();
+        for len in 0..40 {
+            let row = &values[..len];
+            let mut expected = values[50..50 + len].to_vec();
+            let mut chunked = expected.clone();
+            let mut best = expected.clone();
+            axpy_scalar(&mut expected, -1.7, row);
+            axpy_chunked(&mut chunked, -1.7, row);
+            axpy_impl()(&mut best, -1.7, row);
+            let bits = |v: &[f32]| v.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits(&chunked), bits(&expected));
+            assert_eq!(bits(&best), bits(&expected));
+        }
+
+        let model = ModelData::from_slice(include_bytes!("../models/20211027-180925/model.cbor"));
+        let model = model.unwrap();
+        let kernel = model.weights.l1_kernel.as_f32();
+        let input = Matrix::from_buffer(values[..64 * 2].into(), 64, 2);
+        let result = input.dot_axpy(kernel);
+        for i in 0..input.height() {
+            for j in 0..kernel.width() {
+                let mut acc = 0.0f32;
+                for k in 0..input.width() {
+                    acc += input[i][k] * kernel[k][j];
+                }
+                assert_eq!(result[i][j].to_bits(), acc.to_bits());
+            }
+        }
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_ndarray() {
+        let embedded = [
+            &include_bytes!("../models/20211027-180849/model.cbor")[..],
+            &include_bytes!("../models/20211027-180925/model.cbor")[..],
+        ];
+        for bytes in embedded.iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let w = &model.weights;
+            let inputs = w.l0_kernel.height();
+            let values: Box<[f32]> = (0..inputs * 3).map(|i| (i % 7) as f32 - 3.0).collect();
+            let mut input = Matrix::from_buffer(values, inputs, 3);
+            let layers = [
+                (&w.l0_kernel, &w.l0_bias),
+                (&w.l1_kernel, &w.l1_bias),
+                (&w.l2_kernel, &w.l2_bias),
+            ];
+            for (kernel, bias) in layers.iter() {
+                let kernel = kernel.as_f32();
+                assert_eq!(kernel.view().shape(), &[kernel.height(), kernel.width()]);
+                assert_eq!(kernel.view()[[1, 0]], kernel[1][0]);
+                let expected = input.dot_axpy(kernel);
+                let result = input.dot(kernel);
+                // bound of the error of summing the products in any order
+                for i in 0..result.height() {
+                    for j in 0..result.width() {
+                        let terms =
+                            (0..kernel.height()).map(|k| (input[i][k] * kernel[k][j]).abs());
+                        let bound = terms.sum::<f32>() * kernel.height() as f32 * f32::EPSILON;
+                        let (r, e) = (result[i][j], expected[i][j]);
+                        assert!((r - e).abs() <= bound, "{} {}", r, e);
+                    }
+                }
+                let mut next = Matrix::zeros(result.width(), result.height());
+                for i in 0..result.height() {
+                    for j in 0..result.width() {
+                        next[i][j] = expected[i][j] + bias[0][j];
+                    }
+                }
+                input = next.activation(model.activations[0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_quantize() {
+        let model = get_test_model();
+        let kernel = model.weights.l0_kernel.as_f32();
+        let quantized = QuantizedMatrix::quantize(kernel);
+        assert_eq!(quantized.width(), kernel.width());
+        assert_eq!(quantized.height(), kernel.height());
+        for i in 0..kernel.height() {
+            for j in 0..kernel.width() {
+                let error = (quantized.get(i, j) - kernel[i][j]).abs();
+                assert!(error <= quantized.scales()[i] * 0.5 + 1e-6);
+            }
+        }
+        let zeros = QuantizedMatrix::quantize(&Matrix::zeros(3, 2));
+        assert_eq!(zeros.dequantize().as_slice(), &[0.0; 6]);
+
+        let input = Matrix::from_array(vec![0.5; kernel.height()].into_boxed_slice());
+        let expected = input.dot(&quantized.dequantize());
+        assert_eq!(input.dot_int8(&quantized).as_slice(), expected.as_slice());
+
+        let new = |values: Vec<i8>, scales: Vec<f32>| {
+            let zero_points = vec![0i8; scales.len()].into_boxed_slice();
+            QuantizedMatrix::new(values.into(), scales.into(), zero_points, 2)
+        };
+        assert!(new(vec![1, 2, 3, 4], vec![0.1, 0.2]).is_some());
+        assert!(new(vec![1, 2, 3], vec![0.1, 0.2]).is_none());
+        assert!(new(vec![1, 2, 3, 4], vec![0.1, 0.0]).is_none());
+        assert!(new(vec![1, 2, 3, 4], vec![0.1, f32::NAN]).is_none());
+    }
+
+    #[test]
+    fn test_sparse() {
+        let model = get_test_model();
+        let mut kernel = model.weights.l1_kernel.as_f32().clone();
+        for i in 0..kernel.height() {
+            for j in 0..kernel.width() {
+                if kernel[i][j].abs() < 0.05 || (i + j) % 3 == 0 {
+                    kernel[i][j] = 0.0;
+                }
+            }
+        }
+        let sparse = kernel.to_sparse(0.0);
+        assert_eq!(sparse.width(), kernel.width());
+        assert_eq!(sparse.height(), kernel.height());
+        assert!(sparse.values().len() < kernel.as_slice().len());
+        assert_eq!(sparse.to_dense().as_slice(), kernel.as_slice());
+        let input: Vec<f32> = (0..kernel.height()).map(|i| i as f32 / 7.0 - 2.0).collect();
+        let input = Matrix::from_array(input.into_boxed_slice());
+        let dense = input.dot_axpy(&kernel);
+        let result = input.dot_sparse(&sparse);
+        for (a, b) in dense.as_slice().iter().zip(result.as_slice()) {
+            assert_eq!(a.to_bits(), b.to_bits());
+        }
+
+        let pruned = kernel.to_sparse(0.1);
+        assert!(pruned.values().iter().all(|v| v.abs() > 0.1));
+        for i in 0..kernel.height() {
+            for j in 0..kernel.width() {
+                let expected = if kernel[i][j].abs() > 0.1 {
+                    kernel[i][j]
+                } else {
+                    0.0
+                };
+                assert_eq!(pruned.get(i, j), expected);
+            }
+        }
+
+        let new = |columns: Vec<u32>, rows: Vec<u32>| {
+            SparseMatrix::new(
+                vec![1.0; columns.len()].into(),
+                columns.into(),
+                rows.into(),
+                3,
+            )
+        };
+        assert!(new(vec![0, 2, 1], vec![0, 2, 3]).is_some());
+        assert!(new(vec![0, 2, 1], vec![0, 2, 2]).is_none());
+        assert!(new(vec![0, 2, 1], vec![1, 2, 3]).is_none());
+        assert!(new(vec![2, 0, 1], vec![0, 2, 3]).is_none());
+        assert!(new(vec![0, 3, 1], vec![0, 2, 3]).is_none());
+        assert!(new(vec![0, 2, 1], vec![0, 3, 2]).is_none());
+        assert!(new(vec![], vec![]).is_none());
+    }
+
     #[test]
     fn test_relu() {
         for alpha in [0.0f32, 0.1, 0.01].iter() {
-            let m1 = Matrix::<Size2, Size1>::from_array(vec![1.0, -1.0].into_boxed_slice());
-            let expected = Matrix::<Size2, Size1>::from_array(vec![1.0, -alpha].into_boxed_slice());
+            let m1 = Matrix::from_array(vec![1.0, -1.0].into_boxed_slice());
+            let expected = Matrix::from_array(vec![1.0, -alpha].into_boxed_slice());
             let relu = m1.relu(*alpha);
             relu.assert_approx_eq(&expected);
         }
@@ -0,0 +1,425 @@
+//! Statistics of the estimates, collected by the sink of [`FeeModel::with_metrics`]
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::{Error, ModelKind};
+
+#[cfg(doc)]
+use crate::FeeModel;
+
+/// Linear sub-buckets of every power of two of the latency histogram of [`AtomicMetrics`]
+const SUB_BUCKETS: usize = 4;
+
+/// Buckets of the latency histogram, covering every duration in nanoseconds fitting a `u64`
+const LATENCY_BUCKETS: usize = SUB_BUCKETS + (64 - 2) * SUB_BUCKETS;
+
+/// An estimate reported to a [`MetricsSink`]
+#[derive(Debug, Clone, Copy)]
+pub struct EstimateEvent<'a> {
+    /// the target requested, before being bounded to the ones of the models
+    pub block_target: u16,
+    /// the time spent estimating, including the preparation of the inputs
+    pub duration: Duration,
+    pub outcome: Result<EstimateOutcome, &'a Error>,
+}
+
+/// How a successful estimate has been computed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EstimateOutcome {
+    pub model: ModelKind,
+    /// whether the prediction has been bounded, see [`crate::FeeEstimate::clamped`]
+    pub clamped: bool,
+}
+
+/// Receives an event for every estimate of [`FeeModel::estimate`],
+/// [`FeeModel::estimate_request`], [`FeeModel::estimate_with_buckets`],
+/// [`FeeModel::estimate_detailed`] and [`FeeModel::estimate_partial_detailed`], and of the
+/// methods estimating through them, once the estimate is computed: it is called by the thread
+/// estimating, so it should not block.
+pub trait MetricsSink: Send + Sync {
+    fn record(&self, event: &EstimateEvent);
+}
+
+/// A [`MetricsSink`] counting the estimates with atomic counters, the errors by
+/// [`Error::kind`] and the durations in a histogram with a relative error up to 25%
+#[derive(Debug)]
+pub struct AtomicMetrics {
+    estimates: AtomicU64,
+    errors: AtomicU64,
+    clamped: AtomicU64,
+    /// the successful estimates by [`ModelKind`], in the order of [`model_index`]
+    by_model: [AtomicU64; 4],
+    /// locked only for the errors
+    errors_by_kind: Mutex<BTreeMap<&'static str, u64>>,
+    /// the count of the durations in nanoseconds of every bucket of [`latency_bucket`]
+    latency: [AtomicU64; LATENCY_BUCKETS],
+}
+
+impl Default for AtomicMetrics {
+    fn default() -> Self {
+        AtomicMetrics {
+            estimates: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            clamped: AtomicU64::new(0),
+            by_model: Default::default(),
+            errors_by_kind: Mutex::new(BTreeMap::new()),
+            latency: [(); LATENCY_BUCKETS].map(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl AtomicMetrics {
+    pub fn new() -> Self {
+        AtomicMetrics::default()
+    }
+
+    /// The estimates reported, successful or not
+    pub fn estimates(&self) -> u64 {
+        self.estimates.load(Ordering::Relaxed)
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    /// The successful estimates bounded, see [`EstimateOutcome::clamped`]
+    pub fn clamped(&self) -> u64 {
+        self.clamped.load(Ordering::Relaxed)
+    }
+
+    /// The successful estimates computed by `model`
+    pub fn estimates_by(&self, model: ModelKind) -> u64 {
+        self.by_model[model_index(model)].load(Ordering::Relaxed)
+    }
+
+    /// The errors of every [`Error::kind`] reported at least once
+    pub fn errors_by_kind(&self) -> BTreeMap<&'static str, u64> {
+        self.lock_errors().clone()
+    }
+
+    /// The errors of the given [`Error::kind`], like `"InsufficientData"`
+    pub fn errors_of(&self, kind: &str) -> u64 {
+        self.lock_errors().get(kind).copied().unwrap_or(0)
+    }
+
+    /// The duration below which are the `q` fraction of the estimates, like 0.99 for the 99th
+    /// percentile, rounded up to the histogram bucket, `None` without estimates. `q` is bounded
+    /// between 0 and 1.
+    pub fn latency_quantile(&self, q: f64) -> Option<Duration> {
+        let counts: Vec<u64> = self
+            .latency
+            .iter()
+            .map(|c| c.load(Ordering::Relaxed))
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(Duration::from_nanos(bucket_upper_bound(bucket)));
+            }
+        }
+        None
+    }
+
+    /// Reset every counter, like after exporting them
+    pub fn reset(&self) {
+        let counters = [&self.estimates, &self.errors, &self.clamped];
+        let counters = counters.iter().copied().chain(&self.by_model);
+        for counter in counters.chain(&self.latency) {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.lock_errors().clear();
+    }
+
+    fn lock_errors(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, u64>> {
+        self.errors_by_kind
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+impl MetricsSink for AtomicMetrics {
+    fn record(&self, event: &EstimateEvent) {
+        self.estimates.fetch_add(1, Ordering::Relaxed);
+        let nanos = u64::try_from(event.duration.as_nanos()).unwrap_or(u64::MAX);
+        self.latency[latency_bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+        match event.outcome {
+            Ok(outcome) => {
+                self.by_model[model_index(outcome.model)].fetch_add(1, Ordering::Relaxed);
+                if outcome.clamped {
+                    self.clamped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Err(e) => {
+                self.errors.fetch_add(1, Ordering::Relaxed);
+                *self.lock_errors().entry(e.kind()).or_insert(0) += 1;
+            }
+        }
+    }
+}
+
+fn model_index(model: ModelKind) -> usize {
+    match model {
+        ModelKind::Low => 0,
+        ModelKind::High => 1,
+        ModelKind::Long => 2,
+        ModelKind::Blended => 3,
+    }
+}
+
+/// The label of `model` in the metrics
+#[cfg(feature = "prometheus")]
+fn model_label(model: ModelKind) -> &'static str {
+    match model {
+        ModelKind::Low => "low",
+        ModelKind::High => "high",
+        ModelKind::Long => "long",
+        ModelKind::Blended => "blended",
+    }
+}
+
+/// The bucket of `nanos`: exact up to 3, then the power of two at most `nanos` split in
+/// [`SUB_BUCKETS`] linear buckets
+fn latency_bucket(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS as u64 {
+        return nanos as usize;
+    }
+    let exponent = 63 - nanos.leading_zeros() as usize;
+    let sub = (nanos >> (exponent - 2)) as usize & (SUB_BUCKETS - 1);
+    SUB_BUCKETS + (exponent - 2) * SUB_BUCKETS + sub
+}
+
+/// The greatest duration in nanoseconds of `bucket`, see [`latency_bucket`]
+fn bucket_upper_bound(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+    let shift = (bucket - SUB_BUCKETS) / SUB_BUCKETS;
+    let sub = ((bucket - SUB_BUCKETS) % SUB_BUCKETS) as u64;
+    let lower = (SUB_BUCKETS as u64 + sub) << shift;
+    lower + ((1u64 << shift) - 1)
+}
+
+/// A [`MetricsSink`] exporting the estimates to a [`prometheus::Registry`]: the histogram
+/// `fee_model_estimate_duration_seconds`, the counter `fee_model_estimates_total` of the
+/// successful estimates labelled by `model`, `fee_model_clamped_total` and
+/// `fee_model_errors_total` labelled by `kind`, see [`Error::kind`]
+#[cfg(feature = "prometheus")]
+#[derive(Debug, Clone)]
+pub struct PrometheusMetrics {
+    duration: prometheus::Histogram,
+    estimates: prometheus::IntCounterVec,
+    clamped: prometheus::IntCounter,
+    errors: prometheus::IntCounterVec,
+}
+
+#[cfg(feature = "prometheus")]
+impl PrometheusMetrics {
+    /// Register the metrics in `registry`, erroring with [`Error::Prometheus`] if they are
+    /// registered already
+    pub fn new(registry: &prometheus::Registry) -> Result<Self, Error> {
+        use prometheus::{HistogramOpts, Opts};
+
+        // from 1 µs to about 0.5 s
+        let buckets = prometheus::exponential_buckets(1e-6, 2.0, 20)?;
+        let duration = prometheus::Histogram::with_opts(
+            HistogramOpts::new(
+                "fee_model_estimate_duration_seconds",
+                "Time spent estimating the fee rate",
+            )
+            .buckets(buckets),
+        )?;
+        let estimates = prometheus::IntCounterVec::new(
+            Opts::new("fee_model_estimates_total", "Successful estimates by model"),
+            &["model"],
+        )?;
+        let clamped = prometheus::IntCounter::new(
+            "fee_model_clamped_total",
+            "Estimates bounded to the configured fee rates",
+        )?;
+        let errors = prometheus::IntCounterVec::new(
+            Opts::new("fee_model_errors_total", "Failed estimates by error"),
+            &["kind"],
+        )?;
+        registry.register(Box::new(duration.clone()))?;
+        registry.register(Box::new(estimates.clone()))?;
+        registry.register(Box::new(clamped.clone()))?;
+        registry.register(Box::new(errors.clone()))?;
+        Ok(PrometheusMetrics {
+            duration,
+            estimates,
+            clamped,
+            errors,
+        })
+    }
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsSink for PrometheusMetrics {
+    fn record(&self, event: &EstimateEvent) {
+        self.duration.observe(event.duration.as_secs_f64());
+        match event.outcome {
+            Ok(outcome) => {
+                let model = model_label(outcome.model);
+                self.estimates.with_label_values(&[model]).inc();
+                if outcome.clamped {
+                    self.clamped.inc();
+                }
+            }
+            Err(e) => self.errors.with_label_values(&[e.kind()]).inc(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::metrics::{bucket_upper_bound, latency_bucket, LATENCY_BUCKETS};
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::{AtomicMetrics, Error, EstimateEvent, EstimateOptions, EstimateOutcome, ModelKind};
+
+    #[test]
+    fn test_atomic_metrics() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let metrics = Arc::new(AtomicMetrics::new());
+        let model = get_fee_model().with_metrics(metrics.clone());
+        for target in [1u16, 2, 6, 144].iter() {
+            model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+        }
+        let detailed = model
+            .estimate_detailed(1008, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert!(matches!(
+            model.estimate(0, Some(ts), &rates, ts - 300),
+            Err(Error::InvalidTarget(0))
+        ));
+        assert!(matches!(
+            model.estimate(6, Some(ts), &[], ts - 300),
+            Err(Error::InsufficientData)
+        ));
+        assert!(model.estimate_detailed(6, Some(ts), &[], ts - 300).is_err());
+        let buckets = model.fee_buckets().get(&rates);
+        model
+            .estimate_with_buckets(3, Some(ts), &buckets, ts - 300)
+            .unwrap();
+
+        assert_eq!(metrics.estimates(), 9);
+        assert_eq!(metrics.errors(), 3);
+        assert_eq!(metrics.estimates_by(ModelKind::Low), 2);
+        assert_eq!(metrics.estimates_by(ModelKind::High), 4);
+        assert_eq!(metrics.estimates_by(ModelKind::Blended), 0);
+        assert_eq!(metrics.clamped(), detailed.clamped as u64);
+        assert_eq!(metrics.errors_of("InsufficientData"), 2);
+        assert_eq!(metrics.errors_of("InvalidTarget"), 1);
+        assert_eq!(metrics.errors_by_kind().len(), 2);
+        let p50 = metrics.latency_quantile(0.5).unwrap();
+        let p99 = metrics.latency_quantile(0.99).unwrap();
+        assert!(p50 <= p99 && p99 > Duration::ZERO);
+
+        // the clones share the sink, the model without it records nothing
+        model
+            .clone()
+            .estimate(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        get_fee_model()
+            .estimate(6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(metrics.estimates(), 10);
+
+        let bounded = model.with_options(EstimateOptions {
+            min_fee_rate: 1000.0,
+            max_fee_rate: Some(2000.0),
+            ..Default::default()
+        });
+        bounded.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(metrics.clamped(), detailed.clamped as u64 + 1);
+
+        metrics.reset();
+        assert_eq!(metrics.estimates(), 0);
+        assert_eq!(metrics.errors_of("InsufficientData"), 0);
+        assert_eq!(metrics.latency_quantile(0.5), None);
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        for nanos in [0u64, 1, 3, 4, 7, 8, 100, 999_999, u64::MAX].iter() {
+            let bucket = latency_bucket(*nanos);
+            assert!(bucket < LATENCY_BUCKETS);
+            let upper = bucket_upper_bound(bucket);
+            assert!(*nanos <= upper, "{}", nanos);
+            // within 25% of the bucket
+            assert!(upper - nanos <= nanos / 4, "{}", nanos);
+            if bucket > 0 {
+                assert!(bucket_upper_bound(bucket - 1) < *nanos, "{}", nanos);
+            }
+        }
+
+        let metrics = AtomicMetrics::new();
+        let outcome = Ok(EstimateOutcome {
+            model: ModelKind::High,
+            clamped: false,
+        });
+        for micros in 1..=100u64 {
+            let event = EstimateEvent {
+                block_target: 6,
+                duration: Duration::from_micros(micros),
+                outcome,
+            };
+            crate::MetricsSink::record(&metrics, &event);
+        }
+        let p50 = metrics.latency_quantile(0.5).unwrap();
+        assert!(p50 >= Duration::from_micros(50), "{:?}", p50);
+        assert!(p50 <= Duration::from_micros(63), "{:?}", p50);
+        let p99 = metrics.latency_quantile(0.99).unwrap();
+        assert!(p99 >= Duration::from_micros(99), "{:?}", p99);
+        assert!(p99 <= Duration::from_micros(124), "{:?}", p99);
+        assert_eq!(
+            metrics.latency_quantile(0.0),
+            metrics.latency_quantile(0.01)
+        );
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_prometheus_metrics() {
+        use crate::PrometheusMetrics;
+
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let registry = prometheus::Registry::new();
+        let sink = Arc::new(PrometheusMetrics::new(&registry).unwrap());
+        assert!(matches!(
+            PrometheusMetrics::new(&registry),
+            Err(Error::Prometheus(_))
+        ));
+        let model = get_fee_model().with_metrics(sink);
+        model.estimate(1, Some(ts), &rates, ts - 300).unwrap();
+        model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        model.estimate(6, Some(ts), &[], ts - 300).unwrap_err();
+
+        let families = registry.gather();
+        let family = |name: &str| families.iter().find(|f| f.name() == name).unwrap();
+        let duration = family("fee_model_estimate_duration_seconds");
+        assert_eq!(
+            duration.get_metric()[0].get_histogram().get_sample_count(),
+            3
+        );
+        let estimates = family("fee_model_estimates_total").get_metric();
+        assert_eq!(estimates.len(), 2);
+        let errors = family("fee_model_errors_total").get_metric();
+        assert_eq!(errors[0].get_label()[0].value(), "InsufficientData");
+        assert_eq!(errors[0].get_counter().get_value(), 1.0);
+    }
+}
@@ -0,0 +1,233 @@
+//! Swift and Kotlin bindings with UniFFI, generated from the library with
+//! `cargo run --features uniffi-bindgen --bin uniffi-bindgen -- generate --library <lib>`
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{Error, ModelKind as CoreModelKind, Staleness};
+
+/// The errors of the bindings, grouping the ones of [`Error`] like the codes of the C bindings
+#[derive(Debug, Clone, PartialEq, uniffi::Error)]
+pub enum FeeModelError {
+    /// The model data is invalid
+    InvalidModel { message: String },
+    /// The fee buckets don't match the ones expected by the models
+    WrongBucketCount { message: String },
+    /// There are no fee rates to estimate from
+    InsufficientData { message: String },
+    /// A timestamp is out of the supported range
+    InvalidTimestamp { message: String },
+    /// A fee rate, the target or another argument is invalid
+    InvalidInput { message: String },
+    /// Any other error
+    Other { message: String },
+}
+
+impl From<Error> for FeeModelError {
+    fn from(error: Error) -> Self {
+        let message = error.to_string();
+        match error {
+            Error::MissingMeanData(_)
+            | Error::MissingStdData(_)
+            | Error::Deserialize(_)
+            | Error::Decompress
+            | Error::UnsupportedModelVersion(_)
+            | Error::LimitExceeded { .. }
+            | Error::NonFiniteWeight(_)
+            | Error::InvalidScale(_)
+            | Error::InvalidSparseIndices(_)
+            | Error::InvalidNormalization(_)
+            | Error::UnsupportedActivation(_)
+            | Error::InvalidQuantiles
+            | Error::InvalidModel(_)
+            | Error::FeatureMismatch { .. }
+            | Error::BucketModeMismatch { .. }
+            | Error::BucketConfigMismatch { .. }
+            | Error::ModelMismatch(_)
+            | Error::InvalidTiers(_)
+            | Error::DimensionMismatch { .. } => FeeModelError::InvalidModel { message },
+            Error::WrongBucketCount { .. } => FeeModelError::WrongBucketCount { message },
+            Error::InsufficientData => FeeModelError::InsufficientData { message },
+            Error::InvalidTimestamp(_) => FeeModelError::InvalidTimestamp { message },
+            Error::InvalidInput(_) | Error::InvalidOverride(_) | Error::InvalidTarget(_) => {
+                FeeModelError::InvalidInput { message }
+            }
+            _ => FeeModelError::Other { message },
+        }
+    }
+}
+
+impl fmt::Display for FeeModelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FeeModelError::InvalidModel { message }
+            | FeeModelError::WrongBucketCount { message }
+            | FeeModelError::InsufficientData { message }
+            | FeeModelError::InvalidTimestamp { message }
+            | FeeModelError::InvalidInput { message }
+            | FeeModelError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for FeeModelError {}
+
+/// Which of the models produced an estimate, see [`crate::ModelKind`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ModelKind {
+    Low,
+    High,
+    Long,
+    Blended,
+}
+
+impl From<CoreModelKind> for ModelKind {
+    fn from(kind: CoreModelKind) -> Self {
+        match kind {
+            CoreModelKind::Low => ModelKind::Low,
+            CoreModelKind::High => ModelKind::High,
+            CoreModelKind::Long => ModelKind::Long,
+            CoreModelKind::Blended => ModelKind::Blended,
+        }
+    }
+}
+
+/// An estimate with the context used to compute it, the fields of [`crate::FeeEstimate`]
+/// without the inputs of the models
+#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+pub struct FeeEstimate {
+    /// the estimated fee rate in sat/vB
+    pub rate: f32,
+    pub model: ModelKind,
+    pub block_target: u16,
+    pub effective_target: u16,
+    pub raw_output: f32,
+    pub clamped: bool,
+    pub delta_last_clamped: bool,
+    pub filtered_fee_rates: u64,
+    /// the seconds since the last block when older than
+    /// [`crate::EstimateOptions::stale_after`], `None` if fresh
+    pub stale_seconds: Option<u64>,
+}
+
+impl From<crate::FeeEstimate> for FeeEstimate {
+    fn from(estimate: crate::FeeEstimate) -> Self {
+        let stale_seconds = match estimate.staleness {
+            Staleness::Fresh => None,
+            Staleness::Stale { age } => Some(age.as_secs()),
+        };
+        FeeEstimate {
+            rate: estimate.rate,
+            model: estimate.model.into(),
+            block_target: estimate.block_target,
+            effective_target: estimate.effective_target,
+            raw_output: estimate.raw_output,
+            clamped: estimate.clamped,
+            delta_last_clamped: estimate.delta_last_clamped,
+            filtered_fee_rates: estimate.filtered_fee_rates as u64,
+            stale_seconds,
+        }
+    }
+}
+
+/// A [`crate::FeeModel`] usable from Swift and Kotlin
+#[derive(Debug, uniffi::Object)]
+pub struct FeeModel {
+    inner: crate::FeeModel,
+}
+
+#[uniffi::export]
+impl FeeModel {
+    /// Create the fee model using the models embedded in the library
+    #[uniffi::constructor]
+    pub fn new() -> Result<Arc<Self>, FeeModelError> {
+        let inner = crate::FeeModel::new()?;
+        Ok(Arc::new(FeeModel { inner }))
+    }
+
+    /// Create the fee model from the CBOR encoded `low` and `high` models
+    #[uniffi::constructor]
+    pub fn from_bytes(low: Vec<u8>, high: Vec<u8>) -> Result<Arc<Self>, FeeModelError> {
+        let inner = crate::FeeModel::from_bytes(&low, &high)?;
+        Ok(Arc::new(FeeModel { inner }))
+    }
+
+    /// Like [`crate::FeeModel::estimate`], `timestamp` and `last_block_ts` are unix times in
+    /// seconds, the current time if `timestamp` is missing
+    pub fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: Vec<f64>,
+        last_block_ts: i64,
+    ) -> Result<f32, FeeModelError> {
+        let rate = self
+            .inner
+            .estimate(block_target, timestamp, &fee_rates, last_block_ts)?;
+        Ok(rate)
+    }
+
+    /// Like [`crate::FeeModel::estimate_detailed`]
+    pub fn estimate_detailed(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: Vec<f64>,
+        last_block_ts: i64,
+    ) -> Result<FeeEstimate, FeeModelError> {
+        let estimate =
+            self.inner
+                .estimate_detailed(block_target, timestamp, &fee_rates, last_block_ts)?;
+        Ok(estimate.into())
+    }
+
+    /// The highest target estimated, see [`crate::FeeModel::max_target`]
+    pub fn max_target(&self) -> u16 {
+        self.inner.max_target()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mobile::{FeeModel, FeeModelError, ModelKind};
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+
+    #[test]
+    fn test_mobile_bindings() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = FeeModel::new().unwrap();
+        let expected = get_fee_model().estimate(6, Some(ts), &rates, ts - 300);
+        let rate = model
+            .estimate(6, Some(ts), rates.clone(), ts - 300)
+            .unwrap();
+        assert_eq!(rate, expected.unwrap());
+        let detailed = model
+            .estimate_detailed(1, Some(ts), rates.clone(), ts - 300)
+            .unwrap();
+        assert_eq!(detailed.model, ModelKind::Low);
+        assert_eq!(detailed.block_target, 1);
+        assert_eq!(detailed.stale_seconds, None);
+        let detailed = model
+            .estimate_detailed(6, Some(ts), rates.clone(), ts - 3 * 3600)
+            .unwrap();
+        assert_eq!(detailed.stale_seconds, Some(3 * 3600));
+        assert_eq!(model.max_target(), 1008);
+
+        let low = std::fs::read("models/20211027-180849/model.cbor").unwrap();
+        let high = std::fs::read("models/20211027-180925/model.cbor").unwrap();
+        let loaded = FeeModel::from_bytes(low, high).unwrap();
+        assert_eq!(
+            loaded.estimate(6, Some(ts), rates.clone(), ts - 300),
+            Ok(rate)
+        );
+
+        let err = model.estimate(0, Some(ts), rates.clone(), ts - 300);
+        assert!(matches!(err, Err(FeeModelError::InvalidInput { .. })));
+        let err = model.estimate(6, Some(ts), vec![], ts - 300);
+        assert!(matches!(err, Err(FeeModelError::InsufficientData { .. })));
+        let err = FeeModel::from_bytes(vec![1, 2, 3], vec![]).unwrap_err();
+        assert!(matches!(err, FeeModelError::InvalidModel { .. }));
+        assert!(!err.to_string().is_empty());
+    }
+}
@@ -1,97 +1,1667 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
 use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
 
-use crate::matrix::{size::*, Matrix, SizeMarker};
-use crate::Error;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-pub mod models {
-    use super::*;
-    use crate::matrix::Matrix;
-
-    include!(concat!(env!("OUT_DIR"), "/models.rs"));
-}
+use crate::activation::Activation;
+use crate::config::FeeModelConfig;
+#[cfg(feature = "std")]
+use crate::loader::LimitedReader;
+use crate::loader::Loader;
+#[cfg(feature = "f16")]
+use crate::matrix::HalfMatrix;
+use crate::matrix::{self, Matrix, QuantizedMatrix, SparseMatrix};
+use crate::model_input::{bucket_index, Feature, ModelInput, INPUT_LEN};
+use crate::{Error, PredictTrace};
 
-#[derive(Debug)]
-pub struct ModelData<I, N, O> {
+/// Formatted with the weights summarized by their shapes and checksum
+#[derive(Clone)]
+pub struct ModelData {
     pub norm: FieldsDescribe,
-    pub weights: Weights<I, O, N, N>,
+    pub weights: Weights,
     pub fields: Vec<String>,
+    /// slope of the leaky relu of the hidden layers of the models without `activations`
     pub alpha: f32,
+    /// activation of the outputs of every layer
+    pub activations: [Activation; 3],
+    /// transform applied to the target at training time, inverted on the predictions
+    pub output_transform: OutputTransform,
+    /// description of the model, empty for models written before metadata was introduced
+    pub info: ModelInfo,
+    /// quantile predicted by every output, empty for models with a single output
+    quantiles: Vec<f32>,
+    /// indexes of the outputs sorted by increasing quantile
+    quantile_order: Vec<usize>,
+    /// index of the output returned by [`ModelData::predict`], the quantile closest to the median
+    point: usize,
+    /// for every field, its position in [`ModelInput::to_array`], `None` if some field is not
+    /// part of [`ModelInput`]
+    permutation: Option<Vec<usize>>,
+    /// for every field, the offset and the scale used to normalize it: the mean and the standard
+    /// deviation, or the minimum and the range, in the order of the fields so that the inputs are
+    /// normalized without lookups. They are not folded into the weights of the first layer:
+    /// `(x - offset) / scale * w` rounds differently than `x * (w / scale)`, which would change
+    /// the outputs pinned by the golden tests, quantized kernels would lose the scale and
+    /// [`ModelData::predict_trace`] reports the normalized input
+    norm_values: Vec<(f32, f32)>,
+    /// digest of the bytes the model has been loaded from
+    #[cfg(feature = "checksum")]
+    sha256: Option<[u8; 32]>,
 }
 
-#[derive(Debug)]
-pub struct Weights<I, O, N1, N2> {
-    pub l0_bias: Matrix<N1, Size1>,
-    pub l0_kernel: Matrix<N1, I>,
+/// Optional description of a model, stored in the `metadata` section of the serialized model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// unix time in seconds of the training
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trained_at: Option<i64>,
+    /// buckets configuration used to build the training inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket_config: Option<FeeModelConfig>,
+    /// the buckets of the training inputs contain the vsize of the transactions instead of their
+    /// number, see [`crate::FeeModel::estimate_weighted`]
+    #[serde(default, skip_serializing_if = "core::ops::Not::not")]
+    pub weighted_buckets: bool,
+    /// the greatest target of the training inputs, see [`crate::FeeModel::max_target`], and of
+    /// the low models the greatest target they estimate, see
+    /// [`crate::FeeModel::dispatch_threshold`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_target: Option<u16>,
+    /// the target of every output of the models predicting every target in a single pass,
+    /// without the `confirms_in` feature, increasing: see [`ModelData::norm_predict_target`].
+    /// Empty for the models with a single output or with quantiles.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_targets: Vec<u16>,
+    /// the bound in seconds of the `delta_last` of the training inputs, also applied to the
+    /// estimates, see [`crate::FeeModel::max_delta_last`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_delta_last: Option<i64>,
+    /// the range of the values of the fields in the training inputs, for the fields in it, see
+    /// [`crate::Strictness`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub feature_ranges: BTreeMap<String, FeatureRange>,
+    /// the unit of the fields in the training inputs, like `seconds` for `delta_last`, for the
+    /// fields in it, see [`crate::FeeModel::validate_consistency`]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub units: BTreeMap<String, String>,
+}
+
+/// The smallest and the greatest value of a field in the training inputs, see
+/// [`ModelInfo::feature_ranges`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FeatureRange {
+    pub min: f32,
+    pub max: f32,
+}
 
-    pub l1_bias: Matrix<N2, Size1>,
-    pub l1_kernel: Matrix<N2, N1>,
+/// Formatted with the shapes of the kernels and a checksum of every weight instead of the weights
+#[derive(Clone)]
+pub struct Weights {
+    pub l0_bias: Matrix,
+    pub l0_kernel: Kernel,
 
-    pub l2_bias: Matrix<O, Size1>,
-    pub l2_kernel: Matrix<O, N2>,
+    pub l1_bias: Matrix,
+    pub l1_kernel: Kernel,
+
+    pub l2_bias: Matrix,
+    pub l2_kernel: Kernel,
 }
 
-#[derive(Debug)]
+impl Weights {
+    /// FNV-1a hash of the bits of every weight, dequantized, to tell models apart when formatted
+    fn checksum(&self) -> u64 {
+        let layers = [
+            (&self.l0_kernel, &self.l0_bias),
+            (&self.l1_kernel, &self.l1_bias),
+            (&self.l2_kernel, &self.l2_bias),
+        ];
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut add = |x: f32| {
+            for byte in x.to_bits().to_le_bytes().iter() {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        for (kernel, bias) in layers.iter() {
+            for i in 0..kernel.height() {
+                for j in 0..kernel.width() {
+                    add(kernel.get(i, j));
+                }
+            }
+            bias.as_slice().iter().for_each(|b| add(*b));
+        }
+        hash
+    }
+}
+
+impl fmt::Debug for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Weights")
+            .field("l0_kernel", &Shape(&self.l0_kernel))
+            .field("l1_kernel", &Shape(&self.l1_kernel))
+            .field("l2_kernel", &Shape(&self.l2_kernel))
+            .field("checksum", &format_args!("{:016x}", self.checksum()))
+            .finish()
+    }
+}
+
+/// Sizes of a model, see [`ModelData::size_report`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SizeReport {
+    /// weights and biases, see [`ModelData::num_parameters`]
+    pub parameters: usize,
+    /// bytes taken by the kernels and the biases in memory
+    pub weight_bytes: usize,
+    /// bytes of the model serialized by [`ModelData::to_vec`]
+    pub serialized_bytes: usize,
+}
+
+/// Read only description of a dense layer of a model, see [`ModelData::layers`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerInfo {
+    /// the height of the kernel, the width of the previous layer or the number of fields
+    pub input_dim: usize,
+    /// the width of the kernel and of the bias
+    pub output_dim: usize,
+    pub activation: Activation,
+    /// the statistics of the weights of the kernel, dequantized, without the bias
+    pub l2_norm: f32,
+    pub min: f32,
+    pub max: f32,
+    /// fraction of the weights of the kernel exactly zero, like the ones pruned
+    pub zero_fraction: f32,
+}
+
+impl LayerInfo {
+    fn new(kernel: &Kernel, activation: Activation) -> Self {
+        let (mut sum_squares, mut zeros) = (0.0f64, 0usize);
+        let (mut min, mut max) = (f32::INFINITY, f32::NEG_INFINITY);
+        for i in 0..kernel.height() {
+            for j in 0..kernel.width() {
+                let w = kernel.get(i, j);
+                sum_squares += w as f64 * w as f64;
+                zeros += usize::from(w == 0.0);
+                min = min.min(w);
+                max = max.max(w);
+            }
+        }
+        let count = kernel.height() * kernel.width();
+        LayerInfo {
+            input_dim: kernel.height(),
+            output_dim: kernel.width(),
+            activation,
+            l2_norm: libm::sqrt(sum_squares) as f32,
+            min,
+            max,
+            zero_fraction: zeros as f32 / count.max(1) as f32,
+        }
+    }
+}
+
+/// Formats a kernel as its height, width and kind, like `20x4 f32`
+struct Shape<'a>(&'a Kernel);
+
+impl fmt::Debug for Shape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.0 {
+            Kernel::F32(_) => "f32",
+            Kernel::Int8(_) => "int8",
+            Kernel::Sparse(_) => "sparse",
+            #[cfg(feature = "f16")]
+            Kernel::F16(_) => "f16",
+        };
+        write!(f, "{}x{} {}", self.0.height(), self.0.width(), kind)
+    }
+}
+
+impl fmt::Debug for ModelData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ModelData")
+            .field("fields", &self.fields)
+            .field("activations", &self.activations)
+            .field("output_transform", &self.output_transform)
+            .field("quantiles", &self.quantiles)
+            .field("info", &self.info)
+            .field("weights", &self.weights)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Kernel of a dense layer, in f32, quantized to int8, sparse or in half precision
+#[derive(Debug, Clone)]
+pub enum Kernel {
+    F32(Matrix),
+    Int8(QuantizedMatrix),
+    Sparse(SparseMatrix),
+    #[cfg(feature = "f16")]
+    F16(HalfMatrix),
+}
+
+impl Kernel {
+    /// `input` times the kernel
+    pub fn apply(&self, input: &Matrix) -> Matrix {
+        match self {
+            Kernel::F32(kernel) => input.dot(kernel),
+            Kernel::Int8(kernel) => input.dot_int8(kernel),
+            Kernel::Sparse(kernel) => input.dot_sparse(kernel),
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => input.dot_f16(kernel),
+        }
+    }
+
+    /// Like [`Kernel::apply`] for the row vector `input`, writing the product in `out`, of the
+    /// width of the kernel, and using `scratch`, as wide, to dequantize int8 kernels and convert
+    /// f16 ones. Gives the same results without the `ndarray` feature.
+    fn apply_row(&self, input: &[f32], out: &mut [f32], scratch: &mut [f32]) {
+        match self {
+            Kernel::F32(kernel) => matrix::dot_row(input, kernel, out),
+            Kernel::Int8(kernel) => matrix::dot_row_int8(input, kernel, out, scratch),
+            Kernel::Sparse(kernel) => matrix::dot_row_sparse(input, kernel, out),
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => matrix::dot_row_f16(input, kernel, out, scratch),
+        }
+    }
+
+    /// The element at row `i` and column `j`, dequantized if needed
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        match self {
+            Kernel::F32(kernel) => kernel[i][j],
+            Kernel::Int8(kernel) => kernel.get(i, j),
+            Kernel::Sparse(kernel) => kernel.get(i, j),
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => kernel.get(i, j),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        match self {
+            Kernel::F32(kernel) => kernel.width(),
+            Kernel::Int8(kernel) => kernel.width(),
+            Kernel::Sparse(kernel) => kernel.width(),
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => kernel.width(),
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        match self {
+            Kernel::F32(kernel) => kernel.height(),
+            Kernel::Int8(kernel) => kernel.height(),
+            Kernel::Sparse(kernel) => kernel.height(),
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => kernel.height(),
+        }
+    }
+
+    /// Bytes taken by the weights in memory
+    fn size(&self) -> usize {
+        let f32_size = core::mem::size_of::<f32>();
+        match self {
+            Kernel::F32(kernel) => core::mem::size_of_val(kernel.as_slice()),
+            Kernel::Int8(kernel) => (kernel.width() + f32_size + 1) * kernel.height(),
+            Kernel::Sparse(kernel) => {
+                let nonzero = kernel.values().len();
+                (nonzero * 2 + kernel.row_pointers().len()) * f32_size
+            }
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => kernel.width() * kernel.height() * 2,
+        }
+    }
+}
+
+/// Float kernels are serialized as the sequence of their rows, quantized ones as a map with the
+/// rows of int8 `values`, and the `scales` and `zero_points` of every row, sparse ones as a map
+/// with the `width` and the `values`, `column_indices` and `row_pointers` of the CSR form, half
+/// precision ones as a map with their rows in `f16`, written as CBOR half floats. The models
+/// with half precision kernels load without the `f16` feature too, the kernels in f32.
+impl Serialize for Kernel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Kernel::F32(kernel) => kernel.serialize(serializer),
+            Kernel::Int8(kernel) => RawInt8Ref {
+                values: (0..kernel.height()).map(|i| kernel.row(i)).collect(),
+                scales: kernel.scales(),
+                zero_points: kernel.zero_points(),
+            }
+            .serialize(serializer),
+            Kernel::Sparse(kernel) => RawSparseRef {
+                values: kernel.values(),
+                column_indices: kernel.column_indices(),
+                row_pointers: kernel.row_pointers(),
+                width: kernel.width(),
+            }
+            .serialize(serializer),
+            #[cfg(feature = "f16")]
+            Kernel::F16(kernel) => RawHalfRef {
+                f16: &kernel.to_f32(),
+            }
+            .serialize(serializer),
+        }
+    }
+}
+
+/// Normalization of the fields: fields in `mean` and `std` are standardized, the others scaled
+/// with their `min` and `max` to the range from 0 to 1
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FieldsDescribe {
-    mean: HashMap<String, f32>,
-    std: HashMap<String, f32>,
+    mean: BTreeMap<String, f32>,
+    std: BTreeMap<String, f32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    min: BTreeMap<String, f32>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    max: BTreeMap<String, f32>,
+}
+
+impl FieldsDescribe {
+    /// Standardize every field of `mean` and `std`
+    #[cfg(any(feature = "json-import", feature = "onnx"))]
+    pub(crate) fn standardized(mean: BTreeMap<String, f32>, std: BTreeMap<String, f32>) -> Self {
+        FieldsDescribe {
+            mean,
+            std,
+            min: BTreeMap::new(),
+            max: BTreeMap::new(),
+        }
+    }
+
+    /// The offset and the scale normalizing `field` as `(x - offset) / scale`
+    pub(crate) fn offset_scale(&self, field: &str) -> Result<(f32, f32), Error> {
+        let invalid = || Error::InvalidNormalization(field.to_string());
+        let (offset, scale) = match (self.mean.get(field), self.min.get(field)) {
+            (Some(mean), _) => {
+                let std = self.std.get(field);
+                let std = std.ok_or_else(|| Error::MissingStdData(field.to_string()))?;
+                (*mean, *std)
+            }
+            (None, Some(min)) => {
+                let max = self.max.get(field).ok_or_else(invalid)?;
+                (*min, max - min)
+            }
+            (None, None) => return Err(Error::MissingMeanData(field.to_string())),
+        };
+        if !offset.is_finite() || !scale.is_finite() || scale == 0.0 {
+            return Err(invalid());
+        }
+        Ok((offset, scale))
+    }
 }
 
-impl<I: SizeMarker, N: SizeMarker, O: SizeMarker> ModelData<I, N, O> {
-    pub fn predict(&self, input: &Matrix<I, Size1>) -> f32 {
-        let a1 = input.dot(&self.weights.l0_kernel);
+/// Transform applied to the target fee rate at training time, the predictions of the network are
+/// converted back with its inverse
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputTransform {
+    /// The network predicts the fee rate
+    #[default]
+    Identity,
+    /// The network predicts `ln(1 + rate)`, converted back with `e^y - 1`
+    Log1p,
+    /// The network predicts the square root of the rate, converted back squaring the predictions
+    /// not lower than zero
+    Sqrt,
+}
+
+impl OutputTransform {
+    /// Convert a prediction of the network back to a fee rate
+    pub fn invert(&self, y: f32) -> f32 {
+        match self {
+            OutputTransform::Identity => y,
+            OutputTransform::Log1p => libm::expm1f(y),
+            OutputTransform::Sqrt => y.max(0.0) * y.max(0.0),
+        }
+    }
+
+    /// Like [`OutputTransform::invert`] in double precision
+    pub fn invert_f64(&self, y: f64) -> f64 {
+        match self {
+            OutputTransform::Identity => y,
+            OutputTransform::Log1p => libm::expm1(y),
+            OutputTransform::Sqrt => y.max(0.0) * y.max(0.0),
+        }
+    }
+}
+
+/// Version of the serialized model layout written by [`ModelData::to_writer`]. Models without
+/// version, like the ones produced by the training script, are version 0, which has the same
+/// layout without the `metadata` section. Version 2 allows kernels quantized to int8, see
+/// [`ModelData::quantize_int8`]. Version 3 allows sparse kernels, see [`ModelData::to_sparse`], and
+/// adds the `activations` of the layers, older models use a leaky relu with slope `alpha` on the
+/// hidden layers and the identity on the output. Version 4 adds the `min` and `max` normalization
+/// of the fields, the `output_transform`, see [`FieldsDescribe`] and [`OutputTransform`], and the
+/// `quantiles` of the outputs, see [`ModelData::quantiles`]. Newer versions are rejected, but
+/// unknown fields are ignored, so that optional ones can be added without a new version.
+pub const MODEL_FORMAT_VERSION: u32 = 4;
+
+/// The widest layer predicted without allocating, see [`ModelData::norm_predict_slice`]
+const STACK_WIDTH: usize = 128;
+
+/// Bounds checked when loading a model, so that a corrupt or malicious model can't require
+/// excessive memory or time
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelLimits {
+    /// Maximum size of the serialized model in bytes, defaults to 16 MiB
+    pub max_bytes: usize,
+    /// Maximum number of input features and of neurons of every layer, defaults to 1024
+    pub max_layer_width: usize,
+    /// Maximum number of weights and biases of the whole model, defaults to 1 million
+    pub max_parameters: usize,
+}
+
+impl Default for ModelLimits {
+    fn default() -> Self {
+        ModelLimits {
+            max_bytes: 16 * 1024 * 1024,
+            max_layer_width: 1024,
+            max_parameters: 1_000_000,
+        }
+    }
+}
+
+impl ModelLimits {
+    pub(crate) fn check(&self, limit: &'static str, max: usize, got: usize) -> Result<(), Error> {
+        if got > max {
+            return Err(Error::LimitExceeded { limit, max, got });
+        }
+        Ok(())
+    }
+
+    /// Check the dimensions and the number of parameters before allocating the weights
+    fn check_raw(&self, raw: &RawModelData) -> Result<(), Error> {
+        let weights = &raw.weights;
+        self.check_widths(&[
+            raw.fields.len(),
+            weights.l0_bias.len(),
+            weights.l1_bias.len(),
+            weights.l2_bias.len(),
+        ])
+    }
+
+    /// Check the `widths` of the input and of every layer, and the parameters they imply
+    pub(crate) fn check_widths(&self, widths: &[usize; 4]) -> Result<(), Error> {
+        for width in widths.iter() {
+            self.check("max_layer_width", self.max_layer_width, *width)?;
+        }
+        let parameters = widths.windows(2).map(|w| w[0] * w[1] + w[1]).sum::<usize>();
+        self.check("max_parameters", self.max_parameters, parameters)
+    }
+}
+
+/// Read the version before the rest of the model, whose layout depends on it
+#[derive(Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    format_version: u32,
+}
+
+pub(crate) fn check_version(version: u32) -> Result<(), Error> {
+    if version > MODEL_FORMAT_VERSION {
+        return Err(Error::UnsupportedModelVersion(version));
+    }
+    Ok(())
+}
+
+/// Serialized layout of a model as produced by the training script, deserialized by
+/// [`Loader`]. The fields missing in older models have their default value.
+pub(crate) struct RawModelData {
+    pub(crate) format_version: u32,
+    pub(crate) norm: FieldsDescribe,
+    pub(crate) weights: RawWeights,
+    pub(crate) fields: Vec<String>,
+    pub(crate) alpha: f32,
+    /// names of the activations of the layers, see [`Activation::from_name`]
+    pub(crate) activations: Option<Vec<String>>,
+    pub(crate) output_transform: OutputTransform,
+    /// quantile predicted by every output of the models with more than one
+    pub(crate) quantiles: Vec<f32>,
+    pub(crate) metadata: ModelInfo,
+}
+
+/// Same layout of [`RawModelData`], borrowed from a loaded model
+#[derive(Serialize)]
+struct RawModelDataRef<'a> {
+    format_version: u32,
+    norm: &'a FieldsDescribe,
+    weights: RawWeightsRef<'a>,
+    fields: &'a [String],
+    alpha: f32,
+    activations: Vec<String>,
+    output_transform: OutputTransform,
+    #[serde(skip_serializing_if = "<[f32]>::is_empty")]
+    quantiles: &'a [f32],
+    metadata: &'a ModelInfo,
+}
+
+#[derive(Serialize)]
+struct RawWeightsRef<'a> {
+    #[serde(rename = "dense/bias:0")]
+    l0_bias: &'a [f32],
+    #[serde(rename = "dense/kernel:0")]
+    l0_kernel: &'a Kernel,
+
+    #[serde(rename = "dense_1/bias:0")]
+    l1_bias: &'a [f32],
+    #[serde(rename = "dense_1/kernel:0")]
+    l1_kernel: &'a Kernel,
+
+    #[serde(rename = "dense_2/bias:0")]
+    l2_bias: &'a [f32],
+    #[serde(rename = "dense_2/kernel:0")]
+    l2_kernel: &'a Kernel,
+}
+
+#[derive(Serialize)]
+struct RawInt8Ref<'a> {
+    values: Vec<&'a [i8]>,
+    scales: &'a [f32],
+    zero_points: &'a [i8],
+}
+
+#[cfg(feature = "f16")]
+#[derive(Serialize)]
+struct RawHalfRef<'a> {
+    f16: &'a Matrix,
+}
+
+#[derive(Serialize)]
+struct RawSparseRef<'a> {
+    values: &'a [f32],
+    column_indices: &'a [u32],
+    row_pointers: &'a [u32],
+    width: usize,
+}
+
+/// Serialized kernel, see [`Kernel`], the variant is chosen by the type of the value and the keys
+/// of the map
+pub(crate) enum RawKernel {
+    F32(RawRows<f32>),
+    /// the rows of a half precision kernel, in f32
+    F16(RawRows<f32>),
+    Int8 {
+        values: RawRows<i8>,
+        scales: Vec<f32>,
+        zero_points: Vec<i8>,
+    },
+    Sparse {
+        values: Vec<f32>,
+        column_indices: Vec<u32>,
+        row_pointers: Vec<u32>,
+        width: usize,
+    },
+}
+
+/// Serialized weights, named like in [`crate::validate::LAYERS`]
+pub(crate) struct RawWeights {
+    pub(crate) l0_bias: Vec<f32>,
+    pub(crate) l0_kernel: RawKernel,
+
+    pub(crate) l1_bias: Vec<f32>,
+    pub(crate) l1_kernel: RawKernel,
+
+    pub(crate) l2_bias: Vec<f32>,
+    pub(crate) l2_kernel: RawKernel,
+}
+
+/// The rows of a serialized kernel, flattened while deserialized
+#[derive(Debug)]
+pub(crate) struct RawRows<T> {
+    pub(crate) values: Vec<T>,
+    pub(crate) rows: usize,
+    /// the length of the first row
+    pub(crate) width: usize,
+    /// the length of the first row not as long as the first one
+    pub(crate) ragged: Option<usize>,
+}
+
+impl<T> Default for RawRows<T> {
+    fn default() -> Self {
+        RawRows {
+            values: Vec::new(),
+            rows: 0,
+            width: 0,
+            ragged: None,
+        }
+    }
+}
+
+impl<T> RawRows<T> {
+    /// The values of the `height` x `width` rows, checking every row has `width` elements
+    fn into_flat(
+        self,
+        layer: &'static str,
+        height: usize,
+        width: usize,
+    ) -> Result<Box<[T]>, Error> {
+        check_len(layer, height, self.rows)?;
+        if self.rows > 0 {
+            check_len(layer, width, self.width)?;
+        }
+        if let Some(got) = self.ragged {
+            check_len(layer, width, got)?;
+        }
+        Ok(self.values.into_boxed_slice())
+    }
+}
+
+pub(crate) fn check_len(layer: &'static str, expected: usize, got: usize) -> Result<(), Error> {
+    if expected != got {
+        return Err(Error::DimensionMismatch {
+            layer,
+            expected,
+            got,
+        });
+    }
+    Ok(())
+}
+
+pub(crate) fn check_finite(layer: &'static str, values: &[f32]) -> Result<(), Error> {
+    if values.iter().any(|v| !v.is_finite()) {
+        return Err(Error::NonFiniteWeight(layer));
+    }
+    Ok(())
+}
+
+/// Convert the `height` x `width` serialized kernel in a flat matrix
+fn kernel(
+    layer: &'static str,
+    raw: RawKernel,
+    height: usize,
+    width: usize,
+) -> Result<Kernel, Error> {
+    match raw {
+        RawKernel::F32(rows) => {
+            let buf = rows.into_flat(layer, height, width)?;
+            check_finite(layer, &buf)?;
+            Ok(Kernel::F32(Matrix::from_buffer(buf, width, height)))
+        }
+        RawKernel::F16(rows) => {
+            let buf = rows.into_flat(layer, height, width)?;
+            check_finite(layer, &buf)?;
+            let kernel = Matrix::from_buffer(buf, width, height);
+            #[cfg(feature = "f16")]
+            let kernel = Kernel::F16(HalfMatrix::from_matrix(&kernel));
+            #[cfg(not(feature = "f16"))]
+            let kernel = Kernel::F32(kernel);
+            Ok(kernel)
+        }
+        RawKernel::Int8 {
+            values,
+            scales,
+            zero_points,
+        } => {
+            let values = values.into_flat(layer, height, width)?;
+            check_len(layer, height, scales.len())?;
+            check_len(layer, height, zero_points.len())?;
+            let scales = scales.into_boxed_slice();
+            let zero_points = zero_points.into_boxed_slice();
+            let kernel = QuantizedMatrix::new(values, scales, zero_points, width);
+            kernel.map(Kernel::Int8).ok_or(Error::InvalidScale(layer))
+        }
+        RawKernel::Sparse {
+            values,
+            column_indices,
+            row_pointers,
+            width: got,
+        } => {
+            check_len(layer, width, got)?;
+            check_len(layer, height + 1, row_pointers.len())?;
+            check_finite(layer, &values)?;
+            let kernel = SparseMatrix::new(
+                values.into_boxed_slice(),
+                column_indices.into_boxed_slice(),
+                row_pointers.into_boxed_slice(),
+                width,
+            );
+            kernel
+                .map(Kernel::Sparse)
+                .ok_or(Error::InvalidSparseIndices(layer))
+        }
+    }
+}
+
+impl RawWeights {
+    fn into_weights(self, inputs: usize) -> Result<Weights, Error> {
+        let n0 = self.l0_bias.len();
+        let n1 = self.l1_bias.len();
+        let o = self.l2_bias.len();
+        check_finite("dense/bias:0", &self.l0_bias)?;
+        check_finite("dense_1/bias:0", &self.l1_bias)?;
+        check_finite("dense_2/bias:0", &self.l2_bias)?;
+
+        Ok(Weights {
+            l0_kernel: kernel("dense/kernel:0", self.l0_kernel, inputs, n0)?,
+            l0_bias: Matrix::from_array(self.l0_bias.into_boxed_slice()),
+            l1_kernel: kernel("dense_1/kernel:0", self.l1_kernel, n0, n1)?,
+            l1_bias: Matrix::from_array(self.l1_bias.into_boxed_slice()),
+            l2_kernel: kernel("dense_2/kernel:0", self.l2_kernel, n1, o)?,
+            l2_bias: Matrix::from_array(self.l2_bias.into_boxed_slice()),
+        })
+    }
+}
+
+impl ModelData {
+    /// Load a model from CBOR bytes, checking layer dimensions are consistent and normalization
+    /// data is available for every field. Errors with [`Error::UnsupportedModelVersion`] for
+    /// models newer than [`MODEL_FORMAT_VERSION`] and with [`Error::LimitExceeded`] for models
+    /// over the default [`ModelLimits`].
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: Read>(reader: R) -> Result<ModelData, Error> {
+        ModelData::from_reader_with_limits(reader, &ModelLimits::default())
+    }
+
+    /// Like [`ModelData::from_reader`] with custom `limits`.
+    ///
+    /// The model is parsed while read, a layer at a time, so that it doesn't allocate much more
+    /// than the loaded model, and errors as soon as a limit is exceeded, without reading the rest.
+    /// The other checks are done once read. The `format_version`, the first field of the models written by
+    /// [`ModelData::to_writer`], is checked when read: a newer model errors with
+    /// [`Error::UnsupportedModelVersion`] unless another field fails to parse before it, while
+    /// [`ModelData::from_slice`] reads the version first wherever it is.
+    #[cfg(feature = "std")]
+    pub fn from_reader_with_limits<R: Read>(
+        reader: R,
+        limits: &ModelLimits,
+    ) -> Result<ModelData, Error> {
+        let loader = Loader::new(limits);
+        let mut reader = std::io::BufReader::new(LimitedReader::new(reader, limits));
+        let mut deserializer = serde_cbor::Deserializer::from_reader(&mut reader);
+        let raw = loader
+            .deserialize(&mut deserializer)
+            .and_then(|raw| deserializer.end().map(|_| raw));
+        let raw = match raw {
+            Ok(raw) => raw,
+            Err(e) => return Err(reader.get_mut().error(&loader, e)),
+        };
+        let model = ModelData::from_raw(raw, limits)?;
+        #[cfg(feature = "checksum")]
+        let model = ModelData {
+            sha256: Some(reader.into_inner().sha256()),
+            ..model
+        };
+        Ok(model)
+    }
+
+    /// Like [`ModelData::from_reader`], available also without the `std` feature
+    pub fn from_slice(bytes: &[u8]) -> Result<ModelData, Error> {
+        ModelData::from_slice_with_limits(bytes, &ModelLimits::default())
+    }
+
+    /// Like [`ModelData::from_slice`] with custom `limits`
+    pub fn from_slice_with_limits(bytes: &[u8], limits: &ModelLimits) -> Result<ModelData, Error> {
+        limits.check("max_bytes", limits.max_bytes, bytes.len())?;
+        let probe: VersionProbe = serde_cbor::from_slice(bytes).map_err(Error::Deserialize)?;
+        check_version(probe.format_version)?;
+        let loader = Loader::new(limits);
+        let mut deserializer = serde_cbor::Deserializer::from_slice(bytes);
+        let raw = loader
+            .deserialize(&mut deserializer)
+            .and_then(|raw| deserializer.end().map(|_| raw))
+            .map_err(|e| loader.error(Error::Deserialize(e)))?;
+        let model = ModelData::from_raw(raw, limits)?;
+        #[cfg(feature = "checksum")]
+        let model = ModelData {
+            sha256: Some(crate::checksum::sha256(bytes)),
+            ..model
+        };
+        Ok(model)
+    }
+
+    /// Write the model as CBOR, with the same layout accepted by [`ModelData::from_reader`]
+    #[cfg(feature = "std")]
+    pub fn to_writer<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_cbor::to_writer(writer, self).map_err(Error::Serialize)
+    }
+
+    /// Like [`ModelData::to_writer`], available also without the `std` feature
+    pub fn to_vec(&self) -> Result<Vec<u8>, Error> {
+        serde_cbor::to_vec(self).map_err(Error::Serialize)
+    }
+
+    /// Load a model from JSON with the same layout of the CBOR models
+    #[cfg(feature = "json")]
+    pub fn from_json_reader<R: Read>(reader: R) -> Result<ModelData, Error> {
+        let limits = ModelLimits::default();
+        let bytes = read_limited(reader, &limits)?;
+        let probe: VersionProbe = serde_json::from_slice(&bytes).map_err(Error::Json)?;
+        check_version(probe.format_version)?;
+        let loader = Loader::new(&limits);
+        let mut deserializer = serde_json::Deserializer::from_slice(&bytes);
+        let raw = loader
+            .deserialize(&mut deserializer)
+            .and_then(|raw| deserializer.end().map(|_| raw))
+            .map_err(|e| loader.error(Error::Json(e)))?;
+        ModelData::from_raw(raw, &limits)
+    }
+
+    /// Write the model as JSON, loadable with [`ModelData::from_json_reader`]
+    #[cfg(feature = "json")]
+    pub fn to_json_writer<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, self).map_err(Error::Json)
+    }
+
+    fn from_raw(raw: RawModelData, limits: &ModelLimits) -> Result<ModelData, Error> {
+        check_version(raw.format_version)?;
+        limits.check_raw(&raw)?;
+        let weights = raw.weights.into_weights(raw.fields.len())?;
+        ModelData::from_parts(
+            raw.norm,
+            raw.fields,
+            raw.alpha,
+            raw.activations,
+            raw.output_transform,
+            raw.quantiles,
+            raw.metadata,
+            weights,
+        )
+    }
+
+    /// Build the model from `weights` whose dimensions have already been checked, checking the
+    /// normalization data of the fields, the activation names and the quantiles of the outputs,
+    /// then the whole model with [`ModelData::validate`]
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn from_parts(
+        norm: FieldsDescribe,
+        fields: Vec<String>,
+        alpha: f32,
+        activations: Option<Vec<String>>,
+        output_transform: OutputTransform,
+        quantiles: Vec<f32>,
+        info: ModelInfo,
+        weights: Weights,
+    ) -> Result<ModelData, Error> {
+        if !alpha.is_finite() {
+            return Err(Error::NonFiniteWeight("alpha"));
+        }
+        let outputs = weights.l2_bias.width();
+        let targets = &info.output_targets;
+        let increasing = targets.windows(2).all(|w| w[0] < w[1]);
+        if !targets.is_empty() && (!quantiles.is_empty() || targets[0] == 0 || !increasing) {
+            return Err(Error::InvalidOutputTargets(targets.clone()));
+        }
+        if quantiles.is_empty() {
+            check_len("dense_2/bias:0", targets.len().max(1), outputs)?;
+        } else if quantiles.len() != outputs || quantiles.iter().any(|q| !(*q > 0.0 && *q < 1.0)) {
+            return Err(Error::InvalidQuantiles);
+        }
+        let mut quantile_order: Vec<usize> = (0..quantiles.len()).collect();
+        quantile_order.sort_by(|a, b| quantiles[*a].total_cmp(&quantiles[*b]));
+        if quantile_order
+            .windows(2)
+            .any(|w| quantiles[w[0]] == quantiles[w[1]])
+        {
+            return Err(Error::InvalidQuantiles);
+        }
+        let point = (0..quantiles.len())
+            .min_by(|a, b| {
+                let distance = |q: f32| (q - 0.5).abs();
+                distance(quantiles[*a]).total_cmp(&distance(quantiles[*b]))
+            })
+            .unwrap_or(0);
+        let activations = match activations {
+            None => [
+                Activation::LeakyRelu(alpha),
+                Activation::LeakyRelu(alpha),
+                Activation::Identity,
+            ],
+            Some(names) => {
+                check_len("activations", 3, names.len())?;
+                [
+                    Activation::from_name(&names[0])?,
+                    Activation::from_name(&names[1])?,
+                    Activation::from_name(&names[2])?,
+                ]
+            }
+        };
+        let norm_values = fields
+            .iter()
+            .map(|f| norm.offset_scale(f))
+            .collect::<Result<_, _>>()?;
+        let permutation: Option<Vec<usize>> = fields
+            .iter()
+            .map(|f| Feature::from_name(f).map(|f| f.index()))
+            .collect();
+        let permutation = permutation.filter(|p| p.len() <= INPUT_LEN);
+
+        let model = ModelData {
+            weights,
+            norm,
+            fields,
+            alpha,
+            activations,
+            output_transform,
+            info,
+            quantiles,
+            quantile_order,
+            point,
+            permutation,
+            norm_values,
+            #[cfg(feature = "checksum")]
+            sha256: None,
+        };
+        model.validate().map_err(Error::InvalidModel)?;
+        Ok(model)
+    }
+
+    /// A model predicting `rate` for every input, with the fields of a [`ModelInput`] with
+    /// `bucket_count` buckets and null weights, see [`crate::FeeModel::for_network`]
+    pub fn constant(rate: f32, bucket_count: usize) -> Result<ModelData, Error> {
+        let mut fields: Vec<String> = ["confirms_in", "day_of_week", "hour", "delta_last"]
+            .iter()
+            .map(|f| f.to_string())
+            .collect();
+        fields.extend((0..bucket_count).map(|i| format!("b{}", i)));
+        let norm = FieldsDescribe {
+            mean: fields.iter().map(|f| (f.clone(), 0.0)).collect(),
+            std: fields.iter().map(|f| (f.clone(), 1.0)).collect(),
+            min: BTreeMap::new(),
+            max: BTreeMap::new(),
+        };
+        let weights = Weights {
+            l0_bias: Matrix::zeros(1, 1),
+            l0_kernel: Kernel::F32(Matrix::zeros(1, fields.len())),
+            l1_bias: Matrix::zeros(1, 1),
+            l1_kernel: Kernel::F32(Matrix::zeros(1, 1)),
+            l2_bias: Matrix::from_array(vec![rate].into_boxed_slice()),
+            l2_kernel: Kernel::F32(Matrix::zeros(1, 1)),
+        };
+        check_finite("dense_2/bias:0", &[rate])?;
+        let info = ModelInfo {
+            name: Some("constant".to_string()),
+            ..ModelInfo::default()
+        };
+        let transform = OutputTransform::Identity;
+        ModelData::from_parts(norm, fields, 0.0, None, transform, vec![], info, weights)
+    }
+
+    /// SHA-256 digest of the bytes the model has been loaded from, like the `sha256sum` of its
+    /// file, or of [`ModelData::to_vec`] for models not loaded from CBOR
+    #[cfg(feature = "checksum")]
+    pub fn sha256(&self) -> [u8; 32] {
+        match self.sha256 {
+            Some(sha256) => sha256,
+            None => crate::checksum::sha256(&self.to_vec().unwrap_or_default()),
+        }
+    }
+
+    /// Names of the input features of the model, in the order they are fed to it
+    pub fn feature_names(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// The dense layers of the model from the input to the output
+    pub fn layers(&self) -> impl Iterator<Item = LayerInfo> + '_ {
+        let kernels = [
+            &self.weights.l0_kernel,
+            &self.weights.l1_kernel,
+            &self.weights.l2_kernel,
+        ];
+        let activations = self.activations;
+        (0..kernels.len()).map(move |i| LayerInfo::new(kernels[i], activations[i]))
+    }
+
+    /// Number of weights and biases of the model, as bounded by [`ModelLimits::max_parameters`]
+    pub fn num_parameters(&self) -> usize {
+        let biases = [
+            &self.weights.l0_bias,
+            &self.weights.l1_bias,
+            &self.weights.l2_bias,
+        ];
+        let weights = self.layers().map(|l| l.input_dim * l.output_dim);
+        weights.sum::<usize>() + biases.iter().map(|b| b.as_slice().len()).sum::<usize>()
+    }
+
+    /// The number of parameters of the model and the bytes they take in memory and serialized,
+    /// like to compare the kernels converted by [`ModelData::quantize_int8`] or
+    /// [`ModelData::to_f16`]
+    pub fn size_report(&self) -> Result<SizeReport, Error> {
+        let w = &self.weights;
+        let kernels = [&w.l0_kernel, &w.l1_kernel, &w.l2_kernel];
+        let biases = [&w.l0_bias, &w.l1_bias, &w.l2_bias];
+        let bias_bytes = biases.iter().map(|b| b.as_slice().len() * 4);
+        let weight_bytes =
+            kernels.iter().map(|k| k.size()).sum::<usize>() + bias_bytes.sum::<usize>();
+        Ok(SizeReport {
+            parameters: self.num_parameters(),
+            weight_bytes,
+            serialized_bytes: self.to_vec()?.len(),
+        })
+    }
+
+    /// Number of fee bucket features `b0..bN` the model expects
+    pub fn bucket_count(&self) -> usize {
+        self.fields
+            .iter()
+            .filter(|f| bucket_index(f).is_some())
+            .count()
+    }
+
+    /// Quantile predicted by every output, in the order of [`ModelData::norm_predict_quantiles`],
+    /// empty for models with a single output
+    pub fn quantiles(&self) -> &[f32] {
+        &self.quantiles
+    }
+
+    /// Index in [`ModelData::quantiles`] of `quantile`, errors with [`Error::MissingQuantile`] if
+    /// the model doesn't predict it
+    pub fn quantile_index(&self, quantile: f32) -> Result<usize, Error> {
+        self.quantiles
+            .iter()
+            .position(|q| *q == quantile)
+            .ok_or(Error::MissingQuantile(quantile))
+    }
+
+    /// Predict the single output, or for models with quantiles the one closest to the median
+    pub fn predict(&self, input: &Matrix) -> f32 {
+        self.predict_outputs(input)[self.point]
+    }
+
+    /// Predict every output, rearranged so that they increase with their quantile: the trained
+    /// outputs are not constrained and could cross, but the quantiles of a distribution can't
+    pub fn predict_outputs(&self, input: &Matrix) -> Vec<f32> {
+        let a1 = self.weights.l0_kernel.apply(input);
         let a2 = a1.add(&self.weights.l0_bias);
-        let a3 = a2.relu(self.alpha);
+        let a3 = a2.activation(self.activations[0]);
 
-        let b1 = a3.dot(&self.weights.l1_kernel);
+        let b1 = self.weights.l1_kernel.apply(&a3);
         let b2 = b1.add(&self.weights.l1_bias);
-        let b3 = b2.relu(self.alpha);
+        let b3 = b2.activation(self.activations[1]);
 
-        let c1 = b3.dot(&self.weights.l2_kernel);
+        let c1 = self.weights.l2_kernel.apply(&b3);
         let c2 = c1.add(&self.weights.l2_bias);
 
-        c2[0][0]
+        let mut outputs: Vec<f32> = c2[0]
+            .iter()
+            .map(|o| self.output_transform.invert(self.activations[2].apply(*o)))
+            .collect();
+        sort_by_quantile(&self.quantile_order, &mut outputs);
+        outputs
     }
 
-    pub fn norm(&self, input: &HashMap<String, f32>) -> Result<Matrix<I, Size1>, Error> {
+    /// Normalize the value of every field of the model in `input`, errors with
+    /// [`Error::MissingFeature`] if one is missing
+    #[cfg(feature = "std")]
+    pub fn norm(&self, input: &HashMap<String, f32>) -> Result<Matrix, Error> {
         let mut result = vec![];
-        for field in self.fields.iter() {
-            let x = input.get(field).unwrap_or(&0.0);
-            let std = self
-                .norm
-                .std
-                .get(field)
-                .ok_or_else(|| Error::MissingStdData(field.clone()))?;
-            let mean = self
-                .norm
-                .mean
+        for (field, (offset, scale)) in self.fields.iter().zip(self.norm_values.iter()) {
+            let x = input
                 .get(field)
-                .ok_or_else(|| Error::MissingMeanData(field.clone()))?;
-            let res = (x - mean) / std;
-            result.push(res)
+                .ok_or_else(|| Error::MissingFeature(field.clone()))?;
+            result.push((x - offset) / scale)
         }
         Ok(Matrix::from_array(result.into_boxed_slice()))
     }
 
+    #[cfg(feature = "std")]
     pub fn norm_predict(&self, input: &HashMap<String, f32>) -> Result<f32, Error> {
         let input = self.norm(input)?;
         Ok(self.predict(&input))
     }
+
+    /// Normalize and predict a typed input, resolving fields with the mapping computed at load
+    /// time. Fields not available in [`ModelInput`] are zero, see
+    /// [`ModelData::norm_predict_with_extras`] to supply them
+    pub fn norm_predict_input(&self, input: &ModelInput) -> Result<f32, Error> {
+        self.with_input_values(input, |values| self.norm_predict_slice(values))
+    }
+
+    /// Like [`ModelData::norm_predict_input`], predicting every output like
+    /// [`ModelData::predict_outputs`], the value of the quantile `quantiles()[i]` is at index `i`,
+    /// the one of the target `output_targets()[i]` for the models with
+    /// [`ModelData::output_targets`]
+    pub fn norm_predict_quantiles(&self, input: &ModelInput) -> Result<Vec<f32>, Error> {
+        self.with_input_values(input, |values| {
+            Ok(self.predict_outputs(&self.normalize(values)?))
+        })
+    }
+
+    /// The targets of the outputs, see [`ModelInfo::output_targets`], empty for the models
+    /// estimating the target of the `confirms_in` feature
+    pub fn output_targets(&self) -> &[u16] {
+        &self.info.output_targets
+    }
+
+    /// Like [`ModelData::norm_predict_input`] for `block_target`. The models with
+    /// [`ModelData::output_targets`] predict every target in a single pass and give the output of
+    /// `block_target` or, between two targets, the outputs of both linearly interpolated, the
+    /// first or the last output out of them. The other models predict with `confirms_in` set to
+    /// `block_target`.
+    pub fn norm_predict_target(&self, input: &ModelInput, block_target: u16) -> Result<f32, Error> {
+        if self.output_targets().is_empty() {
+            let mut input = input.clone();
+            input.confirms_in = block_target as f32;
+            return self.norm_predict_input(&input);
+        }
+        let outputs = self.norm_predict_quantiles(input)?;
+        Ok(self.target_output(&outputs, block_target))
+    }
+
+    /// Like [`ModelData::norm_predict_quantiles`] taking the extra fields from `extras`, see
+    /// [`ModelData::norm_predict_with_extras`]
+    pub(crate) fn norm_predict_outputs_with_extras(
+        &self,
+        input: &ModelInput,
+        extras: &BTreeMap<String, f32>,
+    ) -> Result<Vec<f32>, Error> {
+        self.with_extras_values(input, extras, |values| {
+            Ok(self.predict_outputs(&self.normalize(values)?))
+        })
+    }
+
+    /// The value for `block_target` of `outputs`, the ones of [`ModelData::output_targets`], see
+    /// [`ModelData::norm_predict_target`]
+    pub(crate) fn target_output(&self, outputs: &[f32], block_target: u16) -> f32 {
+        let targets = self.output_targets();
+        match targets.binary_search(&block_target) {
+            Ok(i) => outputs[i],
+            Err(0) => outputs[0],
+            Err(i) if i == targets.len() => outputs[i - 1],
+            Err(i) => {
+                let (below, above) = (targets[i - 1] as f32, targets[i] as f32);
+                let weight = (block_target as f32 - below) / (above - below);
+                outputs[i - 1] * (1.0 - weight) + outputs[i] * weight
+            }
+        }
+    }
+
+    /// Call `f` with the values of the fields of the model taken from `input`
+    fn with_input_values<T, F: FnOnce(&[f32]) -> T>(&self, input: &ModelInput, f: F) -> T {
+        let array = input.to_array();
+        let permutation = match &self.permutation {
+            Some(permutation) => permutation,
+            None => {
+                let values: Vec<f32> = self
+                    .fields
+                    .iter()
+                    .map(|f| Feature::from_name(f).map_or(0.0, |f| array[f.index()]))
+                    .collect();
+                return f(&values);
+            }
+        };
+        let mut values = [0.0f32; INPUT_LEN];
+        for (value, index) in values.iter_mut().zip(permutation.iter()) {
+            *value = array[*index];
+        }
+        f(&values[..permutation.len()])
+    }
+
+    /// Like [`ModelData::norm_predict_input`], keeping the values computed at every step to debug
+    /// a prediction, for example to tell the normalization from the network. The other predict
+    /// methods don't keep them.
+    pub fn predict_trace(&self, input: &ModelInput) -> Result<PredictTrace, Error> {
+        let mut values = self.with_input_values(input, |v| self.normalize(v))?;
+        let normalized = values.as_slice().to_vec();
+        let w = &self.weights;
+        let layers = [
+            (&w.l0_kernel, &w.l0_bias),
+            (&w.l1_kernel, &w.l1_bias),
+            (&w.l2_kernel, &w.l2_bias),
+        ];
+        let mut pre_activations = Vec::with_capacity(layers.len());
+        let mut activations = Vec::with_capacity(layers.len());
+        for ((kernel, bias), activation) in layers.iter().zip(self.activations.iter()) {
+            let pre_activation = kernel.apply(&values).add(bias);
+            values = pre_activation.activation(*activation);
+            pre_activations.push(pre_activation.as_slice().to_vec());
+            activations.push(values.as_slice().to_vec());
+        }
+        let mut outputs: Vec<f32> = values
+            .as_slice()
+            .iter()
+            .map(|o| self.output_transform.invert(*o))
+            .collect();
+        sort_by_quantile(&self.quantile_order, &mut outputs);
+        Ok(PredictTrace {
+            normalized,
+            pre_activations,
+            activations,
+            prediction: outputs[self.point],
+            outputs,
+        })
+    }
+
+    /// Fields of the model not available in [`ModelInput`], like mempool derived features, to be
+    /// supplied to [`ModelData::norm_predict_with_extras`]
+    pub fn extra_fields(&self) -> impl Iterator<Item = &str> {
+        self.fields
+            .iter()
+            .filter(|f| Feature::from_name(f).is_none())
+            .map(String::as_str)
+    }
+
+    /// Like [`ModelData::norm_predict_input`], taking the values of the [`ModelData::extra_fields`]
+    /// from `extras`. Errors with [`Error::MissingExtra`] if one of them is missing, values in
+    /// `extras` not used by the model are ignored.
+    pub fn norm_predict_with_extras(
+        &self,
+        input: &ModelInput,
+        extras: &BTreeMap<String, f32>,
+    ) -> Result<f32, Error> {
+        self.with_extras_values(input, extras, |values| self.norm_predict_slice(values))
+    }
+
+    /// The fields of `input`, and of `extras` for the other ones, out of the
+    /// [`ModelInfo::feature_ranges`] of the model, in the order of the ranges. The fields
+    /// without a value are not checked, a NaN value is out of range.
+    pub fn out_of_range_features(
+        &self,
+        input: &ModelInput,
+        extras: Option<&BTreeMap<String, f32>>,
+    ) -> Vec<String> {
+        let array = input.to_array();
+        let value = |field: &str| match Feature::from_name(field) {
+            Some(feature) => Some(array[feature.index()]),
+            None => extras.and_then(|e| e.get(field).copied()),
+        };
+        let ranges = self.info.feature_ranges.iter();
+        ranges
+            .filter(|(field, range)| {
+                value(field).is_some_and(|v| !(range.min <= v && v <= range.max))
+            })
+            .map(|(field, _)| field.clone())
+            .collect()
+    }
+
+    /// Like [`ModelData::with_input_values`], taking the extra fields from `extras`
+    fn with_extras_values<T, F: FnOnce(&[f32]) -> Result<T, Error>>(
+        &self,
+        input: &ModelInput,
+        extras: &BTreeMap<String, f32>,
+        f: F,
+    ) -> Result<T, Error> {
+        if self.permutation.is_some() {
+            return self.with_input_values(input, f);
+        }
+        let array = input.to_array();
+        let values = self
+            .fields
+            .iter()
+            .map(|f| match Feature::from_name(f) {
+                Some(feature) => Ok(array[feature.index()]),
+                None => extras
+                    .get(f)
+                    .copied()
+                    .ok_or_else(|| Error::MissingExtra(f.clone())),
+            })
+            .collect::<Result<Vec<f32>, Error>>()?;
+        f(&values)
+    }
+
+    /// Normalize and predict `input`, which contains the value of every field in the same order
+    /// of [`ModelData::fields`]
+    ///
+    /// Without the `ndarray` feature it doesn't allocate: the values of the layers are on the
+    /// stack and, for models with layers wider than 128 neurons, in a [`crate::PredictScratch`] of
+    /// the thread, see [`ModelData::norm_predict_with`].
+    pub fn norm_predict_slice(&self, input: &[f32]) -> Result<f32, Error> {
+        if let Some(prediction) = self.norm_predict_stack(input)? {
+            return Ok(prediction);
+        }
+        #[cfg(all(feature = "std", not(feature = "ndarray")))]
+        {
+            crate::scratch::with_thread_scratch(|scratch| self.norm_predict_with(input, scratch))
+        }
+        #[cfg(not(all(feature = "std", not(feature = "ndarray"))))]
+        {
+            Ok(self.predict(&self.normalize(input)?))
+        }
+    }
+
+    /// Like [`ModelData::norm_predict_slice`] without allocating, with the values of every layer
+    /// in arrays on the stack, giving the same prediction. `None` if a layer is wider than
+    /// [`STACK_WIDTH`] or with the `ndarray` feature, whose products may differ.
+    fn norm_predict_stack(&self, input: &[f32]) -> Result<Option<f32>, Error> {
+        let w = &self.weights;
+        let widths = [
+            input.len(),
+            w.l0_bias.width(),
+            w.l1_bias.width(),
+            w.l2_bias.width(),
+        ];
+        if cfg!(feature = "ndarray") || widths.iter().any(|w| *w > STACK_WIDTH) {
+            return Ok(None);
+        }
+        let mut normalized = [0.0f32; STACK_WIDTH];
+        let [mut l0, mut l1, mut l2] = [[0.0f32; STACK_WIDTH]; 3];
+        let mut dequantized = [0.0f32; STACK_WIDTH];
+        let layers: [&mut [f32]; 3] = [&mut l0, &mut l1, &mut l2];
+        self.forward_row(input, &mut normalized, layers, &mut dequantized)
+            .map(Some)
+    }
+
+    /// Normalize and predict `input` with the row products of [`Kernel::apply_row`], writing the
+    /// normalized input in `normalized`, the values of every layer in `layers` and dequantizing
+    /// int8 kernels in `dequantized`, each at least as wide as needed
+    pub(crate) fn forward_row(
+        &self,
+        input: &[f32],
+        normalized: &mut [f32],
+        layers: [&mut [f32]; 3],
+        dequantized: &mut [f32],
+    ) -> Result<f32, Error> {
+        let w = &self.weights;
+        if input.len() != self.norm_values.len() {
+            return Err(Error::DimensionMismatch {
+                layer: "input",
+                expected: self.norm_values.len(),
+                got: input.len(),
+            });
+        }
+        let values = input.iter().zip(self.norm_values.iter());
+        for (n, (x, (offset, scale))) in normalized.iter_mut().zip(values) {
+            *n = (x - offset) / scale;
+        }
+        let kernels = [
+            (&w.l0_kernel, &w.l0_bias),
+            (&w.l1_kernel, &w.l1_bias),
+            (&w.l2_kernel, &w.l2_bias),
+        ];
+        let mut previous = &mut normalized[..input.len()];
+        for (((kernel, bias), out), activation) in kernels
+            .iter()
+            .zip(IntoIterator::into_iter(layers))
+            .zip(self.activations.iter())
+        {
+            let out = &mut out[..bias.width()];
+            kernel.apply_row(previous, out, &mut dequantized[..kernel.width()]);
+            for (o, b) in out.iter_mut().zip(bias.as_slice()) {
+                *o = activation.apply(*o + b);
+            }
+            previous = out;
+        }
+        let outputs = previous;
+        for o in outputs.iter_mut() {
+            *o = self.output_transform.invert(*o);
+        }
+        if outputs.len() > 1 {
+            sort_by_quantile(&self.quantile_order, outputs);
+        }
+        Ok(outputs[self.point])
+    }
+
+    pub(crate) fn normalize(&self, input: &[f32]) -> Result<Matrix, Error> {
+        if input.len() != self.norm_values.len() {
+            return Err(Error::DimensionMismatch {
+                layer: "input",
+                expected: self.norm_values.len(),
+                got: input.len(),
+            });
+        }
+        let normalized: Box<[f32]> = input
+            .iter()
+            .zip(self.norm_values.iter())
+            .map(|(x, (offset, scale))| (x - offset) / scale)
+            .collect();
+        Ok(Matrix::from_array(normalized))
+    }
+
+    /// Like [`ModelData::norm_predict_slice`] in double precision: the weights stored as f32 are
+    /// upcast to f64 and every operation runs in f64
+    pub fn norm_predict_f64(&self, input: &[f64]) -> Result<f64, Error> {
+        Ok(self.predict_f64(&self.normalize_f64(input)?))
+    }
+
+    fn normalize_f64(&self, input: &[f64]) -> Result<Vec<f64>, Error> {
+        if input.len() != self.norm_values.len() {
+            return Err(Error::DimensionMismatch {
+                layer: "input",
+                expected: self.norm_values.len(),
+                got: input.len(),
+            });
+        }
+        Ok(input
+            .iter()
+            .zip(self.norm_values.iter())
+            .map(|(x, (offset, scale))| (x - *offset as f64) / *scale as f64)
+            .collect())
+    }
+
+    /// Like [`ModelData::predict`] in double precision
+    fn predict_f64(&self, normalized: &[f64]) -> f64 {
+        let w = &self.weights;
+        let [a0, a1, a2] = self.activations;
+        let a = dense_f64(normalized, &w.l0_kernel, &w.l0_bias, a0);
+        let b = dense_f64(&a, &w.l1_kernel, &w.l1_bias, a1);
+        let mut outputs = dense_f64(&b, &w.l2_kernel, &w.l2_bias, a2);
+        sort_by_quantile(&self.quantile_order, &mut outputs);
+        self.output_transform.invert_f64(outputs[self.point])
+    }
+
+    /// Copy of the model with every kernel quantized to int8 by `QuantizedMatrix::quantize`,
+    /// about four times smaller, biases are kept in f32. The predictions of the embedded models
+    /// drift about 2% in median from the float ones, but much more for some inputs because their
+    /// large weights are mostly canceled by the biases: check the drift on the expected inputs
+    /// before using a quantized model.
+    pub fn quantize_int8(&self) -> ModelData {
+        self.map_kernels(|kernel| match kernel {
+            Kernel::F32(kernel) => Kernel::Int8(QuantizedMatrix::quantize(kernel)),
+            _ => kernel.clone(),
+        })
+    }
+
+    /// Copy of the model with every float kernel converted to half precision by
+    /// `HalfMatrix::from_matrix`, half the size in memory and serialized, biases are kept in
+    /// f32. The estimates of the embedded models on [`crate::PredictionGrid::standard`] drift
+    /// about 0.06% in median from the float ones, and up to 10% for the few inputs whose large
+    /// weights are canceled by the biases. Kernels with weights out of the half precision range
+    /// are kept in f32.
+    #[cfg(feature = "f16")]
+    pub fn to_f16(&self) -> ModelData {
+        self.map_kernels(|kernel| match kernel {
+            Kernel::F32(matrix) => {
+                let half = HalfMatrix::from_matrix(matrix);
+                if (0..half.height()).all(|i| (0..half.width()).all(|j| half.get(i, j).is_finite()))
+                {
+                    Kernel::F16(half)
+                } else {
+                    kernel.clone()
+                }
+            }
+            _ => kernel.clone(),
+        })
+    }
+
+    /// Copy of the model with every float kernel converted to sparse by `Matrix::to_sparse`,
+    /// dropping the weights whose absolute value is not greater than `threshold`. With a zero
+    /// `threshold` the predictions are the same, the model is smaller when most of the weights
+    /// are zero, for example after pruning.
+    pub fn to_sparse(&self, threshold: f32) -> ModelData {
+        self.map_kernels(|kernel| match kernel {
+            Kernel::F32(kernel) => Kernel::Sparse(kernel.to_sparse(threshold)),
+            _ => kernel.clone(),
+        })
+    }
+
+    /// Copy of the model with the kernels converted by `f`
+    fn map_kernels<F: Fn(&Kernel) -> Kernel>(&self, f: F) -> ModelData {
+        let w = &self.weights;
+        let weights = Weights {
+            l0_bias: w.l0_bias.clone(),
+            l0_kernel: f(&w.l0_kernel),
+            l1_bias: w.l1_bias.clone(),
+            l1_kernel: f(&w.l1_kernel),
+            l2_bias: w.l2_bias.clone(),
+            l2_kernel: f(&w.l2_kernel),
+        };
+        ModelData {
+            weights,
+            norm: self.norm.clone(),
+            fields: self.fields.clone(),
+            alpha: self.alpha,
+            activations: self.activations,
+            output_transform: self.output_transform,
+            info: self.info.clone(),
+            quantiles: self.quantiles.clone(),
+            quantile_order: self.quantile_order.clone(),
+            point: self.point,
+            permutation: self.permutation.clone(),
+            norm_values: self.norm_values.clone(),
+            #[cfg(feature = "checksum")]
+            sha256: None,
+        }
+    }
+
+    /// Like [`ModelData::norm_predict_input`] in double precision, see
+    /// [`ModelData::norm_predict_f64`]
+    pub fn norm_predict_input_f64(&self, input: &ModelInput) -> Result<f64, Error> {
+        let array = input.to_array();
+        let values: Vec<f64> = self
+            .fields
+            .iter()
+            .map(|f| Feature::from_name(f).map_or(0.0, |f| array[f.index()] as f64))
+            .collect();
+        self.norm_predict_f64(&values)
+    }
+
+    /// Attribution of the prediction of `input` to every field, sorted by decreasing absolute
+    /// value. The attribution of a field is the sensitivity of the prediction to its normalized
+    /// value, estimated with central finite differences in double precision, times the normalized
+    /// value: fields at their training mean, or minimum with the min-max normalization, have no
+    /// attribution. For nearly linear models the attributions sum to the change of the prediction
+    /// from the input with every normalized field at zero.
+    pub fn explain_input(&self, input: &ModelInput) -> Result<Vec<(String, f32)>, Error> {
+        let attributions = self.attributions(input)?;
+        Ok(sort_attributions(
+            self.fields.iter().cloned().zip(attributions).collect(),
+        ))
+    }
+
+    /// The attributions of [`ModelData::explain_input`] in the order of the fields
+    pub(crate) fn attributions(&self, input: &ModelInput) -> Result<Vec<f32>, Error> {
+        const EPSILON: f64 = 1e-4;
+
+        let values: Vec<f64> =
+            self.with_input_values(input, |v| v.iter().map(|x| *x as f64).collect());
+        let mut normalized = self.normalize_f64(&values)?;
+        let mut attributions = Vec::with_capacity(normalized.len());
+        for i in 0..normalized.len() {
+            let x = normalized[i];
+            normalized[i] = x + EPSILON;
+            let up = self.predict_f64(&normalized);
+            normalized[i] = x - EPSILON;
+            let down = self.predict_f64(&normalized);
+            normalized[i] = x;
+            if !up.is_finite() || !down.is_finite() {
+                return Err(Error::NonFiniteOutput);
+            }
+            let sensitivity = (up - down) / (2.0 * EPSILON);
+            attributions.push((sensitivity * x) as f32);
+        }
+        Ok(attributions)
+    }
+}
+
+/// Sort `attributions` by decreasing absolute value
+pub(crate) fn sort_attributions(mut attributions: Vec<(String, f32)>) -> Vec<(String, f32)> {
+    attributions.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+    attributions
+}
+
+/// Rearrange `values`, the outputs of a model, sorting them in the `order` of their quantiles
+fn sort_by_quantile<T: PartialOrd + Copy>(order: &[usize], values: &mut [T]) {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+    for (value, index) in sorted.into_iter().zip(order.iter()) {
+        values[*index] = value;
+    }
+}
+
+/// Dense layer in f64: `input` times `kernel` plus `bias`, followed by `activation`
+fn dense_f64(input: &[f64], kernel: &Kernel, bias: &Matrix, activation: Activation) -> Vec<f64> {
+    (0..kernel.width())
+        .map(|j| {
+            let dot: f64 = input
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x * kernel.get(i, j) as f64)
+                .sum();
+            activation.apply_f64(dot + bias[0][j] as f64)
+        })
+        .collect()
+}
+
+/// Read at most [`ModelLimits::max_bytes`] from `reader`
+#[cfg(feature = "std")]
+pub(crate) fn read_limited<R: Read>(reader: R, limits: &ModelLimits) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![];
+    let max = limits.max_bytes as u64;
+    reader
+        .take(max.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .map_err(Error::Io)?;
+    limits.check("max_bytes", limits.max_bytes, bytes.len())?;
+    Ok(bytes)
+}
+
+/// Serialized with the layout of the models produced by the training script
+impl Serialize for ModelData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let weights = &self.weights;
+        RawModelDataRef {
+            format_version: MODEL_FORMAT_VERSION,
+            norm: &self.norm,
+            weights: RawWeightsRef {
+                l0_bias: &weights.l0_bias[0],
+                l0_kernel: &weights.l0_kernel,
+                l1_bias: &weights.l1_bias[0],
+                l1_kernel: &weights.l1_kernel,
+                l2_bias: &weights.l2_bias[0],
+                l2_kernel: &weights.l2_kernel,
+            },
+            fields: &self.fields,
+            alpha: self.alpha,
+            activations: self.activations.iter().map(Activation::name).collect(),
+            output_transform: self.output_transform,
+            quantiles: &self.quantiles,
+            metadata: &self.info,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Checked like [`ModelData::from_slice`]
+impl<'de> Deserialize<'de> for ModelData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let limits = ModelLimits::default();
+        let raw = Loader::new(&limits).deserialize(deserializer)?;
+        ModelData::from_raw(raw, &limits).map_err(D::Error::custom)
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::excessive_precision)]
 pub mod tests {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, HashMap};
 
-    use crate::matrix::{size::*, Matrix};
+    use serde_cbor::Value;
+
+    use crate::config::FeeModelConfig;
+    use crate::matrix::Matrix;
+    use crate::model_data::Kernel;
+    use crate::model_input::bucket_index;
     use crate::tests::assert_approx_eq;
-    use crate::ModelData;
+    use crate::{
+        Activation, Error, FeatureRange, ModelData, ModelInfo, ModelInput, ModelLimits,
+        OutputTransform, MAX_BUCKETS, MODEL_FORMAT_VERSION,
+    };
 
-    pub fn get_test_model() -> ModelData<Size20, Size4, Size1> {
-        crate::get_model_test_model()
+    pub fn get_test_model() -> ModelData {
+        ModelData::from_slice(include_bytes!("../models/test_model.cbor")).unwrap()
     }
 
     #[rustfmt::skip]
-    pub fn get_test_input() -> Matrix<Size20, Size1> {
+    pub fn get_test_input() -> Matrix {
         Matrix::from_array(vec![-0.23901028,  0.02662498, -0.19410163,  0.03187769, -0.2026636,  -0.31123071,
                                      -0.23820148,  4.48084238,  0.86297716, -0.00825855, -0.1420311,  -0.5924509,
                                       0.62382793, -0.77146702, -0.5813809,  -0.36034099,  0.88637573,  0.3041703,
@@ -130,7 +1700,7 @@ pub mod tests {
         let model = get_test_model();
         let input = get_test_input();
 
-        let a1 = input.dot(&model.weights.l0_kernel);
+        let a1 = model.weights.l0_kernel.apply(&input);
         let a1_expected = Matrix::from_array(
             vec![-8.07738634, 0.32887421, 2.60496564, 0.14431801].into_boxed_slice(),
         );
@@ -142,7 +1712,7 @@ pub mod tests {
         a2.assert_approx_eq(&a2_expected);
         let a3 = a2.relu(0.01);
 
-        let b1 = a3.dot(&model.weights.l1_kernel);
+        let b1 = model.weights.l1_kernel.apply(&a3);
         let b2 = b1.add(&model.weights.l1_bias);
         let b3 = b2.relu(0.01);
         let b3_expected = Matrix::from_array(
@@ -150,7 +1720,7 @@ pub mod tests {
         );
         b3.assert_approx_eq(&b3_expected);
 
-        let c1 = b3.dot(&model.weights.l2_kernel);
+        let c1 = model.weights.l2_kernel.apply(&b3);
         let c2 = c1.add(&model.weights.l2_bias);
 
         assert_approx_eq(c2[0][0], get_test_result())
@@ -163,5 +1733,946 @@ pub mod tests {
         let expected = get_test_input();
         let norm = model.norm(&get_test_pre_norm()).unwrap();
         norm.assert_approx_eq(&expected);
+        let mut input = get_test_pre_norm();
+        input.remove("delta_last");
+        let err = model.norm_predict(&input).unwrap_err();
+        assert!(matches!(err, Error::MissingFeature(field) if field == "delta_last"));
+    }
+
+    pub fn get_test_model_input() -> ModelInput {
+        let mut buckets = [0.0f32; MAX_BUCKETS];
+        for (i, el) in BUCKETS.iter().enumerate() {
+            buckets[i] = *el as f32;
+        }
+        ModelInput {
+            confirms_in: 11.0,
+            day_of_week: 4.0,
+            hour: 4.0,
+            delta_last: 956.0,
+            buckets,
+        }
+    }
+
+    #[test]
+    fn test_norm_predict_input() {
+        let model = get_test_model();
+        assert!(model.permutation.is_some());
+        let input = get_test_model_input();
+        // the unused buckets are zero
+        let mut expected = get_test_pre_norm();
+        expected.extend((BUCKETS.len()..MAX_BUCKETS).map(|i| (format!("b{}", i), 0.0)));
+        assert_eq!(input.to_map(), expected);
+        assert_approx_eq(model.norm_predict_input(&input).unwrap(), get_test_result());
+
+        // a model with non typed fields fallback to the map input
+        let mut model = get_test_model();
+        model.permutation = None;
+        assert_approx_eq(model.norm_predict_input(&input).unwrap(), get_test_result());
+    }
+
+    #[test]
+    fn test_norm_predict_slice() {
+        let model = get_test_model();
+        let map = get_test_pre_norm();
+        let input: Vec<f32> = model.fields.iter().map(|f| map[f]).collect();
+        assert_approx_eq(model.norm_predict_slice(&input).unwrap(), get_test_result());
+        let err = model.norm_predict_slice(&input[1..]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "input",
+                expected: 20,
+                got: 19
+            }
+        ));
+    }
+
+    /// The test model with the `hour` field renamed to `mempool_vsize`
+    pub fn get_test_model_with_extra() -> ModelData {
+        get_test_model_with_hour_renamed("mempool_vsize")
+    }
+
+    /// The test model with the `hour` field renamed to `name`
+    pub fn get_test_model_with_hour_renamed(name: &str) -> ModelData {
+        let bytes = include_bytes!("../models/test_model.cbor");
+        with_field_renamed(bytes, "hour", name)
+    }
+
+    /// The model serialized in `bytes` with the field `from` renamed to `to`
+    pub fn with_field_renamed(bytes: &[u8], from: &str, to: &str) -> ModelData {
+        let mut value: serde_cbor::Value = serde_cbor::from_slice(bytes).unwrap();
+        let from: serde_cbor::Value = from.to_string().into();
+        let to: serde_cbor::Value = to.to_string().into();
+        if let serde_cbor::Value::Map(model) = &mut value {
+            if let Some(serde_cbor::Value::Array(fields)) =
+                model.get_mut(&"fields".to_string().into())
+            {
+                for field in fields.iter_mut().filter(|f| **f == from) {
+                    *field = to.clone();
+                }
+            }
+            if let Some(serde_cbor::Value::Map(norm)) = model.get_mut(&"norm".to_string().into()) {
+                for describe in norm.values_mut() {
+                    if let serde_cbor::Value::Map(describe) = describe {
+                        let v = describe.remove(&from).unwrap();
+                        describe.insert(to.clone(), v);
+                    }
+                }
+            }
+        }
+        ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap()
+    }
+
+    /// The test model with the standard deviation normalizing `field` divided by `divisor`, like
+    /// if trained with the field in another unit
+    pub fn get_test_model_with_scale_divided(field: &str, divisor: f32) -> ModelData {
+        let mut model = get_test_model();
+        *model.norm.std.get_mut(field).unwrap() /= divisor;
+        ModelData::from_slice(&model.to_vec().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_norm_predict_with_extras() {
+        let input = get_test_model_input();
+        let mut extras = BTreeMap::new();
+        extras.insert("unknown".to_string(), 1.0);
+
+        // extras are ignored by models without extra fields
+        let model = get_test_model();
+        assert_eq!(model.extra_fields().count(), 0);
+        let result = model.norm_predict_with_extras(&input, &extras).unwrap();
+        assert_eq!(result, model.norm_predict_input(&input).unwrap());
+
+        let model = get_test_model_with_extra();
+        assert_eq!(
+            model.extra_fields().collect::<Vec<_>>(),
+            vec!["mempool_vsize"]
+        );
+        let err = model.norm_predict_with_extras(&input, &extras).unwrap_err();
+        assert!(matches!(err, Error::MissingExtra(f) if f == "mempool_vsize"));
+        extras.insert("mempool_vsize".to_string(), input.hour);
+        let result = model.norm_predict_with_extras(&input, &extras).unwrap();
+        assert_approx_eq(result, get_test_result());
+    }
+
+    pub(crate) const MODELS: [&[u8]; 3] = [
+        include_bytes!("../models/test_model.cbor"),
+        include_bytes!("../models/20211027-180849/model.cbor"),
+        include_bytes!("../models/20211027-180925/model.cbor"),
+    ];
+
+    /// Assert `a` and `b` give identical predictions on a grid of inputs
+    pub(crate) fn assert_same_predictions(a: &ModelData, b: &ModelData) {
+        for (a, b) in grid_predictions(a).iter().zip(grid_predictions(b).iter()) {
+            assert_eq!(a.to_bits(), b.to_bits());
+        }
+    }
+
+    /// Predictions of `model` on a grid of inputs
+    pub(crate) fn grid_predictions(model: &ModelData) -> Vec<f32> {
+        let mut input = get_test_pre_norm();
+        let mut predictions = vec![];
+        for confirms_in in 1..30 {
+            for hour in 0..24 {
+                input.insert("confirms_in".to_string(), confirms_in as f32);
+                input.insert("hour".to_string(), hour as f32);
+                predictions.push(model.norm_predict(&input).unwrap());
+            }
+        }
+        predictions
+    }
+
+    impl Kernel {
+        pub fn as_f32(&self) -> &Matrix {
+            match self {
+                Kernel::F32(kernel) => kernel,
+                _ => panic!("not a float kernel"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_quantize_int8() {
+        for bytes in MODELS[1..].iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let quantized = model.quantize_int8();
+            assert!(matches!(quantized.weights.l0_kernel, Kernel::Int8(_)));
+            let float = grid_predictions(&model);
+            let drift = relative_drift(&grid_predictions(&quantized), &float);
+            assert!(drift[drift.len() / 2] < 0.05, "{:?}", drift);
+            assert!(drift[drift.len() * 9 / 10] < 0.25, "{:?}", drift);
+
+            let written = quantized.to_vec().unwrap();
+            assert!(written.len() < bytes.len() / 2);
+            let back = ModelData::from_slice(&written).unwrap();
+            assert_same_predictions(&quantized, &back);
+
+            // float and quantized layers can be mixed
+            let mut value: Value = serde_cbor::from_slice(&written).unwrap();
+            let mut original: Value = serde_cbor::from_slice(bytes).unwrap();
+            let l2_kernel = Value::Text("dense_2/kernel:0".into());
+            let float_kernel = weights_mut(&mut original)[&l2_kernel].clone();
+            weights_mut(&mut value).insert(l2_kernel, float_kernel);
+            let mixed = ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap();
+            assert!(matches!(mixed.weights.l1_kernel, Kernel::Int8(_)));
+            assert!(matches!(mixed.weights.l2_kernel, Kernel::F32(_)));
+            let drift = relative_drift(&grid_predictions(&mixed), &float);
+            assert!(drift[drift.len() / 2] < 0.05, "{:?}", drift);
+
+            let l0_kernel = Value::Text("dense/kernel:0".into());
+            if let Value::Map(kernel) = weights_mut(&mut value).get_mut(&l0_kernel).unwrap() {
+                kernel.insert(Value::Text("scales".into()), Value::Array(vec![]));
+            }
+            let err = ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap_err();
+            assert!(matches!(
+                err,
+                Error::DimensionMismatch {
+                    layer: "dense/kernel:0",
+                    ..
+                }
+            ));
+        }
+
+        let quantized = get_test_model().quantize_int8();
+        let mut value: Value = serde_cbor::from_slice(&quantized.to_vec().unwrap()).unwrap();
+        let l1_kernel = Value::Text("dense_1/kernel:0".into());
+        if let Value::Map(kernel) = weights_mut(&mut value).get_mut(&l1_kernel).unwrap() {
+            let scales = vec![Value::Float(0.0); quantized.weights.l1_kernel.height()];
+            kernel.insert(Value::Text("scales".into()), Value::Array(scales));
+        }
+        let err = ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap_err();
+        assert!(matches!(err, Error::InvalidScale("dense_1/kernel:0")));
+    }
+
+    #[test]
+    fn test_norm_predict_stack() {
+        let quantiles =
+            ModelData::from_reader(std::fs::File::open("models/fixtures/quantiles.cbor").unwrap())
+                .unwrap();
+        let mut models = vec![get_test_model(), quantiles];
+        models.extend(MODELS.iter().map(|b| ModelData::from_slice(b).unwrap()));
+        for model in models {
+            for model in [model.quantize_int8(), model.to_sparse(0.05), model] {
+                let input: Vec<f32> = (0..model.fields.len()).map(|i| i as f32).collect();
+                let expected = model.predict(&model.normalize(&input).unwrap());
+                let stack = model.norm_predict_stack(&input).unwrap();
+                if cfg!(feature = "ndarray") {
+                    assert_eq!(stack, None);
+                } else {
+                    assert_eq!(stack.unwrap().to_bits(), expected.to_bits());
+                }
+                let err = model.norm_predict_stack(&input[1..]);
+                assert!(cfg!(feature = "ndarray") || err.is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_sparse() {
+        for bytes in MODELS.iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let sparse = model.to_sparse(0.0);
+            assert!(matches!(sparse.weights.l0_kernel, Kernel::Sparse(_)));
+            if cfg!(feature = "ndarray") {
+                let drift = relative_drift(&grid_predictions(&sparse), &grid_predictions(&model));
+                // the sums of the large weights canceling out amplify the rounding differences
+                assert!(drift[drift.len() / 2] < 1e-5, "{:?}", drift);
+                assert!(drift[drift.len() - 1] < 1e-2, "{:?}", drift);
+            } else {
+                assert_same_predictions(&model, &sparse);
+            }
+            let back = ModelData::from_slice(&sparse.to_vec().unwrap()).unwrap();
+            assert!(matches!(back.weights.l2_kernel, Kernel::Sparse(_)));
+            assert_same_predictions(&sparse, &back);
+
+            let pruned = model.to_sparse(0.05);
+            let written = pruned.to_vec().unwrap();
+            let back = ModelData::from_slice(&written).unwrap();
+            assert_same_predictions(&pruned, &back);
+            let quantized = pruned.quantize_int8();
+            assert!(matches!(quantized.weights.l1_kernel, Kernel::Sparse(_)));
+        }
+
+        let sparse = get_test_model().to_sparse(0.0);
+        let mut value: Value = serde_cbor::from_slice(&sparse.to_vec().unwrap()).unwrap();
+        let l1_kernel = Value::Text("dense_1/kernel:0".into());
+        if let Value::Map(kernel) = weights_mut(&mut value).get_mut(&l1_kernel).unwrap() {
+            let columns = Value::Text("column_indices".into());
+            if let Some(Value::Array(columns)) = kernel.get_mut(&columns) {
+                columns.swap(0, 1);
+            }
+        }
+        let err = ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidSparseIndices("dense_1/kernel:0")
+        ));
+    }
+
+    /// Sorted relative differences of `predictions` from `expected`
+    fn relative_drift(predictions: &[f32], expected: &[f32]) -> Vec<f32> {
+        let mut drift: Vec<f32> = predictions
+            .iter()
+            .zip(expected.iter())
+            .map(|(p, e)| ((p - e) / e).abs())
+            .collect();
+        drift.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        drift
+    }
+
+    fn weights_mut(model: &mut Value) -> &mut BTreeMap<Value, Value> {
+        match model {
+            Value::Map(model) => match model.get_mut(&Value::Text("weights".into())) {
+                Some(Value::Map(weights)) => weights,
+                _ => panic!("missing weights"),
+            },
+            _ => panic!("model is not a map"),
+        }
+    }
+
+    #[cfg(feature = "f16")]
+    #[test]
+    fn test_to_f16() {
+        for bytes in MODELS[1..].iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let half = model.to_f16();
+            assert!(matches!(half.weights.l0_kernel, Kernel::F16(_)));
+            let float = grid_predictions(&model);
+            let drift = relative_drift(&grid_predictions(&half), &float);
+            assert!(drift[drift.len() / 2] < 1e-3, "{:?}", drift);
+            assert!(drift[drift.len() * 9 / 10] < 1e-2, "{:?}", drift);
+
+            let written = half.to_vec().unwrap();
+            let back = ModelData::from_slice(&written).unwrap();
+            assert!(matches!(back.weights.l2_kernel, Kernel::F16(_)));
+            assert_same_predictions(&half, &back);
+            assert_eq!(half.quantize_int8().to_vec().unwrap(), written);
+
+            let (report, half_report) = (model.size_report().unwrap(), half.size_report().unwrap());
+            assert_eq!(half_report.parameters, report.parameters);
+            assert_eq!(half_report.serialized_bytes, written.len());
+            assert!(half_report.serialized_bytes < report.serialized_bytes * 2 / 3);
+            assert!(half_report.weight_bytes < report.weight_bytes * 6 / 10);
+        }
+
+        // kernels out of the half precision range stay in f32
+        let mut model = get_test_model();
+        let mut kernel = model.weights.l1_kernel.as_f32().clone();
+        kernel[0][0] = 1e5;
+        model.weights.l1_kernel = Kernel::F32(kernel);
+        let half = model.to_f16();
+        assert!(matches!(half.weights.l0_kernel, Kernel::F16(_)));
+        assert!(matches!(half.weights.l1_kernel, Kernel::F32(_)));
+    }
+
+    #[test]
+    fn test_f16_kernels() {
+        // a kernel in half precision, loaded also without the `f16` feature
+        let model = get_test_model();
+        let mut value: Value = serde_cbor::from_slice(&model.to_vec().unwrap()).unwrap();
+        let l0_kernel = Value::Text("dense/kernel:0".into());
+        let weights = weights_mut(&mut value);
+        let rows = weights.remove(&l0_kernel).unwrap();
+        let half = BTreeMap::from([(Value::Text("f16".into()), rows)]);
+        weights.insert(l0_kernel, Value::Map(half));
+        let bytes = serde_cbor::to_vec(&value).unwrap();
+        let loaded = ModelData::from_slice(&bytes).unwrap();
+        let drift = relative_drift(&grid_predictions(&loaded), &grid_predictions(&model));
+        assert!(drift[drift.len() / 2] < 1e-3, "{:?}", drift);
+        #[cfg(feature = "f16")]
+        assert!(matches!(loaded.weights.l0_kernel, Kernel::F16(_)));
+        #[cfg(not(feature = "f16"))]
+        assert_same_predictions(&loaded, &model);
+        let from_reader = ModelData::from_reader(&bytes[..]).unwrap();
+        assert_same_predictions(&loaded, &from_reader);
+
+        if let Value::Map(kernel) = weights_mut(&mut value)
+            .get_mut(&Value::Text("dense/kernel:0".into()))
+            .unwrap()
+        {
+            kernel.insert(Value::Text("f16".into()), Value::Array(vec![]));
+        }
+        let err = ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap()).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "dense/kernel:0",
+                ..
+            }
+        ));
+
+        let report = model.size_report().unwrap();
+        assert_eq!(report.parameters, model.num_parameters());
+        assert_eq!(report.weight_bytes, model.num_parameters() * 4);
+    }
+
+    #[test]
+    fn test_cbor_round_trip() {
+        for bytes in MODELS.iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let mut written = vec![];
+            model.to_writer(&mut written).unwrap();
+            assert_eq!(written, model.to_vec().unwrap());
+            let back = ModelData::from_slice(&written).unwrap();
+            assert_eq!(back.fields, model.fields);
+            assert_eq!(back.alpha, model.alpha);
+            assert_same_predictions(&model, &back);
+            // writing is deterministic
+            assert_eq!(back.to_vec().unwrap(), written);
+        }
+    }
+
+    #[test]
+    fn test_format_version() {
+        let v0 = ModelData::from_slice(include_bytes!("../models/fixtures/v0.cbor")).unwrap();
+        assert_eq!(v0.info, ModelInfo::default());
+        // unknown fields are ignored
+        let v1 = ModelData::from_slice(include_bytes!("../models/fixtures/v1.cbor")).unwrap();
+        assert_eq!(v1.info.name.as_deref(), Some("test"));
+        assert_same_predictions(&v0, &v1);
+
+        let v999 = &include_bytes!("../models/fixtures/v999.cbor")[..];
+        let err = ModelData::from_slice(v999).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedModelVersion(999)));
+        let err = ModelData::from_reader(v999).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedModelVersion(999)));
+
+        let value: serde_cbor::Value = serde_cbor::from_slice(&v0.to_vec().unwrap()).unwrap();
+        if let serde_cbor::Value::Map(model) = value {
+            let version = model.get(&"format_version".to_string().into());
+            let expected = serde_cbor::Value::Integer(MODEL_FORMAT_VERSION.into());
+            assert_eq!(version, Some(&expected));
+        }
+    }
+
+    #[test]
+    fn test_model_activations() {
+        let model = get_test_model();
+        let alpha = Activation::LeakyRelu(model.alpha);
+        assert_eq!(model.activations, [alpha, alpha, Activation::Identity]);
+        let bytes = model.to_vec().unwrap();
+        let back = ModelData::from_slice(&bytes).unwrap();
+        assert_eq!(back.activations, model.activations);
+        assert_same_predictions(&model, &back);
+
+        let with_activations = |names: &[&str]| {
+            let mut value: Value = serde_cbor::from_slice(&bytes).unwrap();
+            if let Value::Map(model) = &mut value {
+                let names = names.iter().map(|n| Value::Text(n.to_string())).collect();
+                model.insert(Value::Text("activations".into()), Value::Array(names));
+            }
+            ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap())
+        };
+        let names = ["gelu", "tanh", "sigmoid"];
+        let model = with_activations(&names).unwrap();
+        let expected = [Activation::Gelu, Activation::Tanh, Activation::Sigmoid];
+        assert_eq!(model.activations, expected);
+        let input = get_test_model_input();
+        let prediction = model.norm_predict_input(&input).unwrap();
+        let prediction_f64 = model.norm_predict_input_f64(&input).unwrap();
+        assert!(prediction > 0.0 && prediction < 1.0);
+        assert!((prediction as f64 - prediction_f64).abs() < 1e-5);
+        let back = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+        assert_eq!(back.activations, expected);
+        assert_same_predictions(&model, &back);
+
+        let err = with_activations(&["relu", "swish", "identity"]).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedActivation(name) if name == "swish"));
+        let err = with_activations(&["relu", "relu"]).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "activations",
+                expected: 3,
+                got: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn test_layers() {
+        use crate::tests::{get_high_model, get_low_model};
+
+        for model in [get_test_model(), get_low_model(), get_high_model()].iter() {
+            let layers: Vec<_> = model.layers().collect();
+            assert_eq!(layers.len(), 3);
+            assert_eq!(layers[0].input_dim, model.feature_names().len());
+            for pair in layers.windows(2) {
+                assert_eq!(pair[0].output_dim, pair[1].input_dim);
+            }
+            assert_eq!(layers[2].output_dim, 1);
+            let parameters: usize = layers
+                .iter()
+                .map(|l| (l.input_dim + 1) * l.output_dim)
+                .sum();
+            assert_eq!(model.num_parameters(), parameters);
+            for layer in layers.iter() {
+                assert!(layer.min <= layer.max && layer.l2_norm > 0.0);
+                assert!((0.0..1.0).contains(&layer.zero_fraction));
+            }
+        }
+
+        let layers: Vec<_> = ModelData::constant(3.0, 2).unwrap().layers().collect();
+        assert_eq!(layers[0].input_dim, 6);
+        assert_eq!(layers[0].l2_norm, 0.0);
+        assert_eq!(layers[0].zero_fraction, 1.0);
+        assert_eq!(layers[2].activation, Activation::Identity);
+    }
+
+    #[test]
+    fn test_debug() {
+        let model = get_test_model();
+        let formatted = format!("{:?}", model);
+        assert!(formatted.starts_with("ModelData { fields: [\"confirms_in\""));
+        assert!(formatted.contains("l0_kernel: 20x4 f32"), "{}", formatted);
+        assert!(formatted.contains("l2_kernel: 4x1 f32"), "{}", formatted);
+        assert!(formatted.len() < 1000, "{}", formatted);
+        assert!(format!("{:?}", model.quantize_int8()).contains("l0_kernel: 20x4 int8"));
+
+        let mut changed = model.clone();
+        assert_eq!(format!("{:?}", changed), formatted);
+        assert_same_predictions(&model, &changed);
+        changed.weights.l1_bias[0][3] += 1e-6;
+        assert_ne!(format!("{:?}", changed), formatted);
+    }
+
+    #[test]
+    fn test_predict_trace() {
+        let model = get_test_model();
+        let input = get_test_model_input();
+        let trace = model.predict_trace(&input).unwrap();
+        let expected = model.norm_predict(&input.to_map()).unwrap();
+        assert_eq!(trace.prediction, expected);
+        assert_eq!(trace.outputs, vec![expected]);
+        assert_eq!(trace.activations[2], vec![expected]);
+        assert_eq!(
+            trace.normalized,
+            model.norm(&input.to_map()).unwrap().as_slice()
+        );
+        let widths = [
+            model.weights.l0_bias.width(),
+            model.weights.l1_bias.width(),
+            1,
+        ];
+        for (i, width) in widths.iter().enumerate() {
+            assert_eq!(trace.pre_activations[i].len(), *width);
+            for (pre, post) in trace.pre_activations[i].iter().zip(&trace.activations[i]) {
+                assert_eq!(model.activations[i].apply(*pre), *post);
+            }
+        }
+
+        let model = ModelData::from_slice(include_bytes!("../models/fixtures/quantiles.cbor"));
+        let model = model.unwrap();
+        let trace = model.predict_trace(&input).unwrap();
+        assert_eq!(trace.outputs, model.norm_predict_quantiles(&input).unwrap());
+        assert_eq!(trace.prediction, model.norm_predict_input(&input).unwrap());
+    }
+
+    #[test]
+    fn test_explain_input() {
+        let model = get_test_model();
+        let attributions = model.explain_input(&get_test_model_input()).unwrap();
+        assert_eq!(attributions.len(), model.fields.len());
+        for field in model.fields.iter() {
+            assert!(attributions.iter().any(|(name, _)| name == field));
+        }
+        for pair in attributions.windows(2) {
+            assert!(pair[0].1.abs() >= pair[1].1.abs());
+        }
+        // absolute attributions of the buckets and of the other fields
+        let totals = |attributions: &[(String, f32)]| {
+            let (mut buckets, mut others) = (0.0, 0.0);
+            for (name, attribution) in attributions {
+                match bucket_index(name) {
+                    Some(_) => buckets += attribution.abs(),
+                    None => others += attribution.abs(),
+                }
+            }
+            (buckets, others)
+        };
+        let (buckets, _) = totals(&attributions);
+        let mut input = get_test_model_input();
+        input.buckets = [0.0; MAX_BUCKETS];
+        let (zeroed_buckets, others) = totals(&model.explain_input(&input).unwrap());
+        assert!(
+            zeroed_buckets > 2.0 * buckets,
+            "{} {}",
+            zeroed_buckets,
+            buckets
+        );
+        assert!(
+            zeroed_buckets > 2.0 * others,
+            "{} {}",
+            zeroed_buckets,
+            others
+        );
+
+        // exact for a linear model: 2 * confirms_in + delta_last / 100 - 0.5 for the median
+        let model = ModelData::from_slice(include_bytes!("../models/fixtures/quantiles.cbor"));
+        let mut input = get_test_model_input();
+        input.confirms_in = 6.0;
+        input.delta_last = 300.0;
+        let attributions = model.unwrap().explain_input(&input).unwrap();
+        assert_eq!(attributions[0].0, "confirms_in");
+        assert!((attributions[0].1 - 12.0).abs() < 1e-4);
+        assert_eq!(attributions[1].0, "delta_last");
+        assert!((attributions[1].1 - 3.0).abs() < 1e-4);
+        assert!(attributions[2..].iter().all(|(_, a)| *a == 0.0));
+    }
+
+    #[test]
+    fn test_quantiles() {
+        let bytes = include_bytes!("../models/fixtures/quantiles.cbor");
+        let model = ModelData::from_slice(bytes).unwrap();
+        // outputs are declared in the order q90, q10, q50
+        assert_eq!(model.quantiles(), &[0.9, 0.1, 0.5]);
+        assert_eq!(model.quantile_index(0.9).unwrap(), 0);
+        assert_eq!(model.quantile_index(0.1).unwrap(), 1);
+        assert_eq!(model.quantile_index(0.5).unwrap(), 2);
+        let err = model.quantile_index(0.25).unwrap_err();
+        assert!(matches!(err, Error::MissingQuantile(q) if q == 0.25));
+        assert!(get_test_model().quantiles().is_empty());
+
+        // the outputs are 1.5, 0.5 and 1 times `2 * confirms_in + delta_last / 100 - 0.5`
+        let mut input = ModelInput {
+            confirms_in: 6.0,
+            day_of_week: 0.0,
+            hour: 0.0,
+            delta_last: 300.0,
+            buckets: [1.0; MAX_BUCKETS],
+        };
+        let outputs = model.norm_predict_quantiles(&input).unwrap();
+        assert_eq!(outputs, vec![21.75, 7.25, 14.5]);
+        assert_eq!(model.norm_predict_input(&input).unwrap(), 14.5);
+        assert_eq!(model.norm_predict_input_f64(&input).unwrap(), 14.5);
+        // negative, the trained outputs cross and are sorted to keep q10 <= q50 <= q90
+        input.confirms_in = 0.0;
+        input.delta_last = 0.0;
+        let outputs = model.norm_predict_quantiles(&input).unwrap();
+        assert_eq!(outputs, vec![-0.25, -0.75, -0.5]);
+        assert_eq!(model.norm_predict_input(&input).unwrap(), -0.5);
+        assert_eq!(model.norm_predict_input_f64(&input).unwrap(), -0.5);
+
+        let back = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+        assert_eq!(back.quantiles(), model.quantiles());
+        assert_eq!(back.norm_predict_quantiles(&input).unwrap(), outputs);
+
+        let with_quantiles = |quantiles: &[f64]| {
+            let mut value: Value = serde_cbor::from_slice(bytes).unwrap();
+            if let Value::Map(model) = &mut value {
+                let quantiles = quantiles.iter().map(|q| Value::Float(*q)).collect();
+                model.insert(Value::Text("quantiles".into()), Value::Array(quantiles));
+            }
+            ModelData::from_slice(&serde_cbor::to_vec(&value).unwrap())
+        };
+        assert!(with_quantiles(&[0.25, 0.5, 0.75]).is_ok());
+        for quantiles in [
+            &[0.1, 0.5][..],
+            &[0.1, 0.5, 0.5],
+            &[0.0, 0.5, 0.9],
+            &[0.1, 0.5, 1.0],
+            &[0.1, 0.5, f64::NAN],
+        ]
+        .iter()
+        {
+            let err = with_quantiles(quantiles).unwrap_err();
+            assert!(matches!(err, Error::InvalidQuantiles), "{:?}", quantiles);
+        }
+        let err = with_quantiles(&[]).unwrap_err();
+        let expected = Error::DimensionMismatch {
+            layer: "dense_2/bias:0",
+            expected: 1,
+            got: 3,
+        };
+        assert_eq!(err.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn test_normalization_schemes() {
+        // inputs `a` and `b`, and the expected prediction
+        type Cases = &'static [(f32, f32, f64)];
+        let fixtures: [(&[u8], Cases); 4] = [
+            (
+                include_bytes!("../models/fixtures/norm_zscore.cbor"),
+                &[(5.0, 10.0, 6.5), (-3.0, 2.0, 0.46)],
+            ),
+            (
+                include_bytes!("../models/fixtures/norm_minmax.cbor"),
+                &[(2.0, 20.0, 2.0), (4.0, 30.0, 3.5)],
+            ),
+            (
+                include_bytes!("../models/fixtures/norm_log1p.cbor"),
+                &[
+                    (5.0, 20.0, 147.4131591025766),
+                    (1.0, 10.0, 0.6487212707001282),
+                ],
+            ),
+            (
+                include_bytes!("../models/fixtures/norm_sqrt.cbor"),
+                &[(5.0, 10.0, 42.25), (-3.0, 2.0, 0.2116)],
+            ),
+        ];
+        for (bytes, cases) in fixtures.iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let back = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+            assert_eq!(back.output_transform, model.output_transform);
+            for (a, b, expected) in cases.iter() {
+                let prediction = model.norm_predict_slice(&[*a, *b]).unwrap();
+                let error = (prediction as f64 - expected).abs() / expected;
+                assert!(error < 1e-6, "{} {}", prediction, expected);
+                assert_eq!(back.norm_predict_slice(&[*a, *b]).unwrap(), prediction);
+                let prediction = model.norm_predict_f64(&[*a as f64, *b as f64]).unwrap();
+                assert!((prediction - expected).abs() < 1e-6, "{}", prediction);
+            }
+        }
+        let model = ModelData::from_slice(fixtures[2].0).unwrap();
+        assert_eq!(model.output_transform, OutputTransform::Log1p);
+        assert_eq!(get_test_model().output_transform, OutputTransform::Identity);
+        assert_eq!(OutputTransform::Sqrt.invert(-1.0), 0.0);
+
+        let edit = |key: &str, field: Option<(&str, Value)>| {
+            let mut model: Value = serde_cbor::from_slice(fixtures[1].0).unwrap();
+            if let Value::Map(model) = &mut model {
+                let key = Value::Text(key.into());
+                match (model.get_mut(&key), field) {
+                    (Some(Value::Map(map)), Some((field, value))) => {
+                        map.insert(Value::Text(field.into()), value);
+                    }
+                    (_, None) => {
+                        model.insert(key, Value::Text("exp".into()));
+                    }
+                    _ => panic!("missing {:?}", key),
+                }
+            }
+            ModelData::from_slice(&serde_cbor::to_vec(&model).unwrap())
+        };
+        let a_zero = [(Value::Text("a".into()), Value::Float(0.0))];
+        let a_zero = Value::Map(a_zero.iter().cloned().collect());
+        let err = edit("norm", Some(("max", a_zero))).unwrap_err();
+        assert!(matches!(err, Error::InvalidNormalization(field) if field == "a"));
+        let err = edit("norm", Some(("max", Value::Map(BTreeMap::new())))).unwrap_err();
+        assert!(matches!(err, Error::InvalidNormalization(field) if field == "a"));
+        let err = edit("norm", Some(("min", Value::Map(BTreeMap::new())))).unwrap_err();
+        assert!(matches!(err, Error::MissingMeanData(field) if field == "a"));
+        let err = edit("output_transform", None).unwrap_err();
+        assert!(matches!(err, Error::Deserialize(_)));
+    }
+
+    #[test]
+    fn test_info() {
+        let mut model = get_test_model();
+        assert_eq!(model.info, ModelInfo::default());
+        assert_eq!(model.feature_names().len(), 20);
+        assert_eq!(model.feature_names()[0], "confirms_in");
+
+        model.info = ModelInfo {
+            name: Some("test".to_string()),
+            version: Some("1.0".to_string()),
+            trained_at: Some(1613708045),
+            bucket_config: Some(FeeModelConfig::default()),
+            weighted_buckets: true,
+            max_target: Some(1008),
+            output_targets: vec![],
+            max_delta_last: Some(3600),
+            feature_ranges: [("b15".to_string(), FeatureRange { min: 0.0, max: 5e3 })]
+                .iter()
+                .cloned()
+                .collect(),
+            units: [("delta_last".to_string(), "seconds".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+        };
+        let back = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+        assert_eq!(back.info, model.info);
+
+        model.info.bucket_config = None;
+        let back = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+        assert_eq!(back.info, model.info);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_json_round_trip() {
+        for bytes in MODELS.iter() {
+            let model = ModelData::from_slice(bytes).unwrap();
+            let mut json = vec![];
+            model.to_json_writer(&mut json).unwrap();
+            let back = ModelData::from_json_reader(&json[..]).unwrap();
+            assert_eq!(back.fields, model.fields);
+
+            assert_same_predictions(&model, &back);
+        }
+
+        let err = ModelData::from_json_reader(&b"{}"[..]).unwrap_err();
+        assert!(matches!(err, Error::Json(_)));
+    }
+
+    #[test]
+    fn test_bucket_count() {
+        assert_eq!(get_test_model().bucket_count(), 16);
+    }
+
+    #[test]
+    fn test_from_reader_errors() {
+        let bytes = include_bytes!("../models/test_model.cbor");
+        let err = ModelData::from_slice(&bytes[..bytes.len() / 2]).unwrap_err();
+        assert!(matches!(err, Error::Deserialize(_)));
+
+        let err = ModelData::from_slice(b"not a model").unwrap_err();
+        assert!(matches!(err, Error::Deserialize(_)));
+
+        let mut value: serde_cbor::Value = serde_cbor::from_slice(&bytes[..]).unwrap();
+        if let serde_cbor::Value::Map(model) = &mut value {
+            let weights = model.get_mut(&"weights".to_string().into()).unwrap();
+            if let serde_cbor::Value::Map(weights) = weights {
+                let bias = weights.get_mut(&"dense_1/bias:0".to_string().into());
+                if let Some(serde_cbor::Value::Array(bias)) = bias {
+                    bias.pop();
+                }
+            }
+        }
+        let bytes = serde_cbor::to_vec(&value).unwrap();
+        let err = ModelData::from_slice(&bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "dense_1/kernel:0",
+                expected: 3,
+                got: 4
+            }
+        ));
+    }
+
+    /// A reader of `bytes` failing with `kind` after `fail_after` bytes, in chunks of 7
+    struct FailingReader<'a> {
+        bytes: &'a [u8],
+        fail_after: usize,
+        kind: std::io::ErrorKind,
+    }
+
+    impl std::io::Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.fail_after == 0 {
+                return Err(std::io::Error::new(self.kind, "injected"));
+            }
+            let len = buf.len().min(7).min(self.fail_after).min(self.bytes.len());
+            buf[..len].copy_from_slice(&self.bytes[..len]);
+            self.bytes = &self.bytes[len..];
+            self.fail_after -= len;
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn test_streaming_reader() {
+        let model = get_test_model();
+        let models = [
+            model.to_vec().unwrap(),
+            model.quantize_int8().to_vec().unwrap(),
+            model.to_sparse(0.05).to_vec().unwrap(),
+        ];
+        for bytes in MODELS.iter().copied().chain(models.iter().map(|b| &b[..])) {
+            let from_slice = ModelData::from_slice(bytes).unwrap();
+            let from_reader = ModelData::from_reader(bytes).unwrap();
+            assert_eq!(from_reader.fields, from_slice.fields);
+            assert_eq!(from_reader.info, from_slice.info);
+            assert_eq!(from_reader.to_vec().unwrap(), from_slice.to_vec().unwrap());
+            assert_same_predictions(&from_slice, &from_reader);
+            #[cfg(feature = "checksum")]
+            assert_eq!(from_reader.sha256(), from_slice.sha256());
+        }
+
+        // the errors of the reader are returned as they are, wherever they happen
+        let bytes = &models[0][..];
+        for fail_after in [0, 1, 10, bytes.len() / 3, bytes.len() - 1] {
+            let reader = FailingReader {
+                bytes,
+                fail_after,
+                kind: std::io::ErrorKind::ConnectionReset,
+            };
+            match ModelData::from_reader(reader) {
+                Err(Error::Io(e)) => {
+                    assert_eq!(e.kind(), std::io::ErrorKind::ConnectionReset);
+                    assert_eq!(e.to_string(), "injected");
+                }
+                other => panic!("unexpected {:?}", other.map(|_| ())),
+            }
+        }
+        // a reader ending early is a truncated model
+        for len in [1, bytes.len() / 2, bytes.len() - 1] {
+            let err = ModelData::from_reader(&bytes[..len]).unwrap_err();
+            assert!(matches!(err, Error::Deserialize(_)), "{:?}", err);
+        }
+        // trailing bytes are rejected, like from a slice
+        let mut trailing = bytes.to_vec();
+        trailing.push(0);
+        let err = ModelData::from_reader(&trailing[..]).unwrap_err();
+        assert!(matches!(err, Error::Deserialize(_)));
+    }
+
+    #[test]
+    fn test_adversarial_models() {
+        let fixture = |bytes: &[u8]| ModelData::from_reader(bytes).unwrap_err();
+
+        let err = fixture(include_bytes!("../models/fixtures/huge_array.cbor"));
+        assert!(matches!(err, Error::Deserialize(_)));
+        let err = fixture(include_bytes!("../models/fixtures/wide_layer.cbor"));
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: "max_layer_width",
+                max: 1024,
+                got: 2000
+            }
+        ));
+        let err = fixture(include_bytes!(
+            "../models/fixtures/too_many_parameters.cbor"
+        ));
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: "max_parameters",
+                ..
+            }
+        ));
+        let err = fixture(include_bytes!("../models/fixtures/layer_mismatch.cbor"));
+        assert!(matches!(
+            err,
+            Error::DimensionMismatch {
+                layer: "dense_1/kernel:0",
+                ..
+            }
+        ));
+        let err = fixture(include_bytes!("../models/fixtures/nan_weight.cbor"));
+        assert!(matches!(err, Error::NonFiniteWeight("dense/kernel:0")));
+        let err = fixture(include_bytes!("../models/fixtures/zero_std.cbor"));
+        assert!(matches!(err, Error::InvalidNormalization(f) if f == "hour"));
+
+        let bytes = &include_bytes!("../models/test_model.cbor")[..];
+        let limits = ModelLimits {
+            max_bytes: bytes.len() - 1,
+            ..ModelLimits::default()
+        };
+        let err = ModelData::from_reader_with_limits(bytes, &limits).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: "max_bytes",
+                ..
+            }
+        ));
+        let limits = ModelLimits {
+            max_parameters: 10,
+            ..ModelLimits::default()
+        };
+        let err = ModelData::from_slice_with_limits(bytes, &limits).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::LimitExceeded {
+                limit: "max_parameters",
+                ..
+            }
+        ));
     }
 }
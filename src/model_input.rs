@@ -0,0 +1,169 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+/// Maximum number of fee buckets a [`ModelInput`] can hold, the embedded models use 16
+pub const MAX_BUCKETS: usize = 32;
+
+/// Number of values in [`ModelInput::to_array`]
+pub const INPUT_LEN: usize = 4 + MAX_BUCKETS;
+
+/// The features fed to the models
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelInput {
+    pub confirms_in: f32,
+    /// 0 is monday
+    pub day_of_week: f32,
+    pub hour: f32,
+    /// seconds elapsed since the last block
+    pub delta_last: f32,
+    /// number of transactions in every fee bucket, unused buckets are zero
+    pub buckets: [f32; MAX_BUCKETS],
+}
+
+/// A field of the model resolved to the [`ModelInput`] value it takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Feature {
+    ConfirmsIn,
+    DayOfWeek,
+    Hour,
+    DeltaLast,
+    Bucket(usize),
+}
+
+impl Feature {
+    pub(crate) fn from_name(name: &str) -> Option<Feature> {
+        match name {
+            "confirms_in" => Some(Feature::ConfirmsIn),
+            "day_of_week" => Some(Feature::DayOfWeek),
+            "hour" => Some(Feature::Hour),
+            "delta_last" => Some(Feature::DeltaLast),
+            _ => bucket_index(name)
+                .filter(|i| *i < MAX_BUCKETS)
+                .map(Feature::Bucket),
+        }
+    }
+
+    /// Position of the feature in [`ModelInput::to_array`]
+    pub(crate) fn index(&self) -> usize {
+        match self {
+            Feature::ConfirmsIn => 0,
+            Feature::DayOfWeek => 1,
+            Feature::Hour => 2,
+            Feature::DeltaLast => 3,
+            Feature::Bucket(i) => 4 + i,
+        }
+    }
+}
+
+/// Parse the index of a bucket field like `b12`
+pub(crate) fn bucket_index(name: &str) -> Option<usize> {
+    if name.len() > 1 && name.starts_with('b') && name[1..].bytes().all(|c| c.is_ascii_digit()) {
+        name[1..].parse().ok()
+    } else {
+        None
+    }
+}
+
+impl ModelInput {
+    /// The input as an array: `confirms_in`, `day_of_week`, `hour`, `delta_last`, then the buckets
+    pub fn to_array(&self) -> [f32; INPUT_LEN] {
+        let mut array = [0.0f32; INPUT_LEN];
+        array[0] = self.confirms_in;
+        array[1] = self.day_of_week;
+        array[2] = self.hour;
+        array[3] = self.delta_last;
+        array[4..].copy_from_slice(&self.buckets);
+        array
+    }
+
+    /// Names of the features of an input with `bucket_count` buckets, as named in the models, in
+    /// the order of [`ModelInput::to_array`]
+    pub fn feature_names(bucket_count: usize) -> Vec<String> {
+        let names = ["confirms_in", "day_of_week", "hour", "delta_last"];
+        let buckets = (0..bucket_count).map(|i| format!("b{}", i));
+        names.iter().map(|n| n.to_string()).chain(buckets).collect()
+    }
+
+    /// The input keyed by field name, as used by [`crate::ModelData::norm_predict`]
+    #[cfg(feature = "std")]
+    pub fn to_map(&self) -> HashMap<String, f32> {
+        let mut map = HashMap::new();
+        map.insert("confirms_in".to_string(), self.confirms_in);
+        map.insert("day_of_week".to_string(), self.day_of_week);
+        map.insert("hour".to_string(), self.hour);
+        map.insert("delta_last".to_string(), self.delta_last);
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            map.insert(format!("b{}", i), *bucket);
+        }
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model_input::{bucket_index, Feature, ModelInput, INPUT_LEN, MAX_BUCKETS};
+
+    #[test]
+    fn test_feature_from_name() {
+        assert_eq!(Feature::from_name("hour"), Some(Feature::Hour));
+        assert_eq!(Feature::from_name("b0"), Some(Feature::Bucket(0)));
+        assert_eq!(Feature::from_name("b15"), Some(Feature::Bucket(15)));
+        assert_eq!(Feature::from_name("b16"), Some(Feature::Bucket(16)));
+        assert_eq!(Feature::from_name("b32"), None);
+        assert_eq!(Feature::from_name("hours"), None);
+        assert_eq!(bucket_index("b"), None);
+        assert_eq!(bucket_index("b1x"), None);
+        assert_eq!(bucket_index("b31"), Some(31));
+        assert_eq!(MAX_BUCKETS, 32);
+    }
+
+    #[test]
+    fn test_feature_names() {
+        let names = ModelInput::feature_names(2);
+        let expected = [
+            "confirms_in",
+            "day_of_week",
+            "hour",
+            "delta_last",
+            "b0",
+            "b1",
+        ];
+        assert_eq!(names, expected);
+        for (i, name) in ModelInput::feature_names(MAX_BUCKETS).iter().enumerate() {
+            assert_eq!(Feature::from_name(name).unwrap().index(), i);
+        }
+    }
+
+    #[test]
+    fn test_to_array() {
+        let mut buckets = [0.0; MAX_BUCKETS];
+        buckets[3] = 7.0;
+        let input = ModelInput {
+            confirms_in: 1.0,
+            day_of_week: 2.0,
+            hour: 3.0,
+            delta_last: 4.0,
+            buckets,
+        };
+        let array = input.to_array();
+        assert_eq!(array.len(), INPUT_LEN);
+        let map = input.to_map();
+        for name in [
+            "confirms_in",
+            "day_of_week",
+            "hour",
+            "delta_last",
+            "b3",
+            "b15",
+            "b31",
+        ]
+        .iter()
+        {
+            let feature = Feature::from_name(name).unwrap();
+            assert_eq!(array[feature.index()], map[*name]);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! The Bitcoin networks the models are trained for, see [`crate::FeeModel::for_network`]
+
+/// A Bitcoin network, whose fee rates follow their own dynamics: a model trained on the
+/// transactions of a network estimates only the fee rates of that network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Network {
+    /// mainnet, the network of the embedded models
+    #[default]
+    Bitcoin,
+    Testnet,
+    Signet,
+    Regtest,
+}
+
+impl Network {
+    /// Whether a model trained on this network is embedded in the library
+    pub fn has_embedded_model(&self) -> bool {
+        *self == Network::Bitcoin
+    }
+}
+
+#[cfg(feature = "use-bitcoin")]
+impl From<bitcoin::Network> for Network {
+    fn from(network: bitcoin::Network) -> Self {
+        match network {
+            bitcoin::Network::Bitcoin => Network::Bitcoin,
+            bitcoin::Network::Testnet => Network::Testnet,
+            bitcoin::Network::Signet => Network::Signet,
+            bitcoin::Network::Regtest => Network::Regtest,
+        }
+    }
+}
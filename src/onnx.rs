@@ -0,0 +1,549 @@
+//! Import of the models exported as ONNX, see [`ModelData::from_onnx`].
+//!
+//! Only the fields of the protobuf messages of ONNX read by the import are decoded, by hand, so
+//! that the feature adds no dependency; unknown fields are skipped like protobuf does.
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use std::io::Read;
+
+use crate::matrix::Matrix;
+use crate::model_data::{check_finite, check_len, read_limited, FieldsDescribe, Kernel, Weights};
+use crate::validate::LAYERS;
+use crate::{Error, ModelData, ModelInfo, ModelLimits, OutputTransform};
+
+/// `data_type` of the f32 tensors
+const FLOAT: u64 = 1;
+
+/// A field of a protobuf message
+enum Value<'a> {
+    Varint(u64),
+    Fixed64,
+    Bytes(&'a [u8]),
+    Fixed32([u8; 4]),
+}
+
+/// The fields of a protobuf message as `(field number, value)`
+struct Fields<'a> {
+    bytes: &'a [u8],
+}
+
+fn invalid(reason: &'static str) -> Error {
+    Error::InvalidOnnxModel(reason)
+}
+
+fn varint(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = bytes.split_first().ok_or_else(|| invalid("truncated"))?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint too long"))
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if bytes.len() < len {
+        return Err(invalid("truncated"));
+    }
+    let (taken, rest) = bytes.split_at(len);
+    *bytes = rest;
+    Ok(taken)
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = Result<(u64, Value<'a>), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let mut field = || {
+            let key = varint(&mut self.bytes)?;
+            let value = match key & 7 {
+                0 => Value::Varint(varint(&mut self.bytes)?),
+                1 => {
+                    take(&mut self.bytes, 8)?;
+                    Value::Fixed64
+                }
+                2 => {
+                    let len = varint(&mut self.bytes)?;
+                    let len = usize::try_from(len).map_err(|_| invalid("truncated"))?;
+                    Value::Bytes(take(&mut self.bytes, len)?)
+                }
+                5 => {
+                    let bytes = take(&mut self.bytes, 4)?;
+                    Value::Fixed32([bytes[0], bytes[1], bytes[2], bytes[3]])
+                }
+                _ => return Err(invalid("wire type")),
+            };
+            Ok((key >> 3, value))
+        };
+        let result = field();
+        if result.is_err() {
+            // stop at the first error
+            self.bytes = &[];
+        }
+        Some(result)
+    }
+}
+
+fn fields(bytes: &[u8]) -> Fields<'_> {
+    Fields { bytes }
+}
+
+fn string(value: Value) -> Result<String, Error> {
+    match value {
+        Value::Bytes(bytes) => {
+            String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string not utf-8"))
+        }
+        _ => Err(invalid("string")),
+    }
+}
+
+/// The bytes of an embedded message
+fn embedded(value: Value<'_>) -> Result<&[u8], Error> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        _ => Err(invalid("message")),
+    }
+}
+
+/// An initializer, a f32 tensor with its values in row-major order
+struct Tensor {
+    dims: Vec<usize>,
+    values: Vec<f32>,
+}
+
+fn parse_tensor(message: &[u8]) -> Result<(String, Tensor), Error> {
+    let (mut name, mut dims, mut data_type) = (String::new(), vec![], 0);
+    let (mut float_data, mut raw_data) = (vec![], None);
+    for field in fields(message) {
+        match field? {
+            (1, Value::Varint(dim)) => dims.push(dim),
+            (1, Value::Bytes(mut packed)) => {
+                while !packed.is_empty() {
+                    dims.push(varint(&mut packed)?);
+                }
+            }
+            (2, Value::Varint(t)) => data_type = t,
+            (4, Value::Fixed32(bytes)) => float_data.push(f32::from_le_bytes(bytes)),
+            (4, Value::Bytes(packed)) => float_data.extend(floats(packed)?),
+            (8, value) => name = string(value)?,
+            (9, value) => raw_data = Some(floats(embedded(value)?)?),
+            _ => (),
+        }
+    }
+    if data_type != FLOAT {
+        return Err(invalid("tensor not float"));
+    }
+    let dims = dims
+        .into_iter()
+        .map(|d| usize::try_from(d).map_err(|_| invalid("tensor dims")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let values = raw_data.unwrap_or(float_data);
+    if dims.iter().product::<usize>() != values.len() {
+        return Err(invalid("tensor dims"));
+    }
+    Ok((name, Tensor { dims, values }))
+}
+
+/// Little endian f32, the layout of `raw_data`
+fn floats(bytes: &[u8]) -> Result<Vec<f32>, Error> {
+    if bytes.len() % 4 != 0 {
+        return Err(invalid("tensor data"));
+    }
+    let chunks = bytes.chunks_exact(4);
+    Ok(chunks
+        .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+        .collect())
+}
+
+struct Node {
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    op_type: String,
+    /// the int and float attributes, in f32
+    attributes: BTreeMap<String, f32>,
+}
+
+fn parse_node(message: &[u8]) -> Result<Node, Error> {
+    let mut node = Node {
+        inputs: vec![],
+        outputs: vec![],
+        op_type: String::new(),
+        attributes: BTreeMap::new(),
+    };
+    for field in fields(message) {
+        match field? {
+            (1, value) => node.inputs.push(string(value)?),
+            (2, value) => node.outputs.push(string(value)?),
+            (4, value) => node.op_type = string(value)?,
+            (5, value) => {
+                let (mut name, mut attribute) = (String::new(), None);
+                for field in fields(embedded(value)?) {
+                    match field? {
+                        (1, value) => name = string(value)?,
+                        (2, Value::Fixed32(bytes)) => attribute = Some(f32::from_le_bytes(bytes)),
+                        (3, Value::Varint(i)) => attribute = Some(i as i64 as f32),
+                        _ => (),
+                    }
+                }
+                if let Some(attribute) = attribute {
+                    node.attributes.insert(name, attribute);
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(node)
+}
+
+/// The name of a `ValueInfoProto`, the input or the output of the graph
+fn value_name(message: &[u8]) -> Result<String, Error> {
+    for field in fields(message) {
+        if let (1, value) = field? {
+            return string(value);
+        }
+    }
+    Err(invalid("value without name"))
+}
+
+struct Graph {
+    name: String,
+    nodes: Vec<Node>,
+    initializers: BTreeMap<String, Tensor>,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+}
+
+fn parse_graph(message: &[u8]) -> Result<Graph, Error> {
+    let mut graph = Graph {
+        name: String::new(),
+        nodes: vec![],
+        initializers: BTreeMap::new(),
+        inputs: vec![],
+        outputs: vec![],
+    };
+    for field in fields(message) {
+        match field? {
+            (1, value) => graph.nodes.push(parse_node(embedded(value)?)?),
+            (2, value) => graph.name = string(value)?,
+            (5, value) => {
+                let (name, tensor) = parse_tensor(embedded(value)?)?;
+                graph.initializers.insert(name, tensor);
+            }
+            (11, value) => graph.inputs.push(value_name(embedded(value)?)?),
+            (12, value) => graph.outputs.push(value_name(embedded(value)?)?),
+            _ => (),
+        }
+    }
+    // before IR version 4 the initializers are also inputs of the graph
+    let initializers = &graph.initializers;
+    graph.inputs.retain(|i| !initializers.contains_key(i));
+    Ok(graph)
+}
+
+/// A dense layer read from the graph, its bias and activation are set by the following nodes
+struct Layer {
+    /// `inputs` x `units` in row-major order
+    kernel: Vec<f32>,
+    inputs: usize,
+    units: usize,
+    bias: Option<Vec<f32>>,
+    activation: Option<&'static str>,
+}
+
+impl ModelData {
+    /// Load a model exported as ONNX, checked like the CBOR models of [`ModelData::from_reader`]
+    /// and bounded by the default [`ModelLimits`].
+    ///
+    /// The graph has a single input, whose columns are the features, and a single output,
+    /// computed by the nodes in order: optionally `Sub` and `Div` standardizing the input, then
+    /// three dense layers, each a `Gemm`, or a `MatMul` followed by an `Add`, optionally followed
+    /// by `Relu`, `Tanh` or `Sigmoid`. The other operands of the nodes are initializer tensors of
+    /// f32. Other ops error with [`Error::UnsupportedOnnxOp`], other graphs with
+    /// [`Error::InvalidOnnxModel`] and layers of inconsistent shapes with
+    /// [`Error::DimensionMismatch`] naming them like in the CBOR models.
+    ///
+    /// The columns are named by the comma separated `feature_names` of the metadata of the model,
+    /// converted to the features of the crate with the `(name, feature)` pairs of `features`,
+    /// names not in it are kept. Models without `feature_names` have the columns of `features`
+    /// in order. The graph name is the [`ModelInfo::name`].
+    pub fn from_onnx<R: Read>(reader: R, features: &[(&str, &str)]) -> Result<ModelData, Error> {
+        let limits = ModelLimits::default();
+        let bytes = read_limited(reader, &limits)?;
+        let (mut graph, mut feature_names) = (None, None);
+        for field in fields(&bytes) {
+            match field? {
+                (7, value) => graph = Some(parse_graph(embedded(value)?)?),
+                (14, value) => {
+                    let (mut key, mut value_of) = (String::new(), String::new());
+                    for field in fields(embedded(value)?) {
+                        match field? {
+                            (1, value) => key = string(value)?,
+                            (2, value) => value_of = string(value)?,
+                            _ => (),
+                        }
+                    }
+                    if key == "feature_names" {
+                        feature_names = Some(value_of);
+                    }
+                }
+                _ => (),
+            }
+        }
+        let graph = graph.ok_or_else(|| invalid("no graph"))?;
+        let (input, output) = match (graph.inputs.as_slice(), graph.outputs.as_slice()) {
+            ([input], [output]) => (input.clone(), output.clone()),
+            ([_], _) => return Err(invalid("not a single output")),
+            _ => return Err(invalid("not a single input")),
+        };
+        let fields: Vec<String> = match &feature_names {
+            Some(names) => names
+                .split(',')
+                .map(|name| {
+                    let name = name.trim();
+                    let feature = features.iter().find(|(n, _)| *n == name);
+                    feature.map_or(name, |(_, f)| f).to_string()
+                })
+                .collect(),
+            None => features.iter().map(|(_, f)| f.to_string()).collect(),
+        };
+
+        let (mut mean, mut std) = (None, None);
+        let mut layers: Vec<Layer> = vec![];
+        let mut current = input;
+        for node in graph.nodes.iter() {
+            let initializer = |name: &String| graph.initializers.get(name);
+            let operands: Vec<&Tensor> = node
+                .inputs
+                .iter()
+                .filter(|i| **i != current)
+                .map(|i| initializer(i).ok_or_else(|| invalid("operand not an initializer")))
+                .collect::<Result<_, _>>()?;
+            if node.inputs.first() != Some(&current) || node.outputs.len() != 1 {
+                return Err(invalid("nodes not in sequence"));
+            }
+            let last = layers.last_mut();
+            let op = node.op_type.as_str();
+            match (op, operands.as_slice(), last) {
+                ("Sub", [mean_of], None) if mean.is_none() => mean = Some(mean_of.values.clone()),
+                ("Div", [std_of], None) if std.is_none() => std = Some(std_of.values.clone()),
+                ("Gemm", [b, rest @ ..], _) if rest.len() <= 1 => {
+                    layers.push(gemm(node, b, rest.first().copied())?);
+                }
+                ("MatMul", [b], _) => match b.dims.as_slice() {
+                    [inputs, units] => layers.push(Layer {
+                        kernel: b.values.clone(),
+                        inputs: *inputs,
+                        units: *units,
+                        bias: None,
+                        activation: None,
+                    }),
+                    _ => return Err(invalid("MatMul operand not a matrix")),
+                },
+                ("Add", [bias], Some(layer)) if layer.bias.is_none() => {
+                    layer.bias = Some(bias.values.clone());
+                }
+                ("Relu" | "Tanh" | "Sigmoid", [], Some(layer)) if layer.activation.is_none() => {
+                    layer.activation = Some(match op {
+                        "Relu" => "relu",
+                        "Tanh" => "tanh",
+                        _ => "sigmoid",
+                    });
+                }
+                ("Sub" | "Div" | "Gemm" | "MatMul" | "Add" | "Relu" | "Tanh" | "Sigmoid", _, _) => {
+                    return Err(invalid("unsupported use of a supported op"));
+                }
+                _ => return Err(Error::UnsupportedOnnxOp(node.op_type.clone())),
+            }
+            current = node.outputs[0].clone();
+        }
+        if current != output {
+            return Err(invalid("the last node doesn't compute the output"));
+        }
+
+        check_len("layers", LAYERS.len(), layers.len())?;
+        let widths = [
+            fields.len(),
+            layers[0].units,
+            layers[1].units,
+            layers[2].units,
+        ];
+        limits.check_widths(&widths)?;
+        let mut matrices = Vec::with_capacity(LAYERS.len());
+        let mut activations = Vec::with_capacity(LAYERS.len());
+        for (i, (layer, (kernel_name, bias_name))) in layers.into_iter().zip(LAYERS).enumerate() {
+            check_len(kernel_name, widths[i], layer.inputs)?;
+            let units = layer.units;
+            let bias = layer.bias.unwrap_or_else(|| vec![0.0; units]);
+            check_len(bias_name, layer.units, bias.len())?;
+            check_finite(kernel_name, &layer.kernel)?;
+            check_finite(bias_name, &bias)?;
+            let kernel =
+                Matrix::from_buffer(layer.kernel.into_boxed_slice(), layer.units, widths[i]);
+            let bias = Matrix::from_buffer(bias.into_boxed_slice(), layer.units, 1);
+            matrices.push((Kernel::F32(kernel), bias));
+            activations.push(layer.activation.unwrap_or("identity").to_string());
+        }
+        let mut matrices = matrices.into_iter();
+        let mut next = || matrices.next().expect("three layers");
+        let ((l0_kernel, l0_bias), (l1_kernel, l1_bias), (l2_kernel, l2_bias)) =
+            (next(), next(), next());
+        let weights = Weights {
+            l0_bias,
+            l0_kernel,
+            l1_bias,
+            l1_kernel,
+            l2_bias,
+            l2_kernel,
+        };
+
+        let mean = mean.unwrap_or_else(|| vec![0.0; fields.len()]);
+        let std = std.unwrap_or_else(|| vec![1.0; fields.len()]);
+        check_len("input_norm.mean", fields.len(), mean.len())?;
+        check_len("input_norm.std", fields.len(), std.len())?;
+        let norm = |values: Vec<f32>| fields.iter().cloned().zip(values).collect();
+        let norm = FieldsDescribe::standardized(norm(mean), norm(std));
+        let metadata = ModelInfo {
+            name: Some(graph.name).filter(|n| !n.is_empty()),
+            ..ModelInfo::default()
+        };
+        ModelData::from_parts(
+            norm,
+            fields,
+            0.0,
+            Some(activations),
+            OutputTransform::default(),
+            vec![],
+            metadata,
+            weights,
+        )
+    }
+}
+
+/// The layer of a `Gemm` node computing `alpha * A * B + beta * C`, with `B` transposed with
+/// `transB`
+fn gemm(node: &Node, b: &Tensor, c: Option<&Tensor>) -> Result<Layer, Error> {
+    let attribute =
+        |name: &str, default: f32| node.attributes.get(name).copied().unwrap_or(default);
+    if attribute("transA", 0.0) != 0.0 {
+        return Err(invalid("Gemm with transA"));
+    }
+    let (rows, columns) = match b.dims.as_slice() {
+        [rows, columns] => (*rows, *columns),
+        _ => return Err(invalid("Gemm operand not a matrix")),
+    };
+    let alpha = attribute("alpha", 1.0);
+    let (inputs, units, kernel) = if attribute("transB", 0.0) != 0.0 {
+        let transposed = (0..columns)
+            .flat_map(|i| (0..rows).map(move |j| b.values[j * columns + i]))
+            .map(|v| v * alpha)
+            .collect();
+        (columns, rows, transposed)
+    } else {
+        (rows, columns, b.values.iter().map(|v| v * alpha).collect())
+    };
+    let beta = attribute("beta", 1.0);
+    let bias = c.map(|c| c.values.iter().map(|v| v * beta).collect());
+    Ok(Layer {
+        kernel,
+        inputs,
+        units,
+        bias,
+        activation: None,
+    })
+}
+
+#[cfg(test)]
+#[allow(clippy::excessive_precision)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::tests::assert_approx_eq;
+    use crate::{Activation, Error, ModelData};
+
+    const ONNX: &[u8] = include_bytes!("../models/fixtures/onnx.onnx");
+
+    const FEATURES: [(&str, &str); 4] = [
+        ("target", "confirms_in"),
+        ("since_last", "delta_last"),
+        ("dow", "day_of_week"),
+        ("hour", "hour"),
+    ];
+
+    /// The predictions printed by `models/fixtures/make_onnx.py`, writing `onnx.onnx`, for the
+    /// values of `confirms_in`, `delta_last`, `hour` and `day_of_week`
+    const EXPECTED: [([f32; 4], f32); 3] = [
+        ([1.0, 60.0, 4.0, 0.0], 13.712624851165259),
+        ([6.0, 600.0, 12.0, 3.0], 14.277525160937554),
+        ([144.0, 1800.0, 23.0, 6.0], 16.958628253584138),
+    ];
+
+    #[test]
+    fn test_from_onnx() {
+        let model = ModelData::from_onnx(ONNX, &FEATURES).unwrap();
+        assert_eq!(
+            model.fields,
+            vec!["confirms_in", "delta_last", "hour", "day_of_week"]
+        );
+        assert_eq!(model.info.name.as_deref(), Some("onnx-example"));
+        let activations = &model.activations;
+        assert_eq!(activations[0], Activation::Relu);
+        assert_eq!(activations[1], Activation::Sigmoid);
+        assert_eq!(activations[2], Activation::Identity);
+        let layers: Vec<_> = model
+            .layers()
+            .map(|l| (l.input_dim, l.output_dim))
+            .collect();
+        assert_eq!(layers, vec![(4, 3), (3, 2), (2, 1)]);
+        for (values, expected) in EXPECTED.iter() {
+            let input: HashMap<String, f32> = model
+                .fields
+                .iter()
+                .cloned()
+                .zip(values.iter().copied())
+                .collect();
+            assert_approx_eq(model.norm_predict(&input).unwrap(), *expected);
+        }
+        let cbor = ModelData::from_slice(&model.to_vec().unwrap()).unwrap();
+        crate::model_data::tests::assert_same_predictions(&model, &cbor);
+
+        // the names not in the table are kept
+        let model = ModelData::from_onnx(ONNX, &FEATURES[..2]).unwrap();
+        assert_eq!(model.fields[3], "dow");
+    }
+
+    #[test]
+    fn test_onnx_errors() {
+        let replace = |from: &[u8], to: &[u8]| {
+            assert_eq!(from.len(), to.len());
+            let at = ONNX.windows(from.len()).position(|w| w == from).unwrap();
+            let mut bytes = ONNX.to_vec();
+            bytes[at..at + to.len()].copy_from_slice(to);
+            bytes
+        };
+        let load = |bytes: &[u8]| ModelData::from_onnx(bytes, &FEATURES);
+
+        let err = load(&replace(b"Sigmoid", b"Softmax")).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedOnnxOp(ref op) if op == "Softmax"));
+        assert_eq!(err.to_string(), "Unsupported ONNX op Softmax");
+        // a layer without inputs from the previous one
+        let err = load(&replace(b"\x0a\x04relu", b"\x0a\x04mean")).unwrap_err();
+        assert!(matches!(err, Error::InvalidOnnxModel(_)));
+        let err = load(&ONNX[..ONNX.len() - 3]).unwrap_err();
+        assert!(matches!(err, Error::InvalidOnnxModel("truncated")));
+        // three columns named in the metadata
+        let err = load(&replace(b"target,", b"target ")).unwrap_err();
+        assert!(matches!(err, Error::DimensionMismatch { .. }), "{:?}", err);
+        let err = ModelData::from_onnx(&b"\x3a\x00"[..], &FEATURES).unwrap_err();
+        assert!(matches!(err, Error::InvalidOnnxModel("not a single input")));
+    }
+}
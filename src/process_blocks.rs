@@ -2,6 +2,7 @@ use crate::Error;
 use bitcoin::{Block, Transaction, Txid};
 use std::collections::HashMap;
 
+#[derive(Debug, Clone)]
 pub struct Transactions {
     txs: HashMap<Txid, Transaction>, // TODO use &Transaction to avoid clones
     txs_output_values: HashMap<Txid, OutputValues>,
@@ -17,6 +18,20 @@ pub fn process_blocks(blocks: &[bitcoin::Block; 10]) -> Result<(Vec<f64>, u32),
     Ok((fee_rates, last_block_ts))
 }
 
+/// Fee rates of the transactions of `blocks`, ordered from the oldest to the newest, with every
+/// input in the given blocks, and the timestamp of the newest block. Unlike [`process_blocks`]
+/// any number of blocks is accepted and they are not checked to be connected.
+pub fn fee_rates_from_blocks(blocks: &[Block]) -> (Vec<f64>, u32) {
+    let txs = blocks
+        .iter()
+        .flat_map(|block| block.txdata.iter())
+        .map(|tx| (tx.txid(), tx.clone()))
+        .collect();
+    let last_block_ts = blocks.last().map_or(0, |block| block.header.time);
+    let txs = Transactions::from_txs(txs, last_block_ts);
+    (txs.fee_rates(), last_block_ts)
+}
+
 impl Transactions {
     pub fn from_blocks(blocks: &[Block; 10]) -> Result<Self, Error> {
         let mut prev = blocks[0].header.block_hash();
@@ -37,10 +52,7 @@ impl Transactions {
             }
         }
 
-        Ok(Self::from_txs(
-            txs,
-            time.ok_or_else(|| Error::LastTsMissing)?,
-        ))
+        Ok(Self::from_txs(txs, time.ok_or(Error::LastTsMissing)?))
     }
     pub fn from_txs(txs: HashMap<Txid, Transaction>, last_block_ts: u32) -> Self {
         let mut txs_output_values: HashMap<Txid, OutputValues> = HashMap::new();
@@ -71,9 +83,9 @@ impl Transactions {
         let mut sum_inputs: u64 = 0;
         for input in tx.input.iter() {
             let outputs_values = self.txs_output_values.get(&input.previous_output.txid)?;
-            sum_inputs += outputs_values[input.previous_output.vout as usize];
+            sum_inputs += outputs_values.get(input.previous_output.vout as usize)?;
         }
-        Some(sum_inputs - sum_outputs)
+        sum_inputs.checked_sub(sum_outputs)
     }
 
     pub fn last_block_ts(&self) -> u32 {
@@ -82,13 +94,80 @@ impl Transactions {
 }
 
 #[cfg(test)]
-mod tests {
-    use super::process_blocks;
+pub mod tests {
+    use super::{fee_rates_from_blocks, process_blocks};
     use crate::Error;
     use bitcoin::blockdata::constants::genesis_block;
-    use bitcoin::{Block, Network};
+    use bitcoin::hash_types::TxMerkleNode;
+    use bitcoin::{
+        Block, BlockHash, BlockHeader, Network, OutPoint, Script, Transaction, TxIn, TxOut, Txid,
+    };
     use std::convert::TryInto;
 
+    pub fn make_tx(inputs: &[(Txid, u32)], outputs: &[u64]) -> Transaction {
+        Transaction {
+            version: 2,
+            lock_time: 0,
+            input: inputs
+                .iter()
+                .map(|(txid, vout)| TxIn {
+                    previous_output: OutPoint::new(*txid, *vout),
+                    script_sig: Script::new(),
+                    sequence: 0xffffffff,
+                    witness: vec![],
+                })
+                .collect(),
+            output: outputs
+                .iter()
+                .map(|value| TxOut {
+                    value: *value,
+                    script_pubkey: Script::new(),
+                })
+                .collect(),
+        }
+    }
+
+    /// A coinbase paying `value`, different values give different txids
+    pub fn make_coinbase(value: u64) -> Transaction {
+        let mut tx = make_tx(&[(Txid::default(), u32::MAX)], &[value]);
+        tx.input[0].script_sig = Script::from(vec![1, 2]);
+        tx
+    }
+
+    pub fn make_block(prev_blockhash: BlockHash, time: u32, txdata: Vec<Transaction>) -> Block {
+        Block {
+            header: BlockHeader {
+                version: 1,
+                prev_blockhash,
+                merkle_root: TxMerkleNode::default(),
+                time,
+                bits: 0x207fffff,
+                nonce: 0,
+            },
+            txdata,
+        }
+    }
+
+    /// `n` connected blocks with a coinbase and, from the second one, a transaction spending the
+    /// coinbase of the previous block paying 1000 sat of fee
+    pub fn make_chain(n: usize) -> Vec<Block> {
+        let mut blocks: Vec<Block> = vec![];
+        for i in 0..n {
+            let prev = blocks.last().map(|b| b.block_hash()).unwrap_or_default();
+            let mut txs = vec![make_coinbase(50_000 + i as u64)];
+            if let Some(prev_block) = blocks.last() {
+                let prev_coinbase = &prev_block.txdata[0];
+                let fee_paying = make_tx(
+                    &[(prev_coinbase.txid(), 0)],
+                    &[prev_coinbase.output[0].value - 1000],
+                );
+                txs.push(fee_paying);
+            }
+            blocks.push(make_block(prev, 1_600_000_000 + 600 * i as u32, txs));
+        }
+        blocks
+    }
+
     #[test]
     fn test_blocks() {
         let block = genesis_block(Network::Bitcoin);
@@ -111,4 +190,43 @@ mod tests {
         blocks[0].txdata.push(tx);
         process_blocks(&blocks).unwrap();
     }
+
+    #[test]
+    fn test_fee_rates_from_blocks() {
+        assert_eq!(fee_rates_from_blocks(&[]), (vec![], 0));
+
+        let coinbase = make_coinbase(100_000);
+        let first = make_block(BlockHash::default(), 1_600_000_000, vec![coinbase.clone()]);
+
+        // legacy, 60 vbytes, paying 600 sat
+        let legacy = make_tx(&[(coinbase.txid(), 0)], &[99_400]);
+        // segwit, 79 vbytes, paying 790 sat
+        let mut segwit = make_tx(&[(legacy.txid(), 0)], &[98_610]);
+        segwit.input[0].witness = vec![vec![0; 72]];
+        // zero fee
+        let zero_fee = make_tx(&[(segwit.txid(), 0)], &[98_610]);
+        // spending an output created before the window
+        let outside = make_tx(&[(Txid::default(), 0)], &[1_000]);
+        // spending a non existing output
+        let wrong_vout = make_tx(&[(zero_fee.txid(), 1)], &[1_000]);
+        // spending more than its inputs
+        let negative_fee = make_tx(&[(zero_fee.txid(), 0)], &[200_000]);
+        let txs = vec![
+            make_coinbase(200_000),
+            legacy.clone(),
+            segwit,
+            zero_fee,
+            outside,
+            wrong_vout,
+            negative_fee,
+            // duplicated txid
+            legacy,
+        ];
+        let second = make_block(first.block_hash(), 1_600_000_600, txs);
+
+        let (mut rates, ts) = fee_rates_from_blocks(&[first, second]);
+        rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(ts, 1_600_000_600);
+        assert_eq!(rates, vec![0.0, 10.0, 10.0]);
+    }
 }
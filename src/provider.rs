@@ -0,0 +1,141 @@
+//! Inputs of the models from any source of fee rates, see [`FeatureProvider`]
+
+use alloc::vec::Vec;
+
+use crate::{Error, FeeBuckets, FeeWindow, HISTOGRAM_TX_VSIZE};
+
+/// A source of the inputs of the models: the fee buckets of the transactions and the timestamp of
+/// the last block, see [`crate::FeeModel::estimate_from`]
+pub trait FeatureProvider {
+    /// The transactions counted in the buckets of `fee_buckets`, like [`FeeBuckets::get`]
+    fn buckets(&self, fee_buckets: &FeeBuckets) -> Result<Vec<u64>, Error>;
+
+    /// unix timestamp in seconds of the last block
+    fn last_block_ts(&self) -> i64;
+
+    /// The fee rates of the transactions, for the models using the [`crate::SUMMARY_FEATURES`],
+    /// which are zero without them
+    fn fee_rates(&self) -> Option<&[f64]> {
+        None
+    }
+}
+
+/// The fee rates of the transactions, like the input of [`crate::FeeModel::estimate`]
+impl FeatureProvider for FeeWindow {
+    fn buckets(&self, fee_buckets: &FeeBuckets) -> Result<Vec<u64>, Error> {
+        if self.fee_rates.iter().any(|rate| !rate.is_finite()) {
+            return Err(Error::InvalidInput("fee_rates"));
+        }
+        Ok(fee_buckets.get(&self.fee_rates))
+    }
+
+    fn last_block_ts(&self) -> i64 {
+        self.last_block_ts
+    }
+
+    fn fee_rates(&self) -> Option<&[f64]> {
+        Some(&self.fee_rates)
+    }
+}
+
+/// A mempool fee histogram, like the input of [`crate::FeeModel::estimate_from_histogram`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeHistogram<'a> {
+    /// `(fee_rate, vsize)` pairs, the vsize in every bucket is converted to the number of
+    /// transactions of [`HISTOGRAM_TX_VSIZE`] vB
+    pub histogram: &'a [(f64, u64)],
+    /// unix timestamp in seconds
+    pub last_block_ts: i64,
+}
+
+impl FeatureProvider for FeeHistogram<'_> {
+    fn buckets(&self, fee_buckets: &FeeBuckets) -> Result<Vec<u64>, Error> {
+        if self.histogram.iter().any(|(rate, _)| !rate.is_finite()) {
+            return Err(Error::InvalidInput("histogram"));
+        }
+        Ok(fee_buckets.get_weighted(self.histogram, HISTOGRAM_TX_VSIZE))
+    }
+
+    fn last_block_ts(&self) -> i64 {
+        self.last_block_ts
+    }
+}
+
+/// The transactions of the blocks, like the result of [`crate::process_blocks()`]
+#[cfg(feature = "use-bitcoin")]
+impl FeatureProvider for crate::process_blocks::Transactions {
+    fn buckets(&self, fee_buckets: &FeeBuckets) -> Result<Vec<u64>, Error> {
+        Ok(fee_buckets.get(&self.fee_rates()))
+    }
+
+    fn last_block_ts(&self) -> i64 {
+        crate::process_blocks::Transactions::last_block_ts(self) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use crate::provider::{FeatureProvider, FeeHistogram};
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::{Error, FeeBuckets, FeeWindow};
+
+    /// Transactions at the same fee rate
+    struct Flat {
+        rate: f64,
+        count: usize,
+        last_block_ts: i64,
+    }
+
+    impl FeatureProvider for Flat {
+        fn buckets(&self, fee_buckets: &FeeBuckets) -> Result<Vec<u64>, Error> {
+            Ok(fee_buckets.get(&alloc::vec![self.rate; self.count]))
+        }
+
+        fn last_block_ts(&self) -> i64 {
+            self.last_block_ts
+        }
+    }
+
+    #[test]
+    fn test_estimate_from() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let window = FeeWindow {
+            fee_rates: rates.clone(),
+            last_block_ts: ts - 300,
+        };
+        for target in [1u16, 2, 6, 144] {
+            let estimate = model.estimate_from(&window, target, Some(ts)).unwrap();
+            let expected = model.estimate(target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(estimate, expected);
+        }
+
+        let flat = Flat {
+            rate: 12.0,
+            count: 500,
+            last_block_ts: ts - 600,
+        };
+        let estimate = model.estimate_from(&flat, 6, Some(ts)).unwrap();
+        let expected = model.estimate(6, Some(ts), &[12.0; 500], ts - 600).unwrap();
+        assert_eq!(estimate, expected);
+
+        let histogram = [(30.0, 50_000), (10.0, 200_000), (2.0, 1_000_000)];
+        let provider = FeeHistogram {
+            histogram: &histogram,
+            last_block_ts: ts - 300,
+        };
+        let estimate = model.estimate_from(&provider, 6, Some(ts)).unwrap();
+        let expected = model.estimate_from_histogram(6, Some(ts), &histogram, ts - 300);
+        assert_eq!(estimate, expected.unwrap());
+
+        let invalid = FeeWindow {
+            fee_rates: alloc::vec![f64::NAN],
+            last_block_ts: ts - 300,
+        };
+        let err = model.estimate_from(&invalid, 6, Some(ts));
+        assert!(matches!(err, Err(Error::InvalidInput("fee_rates"))));
+    }
+}
@@ -0,0 +1,236 @@
+//! Fee advice for a PSBT before it is signed, see [`crate::FeeModel::fee_for_psbt`]
+
+use bitcoin::consensus::encode::{serialize, VarInt};
+use bitcoin::util::psbt::{Input, PartiallySignedTransaction};
+use bitcoin::{Script, TxIn};
+
+/// The fee recommended for a PSBT once signed, see [`crate::FeeModel::fee_for_psbt`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PsbtFeeAdvice {
+    /// in satoshi, rounded like in [`crate::FeeModel::estimate_fee`]
+    pub fee: u64,
+    /// estimated vsize of the signed transaction, see [`crate::FeeModel::fee_for_psbt`]
+    pub vsize: u64,
+    /// the estimated fee rate in sat/vB
+    pub rate: f32,
+    /// inputs of unknown kind, counted as the largest known kind
+    pub unknown_inputs: usize,
+}
+
+/// Size of an ECDSA signature in DER with the sighash byte, at most 72 bytes
+const ECDSA_SIGNATURE: usize = 72;
+/// Size of a schnorr signature with a sighash byte, 64 bytes with the default sighash
+const SCHNORR_SIGNATURE: usize = 65;
+/// Size of a compressed public key
+const PUBLIC_KEY: usize = 33;
+
+/// The sizes in bytes of the script sig and of the witness, including the number of its items,
+/// of a signed input
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct InputSize {
+    script_sig: usize,
+    witness: usize,
+}
+
+/// A p2pkh input with a compressed key, the largest of the known kinds
+const P2PKH: InputSize = InputSize {
+    script_sig: 1 + ECDSA_SIGNATURE + 1 + PUBLIC_KEY,
+    witness: 0,
+};
+
+const P2WPKH: InputSize = InputSize {
+    script_sig: 0,
+    witness: 1 + 1 + ECDSA_SIGNATURE + 1 + PUBLIC_KEY,
+};
+
+/// Spending the output key with a signature, without annex
+const P2TR_KEYPATH: InputSize = InputSize {
+    script_sig: 0,
+    witness: 1 + 1 + SCHNORR_SIGNATURE,
+};
+
+/// The script sig pushes the 22 bytes p2wpkh redeem script
+const P2SH_P2WPKH: InputSize = InputSize {
+    script_sig: 1 + 22,
+    witness: P2WPKH.witness,
+};
+
+/// Estimate the weight of the transaction of `psbt` once signed, and the number of inputs whose
+/// kind is unknown, see [`crate::FeeModel::fee_for_psbt`]
+pub(crate) fn estimate_signed_weight(psbt: &PartiallySignedTransaction) -> (u64, usize) {
+    let tx = &psbt.global.unsigned_tx;
+    // version, locktime, inputs with empty script sig and outputs
+    let mut weight = 4 * serialize(tx).len();
+    let mut unknown_inputs = 0;
+    let mut witnesses = 0;
+    let mut has_witness = false;
+    for (i, txin) in tx.input.iter().enumerate() {
+        let size = match psbt.inputs.get(i).and_then(|input| input_size(input, txin)) {
+            Some(size) => size,
+            None => {
+                unknown_inputs += 1;
+                P2PKH
+            }
+        };
+        // the empty script sig is already counted, with its length
+        weight += 4 * (size.script_sig + VarInt(size.script_sig as u64).len() - 1);
+        if size.witness > 0 {
+            has_witness = true;
+            witnesses += size.witness;
+        } else {
+            // the number of items of the empty witness
+            witnesses += 1;
+        }
+    }
+    if has_witness {
+        // the segwit marker and flag
+        weight += 2 + witnesses;
+    }
+    (weight as u64, unknown_inputs)
+}
+
+fn input_size(input: &Input, txin: &TxIn) -> Option<InputSize> {
+    if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+        let script_sig = input.final_script_sig.as_ref().map_or(0, Script::len);
+        let witness = input.final_script_witness.as_ref().map_or(0, |items| {
+            let items_len = items
+                .iter()
+                .map(|item| VarInt(item.len() as u64).len() + item.len());
+            VarInt(items.len() as u64).len() + items_len.sum::<usize>()
+        });
+        return Some(InputSize {
+            script_sig,
+            witness,
+        });
+    }
+    let spent = match (&input.witness_utxo, &input.non_witness_utxo) {
+        (Some(output), _) => output.script_pubkey.clone(),
+        (None, Some(tx)) => {
+            let vout = txin.previous_output.vout as usize;
+            tx.output.get(vout)?.script_pubkey.clone()
+        }
+        (None, None) => return None,
+    };
+    if spent.is_p2pkh() {
+        Some(P2PKH)
+    } else if spent.is_v0_p2wpkh() {
+        Some(P2WPKH)
+    } else if is_p2tr(&spent) {
+        Some(P2TR_KEYPATH)
+    } else if spent.is_p2sh() && input.redeem_script.as_ref()?.is_v0_p2wpkh() {
+        Some(P2SH_P2WPKH)
+    } else {
+        None
+    }
+}
+
+/// Witness version 1 with a 32 bytes program
+fn is_p2tr(script: &Script) -> bool {
+    let bytes = script.as_bytes();
+    bytes.len() == 34 && bytes[0] == 0x51 && bytes[1] == 0x20
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::util::psbt::PartiallySignedTransaction;
+    use bitcoin::{Script, Transaction, TxOut};
+
+    use crate::process_blocks::tests::make_tx;
+    use crate::psbt::estimate_signed_weight;
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+
+    fn script(prefix: &[u8], len: usize, suffix: &[u8]) -> Script {
+        let mut bytes = prefix.to_vec();
+        bytes.resize(prefix.len() + len, 7);
+        bytes.extend_from_slice(suffix);
+        Script::from(bytes)
+    }
+
+    fn p2wpkh() -> Script {
+        script(&[0x00, 0x14], 20, &[])
+    }
+
+    /// An unsigned transaction with `inputs.len()` inputs and two p2wpkh outputs, and its PSBT
+    /// spending `inputs`
+    fn unsigned(inputs: &[Script]) -> (Transaction, PartiallySignedTransaction) {
+        let prevouts: Vec<_> = (0..inputs.len() as u32)
+            .map(|vout| (Default::default(), vout))
+            .collect();
+        let mut tx = make_tx(&prevouts, &[10_000, 20_000]);
+        for output in tx.output.iter_mut() {
+            output.script_pubkey = p2wpkh();
+        }
+        let mut psbt = PartiallySignedTransaction::from_unsigned_tx(tx.clone()).unwrap();
+        for (input, script_pubkey) in psbt.inputs.iter_mut().zip(inputs) {
+            input.witness_utxo = Some(TxOut {
+                value: 50_000,
+                script_pubkey: script_pubkey.clone(),
+            });
+        }
+        (tx, psbt)
+    }
+
+    #[test]
+    fn test_estimate_signed_weight() {
+        let p2tr = script(&[0x51, 0x20], 32, &[]);
+        let p2pkh = script(&[0x76, 0xa9, 0x14], 20, &[0x88, 0xac]);
+        let p2sh = script(&[0xa9, 0x14], 20, &[0x87]);
+        let (mut signed, mut psbt) = unsigned(&[p2wpkh(), p2tr, p2sh, p2pkh]);
+        psbt.inputs[2].redeem_script = Some(p2wpkh());
+        // signed with the common 71 bytes ECDSA and 64 bytes schnorr signatures
+        let ecdsa = vec![vec![1; 71], vec![2; 33]];
+        signed.input[0].witness = ecdsa.clone();
+        signed.input[1].witness = vec![vec![3; 64]];
+        signed.input[2].script_sig = script(&[0x16], 22, &[]);
+        signed.input[2].witness = ecdsa;
+        signed.input[3].script_sig = Script::from([&[71][..], &[1; 71], &[33], &[2; 33]].concat());
+        let actual = signed.get_weight() as u64;
+
+        let (weight, unknown) = estimate_signed_weight(&psbt);
+        assert_eq!(unknown, 0);
+        // one byte more of every signature
+        assert_eq!(weight, actual + 1 + 1 + 1 + 4);
+        assert_eq!(weight.div_ceil(4), 438);
+
+        // finalized inputs have their exact size
+        psbt.inputs[3].witness_utxo = None;
+        assert_eq!(estimate_signed_weight(&psbt), (weight, 1));
+        psbt.inputs[3].final_script_sig = Some(signed.input[3].script_sig.clone());
+        psbt.inputs[1].final_script_witness = Some(signed.input[1].witness.clone());
+        assert_eq!(estimate_signed_weight(&psbt), (weight - 4 - 1, 0));
+    }
+
+    #[test]
+    fn test_unknown_inputs() {
+        // a p2wsh multisig, counted as p2pkh, and a single p2wpkh input
+        let p2wsh = script(&[0x00, 0x20], 32, &[]);
+        let (mut signed, psbt) = unsigned(&[p2wsh, p2wpkh()]);
+        signed.input[0].witness = vec![vec![], vec![1; 71], vec![2; 71], vec![3; 105]];
+        signed.input[1].witness = vec![vec![1; 71], vec![2; 33]];
+        let actual = signed.get_weight() as u64;
+        let (weight, unknown) = estimate_signed_weight(&psbt);
+        assert_eq!(unknown, 1);
+        // never less than the actual one, 45 vB more for the 2-of-3 multisig
+        assert!(
+            weight >= actual && weight <= actual + 45 * 4,
+            "{} {}",
+            weight,
+            actual
+        );
+
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model();
+        let advice = model
+            .fee_for_psbt(&psbt, 6, Some(ts), &rates, ts - 300)
+            .unwrap();
+        assert_eq!(advice.vsize, weight.div_ceil(4));
+        assert_eq!(advice.unknown_inputs, 1);
+        assert_eq!(
+            advice.rate,
+            model.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        );
+        let fee = model.estimate_fee(6, Some(ts), &rates, ts - 300, weight);
+        assert_eq!(advice.fee, fee.unwrap());
+    }
+}
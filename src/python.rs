@@ -0,0 +1,59 @@
+//! Python bindings, built with `maturin build`
+
+use std::path::PathBuf;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+
+use crate::{Error, FeeModel};
+
+fn to_py(e: Error) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// A [`FeeModel`] usable from python
+#[pyclass(name = "FeeModel", frozen)]
+struct PyFeeModel {
+    inner: FeeModel,
+}
+
+#[pymethods]
+impl PyFeeModel {
+    /// Create the fee model using the models embedded in the library
+    #[new]
+    fn new() -> PyResult<Self> {
+        let inner = FeeModel::new().map_err(to_py)?;
+        Ok(PyFeeModel { inner })
+    }
+
+    /// Create the fee model from CBOR files on the filesystem
+    #[classmethod]
+    fn from_files(
+        _cls: &Bound<'_, PyType>,
+        low_path: PathBuf,
+        high_path: PathBuf,
+    ) -> PyResult<Self> {
+        let inner = FeeModel::from_paths(&low_path, &high_path).map_err(to_py)?;
+        Ok(PyFeeModel { inner })
+    }
+
+    /// Like [`FeeModel::estimate`], `fee_rates` is any sequence of floats
+    #[pyo3(signature = (block_target, timestamp=None, *, fee_rates, last_block_ts))]
+    fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: Vec<f64>,
+        last_block_ts: i64,
+    ) -> PyResult<f32> {
+        self.inner
+            .estimate(block_target, timestamp, &fee_rates, last_block_ts)
+            .map_err(to_py)
+    }
+}
+
+#[pymodule]
+fn bitcoin_fee_model(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFeeModel>()
+}
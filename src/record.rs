@@ -0,0 +1,228 @@
+//! Recording of the estimates to replay them against another model, see [`InputRecorder`]
+
+use std::io::{BufRead, Write};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, FeeModel};
+
+/// The inputs and the output of an estimate of [`InputRecorder`], a line of JSON
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCall {
+    pub block_target: u16,
+    /// unix timestamp in seconds, the time of the clock if none was given
+    pub timestamp: i64,
+    /// the fee rates of [`InputRecorder::estimate`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_rates: Option<Vec<f64>>,
+    /// the fee buckets of [`InputRecorder::estimate_with_buckets`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_buckets: Option<Vec<u64>>,
+    pub last_block_ts: i64,
+    /// the estimate, `None` if it errored
+    pub output: Option<f32>,
+}
+
+impl RecordedCall {
+    /// Estimate the call with `model`
+    pub fn estimate(&self, model: &FeeModel) -> Result<f32, Error> {
+        let timestamp = Some(self.timestamp);
+        match (&self.fee_rates, &self.fee_buckets) {
+            (Some(fee_rates), _) => {
+                model.estimate(self.block_target, timestamp, fee_rates, self.last_block_ts)
+            }
+            (None, Some(fee_buckets)) => model.estimate_with_buckets(
+                self.block_target,
+                timestamp,
+                fee_buckets,
+                self.last_block_ts,
+            ),
+            (None, None) => Err(Error::InvalidInput("fee_rates")),
+        }
+    }
+}
+
+/// A [`FeeModel`] writing the inputs and the output of every estimate to `writer`, as a
+/// [`RecordedCall`] in a line of JSON, to [`replay`] them later, for example against a newer
+/// model.
+///
+/// Estimates at the time of the clock use it truncated to the second, the recorded timestamp, so
+/// that they replay exactly.
+#[derive(Debug)]
+pub struct InputRecorder<W> {
+    model: FeeModel,
+    writer: Mutex<W>,
+}
+
+impl<W: Write> InputRecorder<W> {
+    pub fn new(model: FeeModel, writer: W) -> Self {
+        InputRecorder {
+            model,
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn model(&self) -> &FeeModel {
+        &self.model
+    }
+
+    /// The model and the writer, with every recorded call
+    pub fn into_inner(self) -> (FeeModel, W) {
+        let writer = self.writer.into_inner().unwrap_or_else(|e| e.into_inner());
+        (self.model, writer)
+    }
+
+    /// Like [`FeeModel::estimate`], recording the call. Errors also if the call can't be written.
+    pub fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let call = RecordedCall {
+            block_target,
+            timestamp: self.timestamp(timestamp)?,
+            fee_rates: Some(fee_rates.to_vec()),
+            fee_buckets: None,
+            last_block_ts,
+            output: None,
+        };
+        self.record(call)
+    }
+
+    /// Like [`FeeModel::estimate_with_buckets`], recording the call. Errors also if the call
+    /// can't be written.
+    pub fn estimate_with_buckets(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_buckets: &[u64],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        let call = RecordedCall {
+            block_target,
+            timestamp: self.timestamp(timestamp)?,
+            fee_rates: None,
+            fee_buckets: Some(fee_buckets.to_vec()),
+            last_block_ts,
+            output: None,
+        };
+        self.record(call)
+    }
+
+    fn timestamp(&self, timestamp: Option<i64>) -> Result<i64, Error> {
+        match timestamp {
+            Some(timestamp) => Ok(timestamp),
+            None => Ok(self.model.now()?.div_euclid(1000)),
+        }
+    }
+
+    fn record(&self, mut call: RecordedCall) -> Result<f32, Error> {
+        let result = call.estimate(&self.model);
+        call.output = result.as_ref().ok().copied();
+        let mut line = serde_json::to_vec(&call)?;
+        line.push(b'\n');
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer.write_all(&line)?;
+        result
+    }
+}
+
+/// A replayed call whose estimate differs from the recorded one, see [`replay`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayDiff {
+    /// the line of the call, starting from 1
+    pub line: usize,
+    pub call: RecordedCall,
+    /// the estimate of the model of the replay, `None` if it errored
+    pub output: Option<f32>,
+}
+
+/// Estimate every call recorded by [`InputRecorder`] in `reader` with `model`, returns the ones
+/// whose estimate differs by more than `threshold` sat/vB from the recorded one, or that errored
+/// only once between the recording and the replay. Empty lines are skipped, errors with
+/// [`Error::Json`] if a line is not a [`RecordedCall`].
+pub fn replay<R: BufRead>(
+    reader: R,
+    model: &FeeModel,
+    threshold: f32,
+) -> Result<Vec<ReplayDiff>, Error> {
+    let mut diffs = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let call: RecordedCall = serde_json::from_str(&line)?;
+        let output = call.estimate(model).ok();
+        let differs = match (call.output, output) {
+            (Some(recorded), Some(replayed)) => (recorded - replayed).abs() > threshold,
+            (recorded, replayed) => recorded.is_some() != replayed.is_some(),
+        };
+        if differs {
+            diffs.push(ReplayDiff {
+                line: i + 1,
+                call,
+                output,
+            });
+        }
+    }
+    Ok(diffs)
+}
+
+#[cfg(all(test, feature = "chrono"))]
+mod tests {
+    use std::io::Cursor;
+
+    use chrono::{TimeZone, Utc};
+
+    use crate::record::{replay, InputRecorder, RecordedCall};
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::EstimateOptions;
+
+    #[test]
+    fn test_record_replay() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model =
+            get_fee_model().with_clock(move || Utc.timestamp_millis_opt(ts * 1000 + 999).unwrap());
+        let recorder = InputRecorder::new(model, vec![]);
+        let first = recorder.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        let buckets = recorder.model().fee_buckets().get(&rates);
+        let second = recorder
+            .estimate_with_buckets(1, None, &buckets, ts - 600)
+            .unwrap();
+        assert!(recorder.estimate(6, Some(ts), &[], ts - 300).is_err());
+        let (model, recorded) = recorder.into_inner();
+
+        let lines: Vec<RecordedCall> = recorded
+            .split(|b| *b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].output, Some(first));
+        assert_eq!(lines[1].fee_buckets.as_deref(), Some(&buckets[..]));
+        // the clock truncated to the second
+        assert_eq!(lines[1].timestamp, ts);
+        assert_eq!(lines[1].output, Some(second));
+        assert_eq!(lines[2].output, None);
+
+        let diffs = replay(Cursor::new(&recorded), &model, 0.0).unwrap();
+        assert!(diffs.is_empty(), "{:?}", diffs);
+
+        // a model with a higher minimum differs in the estimates lower than it
+        let options = EstimateOptions {
+            min_fee_rate: 40.0,
+            ..Default::default()
+        };
+        let raised = model.with_options(options);
+        let diffs = replay(Cursor::new(&recorded), &raised, 0.5).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].line, 1);
+        assert_eq!(diffs[0].output, Some(40.0));
+        assert!(replay(Cursor::new("{\n"), &raised, 0.5).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Models deployed in a directory with a manifest, see [`crate::FeeModel::from_directory`]
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{load_model_file, Error, ModelData};
+
+/// Name of the manifest file in the directory of the models
+pub(crate) const MANIFEST: &str = "manifest.json";
+
+/// The `manifest.json` in the directory of the models, like:
+///
+/// ```json
+/// {
+///   "models": [
+///     { "name": "20211027-180849", "min_target": 1, "max_target": 2 },
+///     { "name": "20211027-180925", "min_target": 3, "max_target": 1008 }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    /// by increasing targets, covering every target from 1 to the `max_target` of the last one
+    models: Vec<Entry>,
+}
+
+/// A model at `<name>/model.cbor` in the directory, trained for the targets from `min_target` to
+/// `max_target` included
+#[derive(Debug, Deserialize)]
+struct Entry {
+    name: String,
+    min_target: u16,
+    max_target: u16,
+}
+
+impl Manifest {
+    /// Check the targets of the entries are contiguous from 1, following each other without gaps
+    /// or overlaps
+    fn check_ranges(&self) -> Result<(), Error> {
+        let mut expected = 1u16;
+        for entry in self.models.iter() {
+            let model = entry.name.clone();
+            if entry.min_target > entry.max_target {
+                return Err(Error::EmptyTier {
+                    model,
+                    min_target: entry.min_target,
+                    max_target: entry.max_target,
+                });
+            }
+            if entry.min_target < expected && expected > 1 {
+                return Err(Error::TierOverlap {
+                    model,
+                    min_target: entry.min_target,
+                    previous_max: expected - 1,
+                });
+            }
+            if entry.min_target != expected {
+                return Err(Error::TierGap {
+                    model,
+                    min_target: entry.min_target,
+                    expected,
+                });
+            }
+            expected = entry.max_target.saturating_add(1);
+        }
+        Ok(())
+    }
+}
+
+/// The `(max_target, model)` tiers of the manifest in `dir`, the ranges are checked before loading
+/// the models
+pub(crate) fn load_tiers(dir: &Path) -> Result<Vec<(u16, ModelData)>, Error> {
+    let path = dir.join(MANIFEST);
+    let manifest: Manifest = File::open(&path)
+        .map_err(Error::Io)
+        .and_then(|f| serde_json::from_reader(BufReader::new(f)).map_err(Error::Json))
+        .map_err(|e| Error::ManifestFile(path, Box::new(e)))?;
+    manifest.check_ranges()?;
+    manifest
+        .models
+        .iter()
+        .map(|entry| {
+            let model = load_model_file(&dir.join(&entry.name).join("model.cbor"))?;
+            Ok((entry.max_target, model))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::model_data::tests::get_test_model;
+    use crate::registry::Manifest;
+    use crate::tests::{sample_rates, SAMPLE_TS};
+    use crate::{Error, FeeModel, ModelKind};
+
+    #[test]
+    fn test_from_directory() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let dir = Path::new("models/fixtures/registry");
+        let model = FeeModel::from_directory(&dir.join("valid")).unwrap();
+        let tiers = vec![
+            (2, get_test_model()),
+            (47, get_test_model()),
+            (1008, get_test_model()),
+        ];
+        let expected = FeeModel::from_tiers(tiers).unwrap();
+        let kinds = [
+            (2u16, ModelKind::Low),
+            (3, ModelKind::High),
+            (47, ModelKind::High),
+            (48, ModelKind::Long),
+        ];
+        for (target, kind) in kinds.iter() {
+            let detailed = model.estimate_detailed(*target, Some(ts), &rates, ts - 300);
+            let detailed = detailed.unwrap();
+            assert_eq!(detailed.model, *kind);
+            let rate = expected.estimate(*target, Some(ts), &rates, ts - 300);
+            assert_eq!(detailed.rate, rate.unwrap());
+        }
+
+        let err = FeeModel::from_directory(&dir.join("overlap")).unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::TierOverlap { model, min_target: 24, previous_max: 47 } if model == "long"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The targets of model long from 24 overlap the previous ones up to 47"
+        );
+        let err = FeeModel::from_directory(&dir.join("gap")).unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::TierGap { model, min_target: 4, expected: 3 } if model == "high"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "The targets of model high start from 4, expected 3"
+        );
+
+        let err = FeeModel::from_directory(dir).unwrap_err();
+        assert!(matches!(&err, Error::ManifestFile(path, e)
+            if path == &dir.join("manifest.json") && matches!(**e, Error::Io(_))));
+        let missing = std::env::temp_dir().join("bitcoin-fee-model-registry");
+        std::fs::create_dir_all(&missing).unwrap();
+        let manifest = r#"{"models": [{"name": "low", "min_target": 1, "max_target": 1008}]}"#;
+        std::fs::write(missing.join("manifest.json"), manifest).unwrap();
+        let err = FeeModel::from_directory(&missing).unwrap_err();
+        assert!(matches!(&err, Error::ModelFile(path, _)
+            if path == &missing.join("low").join("model.cbor")));
+        std::fs::remove_dir_all(&missing).unwrap();
+    }
+
+    #[test]
+    fn test_check_ranges() {
+        let check = |manifest: &str| {
+            let manifest: Manifest = serde_json::from_str(manifest).unwrap();
+            manifest.check_ranges()
+        };
+        check(r#"{"models": [{"name": "a", "min_target": 1, "max_target": 1}]}"#).unwrap();
+        let err = check(r#"{"models": [{"name": "a", "min_target": 0, "max_target": 6}]}"#);
+        assert!(matches!(err, Err(Error::TierGap { expected: 1, .. })));
+        let err = check(r#"{"models": [{"name": "a", "min_target": 6, "max_target": 1}]}"#);
+        assert!(matches!(
+            err,
+            Err(Error::EmptyTier { model, min_target: 6, max_target: 1 }) if model == "a"
+        ));
+    }
+}
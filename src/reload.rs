@@ -0,0 +1,115 @@
+//! A fee model replaceable while in use, see [`ReloadableFeeModel`]
+
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use crate::{Error, FeeModel};
+
+/// A [`FeeModel`] whose models can be replaced with newly trained ones while the process serves
+/// estimates, for example in a long running service.
+///
+/// Estimates use a snapshot of the model taken at the start of the call: a reload swaps the model
+/// only after the new one has been loaded and validated, so an estimate sees either the old model
+/// or the new one, and a failed reload keeps the old one. Can be shared between threads.
+#[derive(Debug)]
+pub struct ReloadableFeeModel {
+    model: RwLock<Arc<FeeModel>>,
+}
+
+impl ReloadableFeeModel {
+    pub fn new(model: FeeModel) -> Self {
+        ReloadableFeeModel {
+            model: RwLock::new(Arc::new(model)),
+        }
+    }
+
+    /// The currently loaded model, for example to check with [`FeeModel::low_model_info`] which
+    /// models are in use after a reload. Keeps being the same model if a reload happens meanwhile.
+    pub fn model(&self) -> Arc<FeeModel> {
+        self.model.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Like [`FeeModel::estimate`] with the currently loaded model
+    pub fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: Option<i64>,
+        fee_rates: &[f64],
+        last_block_ts: i64,
+    ) -> Result<f32, Error> {
+        self.model()
+            .estimate(block_target, timestamp, fee_rates, last_block_ts)
+    }
+
+    /// Load the models in the files at the given paths like [`FeeModel::from_paths`] and replace
+    /// the current ones, keeping the configuration of the current model: its options, clock and
+    /// fee buckets, which must match the new models, see [`FeeModel::with_fee_buckets`]. On error
+    /// the current model is kept.
+    pub fn reload_from_paths(&self, low: &Path, high: &Path) -> Result<(), Error> {
+        let current = self.model();
+        let loaded = FeeModel::from_paths(low, high)?;
+        let model = FeeModel {
+            options: current.options.clone(),
+            network: current.network,
+            #[cfg(feature = "chrono")]
+            clock: current.clock.clone(),
+            ..loaded
+        }
+        .with_fee_buckets(current.fee_buckets.clone())?;
+        *self.model.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(model);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::sync::Arc;
+
+    use crate::model_data::tests::get_test_model;
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::{EstimateOptions, FeeModel, ReloadableFeeModel};
+
+    #[test]
+    fn test_reload_from_paths() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let options = EstimateOptions {
+            min_fee_rate: 2.0,
+            ..Default::default()
+        };
+        let test_model = FeeModel::from_models(get_test_model(), get_test_model());
+        let reloadable = ReloadableFeeModel::new(test_model.with_options(options.clone()));
+        let before = reloadable.model();
+        let old = reloadable.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(old, before.estimate(6, Some(ts), &rates, ts - 300).unwrap());
+
+        // a corrupt file keeps the old model serving
+        let low = Path::new("models/20211027-180849/model.cbor");
+        let high = Path::new("models/20211027-180925/model.cbor");
+        let truncated = Path::new("models/fixtures/truncated.cbor");
+        assert!(reloadable.reload_from_paths(low, truncated).is_err());
+        assert!(Arc::ptr_eq(&reloadable.model(), &before));
+        let estimate = reloadable.estimate(6, Some(ts), &rates, ts - 300);
+        assert_eq!(estimate.unwrap(), old);
+
+        reloadable.reload_from_paths(low, high).unwrap();
+        let after = reloadable.model();
+        assert!(!Arc::ptr_eq(&after, &before));
+        assert_eq!(after.options(), &options);
+        assert_eq!(after.low_model_info(), get_fee_model().low_model_info());
+        #[cfg(feature = "checksum")]
+        {
+            assert_eq!(after.checksums(), get_fee_model().checksums());
+            assert_ne!(after.checksums(), before.checksums());
+        }
+        let expected = get_fee_model().with_options(options);
+        let estimate = reloadable.estimate(6, Some(ts), &rates, ts - 300);
+        assert_eq!(
+            estimate.unwrap(),
+            expected.estimate(6, Some(ts), &rates, ts - 300).unwrap()
+        );
+        // the snapshot taken before the reload keeps estimating with the old model
+        assert_eq!(before.estimate(6, Some(ts), &rates, ts - 300).unwrap(), old);
+    }
+}
@@ -0,0 +1,231 @@
+//! Estimates with named arguments, see [`EstimateRequest`]
+
+use crate::{Error, FeeModel, ModelKind};
+
+/// The arguments of [`FeeModel::estimate_request`], the ones of [`FeeModel::estimate`] named so
+/// that the timestamps can't be swapped, plus the options overridden for this estimate only.
+/// Built from its target:
+///
+/// ```
+/// # use bitcoin_fee_model::EstimateRequest;
+/// let rates = [1.0, 2.0, 3.0];
+/// let request = EstimateRequest::target(6)
+///     .fee_rates(&rates)
+///     .last_block_ts(1613707745)
+///     .timestamp(1613708045)
+///     .min_fee(2.0)
+///     .build()
+///     .unwrap();
+/// assert_eq!(request.block_target(), 6);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimateRequest<'a> {
+    pub(crate) block_target: u16,
+    pub(crate) timestamp: Option<i64>,
+    pub(crate) fee_rates: &'a [f64],
+    pub(crate) last_block_ts: i64,
+    pub(crate) min_fee: Option<f32>,
+}
+
+impl<'a> EstimateRequest<'a> {
+    /// Start building the request of an estimate for `block_target`
+    pub fn target(block_target: u16) -> EstimateRequestBuilder<'a> {
+        EstimateRequestBuilder {
+            block_target,
+            timestamp: None,
+            fee_rates: None,
+            last_block_ts: None,
+            min_fee: None,
+        }
+    }
+
+    pub fn block_target(&self) -> u16 {
+        self.block_target
+    }
+
+    /// Unix time in seconds of the estimate, `None` for the time of the clock
+    pub fn timestamp(&self) -> Option<i64> {
+        self.timestamp
+    }
+
+    pub fn fee_rates(&self) -> &'a [f64] {
+        self.fee_rates
+    }
+
+    pub fn last_block_ts(&self) -> i64 {
+        self.last_block_ts
+    }
+
+    /// The minimum fee rate replacing [`crate::EstimateOptions::min_fee_rate`], if any
+    pub fn min_fee(&self) -> Option<f32> {
+        self.min_fee
+    }
+}
+
+/// Builder of an [`EstimateRequest`], see [`EstimateRequest::target`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimateRequestBuilder<'a> {
+    block_target: u16,
+    timestamp: Option<i64>,
+    fee_rates: Option<&'a [f64]>,
+    last_block_ts: Option<i64>,
+    min_fee: Option<f32>,
+}
+
+impl<'a> EstimateRequestBuilder<'a> {
+    /// The fee rates of the transactions in the last blocks, required, like in
+    /// [`FeeModel::estimate`]
+    pub fn fee_rates(self, fee_rates: &'a [f64]) -> Self {
+        EstimateRequestBuilder {
+            fee_rates: Some(fee_rates),
+            ..self
+        }
+    }
+
+    /// Unix time in seconds of the last block, required
+    pub fn last_block_ts(self, last_block_ts: i64) -> Self {
+        EstimateRequestBuilder {
+            last_block_ts: Some(last_block_ts),
+            ..self
+        }
+    }
+
+    /// Unix time in seconds of the estimate, the time of the clock if not set, see
+    /// [`FeeModel::with_clock`]
+    pub fn timestamp(self, timestamp: i64) -> Self {
+        EstimateRequestBuilder {
+            timestamp: Some(timestamp),
+            ..self
+        }
+    }
+
+    /// Raise the estimate to `min_fee_rate` (sat/vB) instead of
+    /// [`crate::EstimateOptions::min_fee_rate`], which may then be lowered too
+    pub fn min_fee(self, min_fee_rate: f32) -> Self {
+        EstimateRequestBuilder {
+            min_fee: Some(min_fee_rate),
+            ..self
+        }
+    }
+
+    /// The request, erroring with [`Error::MissingField`] naming the first required field not
+    /// set
+    pub fn build(self) -> Result<EstimateRequest<'a>, Error> {
+        Ok(EstimateRequest {
+            block_target: self.block_target,
+            timestamp: self.timestamp,
+            fee_rates: self.fee_rates.ok_or(Error::MissingField("fee_rates"))?,
+            last_block_ts: self
+                .last_block_ts
+                .ok_or(Error::MissingField("last_block_ts"))?,
+            min_fee: self.min_fee,
+        })
+    }
+}
+
+impl FeeModel {
+    /// Like [`FeeModel::estimate`], which estimates through this method, with the arguments and
+    /// the overridden options of `request`
+    pub fn estimate_request(&self, request: &EstimateRequest) -> Result<f32, Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("estimate", block_target = request.block_target).entered();
+        let outcome = |(_, model, clamped): &(f32, ModelKind, bool)| (*model, *clamped);
+        let estimate = self.measured(request.block_target, outcome, || {
+            let (now, last_block) = self.unix_millis(request.timestamp, request.last_block_ts)?;
+            let prediction =
+                self.predict_millis(request.block_target, now, request.fee_rates, last_block)?;
+            match request.min_fee {
+                Some(min_fee_rate) => {
+                    let (rate, clamped) = self
+                        .options
+                        .clamp_with_min(prediction.raw_output, min_fee_rate);
+                    let rate = self.round(rate, min_fee_rate)?;
+                    Ok((rate, prediction.model, clamped))
+                }
+                None => Ok((prediction.rate, prediction.model, prediction.clamped)),
+            }
+        })?;
+        Ok(estimate.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::{Error, EstimateOptions, EstimateRequest, Rounding};
+
+    #[test]
+    fn test_estimate_request() {
+        let model = get_fee_model();
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        for target in [1u16, 2, 6, 144, 1008].iter() {
+            let request = EstimateRequest::target(*target)
+                .fee_rates(&rates)
+                .last_block_ts(ts - 300)
+                .timestamp(ts)
+                .build()
+                .unwrap();
+            let expected = model.estimate(*target, Some(ts), &rates, ts - 300).unwrap();
+            assert_eq!(model.estimate_request(&request).unwrap(), expected);
+            // the order of the setters doesn't matter
+            let reordered = EstimateRequest::target(*target)
+                .timestamp(ts)
+                .last_block_ts(ts - 300)
+                .fee_rates(&rates)
+                .build()
+                .unwrap();
+            assert_eq!(reordered, request);
+        }
+
+        // the minimum of the request replaces the one of the options, also lowering it
+        let raised = model.clone().with_options(EstimateOptions {
+            min_fee_rate: 100.0,
+            ..Default::default()
+        });
+        let request = |min_fee| {
+            EstimateRequest::target(1008)
+                .fee_rates(&rates)
+                .last_block_ts(ts - 300)
+                .timestamp(ts)
+                .min_fee(min_fee)
+                .build()
+                .unwrap()
+        };
+        let expected = model.estimate(1008, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(
+            raised.estimate(1008, Some(ts), &rates, ts - 300).unwrap(),
+            100.0
+        );
+        assert_eq!(raised.estimate_request(&request(1.0)).unwrap(), expected);
+        assert_eq!(model.estimate_request(&request(50.0)).unwrap(), 50.0);
+        // and the estimate is rounded after it
+        let rounded = model.clone().with_options(EstimateOptions {
+            rounding: Rounding::Up { decimals: 0 },
+            ..Default::default()
+        });
+        assert_eq!(rounded.estimate_request(&request(50.5)).unwrap(), 51.0);
+        assert_eq!(rounded.estimate_request(&request(50.0)).unwrap(), 50.0);
+
+        let err = EstimateRequest::target(6)
+            .fee_rates(&rates)
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, Error::MissingField("last_block_ts")));
+        let err = EstimateRequest::target(6)
+            .last_block_ts(ts)
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Missing field fee_rates of the request");
+        let request = EstimateRequest::target(6)
+            .fee_rates(&[])
+            .last_block_ts(ts)
+            .timestamp(ts)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            model.estimate_request(&request),
+            Err(Error::InsufficientData)
+        ));
+    }
+}
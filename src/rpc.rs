@@ -0,0 +1,329 @@
+//! Fee rates of the last blocks from a Bitcoin Core node via `bitcoincore-rpc`
+
+use std::collections::HashMap;
+
+use bitcoin::BlockHash;
+use bitcoincore_rpc::json::{EstimateMode, EstimateSmartFeeResult};
+use bitcoincore_rpc::RpcApi;
+
+use crate::process_blocks::Transactions;
+use crate::{Error, FeeEstimator, FeeModel, FeeRate, WINDOW};
+
+/// The result of `estimatesmartfee` in one mode
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoreEstimate {
+    /// fee rate in sat/vB, `None` if Core has no estimate
+    pub fee_rate: Option<f64>,
+    /// the target Core used for the estimate
+    pub blocks: i64,
+    /// the errors reported by Core, usually the reason there is no estimate
+    pub errors: Vec<String>,
+}
+
+/// An estimate of the model side by side with the ones of Bitcoin Core, see [`compare_with_core`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub target: u16,
+    /// the estimate of the model in sat/vB
+    pub model: f64,
+    pub conservative: CoreEstimate,
+    pub economical: CoreEstimate,
+    /// `(model - conservative) / conservative`, `None` if Core has no conservative estimate
+    pub conservative_difference: Option<f64>,
+    /// `(model - economical) / economical`, `None` if Core has no economical estimate
+    pub economical_difference: Option<f64>,
+}
+
+impl CoreEstimate {
+    fn from_result(result: EstimateSmartFeeResult) -> Self {
+        CoreEstimate {
+            // BTC/kvB to sat/vB
+            fee_rate: result.fee_rate.map(|rate| rate.as_sat() as f64 / 1000.0),
+            blocks: result.blocks,
+            errors: result.errors.unwrap_or_default(),
+        }
+    }
+}
+
+fn relative_difference(model: f64, core: &CoreEstimate) -> Option<f64> {
+    core.fee_rate.map(|rate| (model - rate) / rate)
+}
+
+/// Fetch the [`WINDOW`] blocks ending at `tip` and return the fee rates of their transactions with
+/// every input in the window, ready for [`crate::FeeModel::estimate`], with the timestamp of `tip`.
+/// Coinbases and transactions spending outputs created before the window are skipped. With less
+/// than [`WINDOW`] blocks in the chain every block down to genesis is used.
+pub fn collect_fee_rates<C: RpcApi>(client: &C, tip: &BlockHash) -> Result<(Vec<f64>, u32), Error> {
+    let mut txs = HashMap::new();
+    let mut hash = *tip;
+    let mut tip_time = 0;
+    for i in 0..WINDOW {
+        let block = client.get_block(&hash).map_err(Error::Rpc)?;
+        if i == 0 {
+            tip_time = block.header.time;
+        }
+        for tx in block.txdata {
+            txs.insert(tx.txid(), tx);
+        }
+        if block.header.prev_blockhash == BlockHash::default() {
+            break;
+        }
+        hash = block.header.prev_blockhash;
+    }
+    let txs = Transactions::from_txs(txs, tip_time);
+    Ok((txs.fee_rates(), txs.last_block_ts()))
+}
+
+/// Estimate every target with `model`, from the fee rates of the blocks ending at the best block of
+/// `client`, and with `estimatesmartfee` in both the `CONSERVATIVE` and the `ECONOMICAL` modes.
+/// Targets Core can't estimate have a [`CoreEstimate`] without fee rate. The estimates use the
+/// current time, without the `chrono` feature they error with [`Error::MissingTimestamp`].
+pub fn compare_with_core<C: RpcApi>(
+    model: &FeeModel,
+    client: &C,
+    targets: &[u16],
+) -> Result<Vec<Comparison>, Error> {
+    let tip = client.get_best_block_hash()?;
+    let (fee_rates, last_block_ts) = collect_fee_rates(client, &tip)?;
+    let mut comparisons = vec![];
+    for target in targets {
+        let estimate = model.estimate(*target, None, &fee_rates, last_block_ts as i64)?;
+        let core = |mode| -> Result<CoreEstimate, Error> {
+            let result = client.estimate_smart_fee(*target, Some(mode))?;
+            Ok(CoreEstimate::from_result(result))
+        };
+        let model = estimate as f64;
+        let conservative = core(EstimateMode::Conservative)?;
+        let economical = core(EstimateMode::Economical)?;
+        comparisons.push(Comparison {
+            target: *target,
+            model,
+            conservative_difference: relative_difference(model, &conservative),
+            economical_difference: relative_difference(model, &economical),
+            conservative,
+            economical,
+        });
+    }
+    Ok(comparisons)
+}
+
+/// A [`FeeEstimator`] returning the `estimatesmartfee` of a Bitcoin Core node, for example as the
+/// fallback of the model with [`crate::Fallback`]. Errors with [`Error::InsufficientData`] when
+/// Core has no estimate for the target.
+#[derive(Debug)]
+pub struct CoreEstimator<C> {
+    client: C,
+    mode: EstimateMode,
+}
+
+impl<C: RpcApi> CoreEstimator<C> {
+    /// Estimate with `mode`, `CONSERVATIVE` or `ECONOMICAL`
+    pub fn new(client: C, mode: EstimateMode) -> Self {
+        CoreEstimator { client, mode }
+    }
+}
+
+impl<C: RpcApi> FeeEstimator for CoreEstimator<C> {
+    fn estimate(&self, block_target: u16) -> Result<FeeRate, Error> {
+        let result = self
+            .client
+            .estimate_smart_fee(block_target, Some(self.mode))?;
+        let estimate = CoreEstimate::from_result(result);
+        let rate = estimate.fee_rate.ok_or(Error::InsufficientData)?;
+        Ok(FeeRate::from_sat_per_vb(rate))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{collect_fee_rates, compare_with_core, CoreEstimator};
+    use crate::process_blocks::tests::make_chain;
+    use crate::tests::{sample_rates, SAMPLE_TS};
+    use crate::{BlockTracker, Error, Fallback, FeeEstimator, FeeRate, TrackedFeeModel};
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::{Block, BlockHash};
+    use bitcoincore_rpc::json::EstimateMode;
+    use bitcoincore_rpc::{Auth, Client, RpcApi};
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+    use std::str::FromStr;
+    use std::sync::{Arc, Mutex};
+
+    /// Serves `getblock` and `getbestblockhash` from an in memory chain, the last block is the tip.
+    /// `estimatesmartfee` returns 20 sat/vB in the `CONSERVATIVE` mode and 12.5 sat/vB for targets
+    /// after 2 in the `ECONOMICAL` mode
+    pub struct MockRpc(pub Arc<Mutex<Vec<Block>>>);
+
+    impl RpcApi for MockRpc {
+        fn call<T: for<'a> serde::de::Deserialize<'a>>(
+            &self,
+            cmd: &str,
+            args: &[serde_json::Value],
+        ) -> bitcoincore_rpc::Result<T> {
+            let chain = self.0.lock().unwrap();
+            let value = match cmd {
+                "getblock" => {
+                    let hash = BlockHash::from_str(args[0].as_str().unwrap()).unwrap();
+                    let block = chain
+                        .iter()
+                        .find(|b| b.block_hash() == hash)
+                        .ok_or(bitcoincore_rpc::Error::UnexpectedStructure)?;
+                    serialize_hex(block)
+                }
+                "getbestblockhash" => chain.last().unwrap().block_hash().to_string(),
+                "estimatesmartfee" => {
+                    let target = args[0].as_u64().unwrap();
+                    let value = match (args[1].as_str().unwrap(), target) {
+                        ("CONSERVATIVE", _) => json!({ "feerate": 0.0002, "blocks": target }),
+                        (_, 1..=2) => json!({
+                            "errors": ["Insufficient data or no feerate found"],
+                            "blocks": 0,
+                        }),
+                        _ => json!({ "feerate": 0.000125, "blocks": target + 1 }),
+                    };
+                    return Ok(serde_json::from_value(value)?);
+                }
+                _ => panic!("unexpected call {}", cmd),
+            };
+            Ok(serde_json::from_value(value.into())?)
+        }
+    }
+
+    #[test]
+    fn test_collect_fee_rates() {
+        let chain = make_chain(12);
+        let client = MockRpc(Arc::new(Mutex::new(chain.clone())));
+        let tip = chain.last().unwrap();
+        let (rates, ts) = collect_fee_rates(&client, &tip.block_hash()).unwrap();
+        assert_eq!(ts, tip.header.time);
+        // the oldest block in the window spends an output created before the window
+        assert_eq!(rates.len(), 9);
+        let vsize = chain[1].txdata[1].get_weight() as f64 / 4.0;
+        for rate in rates {
+            assert!((rate - 1000.0 / vsize).abs() < 1e-9);
+        }
+
+        // shorter chains are used down to genesis
+        let tip = &chain[3];
+        let (rates, ts) = collect_fee_rates(&client, &tip.block_hash()).unwrap();
+        assert_eq!((rates.len(), ts), (3, tip.header.time));
+
+        assert!(collect_fee_rates(&client, &BlockHash::default()).is_err());
+    }
+
+    #[test]
+    fn test_compare_with_core() {
+        let chain = make_chain(12);
+        let client = MockRpc(Arc::new(Mutex::new(chain)));
+        // the blocks of the mock chain are years old
+        let options = crate::EstimateOptions {
+            max_last_block_age: None,
+            ..Default::default()
+        };
+        let model = crate::tests::get_fee_model().with_options(options);
+        let comparisons = compare_with_core(&model, &client, &[1, 6]).unwrap();
+        assert_eq!(comparisons.len(), 2);
+
+        let low = &comparisons[0];
+        assert_eq!(low.target, 1);
+        assert!(low.model.is_finite() && low.model >= 1.0);
+        assert_eq!(low.conservative.fee_rate, Some(20.0));
+        assert_eq!(low.conservative.blocks, 1);
+        assert!(low.conservative.errors.is_empty());
+        assert_eq!(low.economical.fee_rate, None);
+        assert_eq!(
+            low.economical.errors,
+            vec!["Insufficient data or no feerate found".to_string()]
+        );
+        assert_eq!(low.conservative_difference, Some((low.model - 20.0) / 20.0));
+        assert_eq!(low.economical_difference, None);
+
+        let high = &comparisons[1];
+        assert_eq!(high.target, 6);
+        assert_eq!(high.economical.fee_rate, Some(12.5));
+        assert_eq!(high.economical.blocks, 7);
+        assert_eq!(high.economical_difference, Some((high.model - 12.5) / 12.5));
+    }
+
+    #[test]
+    fn test_core_fallback() {
+        let chain = make_chain(12);
+        let client = || MockRpc(Arc::new(Mutex::new(chain.clone())));
+        let economical = CoreEstimator::new(client(), EstimateMode::Economical);
+        assert_eq!(
+            economical.estimate(6).unwrap(),
+            FeeRate::from_sat_per_vb(12.5)
+        );
+        assert!(matches!(
+            economical.estimate(1),
+            Err(Error::InsufficientData)
+        ));
+
+        // the model without blocks falls back to Core
+        let ts = SAMPLE_TS;
+        let model =
+            crate::tests::get_fee_model().with_clock(move || Utc.timestamp_opt(ts, 0).unwrap());
+        let mut fallback = Fallback::new(
+            TrackedFeeModel::new(model, BlockTracker::new(0)),
+            economical,
+        );
+        assert_eq!(
+            fallback.estimate(6).unwrap(),
+            FeeRate::from_sat_per_vb(12.5)
+        );
+        assert!(matches!(fallback.estimate(1), Err(Error::InsufficientData)));
+        let rates = sample_rates();
+        let tracker = fallback.primary.tracker_mut();
+        for block in rates.chunks(100) {
+            tracker.push_block(block.to_vec(), ts - 300);
+        }
+        let expected = fallback
+            .primary
+            .model()
+            .estimate(1, Some(ts), &rates, ts - 300);
+        assert_eq!(
+            fallback.estimate(1).unwrap(),
+            FeeRate::from(expected.unwrap())
+        );
+
+        let conservative = CoreEstimator::new(client(), EstimateMode::Conservative);
+        assert_eq!(
+            conservative.estimate(1).unwrap(),
+            FeeRate::from_sat_per_vb(20.0)
+        );
+    }
+
+    /// Needs a regtest node with at least 10 blocks, for example:
+    /// `BITCOIND_URL=http://127.0.0.1:18443 BITCOIND_COOKIE=~/.bitcoin/regtest/.cookie cargo test --features rpc -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_regtest() {
+        let url = std::env::var("BITCOIND_URL").unwrap();
+        let cookie = std::env::var("BITCOIND_COOKIE").unwrap();
+        let client = Client::new(url, Auth::CookieFile(cookie.into())).unwrap();
+        let tip = client.get_best_block_hash().unwrap();
+        let (rates, ts) = collect_fee_rates(&client, &tip).unwrap();
+        assert_eq!(ts, client.get_block_header(&tip).unwrap().time);
+        assert!(rates.iter().all(|r| r.is_finite() && *r >= 0.0));
+    }
+
+    /// Needs a regtest node like [`test_regtest`], the fee estimates of a regtest node are usually
+    /// missing
+    #[test]
+    #[ignore]
+    fn test_regtest_compare_with_core() {
+        let url = std::env::var("BITCOIND_URL").unwrap();
+        let cookie = std::env::var("BITCOIND_COOKIE").unwrap();
+        let client = Client::new(url, Auth::CookieFile(cookie.into())).unwrap();
+        let model = crate::tests::get_fee_model();
+        let comparisons = compare_with_core(&model, &client, &[1, 2, 6, 24]).unwrap();
+        assert_eq!(comparisons.len(), 4);
+        for comparison in comparisons {
+            assert!(comparison.model.is_finite());
+            let core = [&comparison.conservative, &comparison.economical];
+            assert!(core
+                .iter()
+                .all(|c| c.fee_rate.is_some() || !c.errors.is_empty()));
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Buffers reused by the predictions, see [`PredictScratch`]
+
+use alloc::vec::Vec;
+use core::cell::Cell;
+#[cfg(all(feature = "std", not(feature = "ndarray")))]
+use core::cell::RefCell;
+use core::marker::PhantomData;
+
+use crate::{Error, ModelData};
+
+/// The values of the layers of a prediction, reused by the calls of
+/// [`ModelData::norm_predict_with`] so that they don't allocate. Sized for a model with
+/// [`PredictScratch::for_model`], a wider model grows it once.
+///
+/// A scratch is not `Sync` and must not be shared: every thread predicting needs its own, like
+/// the one [`ModelData::norm_predict_slice`] keeps for every thread, for example with the
+/// `map_init` of rayon.
+#[derive(Debug, Clone, Default)]
+pub struct PredictScratch {
+    normalized: Vec<f32>,
+    layers: [Vec<f32>; 3],
+    dequantized: Vec<f32>,
+    not_sync: PhantomData<Cell<()>>,
+}
+
+impl PredictScratch {
+    /// The buffers predicting `model` without allocating
+    pub fn for_model(model: &ModelData) -> PredictScratch {
+        let mut scratch = PredictScratch::default();
+        scratch.fit(model);
+        scratch
+    }
+
+    /// Grow the buffers narrower than the ones of `model`
+    fn fit(&mut self, model: &ModelData) {
+        let w = &model.weights;
+        let grow = |buffer: &mut Vec<f32>, width: usize| {
+            if buffer.len() < width {
+                buffer.resize(width, 0.0);
+            }
+        };
+        grow(&mut self.normalized, model.fields.len());
+        let widths = [w.l0_bias.width(), w.l1_bias.width(), w.l2_bias.width()];
+        for (layer, width) in self.layers.iter_mut().zip(widths.iter()) {
+            grow(layer, *width);
+        }
+        grow(
+            &mut self.dequantized,
+            widths.iter().copied().max().unwrap_or(0),
+        );
+    }
+}
+
+impl ModelData {
+    /// Like [`ModelData::norm_predict_slice`] with the values of the layers in `scratch`, which
+    /// doesn't allocate once as wide as the model. The prediction is the same of
+    /// `norm_predict_slice` without the `ndarray` feature, whose products may differ.
+    pub fn norm_predict_with(
+        &self,
+        input: &[f32],
+        scratch: &mut PredictScratch,
+    ) -> Result<f32, Error> {
+        scratch.fit(self);
+        let [l0, l1, l2] = &mut scratch.layers;
+        let layers: [&mut [f32]; 3] = [l0, l1, l2];
+        self.forward_row(
+            input,
+            &mut scratch.normalized,
+            layers,
+            &mut scratch.dequantized,
+        )
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "ndarray")))]
+thread_local! {
+    static SCRATCH: RefCell<PredictScratch> = RefCell::new(PredictScratch::default());
+}
+
+/// Call `f` with the scratch of this thread
+#[cfg(all(feature = "std", not(feature = "ndarray")))]
+pub(crate) fn with_thread_scratch<T, F: FnOnce(&mut PredictScratch) -> T>(f: F) -> T {
+    SCRATCH.with(|scratch| f(&mut scratch.borrow_mut()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::matrix::Matrix;
+    use crate::model_data::tests::{get_test_model, MODELS};
+    use crate::model_data::Kernel;
+    use crate::{ModelData, PredictScratch};
+
+    /// A model with a first layer wider than the ones predicted on the stack
+    fn wide_model() -> ModelData {
+        let mut model = get_test_model();
+        let w = &mut model.weights;
+        let (inputs, units) = (w.l0_kernel.height(), 300);
+        let kernel = (0..inputs * units).map(|i| ((i % 7) as f32 - 3.0) / 50.0);
+        w.l0_kernel = Kernel::F32(Matrix::from_buffer(kernel.collect(), units, inputs));
+        w.l0_bias = Matrix::from_buffer(vec![0.1; units].into_boxed_slice(), units, 1);
+        let next = w.l1_kernel.width();
+        let kernel = (0..units * next).map(|i| ((i % 5) as f32 - 2.0) / 30.0);
+        w.l1_kernel = Kernel::F32(Matrix::from_buffer(kernel.collect(), next, units));
+        model
+    }
+
+    #[test]
+    fn test_predict_scratch() {
+        let mut models = vec![get_test_model(), wide_model()];
+        models.extend(MODELS.iter().map(|b| ModelData::from_slice(b).unwrap()));
+        let mut reused = PredictScratch::default();
+        for model in models {
+            for model in [model.quantize_int8(), model.to_sparse(0.05), model] {
+                for offset in [0.0f32, 1.5, -3.0] {
+                    let input: Vec<f32> =
+                        (0..model.fields.len()).map(|i| i as f32 + offset).collect();
+                    let mut fresh = PredictScratch::for_model(&model);
+                    let expected = model.norm_predict_with(&input, &mut fresh).unwrap();
+                    let prediction = model.norm_predict_with(&input, &mut reused).unwrap();
+                    assert_eq!(prediction.to_bits(), expected.to_bits());
+                    let slice = model.norm_predict_slice(&input).unwrap();
+                    if !cfg!(feature = "ndarray") {
+                        assert_eq!(slice.to_bits(), expected.to_bits());
+                        let matrix = model.predict(&model.normalize(&input).unwrap());
+                        assert_eq!(matrix.to_bits(), expected.to_bits());
+                    }
+                    assert!(model.norm_predict_with(&input[1..], &mut reused).is_err());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! HTTP service of the estimates, an [`axum::Router`] to mount in an application, see
+//! [`fee_model_router`]
+
+use std::sync::{Arc, PoisonError, RwLock};
+
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, FeeModel, FeeWindow, Staleness};
+
+/// The state shared by the handlers of [`fee_model_router`]: the model and the last fee rates
+/// window pushed, with `POST /window` or [`AppState::set_window`]
+#[derive(Debug)]
+pub struct AppState {
+    model: FeeModel,
+    window: RwLock<Option<FeeWindow>>,
+}
+
+impl AppState {
+    /// A state without fee rates, the estimates fail with 503 until a window is pushed
+    pub fn new(model: FeeModel) -> AppState {
+        AppState {
+            model,
+            window: RwLock::new(None),
+        }
+    }
+
+    pub fn model(&self) -> &FeeModel {
+        &self.model
+    }
+
+    /// Estimate from `window` from now on, for example from a task following the blocks
+    pub fn set_window(&self, window: FeeWindow) {
+        *self.window.write().unwrap_or_else(PoisonError::into_inner) = Some(window);
+    }
+
+    /// The last window pushed, `None` if none yet
+    pub fn window(&self) -> Option<FeeWindow> {
+        self.window
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+#[derive(Deserialize)]
+struct EstimateQuery {
+    target: u16,
+}
+
+/// The body of `POST /window`, the fields of [`FeeWindow`]
+#[derive(Deserialize)]
+struct WindowBody {
+    fee_rates: Vec<f64>,
+    last_block_ts: i64,
+}
+
+/// The body of the responses of `GET /estimate`
+#[derive(Serialize)]
+struct EstimateResponse {
+    /// in sat/vB
+    rate: f32,
+    target: u16,
+    /// the version declared by the model estimating the target, if any
+    model_version: Option<String>,
+    /// the seconds since the last block when older than
+    /// [`crate::EstimateOptions::stale_after`], `None` if fresh
+    staleness: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+fn error(status: StatusCode, message: String) -> Response {
+    (status, Json(ErrorResponse { error: message })).into_response()
+}
+
+/// The status of the estimates failing with `err`: 400 for an invalid request, 503 while the
+/// pushed window can't be estimated, like a stale chain tip or an input out of the training
+/// ranges rejected
+fn estimate_error(err: Error) -> Response {
+    let status = match err {
+        Error::InvalidTarget(_) | Error::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        Error::InsufficientData | Error::StaleChainTip(_) | Error::OutOfDistribution(_) => {
+            StatusCode::SERVICE_UNAVAILABLE
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    error(status, err.to_string())
+}
+
+async fn estimate(
+    State(state): State<Arc<AppState>>,
+    query: Result<Query<EstimateQuery>, QueryRejection>,
+) -> Response {
+    let target = match query {
+        Ok(query) => query.target,
+        Err(rejection) => return error(StatusCode::BAD_REQUEST, rejection.body_text()),
+    };
+    let window = match state.window() {
+        Some(window) => window,
+        None => {
+            let message = "no fee rates window pushed yet".to_string();
+            return error(StatusCode::SERVICE_UNAVAILABLE, message);
+        }
+    };
+    let model = &state.model;
+    let estimate =
+        match model.estimate_detailed(target, None, &window.fee_rates, window.last_block_ts) {
+            Ok(estimate) => estimate,
+            Err(err) => return estimate_error(err),
+        };
+    let staleness = match estimate.staleness {
+        Staleness::Fresh => None,
+        Staleness::Stale { age } => Some(age.as_secs()),
+    };
+    let response = EstimateResponse {
+        rate: estimate.rate,
+        target,
+        model_version: model.model_for(estimate.effective_target).1[0]
+            .info
+            .version
+            .clone(),
+        staleness,
+    };
+    Json(response).into_response()
+}
+
+async fn push_window(
+    State(state): State<Arc<AppState>>,
+    body: Result<Json<WindowBody>, JsonRejection>,
+) -> Response {
+    match body {
+        Ok(Json(body)) => {
+            state.set_window(FeeWindow {
+                fee_rates: body.fee_rates,
+                last_block_ts: body.last_block_ts,
+            });
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(rejection) => error(StatusCode::BAD_REQUEST, rejection.body_text()),
+    }
+}
+
+/// The routes of a fee estimation service from `state`, answering with JSON:
+///
+/// * `GET /estimate?target=6` with the estimate of the target from the last window pushed, as
+///   `{"rate": 12.5, "target": 6, "model_version": "1", "staleness": null}`, `staleness` being
+///   the seconds since the last block when stale, see [`crate::EstimateOptions::stale_after`].
+///   Errors with 400 for an invalid target and with 503 without a window or if it can't be
+///   estimated, like a stale tip with [`crate::EstimateOptions::reject_stale`]
+/// * `POST /window` with a body like `{"fee_rates": [1.0, 2.5], "last_block_ts": 1613708045}`,
+///   the fee rates in sat/vB of the last blocks and the unix timestamp of the newest one,
+///   answers 204, or 400 for an invalid body
+///
+/// The errors have a body like `{"error": "Invalid block target 0"}`. The estimates use the
+/// current time of the clock of the model, see [`FeeModel::with_clock`].
+pub fn fee_model_router(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/estimate", get(estimate))
+        .route("/window", post(push_window))
+        .with_state(state)
+}
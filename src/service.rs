@@ -0,0 +1,286 @@
+//! Background service keeping the fee rates of the last blocks up to date from the `rawblock`
+//! ZMQ notifications of a Bitcoin Core node
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bitcoin::consensus::deserialize;
+use bitcoin::{BlockHash, BlockHeader};
+use bitcoincore_rpc::RpcApi;
+
+use crate::rpc::collect_fee_rates;
+use crate::{Error, FeeModel};
+
+/// Timeout of a single receive, bounds the time needed to stop the service
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+/// Without notifications the node tip is polled via RPC, in case some were lost
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Bounds of the backoff waiting before recreating a failed socket
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug)]
+struct Window {
+    tip: BlockHash,
+    fee_rates: Vec<f64>,
+    last_block_ts: u32,
+}
+
+#[derive(Debug)]
+struct Shared {
+    window: RwLock<Option<Window>>,
+    stop: AtomicBool,
+}
+
+/// Estimates from the fee rates of the last blocks of a Bitcoin Core node, updated in a
+/// background thread at every new block. Can be shared between threads, the thread is stopped
+/// when the service is dropped.
+#[derive(Debug)]
+pub struct FeeModelService {
+    model: FeeModel,
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl FeeModelService {
+    /// Subscribe to the `rawblock` notifications published at `zmq_endpoint` (the
+    /// `-zmqpubrawblock` option of bitcoind) and use `rpc_client` to fetch the blocks of the
+    /// window. The window at the current tip is fetched before returning.
+    pub fn start<C: RpcApi + Send + 'static>(
+        zmq_endpoint: &str,
+        rpc_client: C,
+        model: FeeModel,
+    ) -> Result<Self, Error> {
+        let context = zmq::Context::new();
+        let socket = subscribe(&context, zmq_endpoint)?;
+        let shared = Arc::new(Shared {
+            window: RwLock::new(None),
+            stop: AtomicBool::new(false),
+        });
+        let tip = rpc_client.get_best_block_hash().map_err(Error::Rpc)?;
+        shared.update(&rpc_client, tip)?;
+
+        let endpoint = zmq_endpoint.to_string();
+        let thread_shared = shared.clone();
+        let handle =
+            thread::spawn(move || run(context, socket, &endpoint, &rpc_client, &thread_shared));
+        Ok(FeeModelService {
+            model,
+            shared,
+            handle: Some(handle),
+        })
+    }
+
+    /// Estimate like [`FeeModel::estimate`] at the current time with the last processed window
+    pub fn current_estimate(&self, block_target: u16) -> Result<f32, Error> {
+        let window = self.shared.window.read().unwrap_or_else(|e| e.into_inner());
+        let window = window.as_ref().ok_or(Error::InsufficientData)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as i64);
+        self.model.estimate(
+            block_target,
+            Some(now),
+            &window.fee_rates,
+            window.last_block_ts as i64,
+        )
+    }
+
+    /// Timestamp of the last processed block, to detect a stale service
+    pub fn last_block_ts(&self) -> Option<u32> {
+        let window = self.shared.window.read().unwrap_or_else(|e| e.into_inner());
+        window.as_ref().map(|w| w.last_block_ts)
+    }
+
+    /// Hash of the last processed block
+    pub fn tip(&self) -> Option<BlockHash> {
+        let window = self.shared.window.read().unwrap_or_else(|e| e.into_inner());
+        window.as_ref().map(|w| w.tip)
+    }
+}
+
+impl Drop for FeeModelService {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Shared {
+    fn update<C: RpcApi>(&self, client: &C, tip: BlockHash) -> Result<(), Error> {
+        let current = self.window.read().unwrap_or_else(|e| e.into_inner());
+        if current.as_ref().map(|w| w.tip) == Some(tip) {
+            return Ok(());
+        }
+        drop(current);
+        let (fee_rates, last_block_ts) = collect_fee_rates(client, &tip)?;
+        *self.window.write().unwrap_or_else(|e| e.into_inner()) = Some(Window {
+            tip,
+            fee_rates,
+            last_block_ts,
+        });
+        Ok(())
+    }
+}
+
+fn subscribe(context: &zmq::Context, endpoint: &str) -> Result<zmq::Socket, Error> {
+    let socket = context.socket(zmq::SUB).map_err(Error::Zmq)?;
+    socket
+        .set_rcvtimeo(RECV_TIMEOUT.as_millis() as i32)
+        .map_err(Error::Zmq)?;
+    // let zmq reconnect with backoff and detect dead connections with heartbeats
+    socket
+        .set_reconnect_ivl(MIN_BACKOFF.as_millis() as i32)
+        .map_err(Error::Zmq)?;
+    socket
+        .set_reconnect_ivl_max(MAX_BACKOFF.as_millis() as i32)
+        .map_err(Error::Zmq)?;
+    socket.set_heartbeat_ivl(10_000).map_err(Error::Zmq)?;
+    socket.set_heartbeat_timeout(30_000).map_err(Error::Zmq)?;
+    socket.set_subscribe(b"rawblock").map_err(Error::Zmq)?;
+    socket.connect(endpoint).map_err(Error::Zmq)?;
+    Ok(socket)
+}
+
+/// Hash of the block of a `rawblock` notification: topic, serialized block and sequence number
+fn block_hash(parts: &[Vec<u8>]) -> Option<BlockHash> {
+    match parts {
+        [topic, block, ..] if topic == b"rawblock" && block.len() >= 80 => {
+            deserialize::<BlockHeader>(&block[..80])
+                .ok()
+                .map(|h| h.block_hash())
+        }
+        _ => None,
+    }
+}
+
+fn run<C: RpcApi>(
+    context: zmq::Context,
+    socket: zmq::Socket,
+    endpoint: &str,
+    client: &C,
+    shared: &Shared,
+) {
+    let mut socket = Some(socket);
+    let mut backoff = MIN_BACKOFF;
+    let mut last_update = Instant::now();
+    while !shared.stop.load(Ordering::Relaxed) {
+        let current = match &socket {
+            Some(socket) => socket,
+            None => {
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                socket = subscribe(&context, endpoint).ok();
+                continue;
+            }
+        };
+        match current.recv_multipart(0) {
+            Ok(parts) => {
+                backoff = MIN_BACKOFF;
+                if let Some(hash) = block_hash(&parts) {
+                    // on error the window is fixed at the next notification or poll
+                    let _ = shared.update(client, hash);
+                    last_update = Instant::now();
+                }
+            }
+            Err(zmq::Error::EAGAIN) => {
+                if last_update.elapsed() >= POLL_INTERVAL {
+                    if let Ok(tip) = client.get_best_block_hash() {
+                        let _ = shared.update(client, tip);
+                    }
+                    last_update = Instant::now();
+                }
+            }
+            Err(zmq::Error::EINTR) => (),
+            Err(_) => socket = None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "embedded-models"))]
+mod tests {
+    use super::FeeModelService;
+    use crate::process_blocks::tests::{make_block, make_chain};
+    use crate::rpc::tests::MockRpc;
+    use crate::{EstimateOptions, FeeModel};
+    use bitcoin::consensus::serialize;
+    use bitcoincore_rpc::{Auth, Client, RpcApi};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_service() {
+        assert_send_sync::<FeeModelService>();
+
+        let chain = Arc::new(Mutex::new(make_chain(12)));
+        let context = zmq::Context::new();
+        let publisher = context.socket(zmq::PUB).unwrap();
+        publisher.bind("tcp://127.0.0.1:*").unwrap();
+        let endpoint = publisher.get_last_endpoint().unwrap().unwrap();
+
+        // the blocks of the mock chain are years old
+        let options = EstimateOptions {
+            max_last_block_age: None,
+            ..Default::default()
+        };
+        let model = FeeModel::new().unwrap().with_options(options);
+        let service = FeeModelService::start(&endpoint, MockRpc(chain.clone()), model).unwrap();
+        let tip = chain.lock().unwrap().last().unwrap().clone();
+        assert_eq!(service.tip(), Some(tip.block_hash()));
+        assert_eq!(service.last_block_ts(), Some(tip.header.time));
+        service.current_estimate(2).unwrap();
+
+        let mut block = make_block(tip.block_hash(), tip.header.time + 600, tip.txdata.clone());
+        block.txdata.truncate(1);
+        chain.lock().unwrap().push(block.clone());
+        let start = Instant::now();
+        // a subscriber misses the messages published before it is connected
+        while service.tip() != Some(block.block_hash()) {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            let parts = [&b"rawblock"[..], &serialize(&block), &[0, 0, 0, 0]];
+            publisher.send_multipart(parts.iter(), 0).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert_eq!(service.last_block_ts(), Some(block.header.time));
+    }
+
+    /// Needs a regtest node with a wallet, at least 10 blocks and `-zmqpubrawblock`, for example:
+    /// `BITCOIND_URL=http://127.0.0.1:18443 BITCOIND_COOKIE=~/.bitcoin/regtest/.cookie BITCOIND_ZMQ=tcp://127.0.0.1:28332 cargo test --features zmq -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_regtest() {
+        let url = std::env::var("BITCOIND_URL").unwrap();
+        let cookie = std::env::var("BITCOIND_COOKIE").unwrap();
+        let zmq_endpoint = std::env::var("BITCOIND_ZMQ").unwrap();
+        let auth = Auth::CookieFile(cookie.into());
+        let client = Client::new(url.clone(), auth.clone()).unwrap();
+        let service_client = Client::new(url, auth).unwrap();
+        let model = FeeModel::new().unwrap();
+        let service = FeeModelService::start(&zmq_endpoint, service_client, model).unwrap();
+        let before = service.current_estimate(6).ok();
+
+        let address = client.get_new_address(None, None).unwrap();
+        for _ in 0..3 {
+            let amount = bitcoin::Amount::from_sat(100_000);
+            client
+                .send_to_address(&address, amount, None, None, None, None, None, None)
+                .unwrap();
+        }
+        let hash = client.generate_to_address(1, &address).unwrap()[0];
+        let start = Instant::now();
+        while service.tip() != Some(hash) {
+            assert!(start.elapsed() < Duration::from_secs(10));
+            thread::sleep(Duration::from_millis(100));
+        }
+        let header = client.get_block_header(&hash).unwrap();
+        assert_eq!(service.last_block_ts(), Some(header.time));
+        assert_ne!(service.current_estimate(6).ok(), before);
+    }
+}
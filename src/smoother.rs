@@ -0,0 +1,126 @@
+//! Smoothing of the successive estimates of a target, see [`EstimateSmoother`]
+
+use core::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Smooths the successive estimates of a target, like a live fee rate shown to users which would
+/// otherwise jitter when the hour changes or a block arrives: every estimate moves the smoothed
+/// value towards it, exponentially more the longer since the previous one, halfway after
+/// `half_life`.
+///
+/// An estimate greater than the smoothed value times `snap_factor`, or lower than it divided by
+/// `snap_factor`, replaces it instead, like during a fee spike. Use one smoother for every
+/// target; it is serializable to keep its state across restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EstimateSmoother {
+    half_life: Duration,
+    snap_factor: f32,
+    /// unix time in seconds and smoothed value of the last estimate
+    last: Option<(i64, f32)>,
+}
+
+impl EstimateSmoother {
+    /// A smoother without estimates, a `half_life` of zero doesn't smooth while a `snap_factor`
+    /// not greater than 1 always snaps and an infinite one never
+    pub fn new(half_life: Duration, snap_factor: f32) -> EstimateSmoother {
+        EstimateSmoother {
+            half_life,
+            snap_factor,
+            last: None,
+        }
+    }
+
+    /// Smooth the estimate `raw_estimate` done at the unix time in seconds `timestamp` and
+    /// return the smoothed value. The first estimate is returned as is; estimates not after the
+    /// last one don't move the smoothed value but can snap it, non finite estimates are ignored.
+    pub fn update(&mut self, timestamp: i64, raw_estimate: f32) -> f32 {
+        let (last_ts, smoothed) = match self.last {
+            Some(last) if raw_estimate.is_finite() => last,
+            Some((_, smoothed)) => return smoothed,
+            None => {
+                if raw_estimate.is_finite() {
+                    self.last = Some((timestamp, raw_estimate));
+                }
+                return raw_estimate;
+            }
+        };
+        let snap = raw_estimate > smoothed * self.snap_factor
+            || raw_estimate < smoothed / self.snap_factor;
+        let elapsed = timestamp.saturating_sub(last_ts).max(0);
+        let value = if snap || self.half_life.is_zero() {
+            raw_estimate
+        } else {
+            let half_lives = elapsed as f64 / self.half_life.as_secs_f64();
+            let weight = libm::pow(0.5, half_lives) as f32;
+            smoothed * weight + raw_estimate * (1.0 - weight)
+        };
+        self.last = Some((last_ts.max(timestamp), value));
+        value
+    }
+
+    /// The last smoothed value, `None` before the first estimate
+    pub fn value(&self) -> Option<f32> {
+        self.last.map(|(_, value)| value)
+    }
+
+    /// Forget the estimates, the next one is returned as is
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::tests::{assert_approx_eq, SAMPLE_TS};
+    use crate::EstimateSmoother;
+
+    #[test]
+    fn test_smoother() {
+        fn is_send<T: Send>(_: &T) {}
+        let ts = SAMPLE_TS;
+        let mut smoother = EstimateSmoother::new(Duration::from_secs(600), 2.0);
+        is_send(&smoother);
+        assert_eq!(smoother.value(), None);
+        assert_eq!(smoother.update(ts, 10.0), 10.0);
+        assert_eq!(smoother.update(ts + 60, 10.0), 10.0);
+
+        // a step not snapping converges, halfway after a half-life
+        assert_approx_eq(smoother.update(ts + 660, 15.0), 12.5);
+        assert_approx_eq(smoother.update(ts + 1260, 15.0), 13.75);
+        let mut value = 0.0;
+        for i in 3..13 {
+            value = smoother.update(ts + 60 + i * 600, 15.0);
+        }
+        assert!((value - 15.0).abs() < 0.01, "{}", value);
+        // estimates at the same time don't move it
+        assert_eq!(smoother.update(ts + 60 + 12 * 600, 12.0), value);
+        assert_eq!(smoother.update(ts, f32::NAN), value);
+
+        // spikes snap, up and down
+        let now = ts + 10_000;
+        assert_eq!(smoother.update(now, 40.0), 40.0);
+        assert_approx_eq(smoother.update(now + 600, 30.0), 35.0);
+        assert_eq!(smoother.update(now + 660, 17.0), 17.0);
+        assert_eq!(smoother.value(), Some(17.0));
+
+        let json = serde_json::to_string(&smoother).unwrap();
+        let mut restored: EstimateSmoother = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, smoother);
+        assert_eq!(
+            restored.update(now + 1260, 20.0),
+            smoother.update(now + 1260, 20.0)
+        );
+        restored.reset();
+        assert_eq!(restored.update(now, 3.0), 3.0);
+
+        let mut unsmoothed = EstimateSmoother::new(Duration::from_secs(0), f32::INFINITY);
+        unsmoothed.update(ts, 10.0);
+        assert_eq!(unsmoothed.update(ts + 1, 100.0), 100.0);
+        let mut never_snapping = EstimateSmoother::new(Duration::from_secs(600), f32::INFINITY);
+        never_snapping.update(ts, 10.0);
+        assert_approx_eq(never_snapping.update(ts + 600, 1000.0), 505.0);
+    }
+}
@@ -0,0 +1,253 @@
+//! Snapshots of the estimates of a model over a grid of inputs, to catch the changes of the
+//! models or of the code altering them, see [`snapshot`] and [`Snapshot::compare`]
+
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{FeeModel, GridPoint, PredictionGrid};
+
+impl PredictionGrid {
+    /// The grid of the golden snapshot of the embedded models, for models with 16 buckets like
+    /// [`crate::FeeModelConfig::default`]:
+    ///
+    /// * the targets 1, 2, 3, 6, 12, 144 and 1008, so every model of the tiers and the ends of
+    ///   the range of the high model
+    /// * four windows of fee rates counted in the buckets: a quiet one with most transactions
+    ///   paying the minimum, a window of the blocks of February 2021, a congested one and the
+    ///   same count in every bucket
+    /// * tuesday and saturday, at 0, 8 and 16 UTC
+    /// * 0 seconds, 10 minutes and an hour since the last block
+    pub fn standard() -> PredictionGrid {
+        PredictionGrid {
+            targets: vec![1, 2, 3, 6, 12, 144, 1008],
+            buckets: vec![
+                vec![3000, 800, 400, 200, 100, 50, 25, 10, 5, 2, 1, 0, 0, 0, 0, 0],
+                vec![
+                    13, 1, 32, 24, 14, 62, 1174, 453, 197, 291, 333, 3304, 307, 229, 36, 58,
+                ],
+                vec![
+                    0, 0, 0, 5, 10, 20, 50, 100, 300, 800, 1500, 2500, 2000, 800, 200, 50,
+                ],
+                vec![100; 16],
+            ],
+            days_of_week: vec![1, 5],
+            hours: vec![0, 8, 16],
+            delta_lasts: vec![0.0, 600.0, 3600.0],
+        }
+    }
+}
+
+/// The estimate of a point of the grid of a [`Snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotPoint {
+    #[serde(flatten)]
+    pub point: GridPoint,
+    /// the estimate in sat/vB, `None` if it errored
+    pub rate: Option<f32>,
+}
+
+/// The estimates of a model at every point of a [`PredictionGrid`], serializable to be stored
+/// and compared with the ones of a later version with [`Snapshot::compare`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub grid: PredictionGrid,
+    /// in the order of [`PredictionGrid::points`]
+    pub points: Vec<SnapshotPoint>,
+}
+
+/// A point whose estimates differ between two snapshots, see [`Snapshot::compare`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Mismatch {
+    pub point: GridPoint,
+    /// the counts of the buckets of the point
+    pub buckets: Vec<u64>,
+    /// the estimate of this snapshot, `None` if it errored or the point is only in the other one
+    pub expected: Option<f32>,
+    /// the estimate of the other snapshot, `None` if it errored or the point is only in this one
+    pub got: Option<f32>,
+}
+
+/// Estimate every point of `grid` with `model`, with the buckets like in
+/// [`FeeModel::estimate_with_buckets`] and the estimates bounded as configured in its options,
+/// see [`PredictionGrid::standard`]. The extras of the models using them are zero.
+pub fn snapshot(model: &FeeModel, grid: &PredictionGrid) -> Snapshot {
+    let points = grid
+        .points()
+        .map(|point| SnapshotPoint {
+            point,
+            rate: model.estimate_grid_point(grid, point).ok(),
+        })
+        .collect();
+    Snapshot {
+        grid: grid.clone(),
+        points,
+    }
+}
+
+/// The point of a snapshot comparable across grids, with its buckets by value
+type Key<'a> = (u16, &'a [u64], u8, u8, u32);
+
+impl Snapshot {
+    /// The points of this snapshot and `other` whose estimates differ by more than `tolerance`
+    /// relative to the estimate of this snapshot, zero for identical estimates, or that errored
+    /// in only one of them. Points are matched by their features, so the grids may differ and the
+    /// points in only one of the snapshots are mismatches too, first the ones of this snapshot.
+    pub fn compare(&self, other: &Snapshot, tolerance: f32) -> Vec<Mismatch> {
+        let (this, that) = (self.keyed(), other.keyed());
+        let mut mismatches = Vec::new();
+        for (key, expected) in this.iter() {
+            let got = that.get(key).map(|p| p.rate);
+            let differs = match (expected.rate, got) {
+                (Some(expected), Some(Some(got))) => {
+                    (got - expected).abs() > tolerance * expected.abs()
+                }
+                (None, Some(None)) => false,
+                _ => true,
+            };
+            if differs {
+                mismatches.push(Mismatch {
+                    point: expected.point,
+                    buckets: key.1.to_vec(),
+                    expected: expected.rate,
+                    got: got.flatten(),
+                });
+            }
+        }
+        for (key, got) in that.iter().filter(|(key, _)| !this.contains_key(key)) {
+            mismatches.push(Mismatch {
+                point: got.point,
+                buckets: key.1.to_vec(),
+                expected: None,
+                got: got.rate,
+            });
+        }
+        mismatches
+    }
+
+    /// The points by their features
+    fn keyed(&self) -> BTreeMap<Key<'_>, &SnapshotPoint> {
+        let keys = self.points.iter().map(|p| {
+            let point = &p.point;
+            let buckets = self.grid.buckets.get(point.buckets);
+            let buckets = buckets.map_or(&[][..], |b| &b[..]);
+            let delta_last = point.delta_last.to_bits();
+            let key = (
+                point.block_target,
+                buckets,
+                point.day_of_week,
+                point.hour,
+                delta_last,
+            );
+            (key, p)
+        });
+        keys.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::snapshot::{snapshot, Snapshot};
+    use crate::tests::get_fee_model;
+    #[cfg(feature = "f16")]
+    use crate::tests::{get_high_model, get_low_model};
+    #[cfg(feature = "f16")]
+    use crate::FeeModel;
+    use crate::{EstimateOptions, PredictionGrid};
+
+    const GOLDEN: &str = "models/fixtures/snapshot.json";
+
+    /// The snapshot of the embedded models, regenerated with `UPDATE_SNAPSHOT=1` after a change
+    /// meant to alter the estimates
+    #[test]
+    fn test_golden_snapshot() {
+        let grid = PredictionGrid::standard();
+        assert_eq!(grid.points().count(), 7 * 4 * 2 * 3 * 3);
+        let current = snapshot(&get_fee_model(), &grid);
+        assert!(current.points.iter().all(|p| p.rate.is_some()));
+        if std::env::var_os("UPDATE_SNAPSHOT").is_some() {
+            fs::write(GOLDEN, serde_json::to_string(&current).unwrap() + "\n").unwrap();
+        }
+        let golden: Snapshot = serde_json::from_str(&fs::read_to_string(GOLDEN).unwrap()).unwrap();
+        assert_eq!(golden.grid, grid);
+        // the products of the `ndarray` feature are summed in another order
+        let mismatches = golden.compare(&current, 1e-4);
+        assert!(
+            mismatches.is_empty(),
+            "{:?}",
+            &mismatches[..mismatches.len().min(5)]
+        );
+    }
+
+    /// The drift of the embedded models converted to half precision, see [`ModelData::to_f16`]
+    #[cfg(feature = "f16")]
+    #[test]
+    fn test_f16_drift() {
+        let grid = PredictionGrid::standard();
+        let float = snapshot(&get_fee_model(), &grid);
+        let half = FeeModel::from_models(get_low_model().to_f16(), get_high_model().to_f16());
+        let half = snapshot(&half, &grid);
+        let mut drift: Vec<f32> = half
+            .points
+            .iter()
+            .zip(float.points.iter())
+            .map(|(h, f)| (h.rate.unwrap() / f.rate.unwrap() - 1.0).abs())
+            .collect();
+        drift.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(drift[drift.len() / 2] < 1e-3, "{:?}", drift);
+        assert!(drift[drift.len() * 99 / 100] < 0.05, "{:?}", drift);
+        // a few estimates cancel large weights with the biases and drift more
+        assert!(float.compare(&half, 0.15).is_empty());
+    }
+
+    #[test]
+    fn test_compare() {
+        let model = get_fee_model();
+        let mut grid = PredictionGrid::standard();
+        grid.targets = vec![1, 6];
+        let base = snapshot(&model, &grid);
+        assert!(base.compare(&base, 0.0).is_empty());
+
+        let raised = model.clone().with_options(EstimateOptions {
+            min_fee_rate: 30.0,
+            ..Default::default()
+        });
+        let other = snapshot(&raised, &grid);
+        let mismatches = base.compare(&other, 0.0);
+        assert!(!mismatches.is_empty());
+        for mismatch in mismatches.iter() {
+            assert_eq!(mismatch.got, Some(30.0));
+            assert!(mismatch.expected.unwrap() < 30.0);
+            assert_eq!(mismatch.buckets, grid.buckets[mismatch.point.buckets]);
+        }
+        assert!(base.compare(&other, 1000.0).is_empty());
+
+        // the points only in one of the snapshots, matched whatever their order in the grids
+        let mut fewer = grid.clone();
+        fewer.hours = vec![16, 0];
+        fewer.buckets.swap(0, 3);
+        let fewer = snapshot(&model, &fewer);
+        let mismatches = base.compare(&fewer, 0.0);
+        assert_eq!(mismatches.len(), base.points.len() / 3);
+        assert!(mismatches
+            .iter()
+            .all(|m| m.point.hour == 8 && m.got.is_none()));
+        let mismatches = fewer.compare(&base, 0.0);
+        assert!(mismatches.iter().all(|m| m.expected.is_none()));
+
+        // an estimate erroring in only one snapshot
+        let mut failing = grid.clone();
+        failing.buckets[1] = vec![0; 16];
+        let failing = snapshot(&model, &failing);
+        assert!(failing.points.iter().any(|p| p.rate.is_none()));
+        let mismatches = failing.compare(&failing, 0.0);
+        assert!(mismatches.is_empty());
+        let json = serde_json::to_string(&failing).unwrap();
+        let restored: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, failing);
+    }
+}
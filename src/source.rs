@@ -0,0 +1,68 @@
+//! Asynchronous sources of the fee rates of the last blocks, see [`AsyncFeeSource`]
+
+use alloc::vec::Vec;
+use core::future::Future;
+
+use crate::Error;
+
+/// The fee rates of the transactions of the last blocks with the timestamp of the newest one, the
+/// inputs of [`crate::FeeModel::estimate`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeWindow {
+    /// in sat/vB
+    pub fee_rates: Vec<f64>,
+    /// unix timestamp in seconds
+    pub last_block_ts: i64,
+}
+
+/// A source of the [`FeeWindow`] fetched asynchronously, for example from the network, see
+/// [`crate::FeeModel::estimate_from_source`].
+///
+/// The trait doesn't depend on an async runtime; implementations may be written with
+/// `async fn fee_window(&self) -> Result<FeeWindow, Error>`, the future must be `Send` to be
+/// spawned on multi threaded runtimes. Timeouts and retries are up to the implementations, like
+/// `EsploraClient::with_timeout`.
+pub trait AsyncFeeSource {
+    fn fee_window(&self) -> impl Future<Output = Result<FeeWindow, Error>> + Send;
+}
+
+#[cfg(all(test, feature = "chrono", not(target_arch = "wasm32")))]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use crate::source::{AsyncFeeSource, FeeWindow};
+    use crate::tests::{get_fee_model, sample_rates, SAMPLE_TS};
+    use crate::Error;
+
+    struct MockSource(Result<FeeWindow, ()>);
+
+    impl AsyncFeeSource for MockSource {
+        async fn fee_window(&self) -> Result<FeeWindow, Error> {
+            tokio::task::yield_now().await;
+            self.0.clone().map_err(|_| Error::InsufficientData)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_estimate_from_source() {
+        let ts = SAMPLE_TS;
+        let rates = sample_rates();
+        let model = get_fee_model().with_clock(move || Utc.timestamp_opt(ts, 0).unwrap());
+        let window = FeeWindow {
+            fee_rates: rates.clone(),
+            last_block_ts: ts - 300,
+        };
+        let source = MockSource(Ok(window));
+        let estimate = model.estimate_from_source(&source, 6).await.unwrap();
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(estimate, expected);
+
+        let err = model.estimate_from_source(&MockSource(Err(())), 6).await;
+        assert!(matches!(err, Err(Error::InsufficientData)));
+        // the future is Send, it can be spawned
+        tokio::spawn(async move { model.estimate_from_source(&source, 2).await })
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+
+/// Names of the extra features computed from the fee rates by [`summary_features`], provided
+/// automatically to the models using them by [`crate::FeeModel::estimate`]
+pub const SUMMARY_FEATURES: [&str; 6] = ["p10", "p25", "p50", "p75", "p90", "n_tx"];
+
+/// Percentiles in sat/vB and number of the fee rates of the last blocks, see [`summary_features`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SummaryFeatures {
+    pub p10: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p90: f64,
+    pub n_tx: usize,
+}
+
+/// Compute the [`SummaryFeatures`] of `fee_rates`, the `p`-th percentile with the nearest-rank
+/// method is the smallest rate such that at least `p` percent of the rates are lower or equal,
+/// that is the rate at position `ceil(p / 100 * n)` of the `n` sorted rates, from 1.
+/// Percentiles of no fee rates are 0.
+pub fn summary_features(fee_rates: &[f64]) -> SummaryFeatures {
+    let mut sorted = fee_rates.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: usize| {
+        // ceil(p * n / 100) in integers, at least the first rate
+        let rank = (p * sorted.len()).div_ceil(100).max(1);
+        sorted.get(rank - 1).copied().unwrap_or(0.0)
+    };
+    SummaryFeatures {
+        p10: percentile(10),
+        p25: percentile(25),
+        p50: percentile(50),
+        p75: percentile(75),
+        p90: percentile(90),
+        n_tx: sorted.len(),
+    }
+}
+
+impl SummaryFeatures {
+    /// The features by the names in [`SUMMARY_FEATURES`], like the extras of
+    /// [`crate::FeeModel::estimate_with_extras`]
+    pub fn to_extras(&self) -> BTreeMap<String, f32> {
+        let values = [self.p10, self.p25, self.p50, self.p75, self.p90];
+        let values = values
+            .iter()
+            .map(|v| *v as f32)
+            .chain(Some(self.n_tx as f32));
+        SUMMARY_FEATURES
+            .iter()
+            .map(|name| name.to_string())
+            .zip(values)
+            .collect()
+    }
+}
+
+/// Whether one of `fields` is in [`SUMMARY_FEATURES`]
+pub(crate) fn uses_summary<'a>(mut fields: impl Iterator<Item = &'a str>) -> bool {
+    fields.any(|f| SUMMARY_FEATURES.contains(&f))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::summary::{summary_features, SummaryFeatures};
+
+    #[test]
+    fn test_summary_features() {
+        let rates: Vec<f64> = (1..=20).rev().map(|i| i as f64).collect();
+        let summary = summary_features(&rates);
+        // ranks 2, 5, 10, 15 and 18 of 20
+        let expected = SummaryFeatures {
+            p10: 2.0,
+            p25: 5.0,
+            p50: 10.0,
+            p75: 15.0,
+            p90: 18.0,
+            n_tx: 20,
+        };
+        assert_eq!(summary, expected);
+
+        // ranks 1, 2, 3, 3 and 4 of 4 with duplicates
+        let summary = summary_features(&[3.0, 1.0, 3.0, 7.5]);
+        assert_eq!(
+            (
+                summary.p10,
+                summary.p25,
+                summary.p50,
+                summary.p75,
+                summary.p90
+            ),
+            (1.0, 1.0, 3.0, 3.0, 7.5)
+        );
+        assert_eq!(summary.n_tx, 4);
+
+        let single = summary_features(&[4.2]);
+        assert_eq!((single.p10, single.p90, single.n_tx), (4.2, 4.2, 1));
+        let two = summary_features(&[5.0, 2.0]);
+        assert_eq!((two.p10, two.p50, two.p75, two.p90), (2.0, 2.0, 5.0, 5.0));
+        let empty = summary_features(&[]);
+        assert_eq!((empty.p10, empty.p90, empty.n_tx), (0.0, 0.0, 0));
+
+        let extras = summary_features(&[3.0, 1.0, 3.0, 7.5]).to_extras();
+        assert_eq!(extras.len(), 6);
+        assert_eq!(extras["p90"], 7.5);
+        assert_eq!(extras["n_tx"], 4.0);
+    }
+}
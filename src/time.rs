@@ -0,0 +1,176 @@
+//! Time features of the models computed from unix timestamps with integer arithmetic, so that
+//! they don't require chrono
+
+use core::convert::TryFrom;
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+
+use crate::Error;
+
+const MILLIS_PER_HOUR: i64 = 60 * 60 * 1000;
+const MILLIS_PER_DAY: i64 = 24 * MILLIS_PER_HOUR;
+
+/// 10000-01-01 00:00:00 UTC, later [`Timestamp`] are rejected as absurd
+const MAX_TIMESTAMP: i64 = 253_402_300_800;
+
+/// A point in time accepted by [`crate::FeeModel::estimate_at_time`], built from the time types
+/// of the caller without converting them by hand. The conversion is checked when estimating:
+/// times before 1970 or from the year 10000 error with [`Error::InvalidTimestamp`]. The
+/// milliseconds of a [`std::time::SystemTime`] or a [`DateTime`] are kept, like in
+/// [`crate::FeeModel::estimate_at`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timestamp {
+    /// unix time in seconds
+    Seconds(u64),
+    #[cfg(feature = "std")]
+    SystemTime(std::time::SystemTime),
+    #[cfg(feature = "chrono")]
+    DateTime(DateTime<Utc>),
+}
+
+impl Timestamp {
+    /// Unix time in milliseconds, errors with [`Error::InvalidTimestamp`] with the seconds of the
+    /// time if it is before the epoch or too far in the future, see [`Timestamp`]
+    pub fn unix_millis(&self) -> Result<i64, Error> {
+        let millis = match self {
+            Timestamp::Seconds(secs) => {
+                let secs = i64::try_from(*secs).map_err(|_| Error::InvalidTimestamp(i64::MAX))?;
+                millis(secs)?
+            }
+            #[cfg(feature = "std")]
+            Timestamp::SystemTime(time) => match time.duration_since(std::time::UNIX_EPOCH) {
+                Ok(since) => i64::try_from(since.as_millis()).unwrap_or(i64::MAX),
+                Err(e) => {
+                    let before = i64::try_from(e.duration().as_secs()).unwrap_or(i64::MAX);
+                    return Err(Error::InvalidTimestamp(-before.max(1)));
+                }
+            },
+            #[cfg(feature = "chrono")]
+            Timestamp::DateTime(time) => time.timestamp_millis(),
+        };
+        if !(0..MAX_TIMESTAMP * 1000).contains(&millis) {
+            return Err(Error::InvalidTimestamp(millis.div_euclid(1000)));
+        }
+        Ok(millis)
+    }
+}
+
+impl From<u64> for Timestamp {
+    fn from(secs: u64) -> Self {
+        Timestamp::Seconds(secs)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::time::SystemTime> for Timestamp {
+    fn from(time: std::time::SystemTime) -> Self {
+        Timestamp::SystemTime(time)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(time: DateTime<Utc>) -> Self {
+        Timestamp::DateTime(time)
+    }
+}
+
+/// Convert unix `timestamp` in seconds to milliseconds, errors if it doesn't fit
+pub(crate) fn millis(timestamp: i64) -> Result<i64, Error> {
+    timestamp
+        .checked_mul(1000)
+        .ok_or(Error::InvalidTimestamp(timestamp))
+}
+
+/// Days since monday of unix time `millis`, the epoch was a thursday
+pub(crate) fn day_of_week(millis: i64) -> u32 {
+    (millis.div_euclid(MILLIS_PER_DAY) + 3).rem_euclid(7) as u32
+}
+
+/// Hour of the day (UTC) of unix time `millis`
+pub(crate) fn hour(millis: i64) -> u32 {
+    (millis.rem_euclid(MILLIS_PER_DAY) / MILLIS_PER_HOUR) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::time::{day_of_week, hour, millis};
+    use crate::Error;
+
+    #[test]
+    fn test_time_features() {
+        // 2021-02-19 04:14:05 UTC, a friday
+        let ts = millis(1613708045).unwrap();
+        assert_eq!(day_of_week(ts), 4);
+        assert_eq!(hour(ts), 4);
+        // 1969-12-31 23:59:59 UTC, a wednesday
+        assert_eq!(day_of_week(-1000), 2);
+        assert_eq!(hour(-1000), 23);
+
+        assert!(matches!(millis(i64::MAX), Err(Error::InvalidTimestamp(_))));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        use crate::Timestamp;
+
+        let ts = 1613708045u64;
+        assert_eq!(Timestamp::from(ts).unix_millis().unwrap(), 1613708045000);
+        let time = UNIX_EPOCH + Duration::from_millis(1613708045999);
+        // the milliseconds are not truncated
+        assert_eq!(Timestamp::from(time).unix_millis().unwrap(), 1613708045999);
+        assert_eq!(Timestamp::from(0u64).unix_millis().unwrap(), 0);
+
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(60);
+        let err = Timestamp::from(before_epoch).unix_millis().unwrap_err();
+        assert!(matches!(err, Error::InvalidTimestamp(-60)));
+        for secs in [253_402_300_800u64, u64::MAX] {
+            let err = Timestamp::from(secs).unix_millis().unwrap_err();
+            assert!(matches!(err, Error::InvalidTimestamp(_)));
+        }
+        let far = UNIX_EPOCH + Duration::from_secs(1 << 40);
+        assert!(Timestamp::from(far).unix_millis().is_err());
+
+        #[cfg(feature = "chrono")]
+        {
+            use chrono::{TimeZone, Utc};
+
+            let utc = Utc.timestamp_millis_opt(1613708045123).unwrap();
+            assert_eq!(Timestamp::from(utc).unix_millis().unwrap(), 1613708045123);
+            let err = Timestamp::from(Utc.timestamp_opt(-1, 0).unwrap()).unix_millis();
+            assert!(matches!(err, Err(Error::InvalidTimestamp(-1))));
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_time_features_chrono() {
+        use chrono::{Datelike, TimeZone, Timelike, Utc};
+
+        let leap_days = [
+            951782400,  // 2000-02-29
+            1330473600, // 2012-02-29
+            1582934400, // 2020-02-29
+            1709164800, // 2024-02-29
+            4107456000, // 2100-02-28, 2100 is not a leap year
+        ];
+        let sweep = (-100_000i64..100_000).map(|i| i * 12_345);
+        let around_leap_days = leap_days
+            .iter()
+            .flat_map(|day| (-48..48).map(move |h| day + h * 1800 - 1));
+        for ts in sweep.chain(around_leap_days) {
+            let utc = Utc.timestamp_opt(ts, 0).unwrap();
+            let ms = millis(ts).unwrap();
+            assert_eq!(
+                day_of_week(ms),
+                utc.weekday().num_days_from_monday(),
+                "{}",
+                ts
+            );
+            assert_eq!(hour(ms), utc.hour(), "{}", ts);
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, FeeModel};
+
+/// Number of blocks whose transactions are used as inputs of the models
+pub const WINDOW: usize = 10;
+
+/// Version of the format written by [`BlockTracker::save_to`], newer ones fail to load
+pub const TRACKER_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TrackedBlock {
+    fee_rates: Vec<f64>,
+    timestamp: i64,
+    /// the hash of the block if pushed with [`BlockTracker::push_block_with_hash`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hash: Option<[u8; 32]>,
+}
+
+/// The state written by [`BlockTracker::save_to`], the tip is repeated to be checked without
+/// the blocks
+#[cfg(feature = "std")]
+#[derive(Serialize)]
+struct SavedTracker<'a> {
+    version: u32,
+    tip_height: Option<u32>,
+    tip_hash: Option<[u8; 32]>,
+    tracker: &'a BlockTracker,
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+struct SavedVersion {
+    version: u32,
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+struct LoadedTracker {
+    tracker: BlockTracker,
+}
+
+/// Fee rates of the last [`WINDOW`] blocks, updated one block at a time, to avoid recomputing
+/// the whole window when a new block arrives. Serializable, or saved with
+/// [`BlockTracker::save_to`], to keep the window across restarts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockTracker {
+    blocks: VecDeque<TrackedBlock>,
+    tip_height: u32,
+}
+
+impl BlockTracker {
+    /// An empty tracker, the first pushed block has height `start_height`
+    pub fn new(start_height: u32) -> Self {
+        BlockTracker {
+            blocks: VecDeque::with_capacity(WINDOW),
+            tip_height: start_height.wrapping_sub(1),
+        }
+    }
+
+    /// Push the fee rates of the transactions of a new block with its `timestamp`, the oldest
+    /// block is dropped if the window is full
+    pub fn push_block(&mut self, fee_rates: Vec<f64>, timestamp: i64) {
+        self.push(fee_rates, timestamp, None)
+    }
+
+    /// Like [`BlockTracker::push_block`] with the `hash` of the block, to reconcile the window
+    /// with the chain after a restore, see [`BlockTracker::reconcile`]
+    pub fn push_block_with_hash(&mut self, fee_rates: Vec<f64>, timestamp: i64, hash: [u8; 32]) {
+        self.push(fee_rates, timestamp, Some(hash))
+    }
+
+    fn push(&mut self, fee_rates: Vec<f64>, timestamp: i64, hash: Option<[u8; 32]>) {
+        // more blocks only in a deserialized tracker
+        while self.blocks.len() >= WINDOW {
+            self.blocks.pop_front();
+        }
+        self.blocks.push_back(TrackedBlock {
+            fee_rates,
+            timestamp,
+            hash,
+        });
+        self.tip_height = self.tip_height.wrapping_add(1);
+    }
+
+    /// Remove the newest block, returning its fee rates. Blocks dropped from the window are not
+    /// restored, so it contains less than [`WINDOW`] blocks until new ones are pushed.
+    pub fn pop_block(&mut self) -> Option<Vec<f64>> {
+        let block = self.blocks.pop_back()?;
+        self.tip_height = self.tip_height.wrapping_sub(1);
+        Some(block.fee_rates)
+    }
+
+    /// Remove the blocks higher than `height`, like after a reorg
+    pub fn reset_to(&mut self, height: u32) {
+        while self.tip_height > height && self.pop_block().is_some() {}
+    }
+
+    /// Reconcile the window with the chain of a node, like after restoring it with
+    /// [`BlockTracker::load_from`]: `tip_height` is the height of the tip of the node and
+    /// `hash_at` the hash of its block at a height, `None` if unknown. The blocks above
+    /// `tip_height` are dropped, then the newest blocks whose hash differs from the one of the
+    /// node, until one matches; blocks pushed without hash are kept. Returns the number of
+    /// blocks dropped, the ones from [`BlockTracker::tip_height`] to the tip of the node are then
+    /// to be pushed.
+    pub fn reconcile<F>(&mut self, tip_height: u32, mut hash_at: F) -> usize
+    where
+        F: FnMut(u32) -> Option<[u8; 32]>,
+    {
+        let len = self.len();
+        self.reset_to(tip_height);
+        while let Some(hash) = self.blocks.back().and_then(|b| b.hash) {
+            if hash_at(self.tip_height) == Some(hash) {
+                break;
+            }
+            self.pop_block();
+        }
+        len - self.len()
+    }
+
+    /// Height of the newest block, `None` if the tracker is empty
+    pub fn tip_height(&self) -> Option<u32> {
+        (!self.blocks.is_empty()).then_some(self.tip_height)
+    }
+
+    /// Hash of the newest block, `None` if the tracker is empty or it was pushed without hash
+    pub fn tip_hash(&self) -> Option<[u8; 32]> {
+        self.blocks.back().and_then(|b| b.hash)
+    }
+
+    /// Timestamp of the newest block
+    pub fn last_block_ts(&self) -> Option<i64> {
+        self.blocks.back().map(|b| b.timestamp)
+    }
+
+    /// Number of blocks in the window
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Fee rates of every block in the window
+    pub fn fee_rates(&self) -> Vec<f64> {
+        self.blocks
+            .iter()
+            .flat_map(|b| b.fee_rates.iter().copied())
+            .collect()
+    }
+
+    /// Write the window in CBOR with the [`TRACKER_FORMAT_VERSION`] and the height and the hash
+    /// of the tip, to be restored with [`BlockTracker::load_from`]
+    #[cfg(feature = "std")]
+    pub fn save_to<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let saved = SavedTracker {
+            version: TRACKER_FORMAT_VERSION,
+            tip_height: self.tip_height(),
+            tip_hash: self.tip_hash(),
+            tracker: self,
+        };
+        serde_cbor::to_writer(writer, &saved).map_err(Error::Serialize)
+    }
+
+    /// Restore a window written by [`BlockTracker::save_to`], errors with
+    /// [`Error::UnsupportedTrackerVersion`] if written by a newer version. The chain may have
+    /// changed meanwhile: reconcile it with [`BlockTracker::reconcile`].
+    #[cfg(feature = "std")]
+    pub fn load_from<R: Read>(mut reader: R) -> Result<BlockTracker, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let saved: SavedVersion = serde_cbor::from_slice(&bytes).map_err(Error::Deserialize)?;
+        if saved.version > TRACKER_FORMAT_VERSION {
+            return Err(Error::UnsupportedTrackerVersion(saved.version));
+        }
+        let loaded: LoadedTracker = serde_cbor::from_slice(&bytes).map_err(Error::Deserialize)?;
+        Ok(loaded.tracker)
+    }
+
+    /// Estimate like [`FeeModel::estimate`] with the fee rates and the last block timestamp of
+    /// the window, or like [`FeeModel::estimate_partial`] while the tracker has fewer than
+    /// [`WINDOW`] blocks. Errors with [`Error::InsufficientData`] if the tracker is empty
+    pub fn estimate(
+        &self,
+        model: &FeeModel,
+        block_target: u16,
+        timestamp: Option<i64>,
+    ) -> Result<f32, Error> {
+        let last_block_ts = self.last_block_ts().ok_or(Error::InsufficientData)?;
+        let fee_rates = self.fee_rates();
+        if self.len() < WINDOW {
+            let blocks_covered = self.len() as u8;
+            model.estimate_partial(
+                block_target,
+                timestamp,
+                &fee_rates,
+                blocks_covered,
+                last_block_ts,
+            )
+        } else {
+            model.estimate(block_target, timestamp, &fee_rates, last_block_ts)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::SAMPLE_TS;
+    use crate::tracker::{BlockTracker, WINDOW};
+    use alloc::vec::Vec;
+
+    fn block_rates(i: usize) -> Vec<f64> {
+        (0..100).map(|j| (i * 100 + j) as f64).collect()
+    }
+
+    #[test]
+    fn test_window() {
+        let mut tracker = BlockTracker::new(1000);
+        assert!(tracker.is_empty());
+        assert_eq!(tracker.tip_height(), None);
+        for i in 0..15 {
+            tracker.push_block(block_rates(i), SAMPLE_TS + i as i64 * 600);
+        }
+        assert_eq!(tracker.len(), WINDOW);
+        assert_eq!(tracker.tip_height(), Some(1014));
+        assert_eq!(tracker.last_block_ts(), Some(SAMPLE_TS + 14 * 600));
+        let expected: Vec<f64> = (5..15).flat_map(block_rates).collect();
+        assert_eq!(tracker.fee_rates(), expected);
+    }
+
+    #[test]
+    fn test_reorg() {
+        let mut tracker = BlockTracker::new(0);
+        for i in 0..15 {
+            tracker.push_block(block_rates(i), i as i64);
+        }
+        assert_eq!(tracker.pop_block(), Some(block_rates(14)));
+        tracker.reset_to(11);
+        assert_eq!(tracker.tip_height(), Some(11));
+        assert_eq!(tracker.last_block_ts(), Some(11));
+        assert_eq!(tracker.len(), 7);
+
+        // the blocks of the new chain replace the reorged ones
+        tracker.push_block(vec![1.0], 100);
+        let mut expected: Vec<f64> = (5..12).flat_map(block_rates).collect();
+        expected.push(1.0);
+        assert_eq!(tracker.fee_rates(), expected);
+        assert_eq!(tracker.tip_height(), Some(12));
+
+        tracker.reset_to(0);
+        assert!(tracker.is_empty());
+        assert_eq!(tracker.pop_block(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_save_restore() {
+        use crate::{Error, TRACKER_FORMAT_VERSION};
+
+        let hash = |i: usize| [i as u8; 32];
+        let push = |tracker: &mut BlockTracker, i: usize| {
+            tracker.push_block_with_hash(block_rates(i), SAMPLE_TS + i as i64 * 600, hash(i))
+        };
+        let mut continuous = BlockTracker::new(1000);
+        let mut restarted = BlockTracker::new(1000);
+        for i in 0..7 {
+            push(&mut continuous, i);
+            push(&mut restarted, i);
+        }
+        let mut saved = Vec::new();
+        restarted.save_to(&mut saved).unwrap();
+        drop(restarted);
+
+        let mut restored = BlockTracker::load_from(&saved[..]).unwrap();
+        assert_eq!(restored.tip_height(), Some(1006));
+        assert_eq!(restored.tip_hash(), Some(hash(6)));
+        // the node has the same chain
+        assert_eq!(
+            restored.reconcile(1006, |h| Some(hash(h as usize - 1000))),
+            0
+        );
+        for i in 7..10 {
+            push(&mut continuous, i);
+            push(&mut restored, i);
+        }
+        assert_eq!(restored, continuous);
+        assert_eq!(restored.fee_rates(), continuous.fee_rates());
+        assert_eq!(restored.last_block_ts(), continuous.last_block_ts());
+
+        // restored on a node behind, or that reorged the last two blocks
+        let mut behind = BlockTracker::load_from(&saved[..]).unwrap();
+        assert_eq!(behind.reconcile(1004, |h| Some(hash(h as usize - 1000))), 2);
+        assert_eq!(behind.tip_height(), Some(1004));
+        let mut reorged = BlockTracker::load_from(&saved[..]).unwrap();
+        let reorged_hash = |h: u32| {
+            Some(if h > 1004 {
+                [0xff; 32]
+            } else {
+                hash(h as usize - 1000)
+            })
+        };
+        assert_eq!(reorged.reconcile(1008, reorged_hash), 2);
+        assert_eq!(reorged, behind);
+        // blocks without hash are kept
+        let mut unhashed = BlockTracker::new(0);
+        unhashed.push_block(vec![1.0], 0);
+        assert_eq!(unhashed.reconcile(0, |_| None), 0);
+        assert_eq!(unhashed.tip_hash(), None);
+
+        let err = BlockTracker::load_from(&b"not a tracker"[..]).unwrap_err();
+        assert!(matches!(err, Error::Deserialize(_)));
+        let newer = serde_cbor::to_vec(&serde_json::json!({
+            "version": TRACKER_FORMAT_VERSION + 1,
+            "layout": "changed",
+        }))
+        .unwrap();
+        let err = BlockTracker::load_from(&newer[..]).unwrap_err();
+        assert!(matches!(err, Error::UnsupportedTrackerVersion(2)));
+        assert_eq!(
+            err.to_string(),
+            "Unsupported block tracker format version 2"
+        );
+    }
+
+    #[cfg(feature = "embedded-models")]
+    #[test]
+    fn test_estimate() {
+        use crate::tests::sample_rates;
+
+        let model = crate::FeeModel::new().unwrap();
+        let ts = SAMPLE_TS;
+        let mut tracker = BlockTracker::new(0);
+        let err = tracker.estimate(&model, 6, Some(ts)).unwrap_err();
+        assert!(matches!(err, crate::Error::InsufficientData));
+
+        let rates = sample_rates();
+        for chunk in rates.chunks(100).take(2) {
+            tracker.push_block(chunk.to_vec(), ts - 300);
+        }
+        // fewer blocks than EstimateOptions::min_blocks_covered
+        let err = tracker.estimate(&model, 6, Some(ts)).unwrap_err();
+        assert!(matches!(err, crate::Error::InsufficientData));
+
+        for chunk in rates.chunks(100).skip(2).take(2) {
+            tracker.push_block(chunk.to_vec(), ts - 300);
+        }
+        let partial = model
+            .estimate_partial(6, Some(ts), &rates[..400], 4, ts - 300)
+            .unwrap();
+        assert_eq!(tracker.estimate(&model, 6, Some(ts)).unwrap(), partial);
+        let unscaled = model
+            .estimate(6, Some(ts), &rates[..400], ts - 300)
+            .unwrap();
+        assert_ne!(partial, unscaled);
+
+        for chunk in rates.chunks(100).skip(4) {
+            tracker.push_block(chunk.to_vec(), ts - 300);
+        }
+        let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+        assert_eq!(tracker.estimate(&model, 6, Some(ts)).unwrap(), expected);
+    }
+}
@@ -0,0 +1,522 @@
+//! Structural checks of a model, see [`ModelData::validate`], and of the models of a fee model
+//! among them, see [`crate::FeeModel::validate_consistency`]
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::Serialize;
+
+use crate::model_data::Kernel;
+use crate::model_input::bucket_index;
+use crate::{Error, FeeModelConfig, ModelData, MAX_BUCKETS};
+
+/// The greatest ratio between the scales normalizing a feature in two models of a fee model,
+/// see [`ConflictReason::Scale`]. The scales of the same feature in models trained on similar
+/// data are close, seconds and minutes differ by 60.
+pub const MAX_SCALE_RATIO: f32 = 20.0;
+
+/// The features of [`crate::ModelInput`] every model has, but `confirms_in` for the models with
+/// [`ModelData::output_targets`], besides the buckets
+const INPUT_FEATURES: [&str; 4] = ["confirms_in", "day_of_week", "hour", "delta_last"];
+
+/// A problem of a model found by [`ModelData::validate`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ValidationIssue {
+    /// The kernel or the bias of the layer, named like in the CBOR models, hasn't the
+    /// dimension of the previous layer or of the other one
+    DimensionMismatch {
+        layer: &'static str,
+        expected: usize,
+        got: usize,
+    },
+    /// A weight of the given layer, or `alpha`, is NaN or infinite
+    NonFiniteWeight(&'static str),
+    /// The field has no normalization data
+    MissingNormalization(String),
+    /// The offset or the scale normalizing the field are not finite or the scale is zero
+    InvalidNormalization(String),
+    /// The model declares a `bucket_config` but its bucket features are not `b0` to
+    /// `b{expected - 1}`
+    BucketCount { expected: usize, got: usize },
+    /// The `bucket_config` of the model declares another number of buckets than its bucket
+    /// features
+    DeclaredBucketCount { declared: usize, got: usize },
+    /// The model has more bucket features than [`MAX_BUCKETS`]
+    TooManyBuckets(usize),
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::DimensionMismatch {
+                layer,
+                expected,
+                got,
+            } => write!(
+                f,
+                "layer {} has wrong dimension: expected {}, found {}",
+                layer, expected, got
+            ),
+            ValidationIssue::NonFiniteWeight(layer) => {
+                write!(f, "layer {} has a non finite weight", layer)
+            }
+            ValidationIssue::MissingNormalization(field) => {
+                write!(f, "field {} has no normalization", field)
+            }
+            ValidationIssue::InvalidNormalization(field) => {
+                write!(f, "field {} has an invalid normalization", field)
+            }
+            ValidationIssue::BucketCount { expected, got } => write!(
+                f,
+                "bucket features are not contiguous: {} up to b{}",
+                got,
+                expected - 1
+            ),
+            ValidationIssue::DeclaredBucketCount { declared, got } => write!(
+                f,
+                "{} bucket features, the bucket configuration declares {}",
+                got, declared
+            ),
+            ValidationIssue::TooManyBuckets(got) => {
+                write!(f, "{} bucket features, at most {}", got, MAX_BUCKETS)
+            }
+        }
+    }
+}
+
+/// A feature two models of a fee model disagree on, see [`crate::FeeModel::validate_consistency`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelConflict {
+    /// the feature, `buckets` for the bucket features as a whole
+    pub feature: String,
+    /// the first model, which the others are compared to, and the conflicting one, `low`,
+    /// `high` or `long`
+    pub models: (&'static str, &'static str),
+    pub reason: ConflictReason,
+}
+
+/// Why two models disagree on a feature, see [`ModelConflict`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum ConflictReason {
+    /// the feature of [`crate::ModelInput`] is an input of only one of the models, not of the
+    /// given one
+    Missing(&'static str),
+    /// the models declare different [`crate::ModelInfo::units`] for the feature
+    Units { expected: String, got: String },
+    /// the scales normalizing the feature, like its standard deviation, differ by more than
+    /// [`MAX_SCALE_RATIO`]. `confirms_in` is not compared as the models estimate different
+    /// targets, nor the features a model doesn't normalize, with offset 0 and scale 1.
+    Scale { expected: f32, got: f32 },
+    /// the models declare buckets with other limits in [`crate::ModelInfo::bucket_config`]
+    BucketLimits {
+        expected: FeeModelConfig,
+        got: FeeModelConfig,
+    },
+    /// only one of the models counts the vsize of the transactions in the buckets, see
+    /// [`crate::ModelInfo::weighted_buckets`]
+    WeightedBuckets,
+}
+
+impl fmt::Display for ModelConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (first, other) = self.models;
+        write!(
+            f,
+            "{} of the {} and the {} model: ",
+            self.feature, first, other
+        )?;
+        match &self.reason {
+            ConflictReason::Missing(model) => write!(f, "not an input of the {} model", model),
+            ConflictReason::Units { expected, got } => write!(f, "in {} and in {}", expected, got),
+            ConflictReason::Scale { expected, got } => {
+                write!(f, "scaled by {} and by {}", expected, got)
+            }
+            ConflictReason::BucketLimits { expected, got } => write!(
+                f,
+                "{}% wide up to {} sat/vB and {}% wide up to {} sat/vB",
+                expected.bucket_increment_percent,
+                expected.bucket_limit,
+                got.bucket_increment_percent,
+                got.bucket_limit
+            ),
+            ConflictReason::WeightedBuckets => write!(f, "weighted by vsize in only one"),
+        }
+    }
+}
+
+/// The features `model` disagrees on with the `first` model, named like in [`ModelConflict`]
+pub(crate) fn conflicts(
+    first: (&'static str, &ModelData),
+    model: (&'static str, &ModelData),
+) -> Vec<ModelConflict> {
+    let ((first_name, first), (name, model)) = (first, model);
+    let mut conflicts = Vec::new();
+    let mut conflict = |feature: &str, reason| {
+        conflicts.push(ModelConflict {
+            feature: feature.to_string(),
+            models: (first_name, name),
+            reason,
+        })
+    };
+    let has = |model: &ModelData, feature: &str| {
+        (feature == "confirms_in" && !model.output_targets().is_empty())
+            || model.feature_names().iter().any(|f| f == feature)
+    };
+    for feature in INPUT_FEATURES.iter() {
+        match (has(first, feature), has(model, feature)) {
+            (true, false) => conflict(feature, ConflictReason::Missing(name)),
+            (false, true) => conflict(feature, ConflictReason::Missing(first_name)),
+            _ => (),
+        }
+    }
+
+    for (feature, expected) in first.info.units.iter() {
+        match model.info.units.get(feature) {
+            Some(got) if got != expected => {
+                let (expected, got) = (expected.clone(), got.clone());
+                conflict(feature, ConflictReason::Units { expected, got })
+            }
+            _ => (),
+        }
+    }
+
+    // the bucket features of a tier with its own number of buckets are other buckets
+    let same_buckets = first.bucket_count() == model.bucket_count();
+    for feature in first.feature_names() {
+        let comparable =
+            feature != "confirms_in" && (same_buckets || bucket_index(feature).is_none());
+        if !comparable || !model.feature_names().contains(feature) {
+            continue;
+        }
+        // not normalized, like in the handcrafted models
+        let normalized = |model: &ModelData| {
+            let offset_scale = model.norm.offset_scale(feature).ok();
+            offset_scale.filter(|offset_scale| *offset_scale != (0.0, 1.0))
+        };
+        if let (Some((_, expected)), Some((_, got))) = (normalized(first), normalized(model)) {
+            let (low, high) = (expected.abs().min(got.abs()), expected.abs().max(got.abs()));
+            if high > low * MAX_SCALE_RATIO {
+                conflict(feature, ConflictReason::Scale { expected, got });
+            }
+        }
+    }
+
+    if let (Some(expected), Some(got)) = (&first.info.bucket_config, &model.info.bucket_config) {
+        if !expected.same_limits(got) {
+            let (expected, got) = (expected.clone(), got.clone());
+            conflict("buckets", ConflictReason::BucketLimits { expected, got });
+        }
+    }
+    if first.info.weighted_buckets != model.info.weighted_buckets {
+        conflict("buckets", ConflictReason::WeightedBuckets);
+    }
+    conflicts
+}
+
+/// The names of the kernel and the bias of every layer in the CBOR models
+pub(crate) const LAYERS: [(&str, &str); 3] = [
+    ("dense/kernel:0", "dense/bias:0"),
+    ("dense_1/kernel:0", "dense_1/bias:0"),
+    ("dense_2/kernel:0", "dense_2/bias:0"),
+];
+
+impl ModelData {
+    /// Check the structure of the model, returning every issue found: the dimensions of the
+    /// kernels and the biases chained from the fields to the outputs, finite weights, the
+    /// normalization of every field, with a non zero scale, and, for the models declaring a
+    /// `bucket_config` in their metadata, bucket features from `b0` without gaps and as many as
+    /// its `bucket_count`, if any.
+    ///
+    /// The models are validated when loaded, failing with [`Error::InvalidModel`], the method is
+    /// public to lint models built or modified in other ways.
+    pub fn validate(&self) -> Result<(), Vec<ValidationIssue>> {
+        let mut issues = Vec::new();
+        let w = &self.weights;
+        let layers = [
+            (&w.l0_kernel, &w.l0_bias),
+            (&w.l1_kernel, &w.l1_bias),
+            (&w.l2_kernel, &w.l2_bias),
+        ];
+        let mut mismatch = |layer, expected, got| {
+            if expected != got {
+                issues.push(ValidationIssue::DimensionMismatch {
+                    layer,
+                    expected,
+                    got,
+                });
+            }
+        };
+        let mut inputs = self.fields.len();
+        for ((kernel, bias), (kernel_name, bias_name)) in layers.iter().zip(LAYERS.iter()) {
+            mismatch(kernel_name, inputs, kernel.height());
+            mismatch(bias_name, kernel.width(), bias.width());
+            mismatch(bias_name, 1, bias.height());
+            inputs = kernel.width();
+        }
+        let outputs = w.l2_bias.width();
+        let expected = self.quantiles().len().max(self.output_targets().len());
+        mismatch("dense_2/bias:0", expected.max(1), outputs);
+
+        if !self.alpha.is_finite() {
+            issues.push(ValidationIssue::NonFiniteWeight("alpha"));
+        }
+        for ((kernel, bias), (kernel_name, bias_name)) in layers.iter().zip(LAYERS.iter()) {
+            if !is_finite(kernel) {
+                issues.push(ValidationIssue::NonFiniteWeight(kernel_name));
+            }
+            if bias.as_slice().iter().any(|v| !v.is_finite()) {
+                issues.push(ValidationIssue::NonFiniteWeight(bias_name));
+            }
+        }
+
+        for field in self.fields.iter() {
+            match self.norm.offset_scale(field) {
+                Err(Error::MissingMeanData(_)) | Err(Error::MissingStdData(_)) => {
+                    issues.push(ValidationIssue::MissingNormalization(field.clone()))
+                }
+                Err(_) => issues.push(ValidationIssue::InvalidNormalization(field.clone())),
+                Ok(_) => (),
+            }
+        }
+
+        let buckets = self.fields.iter().filter_map(|f| bucket_index(f));
+        let expected = buckets.max().map_or(0, |max| max + 1);
+        let got = self.bucket_count();
+        if got > MAX_BUCKETS {
+            issues.push(ValidationIssue::TooManyBuckets(got));
+        } else if self.info.bucket_config.is_some() && expected != got {
+            issues.push(ValidationIssue::BucketCount { expected, got });
+        }
+        let declared = self
+            .info
+            .bucket_config
+            .as_ref()
+            .and_then(|c| c.bucket_count);
+        if let Some(declared) = declared.filter(|d| *d != got) {
+            issues.push(ValidationIssue::DeclaredBucketCount { declared, got });
+        }
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+}
+
+fn is_finite(kernel: &Kernel) -> bool {
+    (0..kernel.height()).all(|i| (0..kernel.width()).all(|j| kernel.get(i, j).is_finite()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use crate::matrix::Matrix;
+    use crate::model_data::tests::{
+        get_test_model, get_test_model_with_hour_renamed, get_test_model_with_scale_divided,
+    };
+    use crate::tests::{get_fee_model, get_low_model};
+    use crate::{
+        ConflictReason, Error, FeeModel, FeeModelConfig, ModelConflict, ModelData, ValidationIssue,
+    };
+
+    fn load(name: &str) -> Result<ModelData, Error> {
+        ModelData::from_reader(File::open(format!("models/fixtures/{}", name)).unwrap())
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(get_test_model().validate(), Ok(()));
+        assert_eq!(get_low_model().validate(), Ok(()));
+        assert_eq!(get_low_model().quantize_int8().validate(), Ok(()));
+        assert_eq!(load("quantiles.cbor").unwrap().validate(), Ok(()));
+
+        let mut model = get_low_model();
+        model.weights.l1_bias = Matrix::zeros(3, 1);
+        model.alpha = f32::NAN;
+        model.weights.l2_bias = Matrix::from_array(vec![f32::INFINITY].into_boxed_slice());
+        model.fields.push("unknown".into());
+        let issues = model.validate().unwrap_err();
+        assert_eq!(
+            issues,
+            vec![
+                ValidationIssue::DimensionMismatch {
+                    layer: "dense/kernel:0",
+                    expected: 21,
+                    got: 20
+                },
+                ValidationIssue::DimensionMismatch {
+                    layer: "dense_1/bias:0",
+                    expected: 64,
+                    got: 3
+                },
+                ValidationIssue::NonFiniteWeight("alpha"),
+                ValidationIssue::NonFiniteWeight("dense_2/bias:0"),
+                ValidationIssue::MissingNormalization("unknown".into()),
+            ]
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "layer dense_1/bias:0 has wrong dimension: expected 64, found 3"
+        );
+    }
+
+    #[test]
+    fn test_invalid_fixtures() {
+        // the models failing the checks while loaded
+        let err = load("layer_mismatch.cbor").unwrap_err();
+        assert!(matches!(err, Error::DimensionMismatch { .. }));
+        let err = load("nan_weight.cbor").unwrap_err();
+        assert!(matches!(err, Error::NonFiniteWeight(_)));
+        let err = load("zero_std.cbor").unwrap_err();
+        assert!(matches!(err, Error::InvalidNormalization(_)));
+
+        let err = load("bucket_gap.cbor").unwrap_err();
+        let issues = match err {
+            Error::InvalidModel(issues) => issues,
+            e => panic!("unexpected {:?}", e),
+        };
+        assert_eq!(
+            issues,
+            vec![ValidationIssue::BucketCount {
+                expected: 16,
+                got: 15
+            }]
+        );
+        let err = load("bucket_gap.cbor").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid model: bucket features are not contiguous: 15 up to b15"
+        );
+
+        // without buckets configuration the features are only named from the buckets
+        let mut model = get_low_model();
+        let index = model.fields.iter().position(|f| f == "b3").unwrap();
+        model.fields.remove(index);
+        model.info.bucket_config = None;
+        let errors = |model: &ModelData| model.validate().unwrap_err();
+        assert!(!errors(&model).contains(&ValidationIssue::BucketCount {
+            expected: 16,
+            got: 15
+        }));
+        model.info.bucket_config = Some(FeeModelConfig::default());
+        assert!(errors(&model).contains(&ValidationIssue::BucketCount {
+            expected: 16,
+            got: 15
+        }));
+
+        let mut model = load("buckets25_low.cbor").unwrap();
+        assert_eq!(model.bucket_count(), 31);
+        model.info.bucket_config.as_mut().unwrap().bucket_count = Some(32);
+        let issues = model.validate().unwrap_err();
+        assert_eq!(
+            issues,
+            vec![ValidationIssue::DeclaredBucketCount {
+                declared: 32,
+                got: 31
+            }]
+        );
+        assert_eq!(
+            issues[0].to_string(),
+            "31 bucket features, the bucket configuration declares 32"
+        );
+    }
+
+    #[test]
+    fn test_validate_consistency() {
+        // the metadata the models of the tests lack
+        let declared = |mut model: ModelData| {
+            model.info.bucket_config = Some(FeeModelConfig::default());
+            let seconds = ("delta_last".to_string(), "seconds".to_string());
+            model.info.units = vec![seconds].into_iter().collect();
+            model
+        };
+        let base = || declared(get_test_model());
+        let pair = |high: ModelData| FeeModel::try_from_models(base(), high);
+        let conflicts = |result: Result<FeeModel, Error>| match result {
+            Err(Error::ModelMismatch(conflicts)) => conflicts,
+            other => panic!("{:?}", other.map(|_| ())),
+        };
+        let conflict = |feature: &str, reason| ModelConflict {
+            feature: feature.to_string(),
+            models: ("low", "high"),
+            reason,
+        };
+        assert!(pair(base()).is_ok());
+        assert!(get_fee_model().validate_consistency().is_ok());
+
+        let mut minutes = base();
+        let unit = minutes.info.units.get_mut("delta_last").unwrap();
+        *unit = "minutes".to_string();
+        let units = conflicts(pair(minutes.clone()));
+        let reason = ConflictReason::Units {
+            expected: "seconds".to_string(),
+            got: "minutes".to_string(),
+        };
+        assert_eq!(units, vec![conflict("delta_last", reason)]);
+        assert_eq!(
+            Error::ModelMismatch(units).to_string(),
+            "Inconsistent models: delta_last of the low and the high model: in seconds and in minutes"
+        );
+
+        // trained with delta_last in minutes, without declaring it
+        let scaled = declared(get_test_model_with_scale_divided("delta_last", 60.0));
+        let (_, expected) = base().norm.offset_scale("delta_last").unwrap();
+        let (_, got) = scaled.norm.offset_scale("delta_last").unwrap();
+        assert_eq!(
+            conflicts(pair(scaled.clone())),
+            vec![conflict(
+                "delta_last",
+                ConflictReason::Scale { expected, got }
+            )]
+        );
+        let close = declared(get_test_model_with_scale_divided("delta_last", 10.0));
+        assert!(pair(close).is_ok());
+
+        // the same number of buckets up to another limit
+        let mut buckets = base();
+        let other = FeeModelConfig {
+            bucket_limit: 1000.0,
+            bucket_count: Some(16),
+            ..Default::default()
+        };
+        buckets.info.bucket_config = Some(other.clone());
+        let reason = ConflictReason::BucketLimits {
+            expected: FeeModelConfig::default(),
+            got: other,
+        };
+        assert_eq!(
+            conflicts(pair(buckets.clone())),
+            vec![conflict("buckets", reason)]
+        );
+        // checked when loaded as a mismatch of the bucket configurations
+        let (low, high) = (base().to_vec().unwrap(), buckets.to_vec().unwrap());
+        let err = FeeModel::from_bytes(&low, &high).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::BucketConfigMismatch { model: "high", .. }
+        ));
+
+        let mut weighted = base();
+        weighted.info.weighted_buckets = true;
+        assert_eq!(
+            conflicts(pair(weighted)),
+            vec![conflict("buckets", ConflictReason::WeightedBuckets)]
+        );
+
+        let renamed = get_test_model_with_hour_renamed("mempool_vsize");
+        let missing = conflicts(FeeModel::try_from_models(get_test_model(), renamed));
+        assert_eq!(
+            missing,
+            vec![conflict("hour", ConflictReason::Missing("high"))]
+        );
+
+        // every conflict of every model, compared to the first
+        let tiers = vec![(2, base()), (47, minutes), (1008, scaled)];
+        let conflicts = conflicts(FeeModel::from_tiers(tiers));
+        let models: Vec<_> = conflicts.iter().map(|c| c.models).collect();
+        assert_eq!(models, vec![("low", "high"), ("low", "long")]);
+    }
+}
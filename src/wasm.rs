@@ -0,0 +1,81 @@
+//! Bindings for javascript, timestamps are unix seconds as javascript numbers
+
+use alloc::string::ToString;
+use wasm_bindgen::prelude::*;
+
+use crate::{Error, FeeModel};
+
+/// A [`FeeModel`] usable from javascript
+#[wasm_bindgen(js_name = FeeModel)]
+#[derive(Debug, Clone)]
+pub struct WasmFeeModel {
+    inner: FeeModel,
+}
+
+fn to_js(e: Error) -> JsValue {
+    JsValue::from_str(&e.to_string())
+}
+
+#[wasm_bindgen(js_class = FeeModel)]
+impl WasmFeeModel {
+    /// Create the fee model using the models embedded in the library
+    #[cfg(feature = "embedded-models")]
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Result<WasmFeeModel, JsValue> {
+        let inner = FeeModel::new().map_err(to_js)?;
+        Ok(WasmFeeModel { inner })
+    }
+
+    /// Create the fee model from the CBOR encoded models in two `Uint8Array`
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(low: &[u8], high: &[u8]) -> Result<WasmFeeModel, JsValue> {
+        let inner = FeeModel::from_bytes(low, high).map_err(to_js)?;
+        Ok(WasmFeeModel { inner })
+    }
+
+    /// Like [`FeeModel::estimate`] with `fee_rates` in a `Float64Array`, when `timestamp` is
+    /// `undefined` the current time is taken from `Date.now()`
+    pub fn estimate(
+        &self,
+        block_target: u16,
+        timestamp: Option<f64>,
+        fee_rates: &[f64],
+        last_block_ts: f64,
+    ) -> Result<f32, JsValue> {
+        let timestamp = timestamp.unwrap_or_else(|| js_sys::Date::now() / 1000.0);
+        self.inner
+            .estimate(
+                block_target,
+                Some(timestamp as i64),
+                fee_rates,
+                last_block_ts as i64,
+            )
+            .map_err(to_js)
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use crate::tests::{sample_rates, SAMPLE_TS};
+    use crate::wasm::WasmFeeModel;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn test_estimate() {
+        let low = include_bytes!("../models/20211027-180849/model.cbor");
+        let high = include_bytes!("../models/20211027-180925/model.cbor");
+        let model = WasmFeeModel::from_bytes(low, high).unwrap();
+        let rates = sample_rates();
+        let ts = SAMPLE_TS as f64;
+        let estimate = model.estimate(6, Some(ts), &rates, ts - 300.0).unwrap();
+        assert!(estimate >= 1.0);
+
+        let now = js_sys::Date::now() / 1000.0;
+        assert!(model.estimate(6, None, &rates, now - 300.0).is_ok());
+        assert!(model.estimate(6, Some(ts), &[], ts - 300.0).is_err());
+
+        assert!(WasmFeeModel::from_bytes(&low[..100], high).is_err());
+    }
+}
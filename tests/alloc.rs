@@ -0,0 +1,127 @@
+//! The estimates don't allocate once the model is loaded, counted by a global allocator: this is
+//! the only test of the binary, so no other thread allocates while counting. The products of the
+//! `ndarray` feature allocate. The peak memory of loading a model is measured too.
+#![cfg(not(feature = "ndarray"))]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bitcoin_fee_model::{BlendRange, EstimateOptions, FeeModel, ModelData, PredictScratch};
+
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+/// bytes allocated and not freed, and their maximum
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn grow(bytes: usize) {
+    let live = LIVE.fetch_add(bytes, Ordering::SeqCst) + bytes;
+    PEAK.fetch_max(live, Ordering::SeqCst);
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        grow(layout.size());
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        // the old block is freed once the new one is allocated
+        grow(new_size);
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// The allocations done by `f`
+fn allocations<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let result = f();
+    (result, ALLOCATIONS.load(Ordering::SeqCst) - before)
+}
+
+/// The peak of the bytes allocated by `f` and not freed before, and the bytes still allocated
+fn peak_memory<T>(f: impl FnOnce() -> T) -> (T, usize, usize) {
+    let before = LIVE.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let result = f();
+    let peak = PEAK.load(Ordering::SeqCst) - before;
+    (result, peak, LIVE.load(Ordering::SeqCst) - before)
+}
+
+#[test]
+fn test_estimate_allocation_free() {
+    let ts = 1613708045i64;
+    let rates: Vec<f64> = (0..1000).map(|i| 1.0 + (i % 300) as f64).collect();
+    let model = FeeModel::new().unwrap();
+    let buckets = model.fee_buckets().get(&rates);
+    // warm up, like the detection of the SIMD instructions
+    let expected = model.estimate(6, Some(ts), &rates, ts - 300).unwrap();
+
+    for target in [1u16, 2, 6, 144, 1008] {
+        let (estimate, count) = allocations(|| model.estimate(target, Some(ts), &rates, ts - 300));
+        assert!(estimate.is_ok());
+        assert_eq!(count, 0, "estimate of target {}", target);
+        let (estimate, count) =
+            allocations(|| model.estimate_with_buckets(target, Some(ts), &buckets, ts - 300));
+        assert!(estimate.is_ok());
+        assert_eq!(count, 0, "estimate_with_buckets of target {}", target);
+    }
+    let (estimate, _) = allocations(|| model.estimate(6, Some(ts), &rates, ts - 300));
+    assert_eq!(estimate.unwrap(), expected);
+
+    let options = EstimateOptions {
+        blend: Some(BlendRange { from: 2, to: 6 }),
+        ..Default::default()
+    };
+    let blended = model.with_options(options);
+    let (estimate, count) = allocations(|| blended.estimate(4, Some(ts), &rates, ts - 300));
+    assert!(estimate.is_ok());
+    assert_eq!(count, 0, "blended estimate");
+
+    let load = |name| {
+        let path = format!("models/{}/model.cbor", name);
+        ModelData::from_reader(std::fs::File::open(path).unwrap()).unwrap()
+    };
+    let high = load("20211027-180925");
+    let ensemble = FeeModel::ensemble(
+        vec![load("20211027-180849")],
+        vec![high.clone(), high.clone(), high],
+    )
+    .unwrap();
+    let (estimate, count) = allocations(|| ensemble.estimate(6, Some(ts), &rates, ts - 300));
+    assert_eq!(estimate.unwrap(), expected);
+    assert_eq!(count, 0, "ensemble estimate");
+
+    let low = load("20211027-180849");
+    let input: Vec<f32> = (0..low.fields.len()).map(|i| i as f32).collect();
+    let mut scratch = PredictScratch::for_model(&low);
+    let (prediction, count) = allocations(|| low.norm_predict_with(&input, &mut scratch));
+    assert_eq!(prediction.unwrap(), low.norm_predict_slice(&input).unwrap());
+    assert_eq!(count, 0, "prediction with a scratch");
+
+    // the model is parsed while read, without the whole file nor nested rows in memory
+    let path = "models/20210221-220141/model.cbor";
+    let file_len = std::fs::metadata(path).unwrap().len() as usize;
+    let (model, peak, kept) =
+        peak_memory(|| ModelData::from_reader(std::fs::File::open(path).unwrap()).unwrap());
+    assert!(
+        peak < kept + file_len / 2,
+        "peak {} for a model of {} bytes from {}",
+        peak,
+        kept,
+        file_len
+    );
+    drop(model);
+}
@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+
+use assert_cmd::Command;
+use bitcoin_fee_model::FeeModel;
+
+const TS: i64 = 1613708045;
+
+fn fee_rates() -> Vec<f64> {
+    (0..1000).map(|i| 1.0 + (i % 300) as f64).collect()
+}
+
+/// Write `content` in a file of the temporary directory named after the test
+fn rates_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("bitcoin-fee-model-{}.txt", name));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn estimate(args: &[&str]) -> Command {
+    let mut cmd = Command::cargo_bin("bitcoin-fee-model").unwrap();
+    let (timestamp, last_block_ts) = (TS.to_string(), (TS - 300).to_string());
+    cmd.args(["estimate", "--timestamp", &timestamp])
+        .args(["--last-block-ts", &last_block_ts])
+        .args(args);
+    cmd
+}
+
+#[test]
+fn test_estimate() {
+    let rates: Vec<String> = fee_rates().iter().map(|r| r.to_string()).collect();
+    let path = rates_file("estimate", &rates.join("\n"));
+    let expected = FeeModel::new()
+        .unwrap()
+        .estimate(6, Some(TS), &fee_rates(), TS - 300)
+        .unwrap();
+
+    let path = path.to_str().unwrap();
+    let output = estimate(&["--target", "6", "--fee-rates-file", path])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout, format!("{}\n", expected));
+
+    let low = "models/20211027-180849/model.cbor";
+    let high = "models/20211027-180925/model.cbor";
+    let args = ["--target", "6", "--fee-rates-file", "-", "--json"];
+    let output = estimate(&args)
+        .args(["--low", low, "--high", high])
+        .write_stdin(rates.join("\n"))
+        .assert()
+        .success();
+    let json: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+    assert_eq!(json["rate"].as_f64().unwrap() as f32, expected);
+    assert_eq!(json["model"], "high");
+    assert_eq!(json["block_target"], 6);
+    assert_eq!(json["inputs"]["delta_last"], 300.0);
+}
+
+#[test]
+fn test_malformed_input() {
+    let path = rates_file("malformed", "1.5\n2.0\nabc\n");
+    let path = path.to_str().unwrap();
+    let output = estimate(&["--target", "6", "--fee-rates-file", path])
+        .assert()
+        .code(1);
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert_eq!(stderr, "error: invalid fee rate at line 3: abc\n");
+
+    let output = estimate(&["--target", "6", "--fee-rates-file", "-"])
+        .write_stdin("")
+        .assert()
+        .code(1);
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert_eq!(stderr, "error: No fee rates to estimate from\n");
+
+    let output = estimate(&["--target", "six"]).assert().code(2);
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert!(stderr.starts_with("error: invalid value of --target: six\n"));
+}
+
+#[test]
+fn test_convert() {
+    let dir = std::env::temp_dir();
+    let low = dir.join("bitcoin-fee-model-convert-low.bfpc");
+    let high = dir.join("bitcoin-fee-model-convert-high.bfpc");
+    for (cbor, output) in [("180849", &low), ("180925", &high)] {
+        let cbor = format!("models/20211027-{}/model.cbor", cbor);
+        let output = output.to_str().unwrap();
+        Command::cargo_bin("bitcoin-fee-model")
+            .unwrap()
+            .args(["convert", &cbor, output])
+            .assert()
+            .success()
+            .stdout(format!("{} written\n", output));
+    }
+
+    let rates: Vec<String> = fee_rates().iter().map(|r| r.to_string()).collect();
+    let expected = FeeModel::new()
+        .unwrap()
+        .estimate(6, Some(TS), &fee_rates(), TS - 300)
+        .unwrap();
+    let args = ["--target", "6", "--fee-rates-file", "-"];
+    let output = estimate(&args)
+        .args([
+            "--low",
+            low.to_str().unwrap(),
+            "--high",
+            high.to_str().unwrap(),
+        ])
+        .write_stdin(rates.join("\n"))
+        .assert()
+        .success();
+    let stdout = String::from_utf8(output.get_output().stdout.clone()).unwrap();
+    assert_eq!(stdout, format!("{}\n", expected));
+
+    let output = Command::cargo_bin("bitcoin-fee-model")
+        .unwrap()
+        .args(["convert", "models/missing.cbor"])
+        .assert()
+        .code(2);
+    let stderr = String::from_utf8(output.get_output().stderr.clone()).unwrap();
+    assert!(stderr.starts_with("error: missing output file\n"));
+}
@@ -0,0 +1,116 @@
+use std::sync::Arc;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use bitcoin_fee_model::server::{fee_model_router, AppState};
+use bitcoin_fee_model::{EstimateOptions, FeeModel, FeeWindow};
+use chrono::{TimeZone, Utc};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+const TS: i64 = 1613708045;
+
+fn fee_rates() -> Vec<f64> {
+    (0..1000).map(|i| 1.0 + (i % 300) as f64).collect()
+}
+
+fn model() -> FeeModel {
+    FeeModel::new()
+        .unwrap()
+        .with_clock(|| Utc.timestamp_opt(TS, 0).unwrap())
+}
+
+async fn call(router: &Router, request: Request<Body>) -> (StatusCode, Value) {
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&body).unwrap()
+    };
+    (status, body)
+}
+
+fn get(uri: &str) -> Request<Body> {
+    Request::get(uri).body(Body::empty()).unwrap()
+}
+
+fn post_window(body: &Value) -> Request<Body> {
+    Request::post("/window")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_estimate() {
+    let state = Arc::new(AppState::new(model()));
+    let router = fee_model_router(state.clone());
+    let window = json!({"fee_rates": fee_rates(), "last_block_ts": TS - 300});
+    let (status, body) = call(&router, post_window(&window)).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(body, Value::Null);
+    assert_eq!(state.window().unwrap().last_block_ts, TS - 300);
+
+    let (status, body) = call(&router, get("/estimate?target=6")).await;
+    assert_eq!(status, StatusCode::OK);
+    let expected = model()
+        .estimate(6, Some(TS), &fee_rates(), TS - 300)
+        .unwrap();
+    assert_eq!(body["rate"].as_f64().unwrap() as f32, expected);
+    assert_eq!(body["target"], 6);
+    assert_eq!(body["staleness"], Value::Null);
+    assert!(body.as_object().unwrap().contains_key("model_version"));
+
+    // a window older than `stale_after` is reported, or rejected
+    state.set_window(FeeWindow {
+        fee_rates: fee_rates(),
+        last_block_ts: TS - 3 * 3600,
+    });
+    let (status, body) = call(&router, get("/estimate?target=1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["staleness"], 3 * 3600);
+    let options = EstimateOptions {
+        reject_stale: true,
+        ..Default::default()
+    };
+    let rejecting = Arc::new(AppState::new(model().with_options(options)));
+    rejecting.set_window(state.window().unwrap());
+    let router = fee_model_router(rejecting);
+    let (status, body) = call(&router, get("/estimate?target=1")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(body["error"].is_string());
+}
+
+#[tokio::test]
+async fn test_invalid_requests() {
+    let state = Arc::new(AppState::new(model()));
+    state.set_window(FeeWindow {
+        fee_rates: fee_rates(),
+        last_block_ts: TS - 300,
+    });
+    let router = fee_model_router(state.clone());
+    for uri in ["/estimate?target=0", "/estimate?target=x", "/estimate"].iter() {
+        let (status, body) = call(&router, get(uri)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", uri);
+        assert!(body["error"].is_string(), "{}", uri);
+    }
+    let (_, body) = call(&router, get("/estimate?target=0")).await;
+    assert_eq!(body["error"], "Invalid block target 0");
+
+    let (status, body) = call(&router, post_window(&json!({"fee_rates": "x"}))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["error"].is_string());
+    // the window is kept
+    assert_eq!(state.window().unwrap().fee_rates, fee_rates());
+}
+
+#[tokio::test]
+async fn test_no_window() {
+    let router = fee_model_router(Arc::new(AppState::new(model())));
+    let (status, body) = call(&router, get("/estimate?target=6")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"], "no fee rates window pushed yet");
+}